@@ -0,0 +1,44 @@
+//! Regression benchmarks for the frame codecs.
+
+use bytes::BytesMut;
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use serial2_tokio::codec::{PppCodec, PppFrame, UbxCodec, UbxFrame};
+use tokio_util::codec::{Decoder, Encoder};
+
+/// Encode a frame with a codec.
+fn encode<T, C: Encoder<T>>(codec: &mut C, frame: T) -> BytesMut
+where
+	C::Error: std::fmt::Debug,
+{
+	let mut encoded = BytesMut::new();
+	codec.encode(frame, &mut encoded).unwrap();
+	encoded
+}
+
+fn ppp(c: &mut Criterion) {
+	// Include bytes that need escaping with the default ACCM.
+	let information: Vec<u8> = (0..1500).map(|i| i as u8).collect();
+	let frame = PppFrame::new(PppFrame::IPV4, &information[..]);
+	let encoded = encode(&mut PppCodec::new(), frame.clone());
+
+	let mut group = c.benchmark_group("ppp");
+	group.throughput(Throughput::Bytes(information.len() as u64));
+	group.bench_function("encode", |b| b.iter(|| encode(&mut PppCodec::new(), frame.clone())));
+	group.bench_function("decode", |b| b.iter(|| PppCodec::new().decode(&mut encoded.clone()).unwrap().unwrap()));
+	group.finish();
+}
+
+fn ubx(c: &mut Criterion) {
+	let payload: Vec<u8> = (0..100).map(|i| i as u8).collect();
+	let frame = UbxFrame::new(0x01, 0x07, &payload[..]);
+	let encoded = encode(&mut UbxCodec::new(), frame.clone());
+
+	let mut group = c.benchmark_group("ubx");
+	group.throughput(Throughput::Bytes(payload.len() as u64));
+	group.bench_function("encode", |b| b.iter(|| encode(&mut UbxCodec::new(), frame.clone())));
+	group.bench_function("decode", |b| b.iter(|| UbxCodec::new().decode(&mut encoded.clone()).unwrap().unwrap()));
+	group.finish();
+}
+
+criterion_group!(benches, ppp, ubx);
+criterion_main!(benches);
@@ -0,0 +1,32 @@
+//! Regression benchmarks for reading and writing through a pseudo-terminal pair.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use serial2_tokio::SerialPort;
+
+/// Write a message to one end of the pair and read it back from the other end.
+async fn round_trip(tx: &SerialPort, rx: &SerialPort, message: &[u8]) {
+	let mut buffer = vec![0; message.len()];
+	tx.write_all(message).await.unwrap();
+	let mut read = 0;
+	while read < message.len() {
+		read += rx.read(&mut buffer[read..]).await.unwrap();
+	}
+}
+
+fn pty(c: &mut Criterion) {
+	let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+	let (tx, rx) = runtime.block_on(async { SerialPort::pair() }).unwrap();
+
+	let mut group = c.benchmark_group("pty_round_trip");
+	for size in [1, 64, 1024] {
+		let message: Vec<u8> = (0..size).map(|i| b'a' + (i % 26) as u8).collect();
+		group.throughput(Throughput::Bytes(size as u64));
+		group.bench_with_input(BenchmarkId::from_parameter(size), &message, |b, message| {
+			b.to_async(&runtime).iter(|| round_trip(&tx, &rx, message));
+		});
+	}
+	group.finish();
+}
+
+criterion_group!(benches, pty);
+criterion_main!(benches);
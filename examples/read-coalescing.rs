@@ -0,0 +1,61 @@
+//! Benchmark the effect of read coalescing on the number of reads.
+//!
+//! A separate thread writes a high-rate stream of single bytes to one end of a pseudo-terminal pair,
+//! similar to a fast sensor streaming telemetry.
+//! The other end is read with and without read coalescing.
+
+use std::time::{Duration, Instant};
+
+use serial2_tokio::{ReadCoalescing, SerialPort};
+
+const TOTAL_BYTES: usize = 20_000;
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> std::io::Result<()> {
+	println!("{:<24} {:>8} {:>14} {:>10}", "mode", "reads", "bytes/read", "time");
+	run("no coalescing", None).await?;
+	run("coalescing 64 B / 1 ms", Some(ReadCoalescing::new(64, Duration::from_millis(1)))).await?;
+	run("coalescing 512 B / 5 ms", Some(ReadCoalescing::new(512, Duration::from_millis(5)))).await?;
+	Ok(())
+}
+
+async fn run(name: &str, coalescing: Option<ReadCoalescing>) -> std::io::Result<()> {
+	let (device, host) = SerialPort::pair()?;
+	host.set_read_coalescing(coalescing)?;
+
+	let writer = std::thread::spawn(move || {
+		let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+		runtime.block_on(async {
+			for i in 0..TOTAL_BYTES {
+				device.write_all(&[i as u8]).await?;
+				// Busy wait to simulate a steady stream without timer granularity.
+				let start = Instant::now();
+				while start.elapsed() < Duration::from_micros(10) {
+					std::hint::spin_loop();
+				}
+			}
+			// Keep the device side open until everything has been read.
+			tokio::time::sleep(Duration::from_millis(500)).await;
+			Ok::<_, std::io::Error>(())
+		})
+	});
+
+	let start = Instant::now();
+	let mut buffer = [0; 256];
+	let mut received = 0;
+	while received < TOTAL_BYTES {
+		received += host.read(&mut buffer).await?;
+	}
+	let elapsed = start.elapsed();
+	let stats = host.stats();
+	println!(
+		"{:<24} {:>8} {:>14.1} {:>10.0?}",
+		name,
+		stats.reads,
+		stats.bytes_read as f64 / stats.reads as f64,
+		elapsed,
+	);
+
+	writer.join().unwrap()?;
+	Ok(())
+}
@@ -0,0 +1,80 @@
+use crate::SerialPort;
+
+impl SerialPort {
+	/// Wrap a file descriptor of a serial port that was opened by another component.
+	///
+	/// On Android, regular apps are not allowed to open serial devices directly:
+	/// the SELinux policy denies access to the device nodes, even if the file permissions would allow it.
+	/// Apps that are granted access to a serial port by a system service or a privileged helper process
+	/// can receive the file descriptor instead.
+	///
+	/// This is meant for apps that receive the file descriptor of a serial port from Java,
+	/// for example from the [`ParcelFileDescriptor`] returned by a system service,
+	/// using `ParcelFileDescriptor.detachFd()` to transfer ownership to native code.
+	///
+	/// This is the same as [`Self::from_raw_fd()`].
+	///
+	/// Note that the file descriptor of a `UsbDeviceConnection` from the USB host API refers to the raw USB device,
+	/// not to a terminal device.
+	/// Such a file descriptor can not be used as a serial port without a user space driver for the USB serial adapter,
+	/// and this function returns an error of kind [`std::io::ErrorKind::InvalidInput`] for it.
+	///
+	/// # Safety
+	/// The file descriptor must be open and it must not be owned by anything else.
+	/// Ownership of the file descriptor is transferred to this function:
+	/// it is closed when the returned serial port is dropped, or immediately if this function returns an error.
+	///
+	/// [`ParcelFileDescriptor`]: https://developer.android.com/reference/android/os/ParcelFileDescriptor
+	#[cfg(any(all(unix, feature = "doc"), target_os = "android"))]
+	#[cfg_attr(feature = "doc-cfg", doc(cfg(target_os = "android")))]
+	pub unsafe fn from_raw_fd_async(fd: std::os::fd::RawFd) -> std::io::Result<Self> {
+		Self::from_raw_fd(fd)
+	}
+}
+
+/// Find serial ports by scanning `/dev`.
+///
+/// Used when the SELinux policy denies access to `/sys/class/tty`, which is needed for the normal enumeration.
+/// Placeholder `/dev/ttyS*` devices can not be told apart from real serial ports without sysfs, so they are all reported.
+///
+/// Returns the original error if `/dev` can not be read either.
+#[cfg(target_os = "android")]
+pub(crate) fn scan_dev(error: std::io::Error) -> std::io::Result<Vec<std::path::PathBuf>> {
+	use std::os::unix::fs::FileTypeExt;
+
+	const PREFIXES: &[&str] = &["ttyS", "ttyHS", "ttyMSM", "ttyMT", "ttyAMA", "ttyUSB", "ttyACM"];
+
+	let Ok(entries) = std::fs::read_dir("/dev") else {
+		return Err(error);
+	};
+	let mut ports: Vec<_> = entries
+		.filter_map(|entry| entry.ok())
+		.filter(|entry| {
+			let name = entry.file_name();
+			let name = name.to_string_lossy();
+			PREFIXES.iter().any(|prefix| name.strip_prefix(prefix).is_some_and(|rest| rest.starts_with(|c: char| c.is_ascii_alphanumeric())))
+		})
+		.filter(|entry| entry.file_type().is_ok_and(|file_type| file_type.is_char_device()))
+		.map(|entry| entry.path())
+		.collect();
+	ports.sort();
+	Ok(ports)
+}
+
+/// Check if the SELinux policy is enforced.
+#[cfg(target_os = "android")]
+pub(crate) fn selinux_enforcing() -> bool {
+	std::fs::read_to_string("/sys/fs/selinux/enforce").is_ok_and(|value| value.trim() == "1")
+}
+
+/// Check if a path refers to an on-board UART.
+///
+/// These are `/dev/ttyS*`, and `/dev/ttyHS*`, `/dev/ttyHSL*` and `/dev/ttyMSM*` on Qualcomm devices.
+/// On Android, they are usually reserved for the kernel console, Bluetooth or a modem.
+#[cfg(target_os = "android")]
+pub(crate) fn is_onboard_uart(path: &std::path::Path) -> bool {
+	let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+		return false;
+	};
+	["ttyS", "ttyHS", "ttyMSM"].iter().any(|prefix| name.starts_with(prefix))
+}
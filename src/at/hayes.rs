@@ -0,0 +1,145 @@
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use super::{AtPort, ErrorResponse, ERROR_RESULTS};
+use crate::SerialPort;
+
+/// The timeout for establishing a connection after dialing or answering.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How often the CD line is checked while reading data with a carrier watchdog.
+const CARRIER_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+impl<T: AsyncRead + AsyncWrite + Unpin> AtPort<T> {
+	/// Dial a number with `ATD` and wait for the connection.
+	///
+	/// Returns the `CONNECT` result code, which often includes the connection speed, like `CONNECT 9600`.
+	/// The modem is then in data mode: use [`Self::read_data()`] and [`Self::write_data()`] to exchange data with the remote side,
+	/// and [`Self::escape()`] to return to command mode.
+	///
+	/// A number that ends with `;` starts a voice call, for which the modem responds with `OK` instead of `CONNECT`.
+	/// In that case, the `OK` result code is returned and the modem stays in command mode.
+	///
+	/// Returns an error of kind [`std::io::ErrorKind::Other`] that wraps an [`ErrorResponse`] for result codes like `BUSY` or `NO CARRIER`,
+	/// or an error of kind [`std::io::ErrorKind::TimedOut`] if no connection was made within 60 seconds.
+	///
+	/// # Example
+	/// ```no_run
+	/// # async fn example() -> std::io::Result<()> {
+	/// use serial2_tokio::SerialPort;
+	/// use serial2_tokio::at::AtPort;
+	/// use std::time::Duration;
+	///
+	/// let port = SerialPort::open("/dev/ttyS0", 9600)?;
+	/// let mut modem = AtPort::new(port);
+	/// let connect = modem.dial("5551234").await?;
+	/// println!("{connect}");
+	/// modem.write_data(b"READ METER\r").await?;
+	/// let mut buffer = [0; 256];
+	/// let read = modem.read_data_with_carrier(&mut buffer).await?;
+	/// println!("{:?}", &buffer[..read]);
+	/// modem.escape(Duration::from_secs(1)).await?;
+	/// modem.hang_up().await?;
+	/// # Ok(())
+	/// # }
+	/// ```
+	pub async fn dial(&mut self, number: &str) -> std::io::Result<String> {
+		let command = format!("ATD{number}");
+		self.send(&command).await?;
+		super::with_timeout(CONNECT_TIMEOUT, self.read_connect(&command)).await
+	}
+
+	/// Answer an incoming call with `ATA` and wait for the connection.
+	///
+	/// Returns the `CONNECT` result code, like [`Self::dial()`].
+	pub async fn answer(&mut self) -> std::io::Result<String> {
+		self.send("ATA").await?;
+		super::with_timeout(CONNECT_TIMEOUT, self.read_connect("ATA")).await
+	}
+
+	/// Hang up the connection with `ATH`.
+	///
+	/// The modem must be in command mode, so use [`Self::escape()`] first if a data connection is active.
+	/// Alternatively, many modems hang up when the DTR line is deasserted.
+	pub async fn hang_up(&mut self) -> std::io::Result<()> {
+		self.command("ATH").await?;
+		Ok(())
+	}
+
+	/// Return from data mode to command mode with the `+++` escape sequence.
+	///
+	/// The escape sequence is only recognized if the line is silent for the guard time before and after it.
+	/// The guard time is configured in the S12 register of the modem, and is 1 second by default.
+	/// This function waits for the guard time, sends `+++`, waits for the guard time again and then waits for the `OK` result code.
+	/// Data received before the `OK` is discarded.
+	///
+	/// The connection stays active: use [`Self::hang_up()`] to end it, or the `ATO` command to return to data mode.
+	pub async fn escape(&mut self, guard_time: Duration) -> std::io::Result<()> {
+		tokio::time::sleep(guard_time).await;
+		self.inner.write_all(b"+++").await?;
+		self.inner.flush().await?;
+		tokio::time::sleep(guard_time).await;
+		super::with_timeout(self.timeout, self.read_response("+++")).await?;
+		Ok(())
+	}
+
+	/// Read data from the remote side while the modem is in data mode.
+	///
+	/// Data that was received together with the `CONNECT` result code is returned first.
+	/// Returns zero bytes if the wrapped stream reached the end of file.
+	pub async fn read_data(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+		if !self.buffer.is_empty() {
+			let len = buf.len().min(self.buffer.len());
+			buf[..len].copy_from_slice(&self.buffer.split_to(len));
+			return Ok(len);
+		}
+		self.inner.read(buf).await
+	}
+
+	/// Write data to the remote side while the modem is in data mode.
+	pub async fn write_data(&mut self, data: &[u8]) -> std::io::Result<()> {
+		self.inner.write_all(data).await?;
+		self.inner.flush().await
+	}
+
+	/// Read the result of a dial or answer command.
+	async fn read_connect(&mut self, command: &str) -> std::io::Result<String> {
+		loop {
+			let Some(line) = self.read_line().await? else {
+				continue;
+			};
+			if line.starts_with("CONNECT") || line == "OK" {
+				return Ok(line);
+			} else if ERROR_RESULTS.iter().any(|&result| line.starts_with(result)) {
+				return Err(std::io::Error::other(ErrorResponse { result: line }));
+			} else if self.is_unsolicited(&line, command) {
+				self.unsolicited.push_back(line);
+			}
+		}
+	}
+}
+
+impl AtPort<SerialPort> {
+	/// Read data from the remote side, and stop when the carrier is lost.
+	///
+	/// This is the same as [`Self::read_data()`], except that the CD line is checked every 100 milliseconds while waiting for data.
+	/// When the modem deasserts the CD line, this returns an error of kind [`std::io::ErrorKind::NotConnected`].
+	/// The modem then returns to command mode by itself, so there is no need to send the escape sequence.
+	///
+	/// This only works if the modem is configured to follow the carrier with the CD line (`AT&C1`), which is the default for most modems.
+	pub async fn read_data_with_carrier(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+		if !self.buffer.is_empty() {
+			return self.read_data(buf).await;
+		}
+		loop {
+			if !self.inner.read_cd()? {
+				return Err(std::io::Error::new(std::io::ErrorKind::NotConnected, "carrier lost"));
+			}
+			// Reads from a serial port can be cancelled without losing data.
+			if let Ok(result) = tokio::time::timeout(CARRIER_POLL_INTERVAL, self.inner.read(buf)).await {
+				return result;
+			}
+		}
+	}
+}
@@ -0,0 +1,304 @@
+//! Sessions with modems that are controlled with AT commands.
+//!
+//! Cellular modems, Bluetooth and WiFi modules, and classic dial-up modems are controlled with AT commands,
+//! originally defined by Hayes and later extended by 3GPP for GSM modems.
+//! The [`AtPort`] sends commands, collects the information lines of the response,
+//! and reports error responses like `ERROR` and `+CME ERROR: <code>` as an [`ErrorResponse`].
+//!
+//! Modems also send unsolicited result codes, like `RING` or `+CMTI: "SM",1` when an SMS message is received.
+//! These are separated from the responses to commands and can be read with [`AtPort::next_unsolicited()`].
+//!
+//! The [`AtPort`] also has functions to send, read, list and delete SMS messages on GSM modems,
+//! and to wait for new SMS messages with [`AtPort::next_new_message()`].
+//!
+//! For classic dial-up modems, [`AtPort::dial()`] and [`AtPort::answer()`] establish a data connection,
+//! [`AtPort::read_data_with_carrier()`] reads data until the carrier is lost,
+//! and [`AtPort::escape()`] and [`AtPort::hang_up()`] end it again.
+//!
+//! This module is only available when the `at` feature is enabled.
+//!
+//! # Example
+//! ```no_run
+//! # async fn example() -> std::io::Result<()> {
+//! use serial2_tokio::SerialPort;
+//! use serial2_tokio::at::AtPort;
+//!
+//! let port = SerialPort::open("/dev/ttyUSB2", 115200)?;
+//! let mut modem = AtPort::new(port);
+//! modem.command("AT").await?;
+//! for line in modem.command("AT+CSQ").await? {
+//!     println!("signal quality: {line}");
+//! }
+//! modem.send_sms("+31612345678", "Hello from serial2-tokio").await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use bytes::BytesMut;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::codec::{AsciiCodec, AsciiConfig, AsciiMessage};
+
+mod hayes;
+mod sms;
+
+pub use sms::{NewMessage, SmsFilter, SmsMessage, SmsStatus};
+
+/// The default timeout for the response to a command.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The prefixes of the unsolicited result codes that are recognized by default.
+const DEFAULT_UNSOLICITED: &[&str] = &[
+	"RING",
+	"+CRING:",
+	"+CLIP:",
+	"+CMTI:",
+	"+CMT:",
+	"+CDSI:",
+	"+CDS:",
+	"+CBM:",
+	"+CREG:",
+	"+CGREG:",
+	"+CEREG:",
+];
+
+/// The final result codes that indicate an error.
+const ERROR_RESULTS: &[&str] = &["ERROR", "+CME ERROR:", "+CMS ERROR:", "NO CARRIER", "BUSY", "NO ANSWER", "NO DIALTONE"];
+
+/// An error response from a modem.
+///
+/// The functions of the [`AtPort`] report error responses as an [`std::io::Error`] of kind [`std::io::ErrorKind::Other`] that wraps this type.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ErrorResponse {
+	/// The final result code, like `ERROR` or `+CME ERROR: 10`.
+	pub result: String,
+}
+
+impl ErrorResponse {
+	/// Get the numeric error code of a `+CME ERROR` or `+CMS ERROR` response.
+	///
+	/// Returns `None` for other responses, or if the modem is configured to report verbose error messages.
+	pub fn code(&self) -> Option<u32> {
+		let (_, code) = self.result.split_once(':')?;
+		code.trim().parse().ok()
+	}
+}
+
+impl std::fmt::Display for ErrorResponse {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "modem responded with {}", self.result)
+	}
+}
+
+impl std::error::Error for ErrorResponse {}
+
+/// A session with a modem that is controlled with AT commands.
+///
+/// See the [module documentation](self) for more information.
+#[derive(Debug)]
+pub struct AtPort<T> {
+	inner: T,
+	codec: AsciiCodec,
+	buffer: BytesMut,
+	timeout: Duration,
+	unsolicited_prefixes: Vec<String>,
+	/// Unsolicited result codes that were received while waiting for a response.
+	unsolicited: VecDeque<String>,
+}
+
+impl<T> AtPort<T> {
+	/// Create a session with a modem.
+	///
+	/// Commands are terminated with `\r`, and command echo is suppressed, so it does not matter if echo is enabled on the modem.
+	pub fn new(inner: T) -> Self {
+		let mut config = AsciiConfig::new();
+		config.set_tx_terminator("\r");
+		config.set_rx_terminator("\n");
+		config.set_prompt(Some("> "));
+		config.set_suppress_echo(true);
+		Self {
+			inner,
+			codec: AsciiCodec::new(config),
+			buffer: BytesMut::new(),
+			timeout: DEFAULT_TIMEOUT,
+			unsolicited_prefixes: DEFAULT_UNSOLICITED.iter().map(|&prefix| prefix.into()).collect(),
+			unsolicited: VecDeque::new(),
+		}
+	}
+
+	/// Set the timeout for the response to a command.
+	///
+	/// The timeout can be overridden for a single command with [`Self::command_with_timeout()`].
+	/// The default timeout is 1 second.
+	pub fn set_timeout(&mut self, timeout: Duration) {
+		self.timeout = timeout;
+	}
+
+	/// Get the timeout for the response to a command.
+	pub fn get_timeout(&self) -> Duration {
+		self.timeout
+	}
+
+	/// Add a prefix for unsolicited result codes.
+	///
+	/// Received lines that start with the prefix are treated as unsolicited result codes,
+	/// unless the prefix is the name of the command that is being executed.
+	/// For example, `+CREG:` lines are unsolicited, except in the response to `AT+CREG?`.
+	///
+	/// The prefixes of the common unsolicited result codes for calls, SMS messages and network registration are added by default.
+	pub fn add_unsolicited_prefix(&mut self, prefix: impl Into<String>) {
+		self.unsolicited_prefixes.push(prefix.into());
+	}
+
+	/// Get a reference to the wrapped stream.
+	pub fn get_ref(&self) -> &T {
+		&self.inner
+	}
+
+	/// Consume the session and return the wrapped stream.
+	///
+	/// Received data that has not been read yet is lost.
+	pub fn into_inner(self) -> T {
+		self.inner
+	}
+
+	/// Check if a line received in response to a command is an unsolicited result code.
+	fn is_unsolicited(&self, line: &str, command: &str) -> bool {
+		self.unsolicited_prefixes.iter().any(|prefix| {
+			if !line.starts_with(prefix.as_str()) {
+				return false;
+			}
+			// Information lines in a response start with the name of the command.
+			let name = prefix.trim_end_matches(':');
+			!(name.starts_with('+') && command.get(2..).is_some_and(|command| command.starts_with(name)))
+		})
+	}
+}
+
+impl<T: AsyncRead + AsyncWrite + Unpin> AtPort<T> {
+	/// Send a command and read the response.
+	///
+	/// The command must include the `AT` prefix.
+	/// Returns the information lines of the response, without the final `OK`.
+	///
+	/// Returns an error of kind [`std::io::ErrorKind::Other`] that wraps an [`ErrorResponse`] if the modem reported an error,
+	/// or an error of kind [`std::io::ErrorKind::TimedOut`] if no final result code was received before the timeout.
+	pub async fn command(&mut self, command: &str) -> std::io::Result<Vec<String>> {
+		self.command_with_timeout(command, self.timeout).await
+	}
+
+	/// Send a command and read the response with a custom timeout.
+	///
+	/// This is useful for commands that take longer than normal, like a network scan.
+	pub async fn command_with_timeout(&mut self, command: &str, timeout: Duration) -> std::io::Result<Vec<String>> {
+		self.send(command).await?;
+		with_timeout(timeout, self.read_response(command)).await
+	}
+
+	/// Wait for the next unsolicited result code.
+	///
+	/// Unsolicited result codes that were received while waiting for the response to a command are returned first.
+	/// While waiting, all received lines are treated as unsolicited result codes.
+	/// This function does not time out.
+	pub async fn next_unsolicited(&mut self) -> std::io::Result<String> {
+		if let Some(line) = self.unsolicited.pop_front() {
+			return Ok(line);
+		}
+		loop {
+			if let Some(line) = self.read_line().await? {
+				return Ok(line);
+			}
+		}
+	}
+
+	/// Send a command that is followed by data after the `> ` prompt, like `AT+CMGS`, and read the response.
+	///
+	/// The data is terminated with Ctrl+Z.
+	async fn command_with_data(&mut self, command: &str, data: &str, timeout: Duration) -> std::io::Result<Vec<String>> {
+		self.send(command).await?;
+		with_timeout(self.timeout, self.wait_prompt(command)).await?;
+		self.inner.write_all(data.as_bytes()).await?;
+		self.inner.write_all(b"\x1A").await?;
+		self.inner.flush().await?;
+		with_timeout(timeout, self.read_response(command)).await
+	}
+
+	/// Send a command without waiting for the response.
+	async fn send(&mut self, command: &str) -> std::io::Result<()> {
+		let mut message = BytesMut::new();
+		self.codec.encode(command, &mut message)?;
+		self.inner.write_all(&message).await?;
+		self.inner.flush().await
+	}
+
+	/// Read the response to a command until the final result code.
+	async fn read_response(&mut self, command: &str) -> std::io::Result<Vec<String>> {
+		let mut lines = Vec::new();
+		loop {
+			let Some(line) = self.read_line().await? else {
+				continue;
+			};
+			if line == "OK" {
+				return Ok(lines);
+			} else if ERROR_RESULTS.iter().any(|&result| line.starts_with(result)) {
+				return Err(std::io::Error::other(ErrorResponse { result: line }));
+			} else if self.is_unsolicited(&line, command) {
+				self.unsolicited.push_back(line);
+			} else {
+				lines.push(line);
+			}
+		}
+	}
+
+	/// Wait for the `> ` prompt after a command.
+	async fn wait_prompt(&mut self, command: &str) -> std::io::Result<()> {
+		loop {
+			match self.codec.decode(&mut self.buffer)? {
+				Some(AsciiMessage::Prompt) => return Ok(()),
+				Some(AsciiMessage::Line(line)) => {
+					let line = line.trim_end_matches('\r');
+					if ERROR_RESULTS.iter().any(|&result| line.starts_with(result)) {
+						return Err(std::io::Error::other(ErrorResponse { result: line.into() }));
+					} else if self.is_unsolicited(line, command) {
+						self.unsolicited.push_back(line.into());
+					}
+				},
+				None => self.fill().await?,
+			}
+		}
+	}
+
+	/// Read a line, or `None` if the line was empty.
+	///
+	/// Prompts are ignored.
+	async fn read_line(&mut self) -> std::io::Result<Option<String>> {
+		loop {
+			match self.codec.decode(&mut self.buffer)? {
+				Some(AsciiMessage::Line(line)) => {
+					let line = line.trim_end_matches('\r');
+					return Ok((!line.is_empty()).then(|| line.to_owned()));
+				},
+				Some(AsciiMessage::Prompt) => continue,
+				None => self.fill().await?,
+			}
+		}
+	}
+
+	/// Read more data from the wrapped stream.
+	async fn fill(&mut self) -> std::io::Result<()> {
+		if self.inner.read_buf(&mut self.buffer).await? == 0 {
+			return Err(std::io::ErrorKind::UnexpectedEof.into());
+		}
+		Ok(())
+	}
+}
+
+/// Run a future with a timeout, and turn a timeout into an error.
+async fn with_timeout<T>(timeout: Duration, future: impl std::future::Future<Output = std::io::Result<T>>) -> std::io::Result<T> {
+	tokio::time::timeout(timeout, future).await
+		.map_err(|_| std::io::Error::new(std::io::ErrorKind::TimedOut, "timeout while waiting for response from modem"))?
+}
@@ -0,0 +1,448 @@
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncWrite};
+
+use super::AtPort;
+
+/// The timeout for sending an SMS message.
+///
+/// The specification allows the network up to a minute to accept a message.
+const SEND_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The status of a stored SMS message.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum SmsStatus {
+	/// A received message that has not been read yet.
+	ReceivedUnread,
+
+	/// A received message that has been read.
+	ReceivedRead,
+
+	/// A message that has been stored but not sent yet.
+	StoredUnsent,
+
+	/// A message that has been stored and sent.
+	StoredSent,
+}
+
+impl SmsStatus {
+	/// Parse the status from the text mode name or the PDU mode number.
+	fn parse(status: &str) -> Option<Self> {
+		match status {
+			"REC UNREAD" | "0" => Some(Self::ReceivedUnread),
+			"REC READ" | "1" => Some(Self::ReceivedRead),
+			"STO UNSENT" | "2" => Some(Self::StoredUnsent),
+			"STO SENT" | "3" => Some(Self::StoredSent),
+			_ => None,
+		}
+	}
+}
+
+/// The messages to list with [`AtPort::list_sms()`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum SmsFilter {
+	/// All messages.
+	All,
+
+	/// Only messages with the given status.
+	Status(SmsStatus),
+}
+
+impl SmsFilter {
+	/// Get the text mode name of the filter.
+	fn text_mode_name(self) -> &'static str {
+		match self {
+			Self::All => "ALL",
+			Self::Status(SmsStatus::ReceivedUnread) => "REC UNREAD",
+			Self::Status(SmsStatus::ReceivedRead) => "REC READ",
+			Self::Status(SmsStatus::StoredUnsent) => "STO UNSENT",
+			Self::Status(SmsStatus::StoredSent) => "STO SENT",
+		}
+	}
+}
+
+/// An SMS message stored on the modem, read in text mode.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SmsMessage {
+	/// The index of the message in the message storage.
+	pub index: u32,
+
+	/// The status of the message.
+	pub status: SmsStatus,
+
+	/// The phone number of the sender, or the recipient for stored messages.
+	pub number: String,
+
+	/// The time at which the service center received the message, as reported by the modem.
+	///
+	/// This is empty for messages that were not received.
+	pub timestamp: String,
+
+	/// The text of the message.
+	pub text: String,
+}
+
+/// A notification of a new SMS message, received with the `+CMTI` unsolicited result code.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct NewMessage {
+	/// The message storage that holds the new message, like `SM` or `ME`.
+	pub storage: String,
+
+	/// The index of the new message in the message storage.
+	pub index: u32,
+}
+
+impl<T: AsyncRead + AsyncWrite + Unpin> AtPort<T> {
+	/// Send an SMS message in text mode.
+	///
+	/// This switches the modem to text mode with `AT+CMGF=1` before sending the message.
+	/// The text is encoded by the modem according to the character set selected with `AT+CSCS`.
+	///
+	/// Returns the message reference assigned by the network.
+	pub async fn send_sms(&mut self, number: &str, text: &str) -> std::io::Result<u8> {
+		if number.is_empty() || !number.chars().all(|c| c.is_ascii_digit() || c == '+') {
+			return Err(invalid_input("the phone number may only contain digits and a leading +"));
+		}
+		check_data(text)?;
+		self.command("AT+CMGF=1").await?;
+		let response = self.command_with_data(&format!("AT+CMGS=\"{number}\""), text, SEND_TIMEOUT).await?;
+		parse_message_reference(&response)
+	}
+
+	/// Send an SMS message in PDU mode.
+	///
+	/// The PDU must be given as hexadecimal digits, including the service center address.
+	/// Use `00` as service center address to use the service center configured in the modem.
+	/// The `tpdu_len` is the length of the PDU in bytes, excluding the service center address.
+	///
+	/// This switches the modem to PDU mode with `AT+CMGF=0` before sending the message.
+	///
+	/// Returns the message reference assigned by the network.
+	pub async fn send_sms_pdu(&mut self, pdu: &str, tpdu_len: usize) -> std::io::Result<u8> {
+		if pdu.is_empty() || !pdu.len().is_multiple_of(2) || !pdu.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+			return Err(invalid_input("the PDU must be an even number of hexadecimal digits"));
+		}
+		self.command("AT+CMGF=0").await?;
+		let response = self.command_with_data(&format!("AT+CMGS={tpdu_len}"), pdu, SEND_TIMEOUT).await?;
+		parse_message_reference(&response)
+	}
+
+	/// List the SMS messages in the message storage, in text mode.
+	///
+	/// This switches the modem to text mode with `AT+CMGF=1`.
+	/// Note that most modems mark unread messages as read when they are listed.
+	pub async fn list_sms(&mut self, filter: SmsFilter) -> std::io::Result<Vec<SmsMessage>> {
+		self.command("AT+CMGF=1").await?;
+		let response = self.command(&format!("AT+CMGL=\"{}\"", filter.text_mode_name())).await?;
+
+		let mut messages = Vec::new();
+		let mut lines = response.into_iter().peekable();
+		while let Some(line) = lines.next() {
+			let Some(header) = line.strip_prefix("+CMGL:") else {
+				continue;
+			};
+			let fields = split_fields(header);
+			let index = fields.first()
+				.and_then(|index| index.parse().ok())
+				.ok_or_else(|| invalid_response("invalid message index"))?;
+			let mut message = parse_message(index, &fields[1..])?;
+			while let Some(text) = lines.next_if(|line| !line.starts_with("+CMGL:")) {
+				append_line(&mut message.text, &text);
+			}
+			messages.push(message);
+		}
+		Ok(messages)
+	}
+
+	/// Read a single SMS message from the message storage, in text mode.
+	///
+	/// This switches the modem to text mode with `AT+CMGF=1`.
+	pub async fn read_sms(&mut self, index: u32) -> std::io::Result<SmsMessage> {
+		self.command("AT+CMGF=1").await?;
+		let response = self.command(&format!("AT+CMGR={index}")).await?;
+		let mut lines = response.into_iter();
+		let header = lines.next()
+			.and_then(|line| line.strip_prefix("+CMGR:").map(String::from))
+			.ok_or_else(|| invalid_response("missing +CMGR header"))?;
+		let mut message = parse_message(index, &split_fields(&header))?;
+		for line in lines {
+			append_line(&mut message.text, &line);
+		}
+		Ok(message)
+	}
+
+	/// Delete an SMS message from the message storage.
+	pub async fn delete_sms(&mut self, index: u32) -> std::io::Result<()> {
+		self.command(&format!("AT+CMGD={index}")).await?;
+		Ok(())
+	}
+
+	/// Wait for the notification of a new SMS message.
+	///
+	/// The modem must be configured to send `+CMTI` notifications, usually with `AT+CNMI=2,1`.
+	/// Other unsolicited result codes are discarded while waiting.
+	/// This function does not time out.
+	pub async fn next_new_message(&mut self) -> std::io::Result<NewMessage> {
+		loop {
+			let line = self.next_unsolicited().await?;
+			let Some(notification) = line.strip_prefix("+CMTI:") else {
+				continue;
+			};
+			let fields = split_fields(notification);
+			if let [storage, index] = fields.as_slice() {
+				if let Ok(index) = index.parse() {
+					return Ok(NewMessage {
+						storage: storage.clone(),
+						index,
+					});
+				}
+			}
+			return Err(invalid_response("invalid +CMTI notification"));
+		}
+	}
+}
+
+/// Parse the fields of a `+CMGL` or `+CMGR` header after the index: the status, the number, an optional name and the timestamp.
+fn parse_message(index: u32, fields: &[String]) -> std::io::Result<SmsMessage> {
+	let status = fields.first()
+		.and_then(|status| SmsStatus::parse(status))
+		.ok_or_else(|| invalid_response("invalid message status"))?;
+	Ok(SmsMessage {
+		index,
+		status,
+		number: fields.get(1).cloned().unwrap_or_default(),
+		timestamp: fields.get(3).cloned().unwrap_or_default(),
+		text: String::new(),
+	})
+}
+
+/// Parse the message reference from the response to `AT+CMGS`.
+fn parse_message_reference(response: &[String]) -> std::io::Result<u8> {
+	response.iter()
+		.find_map(|line| line.strip_prefix("+CMGS:"))
+		.and_then(|reference| reference.trim().parse().ok())
+		.ok_or_else(|| invalid_response("missing message reference"))
+}
+
+/// Split the comma separated fields of a response, and remove the quotes around quoted fields.
+fn split_fields(line: &str) -> Vec<String> {
+	let mut fields = Vec::new();
+	let mut field = String::new();
+	let mut quoted = false;
+	for c in line.trim().chars() {
+		match c {
+			'"' => quoted = !quoted,
+			',' if !quoted => fields.push(std::mem::take(&mut field)),
+			c => field.push(c),
+		}
+	}
+	fields.push(field);
+	fields
+}
+
+/// Append a line to the text of a message.
+fn append_line(text: &mut String, line: &str) {
+	if !text.is_empty() {
+		text.push('\n');
+	}
+	text.push_str(line);
+}
+
+/// Check that the data for `AT+CMGS` does not contain the characters that end or cancel the message.
+fn check_data(data: &str) -> std::io::Result<()> {
+	if data.contains(['\x1A', '\x1B']) {
+		return Err(invalid_input("the message can not contain Ctrl+Z or Escape"));
+	}
+	Ok(())
+}
+
+/// Create an error for invalid input.
+fn invalid_input(message: &str) -> std::io::Error {
+	std::io::Error::new(std::io::ErrorKind::InvalidInput, message)
+}
+
+/// Create an error for an invalid response.
+fn invalid_response(message: &str) -> std::io::Error {
+	std::io::Error::new(std::io::ErrorKind::InvalidData, format!("invalid response from modem: {message}"))
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream};
+
+	fn fields(line: &str) -> Vec<String> {
+		split_fields(line)
+	}
+
+	/// Read exactly the expected bytes on the modem side and check them.
+	async fn expect(modem: &mut DuplexStream, expected: &str) {
+		let mut buffer = vec![0; expected.len()];
+		modem.read_exact(&mut buffer).await.unwrap();
+		assert_eq!(String::from_utf8_lossy(&buffer), expected);
+	}
+
+	/// Read a command on the modem side and send the response.
+	async fn respond(modem: &mut DuplexStream, command: &str, response: &str) {
+		expect(modem, command).await;
+		modem.write_all(response.as_bytes()).await.unwrap();
+	}
+
+	#[test]
+	fn split_fields_handles_quotes() {
+		assert_eq!(fields(r#" 1,"REC READ","+31612345678",,"24/05/01,12:30:00+08""#), [
+			"1",
+			"REC READ",
+			"+31612345678",
+			"",
+			"24/05/01,12:30:00+08",
+		]);
+		assert_eq!(fields(r#""SM",3"#), ["SM", "3"]);
+		assert_eq!(fields(""), [""]);
+	}
+
+	#[test]
+	fn parse_message_header() {
+		let message = parse_message(4, &fields(r#""REC UNREAD","+31612345678","Alice","24/05/01,12:30:00+08""#)).unwrap();
+		assert_eq!(message, SmsMessage {
+			index: 4,
+			status: SmsStatus::ReceivedUnread,
+			number: "+31612345678".into(),
+			timestamp: "24/05/01,12:30:00+08".into(),
+			text: String::new(),
+		});
+
+		// Stored messages have no timestamp.
+		let message = parse_message(5, &fields(r#""STO UNSENT","+31612345678","#)).unwrap();
+		assert_eq!(message.status, SmsStatus::StoredUnsent);
+		assert!(message.timestamp.is_empty());
+
+		assert_eq!(parse_message(6, &fields(r#""UNKNOWN","+31612345678""#)).unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+	}
+
+	#[test]
+	fn status_names_and_numbers() {
+		assert_eq!(SmsStatus::parse("REC READ"), Some(SmsStatus::ReceivedRead));
+		assert_eq!(SmsStatus::parse("3"), Some(SmsStatus::StoredSent));
+		assert_eq!(SmsStatus::parse("4"), None);
+		assert_eq!(SmsFilter::Status(SmsStatus::StoredSent).text_mode_name(), "STO SENT");
+		assert_eq!(SmsFilter::All.text_mode_name(), "ALL");
+	}
+
+	#[test]
+	fn message_reference() {
+		assert_eq!(parse_message_reference(&["+CMGS: 42".into()]).unwrap(), 42);
+		assert_eq!(parse_message_reference(&[]).unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+		assert_eq!(parse_message_reference(&["+CMGS: 300".into()]).unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+	}
+
+	#[test]
+	fn data_can_not_end_message() {
+		assert!(check_data("Hello, world!").is_ok());
+		assert_eq!(check_data("Hello\x1A").unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+		assert_eq!(check_data("Hello\x1B").unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+	}
+
+	#[tokio::test]
+	async fn send_sms_in_text_mode() {
+		let (client, mut modem) = tokio::io::duplex(256);
+		let mut port = AtPort::new(client);
+		let modem_side = async {
+			respond(&mut modem, "AT+CMGF=1\r", "\r\nOK\r\n").await;
+			respond(&mut modem, "AT+CMGS=\"+31612345678\"\r", "\r\n> ").await;
+			respond(&mut modem, "Hello\x1A", "\r\n+CMGS: 42\r\n\r\nOK\r\n").await;
+		};
+		let (reference, ()) = tokio::join!(port.send_sms("+31612345678", "Hello"), modem_side);
+		assert_eq!(reference.unwrap(), 42);
+
+		let error = port.send_sms("+31 6", "Hello").await.unwrap_err();
+		assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+	}
+
+	#[tokio::test]
+	async fn send_sms_in_pdu_mode() {
+		let (client, mut modem) = tokio::io::duplex(256);
+		let mut port = AtPort::new(client);
+		// A message with the text "hellohello" to +46708251358, from the PDU mode examples of GSM 07.05.
+		let pdu = "0011000B916407281553F80000AA0AE8329BFD4697D9EC37";
+		let modem_side = async {
+			respond(&mut modem, "AT+CMGF=0\r", "\r\nOK\r\n").await;
+			respond(&mut modem, "AT+CMGS=23\r", "\r\n> ").await;
+			expect(&mut modem, pdu).await;
+			respond(&mut modem, "\x1A", "\r\n+CMGS: 7\r\n\r\nOK\r\n").await;
+		};
+		let (reference, ()) = tokio::join!(port.send_sms_pdu(pdu, 23), modem_side);
+		assert_eq!(reference.unwrap(), 7);
+
+		assert_eq!(port.send_sms_pdu("0011X", 2).await.unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+	}
+
+	#[tokio::test]
+	async fn list_sms_with_multiline_text() {
+		let (client, mut modem) = tokio::io::duplex(512);
+		let mut port = AtPort::new(client);
+		let modem_side = async {
+			respond(&mut modem, "AT+CMGF=1\r", "\r\nOK\r\n").await;
+			respond(
+				&mut modem,
+				"AT+CMGL=\"ALL\"\r",
+				concat!(
+					"\r\n+CMGL: 1,\"REC READ\",\"+31612345678\",,\"24/05/01,12:30:00+08\"\r\n",
+					"Hello\r\n",
+					"+CMGL: 2,\"REC UNREAD\",\"+31687654321\",,\"24/05/02,08:15:00+08\"\r\n",
+					"First line\r\n",
+					"Second line\r\n",
+					"\r\nOK\r\n",
+				),
+			).await;
+		};
+		let (messages, ()) = tokio::join!(port.list_sms(SmsFilter::All), modem_side);
+		let messages = messages.unwrap();
+		assert_eq!(messages.len(), 2);
+		assert_eq!(messages[0].index, 1);
+		assert_eq!(messages[0].status, SmsStatus::ReceivedRead);
+		assert_eq!(messages[0].text, "Hello");
+		assert_eq!(messages[1].number, "+31687654321");
+		assert_eq!(messages[1].timestamp, "24/05/02,08:15:00+08");
+		assert_eq!(messages[1].text, "First line\nSecond line");
+	}
+
+	#[tokio::test]
+	async fn read_and_delete_sms() {
+		let (client, mut modem) = tokio::io::duplex(256);
+		let mut port = AtPort::new(client);
+		let modem_side = async {
+			respond(&mut modem, "AT+CMGF=1\r", "\r\nOK\r\n").await;
+			respond(
+				&mut modem,
+				"AT+CMGR=3\r",
+				"\r\n+CMGR: \"REC UNREAD\",\"+31612345678\",,\"24/05/01,12:30:00+08\"\r\nHi there\r\n\r\nOK\r\n",
+			).await;
+			respond(&mut modem, "AT+CMGD=3\r", "\r\nOK\r\n").await;
+		};
+		let (result, ()) = tokio::join!(
+			async {
+				let message = port.read_sms(3).await?;
+				port.delete_sms(3).await?;
+				Ok::<_, std::io::Error>(message)
+			},
+			modem_side,
+		);
+		let message = result.unwrap();
+		assert_eq!(message.index, 3);
+		assert_eq!(message.status, SmsStatus::ReceivedUnread);
+		assert_eq!(message.text, "Hi there");
+	}
+
+	#[tokio::test]
+	async fn new_message_notification() {
+		let (client, mut modem) = tokio::io::duplex(256);
+		let mut port = AtPort::new(client);
+		modem.write_all(b"\r\nRING\r\n\r\n+CMTI: \"SM\",3\r\n").await.unwrap();
+		let message = port.next_new_message().await.unwrap();
+		assert_eq!(message, NewMessage { storage: "SM".into(), index: 3 });
+
+		modem.write_all(b"\r\n+CMTI: \"SM\"\r\n").await.unwrap();
+		assert_eq!(port.next_new_message().await.unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+	}
+}
@@ -0,0 +1,288 @@
+use std::time::Duration;
+
+use crate::{LineCounters, SerialPort, Settings};
+
+/// Describes how to probe a device for [`SerialPort::detect_baud_rate()`].
+///
+/// By default, the probe only listens for incoming data and checks if it looks like text without line errors.
+/// You can configure a probe sequence to send for each candidate baud rate,
+/// and the reply that you expect the device to send back.
+#[derive(Debug, Clone)]
+pub struct BaudRateProbe {
+	send: Vec<u8>,
+	expect: Option<Vec<u8>>,
+	listen_time: Duration,
+	min_bytes: usize,
+	min_score: f32,
+}
+
+impl BaudRateProbe {
+	/// Create a new probe that only listens for incoming data.
+	pub fn new() -> Self {
+		Self {
+			send: Vec::new(),
+			expect: None,
+			listen_time: Duration::from_millis(250),
+			min_bytes: 8,
+			min_score: 0.9,
+		}
+	}
+
+	/// Send the given data after switching to each candidate baud rate.
+	///
+	/// This can be used to trigger a response from devices that only speak when spoken to.
+	pub fn send(mut self, data: impl Into<Vec<u8>>) -> Self {
+		self.send = data.into();
+		self
+	}
+
+	/// Accept a baud rate as soon as the given reply is received.
+	///
+	/// When an expected reply is configured, received data is no longer scored on how much it looks like text.
+	pub fn expect_reply(mut self, reply: impl Into<Vec<u8>>) -> Self {
+		self.expect = Some(reply.into());
+		self
+	}
+
+	/// Set how long to listen for data at each candidate baud rate.
+	///
+	/// The default is 250 milliseconds.
+	pub fn listen_time(mut self, listen_time: Duration) -> Self {
+		self.listen_time = listen_time;
+		self
+	}
+
+	/// Set the minimum number of bytes that must be received before a baud rate can be accepted based on text detection.
+	///
+	/// The default is 8 bytes.
+	pub fn min_bytes(mut self, min_bytes: usize) -> Self {
+		self.min_bytes = min_bytes;
+		self
+	}
+
+	/// Set the fraction of received bytes that must look like text for a baud rate to be accepted.
+	///
+	/// Framing errors, parity errors and breaks reported by the driver count as received bytes that do not look like text.
+	///
+	/// The value is clamped to the range `0.0..=1.0`.
+	/// The default is `0.9`.
+	pub fn min_score(mut self, min_score: f32) -> Self {
+		self.min_score = min_score.clamp(0.0, 1.0);
+		self
+	}
+
+	/// Compute the fraction of bytes that look like text.
+	///
+	/// Each line error counts as an additional byte that does not look like text.
+	fn score(&self, data: &[u8], line_errors: usize) -> f32 {
+		if data.len() < self.min_bytes || data.is_empty() {
+			return 0.0;
+		}
+		let text = data.iter()
+			.filter(|&&byte| byte.is_ascii_graphic() || byte.is_ascii_whitespace())
+			.count();
+		text as f32 / (data.len() + line_errors) as f32
+	}
+}
+
+impl Default for BaudRateProbe {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl SerialPort {
+	/// Detect the baud rate of the device connected to the serial port.
+	///
+	/// This function tries each candidate baud rate in order.
+	/// For each candidate, it discards the input buffer, sends the probe sequence (if any) and listens for data.
+	///
+	/// If the probe has an expected reply, the first baud rate that receives the expected reply is selected.
+	/// Otherwise, the baud rate for which the received data looks most like text is selected,
+	/// as long as it meets the minimum score of the probe.
+	/// Receiving data at the wrong baud rate typically results in framing errors and garbage bytes,
+	/// which are rarely valid text.
+	/// If the driver reports line errors (see [`Self::line_counters()`]),
+	/// the framing errors, parity errors and breaks received at a baud rate lower its score.
+	///
+	/// The selected baud rate is applied to the serial port and returned.
+	/// If no candidate matches, an error of kind [`std::io::ErrorKind::NotFound`] is returned.
+	/// The original configuration is restored if no baud rate is selected for any reason:
+	/// when no candidate matches, when an error occurs, or when the returned future is dropped.
+	/// When an error occurs or the future is dropped, the original configuration is applied immediately,
+	/// and probe data that has not been transmitted yet is discarded.
+	///
+	/// The Tokio runtime must have the time driver enabled to use this function.
+	///
+	/// # Example
+	/// ```no_run
+	/// # async fn example() -> std::io::Result<()> {
+	/// use serial2_tokio::{BaudRateProbe, SerialPort, COMMON_BAUD_RATES};
+	///
+	/// let port = SerialPort::open("/dev/ttyUSB0", 9600)?;
+	/// let probe = BaudRateProbe::new()
+	///     .send("AT\r")
+	///     .expect_reply("OK");
+	/// let baud_rate = port.detect_baud_rate(COMMON_BAUD_RATES, &probe).await?;
+	/// println!("Detected baud rate: {baud_rate}");
+	/// # Ok(())
+	/// # }
+	/// ```
+	pub async fn detect_baud_rate(&self, candidates: &[u32], probe: &BaudRateProbe) -> std::io::Result<u32> {
+		let restore = RestoreConfiguration {
+			port: self,
+			original: Some(self.get_configuration()?),
+			saved: Some(self.inner.with_raw(crate::inner::SavedSettings::save)?),
+		};
+		let mut best: Option<(u32, f32)> = None;
+
+		for &baud_rate in candidates {
+			if self.modify_configuration(|settings| settings.set_baud_rate(baud_rate)).is_err() {
+				// Not all baud rates are supported by all devices, so skip those that fail.
+				continue;
+			}
+			self.discard_input_buffer()?;
+
+			// Read the counters before sending the probe, so errors received while sending are counted for this baud rate.
+			let counters_before = self.line_counters().ok();
+			if !probe.send.is_empty() {
+				self.write_all(&probe.send).await?;
+			}
+			let received = self.listen(probe).await?;
+			let counters_after = self.line_counters().ok();
+			match &probe.expect {
+				Some(expect) => {
+					if contains(&received, expect) {
+						restore.keep_current();
+						return Ok(baud_rate);
+					}
+				},
+				None => {
+					let score = probe.score(&received, line_errors(counters_before, counters_after));
+					if score >= probe.min_score && !matches!(best, Some((_, best)) if best >= score) {
+						best = Some((baud_rate, score));
+					}
+				},
+			}
+		}
+
+		let Some((baud_rate, _score)) = best else {
+			restore.restore()?;
+			return Err(std::io::Error::new(std::io::ErrorKind::NotFound, "failed to detect baud rate"));
+		};
+		self.modify_configuration(|settings| settings.set_baud_rate(baud_rate))?;
+		restore.keep_current();
+		Ok(baud_rate)
+	}
+
+	/// Collect data for the listen time of the probe.
+	///
+	/// Stops early once the expected reply has been received.
+	async fn listen(&self, probe: &BaudRateProbe) -> std::io::Result<Vec<u8>> {
+		let deadline = tokio::time::Instant::now() + probe.listen_time;
+		let mut received = Vec::new();
+		let mut buffer = [0; 256];
+		while received.len() < 4096 {
+			let read = match tokio::time::timeout_at(deadline, self.read(&mut buffer)).await {
+				Ok(read) => read?,
+				Err(_elapsed) => break,
+			};
+			if read == 0 {
+				break;
+			}
+			received.extend_from_slice(&buffer[..read]);
+			if probe.expect.as_ref().is_some_and(|expect| contains(&received, expect)) {
+				break;
+			}
+		}
+		Ok(received)
+	}
+}
+
+/// Restores the original configuration of a serial port when dropped, unless the current configuration is kept.
+struct RestoreConfiguration<'a> {
+	port: &'a SerialPort,
+	original: Option<Settings>,
+
+	/// The original settings to restore when dropped.
+	///
+	/// These are applied without waiting for the output buffer to drain,
+	/// because `drop()` can not wait and the output may be stopped by flow control.
+	saved: Option<crate::inner::SavedSettings>,
+}
+
+impl RestoreConfiguration<'_> {
+	/// Keep the current configuration of the serial port.
+	fn keep_current(mut self) {
+		self.original = None;
+		self.saved = None;
+	}
+
+	/// Restore the original configuration now, and report if that failed.
+	///
+	/// The probe that was sent is transmitted before the original configuration is applied.
+	fn restore(mut self) -> std::io::Result<()> {
+		self.saved = None;
+		match self.original.take() {
+			Some(original) => self.port.set_configuration(&original),
+			None => Ok(()),
+		}
+	}
+}
+
+impl Drop for RestoreConfiguration<'_> {
+	fn drop(&mut self) {
+		if let Some(saved) = self.saved.take() {
+			self.port.inner.restore_settings(&saved).ok();
+		}
+	}
+}
+
+/// Get the number of framing errors, parity errors and breaks between two readings of the line counters.
+///
+/// Counters that are not supported by the driver are ignored.
+fn line_errors(before: Option<LineCounters>, after: Option<LineCounters>) -> usize {
+	let (Some(before), Some(after)) = (before, after) else {
+		return 0;
+	};
+	let increase = |before: Option<u32>, after: Option<u32>| match (before, after) {
+		(Some(before), Some(after)) => after.wrapping_sub(before) as usize,
+		_ => 0,
+	};
+	increase(before.frame_errors, after.frame_errors)
+		+ increase(before.parity_errors, after.parity_errors)
+		+ increase(before.breaks, after.breaks)
+}
+
+/// Check if `haystack` contains `needle`.
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+	needle.is_empty() || haystack.windows(needle.len()).any(|window| window == needle)
+}
+
+#[cfg(test)]
+#[cfg(all(unix, feature = "unix"))]
+mod test {
+	use super::*;
+
+	#[tokio::test]
+	async fn configuration_is_restored_when_cancelled() {
+		let (port, _device) = SerialPort::pair_with_baud(9600).unwrap();
+		let probe = BaudRateProbe::new()
+			.send("AT\r")
+			.expect_reply("OK")
+			.listen_time(Duration::from_secs(10));
+		let result = tokio::time::timeout(Duration::from_millis(50), port.detect_baud_rate(&[115200], &probe)).await;
+		assert!(result.is_err());
+		assert_eq!(port.get_configuration().unwrap().get_baud_rate().unwrap(), 9600);
+	}
+
+	#[tokio::test]
+	async fn configuration_is_restored_when_nothing_matches() {
+		let (port, _device) = SerialPort::pair_with_baud(9600).unwrap();
+		let probe = BaudRateProbe::new()
+			.listen_time(Duration::from_millis(10));
+		let error = port.detect_baud_rate(&[115200, 57600], &probe).await.unwrap_err();
+		assert_eq!(error.kind(), std::io::ErrorKind::NotFound);
+		assert_eq!(port.get_configuration().unwrap().get_baud_rate().unwrap(), 9600);
+	}
+}
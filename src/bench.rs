@@ -0,0 +1,307 @@
+//! Measure the latency and throughput of a serial link.
+//!
+//! The functions in this module send test data from one serial port to another and measure how long it takes to arrive.
+//! This helps to choose buffer sizes, baud rates and coalescing settings, and to compare USB serial adapters and drivers.
+//!
+//! The two ports can be connected with a null-modem cable, a pseudo-terminal pair ([`SerialPort::pair()`]),
+//! or they can be the same port with a loopback plug or [internal loopback][SerialPort::set_loopback()].
+//! The test data only contains printable ASCII characters, so it is not affected by software flow control.
+//!
+//! This module is only available when the `bench` feature is enabled.
+//!
+//! # Example
+//! ```no_run
+//! # async fn example() -> std::io::Result<()> {
+//! use serial2_tokio::SerialPort;
+//! use serial2_tokio::bench::{self, BenchConfig};
+//!
+//! let port = SerialPort::open("/dev/ttyUSB0", 921600)?;
+//! let config = BenchConfig::new();
+//! let latency = bench::round_trip_latency(&port, &port, &config).await?;
+//! println!("median latency: {:?}", latency.percentile(50.0));
+//! let throughput = bench::throughput(&port, &port, &config).await?;
+//! println!("throughput: {:.0} bytes/s", throughput.bytes_per_second());
+//! # Ok(())
+//! # }
+//! ```
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::task::AbortOnDrop;
+use crate::SerialPort;
+
+/// Configuration for the measurements of this module.
+#[derive(Debug, Clone)]
+pub struct BenchConfig {
+	message_size: usize,
+	samples: usize,
+	chunk_size: usize,
+	duration: Duration,
+	timeout: Duration,
+}
+
+impl Default for BenchConfig {
+	fn default() -> Self {
+		Self {
+			message_size: 16,
+			samples: 100,
+			chunk_size: 4096,
+			duration: Duration::from_secs(1),
+			timeout: Duration::from_secs(1),
+		}
+	}
+}
+
+impl BenchConfig {
+	/// Create a new configuration with the default values.
+	///
+	/// The default configuration measures the latency of 100 messages of 16 bytes,
+	/// and the throughput during 1 second with writes of 4096 bytes.
+	/// The default timeout is 1 second.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Set the size of the messages for the latency measurement.
+	///
+	/// # Panics
+	/// This function panics if the size is 0.
+	pub fn set_message_size(&mut self, size: usize) {
+		assert!(size > 0, "the message size can not be 0");
+		self.message_size = size;
+	}
+
+	/// Get the size of the messages for the latency measurement.
+	pub fn get_message_size(&self) -> usize {
+		self.message_size
+	}
+
+	/// Set the number of messages for the latency measurement.
+	///
+	/// # Panics
+	/// This function panics if the number of samples is 0.
+	pub fn set_samples(&mut self, samples: usize) {
+		assert!(samples > 0, "the number of samples can not be 0");
+		self.samples = samples;
+	}
+
+	/// Get the number of messages for the latency measurement.
+	pub fn get_samples(&self) -> usize {
+		self.samples
+	}
+
+	/// Set the size of the writes and reads for the throughput measurement.
+	///
+	/// # Panics
+	/// This function panics if the size is 0.
+	pub fn set_chunk_size(&mut self, size: usize) {
+		assert!(size > 0, "the chunk size can not be 0");
+		self.chunk_size = size;
+	}
+
+	/// Get the size of the writes and reads for the throughput measurement.
+	pub fn get_chunk_size(&self) -> usize {
+		self.chunk_size
+	}
+
+	/// Set how long data is sent for the throughput measurement.
+	pub fn set_duration(&mut self, duration: Duration) {
+		self.duration = duration;
+	}
+
+	/// Get how long data is sent for the throughput measurement.
+	pub fn get_duration(&self) -> Duration {
+		self.duration
+	}
+
+	/// Set the maximum time to wait for data to arrive.
+	///
+	/// A latency measurement fails if a message does not arrive within the timeout.
+	/// A throughput measurement stops waiting for the remaining data when nothing was received for the duration of the timeout,
+	/// and reports the missing data as lost.
+	pub fn set_timeout(&mut self, timeout: Duration) {
+		self.timeout = timeout;
+	}
+
+	/// Get the maximum time to wait for data to arrive.
+	pub fn get_timeout(&self) -> Duration {
+		self.timeout
+	}
+}
+
+/// The result of a latency measurement.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[non_exhaustive]
+pub struct LatencyResult {
+	/// The round-trip time of each message, in the order they were sent.
+	pub samples: Vec<Duration>,
+}
+
+impl LatencyResult {
+	/// Get the shortest round-trip time.
+	pub fn min(&self) -> Duration {
+		self.samples.iter().copied().min().unwrap_or_default()
+	}
+
+	/// Get the longest round-trip time.
+	pub fn max(&self) -> Duration {
+		self.samples.iter().copied().max().unwrap_or_default()
+	}
+
+	/// Get the average round-trip time.
+	pub fn mean(&self) -> Duration {
+		match u32::try_from(self.samples.len()) {
+			Ok(0) | Err(_) => Duration::ZERO,
+			Ok(count) => self.samples.iter().sum::<Duration>() / count,
+		}
+	}
+
+	/// Get a percentile of the round-trip times, like `50.0` for the median or `99.0` for the 99th percentile.
+	///
+	/// Uses the nearest-rank method.
+	pub fn percentile(&self, percentile: f64) -> Duration {
+		let mut sorted = self.samples.clone();
+		sorted.sort_unstable();
+		let rank = (percentile.clamp(0.0, 100.0) / 100.0 * sorted.len() as f64).ceil() as usize;
+		sorted.get(rank.saturating_sub(1)).copied().unwrap_or_default()
+	}
+}
+
+/// The result of a throughput measurement.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[non_exhaustive]
+pub struct ThroughputResult {
+	/// The number of bytes that were written.
+	pub bytes_sent: u64,
+
+	/// The number of bytes that were received.
+	pub bytes_received: u64,
+
+	/// The time from the first write until the last byte was received.
+	pub elapsed: Duration,
+}
+
+impl ThroughputResult {
+	/// Get the throughput in received bytes per second.
+	pub fn bytes_per_second(&self) -> f64 {
+		if self.elapsed.is_zero() {
+			return 0.0;
+		}
+		self.bytes_received as f64 / self.elapsed.as_secs_f64()
+	}
+
+	/// Get the number of bytes that were sent but not received.
+	pub fn lost_bytes(&self) -> u64 {
+		self.bytes_sent.saturating_sub(self.bytes_received)
+	}
+}
+
+/// Measure the round-trip time of messages from `tx` to `rx`.
+///
+/// Each message is written to `tx`, and the time until the whole message has been read from `rx` is recorded.
+/// The next message is only sent after the previous one has been received.
+/// The input buffer of `rx` is discarded before the measurement starts.
+///
+/// Pass the same serial port for `tx` and `rx` to measure with a loopback plug or with internal loopback.
+///
+/// Returns an error of kind [`std::io::ErrorKind::TimedOut`] if a message does not arrive within the timeout,
+/// or an error of kind [`std::io::ErrorKind::InvalidData`] if different data is received.
+pub async fn round_trip_latency(tx: &SerialPort, rx: &SerialPort, config: &BenchConfig) -> std::io::Result<LatencyResult> {
+	rx.discard_input_buffer()?;
+	let mut message = vec![0; config.message_size];
+	let mut received = vec![0; config.message_size];
+	let mut samples = Vec::with_capacity(config.samples);
+	for sample in 0..config.samples {
+		fill_pattern(&mut message, sample);
+		let start = Instant::now();
+		tx.write_all(&message).await?;
+		let mut read = 0;
+		while read < received.len() {
+			read += match tokio::time::timeout(config.timeout, rx.read(&mut received[read..])).await {
+				Ok(Ok(0)) => return Err(std::io::ErrorKind::UnexpectedEof.into()),
+				Ok(result) => result?,
+				Err(_) => return Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "message was not received in time")),
+			};
+		}
+		samples.push(start.elapsed());
+		if received != message {
+			return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "received data does not match transmitted data"));
+		}
+	}
+	Ok(LatencyResult { samples })
+}
+
+/// Measure the maximum throughput from `tx` to `rx`.
+///
+/// Data is written to `tx` as fast as possible for the configured duration, while it is read from `rx` at the same time.
+/// The writes happen in a background task on a clone of `tx` (see [`SerialPort::try_clone()`]).
+/// The input buffer of `rx` is discarded before the measurement starts.
+///
+/// Pass the same serial port for `tx` and `rx` to measure with a loopback plug or with internal loopback.
+///
+/// Lost data does not cause an error, but it is reported in the result.
+/// Check [`ThroughputResult::lost_bytes()`] to see if the receiver could keep up.
+pub async fn throughput(tx: &SerialPort, rx: &SerialPort, config: &BenchConfig) -> std::io::Result<ThroughputResult> {
+	rx.discard_input_buffer()?;
+	let writer = tx.try_clone()?;
+	let chunk_size = config.chunk_size;
+	let duration = config.duration;
+	let sent = Arc::new(AtomicU64::new(0));
+	let start = Instant::now();
+	let task = tokio::spawn({
+		let sent = sent.clone();
+		async move {
+			let mut chunk = vec![0; chunk_size];
+			let mut index = 0;
+			while start.elapsed() < duration {
+				fill_pattern(&mut chunk, index);
+				writer.write_all(&chunk).await?;
+				sent.fetch_add(chunk.len() as u64, Ordering::Relaxed);
+				index += 1;
+			}
+			Ok::<_, std::io::Error>(())
+		}
+	});
+	let _task = AbortOnDrop(task.abort_handle());
+	let mut task = Some(task);
+
+	let mut buffer = vec![0; chunk_size];
+	let mut received = 0;
+	let mut last_read = start;
+	loop {
+		if task.as_ref().is_some_and(|task| task.is_finished()) {
+			if let Some(task) = task.take() {
+				task.await.map_err(std::io::Error::other)??;
+			}
+		}
+		if task.is_none() && received >= sent.load(Ordering::Relaxed) {
+			break;
+		}
+		match tokio::time::timeout(config.timeout, rx.read(&mut buffer)).await {
+			Ok(Ok(0)) => break,
+			Ok(Ok(read)) => {
+				received += read as u64;
+				last_read = Instant::now();
+			},
+			Ok(Err(e)) => return Err(e),
+			// Stop if nothing arrives anymore, even if the writer is blocked by flow control.
+			Err(_) if start.elapsed() >= duration => break,
+			Err(_) => (),
+		}
+	}
+
+	Ok(ThroughputResult {
+		bytes_sent: sent.load(Ordering::Relaxed),
+		bytes_received: received,
+		elapsed: last_read - start,
+	})
+}
+
+/// Fill a buffer with printable ASCII characters that depend on the index of the message.
+fn fill_pattern(buffer: &mut [u8], index: usize) {
+	for (i, byte) in buffer.iter_mut().enumerate() {
+		*byte = b' ' + ((index + i) % 95) as u8;
+	}
+}
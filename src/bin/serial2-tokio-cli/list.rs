@@ -0,0 +1,104 @@
+//! Extra information about serial ports for the `list` command.
+
+use std::path::Path;
+
+/// Information about a serial port, as far as it can be determined.
+#[derive(Debug, Default)]
+pub struct PortInfo {
+	/// The kernel driver of the serial port.
+	driver: Option<String>,
+
+	/// The USB vendor and product ID of the device.
+	usb_id: Option<(String, String)>,
+
+	/// The manufacturer of the USB device.
+	manufacturer: Option<String>,
+
+	/// The product name of the USB device.
+	product: Option<String>,
+
+	/// The serial number of the USB device.
+	serial_number: Option<String>,
+}
+
+impl PortInfo {
+	/// Get the information for a serial port.
+	///
+	/// Information that can not be determined is left empty.
+	pub fn get(path: &Path) -> Self {
+		let mut info = Self::default();
+		sys::fill(path, &mut info);
+		info
+	}
+}
+
+impl std::fmt::Display for PortInfo {
+	/// Write the information as a list of fields, each prefixed with two spaces.
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		if let Some(driver) = &self.driver {
+			write!(f, "  driver={driver}")?;
+		}
+		if let Some((vendor, product)) = &self.usb_id {
+			write!(f, "  usb={vendor}:{product}")?;
+		}
+		if let Some(manufacturer) = &self.manufacturer {
+			write!(f, "  manufacturer={manufacturer:?}")?;
+		}
+		if let Some(product) = &self.product {
+			write!(f, "  product={product:?}")?;
+		}
+		if let Some(serial_number) = &self.serial_number {
+			write!(f, "  serial={serial_number:?}")?;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(target_os = "linux")]
+mod sys {
+	use std::path::Path;
+
+	use super::PortInfo;
+
+	/// Fill in the information from sysfs.
+	pub fn fill(path: &Path, info: &mut PortInfo) {
+		let Some(name) = std::fs::canonicalize(path).ok().and_then(|path| Some(path.file_name()?.to_owned())) else {
+			return;
+		};
+		let Ok(device) = std::fs::canonicalize(Path::new("/sys/class/tty").join(name).join("device")) else {
+			return;
+		};
+
+		info.driver = std::fs::read_link(device.join("driver"))
+			.ok()
+			.and_then(|driver| Some(driver.file_name()?.to_string_lossy().into_owned()));
+
+		// The USB device is a parent of the USB interface that provides the serial port.
+		let Some(usb_device) = device.ancestors().find(|dir| dir.join("idVendor").exists()) else {
+			return;
+		};
+		if let (Some(vendor), Some(product)) = (read_attribute(usb_device, "idVendor"), read_attribute(usb_device, "idProduct")) {
+			info.usb_id = Some((vendor, product));
+		}
+		info.manufacturer = read_attribute(usb_device, "manufacturer");
+		info.product = read_attribute(usb_device, "product");
+		info.serial_number = read_attribute(usb_device, "serial");
+	}
+
+	/// Read a sysfs attribute, without the trailing newline.
+	fn read_attribute(dir: &Path, name: &str) -> Option<String> {
+		let value = std::fs::read_to_string(dir.join(name)).ok()?;
+		Some(value.trim_end().to_owned())
+	}
+}
+
+#[cfg(not(target_os = "linux"))]
+mod sys {
+	use std::path::Path;
+
+	use super::PortInfo;
+
+	/// Extra information is not supported on this platform.
+	pub fn fill(_path: &Path, _info: &mut PortInfo) {
+	}
+}
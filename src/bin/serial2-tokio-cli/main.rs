@@ -0,0 +1,246 @@
+//! Command line tool to list, monitor and use serial ports.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use serial2_tokio::bridge::{Bridge, ClientPolicy};
+use serial2_tokio::sniffer::{Direction, Sniffer};
+use serial2_tokio::{KeepSettings, SerialPort};
+
+mod list;
+
+/// List, monitor and use serial ports.
+#[derive(clap::Parser)]
+#[command(version)]
+enum Options {
+	/// List the available serial ports.
+	List(ListOptions),
+
+	/// Show the data received on a serial port as a hexdump with timestamps.
+	Monitor(MonitorOptions),
+
+	/// Send the contents of a file or a hex string to a serial port.
+	Send(SendOptions),
+
+	/// Make a serial port available over TCP.
+	Bridge(BridgeOptions),
+}
+
+#[derive(clap::Args)]
+struct ListOptions {
+	/// Show only the paths of the serial ports, without extra information.
+	#[arg(long, short)]
+	quiet: bool,
+}
+
+#[derive(clap::Args)]
+struct PortOptions {
+	/// The serial port to use.
+	port: PathBuf,
+
+	/// Set the baud rate of the serial port.
+	///
+	/// If not given, the current settings of the serial port are used.
+	#[arg(long, short)]
+	baud: Option<u32>,
+}
+
+#[derive(clap::Args)]
+struct MonitorOptions {
+	#[command(flatten)]
+	port: PortOptions,
+
+	/// Open the serial port read-only and do not change its settings, to observe a link used by another program.
+	#[arg(long, conflicts_with = "baud")]
+	observe: bool,
+
+	/// Also write the received data to a file in the pcapng format.
+	#[arg(long, value_name = "FILE")]
+	pcapng: Option<PathBuf>,
+}
+
+#[derive(clap::Args)]
+struct SendOptions {
+	#[command(flatten)]
+	port: PortOptions,
+
+	/// The file to send.
+	#[arg(required_unless_present = "hex", conflicts_with = "hex")]
+	file: Option<PathBuf>,
+
+	/// Send the given bytes in hexadecimal notation, like "01 02 AB" or "0102AB".
+	#[arg(long, short = 'x')]
+	hex: Option<String>,
+}
+
+#[derive(clap::Args)]
+struct BridgeOptions {
+	#[command(flatten)]
+	port: PortOptions,
+
+	/// The address to listen on for TCP connections.
+	#[arg(long, short, default_value = "127.0.0.1:5331")]
+	listen: String,
+
+	/// Allow multiple clients at the same time.
+	#[arg(long)]
+	shared: bool,
+}
+
+#[tokio::main]
+async fn main() {
+	let options: Options = clap::Parser::parse();
+	let result = match options {
+		Options::List(options) => list(options),
+		Options::Monitor(options) => monitor(options).await,
+		Options::Send(options) => send(options).await,
+		Options::Bridge(options) => bridge(options).await,
+	};
+	if let Err(()) = result {
+		std::process::exit(1);
+	}
+}
+
+fn list(options: ListOptions) -> Result<(), ()> {
+	let ports = SerialPort::available_ports()
+		.map_err(|e| eprintln!("Error: Failed to list serial ports: {e}"))?;
+	for path in ports {
+		if options.quiet {
+			println!("{}", path.display());
+		} else {
+			let info = list::PortInfo::get(&path);
+			println!("{}{}", path.display(), info);
+		}
+	}
+	Ok(())
+}
+
+async fn monitor(options: MonitorOptions) -> Result<(), ()> {
+	let port = if options.observe {
+		let path = &options.port.port;
+		SerialPort::open_observer(path)
+			.map_err(|e| report_open_error(path, &e))?
+	} else {
+		open(&options.port)?
+	};
+
+	let mut sniffer = Sniffer::single(port, Direction::BToA);
+	if let Some(path) = &options.pcapng {
+		let file = std::fs::File::create(path)
+			.map_err(|e| eprintln!("Error: Failed to create {}: {e}", path.display()))?;
+		sniffer = sniffer.pcapng_log(file);
+	}
+
+	let start = SystemTime::now();
+	let mut stdout = std::io::stdout().lock();
+	loop {
+		let capture = sniffer.next_capture()
+			.await
+			.map_err(|e| eprintln!("Error: Failed to read from {}: {e}", options.port.port.display()))?;
+		let Some(capture) = capture else {
+			return Ok(());
+		};
+		let elapsed = capture.timestamp.duration_since(start).unwrap_or_default();
+		write_hexdump(&mut stdout, elapsed.as_secs_f64(), &capture.data)
+			.map_err(|e| eprintln!("Error: Failed to write to standard output: {e}"))?;
+	}
+}
+
+async fn send(options: SendOptions) -> Result<(), ()> {
+	let data = match (&options.file, &options.hex) {
+		(_, Some(hex)) => parse_hex(hex)
+			.map_err(|e| eprintln!("Error: Invalid hex string: {e}"))?,
+		(Some(path), None) => std::fs::read(path)
+			.map_err(|e| eprintln!("Error: Failed to read {}: {e}", path.display()))?,
+		(None, None) => unreachable!("clap requires a file or a hex string"),
+	};
+
+	let port = open(&options.port)?;
+	port.write_all(&data)
+		.await
+		.map_err(|e| eprintln!("Error: Failed to write to {}: {e}", options.port.port.display()))?;
+	port.drain()
+		.await
+		.map_err(|e| eprintln!("Error: Failed to drain {}: {e}", options.port.port.display()))?;
+	Ok(())
+}
+
+async fn bridge(options: BridgeOptions) -> Result<(), ()> {
+	let port = open(&options.port)?;
+	let listener = tokio::net::TcpListener::bind(&options.listen)
+		.await
+		.map_err(|e| eprintln!("Error: Failed to listen on {}: {e}", options.listen))?;
+	let policy = if options.shared {
+		ClientPolicy::Broadcast
+	} else {
+		ClientPolicy::Exclusive
+	};
+
+	eprintln!("Forwarding {} on {}", options.port.port.display(), options.listen);
+	Bridge::new(port)
+		.client_policy(policy)
+		.serve(listener)
+		.await
+		.map_err(|e| eprintln!("Error: {e}"))
+}
+
+/// Open a serial port with the baud rate from the command line, or with the current settings.
+fn open(options: &PortOptions) -> Result<SerialPort, ()> {
+	let result = match options.baud {
+		Some(baud_rate) => SerialPort::open(&options.port, baud_rate),
+		None => SerialPort::open(&options.port, KeepSettings),
+	};
+	result.map_err(|e| report_open_error(&options.port, &e))
+}
+
+/// Print an error for a serial port that could not be opened, with hints on how to solve it.
+fn report_open_error(path: &Path, error: &std::io::Error) {
+	eprintln!("Error: Failed to open {}: {error}", path.display());
+	let diagnosis = SerialPort::diagnose_open_error(path, error);
+	for hint in &diagnosis.hints {
+		eprintln!("  {hint}");
+	}
+}
+
+/// Write data as a hexdump with 16 bytes per line.
+///
+/// The first line is prefixed with the timestamp, the other lines are indented to match.
+fn write_hexdump(output: &mut impl Write, timestamp: f64, data: &[u8]) -> std::io::Result<()> {
+	let prefix = format!("[{timestamp:12.6}]");
+	for (i, chunk) in data.chunks(16).enumerate() {
+		if i == 0 {
+			write!(output, "{prefix} ")?;
+		} else {
+			write!(output, "{:width$} ", "", width = prefix.len())?;
+		}
+		for byte in chunk {
+			write!(output, "{byte:02X} ")?;
+		}
+		let text: String = chunk.iter()
+			.map(|&byte| if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' })
+			.collect();
+		writeln!(output, "{:padding$}|{text}|", "", padding = (16 - chunk.len()) * 3)?;
+	}
+	output.flush()
+}
+
+/// Parse a string of hexadecimal bytes.
+///
+/// Bytes may be separated by whitespace or commas, and may have a `0x` prefix.
+fn parse_hex(input: &str) -> Result<Vec<u8>, String> {
+	let mut data = Vec::new();
+	for word in input.split(|c: char| c.is_whitespace() || c == ',').filter(|word| !word.is_empty()) {
+		let digits = word.strip_prefix("0x").or_else(|| word.strip_prefix("0X")).unwrap_or(word);
+		if !digits.bytes().all(|c| c.is_ascii_hexdigit()) {
+			return Err(format!("invalid hexadecimal digits in {word:?}"));
+		}
+		if digits.len() % 2 != 0 {
+			return Err(format!("odd number of digits in {word:?}"));
+		}
+		for i in (0..digits.len()).step_by(2) {
+			data.push(u8::from_str_radix(&digits[i..i + 2], 16).unwrap());
+		}
+	}
+	Ok(data)
+}
@@ -0,0 +1,331 @@
+//! Forward data between a serial port and TCP clients.
+//!
+//! A [`Bridge`] accepts TCP connections and forwards raw data between the clients and a serial port,
+//! similar to the raw mode of `ser2net`.
+//!
+//! Data is only read from the serial port while at least one client is connected,
+//! and the next read does not start until the data has been written to all clients.
+//! Slow clients therefore apply backpressure to the serial port instead of causing data loss in the bridge.
+//! The data is written to all clients concurrently, so a slow client does not delay the delivery of data that was already read to the other clients.
+//! Data written by clients is forwarded to the serial port as it arrives.
+//!
+//! Use [`ClientPolicy`] to decide if multiple clients may be connected at the same time.
+//!
+//! # Example
+//! ```no_run
+//! # async fn example() -> std::io::Result<()> {
+//! use serial2_tokio::SerialPort;
+//! use serial2_tokio::bridge::{Bridge, ClientPolicy};
+//! use tokio::net::TcpListener;
+//!
+//! let port = SerialPort::open("/dev/ttyUSB0", 115200)?;
+//! let listener = TcpListener::bind("0.0.0.0:2000").await?;
+//! let bridge = Bridge::new(port).client_policy(ClientPolicy::Broadcast);
+//! bridge.serve(listener).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::Poll;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{watch, Mutex};
+use tokio::task::AbortHandle;
+
+#[cfg(any(feature = "doc", feature = "cancel"))]
+use tokio_util::sync::CancellationToken;
+
+use crate::SerialPort;
+use crate::cancel::Cancel;
+use crate::task::AbortOnDrop;
+
+/// How to handle multiple clients connecting to a bridge.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+#[non_exhaustive]
+pub enum ClientPolicy {
+	/// Allow only one client at a time.
+	///
+	/// Connections made while a client is connected are closed immediately.
+	#[default]
+	Exclusive,
+
+	/// Allow any number of clients.
+	///
+	/// Data read from the serial port is sent to all clients.
+	/// Data from all clients is written to the serial port.
+	/// The slowest client determines the speed at which data is read from the serial port.
+	Broadcast,
+}
+
+/// Byte counters for a bridge.
+#[derive(Debug, Default)]
+pub struct BridgeCounters {
+	serial_to_network: AtomicU64,
+	network_to_serial: AtomicU64,
+}
+
+impl BridgeCounters {
+	/// Get the number of bytes read from the serial port and sent to the clients.
+	///
+	/// With [`ClientPolicy::Broadcast`], each byte is counted once, regardless of the number of clients.
+	pub fn serial_to_network(&self) -> u64 {
+		self.serial_to_network.load(Ordering::Relaxed)
+	}
+
+	/// Get the number of bytes received from clients and written to the serial port.
+	pub fn network_to_serial(&self) -> u64 {
+		self.network_to_serial.load(Ordering::Relaxed)
+	}
+}
+
+/// Forward data between a serial port and TCP clients.
+///
+/// See the [module documentation](self) for more information.
+#[derive(Debug)]
+pub struct Bridge {
+	port: Arc<SerialPort>,
+	policy: ClientPolicy,
+	counters: Arc<BridgeCounters>,
+	cancel: Cancel,
+}
+
+impl Bridge {
+	/// Create a new bridge for a serial port.
+	///
+	/// The default client policy is [`ClientPolicy::Exclusive`].
+	pub fn new(port: impl Into<Arc<SerialPort>>) -> Self {
+		Self {
+			port: port.into(),
+			policy: ClientPolicy::default(),
+			counters: Arc::default(),
+			cancel: Cancel::never(),
+		}
+	}
+
+	/// Set the policy for multiple clients.
+	pub fn client_policy(mut self, policy: ClientPolicy) -> Self {
+		self.policy = policy;
+		self
+	}
+
+	/// Set a cancellation token to stop the bridge.
+	///
+	/// When the token is cancelled, [`Self::serve()`] stops accepting clients and stops reading from the serial port and the clients.
+	/// Data that was already read is still forwarded completely:
+	/// a chunk read from the serial port is sent to all clients, and a chunk read from a client is written to the serial port.
+	/// When all forwarding has stopped, the client connections are closed and [`Self::serve()`] returns `Ok(())`.
+	#[cfg(any(feature = "doc", feature = "cancel"))]
+	#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "cancel")))]
+	pub fn cancel_token(mut self, token: CancellationToken) -> Self {
+		self.cancel = Cancel::new(Some(token));
+		self
+	}
+
+	/// Get the byte counters of the bridge.
+	///
+	/// The counters are shared, so you can keep the returned value to monitor a running bridge.
+	pub fn counters(&self) -> Arc<BridgeCounters> {
+		self.counters.clone()
+	}
+
+	/// Get the serial port used by the bridge.
+	pub fn port(&self) -> &Arc<SerialPort> {
+		&self.port
+	}
+
+	/// Accept clients from a TCP listener and forward data between them and the serial port.
+	///
+	/// This only returns when accepting a client or reading from the serial port fails,
+	/// when the serial port reports end-of-file (which normally means the device was removed),
+	/// or when the cancellation token of the bridge is cancelled.
+	/// When the returned future is dropped, all client connections are closed.
+	///
+	/// A client is disconnected when reading from it, writing to it, or writing its data to the serial port fails.
+	pub async fn serve(&self, listener: TcpListener) -> std::io::Result<()> {
+		let clients = Arc::new(Clients::new());
+		let _clients_guard = AbortClientsOnDrop(clients.clone());
+		let mut reader = tokio::spawn(forward_serial(self.port.clone(), clients.clone(), self.counters.clone(), self.cancel.clone()));
+		let _reader_guard = AbortOnDrop(reader.abort_handle());
+
+		loop {
+			let accepted = self.cancel.run(std::future::poll_fn(|cx| {
+				if let Poll::Ready(result) = Pin::new(&mut reader).poll(cx) {
+					return Poll::Ready(Err(result));
+				}
+				listener.poll_accept(cx).map(Ok)
+			})).await;
+
+			let stream = match accepted {
+				Some(Ok(accepted)) => accepted?.0,
+				Some(Err(Ok(result))) => return result,
+				Some(Err(Err(e))) => return Err(std::io::Error::other(e)),
+				None => break,
+			};
+
+			if self.policy == ClientPolicy::Exclusive && *clients.count.borrow() > 0 {
+				continue;
+			}
+			self.add_client(&clients, stream).await;
+		}
+
+		// Cancelled: let the forwarding tasks finish the data they already read.
+		let result = (&mut reader).await.map_err(std::io::Error::other)?;
+		clients.count.subscribe().wait_for(|&count| count == 0).await.ok();
+		result
+	}
+
+	async fn add_client(&self, clients: &Arc<Clients>, stream: TcpStream) {
+		let (read, write) = stream.into_split();
+		let mut list = clients.list.lock().await;
+		let id = clients.next_id.fetch_add(1, Ordering::Relaxed);
+		let task = tokio::spawn(forward_client(id, read, self.port.clone(), clients.clone(), self.counters.clone(), self.cancel.clone()));
+		list.push(Client { id, writer: write });
+		clients.tasks.lock().unwrap_or_else(|e| e.into_inner()).push((id, task.abort_handle()));
+		clients.count.send_replace(list.len());
+	}
+}
+
+/// A connected client.
+struct Client {
+	id: u64,
+	writer: OwnedWriteHalf,
+}
+
+/// The clients of a bridge.
+struct Clients {
+	list: Mutex<Vec<Client>>,
+	tasks: std::sync::Mutex<Vec<(u64, AbortHandle)>>,
+	count: watch::Sender<usize>,
+	next_id: AtomicU64,
+}
+
+impl Clients {
+	fn new() -> Self {
+		Self {
+			list: Mutex::new(Vec::new()),
+			tasks: std::sync::Mutex::new(Vec::new()),
+			count: watch::channel(0).0,
+			next_id: AtomicU64::new(0),
+		}
+	}
+
+	async fn remove(&self, id: u64) {
+		let mut list = self.list.lock().await;
+		self.remove_locked(&mut list, &[id]);
+	}
+
+	/// Remove clients while already holding the lock on the client list, and abort their tasks.
+	fn remove_locked(&self, list: &mut Vec<Client>, ids: &[u64]) {
+		list.retain(|client| !ids.contains(&client.id));
+		self.tasks.lock().unwrap_or_else(|e| e.into_inner()).retain(|(id, task)| {
+			if ids.contains(id) {
+				task.abort();
+				false
+			} else {
+				true
+			}
+		});
+		self.count.send_replace(list.len());
+	}
+}
+
+/// Abort all client tasks when dropped.
+struct AbortClientsOnDrop(Arc<Clients>);
+
+impl Drop for AbortClientsOnDrop {
+	fn drop(&mut self) {
+		for (_id, task) in self.0.tasks.lock().unwrap_or_else(|e| e.into_inner()).drain(..) {
+			task.abort();
+		}
+	}
+}
+
+/// Read from the serial port and write the data to all clients.
+///
+/// When the bridge is cancelled, this stops before the next read from the serial port.
+async fn forward_serial(port: Arc<SerialPort>, clients: Arc<Clients>, counters: Arc<BridgeCounters>, cancel: Cancel) -> std::io::Result<()> {
+	let mut count = clients.count.subscribe();
+	let mut buffer = vec![0; 4096];
+	loop {
+		// The sender is owned by `clients`, so this can not fail.
+		if cancel.run(count.wait_for(|&count| count > 0)).await.is_none() {
+			return Ok(());
+		}
+
+		// Reads from a serial port can be cancelled without losing data.
+		let Some(read) = cancel.run(port.read(&mut buffer)).await else {
+			return Ok(());
+		};
+		let read = read?;
+		if read == 0 {
+			return Ok(());
+		}
+		counters.serial_to_network.fetch_add(read as u64, Ordering::Relaxed);
+
+		let mut list = clients.list.lock().await;
+		let failed = write_to_clients(&mut list, &buffer[..read]).await;
+		if !failed.is_empty() {
+			clients.remove_locked(&mut list, &failed);
+		}
+	}
+}
+
+/// Write data to all clients concurrently.
+///
+/// Returns the IDs of the clients for which the write failed.
+async fn write_to_clients(list: &mut [Client], data: &[u8]) -> Vec<u64> {
+	let mut writes: Vec<_> = list
+		.iter_mut()
+		.map(|client| Some(Box::pin(async move {
+			client.writer.write_all(data).await.err().map(|_| client.id)
+		})))
+		.collect();
+	let mut failed = Vec::new();
+
+	std::future::poll_fn(|cx| {
+		let mut pending = false;
+		for write in writes.iter_mut() {
+			let Some(future) = write else {
+				continue;
+			};
+			match future.as_mut().poll(cx) {
+				Poll::Ready(result) => {
+					failed.extend(result);
+					*write = None;
+				},
+				Poll::Pending => pending = true,
+			}
+		}
+		if pending {
+			Poll::Pending
+		} else {
+			Poll::Ready(())
+		}
+	}).await;
+
+	failed
+}
+
+/// Read from a client and write the data to the serial port.
+///
+/// When the bridge is cancelled, this stops before the next read from the client.
+async fn forward_client(id: u64, mut reader: OwnedReadHalf, port: Arc<SerialPort>, clients: Arc<Clients>, counters: Arc<BridgeCounters>, cancel: Cancel) {
+	let mut buffer = vec![0; 4096];
+	loop {
+		let read = match cancel.run(reader.read(&mut buffer)).await {
+			None | Some(Ok(0)) | Some(Err(_)) => break,
+			Some(Ok(read)) => read,
+		};
+		if port.write_all(&buffer[..read]).await.is_err() {
+			break;
+		}
+		counters.network_to_serial.fetch_add(read as u64, Ordering::Relaxed);
+	}
+	clients.remove(id).await;
+}
@@ -0,0 +1,143 @@
+use std::future::Future;
+use std::sync::Arc;
+use std::task::Poll;
+use std::time::Duration;
+
+use crate::SerialPort;
+
+/// Writes the same data to multiple serial ports concurrently.
+///
+/// This is useful to update the firmware of a rack of identical devices, or to drive a chain of displays.
+/// All ports are written at the same time, so the total time is determined by the slowest port instead of the sum of all ports.
+/// The result is reported for each port separately, so one failing port does not affect the others.
+///
+/// A deadline can be set to give up on ports that are too slow, for example because a device is not reading its input and flow control is enabled.
+///
+/// # Example
+/// ```no_run
+/// # async fn example() -> std::io::Result<()> {
+/// use serial2_tokio::{Broadcast, SerialPort};
+/// use std::time::Duration;
+///
+/// let mut broadcast = Broadcast::new();
+/// for path in ["/dev/ttyUSB0", "/dev/ttyUSB1", "/dev/ttyUSB2"] {
+///     broadcast.add(SerialPort::open(path, 115200)?);
+/// }
+/// broadcast.set_deadline(Some(Duration::from_secs(2)));
+/// for (port, result) in broadcast.ports().iter().zip(broadcast.write_all(b"RESET\r\n").await) {
+///     if let Err(e) = result {
+///         eprintln!("failed to write to {port:?}: {e}");
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default)]
+pub struct Broadcast {
+	ports: Vec<Arc<SerialPort>>,
+	deadline: Option<Duration>,
+}
+
+impl Broadcast {
+	/// Create a new broadcast writer without any serial ports.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Add a serial port.
+	///
+	/// Returns the index of the port, which is also the index of its result in [`Self::write_all()`].
+	pub fn add(&mut self, port: impl Into<Arc<SerialPort>>) -> usize {
+		self.ports.push(port.into());
+		self.ports.len() - 1
+	}
+
+	/// Remove a serial port by index.
+	///
+	/// The ports after it move down by one index.
+	///
+	/// # Panics
+	/// This function panics if the index is out of bounds.
+	pub fn remove(&mut self, index: usize) -> Arc<SerialPort> {
+		self.ports.remove(index)
+	}
+
+	/// Get the serial ports, in the order they were added.
+	pub fn ports(&self) -> &[Arc<SerialPort>] {
+		&self.ports
+	}
+
+	/// Get the number of serial ports.
+	pub fn len(&self) -> usize {
+		self.ports.len()
+	}
+
+	/// Check if there are no serial ports.
+	pub fn is_empty(&self) -> bool {
+		self.ports.is_empty()
+	}
+
+	/// Set the maximum time to write the data to each serial port.
+	///
+	/// When the deadline passes, the write to the ports that are not done yet is aborted,
+	/// and their result is an error of kind [`std::io::ErrorKind::TimedOut`].
+	/// Part of the data may have been written to those ports already.
+	///
+	/// Pass `None` to wait for all ports without a deadline, which is the default.
+	pub fn set_deadline(&mut self, deadline: Option<Duration>) {
+		self.deadline = deadline;
+	}
+
+	/// Get the maximum time to write the data to each serial port.
+	pub fn get_deadline(&self) -> Option<Duration> {
+		self.deadline
+	}
+
+	/// Write all data to all serial ports concurrently.
+	///
+	/// Returns the result for each serial port, in the same order as [`Self::ports()`].
+	/// A result is `Ok` when all data has been handed to the OS,
+	/// which does not mean that it has been transmitted yet (see [`SerialPort::drain()`]).
+	///
+	/// If the returned future is dropped before it completes, the writes to all ports are aborted.
+	pub async fn write_all(&self, data: &[u8]) -> Vec<std::io::Result<()>> {
+		let deadline = self.deadline.map(|deadline| tokio::time::Instant::now() + deadline);
+		let mut writes: Vec<_> = self.ports
+			.iter()
+			.map(|port| Some(Box::pin(write_all_until(port, data, deadline))))
+			.collect();
+		let mut results: Vec<Option<std::io::Result<()>>> = writes.iter().map(|_| None).collect();
+
+		std::future::poll_fn(|cx| {
+			let mut pending = false;
+			for (write, result) in writes.iter_mut().zip(results.iter_mut()) {
+				let Some(future) = write else {
+					continue;
+				};
+				match future.as_mut().poll(cx) {
+					Poll::Ready(value) => {
+						*result = Some(value);
+						*write = None;
+					},
+					Poll::Pending => pending = true,
+				}
+			}
+			if pending {
+				Poll::Pending
+			} else {
+				Poll::Ready(())
+			}
+		}).await;
+
+		// All futures completed, so all results are filled in.
+		results.into_iter().map(|result| result.unwrap()).collect()
+	}
+}
+
+/// Write all data to a serial port, and give up at the deadline if there is one.
+async fn write_all_until(port: &SerialPort, data: &[u8], deadline: Option<tokio::time::Instant>) -> std::io::Result<()> {
+	match deadline {
+		Some(deadline) => port.write_all_until(data, deadline).await,
+		None => port.write_all(data).await,
+	}
+}
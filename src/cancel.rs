@@ -0,0 +1,92 @@
+//! Support for cancelling long running operations with a [`CancellationToken`](tokio_util::sync::CancellationToken).
+//!
+//! Without the `cancel` feature, an operation can never be cancelled and all of this compiles down to nothing.
+
+use std::future::Future;
+use std::task::Poll;
+
+#[cfg(any(feature = "doc", feature = "cancel"))]
+use tokio_util::sync::CancellationToken;
+
+/// An optional cancellation token.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Cancel {
+	#[cfg(any(feature = "doc", feature = "cancel"))]
+	token: Option<CancellationToken>,
+}
+
+impl Cancel {
+	/// Create a cancellation signal that never fires.
+	pub fn never() -> Self {
+		Self::default()
+	}
+
+	/// Create a cancellation signal from an optional token.
+	#[cfg(any(feature = "doc", feature = "cancel"))]
+	pub fn new(token: Option<CancellationToken>) -> Self {
+		Self { token }
+	}
+
+	/// Get the cancellation token, if there is one.
+	#[cfg(any(feature = "doc", feature = "cancel"))]
+	pub fn token(&self) -> Option<&CancellationToken> {
+		self.token.as_ref()
+	}
+
+	/// Check if the operation has been cancelled.
+	pub fn is_cancelled(&self) -> bool {
+		#[cfg(any(feature = "doc", feature = "cancel"))] {
+			self.token.as_ref().is_some_and(|token| token.is_cancelled())
+		}
+		#[cfg(not(any(feature = "doc", feature = "cancel")))] {
+			false
+		}
+	}
+
+	/// Wait until the operation is cancelled.
+	///
+	/// Without a cancellation token, this never completes.
+	pub async fn cancelled(&self) {
+		#[cfg(any(feature = "doc", feature = "cancel"))]
+		if let Some(token) = &self.token {
+			return token.cancelled().await;
+		}
+		std::future::pending().await
+	}
+
+	/// Run a future until it completes or the operation is cancelled.
+	///
+	/// Returns `None` if the operation was cancelled first, in which case the future is dropped.
+	pub async fn run<F: Future>(&self, future: F) -> Option<F::Output> {
+		let mut future = std::pin::pin!(future);
+		let mut cancelled = std::pin::pin!(self.cancelled());
+		std::future::poll_fn(|cx| {
+			if cancelled.as_mut().poll(cx).is_ready() {
+				return Poll::Ready(None);
+			}
+			future.as_mut().poll(cx).map(Some)
+		}).await
+	}
+}
+
+/// The payload of the error returned by cancelled operations.
+#[derive(Debug)]
+struct CancelledError;
+
+impl std::fmt::Display for CancelledError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str("operation cancelled")
+	}
+}
+
+impl std::error::Error for CancelledError {}
+
+/// Create the error returned by cancelled operations.
+pub(crate) fn cancelled_error() -> std::io::Error {
+	std::io::Error::other(CancelledError)
+}
+
+/// Check if an error was returned by a cancelled operation.
+pub(crate) fn is_cancelled(error: &std::io::Error) -> bool {
+	error.get_ref().is_some_and(|error| error.is::<CancelledError>())
+}
@@ -0,0 +1,36 @@
+use std::time::Duration;
+
+use crate::SerialPort;
+
+/// How often the CD line is checked while waiting for carrier.
+const CARRIER_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+impl SerialPort {
+	/// Wait until the CD (carrier detect) line is asserted.
+	///
+	/// Modems and radio modems assert the CD line when a connection to the remote side is established.
+	/// This function returns immediately if the CD line is already asserted.
+	/// Otherwise, the line is polled every 50 milliseconds.
+	///
+	/// Use [`tokio::time::timeout()`] to limit the time spent waiting.
+	/// See also [`OpenOptions::wait_for_carrier()`][crate::OpenOptions::wait_for_carrier()] to wait for carrier when opening the serial port.
+	///
+	/// # Example
+	/// ```no_run
+	/// # async fn example() -> std::io::Result<()> {
+	/// use serial2_tokio::SerialPort;
+	/// use std::time::Duration;
+	///
+	/// let port = SerialPort::open("/dev/ttyS0", 9600)?;
+	/// tokio::time::timeout(Duration::from_secs(60), port.wait_for_carrier()).await??;
+	/// port.write_all(b"hello\r\n").await?;
+	/// # Ok(())
+	/// # }
+	/// ```
+	pub async fn wait_for_carrier(&self) -> std::io::Result<()> {
+		while !self.read_cd()? {
+			tokio::time::sleep(CARRIER_POLL_INTERVAL).await;
+		}
+		Ok(())
+	}
+}
@@ -0,0 +1,289 @@
+//! Checksums that are commonly used on serial links.
+//!
+//! This module implements the CRC variants and simple checksums found in many serial protocols:
+//!
+//! * [`crc8_maxim()`]: CRC-8/MAXIM, used by 1-Wire devices and many sensors.
+//! * [`crc16_modbus()`]: CRC-16/MODBUS, used by Modbus RTU.
+//! * [`crc16_ccitt_false()`]: CRC-16/CCITT-FALSE, used by many custom binary protocols.
+//! * [`crc16_mcrf4xx()`]: CRC-16/MCRF4XX, used by MAVLink.
+//! * [`crc16_umts()`]: CRC-16/UMTS, used by version 2.0 of the Dynamixel protocol.
+//! * [`xor()`]: the XOR of all bytes, used by NMEA 0183 sentences.
+//! * [`lrc()`]: the longitudinal redundancy check used by Modbus ASCII.
+//! * [`fletcher8()`]: the 8 bit Fletcher checksum used by the UBX protocol of u-blox GNSS receivers.
+//!
+//! The CRC functions also have an `update` variant to compute the checksum over data that is not available in one piece.
+//!
+//! # Example
+//! ```
+//! use serial2_tokio::checksum;
+//!
+//! let frame = [0x01, 0x03, 0x00, 0x00, 0x00, 0x0A];
+//! let crc = checksum::crc16_modbus(&frame);
+//!
+//! // Modbus RTU sends the CRC with the low byte first.
+//! let mut message = frame.to_vec();
+//! message.extend_from_slice(&crc.to_le_bytes());
+//! assert_eq!(message, [0x01, 0x03, 0x00, 0x00, 0x00, 0x0A, 0xC5, 0xCD]);
+//! ```
+
+/// The initial value for [`crc8_maxim_update()`].
+pub const CRC8_MAXIM_INIT: u8 = 0x00;
+
+/// The initial value for [`crc16_modbus_update()`].
+pub const CRC16_MODBUS_INIT: u16 = 0xFFFF;
+
+/// The initial value for [`crc16_ccitt_false_update()`].
+pub const CRC16_CCITT_FALSE_INIT: u16 = 0xFFFF;
+
+/// The initial value for [`crc16_mcrf4xx_update()`].
+pub const CRC16_MCRF4XX_INIT: u16 = 0xFFFF;
+
+/// The initial value for [`crc16_umts_update()`].
+pub const CRC16_UMTS_INIT: u16 = 0x0000;
+
+/// Lookup table for CRC-8/MAXIM: reflected polynomial 0x31.
+const CRC8_MAXIM_TABLE: [u8; 256] = crc8_reflected_table(0x8C);
+
+/// Lookup table for CRC-16/MODBUS: reflected polynomial 0x8005.
+const CRC16_MODBUS_TABLE: [u16; 256] = crc16_reflected_table(0xA001);
+
+/// Lookup table for CRC-16/CCITT-FALSE: polynomial 0x1021.
+const CRC16_CCITT_TABLE: [u16; 256] = crc16_table(0x1021);
+
+/// Lookup table for CRC-16/MCRF4XX: reflected polynomial 0x1021.
+const CRC16_MCRF4XX_TABLE: [u16; 256] = crc16_reflected_table(0x8408);
+
+/// Lookup table for CRC-16/UMTS: polynomial 0x8005.
+const CRC16_UMTS_TABLE: [u16; 256] = crc16_table(0x8005);
+
+/// Compute the CRC-8/MAXIM checksum of the data.
+///
+/// This is also known as CRC-8/DALLAS or the 1-Wire CRC.
+/// It uses the polynomial `0x31`, reflected input and output, an initial value of `0x00` and no final XOR.
+pub fn crc8_maxim(data: &[u8]) -> u8 {
+	crc8_maxim_update(CRC8_MAXIM_INIT, data)
+}
+
+/// Update a CRC-8/MAXIM checksum with more data.
+///
+/// Start with [`CRC8_MAXIM_INIT`] and pass the result of each call to the next.
+pub fn crc8_maxim_update(crc: u8, data: &[u8]) -> u8 {
+	data.iter().fold(crc, |crc, &byte| CRC8_MAXIM_TABLE[usize::from(crc ^ byte)])
+}
+
+/// Compute the CRC-16/MODBUS checksum of the data.
+///
+/// It uses the polynomial `0x8005`, reflected input and output, an initial value of `0xFFFF` and no final XOR.
+/// Modbus RTU transmits the checksum in little endian byte order.
+pub fn crc16_modbus(data: &[u8]) -> u16 {
+	crc16_modbus_update(CRC16_MODBUS_INIT, data)
+}
+
+/// Update a CRC-16/MODBUS checksum with more data.
+///
+/// Start with [`CRC16_MODBUS_INIT`] and pass the result of each call to the next.
+pub fn crc16_modbus_update(crc: u16, data: &[u8]) -> u16 {
+	data.iter().fold(crc, |crc, &byte| {
+		(crc >> 8) ^ CRC16_MODBUS_TABLE[usize::from(crc as u8 ^ byte)]
+	})
+}
+
+/// Compute the CRC-16/CCITT-FALSE checksum of the data.
+///
+/// This is also known as CRC-16/IBM-3740 or CRC-16/AUTOSAR.
+/// It uses the polynomial `0x1021`, no reflection, an initial value of `0xFFFF` and no final XOR.
+/// It is normally transmitted in big endian byte order.
+pub fn crc16_ccitt_false(data: &[u8]) -> u16 {
+	crc16_ccitt_false_update(CRC16_CCITT_FALSE_INIT, data)
+}
+
+/// Update a CRC-16/CCITT-FALSE checksum with more data.
+///
+/// Start with [`CRC16_CCITT_FALSE_INIT`] and pass the result of each call to the next.
+pub fn crc16_ccitt_false_update(crc: u16, data: &[u8]) -> u16 {
+	data.iter().fold(crc, |crc, &byte| {
+		(crc << 8) ^ CRC16_CCITT_TABLE[usize::from((crc >> 8) as u8 ^ byte)]
+	})
+}
+
+/// Compute the CRC-16/MCRF4XX checksum of the data.
+///
+/// This is the checksum used by MAVLink, also known as the X.25 CRC without final XOR.
+/// It uses the polynomial `0x1021`, reflected input and output, an initial value of `0xFFFF` and no final XOR.
+/// MAVLink transmits the checksum in little endian byte order.
+pub fn crc16_mcrf4xx(data: &[u8]) -> u16 {
+	crc16_mcrf4xx_update(CRC16_MCRF4XX_INIT, data)
+}
+
+/// Update a CRC-16/MCRF4XX checksum with more data.
+///
+/// Start with [`CRC16_MCRF4XX_INIT`] and pass the result of each call to the next.
+pub fn crc16_mcrf4xx_update(crc: u16, data: &[u8]) -> u16 {
+	data.iter().fold(crc, |crc, &byte| {
+		(crc >> 8) ^ CRC16_MCRF4XX_TABLE[usize::from(crc as u8 ^ byte)]
+	})
+}
+
+/// Compute the CRC-16/UMTS checksum of the data.
+///
+/// This is also known as CRC-16/BUYPASS and is used by version 2.0 of the Dynamixel protocol.
+/// It uses the polynomial `0x8005`, no reflection, an initial value of `0x0000` and no final XOR.
+/// Dynamixel transmits the checksum in little endian byte order.
+pub fn crc16_umts(data: &[u8]) -> u16 {
+	crc16_umts_update(CRC16_UMTS_INIT, data)
+}
+
+/// Update a CRC-16/UMTS checksum with more data.
+///
+/// Start with [`CRC16_UMTS_INIT`] and pass the result of each call to the next.
+pub fn crc16_umts_update(crc: u16, data: &[u8]) -> u16 {
+	data.iter().fold(crc, |crc, &byte| {
+		(crc << 8) ^ CRC16_UMTS_TABLE[usize::from((crc >> 8) as u8 ^ byte)]
+	})
+}
+
+/// Compute the XOR of all bytes.
+///
+/// For NMEA 0183 sentences, the checksum is computed over the characters between the `$` and the `*`,
+/// and transmitted as two uppercase hexadecimal digits.
+pub fn xor(data: &[u8]) -> u8 {
+	data.iter().fold(0, |checksum, byte| checksum ^ byte)
+}
+
+/// Compute the longitudinal redundancy check of the data, as used by Modbus ASCII.
+///
+/// The LRC is the two's complement of the sum of all bytes, ignoring overflow.
+/// For Modbus ASCII, it is computed over the binary message (before encoding it as hexadecimal characters),
+/// without the leading `:` and the trailing CR LF.
+pub fn lrc(data: &[u8]) -> u8 {
+	data.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte)).wrapping_neg()
+}
+
+/// Compute the 8 bit Fletcher checksum of the data, as used by the UBX protocol of u-blox GNSS receivers.
+///
+/// The two running sums are computed modulo 256, and returned in the order in which they are transmitted: `[CK_A, CK_B]`.
+/// For UBX messages, the checksum is computed over the class, the ID, the length and the payload.
+pub fn fletcher8(data: &[u8]) -> [u8; 2] {
+	data.iter().fold([0u8, 0u8], |[a, b], &byte| {
+		let a = a.wrapping_add(byte);
+		[a, b.wrapping_add(a)]
+	})
+}
+
+/// Generate the lookup table for a reflected 8 bit CRC.
+const fn crc8_reflected_table(polynomial: u8) -> [u8; 256] {
+	let mut table = [0; 256];
+	let mut i = 0;
+	while i < 256 {
+		let mut crc = i as u8;
+		let mut bit = 0;
+		while bit < 8 {
+			crc = if crc & 1 != 0 { (crc >> 1) ^ polynomial } else { crc >> 1 };
+			bit += 1;
+		}
+		table[i] = crc;
+		i += 1;
+	}
+	table
+}
+
+/// Generate the lookup table for a reflected 16 bit CRC.
+const fn crc16_reflected_table(polynomial: u16) -> [u16; 256] {
+	let mut table = [0; 256];
+	let mut i = 0;
+	while i < 256 {
+		let mut crc = i as u16;
+		let mut bit = 0;
+		while bit < 8 {
+			crc = if crc & 1 != 0 { (crc >> 1) ^ polynomial } else { crc >> 1 };
+			bit += 1;
+		}
+		table[i] = crc;
+		i += 1;
+	}
+	table
+}
+
+/// Generate the lookup table for a non-reflected 16 bit CRC.
+const fn crc16_table(polynomial: u16) -> [u16; 256] {
+	let mut table = [0; 256];
+	let mut i = 0;
+	while i < 256 {
+		let mut crc = (i as u16) << 8;
+		let mut bit = 0;
+		while bit < 8 {
+			crc = if crc & 0x8000 != 0 { (crc << 1) ^ polynomial } else { crc << 1 };
+			bit += 1;
+		}
+		table[i] = crc;
+		i += 1;
+	}
+	table
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	/// The input for the standard CRC check values.
+	const CHECK_INPUT: &[u8] = b"123456789";
+
+	#[test]
+	fn crc_check_values() {
+		assert_eq!(crc8_maxim(CHECK_INPUT), 0xA1);
+		assert_eq!(crc16_modbus(CHECK_INPUT), 0x4B37);
+		assert_eq!(crc16_ccitt_false(CHECK_INPUT), 0x29B1);
+		assert_eq!(crc16_mcrf4xx(CHECK_INPUT), 0x6F91);
+		assert_eq!(crc16_umts(CHECK_INPUT), 0xFEE8);
+	}
+
+	#[test]
+	fn crc_update_in_pieces() {
+		let (head, tail) = CHECK_INPUT.split_at(4);
+		assert_eq!(crc8_maxim_update(crc8_maxim_update(CRC8_MAXIM_INIT, head), tail), crc8_maxim(CHECK_INPUT));
+		assert_eq!(crc16_modbus_update(crc16_modbus_update(CRC16_MODBUS_INIT, head), tail), crc16_modbus(CHECK_INPUT));
+		assert_eq!(crc16_ccitt_false_update(crc16_ccitt_false_update(CRC16_CCITT_FALSE_INIT, head), tail), crc16_ccitt_false(CHECK_INPUT));
+		assert_eq!(crc16_mcrf4xx_update(crc16_mcrf4xx_update(CRC16_MCRF4XX_INIT, head), tail), crc16_mcrf4xx(CHECK_INPUT));
+		assert_eq!(crc16_umts_update(crc16_umts_update(CRC16_UMTS_INIT, head), tail), crc16_umts(CHECK_INPUT));
+	}
+
+	#[test]
+	fn crc16_modbus_spec_example() {
+		// Read three holding registers starting at 0x006B from slave 0x11, from the Modbus specification.
+		assert_eq!(crc16_modbus(&[0x11, 0x03, 0x00, 0x6B, 0x00, 0x03]).to_le_bytes(), [0x76, 0x87]);
+	}
+
+	#[test]
+	fn crc_of_message_with_crc_is_zero() {
+		let mut message = CHECK_INPUT.to_vec();
+		message.extend_from_slice(&crc16_modbus(CHECK_INPUT).to_le_bytes());
+		assert_eq!(crc16_modbus(&message), 0);
+	}
+
+	#[test]
+	fn simple_checksums() {
+		assert_eq!(xor(CHECK_INPUT), 0x31);
+		assert_eq!(lrc(CHECK_INPUT), 0x23);
+		assert_eq!(fletcher8(CHECK_INPUT), [0xDD, 0x15]);
+		assert_eq!(xor(&[]), 0);
+		assert_eq!(lrc(&[]), 0);
+	}
+
+	#[test]
+	fn nmea_checksum() {
+		assert_eq!(xor(b"GPGLL,5057.970,N,00146.110,E,142451,A"), 0x27);
+	}
+
+	#[test]
+	fn ubx_checksum() {
+		// Poll the navigation rate: UBX-CFG-RATE without payload.
+		assert_eq!(fletcher8(&[0x06, 0x08, 0x00, 0x00]), [0x0E, 0x30]);
+	}
+
+	#[test]
+	fn lrc_makes_sum_zero() {
+		let data = [0x11, 0x03, 0x00, 0x6B, 0x00, 0x03];
+		let sum = data.iter().fold(lrc(&data), |sum, &byte| sum.wrapping_add(byte));
+		assert_eq!(sum, 0);
+	}
+}
@@ -0,0 +1,311 @@
+//! Frames of the basic option of 3GPP TS 27.010.
+
+/// The flag that starts and ends each frame.
+pub const FLAG: u8 = 0xF9;
+
+/// The extension bit of the address and length fields.
+const EA: u8 = 0x01;
+
+/// The command/response bit of the address field and of control messages.
+pub const CR: u8 = 0x02;
+
+/// The poll/final bit of the control field.
+const PF: u8 = 0x10;
+
+/// Frame types, without the poll/final bit.
+pub mod kind {
+	/// Set asynchronous balanced mode: open a channel.
+	pub const SABM: u8 = 0x2F;
+
+	/// Unnumbered acknowledgement.
+	pub const UA: u8 = 0x63;
+
+	/// Disconnected mode: the channel is not open.
+	pub const DM: u8 = 0x0F;
+
+	/// Disconnect: close a channel.
+	pub const DISC: u8 = 0x43;
+
+	/// Unnumbered information with header check: user data and control messages.
+	pub const UIH: u8 = 0xEF;
+}
+
+/// Types of control messages on DLCI 0, without the extension and command/response bits.
+pub mod message {
+	/// Non supported command response.
+	pub const NSC: u8 = 0x10;
+
+	/// Test command.
+	pub const TEST: u8 = 0x20;
+
+	/// Multiplexer close down.
+	pub const CLD: u8 = 0xC0;
+
+	/// Modem status command.
+	pub const MSC: u8 = 0xE0;
+}
+
+/// A decoded frame.
+#[derive(Debug, Clone)]
+pub struct Frame {
+	pub dlci: u8,
+	pub kind: u8,
+	pub info: Vec<u8>,
+}
+
+/// Encode a frame and append it to `output`.
+///
+/// The poll bit is set for all frames except UIH frames.
+pub fn encode(dlci: u8, command: bool, kind: u8, info: &[u8], output: &mut Vec<u8>) {
+	let address = EA | if command { CR } else { 0 } | dlci << 2;
+	let control = if kind == kind::UIH { kind } else { kind | PF };
+	let start = output.len();
+	output.push(FLAG);
+	output.push(address);
+	output.push(control);
+	if info.len() < 0x80 {
+		output.push((info.len() as u8) << 1 | EA);
+	} else {
+		output.push((info.len() as u8) << 1);
+		output.push((info.len() >> 7) as u8);
+	}
+	let fcs = 0xFF - crc(&output[start + 1..]);
+	output.extend_from_slice(info);
+	output.push(fcs);
+	output.push(FLAG);
+}
+
+/// Encode a control message for DLCI 0 and append it to `output`.
+pub fn encode_message(message: u8, command: bool, value: &[u8], output: &mut Vec<u8>) {
+	let mut info = Vec::with_capacity(value.len() + 2);
+	info.push(message | EA | if command { CR } else { 0 });
+	info.push((value.len() as u8) << 1 | EA);
+	info.extend_from_slice(value);
+	encode(0, true, kind::UIH, &info, output);
+}
+
+/// Parse a control message from the information field of a frame on DLCI 0.
+///
+/// Returns the message type (including the command/response bit) and the value.
+/// Messages with a value longer than 127 bytes are not used by the basic option and are rejected.
+pub fn parse_message(info: &[u8]) -> Option<(u8, &[u8])> {
+	let (&message, rest) = info.split_first()?;
+	let (&length, value) = rest.split_first()?;
+	if length & EA == 0 {
+		return None;
+	}
+	value.get(..usize::from(length >> 1)).map(|value| (message & !EA, value))
+}
+
+/// Parses frames from a stream of bytes.
+#[derive(Debug)]
+pub struct Parser {
+	buffer: Vec<u8>,
+	max_frame_size: usize,
+}
+
+impl Parser {
+	pub fn new(max_frame_size: usize) -> Self {
+		Self {
+			buffer: Vec::new(),
+			max_frame_size,
+		}
+	}
+
+	/// Add received data to the parser.
+	pub fn extend(&mut self, data: &[u8]) {
+		self.buffer.extend_from_slice(data);
+	}
+
+	/// Get the next complete frame from the received data.
+	///
+	/// Data outside of frames and frames with an invalid check sequence are skipped.
+	pub fn next_frame(&mut self) -> Option<Frame> {
+		loop {
+			// Skip everything before the last flag of a run of flags.
+			let Some(start) = self.buffer.iter().position(|&byte| byte != FLAG) else {
+				let flags = self.buffer.len().saturating_sub(1);
+				self.buffer.drain(..flags);
+				return None;
+			};
+			if start == 0 {
+				self.skip_to_flag(1);
+				continue;
+			}
+			self.buffer.drain(..start - 1);
+
+			let (header_len, info_len) = match self.buffer.get(1..5) {
+				Some(&[_, _, length, _]) if length & EA != 0 => (3, usize::from(length >> 1)),
+				Some(&[_, _, low, high]) => (4, usize::from(low >> 1) | usize::from(high) << 7),
+				_ => return None,
+			};
+			if info_len > self.max_frame_size {
+				self.skip_to_flag(1);
+				continue;
+			}
+			let frame_len = 1 + header_len + info_len + 2;
+			if self.buffer.len() < frame_len {
+				return None;
+			}
+
+			let header = &self.buffer[1..1 + header_len];
+			let fcs = self.buffer[frame_len - 2];
+			if self.buffer[frame_len - 1] != FLAG || 0xFF - crc(header) != fcs {
+				self.skip_to_flag(1);
+				continue;
+			}
+
+			let frame = Frame {
+				dlci: header[0] >> 2,
+				kind: header[1] & !PF,
+				info: self.buffer[1 + header_len..frame_len - 2].to_vec(),
+			};
+			// Keep the closing flag, since it may also be the opening flag of the next frame.
+			self.buffer.drain(..frame_len - 1);
+			return Some(frame);
+		}
+	}
+
+	/// Discard data up to the next flag after `offset`.
+	fn skip_to_flag(&mut self, offset: usize) {
+		match self.buffer.iter().skip(offset).position(|&byte| byte == FLAG) {
+			Some(position) => self.buffer.drain(..offset + position),
+			None => self.buffer.drain(..),
+		};
+	}
+}
+
+/// Compute the reflected CRC-8 that is used for the frame check sequence.
+fn crc(data: &[u8]) -> u8 {
+	let mut crc = 0xFF;
+	for &byte in data {
+		crc ^= byte;
+		for _ in 0..8 {
+			crc = if crc & 1 != 0 { (crc >> 1) ^ 0xE0 } else { crc >> 1 };
+		}
+	}
+	crc
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	/// SABM on DLCI 0, as sent by the initiator to start the multiplexer.
+	const SABM_DLCI_0: [u8; 6] = [0xF9, 0x03, 0x3F, 0x01, 0x1C, 0xF9];
+
+	/// UA on DLCI 0, as sent by the modem to accept the multiplexer.
+	const UA_DLCI_0: [u8; 6] = [0xF9, 0x03, 0x73, 0x01, 0xD7, 0xF9];
+
+	/// The multiplexer close down command.
+	const CLD: [u8; 8] = [0xF9, 0x03, 0xEF, 0x05, 0xC3, 0x01, 0xF2, 0xF9];
+
+	/// UIH with "OK\r\n" on DLCI 1.
+	const UIH_OK_DLCI_1: [u8; 10] = [0xF9, 0x07, 0xEF, 0x09, 0x4F, 0x4B, 0x0D, 0x0A, 0x39, 0xF9];
+
+	#[test]
+	fn encode_known_frames() {
+		let mut output = Vec::new();
+		encode(0, true, kind::SABM, &[], &mut output);
+		assert_eq!(output, SABM_DLCI_0);
+
+		output.clear();
+		encode_message(message::CLD, true, &[], &mut output);
+		assert_eq!(output, CLD);
+
+		output.clear();
+		encode(1, true, kind::UIH, b"OK\r\n", &mut output);
+		assert_eq!(output, UIH_OK_DLCI_1);
+	}
+
+	#[test]
+	fn encode_long_frame_uses_two_length_bytes() {
+		let info = vec![0x55; 200];
+		let mut output = Vec::new();
+		encode(2, true, kind::UIH, &info, &mut output);
+		assert_eq!(output[3..5], [0x90, 0x01]);
+		assert_eq!(output.len(), 1 + 4 + 200 + 2);
+
+		let mut parser = Parser::new(256);
+		parser.extend(&output);
+		let frame = parser.next_frame().unwrap();
+		assert_eq!(frame.dlci, 2);
+		assert_eq!(frame.info, info);
+	}
+
+	#[test]
+	fn parse_known_frames() {
+		let mut parser = Parser::new(128);
+		parser.extend(&UA_DLCI_0);
+		let frame = parser.next_frame().unwrap();
+		assert_eq!(frame.dlci, 0);
+		assert_eq!(frame.kind, kind::UA);
+		assert!(frame.info.is_empty());
+
+		parser.extend(&UIH_OK_DLCI_1);
+		let frame = parser.next_frame().unwrap();
+		assert_eq!(frame.dlci, 1);
+		assert_eq!(frame.kind, kind::UIH);
+		assert_eq!(frame.info, b"OK\r\n");
+		assert!(parser.next_frame().is_none());
+	}
+
+	#[test]
+	fn parse_frames_sharing_a_flag() {
+		// Consecutive frames may share the flag between them.
+		let mut parser = Parser::new(128);
+		parser.extend(&UA_DLCI_0[..5]);
+		parser.extend(&UIH_OK_DLCI_1);
+		assert_eq!(parser.next_frame().unwrap().kind, kind::UA);
+		assert_eq!(parser.next_frame().unwrap().info, b"OK\r\n");
+		assert!(parser.next_frame().is_none());
+	}
+
+	#[test]
+	fn parse_frame_in_pieces() {
+		let mut parser = Parser::new(128);
+		for &byte in &UIH_OK_DLCI_1 {
+			assert!(parser.next_frame().is_none());
+			parser.extend(&[byte]);
+		}
+		assert_eq!(parser.next_frame().unwrap().info, b"OK\r\n");
+	}
+
+	#[test]
+	fn parse_skips_garbage_and_bad_fcs() {
+		let mut parser = Parser::new(128);
+		let mut corrupted = UIH_OK_DLCI_1;
+		corrupted[8] ^= 0x01;
+		parser.extend(b"AT+CMUX=0\r\r\nOK\r\n");
+		parser.extend(&corrupted);
+		parser.extend(&UA_DLCI_0);
+		let frame = parser.next_frame().unwrap();
+		assert_eq!(frame.kind, kind::UA);
+		assert!(parser.next_frame().is_none());
+	}
+
+	#[test]
+	fn parse_skips_oversized_frames() {
+		let mut output = Vec::new();
+		encode(1, true, kind::UIH, &[0; 64], &mut output);
+		output.extend_from_slice(&UA_DLCI_0);
+		let mut parser = Parser::new(32);
+		parser.extend(&output);
+		assert_eq!(parser.next_frame().unwrap().kind, kind::UA);
+	}
+
+	#[test]
+	fn parse_control_messages() {
+		let mut parser = Parser::new(128);
+		parser.extend(&CLD);
+		let frame = parser.next_frame().unwrap();
+		assert_eq!(frame.dlci, 0);
+		assert_eq!(parse_message(&frame.info), Some((message::CLD | CR, &[][..])));
+
+		// Modem status command for DLCI 1.
+		assert_eq!(parse_message(&[0xE3, 0x05, 0x07, 0x8D]), Some((message::MSC | CR, &[0x07, 0x8D][..])));
+		assert_eq!(parse_message(&[0xE3, 0x05, 0x07]), None);
+		assert_eq!(parse_message(&[0xE3, 0x04, 0x07, 0x8D]), None);
+		assert_eq!(parse_message(&[0xE3]), None);
+	}
+}
@@ -0,0 +1,562 @@
+//! Multiple logical channels over one serial port with the 3GPP TS 27.010 multiplexer protocol.
+//!
+//! Cellular modems usually have a single serial port, but an application often needs more than one connection to the modem:
+//! for example a PPP data connection, and an AT command channel to monitor the signal quality and receive SMS messages at the same time.
+//! The multiplexer protocol of 3GPP TS 27.010, also known as CMUX, splits the serial port into up to 63 independent channels.
+//!
+//! The [`Cmux`] implements the basic option of the protocol as the initiator.
+//! Each channel that is opened with [`Cmux::open_channel()`] is a [`CmuxChannel`],
+//! which implements [`AsyncRead`] and [`AsyncWrite`] like a [`SerialPort`],
+//! so it can be used with an [`AtPort`][crate::at::AtPort] or a codec.
+//!
+//! The modem must be switched to multiplexer mode before starting the multiplexer, usually with the `AT+CMUX=0` command.
+//! Which channels are available and what they are used for depends on the modem:
+//! most modems accept AT commands on all channels.
+//!
+//! Data is received by a background task that distributes it over the channels.
+//! Received data is kept in memory until it is read from the channel, so the data of one channel never blocks another channel.
+//! Flow control requests of the modem are not supported:
+//! use hardware flow control on the serial port if the modem can not keep up with the data.
+//!
+//! This module is only available when the `cmux` feature is enabled.
+//!
+//! # Example
+//! ```no_run
+//! # async fn example() -> std::io::Result<()> {
+//! use serial2_tokio::SerialPort;
+//! use serial2_tokio::cmux::{Cmux, CmuxConfig};
+//! use tokio::io::{AsyncReadExt, AsyncWriteExt};
+//!
+//! let port = SerialPort::open("/dev/ttyUSB2", 115200)?;
+//! port.write_all(b"AT+CMUX=0\r").await?;
+//! let mut buffer = [0; 256];
+//! port.read(&mut buffer).await?;
+//!
+//! let cmux = Cmux::start(port, CmuxConfig::new()).await?;
+//! let mut control = cmux.open_channel(1).await?;
+//! let data = cmux.open_channel(2).await?;
+//! // Use the data channel for PPP, while monitoring the modem on the control channel.
+//! control.write_all(b"AT+CSQ\r").await?;
+//! let read = control.read(&mut buffer).await?;
+//! println!("{}", String::from_utf8_lossy(&buffer[..read]));
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::sync::{mpsc, oneshot, Mutex};
+
+use crate::task::AbortOnDrop;
+use crate::SerialPort;
+
+mod frame;
+
+use frame::{kind, message, Frame};
+
+/// The modem status signals that are sent when a channel is opened: RTC, RTR and DV are set.
+const MODEM_STATUS: u8 = 0x8D;
+
+/// Configuration for a [`Cmux`].
+#[derive(Debug, Clone)]
+pub struct CmuxConfig {
+	max_frame_size: usize,
+	response_timeout: Duration,
+}
+
+impl Default for CmuxConfig {
+	fn default() -> Self {
+		Self {
+			max_frame_size: 31,
+			response_timeout: Duration::from_secs(1),
+		}
+	}
+}
+
+impl CmuxConfig {
+	/// Create a new configuration with the default values.
+	///
+	/// The default configuration uses the default maximum frame size of the protocol (31 bytes),
+	/// and a response timeout of 1 second.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Set the maximum number of data bytes in a frame.
+	///
+	/// This must match the value that was passed to the modem in the `AT+CMUX` command.
+	/// Received frames that are larger are discarded.
+	///
+	/// # Panics
+	/// This function panics if the size is 0 or larger than 32768.
+	pub fn set_max_frame_size(&mut self, size: usize) {
+		assert!((1..=32768).contains(&size), "the maximum frame size must be between 1 and 32768");
+		self.max_frame_size = size;
+	}
+
+	/// Get the maximum number of data bytes in a frame.
+	pub fn get_max_frame_size(&self) -> usize {
+		self.max_frame_size
+	}
+
+	/// Set the maximum time to wait for the modem to respond to a command, like opening or closing a channel.
+	pub fn set_response_timeout(&mut self, timeout: Duration) {
+		self.response_timeout = timeout;
+	}
+
+	/// Get the maximum time to wait for the modem to respond to a command.
+	pub fn get_response_timeout(&self) -> Duration {
+		self.response_timeout
+	}
+}
+
+/// A 3GPP TS 27.010 multiplexer on a serial port.
+///
+/// See the [module documentation](self) for more information.
+pub struct Cmux {
+	shared: Arc<Shared>,
+	_task: AbortOnDrop,
+}
+
+/// A logical channel of a [`Cmux`].
+///
+/// The channel implements [`AsyncRead`] and [`AsyncWrite`].
+/// Reading returns zero bytes when the channel or the multiplexer has been closed.
+///
+/// Dropping the channel disconnects it.
+/// Use [`Self::close()`] to wait for the modem to confirm the disconnect.
+pub struct CmuxChannel {
+	dlci: u8,
+	shared: Arc<Shared>,
+	channel: mpsc::UnboundedReceiver<Vec<u8>>,
+	pending: Vec<u8>,
+	position: usize,
+	write: Option<Pin<Box<dyn Future<Output = std::io::Result<usize>> + Send>>>,
+	connected: bool,
+}
+
+/// State shared between the multiplexer, the channels and the background task.
+struct Shared {
+	port: SerialPort,
+	write_lock: Mutex<()>,
+	max_frame_size: usize,
+	response_timeout: Duration,
+	channels: std::sync::Mutex<HashMap<u8, mpsc::UnboundedSender<Vec<u8>>>>,
+	responses: std::sync::Mutex<Vec<(Response, oneshot::Sender<Frame>)>>,
+	closed: AtomicBool,
+}
+
+/// A response that a task is waiting for.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Response {
+	/// A UA or DM frame for a DLCI.
+	Link(u8),
+
+	/// A control message response of a message type.
+	Message(u8),
+}
+
+impl Cmux {
+	/// Start the multiplexer on a serial port.
+	///
+	/// The modem must already be in multiplexer mode.
+	/// This opens the control channel (DLCI 0) and waits for the modem to accept it.
+	///
+	/// The serial port is owned by the multiplexer.
+	/// Use [`SerialPort::try_clone()`] first if you need to use the serial port again after closing the multiplexer.
+	pub async fn start(port: SerialPort, config: CmuxConfig) -> std::io::Result<Self> {
+		let shared = Arc::new(Shared {
+			port,
+			write_lock: Mutex::new(()),
+			max_frame_size: config.max_frame_size,
+			response_timeout: config.response_timeout,
+			channels: std::sync::Mutex::new(HashMap::new()),
+			responses: std::sync::Mutex::new(Vec::new()),
+			closed: AtomicBool::new(false),
+		});
+		let task = tokio::spawn(receive(shared.clone()));
+		let cmux = Self {
+			shared,
+			_task: AbortOnDrop(task.abort_handle()),
+		};
+		cmux.shared.connect(0).await?;
+		Ok(cmux)
+	}
+
+	/// Open a logical channel.
+	///
+	/// The DLCI (data link connection identifier) must be between 1 and 63.
+	/// Returns an error of kind [`std::io::ErrorKind::ConnectionRefused`] if the modem rejects the channel,
+	/// and an error of kind [`std::io::ErrorKind::AlreadyExists`] if the channel is already open.
+	pub async fn open_channel(&self, dlci: u8) -> std::io::Result<CmuxChannel> {
+		if !(1..=63).contains(&dlci) {
+			return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "the DLCI must be between 1 and 63"));
+		}
+		let (data_tx, data_rx) = mpsc::unbounded_channel();
+		{
+			let mut channels = self.shared.channels.lock().unwrap_or_else(|e| e.into_inner());
+			if channels.contains_key(&dlci) {
+				return Err(std::io::Error::new(std::io::ErrorKind::AlreadyExists, "the channel is already open"));
+			}
+			channels.insert(dlci, data_tx);
+		}
+		let mut channel = CmuxChannel {
+			dlci,
+			shared: self.shared.clone(),
+			channel: data_rx,
+			pending: Vec::new(),
+			position: 0,
+			write: None,
+			connected: false,
+		};
+		channel.shared.connect(dlci).await?;
+		channel.connected = true;
+
+		// Tell the modem that we are ready to exchange data.
+		let mut status = Vec::new();
+		frame::encode_message(message::MSC, true, &[dlci << 2 | frame::CR | 0x01, MODEM_STATUS], &mut status);
+		self.shared.send(&status).await?;
+		Ok(channel)
+	}
+
+	/// Close the multiplexer.
+	///
+	/// This sends the close down command to the modem, after which the modem returns to AT command mode.
+	/// All channels are closed: reading from them returns zero bytes, and writing to them returns an error.
+	///
+	/// Dropping the multiplexer only stops receiving data without informing the modem.
+	pub async fn close(self) -> std::io::Result<()> {
+		let mut command = Vec::new();
+		frame::encode_message(message::CLD, true, &[], &mut command);
+		let result = self.shared.request(Response::Message(message::CLD), &command).await;
+		self.shared.close();
+		result.map(drop)
+	}
+}
+
+impl std::fmt::Debug for Cmux {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("Cmux")
+			.field("port", &self.shared.port)
+			.field("max_frame_size", &self.shared.max_frame_size)
+			.finish_non_exhaustive()
+	}
+}
+
+impl CmuxChannel {
+	/// Get the DLCI (data link connection identifier) of the channel.
+	pub fn dlci(&self) -> u8 {
+		self.dlci
+	}
+
+	/// Close the channel and wait for the modem to confirm.
+	pub async fn close(mut self) -> std::io::Result<()> {
+		self.connected = false;
+		self.shared.channels.lock().unwrap_or_else(|e| e.into_inner()).remove(&self.dlci);
+		self.shared.disconnect(self.dlci).await
+	}
+}
+
+impl std::fmt::Debug for CmuxChannel {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("CmuxChannel")
+			.field("dlci", &self.dlci)
+			.finish_non_exhaustive()
+	}
+}
+
+impl Drop for CmuxChannel {
+	fn drop(&mut self) {
+		self.shared.channels.lock().unwrap_or_else(|e| e.into_inner()).remove(&self.dlci);
+		if !self.connected || self.shared.closed.load(Ordering::Acquire) {
+			return;
+		}
+		if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+			let shared = self.shared.clone();
+			let dlci = self.dlci;
+			runtime.spawn(async move {
+				let mut command = Vec::new();
+				frame::encode(dlci, true, kind::DISC, &[], &mut command);
+				shared.send(&command).await.ok();
+			});
+		}
+	}
+}
+
+impl AsyncRead for CmuxChannel {
+	fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<std::io::Result<()>> {
+		let this = self.get_mut();
+		if this.position == this.pending.len() {
+			match this.channel.poll_recv(cx) {
+				Poll::Pending => return Poll::Pending,
+				Poll::Ready(None) => return Poll::Ready(Ok(())),
+				Poll::Ready(Some(data)) => {
+					this.pending = data;
+					this.position = 0;
+				},
+			}
+		}
+		let available = &this.pending[this.position..];
+		let read = available.len().min(buf.remaining());
+		buf.put_slice(&available[..read]);
+		this.position += read;
+		Poll::Ready(Ok(()))
+	}
+}
+
+impl AsyncWrite for CmuxChannel {
+	/// Write at most one frame of data to the channel.
+	fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>> {
+		let this = self.get_mut();
+		if buf.is_empty() && this.write.is_none() {
+			return Poll::Ready(Ok(0));
+		}
+		let write = this.write.get_or_insert_with(|| {
+			let shared = this.shared.clone();
+			let dlci = this.dlci;
+			let data = buf[..buf.len().min(shared.max_frame_size)].to_vec();
+			Box::pin(async move {
+				let mut frame = Vec::with_capacity(data.len() + 6);
+				frame::encode(dlci, true, kind::UIH, &data, &mut frame);
+				shared.send(&frame).await?;
+				Ok(data.len())
+			})
+		});
+		let result = match write.as_mut().poll(cx) {
+			Poll::Pending => return Poll::Pending,
+			Poll::Ready(result) => result,
+		};
+		this.write = None;
+		Poll::Ready(result)
+	}
+
+	fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+		let this = self.get_mut();
+		if let Some(write) = &mut this.write {
+			let result = match write.as_mut().poll(cx) {
+				Poll::Pending => return Poll::Pending,
+				Poll::Ready(result) => result,
+			};
+			this.write = None;
+			result?;
+		}
+		Poll::Ready(Ok(()))
+	}
+
+	fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+		self.poll_flush(cx)
+	}
+}
+
+impl Shared {
+	/// Write an encoded frame to the serial port.
+	async fn send(&self, frame: &[u8]) -> std::io::Result<()> {
+		if self.closed.load(Ordering::Acquire) {
+			return Err(std::io::Error::new(std::io::ErrorKind::NotConnected, "the multiplexer is closed"));
+		}
+		let _lock = self.write_lock.lock().await;
+		self.port.write_all(frame).await
+	}
+
+	/// Send a command and wait for the response of the modem.
+	async fn request(&self, response: Response, command: &[u8]) -> std::io::Result<Frame> {
+		let (response_tx, response_rx) = oneshot::channel();
+		self.responses.lock().unwrap_or_else(|e| e.into_inner()).push((response, response_tx));
+		if let Err(e) = self.send(command).await {
+			self.responses.lock().unwrap_or_else(|e| e.into_inner()).retain(|(expected, _)| *expected != response);
+			return Err(e);
+		}
+		match tokio::time::timeout(self.response_timeout, response_rx).await {
+			Ok(Ok(frame)) => Ok(frame),
+			Ok(Err(_)) => Err(std::io::Error::new(std::io::ErrorKind::NotConnected, "the multiplexer is closed")),
+			Err(_) => {
+				self.responses.lock().unwrap_or_else(|e| e.into_inner()).retain(|(expected, sender)| *expected != response || !sender.is_closed());
+				Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "no response from the modem"))
+			},
+		}
+	}
+
+	/// Open a channel and wait for the modem to accept it.
+	async fn connect(&self, dlci: u8) -> std::io::Result<()> {
+		let mut command = Vec::new();
+		frame::encode(dlci, true, kind::SABM, &[], &mut command);
+		let result = match self.request(Response::Link(dlci), &command).await {
+			Ok(frame) if frame.kind == kind::UA => Ok(()),
+			Ok(_) => Err(std::io::Error::new(std::io::ErrorKind::ConnectionRefused, format!("the modem rejected DLCI {dlci}"))),
+			Err(e) => Err(e),
+		};
+		if result.is_err() {
+			self.channels.lock().unwrap_or_else(|e| e.into_inner()).remove(&dlci);
+		}
+		result
+	}
+
+	/// Close a channel and wait for the modem to confirm.
+	async fn disconnect(&self, dlci: u8) -> std::io::Result<()> {
+		let mut command = Vec::new();
+		frame::encode(dlci, true, kind::DISC, &[], &mut command);
+		self.request(Response::Link(dlci), &command).await?;
+		Ok(())
+	}
+
+	/// Pass a response to the task that is waiting for it.
+	fn respond(&self, response: Response, frame: Frame) {
+		let mut responses = self.responses.lock().unwrap_or_else(|e| e.into_inner());
+		if let Some(index) = responses.iter().position(|(expected, _)| *expected == response) {
+			let (_, sender) = responses.remove(index);
+			sender.send(frame).ok();
+		}
+	}
+
+	/// Mark the multiplexer as closed, and close all channels.
+	fn close(&self) {
+		self.closed.store(true, Ordering::Release);
+		self.channels.lock().unwrap_or_else(|e| e.into_inner()).clear();
+		self.responses.lock().unwrap_or_else(|e| e.into_inner()).clear();
+	}
+
+	/// Handle a frame that was received from the modem.
+	async fn handle_frame(&self, frame: Frame) -> std::io::Result<()> {
+		match frame.kind {
+			kind::UA | kind::DM => self.respond(Response::Link(frame.dlci), frame),
+			kind::UIH if frame.dlci == 0 => self.handle_message(frame).await?,
+			kind::UIH => {
+				if let Some(channel) = self.channels.lock().unwrap_or_else(|e| e.into_inner()).get(&frame.dlci) {
+					channel.send(frame.info).ok();
+				}
+			},
+			kind::SABM => {
+				// The modem can not open channels.
+				let mut response = Vec::new();
+				frame::encode(frame.dlci, false, kind::DM, &[], &mut response);
+				self.send(&response).await?;
+			},
+			kind::DISC => {
+				let mut response = Vec::new();
+				frame::encode(frame.dlci, false, kind::UA, &[], &mut response);
+				self.send(&response).await?;
+				if frame.dlci == 0 {
+					self.close();
+				} else {
+					self.channels.lock().unwrap_or_else(|e| e.into_inner()).remove(&frame.dlci);
+				}
+			},
+			_ => (),
+		}
+		Ok(())
+	}
+
+	/// Handle a control message on DLCI 0.
+	async fn handle_message(&self, frame: Frame) -> std::io::Result<()> {
+		let Some((message_type, value)) = frame::parse_message(&frame.info) else {
+			return Ok(());
+		};
+		let message = message_type & !frame::CR;
+		if message_type & frame::CR == 0 {
+			let value = value.to_vec();
+			self.respond(Response::Message(message), Frame { info: value, ..frame });
+			return Ok(());
+		}
+
+		let mut response = Vec::new();
+		match message {
+			message::MSC | message::TEST | message::CLD => frame::encode_message(message, false, value, &mut response),
+			_ => frame::encode_message(message::NSC, false, &[message_type | 0x01], &mut response),
+		}
+		self.send(&response).await?;
+		if message == message::CLD {
+			self.close();
+		}
+		Ok(())
+	}
+}
+
+/// Receive frames from the modem.
+async fn receive(shared: Arc<Shared>) {
+	let mut parser = frame::Parser::new(shared.max_frame_size);
+	let mut buffer = vec![0; 4096];
+	'receive: loop {
+		let read = match shared.port.read(&mut buffer).await {
+			Ok(0) | Err(_) => break,
+			Ok(read) => read,
+		};
+		parser.extend(&buffer[..read]);
+		while let Some(frame) = parser.next_frame() {
+			if shared.handle_frame(frame).await.is_err() {
+				break 'receive;
+			}
+		}
+	}
+	shared.close();
+}
+
+#[cfg(test)]
+#[cfg(all(unix, feature = "unix"))]
+mod test {
+	use super::*;
+	use crate::test_util::{expect, expect_then_reply};
+
+	#[tokio::test]
+	async fn start_open_channel_and_exchange_data() {
+		use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+		let (port, modem) = SerialPort::pair().unwrap();
+		let modem_side = expect_then_reply(&modem, &[0xF9, 0x03, 0x3F, 0x01, 0x1C, 0xF9], &[0xF9, 0x03, 0x73, 0x01, 0xD7, 0xF9]);
+		let (cmux, ()) = tokio::join!(Cmux::start(port, CmuxConfig::new()), modem_side);
+		let cmux = cmux.unwrap();
+
+		let modem_side = async {
+			expect_then_reply(&modem, &[0xF9, 0x07, 0x3F, 0x01, 0xDE, 0xF9], &[0xF9, 0x07, 0x73, 0x01, 0x15, 0xF9]).await;
+			// The modem status command that signals that the channel is ready.
+			expect(&modem, &[0xF9, 0x03, 0xEF, 0x09, 0xE3, 0x05, 0x07, 0x8D, 0xFB, 0xF9]).await;
+		};
+		let (channel, ()) = tokio::join!(cmux.open_channel(1), modem_side);
+		let mut channel = channel.unwrap();
+		assert_eq!(channel.dlci(), 1);
+
+		channel.write_all(b"AT\r").await.unwrap();
+		expect(&modem, &[0xF9, 0x07, 0xEF, 0x07, 0x41, 0x54, 0x0D, 0xD3, 0xF9]).await;
+
+		modem.write_all(&[0xF9, 0x07, 0xEF, 0x09, 0x4F, 0x4B, 0x0D, 0x0A, 0x39, 0xF9]).await.unwrap();
+		let mut buffer = [0; 4];
+		channel.read_exact(&mut buffer).await.unwrap();
+		assert_eq!(&buffer, b"OK\r\n");
+
+		let error = cmux.open_channel(1).await.unwrap_err();
+		assert_eq!(error.kind(), std::io::ErrorKind::AlreadyExists);
+		let error = cmux.open_channel(64).await.unwrap_err();
+		assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+	}
+
+	#[tokio::test]
+	async fn rejected_channel_is_reported() {
+		let (port, modem) = SerialPort::pair().unwrap();
+		let modem_side = expect_then_reply(&modem, &[0xF9, 0x03, 0x3F, 0x01, 0x1C, 0xF9], &[0xF9, 0x03, 0x73, 0x01, 0xD7, 0xF9]);
+		let (cmux, ()) = tokio::join!(Cmux::start(port, CmuxConfig::new()), modem_side);
+		let cmux = cmux.unwrap();
+
+		let modem_side = async {
+			expect(&modem, &[0xF9, 0x07, 0x3F, 0x01, 0xDE, 0xF9]).await;
+			// DM: the channel is not available.
+			modem.write_all(&[0xF9, 0x07, 0x1F, 0x01, 0xF4, 0xF9]).await.unwrap();
+		};
+		let (channel, ()) = tokio::join!(cmux.open_channel(1), modem_side);
+		assert_eq!(channel.unwrap_err().kind(), std::io::ErrorKind::ConnectionRefused);
+	}
+
+	#[tokio::test]
+	async fn start_times_out_without_modem() {
+		let (port, _modem) = SerialPort::pair().unwrap();
+		let mut config = CmuxConfig::new();
+		config.set_response_timeout(Duration::from_millis(50));
+		let error = Cmux::start(port, config).await.unwrap_err();
+		assert_eq!(error.kind(), std::io::ErrorKind::TimedOut);
+	}
+}
@@ -0,0 +1,561 @@
+use std::time::Duration;
+
+use crate::SerialPort;
+
+#[cfg(unix)]
+use std::{future::Future, pin::Pin, sync::Mutex, sync::atomic::{AtomicU8, Ordering}, task::{ready, Poll}};
+
+/// Configuration for read coalescing.
+///
+/// Used with [`SerialPort::set_read_coalescing()`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct ReadCoalescing {
+	/// Read immediately once this many bytes are available.
+	pub min_bytes: usize,
+
+	/// The maximum time to wait for more data after the first byte became available.
+	pub max_delay: Duration,
+
+	/// The size of the internal read buffer.
+	pub buffer_size: usize,
+}
+
+impl ReadCoalescing {
+	/// Create a new read coalescing configuration with an internal buffer of 4096 bytes.
+	pub fn new(min_bytes: usize, max_delay: Duration) -> Self {
+		Self {
+			min_bytes,
+			max_delay,
+			buffer_size: 4096,
+		}
+	}
+}
+
+/// Configuration for an adaptive read buffer.
+///
+/// Used with [`SerialPort::set_adaptive_read_buffer()`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct AdaptiveReadBuffer {
+	/// The smallest size of the internal read buffer.
+	pub min_size: usize,
+
+	/// The largest size of the internal read buffer.
+	pub max_size: usize,
+}
+
+impl AdaptiveReadBuffer {
+	/// Create a new adaptive read buffer configuration.
+	pub fn new(min_size: usize, max_size: usize) -> Self {
+		Self {
+			min_size,
+			max_size,
+		}
+	}
+}
+
+impl SerialPort {
+	/// Enable or disable read coalescing.
+	///
+	/// Normally, each arriving byte can wake up the reading task and cause a separate read system call.
+	/// For high-rate data streams, this can use a lot of CPU time.
+	/// With read coalescing enabled, a read waits until at least [`ReadCoalescing::min_bytes`] are available,
+	/// or until [`ReadCoalescing::max_delay`] has passed since data became available, whichever comes first,
+	/// and then reads all available data into an internal buffer in a single system call.
+	/// Following reads are served from the internal buffer until it is empty.
+	///
+	/// This trades a little latency for fewer wake-ups and system calls.
+	/// It applies to [`Self::read()`] and the [`AsyncRead`][tokio::io::AsyncRead] implementation.
+	///
+	/// Pass `None` to disable read coalescing.
+	/// Data that is still in the internal buffer can still be read afterwards.
+	///
+	/// See the `read-coalescing` example for a benchmark.
+	///
+	/// # Example
+	/// ```no_run
+	/// # async fn example() -> std::io::Result<()> {
+	/// use serial2_tokio::{ReadCoalescing, SerialPort};
+	/// use std::time::Duration;
+	///
+	/// let port = SerialPort::open("/dev/ttyUSB0", 3_000_000)?;
+	/// port.set_read_coalescing(Some(ReadCoalescing::new(512, Duration::from_millis(2))))?;
+	/// # Ok(())
+	/// # }
+	/// ```
+	#[cfg(any(feature = "doc", unix))]
+	#[cfg_attr(feature = "doc-cfg", doc(cfg(unix)))]
+	pub fn set_read_coalescing(&self, config: Option<ReadCoalescing>) -> std::io::Result<()> {
+		#[cfg(unix)] {
+			if config.is_some_and(|config| config.buffer_size == 0) {
+				return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "read coalescing buffer size must be greater than zero"));
+			}
+			*self.coalescer.config.lock().unwrap_or_else(|e| e.into_inner()) = config;
+			self.coalescer.set_active(Coalescer::COALESCING, config.is_some());
+			Ok(())
+		}
+		#[cfg(not(unix))] {
+			let _ = config;
+			unreachable!("this code is only enabled on Unix platforms or during documentation generation")
+		}
+	}
+
+	/// Enable or disable the adaptive read buffer.
+	///
+	/// With the adaptive read buffer enabled, data is read from the OS into an internal buffer,
+	/// and reads are served from the internal buffer until it is empty.
+	/// This reduces the number of system calls when the application reads in small pieces, like a parser that reads one header at a time.
+	///
+	/// The size of the internal buffer follows the rate at which data arrives:
+	/// it doubles when a read fills the whole buffer, and it halves when a read fills less than a quarter of it,
+	/// within the limits of the configuration.
+	/// So the buffer stays small for an interactive console and grows for a bulk transfer like a firmware dump.
+	/// Unlike [read coalescing][Self::set_read_coalescing()], reads never wait for more data, so no latency is added.
+	/// If read coalescing is enabled too, it takes precedence.
+	///
+	/// Reads with a buffer that is at least as large as the internal buffer bypass it.
+	/// The current size of the internal buffer is reported in [`Stats::read_buffer_size`][crate::Stats::read_buffer_size].
+	///
+	/// Pass `None` to disable the adaptive read buffer.
+	/// Data that is still in the internal buffer can still be read afterwards.
+	///
+	/// # Example
+	/// ```no_run
+	/// # async fn example() -> std::io::Result<()> {
+	/// use serial2_tokio::{AdaptiveReadBuffer, SerialPort};
+	///
+	/// let port = SerialPort::open("/dev/ttyUSB0", 921600)?;
+	/// port.set_adaptive_read_buffer(Some(AdaptiveReadBuffer::new(64, 16 * 1024)))?;
+	/// let mut header = [0; 4];
+	/// port.read(&mut header).await?;
+	/// println!("read buffer size: {}", port.stats().read_buffer_size);
+	/// # Ok(())
+	/// # }
+	/// ```
+	#[cfg(any(feature = "doc", unix))]
+	#[cfg_attr(feature = "doc-cfg", doc(cfg(unix)))]
+	pub fn set_adaptive_read_buffer(&self, config: Option<AdaptiveReadBuffer>) -> std::io::Result<()> {
+		#[cfg(unix)] {
+			if config.is_some_and(|config| config.min_size == 0 || config.min_size > config.max_size) {
+				return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "adaptive read buffer size limits must be non-zero and in order"));
+			}
+			let state = config.map(|config| AdaptiveState {
+				config,
+				size: config.min_size,
+			});
+			self.stats.set_read_buffer_size(config.map_or(0, |config| config.min_size));
+			*self.coalescer.adaptive.lock().unwrap_or_else(|e| e.into_inner()) = state;
+			self.coalescer.set_active(Coalescer::ADAPTIVE, config.is_some());
+			Ok(())
+		}
+		#[cfg(not(unix))] {
+			let _ = config;
+			unreachable!("this code is only enabled on Unix platforms or during documentation generation")
+		}
+	}
+
+	/// Get the current adaptive read buffer configuration.
+	#[cfg(any(feature = "doc", unix))]
+	#[cfg_attr(feature = "doc-cfg", doc(cfg(unix)))]
+	pub fn get_adaptive_read_buffer(&self) -> Option<AdaptiveReadBuffer> {
+		#[cfg(unix)] {
+			self.coalescer.adaptive.lock().unwrap_or_else(|e| e.into_inner()).map(|state| state.config)
+		}
+		#[cfg(not(unix))] {
+			unreachable!("this code is only enabled on Unix platforms or during documentation generation")
+		}
+	}
+
+	/// Get the current read coalescing configuration.
+	#[cfg(any(feature = "doc", unix))]
+	#[cfg_attr(feature = "doc-cfg", doc(cfg(unix)))]
+	pub fn get_read_coalescing(&self) -> Option<ReadCoalescing> {
+		#[cfg(unix)] {
+			*self.coalescer.config.lock().unwrap_or_else(|e| e.into_inner())
+		}
+		#[cfg(not(unix))] {
+			unreachable!("this code is only enabled on Unix platforms or during documentation generation")
+		}
+	}
+}
+
+/// The state of read coalescing for a serial port.
+#[cfg(unix)]
+#[derive(Debug, Default)]
+pub(crate) struct Coalescer {
+	/// Flags for the enabled features and for buffered data, so reads can skip the locks when everything is off.
+	active: AtomicU8,
+	config: Mutex<Option<ReadCoalescing>>,
+	adaptive: Mutex<Option<AdaptiveState>>,
+	buffer: Mutex<ReadBuffer>,
+}
+
+/// The configuration and current size of the adaptive read buffer.
+#[cfg(unix)]
+#[derive(Debug, Copy, Clone)]
+struct AdaptiveState {
+	config: AdaptiveReadBuffer,
+	size: usize,
+}
+
+/// Data that has been read from the serial port, but not by the user yet.
+#[cfg(unix)]
+#[derive(Debug, Default)]
+struct ReadBuffer {
+	data: Vec<u8>,
+	position: usize,
+}
+
+#[cfg(unix)]
+impl Coalescer {
+	const COALESCING: u8 = 1;
+	const ADAPTIVE: u8 = 2;
+	const BUFFERED: u8 = 4;
+
+	/// Set or clear a flag in `self.active`.
+	fn set_active(&self, flag: u8, enabled: bool) {
+		if enabled {
+			self.active.fetch_or(flag, Ordering::Relaxed);
+		} else {
+			self.active.fetch_and(!flag, Ordering::Relaxed);
+		}
+	}
+
+	/// Check if a read needs to go through the coalescer.
+	fn is_active(&self) -> bool {
+		self.active.load(Ordering::Relaxed) != 0
+	}
+
+	/// Copy data from the internal buffer.
+	///
+	/// Returns `None` if the internal buffer is empty.
+	fn take_buffered(&self, buf: &mut [u8]) -> Option<usize> {
+		let mut buffer = self.buffer.lock().unwrap_or_else(|e| e.into_inner());
+		let available = &buffer.data[buffer.position..];
+		if available.is_empty() || buf.is_empty() {
+			return None;
+		}
+		let len = available.len().min(buf.len());
+		buf[..len].copy_from_slice(&available[..len]);
+		buffer.position += len;
+		self.set_active(Self::BUFFERED, buffer.position < buffer.data.len());
+		Some(len)
+	}
+
+	/// Take the internal buffer to read new data into it.
+	///
+	/// If another task stored data that has not been read yet, that data stays in the internal buffer and a new buffer is allocated.
+	fn take_buffer(&self, size: usize) -> Vec<u8> {
+		let mut buffer = self.buffer.lock().unwrap_or_else(|e| e.into_inner());
+		let mut data = if buffer.position < buffer.data.len() {
+			Vec::new()
+		} else {
+			buffer.position = 0;
+			std::mem::take(&mut buffer.data)
+		};
+		drop(buffer);
+		data.resize(size, 0);
+		data
+	}
+
+	/// Store newly read data and copy as much as possible into `buf`.
+	fn store(&self, mut data: Vec<u8>, read: usize, buf: &mut [u8]) -> usize {
+		data.truncate(read);
+		let mut buffer = self.buffer.lock().unwrap_or_else(|e| e.into_inner());
+		if buffer.position < buffer.data.len() {
+			// Another task stored data in the mean time, so append to it to preserve the order.
+			let position = buffer.position;
+			buffer.data.drain(..position);
+			buffer.data.extend_from_slice(&data);
+		} else {
+			buffer.data = data;
+		}
+		buffer.position = 0;
+
+		let len = buffer.data.len().min(buf.len());
+		buf[..len].copy_from_slice(&buffer.data[..len]);
+		buffer.position = len;
+		self.set_active(Self::BUFFERED, buffer.position < buffer.data.len());
+		len
+	}
+
+	/// Discard the data in the internal buffer.
+	fn discard(&self) {
+		let mut buffer = self.buffer.lock().unwrap_or_else(|e| e.into_inner());
+		buffer.data.clear();
+		buffer.position = 0;
+		self.set_active(Self::BUFFERED, false);
+	}
+
+	/// Get the current size of the adaptive read buffer, if it is enabled.
+	fn adaptive_size(&self) -> Option<usize> {
+		self.adaptive.lock().unwrap_or_else(|e| e.into_inner()).map(|state| state.size)
+	}
+
+	/// Adjust the size of the adaptive read buffer after reading `read` bytes with a buffer of `size` bytes.
+	///
+	/// Returns the new size, or `None` if the adaptive read buffer was disabled in the mean time.
+	fn adapt(&self, read: usize, size: usize) -> Option<usize> {
+		let mut adaptive = self.adaptive.lock().unwrap_or_else(|e| e.into_inner());
+		let state = adaptive.as_mut()?;
+		if read >= size {
+			state.size = (state.size * 2).min(state.config.max_size);
+		} else if read < size / 4 {
+			state.size = (state.size / 2).max(state.config.min_size);
+		}
+		Some(state.size)
+	}
+}
+
+#[cfg(unix)]
+impl SerialPort {
+	/// Discard data that was read into an internal buffer, but not by the user yet.
+	pub(crate) fn discard_read_buffers(&self) {
+		self.coalescer.discard();
+		self.inner.discard_leftover();
+	}
+
+	/// Read with coalescing, if it is enabled.
+	///
+	/// Returns `None` if read coalescing and the adaptive read buffer are disabled and the internal buffer is empty.
+	pub(crate) async fn read_coalesced(&self, buf: &mut [u8]) -> Option<std::io::Result<usize>> {
+		if !self.coalescer.is_active() {
+			return None;
+		}
+		if let Some(read) = self.coalescer.take_buffered(buf) {
+			return Some(Ok(read));
+		}
+		let config = *self.coalescer.config.lock().unwrap_or_else(|e| e.into_inner());
+		if let Some(config) = config {
+			return Some(self.read_coalesced_with(buf, config).await);
+		}
+		let size = self.coalescer.adaptive_size()?;
+		Some(self.read_adaptive(buf, size).await)
+	}
+
+	async fn read_adaptive(&self, buf: &mut [u8], size: usize) -> std::io::Result<usize> {
+		if buf.len() >= size {
+			let read = self.inner.read(buf).await?;
+			self.adapt_read_buffer(read, size);
+			return Ok(read);
+		}
+		let mut data = self.coalescer.take_buffer(size);
+		let read = match self.inner.read(&mut data).await {
+			Ok(read) => read,
+			Err(e) => {
+				self.coalescer.store(data, 0, &mut []);
+				return Err(e);
+			},
+		};
+		self.adapt_read_buffer(read, size);
+		Ok(self.coalescer.store(data, read, buf))
+	}
+
+	/// Adjust the size of the adaptive read buffer and report it in the statistics.
+	fn adapt_read_buffer(&self, read: usize, size: usize) {
+		if let Some(size) = self.coalescer.adapt(read, size) {
+			self.stats.set_read_buffer_size(size);
+		}
+	}
+
+	async fn read_coalesced_with(&self, buf: &mut [u8], config: ReadCoalescing) -> std::io::Result<usize> {
+		self.inner.readable().await?;
+		let enough_data = std::future::poll_fn(|cx| self.inner.poll_input_queue_len(cx, config.min_bytes));
+		if let Ok(result) = tokio::time::timeout(config.max_delay, enough_data).await {
+			result?;
+		}
+		if buf.len() >= config.buffer_size {
+			return self.inner.read(buf).await;
+		}
+		let mut data = self.coalescer.take_buffer(config.buffer_size);
+		let read = self.inner.read(&mut data).await?;
+		Ok(self.coalescer.store(data, read, buf))
+	}
+
+	/// Poll a read with coalescing, if it is enabled.
+	///
+	/// Returns `None` if read coalescing and the adaptive read buffer are disabled and the internal buffer is empty.
+	pub(crate) fn poll_read_coalesced(&mut self, cx: &mut std::task::Context<'_>, buf: &mut tokio::io::ReadBuf<'_>) -> Option<Poll<std::io::Result<()>>> {
+		if !self.coalescer.is_active() {
+			return None;
+		}
+		if let Some(read) = self.coalescer.take_buffered(buf.initialize_unfilled()) {
+			buf.advance(read);
+			return Some(Poll::Ready(Ok(())));
+		}
+		let config = *self.coalescer.config.lock().unwrap_or_else(|e| e.into_inner());
+		if let Some(config) = config {
+			return Some(self.poll_read_coalesced_with(cx, buf, config));
+		}
+		let size = self.coalescer.adaptive_size()?;
+		Some(self.poll_read_adaptive(cx, buf, size))
+	}
+
+	fn poll_read_adaptive(&mut self, cx: &mut std::task::Context<'_>, buf: &mut tokio::io::ReadBuf<'_>, size: usize) -> Poll<std::io::Result<()>> {
+		if buf.remaining() >= size {
+			let filled = buf.filled().len();
+			ready!(self.inner.poll_read(cx, buf))?;
+			self.adapt_read_buffer(buf.filled().len() - filled, size);
+			return Poll::Ready(Ok(()));
+		}
+		self.poll_read_buffered(cx, buf, size).map_ok(|read| self.adapt_read_buffer(read, size))
+	}
+
+	fn poll_read_coalesced_with(&mut self, cx: &mut std::task::Context<'_>, buf: &mut tokio::io::ReadBuf<'_>, config: ReadCoalescing) -> Poll<std::io::Result<()>> {
+		if self.read_sleep.is_none() {
+			ready!(self.inner.poll_read_ready(cx))?;
+			if self.inner.input_queue_len()? < config.min_bytes {
+				self.read_sleep = Some(Box::pin(tokio::time::sleep(config.max_delay)));
+			}
+		}
+		if let Some(sleep) = &mut self.read_sleep {
+			match self.inner.poll_input_queue_len(cx, config.min_bytes) {
+				Poll::Ready(result) => {
+					self.read_sleep = None;
+					result?;
+				},
+				Poll::Pending => {
+					ready!(sleep.as_mut().poll(cx));
+					self.read_sleep = None;
+				},
+			}
+		}
+
+		if buf.remaining() >= config.buffer_size {
+			return self.inner.poll_read(cx, buf);
+		}
+		self.poll_read_buffered(cx, buf, config.buffer_size).map_ok(drop)
+	}
+
+	/// Poll a read into the internal buffer of `size` bytes, and copy as much as possible into `buf`.
+	///
+	/// Returns the number of bytes that were read into the internal buffer.
+	fn poll_read_buffered(&mut self, cx: &mut std::task::Context<'_>, buf: &mut tokio::io::ReadBuf<'_>, size: usize) -> Poll<std::io::Result<usize>> {
+		let mut data = self.coalescer.take_buffer(size);
+		let mut data_buf = tokio::io::ReadBuf::new(&mut data);
+		let result = self.inner.poll_read(cx, &mut data_buf);
+		let read = data_buf.filled().len();
+		match result {
+			Poll::Ready(Ok(())) => {
+				let copied = self.coalescer.store(data, read, buf.initialize_unfilled());
+				buf.advance(copied);
+				Poll::Ready(Ok(read))
+			},
+			Poll::Ready(Err(e)) => {
+				self.coalescer.store(data, 0, &mut []);
+				Poll::Ready(Err(e))
+			},
+			Poll::Pending => {
+				self.coalescer.store(data, 0, &mut []);
+				Poll::Pending
+			},
+		}
+	}
+}
+
+/// A sleep used while coalescing reads through the `AsyncRead` implementation.
+#[cfg(unix)]
+pub(crate) type ReadSleep = Option<Pin<Box<tokio::time::Sleep>>>;
+
+#[cfg(test)]
+#[cfg(unix)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn take_buffer_keeps_data_stored_by_another_reader() {
+		let coalescer = Coalescer::default();
+		let mut buf = [0; 8];
+
+		// The first reader finds the internal buffer empty.
+		assert_eq!(coalescer.take_buffered(&mut buf), None);
+
+		// The second reader stores four bytes, but only reads one of them.
+		let data = coalescer.take_buffer(8);
+		assert_eq!(coalescer.store(data, 0, &mut []), 0);
+		let mut data = coalescer.take_buffer(8);
+		data[..4].copy_from_slice(b"abcd");
+		assert_eq!(coalescer.store(data, 4, &mut buf[..1]), 1);
+		assert_eq!(&buf[..1], b"a");
+
+		// The first reader reads new data, which must come after the unread data of the second reader.
+		let mut data = coalescer.take_buffer(8);
+		data[..2].copy_from_slice(b"ef");
+		assert_eq!(coalescer.store(data, 2, &mut buf), 5);
+		assert_eq!(&buf[..5], b"bcdef");
+		assert_eq!(coalescer.take_buffered(&mut buf), None);
+	}
+
+	#[test]
+	fn take_buffer_reuses_consumed_buffer() {
+		let coalescer = Coalescer::default();
+		let mut buf = [0; 8];
+		let mut data = coalescer.take_buffer(8);
+		data[..3].copy_from_slice(b"abc");
+		assert_eq!(coalescer.store(data, 3, &mut buf[..2]), 2);
+		assert_eq!(coalescer.take_buffered(&mut buf), Some(1));
+
+		// Another reader must not see stale data while the buffer is taken.
+		let data = coalescer.take_buffer(8);
+		assert_eq!(data.len(), 8);
+		assert_eq!(coalescer.take_buffered(&mut buf), None);
+		assert_eq!(coalescer.store(data, 0, &mut buf), 0);
+	}
+
+	#[cfg(feature = "unix")]
+	mod pty {
+		use super::*;
+		use std::time::Instant;
+		use tokio::io::AsyncReadExt;
+
+		/// Write `first`, and then `second` after a short pause.
+		fn write_in_two_parts(port: SerialPort, first: &'static [u8], second: &'static [u8]) -> tokio::task::JoinHandle<SerialPort> {
+			tokio::spawn(async move {
+				port.write_all(first).await.unwrap();
+				tokio::time::sleep(Duration::from_millis(50)).await;
+				port.write_all(second).await.unwrap();
+				port
+			})
+		}
+
+		#[tokio::test]
+		async fn read_returns_early_when_min_bytes_arrive() {
+			let (port, other) = SerialPort::pair().unwrap();
+			port.set_read_coalescing(Some(ReadCoalescing::new(4, Duration::from_secs(5)))).unwrap();
+			let writer = write_in_two_parts(other, b"a", b"bcd");
+
+			let start = Instant::now();
+			let mut buf = [0; 16];
+			let read = port.read(&mut buf).await.unwrap();
+			assert_eq!(&buf[..read], b"abcd");
+			assert!(start.elapsed() < Duration::from_secs(2));
+			writer.await.unwrap();
+		}
+
+		#[tokio::test]
+		async fn poll_read_returns_early_when_min_bytes_arrive() {
+			let (mut port, other) = SerialPort::pair().unwrap();
+			port.set_read_coalescing(Some(ReadCoalescing::new(4, Duration::from_secs(5)))).unwrap();
+			let writer = write_in_two_parts(other, b"a", b"bcd");
+
+			let start = Instant::now();
+			let mut buf = [0; 16];
+			let read = AsyncReadExt::read(&mut port, &mut buf).await.unwrap();
+			assert_eq!(&buf[..read], b"abcd");
+			assert!(start.elapsed() < Duration::from_secs(2));
+			writer.await.unwrap();
+		}
+
+		#[tokio::test]
+		async fn read_returns_after_max_delay() {
+			let (port, other) = SerialPort::pair().unwrap();
+			port.set_read_coalescing(Some(ReadCoalescing::new(100, Duration::from_millis(100)))).unwrap();
+			other.write_all(b"abc").await.unwrap();
+
+			let start = Instant::now();
+			let mut buf = [0; 16];
+			let read = port.read(&mut buf).await.unwrap();
+			assert_eq!(&buf[..read], b"abc");
+			assert!(start.elapsed() >= Duration::from_millis(100));
+		}
+	}
+}
@@ -0,0 +1,397 @@
+//! A codec for line based ASCII command/response protocols.
+
+use std::collections::VecDeque;
+
+use bytes::{Buf, BufMut, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+/// Configuration for an [`AsciiCodec`].
+#[derive(Debug, Clone)]
+pub struct AsciiConfig {
+	tx_terminator: String,
+	rx_terminator: String,
+	prompt: Option<String>,
+	suppress_echo: bool,
+	max_line_len: usize,
+}
+
+impl Default for AsciiConfig {
+	fn default() -> Self {
+		Self {
+			tx_terminator: "\n".into(),
+			rx_terminator: "\n".into(),
+			prompt: None,
+			suppress_echo: false,
+			max_line_len: 4096,
+		}
+	}
+}
+
+impl AsciiConfig {
+	/// Create a new configuration that uses `\n` to terminate commands and responses, without prompt and echo suppression.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Set the terminator that is appended to each command.
+	pub fn set_tx_terminator(&mut self, terminator: impl Into<String>) {
+		self.tx_terminator = terminator.into();
+	}
+
+	/// Get the terminator that is appended to each command.
+	pub fn get_tx_terminator(&self) -> &str {
+		&self.tx_terminator
+	}
+
+	/// Set the terminator of received lines.
+	///
+	/// The terminator is removed from the decoded lines.
+	///
+	/// # Panics
+	/// This function panics if the terminator is empty.
+	pub fn set_rx_terminator(&mut self, terminator: impl Into<String>) {
+		let terminator = terminator.into();
+		assert!(!terminator.is_empty(), "the receive terminator can not be empty");
+		self.rx_terminator = terminator;
+	}
+
+	/// Get the terminator of received lines.
+	pub fn get_rx_terminator(&self) -> &str {
+		&self.rx_terminator
+	}
+
+	/// Set the prompt that the device sends when it is ready for a new command, or `None` if the device does not send a prompt.
+	///
+	/// The prompt is only recognized at the start of a line, and is not followed by a terminator.
+	pub fn set_prompt(&mut self, prompt: Option<&str>) {
+		self.prompt = prompt.filter(|prompt| !prompt.is_empty()).map(String::from);
+	}
+
+	/// Get the prompt that the device sends when it is ready for a new command.
+	pub fn get_prompt(&self) -> Option<&str> {
+		self.prompt.as_deref()
+	}
+
+	/// Enable or disable echo suppression, for devices that echo the commands they receive.
+	///
+	/// With echo suppression enabled, the decoder skips a received line if it is equal to the oldest command that has not been echoed yet.
+	/// If the received line is different, the device is assumed to not echo the pending commands.
+	pub fn set_suppress_echo(&mut self, enable: bool) {
+		self.suppress_echo = enable;
+	}
+
+	/// Check if echo suppression is enabled.
+	pub fn get_suppress_echo(&self) -> bool {
+		self.suppress_echo
+	}
+
+	/// Set the maximum length of a received line, excluding the terminator.
+	///
+	/// Longer lines are discarded.
+	///
+	/// The default maximum is 4096 bytes.
+	pub fn set_max_line_len(&mut self, len: usize) {
+		self.max_line_len = len;
+	}
+
+	/// Get the maximum length of a received line.
+	pub fn get_max_line_len(&self) -> usize {
+		self.max_line_len
+	}
+}
+
+/// A message decoded by an [`AsciiCodec`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum AsciiMessage {
+	/// A line of text, without the terminator.
+	///
+	/// Invalid UTF-8 sequences are replaced with the replacement character.
+	Line(String),
+
+	/// The device sent the prompt, so it is ready for a new command.
+	Prompt,
+}
+
+/// A codec for line based ASCII protocols, such as the protocols used by many lab instruments.
+///
+/// The encoder accepts commands as strings and appends the configured terminator.
+/// The decoder produces an [`AsciiMessage`] for each received line and each received prompt.
+///
+/// # Example
+/// ```no_run
+/// # async fn example() -> std::io::Result<()> {
+/// use futures::{SinkExt, StreamExt};
+/// use serial2_tokio::SerialPort;
+/// use serial2_tokio::codec::{AsciiCodec, AsciiConfig, AsciiMessage};
+/// use tokio_util::codec::Framed;
+///
+/// let mut config = AsciiConfig::new();
+/// config.set_tx_terminator("\r");
+/// config.set_rx_terminator("\r\n");
+/// config.set_prompt(Some("> "));
+/// config.set_suppress_echo(true);
+///
+/// let port = SerialPort::open("/dev/ttyUSB0", 9600)?;
+/// let mut framed = Framed::new(port, AsciiCodec::new(config));
+/// framed.send("status").await?;
+/// while let Some(message) = framed.next().await {
+///     match message? {
+///         AsciiMessage::Line(line) => println!("{line}"),
+///         AsciiMessage::Prompt => break,
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct AsciiCodec {
+	config: AsciiConfig,
+	/// Commands that may still be echoed by the device.
+	pending_echo: VecDeque<String>,
+	/// A line that was too long is being discarded.
+	discarding: bool,
+	discarded: u64,
+}
+
+impl AsciiCodec {
+	/// Create a codec with the given configuration.
+	pub fn new(config: AsciiConfig) -> Self {
+		Self {
+			config,
+			pending_echo: VecDeque::new(),
+			discarding: false,
+			discarded: 0,
+		}
+	}
+
+	/// Get the configuration of the codec.
+	pub fn config(&self) -> &AsciiConfig {
+		&self.config
+	}
+
+	/// Get the total number of bytes discarded by the decoder because lines were too long.
+	pub fn discarded_bytes(&self) -> u64 {
+		self.discarded
+	}
+}
+
+impl Decoder for AsciiCodec {
+	type Item = AsciiMessage;
+	type Error = std::io::Error;
+
+	fn decode(&mut self, src: &mut BytesMut) -> Result<Option<AsciiMessage>, std::io::Error> {
+		let Self { config, pending_echo, discarding, discarded } = self;
+		let terminator = config.rx_terminator.as_bytes();
+		loop {
+			let end = src.windows(terminator.len()).position(|window| window == terminator);
+
+			if *discarding {
+				match end {
+					Some(end) => {
+						discard(discarded, src, end + terminator.len());
+						*discarding = false;
+						continue;
+					},
+					None => {
+						// Keep the bytes that could be the start of the terminator.
+						let keep = (terminator.len() - 1).min(src.len());
+						discard(discarded, src, src.len() - keep);
+						return Ok(None);
+					},
+				}
+			}
+
+			if let Some(prompt) = &config.prompt {
+				let prompt = prompt.as_bytes();
+				if src.starts_with(prompt) {
+					src.advance(prompt.len());
+					return Ok(Some(AsciiMessage::Prompt));
+				}
+				if !src.is_empty() && prompt.starts_with(src) {
+					// Wait for more data to see if this is the prompt or a line.
+					return Ok(None);
+				}
+			}
+
+			let Some(end) = end else {
+				if src.len() > config.max_line_len {
+					*discarding = true;
+					continue;
+				}
+				return Ok(None);
+			};
+			if end > config.max_line_len {
+				discard(discarded, src, end + terminator.len());
+				continue;
+			}
+
+			let line = src.split_to(end);
+			src.advance(terminator.len());
+			let line = String::from_utf8_lossy(&line).into_owned();
+			if is_echo(pending_echo, &line) {
+				continue;
+			}
+			return Ok(Some(AsciiMessage::Line(line)));
+		}
+	}
+
+	fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<AsciiMessage>, std::io::Error> {
+		if let Some(message) = self.decode(src)? {
+			return Ok(Some(message));
+		}
+		// Return the last line even if it is not terminated.
+		if self.discarding || src.is_empty() {
+			let remaining = src.len();
+			discard(&mut self.discarded, src, remaining);
+			return Ok(None);
+		}
+		let line = src.split();
+		let line = String::from_utf8_lossy(&line).into_owned();
+		if is_echo(&mut self.pending_echo, &line) {
+			return Ok(None);
+		}
+		Ok(Some(AsciiMessage::Line(line)))
+	}
+}
+
+impl<T: AsRef<str>> Encoder<T> for AsciiCodec {
+	type Error = std::io::Error;
+
+	fn encode(&mut self, command: T, dst: &mut BytesMut) -> Result<(), std::io::Error> {
+		let command = command.as_ref();
+		dst.reserve(command.len() + self.config.tx_terminator.len());
+		dst.put_slice(command.as_bytes());
+		dst.put_slice(self.config.tx_terminator.as_bytes());
+		if self.config.suppress_echo {
+			self.pending_echo.push_back(command.to_owned());
+		}
+		Ok(())
+	}
+}
+
+/// Discard bytes from the start of the buffer.
+fn discard(discarded: &mut u64, src: &mut BytesMut, count: usize) {
+	src.advance(count);
+	*discarded += count as u64;
+}
+
+/// Check if a received line is the echo of a pending command, and forget the pending commands if it is not.
+fn is_echo(pending_echo: &mut VecDeque<String>, line: &str) -> bool {
+	let Some(command) = pending_echo.pop_front() else {
+		return false;
+	};
+	if line.trim_end_matches(['\r', '\n']) == command.trim_end_matches(['\r', '\n']) {
+		true
+	} else {
+		// The device does not echo (or not anymore), so do not wait for the other echoes either.
+		pending_echo.clear();
+		false
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	fn codec() -> AsciiCodec {
+		let mut config = AsciiConfig::new();
+		config.set_tx_terminator("\r");
+		config.set_rx_terminator("\r\n");
+		config.set_prompt(Some("> "));
+		AsciiCodec::new(config)
+	}
+
+	fn line(line: &str) -> Option<AsciiMessage> {
+		Some(AsciiMessage::Line(line.into()))
+	}
+
+	#[test]
+	fn encode_appends_terminator() {
+		let mut buffer = BytesMut::new();
+		codec().encode("*IDN?", &mut buffer).unwrap();
+		assert_eq!(&buffer[..], b"*IDN?\r");
+	}
+
+	#[test]
+	fn decode_lines_and_prompt() {
+		let mut codec = codec();
+		let mut buffer = BytesMut::from(&b"OK\r\nvalue: 3\r\n> "[..]);
+		assert_eq!(codec.decode(&mut buffer).unwrap(), line("OK"));
+		assert_eq!(codec.decode(&mut buffer).unwrap(), line("value: 3"));
+		assert_eq!(codec.decode(&mut buffer).unwrap(), Some(AsciiMessage::Prompt));
+		assert_eq!(codec.decode(&mut buffer).unwrap(), None);
+		assert!(buffer.is_empty());
+	}
+
+	#[test]
+	fn decode_waits_for_split_terminator_and_prompt() {
+		let mut codec = codec();
+		let mut buffer = BytesMut::from(&b"OK\r"[..]);
+		assert_eq!(codec.decode(&mut buffer).unwrap(), None);
+		buffer.extend_from_slice(b"\n>");
+		assert_eq!(codec.decode(&mut buffer).unwrap(), line("OK"));
+		assert_eq!(codec.decode(&mut buffer).unwrap(), None);
+		buffer.extend_from_slice(b" ");
+		assert_eq!(codec.decode(&mut buffer).unwrap(), Some(AsciiMessage::Prompt));
+	}
+
+	#[test]
+	fn line_starting_like_prompt() {
+		let mut codec = codec();
+		let mut buffer = BytesMut::from(&b">>\r\n"[..]);
+		assert_eq!(codec.decode(&mut buffer).unwrap(), line(">>"));
+	}
+
+	#[test]
+	fn round_trip_with_echo_suppression() {
+		let mut config = AsciiConfig::new();
+		config.set_suppress_echo(true);
+		let mut codec = AsciiCodec::new(config);
+		let mut buffer = BytesMut::new();
+		codec.encode("get", &mut buffer).unwrap();
+		assert_eq!(codec.decode(&mut buffer).unwrap(), None);
+		buffer.extend_from_slice(b"42\n");
+		assert_eq!(codec.decode(&mut buffer).unwrap(), line("42"));
+	}
+
+	#[test]
+	fn echo_suppression_stops_when_device_does_not_echo() {
+		let mut config = AsciiConfig::new();
+		config.set_suppress_echo(true);
+		let mut codec = AsciiCodec::new(config);
+		let mut output = BytesMut::new();
+		codec.encode("first", &mut output).unwrap();
+		codec.encode("second", &mut output).unwrap();
+		let mut buffer = BytesMut::from(&b"reply\nsecond\n"[..]);
+		assert_eq!(codec.decode(&mut buffer).unwrap(), line("reply"));
+		assert_eq!(codec.decode(&mut buffer).unwrap(), line("second"));
+	}
+
+	#[test]
+	fn decode_discards_long_lines() {
+		let mut config = AsciiConfig::new();
+		config.set_max_line_len(4);
+		let mut codec = AsciiCodec::new(config);
+		let mut buffer = BytesMut::from(&b"too long"[..]);
+		assert_eq!(codec.decode(&mut buffer).unwrap(), None);
+		buffer.extend_from_slice(b" line\nok\n");
+		assert_eq!(codec.decode(&mut buffer).unwrap(), line("ok"));
+		assert_eq!(codec.discarded_bytes(), 14);
+	}
+
+	#[test]
+	fn decode_eof_returns_unterminated_line() {
+		let mut codec = codec();
+		let mut buffer = BytesMut::from(&b"last"[..]);
+		assert_eq!(codec.decode(&mut buffer).unwrap(), None);
+		assert_eq!(codec.decode_eof(&mut buffer).unwrap(), line("last"));
+		assert_eq!(codec.decode_eof(&mut buffer).unwrap(), None);
+	}
+
+	#[test]
+	fn decode_replaces_invalid_utf8() {
+		let mut codec = AsciiCodec::new(AsciiConfig::new());
+		let mut buffer = BytesMut::from(&b"a\xFFb\n"[..]);
+		assert_eq!(codec.decode(&mut buffer).unwrap(), line("a\u{FFFD}b"));
+	}
+}
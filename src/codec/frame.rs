@@ -0,0 +1,593 @@
+//! A codec for binary frames with sync bytes, a length field and an optional checksum.
+
+use bytes::{Buf, BufMut, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::checksum;
+
+/// The layout of the length field of a frame.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum LengthField {
+	/// A single byte.
+	U8,
+
+	/// Two bytes in little endian byte order.
+	#[default]
+	U16Le,
+
+	/// Two bytes in big endian byte order.
+	U16Be,
+
+	/// Four bytes in little endian byte order.
+	U32Le,
+
+	/// Four bytes in big endian byte order.
+	U32Be,
+}
+
+impl LengthField {
+	/// Get the size of the length field in bytes.
+	pub fn size(self) -> usize {
+		match self {
+			Self::U8 => 1,
+			Self::U16Le | Self::U16Be => 2,
+			Self::U32Le | Self::U32Be => 4,
+		}
+	}
+
+	/// Get the maximum value that fits in the length field.
+	fn max_value(self) -> u64 {
+		match self {
+			Self::U8 => u8::MAX.into(),
+			Self::U16Le | Self::U16Be => u16::MAX.into(),
+			Self::U32Le | Self::U32Be => u32::MAX.into(),
+		}
+	}
+
+	/// Read the length field from the start of the data.
+	fn read(self, data: &[u8]) -> u64 {
+		match self {
+			Self::U8 => data[0].into(),
+			Self::U16Le => u16::from_le_bytes([data[0], data[1]]).into(),
+			Self::U16Be => u16::from_be_bytes([data[0], data[1]]).into(),
+			Self::U32Le => u32::from_le_bytes([data[0], data[1], data[2], data[3]]).into(),
+			Self::U32Be => u32::from_be_bytes([data[0], data[1], data[2], data[3]]).into(),
+		}
+	}
+
+	/// Write the length field.
+	///
+	/// The value must fit in the length field.
+	fn write(self, value: u64, dst: &mut BytesMut) {
+		match self {
+			Self::U8 => dst.put_u8(value as u8),
+			Self::U16Le => dst.put_u16_le(value as u16),
+			Self::U16Be => dst.put_u16(value as u16),
+			Self::U32Le => dst.put_u32_le(value as u32),
+			Self::U32Be => dst.put_u32(value as u32),
+		}
+	}
+}
+
+/// The checksum at the end of a frame.
+///
+/// The checksum is computed over all bytes of the frame after the sync bytes:
+/// the header, the length field and the payload.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum Checksum {
+	/// A [CRC-8/MAXIM][checksum::crc8_maxim] checksum.
+	Crc8Maxim,
+
+	/// A [CRC-16/MODBUS][checksum::crc16_modbus] checksum, in little endian byte order.
+	Crc16Modbus,
+
+	/// A [CRC-16/CCITT-FALSE][checksum::crc16_ccitt_false] checksum, in big endian byte order.
+	Crc16CcittFalse,
+
+	/// The [XOR][checksum::xor] of all bytes.
+	Xor,
+
+	/// A [longitudinal redundancy check][checksum::lrc].
+	Lrc,
+
+	/// An [8 bit Fletcher checksum][checksum::fletcher8], as used by the UBX protocol.
+	Fletcher8,
+}
+
+impl Checksum {
+	/// Get the size of the checksum in bytes.
+	pub fn size(self) -> usize {
+		match self {
+			Self::Crc8Maxim | Self::Xor | Self::Lrc => 1,
+			Self::Crc16Modbus | Self::Crc16CcittFalse | Self::Fletcher8 => 2,
+		}
+	}
+
+	/// Compute the checksum of the data.
+	///
+	/// Only the first [`Self::size()`] bytes of the returned array are used.
+	fn compute(self, data: &[u8]) -> [u8; 2] {
+		match self {
+			Self::Crc8Maxim => [checksum::crc8_maxim(data), 0],
+			Self::Crc16Modbus => checksum::crc16_modbus(data).to_le_bytes(),
+			Self::Crc16CcittFalse => checksum::crc16_ccitt_false(data).to_be_bytes(),
+			Self::Xor => [checksum::xor(data), 0],
+			Self::Lrc => [checksum::lrc(data), 0],
+			Self::Fletcher8 => checksum::fletcher8(data),
+		}
+	}
+}
+
+/// The layout of the frames for a [`FrameCodec`].
+///
+/// A frame consists of the following parts, in order:
+///
+/// * The sync bytes, which mark the start of a frame.
+/// * A header of a fixed size, which is not interpreted by the codec.
+/// * The length field, which holds the size of the payload.
+/// * The payload.
+/// * An optional checksum.
+#[derive(Debug, Clone)]
+pub struct FrameConfig {
+	sync: Vec<u8>,
+	header_len: usize,
+	length_field: LengthField,
+	length_adjustment: i64,
+	checksum: Option<Checksum>,
+	max_payload_len: usize,
+}
+
+impl Default for FrameConfig {
+	fn default() -> Self {
+		Self {
+			sync: Vec::new(),
+			header_len: 0,
+			length_field: LengthField::default(),
+			length_adjustment: 0,
+			checksum: None,
+			max_payload_len: 0xFFFF,
+		}
+	}
+}
+
+impl FrameConfig {
+	/// Create a new frame layout without sync bytes, header or checksum, and with a 16 bit little endian length field.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Set the sync bytes that mark the start of a frame.
+	///
+	/// The sync bytes are used to find the start of the next frame after receiving invalid data.
+	/// Without sync bytes, the decoder can only discard one byte at a time until it finds a valid frame,
+	/// so it is strongly recommended to use sync bytes together with a checksum.
+	pub fn set_sync(&mut self, sync: impl Into<Vec<u8>>) {
+		self.sync = sync.into();
+	}
+
+	/// Get the sync bytes that mark the start of a frame.
+	pub fn get_sync(&self) -> &[u8] {
+		&self.sync
+	}
+
+	/// Set the size of the header between the sync bytes and the length field.
+	pub fn set_header_len(&mut self, len: usize) {
+		self.header_len = len;
+	}
+
+	/// Get the size of the header between the sync bytes and the length field.
+	pub fn get_header_len(&self) -> usize {
+		self.header_len
+	}
+
+	/// Set the layout of the length field.
+	pub fn set_length_field(&mut self, length_field: LengthField) {
+		self.length_field = length_field;
+	}
+
+	/// Get the layout of the length field.
+	pub fn get_length_field(&self) -> LengthField {
+		self.length_field
+	}
+
+	/// Set the value that is added to the length field to get the size of the payload.
+	///
+	/// By default, the length field holds the size of the payload.
+	/// If the length field also counts other parts of the frame, such as the checksum, use a negative adjustment.
+	pub fn set_length_adjustment(&mut self, adjustment: i64) {
+		self.length_adjustment = adjustment;
+	}
+
+	/// Get the value that is added to the length field to get the size of the payload.
+	pub fn get_length_adjustment(&self) -> i64 {
+		self.length_adjustment
+	}
+
+	/// Set the checksum at the end of the frame, or `None` to disable the checksum.
+	pub fn set_checksum(&mut self, checksum: Option<Checksum>) {
+		self.checksum = checksum;
+	}
+
+	/// Get the checksum at the end of the frame.
+	pub fn get_checksum(&self) -> Option<Checksum> {
+		self.checksum
+	}
+
+	/// Set the maximum size of the payload.
+	///
+	/// A frame with a larger payload is treated as invalid data.
+	/// This also limits how much data the decoder waits for when corrupted data looks like the start of a frame with a large length.
+	///
+	/// The default maximum is 65535 bytes.
+	pub fn set_max_payload_len(&mut self, len: usize) {
+		self.max_payload_len = len;
+	}
+
+	/// Get the maximum size of the payload.
+	pub fn get_max_payload_len(&self) -> usize {
+		self.max_payload_len
+	}
+
+	/// Get the offset of the length field in the frame.
+	fn length_offset(&self) -> usize {
+		self.sync.len() + self.header_len
+	}
+
+	/// Get the size of the frame up to and including the length field.
+	fn prefix_len(&self) -> usize {
+		self.length_offset() + self.length_field.size()
+	}
+
+	/// Get the size of the checksum.
+	fn checksum_len(&self) -> usize {
+		self.checksum.map(Checksum::size).unwrap_or(0)
+	}
+}
+
+/// A frame decoded or encoded by a [`FrameCodec`].
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Frame {
+	/// The header between the sync bytes and the length field.
+	///
+	/// When encoding a frame, the header must have the size set with [`FrameConfig::set_header_len()`].
+	pub header: BytesMut,
+
+	/// The payload of the frame.
+	pub payload: BytesMut,
+}
+
+impl Frame {
+	/// Create a frame with an empty header and the given payload.
+	pub fn new(payload: impl Into<BytesMut>) -> Self {
+		Self {
+			header: BytesMut::new(),
+			payload: payload.into(),
+		}
+	}
+}
+
+/// A codec for binary frames with sync bytes, a length field and an optional checksum.
+///
+/// The decoder resynchronizes on invalid data:
+/// if the length is too large or the checksum does not match,
+/// it discards data up to the next occurrence of the sync bytes and tries again.
+/// The number of discarded bytes is available through [`Self::discarded_bytes()`].
+///
+/// # Example
+/// ```no_run
+/// # async fn example() -> std::io::Result<()> {
+/// use futures::{SinkExt, StreamExt};
+/// use serial2_tokio::SerialPort;
+/// use serial2_tokio::codec::{Checksum, Frame, FrameCodec, FrameConfig, LengthField};
+/// use tokio_util::codec::Framed;
+///
+/// let mut config = FrameConfig::new();
+/// config.set_sync([0xAA, 0x55]);
+/// config.set_header_len(1);
+/// config.set_length_field(LengthField::U16Be);
+/// config.set_checksum(Some(Checksum::Crc16CcittFalse));
+///
+/// let port = SerialPort::open("/dev/ttyUSB0", 115200)?;
+/// let mut framed = Framed::new(port, FrameCodec::new(config));
+/// framed.send(Frame { header: [0x01][..].into(), payload: b"hello"[..].into() }).await?;
+/// while let Some(frame) = framed.next().await {
+///     let frame = frame?;
+///     println!("type 0x{:02X}: {:02X?}", frame.header[0], frame.payload);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct FrameCodec {
+	config: FrameConfig,
+	discarded: u64,
+}
+
+impl FrameCodec {
+	/// Create a codec for the given frame layout.
+	pub fn new(config: FrameConfig) -> Self {
+		Self {
+			config,
+			discarded: 0,
+		}
+	}
+
+	/// Get the frame layout.
+	pub fn config(&self) -> &FrameConfig {
+		&self.config
+	}
+
+	/// Get the total number of bytes discarded by the decoder while searching for valid frames.
+	pub fn discarded_bytes(&self) -> u64 {
+		self.discarded
+	}
+
+	/// Get a mutable reference to the frame layout, for the codecs that are built on top of this codec.
+	pub(super) fn config_mut(&mut self) -> &mut FrameConfig {
+		&mut self.config
+	}
+
+	/// Discard bytes from the start of the buffer.
+	fn discard(&mut self, src: &mut BytesMut, count: usize) {
+		src.advance(count);
+		self.discarded += count as u64;
+	}
+
+	/// Discard data until the buffer starts with the sync bytes, or until no sync bytes can be found.
+	///
+	/// Returns `true` if the buffer starts with the sync bytes.
+	fn find_sync(&mut self, src: &mut BytesMut) -> bool {
+		let sync_len = self.config.sync.len();
+		if sync_len == 0 {
+			return true;
+		}
+		match src.windows(sync_len).position(|window| window == self.config.sync) {
+			Some(position) => {
+				self.discard(src, position);
+				true
+			},
+			None => {
+				// Keep the bytes that could be the start of the sync bytes.
+				let keep = (sync_len - 1).min(src.len());
+				self.discard(src, src.len() - keep);
+				false
+			},
+		}
+	}
+}
+
+impl Decoder for FrameCodec {
+	type Item = Frame;
+	type Error = std::io::Error;
+
+	fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Frame>, std::io::Error> {
+		loop {
+			if !self.find_sync(src) {
+				return Ok(None);
+			}
+
+			let config = &self.config;
+			let prefix_len = config.prefix_len();
+			if src.len() < prefix_len {
+				src.reserve(prefix_len - src.len());
+				return Ok(None);
+			}
+
+			let length = config.length_field.read(&src[config.length_offset()..]);
+			let payload_len = match usize::try_from((length as i64).saturating_add(config.length_adjustment)) {
+				Ok(len) if len <= config.max_payload_len => len,
+				_ => {
+					self.discard(src, 1);
+					continue;
+				},
+			};
+
+			let checksum_start = prefix_len + payload_len;
+			let frame_len = checksum_start + config.checksum_len();
+			if src.len() < frame_len {
+				src.reserve(frame_len - src.len());
+				return Ok(None);
+			}
+
+			if let Some(checksum) = config.checksum {
+				let expected = checksum.compute(&src[config.sync.len()..checksum_start]);
+				if src[checksum_start..frame_len] != expected[..checksum.size()] {
+					self.discard(src, 1);
+					continue;
+				}
+			}
+
+			let mut frame = src.split_to(frame_len);
+			frame.advance(config.sync.len());
+			let header = frame.split_to(config.header_len);
+			frame.advance(config.length_field.size());
+			frame.truncate(payload_len);
+			return Ok(Some(Frame { header, payload: frame }));
+		}
+	}
+
+	fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Frame>, std::io::Error> {
+		if let Some(frame) = self.decode(src)? {
+			return Ok(Some(frame));
+		}
+		// An incomplete frame at the end of the stream is treated as invalid data.
+		let remaining = src.len();
+		self.discard(src, remaining);
+		Ok(None)
+	}
+}
+
+impl Encoder<Frame> for FrameCodec {
+	type Error = std::io::Error;
+
+	fn encode(&mut self, frame: Frame, dst: &mut BytesMut) -> Result<(), std::io::Error> {
+		let config = &self.config;
+		if frame.header.len() != config.header_len {
+			return Err(std::io::Error::new(
+				std::io::ErrorKind::InvalidInput,
+				format!("frame header must be {} bytes, got {}", config.header_len, frame.header.len()),
+			));
+		}
+		if frame.payload.len() > config.max_payload_len {
+			return Err(std::io::Error::new(
+				std::io::ErrorKind::InvalidInput,
+				format!("frame payload exceeds the maximum of {} bytes", config.max_payload_len),
+			));
+		}
+		let length = match u64::try_from((frame.payload.len() as i64).saturating_sub(config.length_adjustment)) {
+			Ok(length) if length <= config.length_field.max_value() => length,
+			_ => return Err(std::io::Error::new(
+				std::io::ErrorKind::InvalidInput,
+				"frame payload size does not fit in the length field",
+			)),
+		};
+
+		let start = dst.len();
+		dst.reserve(config.prefix_len() + frame.payload.len() + config.checksum_len());
+		dst.put_slice(&config.sync);
+		dst.put_slice(&frame.header);
+		config.length_field.write(length, dst);
+		dst.put_slice(&frame.payload);
+		if let Some(checksum) = config.checksum {
+			let value = checksum.compute(&dst[start + config.sync.len()..]);
+			dst.put_slice(&value[..checksum.size()]);
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	fn codec() -> FrameCodec {
+		let mut config = FrameConfig::new();
+		config.set_sync([0xAA, 0x55]);
+		config.set_header_len(1);
+		config.set_length_field(LengthField::U16Be);
+		config.set_checksum(Some(Checksum::Crc16CcittFalse));
+		FrameCodec::new(config)
+	}
+
+	fn frame(header: &[u8], payload: &[u8]) -> Frame {
+		Frame {
+			header: header.into(),
+			payload: payload.into(),
+		}
+	}
+
+	#[test]
+	fn encode_known_frame() {
+		let mut buffer = BytesMut::new();
+		codec().encode(frame(&[0x01], b"123456789"), &mut buffer).unwrap();
+		let crc = checksum::crc16_ccitt_false(&[&[0x01, 0x00, 0x09][..], b"123456789"].concat());
+		let mut expected = vec![0xAA, 0x55, 0x01, 0x00, 0x09];
+		expected.extend_from_slice(b"123456789");
+		expected.extend_from_slice(&crc.to_be_bytes());
+		assert_eq!(buffer[..], expected[..]);
+	}
+
+	#[test]
+	fn round_trip() {
+		let mut codec = codec();
+		let mut buffer = BytesMut::new();
+		codec.encode(frame(&[0x01], b"hello"), &mut buffer).unwrap();
+		codec.encode(frame(&[0x02], b""), &mut buffer).unwrap();
+		assert_eq!(codec.decode(&mut buffer).unwrap(), Some(frame(&[0x01], b"hello")));
+		assert_eq!(codec.decode(&mut buffer).unwrap(), Some(frame(&[0x02], b"")));
+		assert_eq!(codec.decode(&mut buffer).unwrap(), None);
+		assert!(buffer.is_empty());
+		assert_eq!(codec.discarded_bytes(), 0);
+	}
+
+	#[test]
+	fn decode_partial_frame() {
+		let mut codec = codec();
+		let mut encoded = BytesMut::new();
+		codec.encode(frame(&[0x01], b"hello"), &mut encoded).unwrap();
+		let mut buffer = BytesMut::new();
+		for &byte in &encoded[..encoded.len() - 1] {
+			buffer.put_u8(byte);
+			assert_eq!(codec.decode(&mut buffer).unwrap(), None);
+		}
+		buffer.put_u8(encoded[encoded.len() - 1]);
+		assert_eq!(codec.decode(&mut buffer).unwrap(), Some(frame(&[0x01], b"hello")));
+	}
+
+	#[test]
+	fn decode_skips_garbage_and_bad_checksums() {
+		let mut codec = codec();
+		let mut corrupted = BytesMut::new();
+		codec.encode(frame(&[0x01], b"bad"), &mut corrupted).unwrap();
+		let last = corrupted.len() - 1;
+		corrupted[last] ^= 0xFF;
+
+		let mut buffer = BytesMut::from(&[0x00, 0xAA, 0x12][..]);
+		buffer.extend_from_slice(&corrupted);
+		codec.encode(frame(&[0x02], b"good"), &mut buffer).unwrap();
+		assert_eq!(codec.decode(&mut buffer).unwrap(), Some(frame(&[0x02], b"good")));
+		assert_eq!(codec.discarded_bytes(), 3 + corrupted.len() as u64);
+	}
+
+	#[test]
+	fn decode_rejects_too_large_length() {
+		let mut config = FrameConfig::new();
+		config.set_sync([0x7E]);
+		config.set_length_field(LengthField::U8);
+		config.set_max_payload_len(4);
+		let mut codec = FrameCodec::new(config);
+		let mut buffer = BytesMut::from(&[0x7E, 0x05, 0x7E, 0x02, b'o', b'k'][..]);
+		assert_eq!(codec.decode(&mut buffer).unwrap(), Some(Frame::new(&b"ok"[..])));
+		assert_eq!(codec.discarded_bytes(), 2);
+	}
+
+	#[test]
+	fn length_adjustment() {
+		// The length field counts the length field itself and the checksum.
+		let mut config = FrameConfig::new();
+		config.set_length_field(LengthField::U8);
+		config.set_length_adjustment(-2);
+		config.set_checksum(Some(Checksum::Xor));
+		let mut codec = FrameCodec::new(config);
+		let mut buffer = BytesMut::new();
+		codec.encode(Frame::new(&[0x10, 0x20][..]), &mut buffer).unwrap();
+		assert_eq!(buffer[..], [0x04, 0x10, 0x20, 0x04 ^ 0x10 ^ 0x20]);
+		assert_eq!(codec.decode(&mut buffer).unwrap(), Some(Frame::new(&[0x10, 0x20][..])));
+	}
+
+	#[test]
+	fn length_fields() {
+		for length_field in [LengthField::U8, LengthField::U16Le, LengthField::U16Be, LengthField::U32Le, LengthField::U32Be] {
+			let mut config = FrameConfig::new();
+			config.set_length_field(length_field);
+			let mut codec = FrameCodec::new(config);
+			let mut buffer = BytesMut::new();
+			codec.encode(Frame::new(&b"abc"[..]), &mut buffer).unwrap();
+			assert_eq!(buffer.len(), length_field.size() + 3);
+			assert_eq!(length_field.read(&buffer), 3);
+			assert_eq!(codec.decode(&mut buffer).unwrap(), Some(Frame::new(&b"abc"[..])));
+		}
+	}
+
+	#[test]
+	fn encode_rejects_invalid_frames() {
+		let mut buffer = BytesMut::new();
+		assert!(codec().encode(frame(&[], b"no header"), &mut buffer).is_err());
+
+		let mut config = FrameConfig::new();
+		config.set_length_field(LengthField::U8);
+		config.set_max_payload_len(1000);
+		assert!(FrameCodec::new(config).encode(Frame::new(vec![0; 256].as_slice()), &mut buffer).is_err());
+		assert!(buffer.is_empty());
+	}
+
+	#[test]
+	fn decode_eof_discards_incomplete_frame() {
+		let mut codec = codec();
+		let mut buffer = BytesMut::from(&[0xAA, 0x55, 0x01, 0x00][..]);
+		assert_eq!(codec.decode_eof(&mut buffer).unwrap(), None);
+		assert!(buffer.is_empty());
+		assert_eq!(codec.discarded_bytes(), 4);
+	}
+}
@@ -0,0 +1,426 @@
+//! A codec that frames MAVLink packets without decoding the messages.
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::checksum::{crc16_mcrf4xx, crc16_mcrf4xx_update};
+
+/// The start byte of a MAVLink 1 packet.
+const STX_V1: u8 = 0xFE;
+
+/// The start byte of a MAVLink 2 packet.
+const STX_V2: u8 = 0xFD;
+
+/// The size of the header of a MAVLink 1 packet, including the start byte.
+const HEADER_LEN_V1: usize = 6;
+
+/// The size of the header of a MAVLink 2 packet, including the start byte.
+const HEADER_LEN_V2: usize = 10;
+
+/// The size of the checksum.
+const CHECKSUM_LEN: usize = 2;
+
+/// The size of the signature of a signed MAVLink 2 packet.
+const SIGNATURE_LEN: usize = 13;
+
+/// The incompatibility flag for signed MAVLink 2 packets.
+const INCOMPAT_FLAG_SIGNED: u8 = 0x01;
+
+/// The version of the MAVLink protocol of a packet.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum MavlinkVersion {
+	/// MAVLink 1, with start byte `0xFE`.
+	V1,
+
+	/// MAVLink 2, with start byte `0xFD`.
+	V2,
+}
+
+/// A MAVLink packet framed by a [`MavlinkCodec`].
+///
+/// The packet holds the raw bytes of the packet, including the header, the checksum and the signature.
+/// The accessors only interpret the header, so the packet can be forwarded unchanged.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct MavlinkPacket {
+	data: Bytes,
+}
+
+impl MavlinkPacket {
+	/// Create a packet from raw bytes.
+	///
+	/// Returns an error of kind [`std::io::ErrorKind::InvalidData`] if the data is not exactly one MAVLink 1 or MAVLink 2 packet.
+	/// The checksum is not verified.
+	pub fn from_bytes(data: impl Into<Bytes>) -> std::io::Result<Self> {
+		let data = data.into();
+		match packet_len(&data) {
+			Some(len) if len == data.len() => Ok(Self { data }),
+			_ => Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "data is not a single MAVLink packet")),
+		}
+	}
+
+	/// Get the protocol version of the packet.
+	pub fn version(&self) -> MavlinkVersion {
+		if self.data[0] == STX_V1 {
+			MavlinkVersion::V1
+		} else {
+			MavlinkVersion::V2
+		}
+	}
+
+	/// Get the sequence number of the packet.
+	pub fn sequence(&self) -> u8 {
+		match self.version() {
+			MavlinkVersion::V1 => self.data[2],
+			MavlinkVersion::V2 => self.data[4],
+		}
+	}
+
+	/// Get the ID of the system that sent the packet.
+	pub fn system_id(&self) -> u8 {
+		match self.version() {
+			MavlinkVersion::V1 => self.data[3],
+			MavlinkVersion::V2 => self.data[5],
+		}
+	}
+
+	/// Get the ID of the component that sent the packet.
+	pub fn component_id(&self) -> u8 {
+		match self.version() {
+			MavlinkVersion::V1 => self.data[4],
+			MavlinkVersion::V2 => self.data[6],
+		}
+	}
+
+	/// Get the message ID of the packet.
+	pub fn message_id(&self) -> u32 {
+		match self.version() {
+			MavlinkVersion::V1 => self.data[5].into(),
+			MavlinkVersion::V2 => u32::from_le_bytes([self.data[7], self.data[8], self.data[9], 0]),
+		}
+	}
+
+	/// Check if the packet is a signed MAVLink 2 packet.
+	pub fn is_signed(&self) -> bool {
+		self.version() == MavlinkVersion::V2 && self.data[2] & INCOMPAT_FLAG_SIGNED != 0
+	}
+
+	/// Get the payload of the packet.
+	///
+	/// MAVLink 2 removes trailing zero bytes from the payload, so the payload may be shorter than the message definition.
+	pub fn payload(&self) -> &[u8] {
+		let start = header_len(self.data[0]);
+		&self.data[start..start + usize::from(self.data[1])]
+	}
+
+	/// Get the raw bytes of the packet.
+	pub fn as_bytes(&self) -> &[u8] {
+		&self.data
+	}
+
+	/// Consume the packet and return the raw bytes.
+	pub fn into_bytes(self) -> Bytes {
+		self.data
+	}
+}
+
+/// A codec that frames MAVLink 1 and MAVLink 2 packets, without decoding the messages.
+///
+/// This is useful to route MAVLink packets between telemetry radios, flight controllers and ground stations.
+/// The encoder writes packets unchanged, so packets can be forwarded without touching the checksum or signature.
+///
+/// The checksum of a MAVLink packet includes a "CRC extra" byte that depends on the message definition.
+/// Without a lookup function for these bytes, the decoder can not verify the checksum,
+/// so it relies only on the start byte and the length to find the packets.
+/// Use [`Self::set_crc_extra()`] to verify the checksum of known messages,
+/// which allows the decoder to reliably skip corrupted packets and noise.
+/// The number of discarded bytes is available through [`Self::discarded_bytes()`].
+///
+/// # Example
+/// ```no_run
+/// # async fn example() -> std::io::Result<()> {
+/// use futures::{SinkExt, StreamExt};
+/// use serial2_tokio::SerialPort;
+/// use serial2_tokio::codec::MavlinkCodec;
+/// use tokio_util::codec::Framed;
+///
+/// fn crc_extra(message_id: u32) -> Option<u8> {
+///     match message_id {
+///         0 => Some(50), // HEARTBEAT
+///         _ => None,
+///     }
+/// }
+///
+/// let mut codec = MavlinkCodec::new();
+/// codec.set_crc_extra(Some(crc_extra));
+/// let radio = SerialPort::open("/dev/ttyUSB0", 57600)?;
+/// let autopilot = SerialPort::open("/dev/ttyACM0", 115200)?;
+/// let mut radio = Framed::new(radio, codec.clone());
+/// let mut autopilot = Framed::new(autopilot, codec);
+/// while let Some(packet) = radio.next().await {
+///     let packet = packet?;
+///     println!("forwarding message {} from system {}", packet.message_id(), packet.system_id());
+///     autopilot.send(packet).await?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MavlinkCodec {
+	crc_extra: Option<fn(u32) -> Option<u8>>,
+	discarded: u64,
+}
+
+impl MavlinkCodec {
+	/// Create a codec that does not verify the checksum of packets.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Set the function that looks up the CRC extra byte for a message ID.
+	///
+	/// With a lookup function, the decoder verifies the checksum of packets and discards packets with an invalid checksum.
+	/// Packets with a message ID for which the function returns `None` are passed on without verification,
+	/// so that messages from unknown dialects can still be forwarded.
+	pub fn set_crc_extra(&mut self, crc_extra: Option<fn(u32) -> Option<u8>>) {
+		self.crc_extra = crc_extra;
+	}
+
+	/// Get the function that looks up the CRC extra byte for a message ID.
+	pub fn get_crc_extra(&self) -> Option<fn(u32) -> Option<u8>> {
+		self.crc_extra
+	}
+
+	/// Get the total number of bytes discarded by the decoder while searching for valid packets.
+	pub fn discarded_bytes(&self) -> u64 {
+		self.discarded
+	}
+
+	/// Discard bytes from the start of the buffer.
+	fn discard(&mut self, src: &mut BytesMut, count: usize) {
+		src.advance(count);
+		self.discarded += count as u64;
+	}
+
+	/// Verify the checksum of a complete packet, if the CRC extra byte of the message is known.
+	fn check_crc(&self, packet: &[u8]) -> bool {
+		let Some(crc_extra) = self.crc_extra else {
+			return true;
+		};
+		let message_id = if packet[0] == STX_V1 {
+			packet[5].into()
+		} else {
+			u32::from_le_bytes([packet[7], packet[8], packet[9], 0])
+		};
+		let Some(extra) = crc_extra(message_id) else {
+			return true;
+		};
+		let end = header_len(packet[0]) + usize::from(packet[1]);
+		let crc = crc16_mcrf4xx_update(crc16_mcrf4xx(&packet[1..end]), &[extra]);
+		packet[end..end + CHECKSUM_LEN] == crc.to_le_bytes()
+	}
+}
+
+impl Decoder for MavlinkCodec {
+	type Item = MavlinkPacket;
+	type Error = std::io::Error;
+
+	fn decode(&mut self, src: &mut BytesMut) -> Result<Option<MavlinkPacket>, std::io::Error> {
+		loop {
+			match src.iter().position(|&byte| byte == STX_V1 || byte == STX_V2) {
+				Some(start) => self.discard(src, start),
+				None => {
+					let len = src.len();
+					self.discard(src, len);
+					return Ok(None);
+				},
+			}
+
+			let header_len = header_len(src[0]);
+			if src.len() < header_len {
+				return Ok(None);
+			}
+			// Packets with unknown incompatibility flags must be dropped.
+			if src[0] == STX_V2 && src[2] & !INCOMPAT_FLAG_SIGNED != 0 {
+				self.discard(src, 1);
+				continue;
+			}
+
+			let Some(len) = packet_len(src) else {
+				return Ok(None);
+			};
+			if src.len() < len {
+				src.reserve(len - src.len());
+				return Ok(None);
+			}
+			if !self.check_crc(&src[..len]) {
+				self.discard(src, 1);
+				continue;
+			}
+			let data = src.split_to(len).freeze();
+			return Ok(Some(MavlinkPacket { data }));
+		}
+	}
+
+	fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<MavlinkPacket>, std::io::Error> {
+		if let Some(packet) = self.decode(src)? {
+			return Ok(Some(packet));
+		}
+		// An incomplete packet at the end of the stream is treated as invalid data.
+		let remaining = src.len();
+		self.discard(src, remaining);
+		Ok(None)
+	}
+}
+
+impl Encoder<MavlinkPacket> for MavlinkCodec {
+	type Error = std::io::Error;
+
+	fn encode(&mut self, packet: MavlinkPacket, dst: &mut BytesMut) -> Result<(), std::io::Error> {
+		dst.put_slice(&packet.data);
+		Ok(())
+	}
+}
+
+/// Get the size of the header for a start byte.
+fn header_len(stx: u8) -> usize {
+	if stx == STX_V1 {
+		HEADER_LEN_V1
+	} else {
+		HEADER_LEN_V2
+	}
+}
+
+/// Get the total size of the packet at the start of the data, or `None` if the header is not complete or not valid.
+fn packet_len(data: &[u8]) -> Option<usize> {
+	let stx = *data.first()?;
+	if stx != STX_V1 && stx != STX_V2 {
+		return None;
+	}
+	let header_len = header_len(stx);
+	if data.len() < header_len {
+		return None;
+	}
+	let signature_len = if stx == STX_V2 && data[2] & INCOMPAT_FLAG_SIGNED != 0 {
+		SIGNATURE_LEN
+	} else {
+		0
+	};
+	Some(header_len + usize::from(data[1]) + CHECKSUM_LEN + signature_len)
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	/// A MAVLink 1 HEARTBEAT from an ArduPilot quadrotor.
+	const HEARTBEAT_V1: [u8; 17] = [
+		0xFE, 0x09, 0x4E, 0x01, 0x01, 0x00,
+		0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0x51, 0x04, 0x03,
+		0x1C, 0x7F,
+	];
+
+	/// The same HEARTBEAT as MAVLink 2 packet.
+	const HEARTBEAT_V2: [u8; 21] = [
+		0xFD, 0x09, 0x00, 0x00, 0x4E, 0x01, 0x01, 0x00, 0x00, 0x00,
+		0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0x51, 0x04, 0x03,
+		0x72, 0xE4,
+	];
+
+	fn crc_extra(message_id: u32) -> Option<u8> {
+		match message_id {
+			0 => Some(50),
+			_ => None,
+		}
+	}
+
+	fn verifying_codec() -> MavlinkCodec {
+		let mut codec = MavlinkCodec::new();
+		codec.set_crc_extra(Some(crc_extra));
+		codec
+	}
+
+	#[test]
+	fn decode_heartbeat_v1() {
+		let mut buffer = BytesMut::from(&HEARTBEAT_V1[..]);
+		let packet = verifying_codec().decode(&mut buffer).unwrap().unwrap();
+		assert_eq!(packet.version(), MavlinkVersion::V1);
+		assert_eq!(packet.sequence(), 0x4E);
+		assert_eq!(packet.system_id(), 1);
+		assert_eq!(packet.component_id(), 1);
+		assert_eq!(packet.message_id(), 0);
+		assert!(!packet.is_signed());
+		assert_eq!(packet.payload(), &HEARTBEAT_V1[6..15]);
+		assert_eq!(packet.as_bytes(), HEARTBEAT_V1);
+		assert!(buffer.is_empty());
+	}
+
+	#[test]
+	fn decode_heartbeat_v2() {
+		let mut buffer = BytesMut::from(&HEARTBEAT_V2[..]);
+		let packet = verifying_codec().decode(&mut buffer).unwrap().unwrap();
+		assert_eq!(packet.version(), MavlinkVersion::V2);
+		assert_eq!(packet.sequence(), 0x4E);
+		assert_eq!(packet.message_id(), 0);
+		assert_eq!(packet.payload(), &HEARTBEAT_V2[10..19]);
+	}
+
+	#[test]
+	fn decode_signed_packet() {
+		let mut data = HEARTBEAT_V2.to_vec();
+		data[2] |= INCOMPAT_FLAG_SIGNED;
+		data.extend_from_slice(&[0xAA; SIGNATURE_LEN]);
+		let mut buffer = BytesMut::from(&data[..]);
+		let packet = MavlinkCodec::new().decode(&mut buffer).unwrap().unwrap();
+		assert!(packet.is_signed());
+		assert_eq!(packet.as_bytes(), data);
+	}
+
+	#[test]
+	fn decode_skips_noise_and_bad_checksums() {
+		let mut codec = verifying_codec();
+		let mut corrupted = HEARTBEAT_V1;
+		corrupted[16] ^= 0xFF;
+		let mut buffer = BytesMut::from(&[0x00, 0x55][..]);
+		buffer.extend_from_slice(&corrupted);
+		buffer.extend_from_slice(&HEARTBEAT_V2);
+		let packet = codec.decode(&mut buffer).unwrap().unwrap();
+		assert_eq!(packet.as_bytes(), HEARTBEAT_V2);
+		assert_eq!(codec.discarded_bytes(), 2 + corrupted.len() as u64);
+	}
+
+	#[test]
+	fn decode_without_crc_extra_does_not_verify() {
+		let mut corrupted = HEARTBEAT_V1;
+		corrupted[16] ^= 0xFF;
+		let mut buffer = BytesMut::from(&corrupted[..]);
+		let packet = MavlinkCodec::new().decode(&mut buffer).unwrap().unwrap();
+		assert_eq!(packet.as_bytes(), corrupted);
+	}
+
+	#[test]
+	fn decode_partial_packet() {
+		let mut codec = verifying_codec();
+		let mut buffer = BytesMut::from(&HEARTBEAT_V2[..12]);
+		assert_eq!(codec.decode(&mut buffer).unwrap(), None);
+		buffer.extend_from_slice(&HEARTBEAT_V2[12..]);
+		assert_eq!(codec.decode(&mut buffer).unwrap().unwrap().as_bytes(), HEARTBEAT_V2);
+		assert_eq!(codec.discarded_bytes(), 0);
+	}
+
+	#[test]
+	fn round_trip() {
+		let mut codec = verifying_codec();
+		let packet = MavlinkPacket::from_bytes(&HEARTBEAT_V1[..]).unwrap();
+		let mut buffer = BytesMut::new();
+		codec.encode(packet.clone(), &mut buffer).unwrap();
+		assert_eq!(buffer[..], HEARTBEAT_V1);
+		assert_eq!(codec.decode(&mut buffer).unwrap(), Some(packet));
+	}
+
+	#[test]
+	fn from_bytes_rejects_invalid_data() {
+		assert!(MavlinkPacket::from_bytes(&HEARTBEAT_V1[..16]).is_err());
+		assert!(MavlinkPacket::from_bytes([&HEARTBEAT_V1[..], &[0]].concat()).is_err());
+		assert!(MavlinkPacket::from_bytes(&b"hello"[..]).is_err());
+	}
+}
@@ -0,0 +1,23 @@
+//! Frame codecs for use with [`tokio_util::codec`].
+//!
+//! The codecs in this module implement the [`Decoder`][tokio_util::codec::Decoder] and [`Encoder`][tokio_util::codec::Encoder] traits,
+//! so they can be used with [`Framed`][tokio_util::codec::Framed], [`FramedRead`][tokio_util::codec::FramedRead] and [`FramedWrite`][tokio_util::codec::FramedWrite]
+//! to turn a [`SerialPort`][crate::SerialPort] into a stream and sink of frames.
+//!
+//! Serial links can corrupt, drop or insert bytes.
+//! Unlike most codecs for reliable transports, the decoders in this module do not give up when they receive invalid data.
+//! Instead, they discard data until they find the start of the next valid frame.
+//!
+//! This module is only available when the `codec` feature is enabled.
+
+mod ascii;
+mod frame;
+mod mavlink;
+mod ppp;
+mod ubx;
+
+pub use ascii::{AsciiCodec, AsciiConfig, AsciiMessage};
+pub use frame::{Checksum, Frame, FrameCodec, FrameConfig, LengthField};
+pub use mavlink::{MavlinkCodec, MavlinkPacket, MavlinkVersion};
+pub use ppp::{PppCodec, PppFrame};
+pub use ubx::{UbxCodec, UbxFrame};
@@ -0,0 +1,444 @@
+//! A codec for the HDLC-like framing of PPP on asynchronous serial links (RFC 1662).
+
+use bytes::{Buf, BufMut, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+/// The flag sequence that separates frames.
+const FLAG: u8 = 0x7E;
+
+/// The control escape octet.
+const ESCAPE: u8 = 0x7D;
+
+/// The all-stations address and the unnumbered information control field.
+const ADDRESS_CONTROL: [u8; 2] = [0xFF, 0x03];
+
+/// The remainder of the frame check sequence over a frame including its FCS.
+const GOOD_FCS: u16 = 0xF0B8;
+
+/// The default async control character map: escape all control characters.
+const DEFAULT_ACCM: u32 = 0xFFFF_FFFF;
+
+/// The default maximum receive unit.
+const DEFAULT_MRU: usize = 1500;
+
+/// A PPP frame decoded or encoded by a [`PppCodec`].
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct PppFrame {
+	/// The protocol of the information field, like [`Self::LCP`] or [`Self::IPV4`].
+	pub protocol: u16,
+
+	/// The information field: the packet of the protocol, including any padding.
+	pub information: BytesMut,
+}
+
+impl PppFrame {
+	/// Link Control Protocol.
+	pub const LCP: u16 = 0xC021;
+
+	/// Password Authentication Protocol.
+	pub const PAP: u16 = 0xC023;
+
+	/// Challenge Handshake Authentication Protocol.
+	pub const CHAP: u16 = 0xC223;
+
+	/// Internet Protocol Control Protocol.
+	pub const IPCP: u16 = 0x8021;
+
+	/// IPv6 Control Protocol.
+	pub const IPV6CP: u16 = 0x8057;
+
+	/// Internet Protocol version 4.
+	pub const IPV4: u16 = 0x0021;
+
+	/// Internet Protocol version 6.
+	pub const IPV6: u16 = 0x0057;
+
+	/// Create a frame with the given protocol and information field.
+	pub fn new(protocol: u16, information: impl Into<BytesMut>) -> Self {
+		Self {
+			protocol,
+			information: information.into(),
+		}
+	}
+}
+
+/// A codec for the HDLC-like framing of PPP on asynchronous serial links, as used by dial-up, cellular and satellite modems.
+///
+/// The codec takes care of the framing layer described in RFC 1662:
+/// the flag sequences, the escaping of control characters according to the async control character map (ACCM),
+/// the address and control fields, the protocol field and the 16 bit frame check sequence.
+/// The decoded [`PppFrame`]s can be handed to a PPP implementation that handles LCP negotiation, authentication and the network protocols.
+///
+/// The PPP implementation must tell the codec about the negotiated LCP options:
+/// the ACCM with [`Self::set_tx_accm()`] and [`Self::set_rx_accm()`],
+/// and address/control and protocol field compression with [`Self::set_address_control_compression()`] and [`Self::set_protocol_compression()`].
+/// Received frames are always accepted with and without compression.
+/// LCP frames are always sent without compression and with all control characters escaped, as required by RFC 1662.
+///
+/// Frames with an invalid frame check sequence, frames that were aborted by the sender,
+/// and frames with an information field larger than the maximum receive unit are discarded.
+/// The number of discarded bytes is available through [`Self::discarded_bytes()`].
+///
+/// # Example
+/// ```no_run
+/// # async fn example() -> std::io::Result<()> {
+/// use futures::{SinkExt, StreamExt};
+/// use serial2_tokio::SerialPort;
+/// use serial2_tokio::codec::{PppCodec, PppFrame};
+/// use tokio_util::codec::Framed;
+///
+/// let port = SerialPort::open("/dev/ttyUSB2", 115200)?;
+/// let mut framed = Framed::new(port, PppCodec::new());
+///
+/// // Send an LCP Configure-Request without options.
+/// framed.send(PppFrame::new(PppFrame::LCP, &[0x01, 0x01, 0x00, 0x04][..])).await?;
+/// while let Some(frame) = framed.next().await {
+///     let frame = frame?;
+///     println!("protocol 0x{:04X}: {:02X?}", frame.protocol, frame.information);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct PppCodec {
+	tx_accm: u32,
+	rx_accm: u32,
+	address_control_compression: bool,
+	protocol_compression: bool,
+	mru: usize,
+	discarded: u64,
+}
+
+impl Default for PppCodec {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl PppCodec {
+	/// Create a new codec with the default settings of an unconfigured link.
+	///
+	/// All control characters are escaped and no compression is used.
+	/// The maximum receive unit is 1500 bytes.
+	pub fn new() -> Self {
+		Self {
+			tx_accm: DEFAULT_ACCM,
+			rx_accm: DEFAULT_ACCM,
+			address_control_compression: false,
+			protocol_compression: false,
+			mru: DEFAULT_MRU,
+			discarded: 0,
+		}
+	}
+
+	/// Set the async control character map for transmitted frames.
+	///
+	/// Each bit corresponds to a control character, with the least significant bit for `0x00`.
+	/// The control characters with a bit set are escaped in transmitted frames.
+	/// Set this to the ACCM that the peer requested during LCP negotiation.
+	///
+	/// The flag sequence and the control escape octet are always escaped.
+	pub fn set_tx_accm(&mut self, accm: u32) {
+		self.tx_accm = accm;
+	}
+
+	/// Get the async control character map for transmitted frames.
+	pub fn get_tx_accm(&self) -> u32 {
+		self.tx_accm
+	}
+
+	/// Set the async control character map for received frames.
+	///
+	/// The control characters with a bit set are expected to be escaped by the peer.
+	/// If they are received without escaping, they were inserted by the modem or the serial link (for example for software flow control),
+	/// and they are removed from the received data.
+	/// Set this to the ACCM that you requested during LCP negotiation.
+	pub fn set_rx_accm(&mut self, accm: u32) {
+		self.rx_accm = accm;
+	}
+
+	/// Get the async control character map for received frames.
+	pub fn get_rx_accm(&self) -> u32 {
+		self.rx_accm
+	}
+
+	/// Enable or disable address and control field compression for transmitted frames.
+	///
+	/// Only enable this if the peer accepted the address and control field compression option during LCP negotiation.
+	pub fn set_address_control_compression(&mut self, enable: bool) {
+		self.address_control_compression = enable;
+	}
+
+	/// Check if address and control field compression is enabled for transmitted frames.
+	pub fn get_address_control_compression(&self) -> bool {
+		self.address_control_compression
+	}
+
+	/// Enable or disable protocol field compression for transmitted frames.
+	///
+	/// Only enable this if the peer accepted the protocol field compression option during LCP negotiation.
+	pub fn set_protocol_compression(&mut self, enable: bool) {
+		self.protocol_compression = enable;
+	}
+
+	/// Check if protocol field compression is enabled for transmitted frames.
+	pub fn get_protocol_compression(&self) -> bool {
+		self.protocol_compression
+	}
+
+	/// Set the maximum receive unit: the maximum length of the information field of received frames.
+	///
+	/// Received frames with a larger information field are discarded.
+	/// The default is 1500 bytes.
+	pub fn set_mru(&mut self, mru: usize) {
+		self.mru = mru;
+	}
+
+	/// Get the maximum receive unit.
+	pub fn get_mru(&self) -> usize {
+		self.mru
+	}
+
+	/// Get the total number of bytes discarded by the decoder because they were not part of a valid frame.
+	pub fn discarded_bytes(&self) -> u64 {
+		self.discarded
+	}
+
+	/// Get the maximum length of a frame between flags, including escape octets.
+	fn max_escaped_len(&self) -> usize {
+		// Address, control, protocol and FCS, all escaped.
+		2 * (self.mru + 8)
+	}
+
+	/// Remove the escaping of the data of a received frame, and check the frame check sequence.
+	///
+	/// Returns `None` if the frame is invalid.
+	fn unescape(&self, raw: &[u8]) -> Option<Vec<u8>> {
+		let mut data = Vec::with_capacity(raw.len());
+		let mut escape = false;
+		for &byte in raw {
+			if is_mapped(self.rx_accm, byte) {
+				continue;
+			} else if escape {
+				data.push(byte ^ 0x20);
+				escape = false;
+			} else if byte == ESCAPE {
+				escape = true;
+			} else {
+				data.push(byte);
+			}
+		}
+		// A frame that ends with the control escape octet was aborted by the sender.
+		if escape || data.len() < 4 || crate::checksum::crc16_mcrf4xx(&data) != GOOD_FCS {
+			return None;
+		}
+		data.truncate(data.len() - 2);
+		Some(data)
+	}
+
+	/// Parse the address, control and protocol fields of a received frame.
+	fn parse(&self, data: &[u8]) -> Option<PppFrame> {
+		let data = data.strip_prefix(&ADDRESS_CONTROL[..]).unwrap_or(data);
+		let (protocol, information) = match data {
+			[protocol, information @ ..] if protocol & 1 == 1 => (u16::from(*protocol), information),
+			[high, low, information @ ..] if low & 1 == 1 => (u16::from_be_bytes([*high, *low]), information),
+			_ => return None,
+		};
+		if information.len() > self.mru {
+			return None;
+		}
+		Some(PppFrame::new(protocol, information))
+	}
+}
+
+impl Decoder for PppCodec {
+	type Item = PppFrame;
+	type Error = std::io::Error;
+
+	fn decode(&mut self, src: &mut BytesMut) -> Result<Option<PppFrame>, std::io::Error> {
+		loop {
+			let Some(end) = src.iter().position(|&byte| byte == FLAG) else {
+				if src.len() > self.max_escaped_len() {
+					self.discarded += src.len() as u64;
+					src.clear();
+				}
+				return Ok(None);
+			};
+			let raw = src.split_to(end);
+			src.advance(1);
+			if raw.is_empty() {
+				continue;
+			}
+			match self.unescape(&raw).and_then(|data| self.parse(&data)) {
+				Some(frame) => return Ok(Some(frame)),
+				None => self.discarded += raw.len() as u64,
+			}
+		}
+	}
+
+	fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<PppFrame>, std::io::Error> {
+		if let Some(frame) = self.decode(src)? {
+			return Ok(Some(frame));
+		}
+		// A frame without closing flag at the end of the stream is treated as invalid data.
+		self.discarded += src.len() as u64;
+		src.clear();
+		Ok(None)
+	}
+}
+
+impl Encoder<PppFrame> for PppCodec {
+	type Error = std::io::Error;
+
+	fn encode(&mut self, frame: PppFrame, dst: &mut BytesMut) -> Result<(), std::io::Error> {
+		if frame.protocol & 1 == 0 || frame.protocol & 0x100 != 0 {
+			return Err(std::io::Error::new(
+				std::io::ErrorKind::InvalidInput,
+				format!("invalid PPP protocol number: 0x{:04X}", frame.protocol),
+			));
+		}
+
+		// LCP frames must be sent as if no options have been negotiated.
+		let negotiated = frame.protocol != PppFrame::LCP;
+		let accm = if negotiated { self.tx_accm } else { DEFAULT_ACCM };
+
+		let mut data = Vec::with_capacity(frame.information.len() + 6);
+		if !(negotiated && self.address_control_compression) {
+			data.extend_from_slice(&ADDRESS_CONTROL);
+		}
+		if negotiated && self.protocol_compression && frame.protocol < 0x100 {
+			data.push(frame.protocol as u8);
+		} else {
+			data.extend_from_slice(&frame.protocol.to_be_bytes());
+		}
+		data.extend_from_slice(&frame.information);
+		// The FCS is CRC-16/X-25, which is CRC-16/MCRF4XX with the result inverted.
+		let fcs = !crate::checksum::crc16_mcrf4xx(&data);
+		data.extend_from_slice(&fcs.to_le_bytes());
+
+		dst.reserve(data.len() * 2 + 2);
+		dst.put_u8(FLAG);
+		for byte in data {
+			if byte == FLAG || byte == ESCAPE || is_mapped(accm, byte) {
+				dst.put_u8(ESCAPE);
+				dst.put_u8(byte ^ 0x20);
+			} else {
+				dst.put_u8(byte);
+			}
+		}
+		dst.put_u8(FLAG);
+		Ok(())
+	}
+}
+
+/// Check if a byte is a control character that is set in an async control character map.
+fn is_mapped(accm: u32, byte: u8) -> bool {
+	byte < 0x20 && accm & (1 << byte) != 0
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	/// An LCP Configure-Request without options, as sent on an unconfigured link.
+	const LCP_CONFIGURE_REQUEST: [u8; 17] = [
+		0x7E, 0xFF, 0x7D, 0x23, 0xC0, 0x21, 0x7D, 0x21, 0x7D, 0x21, 0x7D, 0x20, 0x7D, 0x24, 0xD1, 0xB5, 0x7E,
+	];
+
+	fn lcp_configure_request() -> PppFrame {
+		PppFrame::new(PppFrame::LCP, &[0x01, 0x01, 0x00, 0x04][..])
+	}
+
+	#[test]
+	fn fcs_check_value() {
+		// The FCS of RFC 1662 is CRC-16/X-25.
+		assert_eq!(!crate::checksum::crc16_mcrf4xx(b"123456789"), 0x906E);
+	}
+
+	#[test]
+	fn fcs_of_frame_with_fcs_is_good_fcs() {
+		let mut data = vec![0xFF, 0x03, 0xC0, 0x21, 0x01, 0x01, 0x00, 0x04];
+		let fcs = !crate::checksum::crc16_mcrf4xx(&data);
+		data.extend_from_slice(&fcs.to_le_bytes());
+		assert_eq!(crate::checksum::crc16_mcrf4xx(&data), GOOD_FCS);
+	}
+
+	#[test]
+	fn encode_lcp_frame() {
+		let mut buffer = BytesMut::new();
+		PppCodec::new().encode(lcp_configure_request(), &mut buffer).unwrap();
+		assert_eq!(buffer[..], LCP_CONFIGURE_REQUEST);
+	}
+
+	#[test]
+	fn decode_lcp_frame() {
+		let mut buffer = BytesMut::from(&LCP_CONFIGURE_REQUEST[..]);
+		let mut codec = PppCodec::new();
+		assert_eq!(codec.decode(&mut buffer).unwrap(), Some(lcp_configure_request()));
+		assert_eq!(codec.decode(&mut buffer).unwrap(), None);
+		assert_eq!(codec.discarded_bytes(), 0);
+	}
+
+	#[test]
+	fn lcp_ignores_negotiated_options() {
+		let mut codec = PppCodec::new();
+		codec.set_tx_accm(0);
+		codec.set_address_control_compression(true);
+		codec.set_protocol_compression(true);
+		let mut buffer = BytesMut::new();
+		codec.encode(lcp_configure_request(), &mut buffer).unwrap();
+		assert_eq!(buffer[..], LCP_CONFIGURE_REQUEST);
+	}
+
+	#[test]
+	fn round_trip_with_compression() {
+		let mut codec = PppCodec::new();
+		codec.set_tx_accm(0);
+		codec.set_rx_accm(0);
+		codec.set_address_control_compression(true);
+		codec.set_protocol_compression(true);
+		let frame = PppFrame::new(PppFrame::IPV4, &[0x45, 0x00, 0x7E, 0x7D, 0x11, 0x01][..]);
+		let mut buffer = BytesMut::new();
+		codec.encode(frame.clone(), &mut buffer).unwrap();
+		assert_eq!(buffer[..5], [FLAG, 0x21, 0x45, 0x00, ESCAPE]);
+		assert!(!buffer[1..buffer.len() - 1].contains(&FLAG));
+		assert_eq!(codec.decode(&mut buffer).unwrap(), Some(frame));
+	}
+
+	#[test]
+	fn decode_removes_unescaped_mapped_characters() {
+		// XON and XOFF inserted by the modem.
+		let mut buffer = BytesMut::from(&LCP_CONFIGURE_REQUEST[..6]);
+		buffer.extend_from_slice(&[0x11, 0x13]);
+		buffer.extend_from_slice(&LCP_CONFIGURE_REQUEST[6..]);
+		assert_eq!(PppCodec::new().decode(&mut buffer).unwrap(), Some(lcp_configure_request()));
+	}
+
+	#[test]
+	fn decode_discards_bad_and_aborted_frames() {
+		let mut codec = PppCodec::new();
+		let mut corrupted = LCP_CONFIGURE_REQUEST;
+		corrupted[4] ^= 0x01;
+		let mut buffer = BytesMut::from(&corrupted[..]);
+		buffer.extend_from_slice(&[0x41, 0x42, ESCAPE, FLAG]);
+		buffer.extend_from_slice(&LCP_CONFIGURE_REQUEST);
+		assert_eq!(codec.decode(&mut buffer).unwrap(), Some(lcp_configure_request()));
+		assert_eq!(codec.discarded_bytes(), 15 + 3);
+	}
+
+	#[test]
+	fn decode_discards_frames_larger_than_mru() {
+		let mut codec = PppCodec::new();
+		codec.set_mru(3);
+		let mut buffer = BytesMut::from(&LCP_CONFIGURE_REQUEST[..]);
+		assert_eq!(codec.decode(&mut buffer).unwrap(), None);
+		assert_eq!(codec.discarded_bytes(), 15);
+	}
+
+	#[test]
+	fn encode_rejects_invalid_protocol() {
+		let mut buffer = BytesMut::new();
+		assert!(PppCodec::new().encode(PppFrame::new(0x0020, &[][..]), &mut buffer).is_err());
+		assert!(PppCodec::new().encode(PppFrame::new(0x0121, &[][..]), &mut buffer).is_err());
+	}
+}
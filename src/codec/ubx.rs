@@ -0,0 +1,203 @@
+//! A codec for the UBX binary protocol of u-blox GNSS receivers.
+
+use bytes::BytesMut;
+use tokio_util::codec::{Decoder, Encoder};
+
+use super::{Checksum, Frame, FrameCodec, FrameConfig, LengthField};
+
+/// The sync bytes at the start of each UBX message.
+const SYNC: [u8; 2] = [0xB5, 0x62];
+
+/// The default maximum payload length.
+const DEFAULT_MAX_PAYLOAD_LEN: usize = 8192;
+
+/// A message decoded or encoded by a [`UbxCodec`].
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct UbxFrame {
+	/// The message class, like `0x01` for navigation results or `0x06` for configuration messages.
+	pub class: u8,
+
+	/// The message ID within the class.
+	pub id: u8,
+
+	/// The payload of the message.
+	pub payload: BytesMut,
+}
+
+impl UbxFrame {
+	/// Create a message with the given class, ID and payload.
+	pub fn new(class: u8, id: u8, payload: impl Into<BytesMut>) -> Self {
+		Self {
+			class,
+			id,
+			payload: payload.into(),
+		}
+	}
+}
+
+/// A codec for the UBX binary protocol of u-blox GNSS receivers.
+///
+/// UBX messages start with the sync bytes `0xB5 0x62`, followed by the message class, the message ID,
+/// the payload length as 16 bit little endian number, the payload and an [8 bit Fletcher checksum][crate::checksum::fletcher8].
+/// The decoder yields the class, ID and payload of each message, without interpreting the payload.
+///
+/// Receivers often send NMEA sentences on the same port.
+/// The decoder discards everything that is not a valid UBX message, including NMEA sentences.
+/// The number of discarded bytes is available through [`Self::discarded_bytes()`].
+///
+/// # Example
+/// ```no_run
+/// # async fn example() -> std::io::Result<()> {
+/// use futures::{SinkExt, StreamExt};
+/// use serial2_tokio::SerialPort;
+/// use serial2_tokio::codec::{UbxCodec, UbxFrame};
+/// use tokio_util::codec::Framed;
+///
+/// let port = SerialPort::open("/dev/ttyACM0", 115200)?;
+/// let mut framed = Framed::new(port, UbxCodec::new());
+///
+/// // Poll the receiver and software version (MON-VER).
+/// framed.send(UbxFrame::new(0x0A, 0x04, &[][..])).await?;
+/// while let Some(frame) = framed.next().await {
+///     let frame = frame?;
+///     println!("class 0x{:02X}, id 0x{:02X}: {} bytes", frame.class, frame.id, frame.payload.len());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct UbxCodec {
+	inner: FrameCodec,
+}
+
+impl Default for UbxCodec {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl UbxCodec {
+	/// Create a new codec.
+	pub fn new() -> Self {
+		let mut config = FrameConfig::new();
+		config.set_sync(SYNC);
+		config.set_header_len(2);
+		config.set_length_field(LengthField::U16Le);
+		config.set_checksum(Some(Checksum::Fletcher8));
+		config.set_max_payload_len(DEFAULT_MAX_PAYLOAD_LEN);
+		Self {
+			inner: FrameCodec::new(config),
+		}
+	}
+
+	/// Set the maximum payload length of a message.
+	///
+	/// Received messages with a larger payload are treated as invalid data, and larger messages can not be encoded.
+	/// A lower maximum helps the decoder to recover faster from a corrupted length field,
+	/// because it does not have to wait for the full (invalid) length before it can verify the checksum.
+	///
+	/// The default maximum is 8192 bytes, which is enough for the messages of current u-blox receivers.
+	pub fn set_max_payload_len(&mut self, len: usize) {
+		self.inner.config_mut().set_max_payload_len(len);
+	}
+
+	/// Get the maximum payload length of a message.
+	pub fn get_max_payload_len(&self) -> usize {
+		self.inner.config().get_max_payload_len()
+	}
+
+	/// Get the total number of bytes discarded by the decoder while searching for valid messages.
+	pub fn discarded_bytes(&self) -> u64 {
+		self.inner.discarded_bytes()
+	}
+}
+
+impl Decoder for UbxCodec {
+	type Item = UbxFrame;
+	type Error = std::io::Error;
+
+	fn decode(&mut self, src: &mut BytesMut) -> Result<Option<UbxFrame>, std::io::Error> {
+		Ok(self.inner.decode(src)?.map(from_frame))
+	}
+
+	fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<UbxFrame>, std::io::Error> {
+		Ok(self.inner.decode_eof(src)?.map(from_frame))
+	}
+}
+
+impl Encoder<UbxFrame> for UbxCodec {
+	type Error = std::io::Error;
+
+	fn encode(&mut self, frame: UbxFrame, dst: &mut BytesMut) -> Result<(), std::io::Error> {
+		let frame = Frame {
+			header: [frame.class, frame.id][..].into(),
+			payload: frame.payload,
+		};
+		self.inner.encode(frame, dst)
+	}
+}
+
+/// Convert a decoded frame to a UBX message.
+fn from_frame(frame: Frame) -> UbxFrame {
+	UbxFrame {
+		class: frame.header[0],
+		id: frame.header[1],
+		payload: frame.payload,
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	/// UBX-CFG-MSG to disable the NMEA GLL sentence.
+	const CFG_MSG_DISABLE_GLL: [u8; 11] = [0xB5, 0x62, 0x06, 0x01, 0x03, 0x00, 0xF0, 0x01, 0x00, 0xFB, 0x11];
+
+	#[test]
+	fn encode_known_messages() {
+		let mut buffer = BytesMut::new();
+		UbxCodec::new().encode(UbxFrame::new(0x0A, 0x04, &[][..]), &mut buffer).unwrap();
+		assert_eq!(buffer[..], [0xB5, 0x62, 0x0A, 0x04, 0x00, 0x00, 0x0E, 0x34]);
+
+		let mut buffer = BytesMut::new();
+		UbxCodec::new().encode(UbxFrame::new(0x06, 0x01, &[0xF0, 0x01, 0x00][..]), &mut buffer).unwrap();
+		assert_eq!(buffer[..], CFG_MSG_DISABLE_GLL);
+	}
+
+	#[test]
+	fn decode_known_message() {
+		let mut buffer = BytesMut::from(&CFG_MSG_DISABLE_GLL[..]);
+		let frame = UbxCodec::new().decode(&mut buffer).unwrap();
+		assert_eq!(frame, Some(UbxFrame::new(0x06, 0x01, &[0xF0, 0x01, 0x00][..])));
+		assert!(buffer.is_empty());
+	}
+
+	#[test]
+	fn decode_skips_nmea_sentences() {
+		let mut codec = UbxCodec::new();
+		let nmea = b"$GPGLL,5057.970,N,00146.110,E,142451,A*27\r\n";
+		let mut buffer = BytesMut::from(&nmea[..]);
+		buffer.extend_from_slice(&CFG_MSG_DISABLE_GLL);
+		buffer.extend_from_slice(nmea);
+		assert_eq!(codec.decode(&mut buffer).unwrap(), Some(UbxFrame::new(0x06, 0x01, &[0xF0, 0x01, 0x00][..])));
+		assert_eq!(codec.decode(&mut buffer).unwrap(), None);
+		assert_eq!(codec.discarded_bytes(), 2 * nmea.len() as u64 - 1);
+	}
+
+	#[test]
+	fn round_trip() {
+		let mut codec = UbxCodec::new();
+		let frame = UbxFrame::new(0x01, 0x07, (0..92).collect::<Vec<u8>>().as_slice());
+		let mut buffer = BytesMut::new();
+		codec.encode(frame.clone(), &mut buffer).unwrap();
+		assert_eq!(codec.decode(&mut buffer).unwrap(), Some(frame));
+	}
+
+	#[test]
+	fn encode_rejects_large_payload() {
+		let mut codec = UbxCodec::new();
+		codec.set_max_payload_len(4);
+		let mut buffer = BytesMut::new();
+		assert!(codec.encode(UbxFrame::new(0x01, 0x07, &[0; 5][..]), &mut buffer).is_err());
+	}
+}
@@ -0,0 +1,69 @@
+use crate::SerialPort;
+
+impl SerialPort {
+	/// Get the Windows specific timeouts of the serial port.
+	///
+	/// When a serial port is opened, the read and write timeouts are set to the maximum value,
+	/// so that reads and writes only complete when there is data.
+	/// You can use [`tokio::time::timeout()`] to limit the time spent waiting for a read or write.
+	///
+	/// See [`Self::set_windows_timeouts()`] for more information.
+	#[cfg(any(feature = "doc", all(feature = "windows", windows)))]
+	#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "windows")))]
+	pub fn get_windows_timeouts(&self) -> std::io::Result<crate::os::windows::CommTimeouts> {
+		#[cfg(windows)] {
+			self.inner.with_raw(|raw| raw.get_windows_timeouts())
+		}
+		#[cfg(not(windows))] {
+			unreachable!("this code is only enabled on Windows or during documentation generation")
+		}
+	}
+
+	/// Set the Windows specific timeouts of the serial port.
+	///
+	/// This gives access to the inter-byte timeout semantics of the driver.
+	/// For example, with a read interval timeout the driver completes a read when the line has been idle for the given time,
+	/// which can be used to receive a complete frame in a single read.
+	///
+	/// Reads that complete without any data do not return `0` as end-of-file.
+	/// Instead, they wait until data is received, so [`Self::read()`] never reports a timeout.
+	/// You can still use [`tokio::time::timeout()`] to limit the time spent waiting.
+	///
+	/// The timeouts apply to the serial port itself, so they also affect other handles created with [`Self::try_clone()`].
+	///
+	/// Please read the whole MSDN article about serial port timeouts before using this, including all remarks:
+	/// [https://learn.microsoft.com/en-us/windows/win32/api/winbase/ns-winbase-commtimeouts](https://learn.microsoft.com/en-us/windows/win32/api/winbase/ns-winbase-commtimeouts)
+	///
+	/// # Example
+	/// ```no_run
+	/// # #[cfg(windows)]
+	/// # async fn example() -> std::io::Result<()> {
+	/// use serial2_tokio::SerialPort;
+	/// use serial2_tokio::os::windows::CommTimeouts;
+	///
+	/// let port = SerialPort::open("COM1", 9600)?;
+	/// // Complete reads after the line has been idle for 5 milliseconds.
+	/// port.set_windows_timeouts(&CommTimeouts {
+	///     read_interval_timeout: 5,
+	///     read_total_timeout_multiplier: 0,
+	///     read_total_timeout_constant: 0,
+	///     write_total_timeout_multiplier: 0,
+	///     write_total_timeout_constant: 0,
+	/// })?;
+	/// let mut frame = [0; 256];
+	/// let read = port.read(&mut frame).await?;
+	/// # Ok(())
+	/// # }
+	/// ```
+	#[cfg(any(feature = "doc", all(feature = "windows", windows)))]
+	#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "windows")))]
+	pub fn set_windows_timeouts(&self, timeouts: &crate::os::windows::CommTimeouts) -> std::io::Result<()> {
+		#[cfg(windows)] {
+			self.inner.with_raw(|raw| raw.set_windows_timeouts(timeouts))
+		}
+		#[cfg(not(windows))] {
+			let _ = timeouts;
+			unreachable!("this code is only enabled on Windows or during documentation generation")
+		}
+	}
+}
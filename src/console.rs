@@ -0,0 +1,355 @@
+//! An interactive serial console for the terminal.
+//!
+//! The [`Console`] connects the terminal to a serial port, similar to tools like `picocom` and `miniterm`.
+//! The terminal is put in raw mode, so that every key press is sent to the serial port immediately,
+//! including control characters like Ctrl-C.
+//! The original terminal mode is restored when the console exits.
+//!
+//! The console is controlled with an escape character, which is Ctrl-A by default.
+//! After the escape character, the following keys are recognized:
+//!
+//! * `x` or `q`: exit the console.
+//! * `b`: send a break condition for 250 milliseconds.
+//! * `d`: toggle the DTR line.
+//! * `r`: toggle the RTS line.
+//! * `u`: increase the baud rate to the next common baud rate.
+//! * `n`: decrease the baud rate to the previous common baud rate.
+//! * `h` or `?`: show the available commands.
+//! * The escape character: send the escape character itself.
+//!
+//! The console assumes that the DTR and RTS lines are asserted when it starts, which is the normal state after opening a serial port.
+//!
+//! All data received from the serial port can be written to a session log.
+//!
+//! This module is only available when the `console` feature is enabled.
+//!
+//! # Example
+//! ```no_run
+//! # async fn example() -> std::io::Result<()> {
+//! use serial2_tokio::SerialPort;
+//! use serial2_tokio::console::Console;
+//!
+//! let port = SerialPort::open("/dev/ttyUSB0", 115200)?;
+//! Console::new(port)
+//!     .log(std::fs::File::create("session.log")?)
+//!     .run()
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::future::Future;
+use std::io::Write;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::Poll;
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, ReadBuf};
+
+use crate::task::AbortOnDrop;
+use crate::{SerialPort, COMMON_BAUD_RATES};
+
+/// The default escape character: Ctrl-A.
+const DEFAULT_ESCAPE: u8 = 0x01;
+
+/// The duration of a break condition sent from the console.
+const BREAK_DURATION: Duration = Duration::from_millis(250);
+
+/// An interactive serial console.
+///
+/// See the [module documentation](self) for more information.
+pub struct Console {
+	port: Arc<SerialPort>,
+	escape: u8,
+	log: Option<Box<dyn Write + Send>>,
+	/// The state of the DTR line, as last set by the console.
+	dtr: bool,
+	/// The state of the RTS line, as last set by the console.
+	rts: bool,
+}
+
+impl std::fmt::Debug for Console {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("Console")
+			.field("port", &self.port)
+			.field("escape", &self.escape)
+			.finish_non_exhaustive()
+	}
+}
+
+/// What to do after a console command.
+enum Action {
+	Continue,
+	Exit,
+}
+
+impl Console {
+	/// Create a console for a serial port.
+	pub fn new(port: impl Into<Arc<SerialPort>>) -> Self {
+		Self {
+			port: port.into(),
+			escape: DEFAULT_ESCAPE,
+			log: None,
+			// Most platforms assert DTR and RTS when a serial port is opened.
+			dtr: true,
+			rts: true,
+		}
+	}
+
+	/// Set the escape character.
+	///
+	/// The default escape character is Ctrl-A (`0x01`).
+	pub fn escape_char(mut self, escape: u8) -> Self {
+		self.escape = escape;
+		self
+	}
+
+	/// Write all data received from the serial port to a session log.
+	///
+	/// The data is written exactly as it was received, before it is written to the terminal.
+	pub fn log(mut self, writer: impl Write + Send + 'static) -> Self {
+		self.log = Some(Box::new(writer));
+		self
+	}
+
+	/// Get the serial port used by the console.
+	pub fn port(&self) -> &Arc<SerialPort> {
+		&self.port
+	}
+
+	/// Run the console until the user exits it.
+	///
+	/// This also returns when the serial port reports end-of-file (which normally means the device was removed),
+	/// when reading from the terminal reports end-of-file, or when an error occurs.
+	///
+	/// If standard input is a terminal, it is put in raw mode while the console is running.
+	pub async fn run(mut self) -> std::io::Result<()> {
+		let _raw_mode = sys::RawMode::enable()?;
+		print_message(&format!("Connected, press {} h for help", key_name(self.escape)))?;
+
+		let mut output = tokio::spawn(forward_output(self.port.clone(), self.log.take()));
+		let _output_guard = AbortOnDrop(output.abort_handle());
+		let mut stdin = tokio::io::stdin();
+		let mut buffer = [0; 256];
+		let mut escaped = false;
+
+		loop {
+			let read = std::future::poll_fn(|cx| {
+				if let Poll::Ready(result) = Pin::new(&mut output).poll(cx) {
+					return Poll::Ready(Err(result));
+				}
+				let mut read_buf = ReadBuf::new(&mut buffer);
+				Pin::new(&mut stdin).poll_read(cx, &mut read_buf)
+					.map(|result| Ok(result.map(|()| read_buf.filled().len())))
+			}).await;
+
+			let read = match read {
+				Ok(read) => read?,
+				Err(Ok(result)) => return result,
+				Err(Err(e)) => return Err(std::io::Error::other(e)),
+			};
+			if read == 0 {
+				return Ok(());
+			}
+
+			let mut data = Vec::with_capacity(read);
+			for &byte in &buffer[..read] {
+				if std::mem::take(&mut escaped) {
+					if byte == self.escape {
+						data.push(byte);
+						continue;
+					}
+					self.port.write_all(&data).await?;
+					data.clear();
+					if let Action::Exit = self.command(byte).await? {
+						print_message("Exiting")?;
+						return Ok(());
+					}
+				} else if byte == self.escape {
+					escaped = true;
+				} else {
+					data.push(byte);
+				}
+			}
+			self.port.write_all(&data).await?;
+		}
+	}
+
+	/// Execute a console command.
+	async fn command(&mut self, key: u8) -> std::io::Result<Action> {
+		match key.to_ascii_lowercase() {
+			b'x' | b'q' => return Ok(Action::Exit),
+			b'b' => {
+				self.port.set_break(true)?;
+				tokio::time::sleep(BREAK_DURATION).await;
+				self.port.set_break(false)?;
+				print_message("Sent break")?;
+			},
+			b'd' => {
+				self.port.set_dtr(!self.dtr)?;
+				self.dtr = !self.dtr;
+				print_message(&format!("DTR: {}", if self.dtr { "up" } else { "down" }))?;
+			},
+			b'r' => {
+				self.port.set_rts(!self.rts)?;
+				self.rts = !self.rts;
+				print_message(&format!("RTS: {}", if self.rts { "up" } else { "down" }))?;
+			},
+			b'u' => self.step_baud_rate(true)?,
+			b'n' => self.step_baud_rate(false)?,
+			b'h' | b'?' => {
+				let escape = key_name(self.escape);
+				print_message(&format!(concat!(
+					"Commands:\r\n",
+					"  {escape} x  exit\r\n",
+					"  {escape} b  send break\r\n",
+					"  {escape} d  toggle DTR\r\n",
+					"  {escape} r  toggle RTS\r\n",
+					"  {escape} u  increase baud rate\r\n",
+					"  {escape} n  decrease baud rate\r\n",
+					"  {escape} {escape}  send {escape}",
+				), escape = escape))?;
+			},
+			_ => print_message(&format!("Unknown command: {}", key_name(key)))?,
+		}
+		Ok(Action::Continue)
+	}
+
+	/// Change the baud rate to the next higher or lower common baud rate.
+	fn step_baud_rate(&self, up: bool) -> std::io::Result<()> {
+		let mut settings = self.port.get_configuration()?;
+		let current = settings.get_baud_rate()?;
+		let next = if up {
+			COMMON_BAUD_RATES.iter().copied().find(|&baud_rate| baud_rate > current)
+		} else {
+			COMMON_BAUD_RATES.iter().copied().rev().find(|&baud_rate| baud_rate < current)
+		};
+		match next {
+			Some(baud_rate) => {
+				settings.set_baud_rate(baud_rate)?;
+				self.port.set_configuration(&settings)?;
+				print_message(&format!("Baud rate: {}", self.port.get_configuration()?.get_baud_rate()?))
+			},
+			None => print_message(&format!("Baud rate: {current}")),
+		}
+	}
+}
+
+/// Read from the serial port and write the data to the session log and the terminal.
+async fn forward_output(port: Arc<SerialPort>, mut log: Option<Box<dyn Write + Send>>) -> std::io::Result<()> {
+	let mut buffer = [0; 4096];
+	let mut stdout = std::io::stdout();
+	loop {
+		let read = port.read(&mut buffer).await?;
+		if read == 0 {
+			print_message("Serial port closed")?;
+			return Ok(());
+		}
+		if let Some(log) = &mut log {
+			log.write_all(&buffer[..read])?;
+			log.flush()?;
+		}
+		stdout.write_all(&buffer[..read])?;
+		stdout.flush()?;
+	}
+}
+
+/// Print a message from the console on a separate line.
+fn print_message(message: &str) -> std::io::Result<()> {
+	let mut stdout = std::io::stdout();
+	write!(stdout, "\r\n*** {message}\r\n")?;
+	stdout.flush()
+}
+
+/// Get a readable name for a key.
+fn key_name(key: u8) -> String {
+	match key {
+		0..=0x1F => format!("Ctrl-{}", (key + b'@') as char),
+		0x20..=0x7E => (key as char).to_string(),
+		_ => format!("0x{key:02X}"),
+	}
+}
+
+#[cfg(unix)]
+mod sys {
+	/// Raw mode for the terminal on standard input, restored when dropped.
+	pub struct RawMode {
+		original: Option<libc::termios>,
+	}
+
+	impl RawMode {
+		pub fn enable() -> std::io::Result<Self> {
+			unsafe {
+				let mut original: libc::termios = std::mem::zeroed();
+				if libc::tcgetattr(libc::STDIN_FILENO, &mut original) != 0 {
+					let error = std::io::Error::last_os_error();
+					// Standard input is not a terminal, so there is nothing to do.
+					if error.raw_os_error() == Some(libc::ENOTTY) {
+						return Ok(Self { original: None });
+					}
+					return Err(error);
+				}
+				let mut raw = original;
+				libc::cfmakeraw(&mut raw);
+				if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw) != 0 {
+					return Err(std::io::Error::last_os_error());
+				}
+				Ok(Self { original: Some(original) })
+			}
+		}
+	}
+
+	impl Drop for RawMode {
+		fn drop(&mut self) {
+			if let Some(original) = &self.original {
+				unsafe {
+					libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, original);
+				}
+			}
+		}
+	}
+}
+
+#[cfg(windows)]
+mod sys {
+	use winapi::shared::minwindef::DWORD;
+	use winapi::um::{consoleapi, processenv, winbase, wincon};
+	use winapi::um::winnt::HANDLE;
+
+	/// Raw mode for the console on standard input, restored when dropped.
+	pub struct RawMode {
+		handle: HANDLE,
+		original: Option<DWORD>,
+	}
+
+	impl RawMode {
+		pub fn enable() -> std::io::Result<Self> {
+			unsafe {
+				let handle = processenv::GetStdHandle(winbase::STD_INPUT_HANDLE);
+				let mut original = 0;
+				if consoleapi::GetConsoleMode(handle, &mut original) == 0 {
+					// Standard input is not a console, so there is nothing to do.
+					return Ok(Self { handle, original: None });
+				}
+				let raw = original
+					& !(wincon::ENABLE_LINE_INPUT | wincon::ENABLE_ECHO_INPUT | wincon::ENABLE_PROCESSED_INPUT)
+					| wincon::ENABLE_VIRTUAL_TERMINAL_INPUT;
+				if consoleapi::SetConsoleMode(handle, raw) == 0 {
+					return Err(std::io::Error::last_os_error());
+				}
+				Ok(Self { handle, original: Some(original) })
+			}
+		}
+	}
+
+	impl Drop for RawMode {
+		fn drop(&mut self) {
+			if let Some(original) = self.original {
+				unsafe {
+					consoleapi::SetConsoleMode(self.handle, original);
+				}
+			}
+		}
+	}
+}
@@ -0,0 +1,159 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{ready, Poll, Waker};
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::time::Sleep;
+
+use crate::SerialPort;
+
+/// A wrapper that makes a serial port compose with the generic copy utilities of Tokio.
+///
+/// Utilities like [`tokio::io::copy_bidirectional()`] only complete when both streams report the end of the stream,
+/// but a serial port never does: there is no such thing as closing the connection on a serial line.
+/// A bridge between a TCP stream and a serial port would therefore never finish after the TCP peer disconnects.
+///
+/// This wrapper treats a shutdown of the write side as the end of the whole session:
+/// once [`AsyncWrite::poll_shutdown()`] has completed, all reads report the end of the stream,
+/// including a read that was already waiting for data.
+///
+/// Shutting down the serial port waits until the output buffer has been transmitted (see [`SerialPort::drain()`]).
+/// With flow control, that may take forever if the device stopped accepting data.
+/// Use [`Self::set_shutdown_timeout()`] to discard the remaining data after a timeout instead.
+///
+/// Flushing is a no-op, just like for [`SerialPort`], since the copy utilities flush whenever the reader has no data available.
+///
+/// # Example
+/// ```no_run
+/// # async fn example() -> std::io::Result<()> {
+/// use serial2_tokio::{CopyCompat, SerialPort};
+/// use std::time::Duration;
+/// use tokio::net::TcpListener;
+///
+/// let listener = TcpListener::bind("0.0.0.0:2000").await?;
+/// let (mut stream, _address) = listener.accept().await?;
+/// let mut port = CopyCompat::new(SerialPort::open("/dev/ttyUSB0", 115200)?);
+/// port.set_shutdown_timeout(Some(Duration::from_secs(1)));
+/// let (to_serial, to_network) = tokio::io::copy_bidirectional(&mut stream, &mut port).await?;
+/// println!("forwarded {to_serial} bytes to the serial port and {to_network} bytes to the network");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+#[cfg_attr(not(feature = "doc"), allow(rustdoc::broken_intra_doc_links))]
+pub struct CopyCompat {
+	port: SerialPort,
+	shutdown_timeout: Option<Duration>,
+	/// The timer for the shutdown timeout, while shutting down.
+	shutdown_sleep: Option<Pin<Box<Sleep>>>,
+	/// The write side has been shut down, so reads report the end of the stream.
+	shut_down: bool,
+	/// The waker of a pending read, to wake it when the write side is shut down.
+	read_waker: Option<Waker>,
+}
+
+impl CopyCompat {
+	/// Wrap a serial port.
+	pub fn new(port: SerialPort) -> Self {
+		Self {
+			port,
+			shutdown_timeout: None,
+			shutdown_sleep: None,
+			shut_down: false,
+			read_waker: None,
+		}
+	}
+
+	/// Set the maximum time to wait for the output buffer to drain when shutting down.
+	///
+	/// If the timeout expires, the remaining data in the output buffer is discarded and the shutdown completes successfully.
+	/// Pass `None` to wait until all data has been transmitted, which is the default.
+	pub fn set_shutdown_timeout(&mut self, timeout: Option<Duration>) {
+		self.shutdown_timeout = timeout;
+	}
+
+	/// Get the maximum time to wait for the output buffer to drain when shutting down.
+	pub fn get_shutdown_timeout(&self) -> Option<Duration> {
+		self.shutdown_timeout
+	}
+
+	/// Check if the write side has been shut down.
+	///
+	/// When this returns true, all reads report the end of the stream.
+	pub fn is_shut_down(&self) -> bool {
+		self.shut_down
+	}
+
+	/// Get a reference to the wrapped serial port.
+	pub fn get_ref(&self) -> &SerialPort {
+		&self.port
+	}
+
+	/// Get a mutable reference to the wrapped serial port.
+	pub fn get_mut(&mut self) -> &mut SerialPort {
+		&mut self.port
+	}
+
+	/// Consume the wrapper and get the serial port back.
+	pub fn into_inner(self) -> SerialPort {
+		self.port
+	}
+}
+
+impl AsyncRead for CopyCompat {
+	fn poll_read(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<std::io::Result<()>> {
+		let this = self.get_mut();
+		if this.shut_down {
+			return Poll::Ready(Ok(()));
+		}
+		let result = Pin::new(&mut this.port).poll_read(cx, buf);
+		if result.is_pending() {
+			this.read_waker = Some(cx.waker().clone());
+		}
+		result
+	}
+}
+
+impl AsyncWrite for CopyCompat {
+	fn poll_write(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>> {
+		Pin::new(&mut self.get_mut().port).poll_write(cx, buf)
+	}
+
+	fn poll_write_vectored(
+		self: Pin<&mut Self>,
+		cx: &mut std::task::Context<'_>,
+		bufs: &[std::io::IoSlice<'_>],
+	) -> Poll<std::io::Result<usize>> {
+		Pin::new(&mut self.get_mut().port).poll_write_vectored(cx, bufs)
+	}
+
+	fn is_write_vectored(&self) -> bool {
+		self.port.is_write_vectored()
+	}
+
+	fn poll_flush(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<std::io::Result<()>> {
+		Pin::new(&mut self.get_mut().port).poll_flush(cx)
+	}
+
+	fn poll_shutdown(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<std::io::Result<()>> {
+		let this = self.get_mut();
+		if !this.shut_down {
+			if let Some(timeout) = this.shutdown_timeout {
+				let sleep = this.shutdown_sleep.get_or_insert_with(|| Box::pin(tokio::time::sleep(timeout)));
+				if sleep.as_mut().poll(cx).is_ready() {
+					// Discarding the output buffer also makes the pending drain complete.
+					this.port.discard_output_buffer()?;
+				}
+			}
+			let result = ready!(Pin::new(&mut this.port).poll_shutdown(cx));
+			this.shutdown_sleep = None;
+			result?;
+			this.shut_down = true;
+			if let Some(waker) = this.read_waker.take() {
+				waker.wake();
+			}
+		}
+		Poll::Ready(Ok(()))
+	}
+}
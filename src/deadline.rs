@@ -0,0 +1,78 @@
+use tokio::time::Instant;
+
+use crate::SerialPort;
+
+impl SerialPort {
+	/// Read bytes from the serial port, and give up at the deadline.
+	///
+	/// This works like [`Self::read()`], but it returns an error of kind [`std::io::ErrorKind::TimedOut`]
+	/// if no data is received before the deadline.
+	/// If the deadline has already passed, data that is available immediately is still returned.
+	///
+	/// A protocol transaction often has a single deadline for all its steps.
+	/// The `*_until()` functions take that deadline directly, so you don't have to compute the remaining time before every step.
+	///
+	/// Despite the name, this function is unrelated to `AsyncBufReadExt::read_until()` from Tokio, which reads until a delimiter is found.
+	///
+	/// # Example
+	/// ```no_run
+	/// # async fn example() -> std::io::Result<()> {
+	/// use serial2_tokio::SerialPort;
+	/// use std::time::Duration;
+	/// use tokio::time::Instant;
+	///
+	/// let port = SerialPort::open("/dev/ttyUSB0", 115200)?;
+	/// let deadline = Instant::now() + Duration::from_millis(500);
+	/// port.write_all_until(b"ID?\r\n", deadline).await?;
+	/// let mut buffer = [0; 256];
+	/// let mut len = 0;
+	/// while !buffer[..len].ends_with(b"\r\n") {
+	///     len += port.read_until(&mut buffer[len..], deadline).await?;
+	/// }
+	/// # Ok(())
+	/// # }
+	/// ```
+	pub async fn read_until(&self, buf: &mut [u8], deadline: Instant) -> std::io::Result<usize> {
+		with_deadline(deadline, self.read(buf), "deadline passed before any data was received").await
+	}
+
+	/// Write bytes to the serial port, and give up at the deadline.
+	///
+	/// This works like [`Self::write()`], but it returns an error of kind [`std::io::ErrorKind::TimedOut`]
+	/// if no data could be written before the deadline.
+	pub async fn write_until(&self, buf: &[u8], deadline: Instant) -> std::io::Result<usize> {
+		with_deadline(deadline, self.write(buf), "deadline passed before any data was written").await
+	}
+
+	/// Write all bytes to the serial port, and give up at the deadline.
+	///
+	/// This works like [`Self::write_all()`], but it returns an error of kind [`std::io::ErrorKind::TimedOut`]
+	/// if not all data could be written before the deadline.
+	/// Part of the data may have been written already when the deadline passes.
+	/// Use [`Self::write_tracked()`] with [`tokio::time::timeout_at()`] if you need to know how much.
+	pub async fn write_all_until(&self, buf: &[u8], deadline: Instant) -> std::io::Result<()> {
+		with_deadline(deadline, self.write_all(buf), "deadline passed before all data was written").await
+	}
+
+	/// Wait until all data in the output buffer has been transmitted, and give up at the deadline.
+	///
+	/// This works like [`Self::drain()`], but it returns an error of kind [`std::io::ErrorKind::TimedOut`]
+	/// if the output buffer is not empty before the deadline.
+	///
+	/// The blocking system call keeps running on the blocking thread pool until the OS reports that the buffer is empty,
+	/// even after the deadline has passed.
+	pub async fn drain_until(&self, deadline: Instant) -> std::io::Result<()> {
+		with_deadline(deadline, self.drain(), "deadline passed before the output buffer was transmitted").await
+	}
+}
+
+/// Run an I/O future, and turn an expired deadline into an error of kind [`std::io::ErrorKind::TimedOut`].
+async fn with_deadline<T>(
+	deadline: Instant,
+	future: impl std::future::Future<Output = std::io::Result<T>>,
+	message: &'static str,
+) -> std::io::Result<T> {
+	tokio::time::timeout_at(deadline, future)
+		.await
+		.map_err(|_| std::io::Error::new(std::io::ErrorKind::TimedOut, message))?
+}
@@ -0,0 +1,191 @@
+use std::path::PathBuf;
+
+use crate::{CharSize, FlowControl, Parity, SerialPort, Settings, StopBits};
+
+/// A printable summary of serial port settings, like `115200 8N1, flow control: none`.
+///
+/// [`Settings`] is defined by the `serial2` crate and can not implement [`Display`][std::fmt::Display] here,
+/// so convert it to a summary to print it:
+///
+/// ```no_run
+/// # fn example() -> std::io::Result<()> {
+/// use serial2_tokio::{SerialPort, SettingsSummary};
+///
+/// let port = SerialPort::open("/dev/ttyUSB0", 115200)?;
+/// println!("{}", SettingsSummary::from(&port.get_configuration()?));
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Settings that can not be represented are set to `None`, and printed as `?`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct SettingsSummary {
+	/// The baud rate.
+	pub baud_rate: Option<u32>,
+
+	/// The number of data bits per character.
+	pub char_size: Option<CharSize>,
+
+	/// The parity.
+	pub parity: Option<Parity>,
+
+	/// The number of stop bits.
+	pub stop_bits: Option<StopBits>,
+
+	/// The flow control mode.
+	pub flow_control: Option<FlowControl>,
+}
+
+impl From<&Settings> for SettingsSummary {
+	fn from(settings: &Settings) -> Self {
+		Self {
+			baud_rate: settings.get_baud_rate().ok(),
+			char_size: settings.get_char_size().ok(),
+			parity: settings.get_parity().ok(),
+			stop_bits: settings.get_stop_bits().ok(),
+			flow_control: settings.get_flow_control().ok(),
+		}
+	}
+}
+
+impl std::fmt::Display for SettingsSummary {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self.baud_rate {
+			Some(baud_rate) => write!(f, "{baud_rate} ")?,
+			None => write!(f, "? ")?,
+		}
+		match self.char_size {
+			Some(char_size) => write!(f, "{char_size}")?,
+			None => write!(f, "?")?,
+		}
+		match self.parity {
+			Some(Parity::None) => write!(f, "N")?,
+			Some(Parity::Odd) => write!(f, "O")?,
+			Some(Parity::Even) => write!(f, "E")?,
+			None => write!(f, "?")?,
+		}
+		match self.stop_bits {
+			Some(stop_bits) => write!(f, "{stop_bits}")?,
+			None => write!(f, "?")?,
+		}
+		match self.flow_control {
+			Some(flow_control) => write!(f, ", flow control: {flow_control}"),
+			None => write!(f, ", flow control: ?"),
+		}
+	}
+}
+
+/// A summary of the complete state of a serial port, meant for logging.
+///
+/// Created by [`SerialPort::describe()`].
+/// The [`Display`][std::fmt::Display] implementation prints the summary on a single line.
+///
+/// The state is collected with separate system calls, so it is not an atomic snapshot.
+/// Information that could not be determined is set to `None`, and printed as `?`.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct PortDescription {
+	/// The path of the device.
+	///
+	/// Only available on Unix platforms (see [`SerialPort::identity()`]).
+	pub path: Option<PathBuf>,
+
+	/// The settings of the serial port.
+	pub settings: Option<SettingsSummary>,
+
+	/// The state of the RTS line.
+	pub rts: Option<bool>,
+
+	/// The state of the DTR line.
+	pub dtr: Option<bool>,
+
+	/// The state of the CTS line.
+	pub cts: Option<bool>,
+
+	/// The state of the DSR line.
+	pub dsr: Option<bool>,
+
+	/// The state of the RI line.
+	pub ri: Option<bool>,
+
+	/// The state of the CD line.
+	pub cd: Option<bool>,
+
+	/// The number of bytes in the input buffer of the OS.
+	pub input_queue: Option<usize>,
+
+	/// The number of bytes in the output buffer of the OS that have not been transmitted yet.
+	pub output_queue: Option<usize>,
+}
+
+impl std::fmt::Display for PortDescription {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match &self.path {
+			Some(path) => write!(f, "{}: ", path.display())?,
+			None => write!(f, "?: ")?,
+		}
+		match &self.settings {
+			Some(settings) => write!(f, "{settings}")?,
+			None => write!(f, "settings: ?")?,
+		}
+		let lines = [("RTS", self.rts), ("DTR", self.dtr), ("CTS", self.cts), ("DSR", self.dsr), ("RI", self.ri), ("CD", self.cd)];
+		for (i, (name, state)) in lines.into_iter().enumerate() {
+			let separator = if i == 0 { ", " } else { " " };
+			match state {
+				Some(state) => write!(f, "{separator}{name}={}", u8::from(state))?,
+				None => write!(f, "{separator}{name}=?")?,
+			}
+		}
+		write!(f, ", input queue: ")?;
+		write_option(f, self.input_queue)?;
+		write!(f, ", output queue: ")?;
+		write_option(f, self.output_queue)
+	}
+}
+
+/// Write an optional value, or `?` if it is `None`.
+fn write_option(f: &mut std::fmt::Formatter<'_>, value: Option<usize>) -> std::fmt::Result {
+	match value {
+		Some(value) => write!(f, "{value}"),
+		None => write!(f, "?"),
+	}
+}
+
+impl SerialPort {
+	/// Get a printable summary of the complete state of the serial port.
+	///
+	/// This collects the path, the settings, the state of all modem control lines and the number of bytes in the OS buffers.
+	/// It does not fail: information that can not be determined is left out.
+	///
+	/// On Windows, this also clears the error flags of the serial port (see [`Self::flow_control_status()`]).
+	///
+	/// # Example
+	/// ```no_run
+	/// # fn example() -> std::io::Result<()> {
+	/// use serial2_tokio::SerialPort;
+	///
+	/// let port = SerialPort::open("/dev/ttyUSB0", 115200)?;
+	/// // Prints something like:
+	/// // /dev/ttyUSB0: 115200 8N1, flow control: none, RTS=1 DTR=1 CTS=0 DSR=0 RI=0 CD=0, input queue: 0, output queue: 0
+	/// println!("{}", port.describe());
+	/// # Ok(())
+	/// # }
+	/// ```
+	pub fn describe(&self) -> PortDescription {
+		let status = self.flow_control_status().ok();
+		let output_lines = self.inner.read_output_lines().ok();
+		PortDescription {
+			path: self.identity().ok().map(|identity| identity.device_path),
+			settings: self.get_configuration().ok().map(|settings| SettingsSummary::from(&settings)),
+			rts: output_lines.map(|(rts, _dtr)| rts),
+			dtr: output_lines.map(|(_rts, dtr)| dtr),
+			cts: self.read_cts().ok(),
+			dsr: self.read_dsr().ok(),
+			ri: self.read_ri().ok(),
+			cd: self.read_cd().ok(),
+			input_queue: status.as_ref().and_then(|status| status.input_queue),
+			output_queue: status.as_ref().and_then(|status| status.output_queue),
+		}
+	}
+}
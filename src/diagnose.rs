@@ -0,0 +1,275 @@
+use std::path::Path;
+
+use crate::SerialPort;
+
+/// The result of diagnosing a failure to open a serial port.
+///
+/// Created by [`SerialPort::diagnose_open_error()`].
+/// The [`Display`][std::fmt::Display] implementation prints the hints, one per line.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct OpenDiagnosis {
+	/// Does the device exist?
+	///
+	/// Set to `None` if this could not be determined.
+	pub device_exists: Option<bool>,
+
+	/// The groups that would grant access to the device, if the current user is not a member of them.
+	///
+	/// Only filled in on Unix platforms.
+	pub missing_groups: Vec<String>,
+
+	/// The processes that have the device open.
+	///
+	/// Set to `None` if this could not be determined.
+	/// This is currently only supported on Linux,
+	/// and only processes that the current user is allowed to inspect are reported.
+	pub in_use_by: Option<Vec<ProcessInfo>>,
+
+	/// Human readable hints on how to solve the problem.
+	pub hints: Vec<String>,
+}
+
+/// A process that has a serial port open.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ProcessInfo {
+	/// The process ID.
+	pub pid: u32,
+
+	/// The name of the process, if it could be determined.
+	pub name: Option<String>,
+}
+
+impl std::fmt::Display for OpenDiagnosis {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		for (i, hint) in self.hints.iter().enumerate() {
+			if i > 0 {
+				writeln!(f)?;
+			}
+			write!(f, "{hint}")?;
+		}
+		Ok(())
+	}
+}
+
+impl SerialPort {
+	/// Diagnose why opening a serial port failed.
+	///
+	/// This inspects the system to find common causes for the error returned by [`Self::open()`]:
+	/// a device that does not exist, missing group membership (such as the `dialout` group on many Linux distributions),
+	/// or another process that already has the port open.
+	///
+	/// The returned diagnosis is meant to be shown to the user, and may be incomplete.
+	/// The checks are best effort: failures to inspect the system are not reported as errors.
+	///
+	/// # Example
+	/// ```no_run
+	/// use serial2_tokio::SerialPort;
+	///
+	/// let path = "/dev/ttyUSB0";
+	/// if let Err(e) = SerialPort::open(path, 115200) {
+	///     eprintln!("Failed to open {path}: {e}");
+	///     eprintln!("{}", SerialPort::diagnose_open_error(path, &e));
+	/// }
+	/// ```
+	pub fn diagnose_open_error(path: impl AsRef<Path>, error: &std::io::Error) -> OpenDiagnosis {
+		let path = path.as_ref();
+		let mut diagnosis = OpenDiagnosis::default();
+		sys::diagnose(path, error, &mut diagnosis);
+
+		if diagnosis.device_exists == Some(false) {
+			diagnosis.hints.push(format!("The device {} does not exist. Check that it is connected and that the name is correct.", path.display()));
+			if let Ok(ports) = Self::available_ports() {
+				if !ports.is_empty() {
+					let ports: Vec<_> = ports.iter().map(|x| x.display().to_string()).collect();
+					diagnosis.hints.push(format!("Available serial ports: {}", ports.join(", ")));
+				}
+			}
+		}
+
+		if !diagnosis.missing_groups.is_empty() {
+			diagnosis.hints.push(format!(
+				"You do not have permission to access {}. Add your user to the {} group and log in again.",
+				path.display(),
+				diagnosis.missing_groups.join(" or "),
+			));
+		}
+
+		if let Some(processes) = &diagnosis.in_use_by {
+			for process in processes {
+				match &process.name {
+					Some(name) => diagnosis.hints.push(format!("The device is in use by process {} ({}).", process.pid, name)),
+					None => diagnosis.hints.push(format!("The device is in use by process {}.", process.pid)),
+				}
+			}
+		}
+
+		if diagnosis.hints.is_empty() {
+			diagnosis.hints.push(format!("No specific cause found for the error: {error}"));
+		}
+
+		diagnosis
+	}
+}
+
+#[cfg(unix)]
+mod sys {
+	use std::ffi::CStr;
+	use std::os::unix::fs::MetadataExt;
+	use std::path::Path;
+
+	use super::OpenDiagnosis;
+
+	pub fn diagnose(path: &Path, error: &std::io::Error, diagnosis: &mut OpenDiagnosis) {
+		let metadata = match std::fs::metadata(path) {
+			Ok(x) => x,
+			Err(e) => {
+				if e.kind() == std::io::ErrorKind::NotFound {
+					diagnosis.device_exists = Some(false);
+				}
+				return;
+			},
+		};
+		diagnosis.device_exists = Some(true);
+
+		if error.kind() == std::io::ErrorKind::PermissionDenied {
+			check_permissions(&metadata, diagnosis);
+			#[cfg(target_os = "android")]
+			if diagnosis.missing_groups.is_empty() {
+				android_hints(path, diagnosis);
+			}
+		}
+
+		#[cfg(target_os = "linux")]
+		if let Ok(path) = path.canonicalize() {
+			diagnosis.in_use_by = Some(find_processes(&path));
+		}
+
+		if error.raw_os_error() == Some(libc::EBUSY) && diagnosis.in_use_by.as_ref().is_none_or(|x| x.is_empty()) {
+			diagnosis.hints.push("The device is busy. It may be opened in exclusive mode by another process.".into());
+		}
+	}
+
+	/// Explain why opening a serial port is denied on Android.
+	#[cfg(target_os = "android")]
+	fn android_hints(path: &Path, diagnosis: &mut OpenDiagnosis) {
+		if crate::android::selinux_enforcing() {
+			diagnosis.hints.push(
+				"Access to the device is probably denied by the SELinux policy. \
+				Apps can not open serial devices directly, but they can use a file descriptor from a system service or a privileged process \
+				with SerialPort::from_raw_fd_async()."
+					.into(),
+			);
+		}
+		if crate::android::is_onboard_uart(path) {
+			diagnosis.hints.push(format!(
+				"{} is an on-board UART, which is usually reserved for the kernel console, Bluetooth or a modem.",
+				path.display(),
+			));
+		}
+	}
+
+	fn check_permissions(metadata: &std::fs::Metadata, diagnosis: &mut OpenDiagnosis) {
+		let mode = metadata.mode();
+		let uid = unsafe { libc::geteuid() };
+		if uid == 0 || (metadata.uid() == uid && mode & 0o600 == 0o600) {
+			return;
+		}
+		if mode & 0o060 != 0o060 || user_groups().contains(&metadata.gid()) {
+			return;
+		}
+		match group_name(metadata.gid()) {
+			Some(name) => diagnosis.missing_groups.push(name),
+			None => diagnosis.missing_groups.push(metadata.gid().to_string()),
+		}
+	}
+
+	fn user_groups() -> Vec<libc::gid_t> {
+		unsafe {
+			let count = libc::getgroups(0, std::ptr::null_mut());
+			if count < 0 {
+				return Vec::new();
+			}
+			let mut groups = vec![0; count as usize];
+			let count = libc::getgroups(count, groups.as_mut_ptr());
+			if count < 0 {
+				return Vec::new();
+			}
+			groups.truncate(count as usize);
+			groups.push(libc::getegid());
+			groups
+		}
+	}
+
+	fn group_name(gid: libc::gid_t) -> Option<String> {
+		let mut buffer = vec![0; 1024];
+		loop {
+			unsafe {
+				let mut group: libc::group = std::mem::zeroed();
+				let mut result = std::ptr::null_mut();
+				let ret = libc::getgrgid_r(gid, &mut group, buffer.as_mut_ptr(), buffer.len(), &mut result);
+				if ret == libc::ERANGE && buffer.len() < 1024 * 1024 {
+					buffer.resize(buffer.len() * 2, 0);
+					continue;
+				}
+				if ret != 0 || result.is_null() {
+					return None;
+				}
+				return Some(CStr::from_ptr(group.gr_name).to_string_lossy().into_owned());
+			}
+		}
+	}
+
+	#[cfg(target_os = "linux")]
+	fn find_processes(path: &Path) -> Vec<super::ProcessInfo> {
+		let mut processes = Vec::new();
+		let Ok(entries) = std::fs::read_dir("/proc") else {
+			return processes;
+		};
+		let own_pid = std::process::id();
+		for entry in entries.flatten() {
+			let Some(pid) = entry.file_name().to_str().and_then(|x| x.parse::<u32>().ok()) else {
+				continue;
+			};
+			if pid == own_pid {
+				continue;
+			}
+			let Ok(fds) = std::fs::read_dir(entry.path().join("fd")) else {
+				continue;
+			};
+			let has_open = fds.flatten().any(|fd| std::fs::read_link(fd.path()).is_ok_and(|target| target == path));
+			if has_open {
+				let name = std::fs::read_to_string(entry.path().join("comm")).ok().map(|x| x.trim_end().to_owned());
+				processes.push(super::ProcessInfo { pid, name });
+			}
+		}
+		processes
+	}
+}
+
+#[cfg(windows)]
+mod sys {
+	use std::path::Path;
+	use winapi::shared::winerror;
+
+	use super::OpenDiagnosis;
+
+	pub fn diagnose(path: &Path, error: &std::io::Error, diagnosis: &mut OpenDiagnosis) {
+		let name = path.to_string_lossy();
+		let name = name.strip_prefix(r"\\.\").unwrap_or(&name);
+		if let Ok(ports) = serial2::SerialPort::available_ports() {
+			let exists = ports.iter().any(|port| port.to_string_lossy().eq_ignore_ascii_case(name));
+			diagnosis.device_exists = Some(exists);
+		}
+
+		match error.raw_os_error().map(|x| x as u32) {
+			Some(winerror::ERROR_FILE_NOT_FOUND) | Some(winerror::ERROR_PATH_NOT_FOUND) => {
+				diagnosis.device_exists = Some(false);
+			},
+			Some(winerror::ERROR_ACCESS_DENIED) | Some(winerror::ERROR_SHARING_VIOLATION) if diagnosis.device_exists != Some(false) => {
+				diagnosis.hints.push("The device is probably in use by another application. Serial ports can only be opened by one application at a time on Windows.".into());
+			},
+			_ => (),
+		}
+	}
+}
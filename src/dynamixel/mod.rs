@@ -0,0 +1,592 @@
+//! Control Dynamixel servos and other devices on a half-duplex servo bus.
+//!
+//! Dynamixel servos from Robotis, and compatible devices, share a single half-duplex bus.
+//! The host sends instruction packets to a device ID, and the addressed device answers with a status packet.
+//! Both version 1.0 and version 2.0 of the protocol are supported.
+//!
+//! There are a few ways to connect a servo bus to a serial port:
+//!
+//! * With an adapter that switches the bus direction by itself, like the U2D2.
+//!   Use [`DynamixelBus::new()`].
+//! * With an RS-485 transceiver or a TTL tri-state buffer that is controlled by the RTS line.
+//!   Use [`DynamixelBus::with_half_duplex()`] with a [`HalfDuplexPort`] to toggle the RTS line from software,
+//!   or enable RS-485 mode in the kernel driver and use [`DynamixelBus::new()`].
+//! * With the TX and RX lines of the UART connected to the TTL bus directly or through a resistor.
+//!   In this case, the serial port also receives everything the host transmits.
+//!   Enable echo suppression with [`DynamixelBus::set_echo()`] to discard the echo.
+//!
+//! This module is only available when the `dynamixel` feature is enabled.
+//!
+//! # Example
+//! ```no_run
+//! # async fn example() -> std::io::Result<()> {
+//! use serial2_tokio::SerialPort;
+//! use serial2_tokio::dynamixel::{DynamixelBus, Protocol};
+//!
+//! let port = SerialPort::open("/dev/ttyUSB0", 57600)?;
+//! let mut bus = DynamixelBus::new(port, Protocol::V2);
+//!
+//! // Enable the torque of servo 1 and move it to the center position.
+//! bus.write(1, 64, &[1]).await?;
+//! bus.write(1, 116, &2048u32.to_le_bytes()).await?;
+//!
+//! let position = bus.read(1, 132, 4).await?;
+//! println!("present position: {}", u32::from_le_bytes([position[0], position[1], position[2], position[3]]));
+//! # Ok(())
+//! # }
+//! ```
+
+use std::time::Duration;
+
+use crate::SerialPort;
+use crate::half_duplex::HalfDuplexPort;
+
+mod v1;
+mod v2;
+
+/// The ID used to send an instruction to all devices on the bus.
+pub const BROADCAST_ID: u8 = 0xFE;
+
+/// The default timeout for a status packet.
+const DEFAULT_TIMEOUT: Duration = Duration::from_millis(100);
+
+const INSTRUCTION_PING: u8 = 0x01;
+const INSTRUCTION_READ: u8 = 0x02;
+const INSTRUCTION_WRITE: u8 = 0x03;
+const INSTRUCTION_REG_WRITE: u8 = 0x04;
+const INSTRUCTION_ACTION: u8 = 0x05;
+const INSTRUCTION_REBOOT: u8 = 0x08;
+const INSTRUCTION_SYNC_WRITE: u8 = 0x83;
+
+/// The bit in the error byte of a version 2.0 status packet that signals a hardware error.
+const HARDWARE_ALERT: u8 = 0x80;
+
+/// The version of the Dynamixel protocol.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Protocol {
+	/// Version 1.0 of the protocol, used by older servos like the AX-12 and MX series with protocol 1.0 firmware.
+	V1,
+
+	/// Version 2.0 of the protocol, used by the X series and newer servos.
+	V2,
+}
+
+/// A status packet received from a device.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct StatusPacket {
+	/// The ID of the device that sent the status packet.
+	pub id: u8,
+
+	/// The error byte of the status packet.
+	///
+	/// For version 1.0 of the protocol, each bit is a separate error flag.
+	/// For version 2.0 of the protocol, the lower 7 bits are an error number,
+	/// and the highest bit signals that the device has a hardware error.
+	pub error: u8,
+
+	/// The parameters of the status packet.
+	pub params: Vec<u8>,
+}
+
+/// An error reported by a device in a status packet.
+///
+/// The functions of the [`DynamixelBus`] report errors in status packets as an [`std::io::Error`] of kind [`std::io::ErrorKind::Other`] that wraps this type.
+/// For version 2.0 of the protocol, the hardware error alert bit alone is not reported as an error.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct StatusError {
+	/// The ID of the device that reported the error.
+	pub id: u8,
+
+	/// The error byte of the status packet.
+	pub error: u8,
+}
+
+impl std::fmt::Display for StatusError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "dynamixel device {} reported error 0x{:02X}", self.id, self.error)
+	}
+}
+
+impl std::error::Error for StatusError {}
+
+/// The serial port of a [`DynamixelBus`], with or without software direction control.
+#[derive(Debug)]
+enum Transport {
+	Port(SerialPort),
+	HalfDuplex(HalfDuplexPort),
+}
+
+impl Transport {
+	fn port(&self) -> &SerialPort {
+		match self {
+			Self::Port(port) => port,
+			Self::HalfDuplex(port) => port.get_ref(),
+		}
+	}
+
+	/// Transmit a packet and wait until it has been sent.
+	async fn transmit(&self, packet: &[u8]) -> std::io::Result<()> {
+		match self {
+			Self::Port(port) => {
+				port.write_all(packet).await?;
+				port.drain().await
+			},
+			Self::HalfDuplex(port) => port.write_all(packet).await,
+		}
+	}
+}
+
+/// The result of parsing a status packet from received data.
+enum Parse {
+	/// More data is needed.
+	Incomplete,
+
+	/// The given number of bytes at the start of the data is not a valid status packet.
+	Skip(usize),
+
+	/// A status packet with the given size was found at the start of the data.
+	Packet(StatusPacket, usize),
+}
+
+/// A Dynamixel servo bus.
+///
+/// See the [module documentation](self) for more information.
+#[derive(Debug)]
+pub struct DynamixelBus {
+	transport: Transport,
+	protocol: Protocol,
+	timeout: Duration,
+	echo: bool,
+	buffer: Vec<u8>,
+}
+
+impl DynamixelBus {
+	/// Create a bus on a serial port that does not need software direction control.
+	///
+	/// The serial port must already be configured with the baud rate of the devices.
+	pub fn new(port: SerialPort, protocol: Protocol) -> Self {
+		Self::with_transport(Transport::Port(port), protocol)
+	}
+
+	/// Create a bus on a serial port that toggles the RTS line to switch the bus direction.
+	pub fn with_half_duplex(port: HalfDuplexPort, protocol: Protocol) -> Self {
+		Self::with_transport(Transport::HalfDuplex(port), protocol)
+	}
+
+	fn with_transport(transport: Transport, protocol: Protocol) -> Self {
+		Self {
+			transport,
+			protocol,
+			timeout: DEFAULT_TIMEOUT,
+			echo: false,
+			buffer: Vec::new(),
+		}
+	}
+
+	/// Get the protocol version used on the bus.
+	pub fn protocol(&self) -> Protocol {
+		self.protocol
+	}
+
+	/// Set the timeout for a status packet.
+	///
+	/// The default timeout is 100 milliseconds.
+	pub fn set_timeout(&mut self, timeout: Duration) {
+		self.timeout = timeout;
+	}
+
+	/// Get the timeout for a status packet.
+	pub fn get_timeout(&self) -> Duration {
+		self.timeout
+	}
+
+	/// Enable or disable echo suppression.
+	///
+	/// Enable this if the serial port receives its own transmissions, for example when TX and RX are connected to the same wire.
+	/// With echo suppression enabled, each transmitted packet must be received back exactly,
+	/// or the transaction fails with an error of kind [`std::io::ErrorKind::InvalidData`].
+	pub fn set_echo(&mut self, enable: bool) {
+		self.echo = enable;
+	}
+
+	/// Check if echo suppression is enabled.
+	pub fn get_echo(&self) -> bool {
+		self.echo
+	}
+
+	/// Get a reference to the serial port.
+	pub fn get_ref(&self) -> &SerialPort {
+		self.transport.port()
+	}
+
+	/// Consume the bus and return the serial port.
+	pub fn into_inner(self) -> SerialPort {
+		match self.transport {
+			Transport::Port(port) => port,
+			Transport::HalfDuplex(port) => port.into_inner(),
+		}
+	}
+
+	/// Ping a device.
+	///
+	/// For version 2.0 of the protocol, the parameters of the status packet contain the model number and the firmware version.
+	pub async fn ping(&mut self, id: u8) -> std::io::Result<StatusPacket> {
+		check_id(id)?;
+		self.checked_transaction(id, INSTRUCTION_PING, &[]).await
+	}
+
+	/// Read `len` bytes from the control table of a device, starting at `address`.
+	pub async fn read(&mut self, id: u8, address: u16, len: u16) -> std::io::Result<Vec<u8>> {
+		check_id(id)?;
+		let params = self.address_and_len(address, len)?;
+		let status = self.checked_transaction(id, INSTRUCTION_READ, &params).await?;
+		if status.params.len() != usize::from(len) {
+			return Err(invalid_response(&format!("expected {len} bytes, got {}", status.params.len())));
+		}
+		Ok(status.params)
+	}
+
+	/// Write data to the control table of a device, starting at `address`.
+	///
+	/// Use [`BROADCAST_ID`] to write to all devices without waiting for a status packet.
+	pub async fn write(&mut self, id: u8, address: u16, data: &[u8]) -> std::io::Result<()> {
+		let params = self.address_and_data(address, data)?;
+		self.write_instruction(id, INSTRUCTION_WRITE, &params).await
+	}
+
+	/// Register a write to the control table of a device, to be executed later with [`Self::action()`].
+	///
+	/// Use [`BROADCAST_ID`] to register the write on all devices without waiting for a status packet.
+	pub async fn reg_write(&mut self, id: u8, address: u16, data: &[u8]) -> std::io::Result<()> {
+		let params = self.address_and_data(address, data)?;
+		self.write_instruction(id, INSTRUCTION_REG_WRITE, &params).await
+	}
+
+	/// Execute the writes registered with [`Self::reg_write()`].
+	///
+	/// Use [`BROADCAST_ID`] to start the registered writes on all devices at the same time.
+	pub async fn action(&mut self, id: u8) -> std::io::Result<()> {
+		self.write_instruction(id, INSTRUCTION_ACTION, &[]).await
+	}
+
+	/// Reboot a device.
+	///
+	/// This is only supported by version 2.0 of the protocol.
+	/// For version 1.0, this returns an error of kind [`std::io::ErrorKind::Unsupported`].
+	pub async fn reboot(&mut self, id: u8) -> std::io::Result<()> {
+		if self.protocol == Protocol::V1 {
+			return Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "reboot requires protocol 2.0"));
+		}
+		self.write_instruction(id, INSTRUCTION_REBOOT, &[]).await
+	}
+
+	/// Write the same range of the control table of multiple devices with a single broadcast packet.
+	///
+	/// Each entry holds the ID of a device and the data for that device.
+	/// All data must have the same length.
+	/// The devices do not send a status packet.
+	pub async fn sync_write(&mut self, address: u16, data: &[(u8, &[u8])]) -> std::io::Result<()> {
+		let len = data.first().map(|(_, data)| data.len()).unwrap_or(0);
+		if data.iter().any(|(_, data)| data.len() != len) {
+			return Err(invalid_request("all data for a sync write must have the same length"));
+		}
+		let len = u16::try_from(len).map_err(|_| invalid_request("data is too long"))?;
+		let mut params = self.address_and_len(address, len)?;
+		for (id, data) in data {
+			check_id(*id)?;
+			params.push(*id);
+			params.extend_from_slice(data);
+		}
+		self.transaction(BROADCAST_ID, INSTRUCTION_SYNC_WRITE, &params).await?;
+		Ok(())
+	}
+
+	/// Send an instruction packet and read the status packet.
+	///
+	/// For the broadcast ID, no status packet is read and this returns `None`.
+	/// Errors reported in the status packet are not turned into an error.
+	pub async fn transaction(&mut self, id: u8, instruction: u8, params: &[u8]) -> std::io::Result<Option<StatusPacket>> {
+		let packet = match self.protocol {
+			Protocol::V1 => v1::encode(id, instruction, params)?,
+			Protocol::V2 => v2::encode(id, instruction, params)?,
+		};
+
+		// Discard late status packets and noise on the bus.
+		self.transport.port().discard_input_buffer()?;
+		self.buffer.clear();
+		self.transport.transmit(&packet).await?;
+
+		let timeout = self.timeout;
+		tokio::time::timeout(timeout, async {
+			if self.echo {
+				self.read_echo(&packet).await?;
+			}
+			if id == BROADCAST_ID {
+				return Ok(None);
+			}
+			self.read_status(id).await.map(Some)
+		}).await
+			.map_err(|_| std::io::Error::new(std::io::ErrorKind::TimedOut, "timeout while waiting for dynamixel status packet"))?
+	}
+
+	/// Send an instruction packet, read the status packet and turn errors in the status packet into an error.
+	async fn checked_transaction(&mut self, id: u8, instruction: u8, params: &[u8]) -> std::io::Result<StatusPacket> {
+		let status = self.transaction(id, instruction, params).await?
+			.ok_or_else(|| invalid_request("instruction requires a status packet and can not be broadcast"))?;
+		let error = match self.protocol {
+			Protocol::V1 => status.error,
+			Protocol::V2 => status.error & !HARDWARE_ALERT,
+		};
+		if error != 0 {
+			return Err(std::io::Error::other(StatusError { id, error: status.error }));
+		}
+		Ok(status)
+	}
+
+	/// Send an instruction that only has a status packet for non-broadcast IDs.
+	async fn write_instruction(&mut self, id: u8, instruction: u8, params: &[u8]) -> std::io::Result<()> {
+		if id == BROADCAST_ID {
+			self.transaction(id, instruction, params).await?;
+		} else {
+			check_id(id)?;
+			self.checked_transaction(id, instruction, params).await?;
+		}
+		Ok(())
+	}
+
+	/// Encode the address and length parameters of a read or sync write instruction.
+	fn address_and_len(&self, address: u16, len: u16) -> std::io::Result<Vec<u8>> {
+		match self.protocol {
+			Protocol::V1 => Ok(vec![address_v1(address)?, u8::try_from(len).map_err(|_| invalid_request("length must be below 256 for protocol 1.0"))?]),
+			Protocol::V2 => {
+				let mut params = address.to_le_bytes().to_vec();
+				params.extend_from_slice(&len.to_le_bytes());
+				Ok(params)
+			},
+		}
+	}
+
+	/// Encode the address and data parameters of a write instruction.
+	fn address_and_data(&self, address: u16, data: &[u8]) -> std::io::Result<Vec<u8>> {
+		let mut params = match self.protocol {
+			Protocol::V1 => vec![address_v1(address)?],
+			Protocol::V2 => address.to_le_bytes().to_vec(),
+		};
+		params.extend_from_slice(data);
+		Ok(params)
+	}
+
+	/// Read back the echo of a transmitted packet.
+	async fn read_echo(&mut self, packet: &[u8]) -> std::io::Result<()> {
+		while self.buffer.len() < packet.len() {
+			self.fill().await?;
+		}
+		if self.buffer[..packet.len()] != *packet {
+			return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "echo does not match transmitted packet, possible bus collision"));
+		}
+		self.buffer.drain(..packet.len());
+		Ok(())
+	}
+
+	/// Read a status packet from the given device.
+	async fn read_status(&mut self, id: u8) -> std::io::Result<StatusPacket> {
+		loop {
+			let parsed = match self.protocol {
+				Protocol::V1 => v1::parse(&self.buffer),
+				Protocol::V2 => v2::parse(&self.buffer),
+			};
+			match parsed {
+				Parse::Incomplete => self.fill().await?,
+				Parse::Skip(len) => {
+					self.buffer.drain(..len);
+				},
+				Parse::Packet(status, len) => {
+					self.buffer.drain(..len);
+					if status.id != id {
+						return Err(invalid_response(&format!("expected status packet from device {id}, got {}", status.id)));
+					}
+					return Ok(status);
+				},
+			}
+		}
+	}
+
+	/// Read more data from the serial port into the buffer.
+	async fn fill(&mut self) -> std::io::Result<()> {
+		let mut chunk = [0; 256];
+		match self.transport.port().read(&mut chunk).await? {
+			0 => Err(std::io::ErrorKind::UnexpectedEof.into()),
+			n => {
+				self.buffer.extend_from_slice(&chunk[..n]);
+				Ok(())
+			},
+		}
+	}
+}
+
+/// Skip the data before a possible partial header at the end of the data.
+fn skip_partial_header(data: &[u8], header: &[u8]) -> Parse {
+	let keep = (1..header.len().min(data.len() + 1))
+		.rev()
+		.find(|&len| data.ends_with(&header[..len]))
+		.unwrap_or(0);
+	match data.len() - keep {
+		0 => Parse::Incomplete,
+		skip => Parse::Skip(skip),
+	}
+}
+
+/// Check that an ID is a valid device ID.
+fn check_id(id: u8) -> std::io::Result<()> {
+	if id >= BROADCAST_ID {
+		return Err(invalid_request("device ID must be below 254"));
+	}
+	Ok(())
+}
+
+/// Check that an address fits in the address field of version 1.0 of the protocol.
+fn address_v1(address: u16) -> std::io::Result<u8> {
+	u8::try_from(address).map_err(|_| invalid_request("address must be below 256 for protocol 1.0"))
+}
+
+/// Create an error for an invalid request.
+fn invalid_request(message: &str) -> std::io::Error {
+	std::io::Error::new(std::io::ErrorKind::InvalidInput, message)
+}
+
+/// Create an error for an invalid response.
+fn invalid_response(message: &str) -> std::io::Error {
+	std::io::Error::new(std::io::ErrorKind::InvalidData, format!("invalid dynamixel status packet: {message}"))
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn skip_partial_header_keeps_possible_start() {
+		let header = [0xFF, 0xFF, 0xFD, 0x00];
+		assert!(matches!(skip_partial_header(&[], &header), Parse::Incomplete));
+		assert!(matches!(skip_partial_header(&[0xFF, 0xFF], &header), Parse::Incomplete));
+		assert!(matches!(skip_partial_header(&[0x01, 0x02, 0xFF, 0xFF, 0xFD], &header), Parse::Skip(2)));
+		assert!(matches!(skip_partial_header(&[0x01, 0x02, 0x03], &header), Parse::Skip(3)));
+	}
+
+	#[cfg(all(unix, feature = "unix"))]
+	mod pty {
+		use super::*;
+		use crate::test_util::{expect_then_reply, pair_with_timeout};
+
+		/// Create a bus on one side of a pseudo-terminal pair, with the other side acting as device.
+		fn bus(protocol: Protocol) -> (DynamixelBus, SerialPort) {
+			let (a, b, timeout) = pair_with_timeout();
+			let mut bus = DynamixelBus::new(a, protocol);
+			bus.set_timeout(timeout);
+			(bus, b)
+		}
+
+		#[tokio::test]
+		async fn ping_v2() {
+			let (mut bus, device) = bus(Protocol::V2);
+			let (status, ()) = tokio::join!(
+				bus.ping(1),
+				expect_then_reply(
+					&device,
+					&[0xFF, 0xFF, 0xFD, 0x00, 0x01, 0x03, 0x00, 0x01, 0x19, 0x4E],
+					&[0xFF, 0xFF, 0xFD, 0x00, 0x01, 0x07, 0x00, 0x55, 0x00, 0x06, 0x04, 0x26, 0x65, 0x5D],
+				),
+			);
+			assert_eq!(status.unwrap().params, [0x06, 0x04, 0x26]);
+		}
+
+		#[tokio::test]
+		async fn read_v2() {
+			let (mut bus, device) = bus(Protocol::V2);
+			let (data, ()) = tokio::join!(
+				bus.read(1, 132, 4),
+				expect_then_reply(
+					&device,
+					&[0xFF, 0xFF, 0xFD, 0x00, 0x01, 0x07, 0x00, 0x02, 0x84, 0x00, 0x04, 0x00, 0x1D, 0x15],
+					&[0xFF, 0xFF, 0xFD, 0x00, 0x01, 0x08, 0x00, 0x55, 0x00, 0xA6, 0x00, 0x00, 0x00, 0x8C, 0xC0],
+				),
+			);
+			assert_eq!(data.unwrap(), [0xA6, 0x00, 0x00, 0x00]);
+		}
+
+		#[tokio::test]
+		async fn read_v1() {
+			let (mut bus, device) = bus(Protocol::V1);
+			let (data, ()) = tokio::join!(
+				bus.read(1, 0x2B, 1),
+				expect_then_reply(&device, &[0xFF, 0xFF, 0x01, 0x04, 0x02, 0x2B, 0x01, 0xCC], &[0xFF, 0xFF, 0x01, 0x03, 0x00, 0x20, 0xDB]),
+			);
+			assert_eq!(data.unwrap(), [0x20]);
+		}
+
+		#[tokio::test]
+		async fn status_error_is_reported() {
+			let (mut bus, device) = bus(Protocol::V2);
+			let (result, ()) = tokio::join!(
+				bus.write(1, 64, &[1]),
+				expect_then_reply(
+					&device,
+					&[0xFF, 0xFF, 0xFD, 0x00, 0x01, 0x06, 0x00, 0x03, 0x40, 0x00, 0x01, 0xDB, 0x66],
+					&[0xFF, 0xFF, 0xFD, 0x00, 0x01, 0x04, 0x00, 0x55, 0x02, 0xAE, 0x8C],
+				),
+			);
+			let error = result.unwrap_err();
+			let status = error.get_ref().and_then(|e| e.downcast_ref::<StatusError>()).unwrap();
+			assert_eq!(*status, StatusError { id: 1, error: 0x02 });
+		}
+
+		#[tokio::test]
+		async fn hardware_alert_alone_is_not_an_error() {
+			let (mut bus, device) = bus(Protocol::V2);
+			let (result, ()) = tokio::join!(
+				bus.write(1, 64, &[1]),
+				expect_then_reply(
+					&device,
+					&[0xFF, 0xFF, 0xFD, 0x00, 0x01, 0x06, 0x00, 0x03, 0x40, 0x00, 0x01, 0xDB, 0x66],
+					&[0xFF, 0xFF, 0xFD, 0x00, 0x01, 0x04, 0x00, 0x55, 0x80, 0xA2, 0x8F],
+				),
+			);
+			result.unwrap();
+		}
+
+		#[tokio::test]
+		async fn echo_is_discarded() {
+			let (mut bus, device) = bus(Protocol::V2);
+			bus.set_echo(true);
+			let ping = [0xFF, 0xFF, 0xFD, 0x00, 0x01, 0x03, 0x00, 0x01, 0x19, 0x4E];
+			let mut response = ping.to_vec();
+			response.extend_from_slice(&[0xFF, 0xFF, 0xFD, 0x00, 0x01, 0x07, 0x00, 0x55, 0x00, 0x06, 0x04, 0x26, 0x65, 0x5D]);
+			let (status, ()) = tokio::join!(bus.ping(1), expect_then_reply(&device, &ping, &response));
+			assert_eq!(status.unwrap().id, 1);
+		}
+
+		#[tokio::test]
+		async fn status_from_wrong_device_is_rejected() {
+			let (mut bus, device) = bus(Protocol::V1);
+			let (result, ()) = tokio::join!(
+				bus.ping(2),
+				expect_then_reply(&device, &[0xFF, 0xFF, 0x02, 0x02, 0x01, 0xFA], &[0xFF, 0xFF, 0x01, 0x02, 0x00, 0xFC]),
+			);
+			assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+		}
+
+		#[tokio::test]
+		async fn broadcast_and_invalid_requests() {
+			let (mut bus, device) = bus(Protocol::V1);
+			let (result, ()) = tokio::join!(
+				bus.action(BROADCAST_ID),
+				expect_then_reply(&device, &[0xFF, 0xFF, 0xFE, 0x02, 0x05, 0xFA], &[]),
+			);
+			result.unwrap();
+			assert_eq!(bus.ping(BROADCAST_ID).await.unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+			assert_eq!(bus.read(1, 256, 1).await.unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+			assert_eq!(bus.reboot(1).await.unwrap_err().kind(), std::io::ErrorKind::Unsupported);
+			let error = bus.sync_write(30, &[(1, &[1, 2]), (2, &[3])]).await.unwrap_err();
+			assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+		}
+	}
+}
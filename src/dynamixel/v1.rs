@@ -0,0 +1,104 @@
+//! Version 1.0 of the Dynamixel protocol.
+//!
+//! Each packet is framed as `[0xFF, 0xFF, id, length, instruction or error, parameters..., checksum]`,
+//! where the length counts the parameters plus 2, and the checksum is the inverted sum of all bytes after the header.
+
+use super::{Parse, StatusPacket};
+
+const HEADER: [u8; 2] = [0xFF, 0xFF];
+
+/// The size of a packet without parameters.
+const MIN_PACKET_LEN: usize = 6;
+
+/// Encode an instruction packet.
+pub fn encode(id: u8, instruction: u8, params: &[u8]) -> std::io::Result<Vec<u8>> {
+	let length = u8::try_from(params.len() + 2)
+		.map_err(|_| super::invalid_request("too many parameters for protocol 1.0"))?;
+	let mut packet = Vec::with_capacity(params.len() + MIN_PACKET_LEN);
+	packet.extend_from_slice(&HEADER);
+	packet.extend_from_slice(&[id, length, instruction]);
+	packet.extend_from_slice(params);
+	packet.push(checksum(&packet[2..]));
+	Ok(packet)
+}
+
+/// Parse a status packet at the start of the data.
+pub fn parse(data: &[u8]) -> Parse {
+	match data.windows(2).position(|window| window == HEADER) {
+		Some(0) => (),
+		Some(start) => return Parse::Skip(start),
+		None => return super::skip_partial_header(data, &HEADER),
+	}
+	if data.len() < 4 {
+		return Parse::Incomplete;
+	}
+	// A third 0xFF is not a valid ID, so the header starts one byte later.
+	if data[2] == 0xFF || data[3] < 2 {
+		return Parse::Skip(1);
+	}
+	let len = 4 + usize::from(data[3]);
+	if data.len() < len {
+		return Parse::Incomplete;
+	}
+	if checksum(&data[2..len - 1]) != data[len - 1] {
+		return Parse::Skip(1);
+	}
+	Parse::Packet(StatusPacket {
+		id: data[2],
+		error: data[4],
+		params: data[5..len - 1].to_vec(),
+	}, len)
+}
+
+/// Compute the checksum of the bytes after the header.
+fn checksum(data: &[u8]) -> u8 {
+	!data.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte))
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	fn parse_packet(data: &[u8]) -> (StatusPacket, usize) {
+		match parse(data) {
+			Parse::Packet(status, len) => (status, len),
+			Parse::Incomplete => panic!("incomplete packet"),
+			Parse::Skip(len) => panic!("skipped {len} bytes"),
+		}
+	}
+
+	#[test]
+	fn encode_instructions() {
+		// The examples of the protocol 1.0 e-manual.
+		assert_eq!(encode(0x01, super::super::INSTRUCTION_PING, &[]).unwrap(), [0xFF, 0xFF, 0x01, 0x02, 0x01, 0xFB]);
+		assert_eq!(encode(0x01, super::super::INSTRUCTION_READ, &[0x2B, 0x01]).unwrap(), [0xFF, 0xFF, 0x01, 0x04, 0x02, 0x2B, 0x01, 0xCC]);
+		assert!(encode(0x01, super::super::INSTRUCTION_WRITE, &[0; 254]).is_err());
+	}
+
+	#[test]
+	fn parse_status_packets() {
+		let (status, len) = parse_packet(&[0xFF, 0xFF, 0x01, 0x02, 0x00, 0xFC]);
+		assert_eq!(status, StatusPacket { id: 1, error: 0, params: vec![] });
+		assert_eq!(len, 6);
+
+		let (status, len) = parse_packet(&[0xFF, 0xFF, 0x01, 0x03, 0x00, 0x20, 0xDB, 0xFF]);
+		assert_eq!(status, StatusPacket { id: 1, error: 0, params: vec![0x20] });
+		assert_eq!(len, 7);
+
+		let (status, _) = parse_packet(&[0xFF, 0xFF, 0x01, 0x02, 0x24, 0xD8]);
+		assert_eq!(status.error, 0x24);
+	}
+
+	#[test]
+	fn parse_incomplete_and_invalid_data() {
+		assert!(matches!(parse(&[]), Parse::Incomplete));
+		assert!(matches!(parse(&[0xFF]), Parse::Incomplete));
+		assert!(matches!(parse(&[0xFF, 0xFF, 0x01, 0x03, 0x00]), Parse::Incomplete));
+		assert!(matches!(parse(&[0x12, 0x34, 0xFF]), Parse::Skip(2)));
+		assert!(matches!(parse(&[0x00, 0xFF, 0xFF, 0x01]), Parse::Skip(1)));
+		// Wrong checksum.
+		assert!(matches!(parse(&[0xFF, 0xFF, 0x01, 0x02, 0x00, 0xFD]), Parse::Skip(1)));
+		// A run of three 0xFF bytes.
+		assert!(matches!(parse(&[0xFF, 0xFF, 0xFF, 0x01, 0x02, 0x00, 0xFC]), Parse::Skip(1)));
+	}
+}
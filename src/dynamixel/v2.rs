@@ -0,0 +1,169 @@
+//! Version 2.0 of the Dynamixel protocol.
+//!
+//! Each packet is framed as `[0xFF, 0xFF, 0xFD, 0x00, id, length (2 bytes), instruction, parameters..., crc (2 bytes)]`,
+//! where the length counts the instruction, the parameters and the CRC.
+//! Multi-byte values are little endian, and the CRC is a [CRC-16/UMTS][crate::checksum::crc16_umts] over all preceding bytes.
+//!
+//! To prevent the header from appearing inside a packet, `0xFD` is inserted after each `0xFF 0xFF 0xFD` in the instruction and parameters.
+
+use crate::checksum::crc16_umts;
+
+use super::{Parse, StatusPacket};
+
+const HEADER: [u8; 4] = [0xFF, 0xFF, 0xFD, 0x00];
+
+/// The instruction byte of a status packet.
+const STATUS: u8 = 0x55;
+
+/// The size of a packet without parameters.
+const MIN_PACKET_LEN: usize = 10;
+
+/// Encode an instruction packet.
+pub fn encode(id: u8, instruction: u8, params: &[u8]) -> std::io::Result<Vec<u8>> {
+	let mut body = Vec::with_capacity(params.len() + 1);
+	body.push(instruction);
+	body.extend_from_slice(params);
+	let body = stuff(&body);
+	let length = u16::try_from(body.len() + 2)
+		.map_err(|_| super::invalid_request("too many parameters"))?;
+
+	let mut packet = Vec::with_capacity(body.len() + MIN_PACKET_LEN - 1);
+	packet.extend_from_slice(&HEADER);
+	packet.push(id);
+	packet.extend_from_slice(&length.to_le_bytes());
+	packet.extend_from_slice(&body);
+	let crc = crc16_umts(&packet);
+	packet.extend_from_slice(&crc.to_le_bytes());
+	Ok(packet)
+}
+
+/// Parse a status packet at the start of the data.
+pub fn parse(data: &[u8]) -> Parse {
+	match data.windows(HEADER.len()).position(|window| window == HEADER) {
+		Some(0) => (),
+		Some(start) => return Parse::Skip(start),
+		None => return super::skip_partial_header(data, &HEADER),
+	}
+	if data.len() < 7 {
+		return Parse::Incomplete;
+	}
+	let length = usize::from(u16::from_le_bytes([data[5], data[6]]));
+	if length < 4 {
+		return Parse::Skip(1);
+	}
+	let len = 7 + length;
+	if data.len() < len {
+		return Parse::Incomplete;
+	}
+	if crc16_umts(&data[..len - 2]).to_le_bytes() != data[len - 2..len] {
+		return Parse::Skip(1);
+	}
+	if data[7] != STATUS {
+		// An instruction packet from another master, or our own echo.
+		return Parse::Skip(len);
+	}
+	Parse::Packet(StatusPacket {
+		id: data[4],
+		error: data[8],
+		params: unstuff(&data[9..len - 2]),
+	}, len)
+}
+
+/// Insert `0xFD` after each `0xFF 0xFF 0xFD` sequence.
+fn stuff(data: &[u8]) -> Vec<u8> {
+	let mut output = Vec::with_capacity(data.len());
+	for &byte in data {
+		output.push(byte);
+		if output.ends_with(&HEADER[..3]) {
+			output.push(0xFD);
+		}
+	}
+	output
+}
+
+/// Remove the `0xFD` after each `0xFF 0xFF 0xFD` sequence.
+fn unstuff(data: &[u8]) -> Vec<u8> {
+	let mut output = Vec::with_capacity(data.len());
+	let mut skip_next = false;
+	for &byte in data {
+		if std::mem::take(&mut skip_next) && byte == 0xFD {
+			continue;
+		}
+		output.push(byte);
+		skip_next = output.ends_with(&HEADER[..3]);
+	}
+	output
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	fn parse_packet(data: &[u8]) -> (StatusPacket, usize) {
+		match parse(data) {
+			Parse::Packet(status, len) => (status, len),
+			Parse::Incomplete => panic!("incomplete packet"),
+			Parse::Skip(len) => panic!("skipped {len} bytes"),
+		}
+	}
+
+	#[test]
+	fn encode_instructions() {
+		// The examples of the protocol 2.0 e-manual.
+		assert_eq!(encode(0x01, super::super::INSTRUCTION_PING, &[]).unwrap(), [0xFF, 0xFF, 0xFD, 0x00, 0x01, 0x03, 0x00, 0x01, 0x19, 0x4E]);
+		assert_eq!(
+			encode(0x01, super::super::INSTRUCTION_READ, &[0x84, 0x00, 0x04, 0x00]).unwrap(),
+			[0xFF, 0xFF, 0xFD, 0x00, 0x01, 0x07, 0x00, 0x02, 0x84, 0x00, 0x04, 0x00, 0x1D, 0x15],
+		);
+	}
+
+	#[test]
+	fn encode_stuffs_header_in_parameters() {
+		assert_eq!(
+			encode(0x01, super::super::INSTRUCTION_WRITE, &[0x74, 0x00, 0xFF, 0xFF, 0xFD]).unwrap(),
+			[0xFF, 0xFF, 0xFD, 0x00, 0x01, 0x09, 0x00, 0x03, 0x74, 0x00, 0xFF, 0xFF, 0xFD, 0xFD, 0xC4, 0x85],
+		);
+	}
+
+	#[test]
+	fn parse_status_packets() {
+		// Ping response of an XM430-W210 with firmware version 38.
+		let (status, len) = parse_packet(&[0xFF, 0xFF, 0xFD, 0x00, 0x01, 0x07, 0x00, 0x55, 0x00, 0x06, 0x04, 0x26, 0x65, 0x5D]);
+		assert_eq!(status, StatusPacket { id: 1, error: 0, params: vec![0x06, 0x04, 0x26] });
+		assert_eq!(len, 14);
+
+		let (status, _) = parse_packet(&[0xFF, 0xFF, 0xFD, 0x00, 0x01, 0x08, 0x00, 0x55, 0x00, 0xA6, 0x00, 0x00, 0x00, 0x8C, 0xC0]);
+		assert_eq!(status.params, [0xA6, 0x00, 0x00, 0x00]);
+
+		let (status, _) = parse_packet(&[0xFF, 0xFF, 0xFD, 0x00, 0x01, 0x04, 0x00, 0x55, 0x02, 0xAE, 0x8C]);
+		assert_eq!(status.error, 0x02);
+		assert!(status.params.is_empty());
+	}
+
+	#[test]
+	fn parse_unstuffs_parameters() {
+		let (status, _) = parse_packet(&[0xFF, 0xFF, 0xFD, 0x00, 0x01, 0x08, 0x00, 0x55, 0x00, 0xFF, 0xFF, 0xFD, 0xFD, 0x9A, 0x34]);
+		assert_eq!(status.params, [0xFF, 0xFF, 0xFD]);
+	}
+
+	#[test]
+	fn parse_incomplete_and_invalid_data() {
+		assert!(matches!(parse(&[]), Parse::Incomplete));
+		assert!(matches!(parse(&[0xFF, 0xFF, 0xFD]), Parse::Incomplete));
+		assert!(matches!(parse(&[0xFF, 0xFF, 0xFD, 0x00, 0x01, 0x04, 0x00, 0x55]), Parse::Incomplete));
+		assert!(matches!(parse(&[0x12, 0xFF, 0xFF, 0xFD]), Parse::Skip(1)));
+		assert!(matches!(parse(&[0x12, 0x34, 0x56, 0x78, 0x9A]), Parse::Skip(5)));
+		// Wrong CRC.
+		assert!(matches!(parse(&[0xFF, 0xFF, 0xFD, 0x00, 0x01, 0x04, 0x00, 0x55, 0x02, 0xAE, 0x8D]), Parse::Skip(1)));
+		// An instruction packet is skipped completely.
+		assert!(matches!(parse(&[0xFF, 0xFF, 0xFD, 0x00, 0x01, 0x07, 0x00, 0x02, 0x84, 0x00, 0x04, 0x00, 0x1D, 0x15]), Parse::Skip(14)));
+	}
+
+	#[test]
+	fn stuff_and_unstuff() {
+		let data = [0xFF, 0xFF, 0xFD, 0xFF, 0xFF, 0xFD, 0x01];
+		let stuffed = stuff(&data);
+		assert_eq!(stuffed, [0xFF, 0xFF, 0xFD, 0xFD, 0xFF, 0xFF, 0xFD, 0xFD, 0x01]);
+		assert_eq!(unstuff(&stuffed), data);
+	}
+}
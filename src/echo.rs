@@ -0,0 +1,156 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use crate::SerialPort;
+
+impl SerialPort {
+	/// Remove the echo of transmitted data from the received data.
+	///
+	/// On 2-wire RS-485 and single-wire TTL buses, the host receives everything it transmits.
+	/// With echo suppression enabled, the data written through this handle is remembered,
+	/// and the same bytes are removed from the start of the received data before it is returned by the read functions
+	/// (including [`AsyncRead`][tokio::io::AsyncRead]).
+	///
+	/// The echo is expected to arrive within `window` after the data has been transmitted.
+	/// The transmission time is estimated from the baud rate, character size, parity and stop bits
+	/// when this function is called, so if you change these settings afterwards, you should enable echo suppression again.
+	/// Pending echo bytes that did not arrive within the window are forgotten,
+	/// so that a later reply with the same content is not removed.
+	///
+	/// If a received byte does not match the expected echo, the echo is assumed to be lost or corrupted:
+	/// the byte and everything after it is returned as normal data, and the rest of the pending echo is forgotten.
+	///
+	/// Data written with [`Self::write_vectored()`] is written one buffer at a time while echo suppression is enabled,
+	/// and [`Self::read_vectored()`] only fills the first non-empty buffer.
+	/// Data written through other handles to the same serial port (see [`Self::try_clone()`]) is not suppressed.
+	///
+	/// Pass `None` to disable echo suppression.
+	///
+	/// # Example
+	/// ```no_run
+	/// # async fn example() -> std::io::Result<()> {
+	/// use serial2_tokio::SerialPort;
+	/// use std::time::Duration;
+	///
+	/// let port = SerialPort::open("/dev/ttyUSB0", 115200)?;
+	/// port.set_echo_suppression(Some(Duration::from_millis(10)))?;
+	/// port.write_all(b"\x01\x03\x00\x00\x00\x01\x84\x0A").await?;
+	///
+	/// // The reply of the device, without our own request.
+	/// let mut buffer = [0; 256];
+	/// let read = port.read(&mut buffer).await?;
+	/// println!("{:02X?}", &buffer[..read]);
+	/// # Ok(())
+	/// # }
+	/// ```
+	pub fn set_echo_suppression(&self, window: Option<Duration>) -> std::io::Result<()> {
+		let char_time = match window {
+			Some(_) => crate::pacing::char_time(&self.get_configuration()?)?,
+			None => Duration::ZERO,
+		};
+		let mut state = self.echo.state.lock().unwrap_or_else(|e| e.into_inner());
+		state.window = window;
+		state.char_time = char_time;
+		state.pending.clear();
+		self.echo.enabled.store(window.is_some(), Ordering::Relaxed);
+		Ok(())
+	}
+
+	/// Get the echo suppression window of the serial port, or `None` if echo suppression is disabled.
+	pub fn get_echo_suppression(&self) -> Option<Duration> {
+		self.echo.state.lock().unwrap_or_else(|e| e.into_inner()).window
+	}
+}
+
+/// Keeps track of transmitted data that is expected to be echoed back.
+pub(crate) struct EchoFilter {
+	/// Set when echo suppression is enabled, so reads and writes can skip the lock when it is not.
+	enabled: AtomicBool,
+
+	state: Mutex<EchoState>,
+}
+
+struct EchoState {
+	/// The time window in which the echo is expected, or `None` if echo suppression is disabled.
+	window: Option<Duration>,
+
+	/// The transmitted bytes that have not been echoed yet.
+	pending: VecDeque<u8>,
+
+	/// The estimated time when the last pending byte has been transmitted.
+	tx_end: Instant,
+
+	/// The estimated time to transmit a single character.
+	char_time: Duration,
+}
+
+impl EchoFilter {
+	pub fn new() -> Self {
+		Self {
+			enabled: AtomicBool::new(false),
+			state: Mutex::new(EchoState {
+				window: None,
+				pending: VecDeque::new(),
+				tx_end: Instant::now(),
+				char_time: Duration::ZERO,
+			}),
+		}
+	}
+
+	/// Check if echo suppression is enabled.
+	pub fn is_enabled(&self) -> bool {
+		self.enabled.load(Ordering::Relaxed)
+	}
+
+	/// Remember data that was handed to the OS for transmission.
+	pub fn record(&self, data: &[u8]) {
+		if !self.is_enabled() {
+			return;
+		}
+		let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+		if state.window.is_none() || data.is_empty() {
+			return;
+		}
+		let now = Instant::now();
+		state.expire(now);
+		let tx_start = state.tx_end.max(now);
+		state.tx_end = tx_start + state.char_time * data.len().try_into().unwrap_or(u32::MAX);
+		state.pending.extend(data);
+	}
+
+	/// Remove the expected echo from the start of received data.
+	///
+	/// The remaining data is moved to the start of the buffer, and the new length is returned.
+	pub fn strip(&self, data: &mut [u8]) -> usize {
+		if !self.is_enabled() {
+			return data.len();
+		}
+		let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+		state.expire(Instant::now());
+		let mut matched = 0;
+		while matched < data.len() && state.pending.front() == Some(&data[matched]) {
+			state.pending.pop_front();
+			matched += 1;
+		}
+		if matched < data.len() {
+			state.pending.clear();
+		}
+		data.copy_within(matched.., 0);
+		data.len() - matched
+	}
+}
+
+impl EchoState {
+	/// Forget the pending echo if it did not arrive in time.
+	fn expire(&mut self, now: Instant) {
+		if let Some(window) = self.window {
+			if now > self.tx_end + window {
+				self.pending.clear();
+			}
+		}
+	}
+}
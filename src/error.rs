@@ -0,0 +1,163 @@
+/// A classified serial port error.
+///
+/// All functions in this crate return a plain [`std::io::Error`].
+/// You can convert it into this type with [`From`] to find out what kind of failure occurred,
+/// for example to decide whether to retry an operation, to reopen the serial port or to give up.
+///
+/// The original I/O error is kept in every variant, including the OS error code.
+/// Converting back to [`std::io::Error`] returns the original error unchanged.
+///
+/// # Example
+/// ```no_run
+/// # async fn example() -> std::io::Result<()> {
+/// use serial2_tokio::{Error, SerialPort};
+///
+/// let port = SerialPort::open("/dev/ttyUSB0", 115200)?;
+/// let mut buffer = [0; 256];
+/// match port.read(&mut buffer).await.map_err(Error::from) {
+///     Ok(read) => println!("read {read} bytes"),
+///     Err(Error::DeviceRemoved(_)) => println!("device removed, reopening later"),
+///     Err(Error::TimedOut(_)) => println!("timeout, retrying"),
+///     Err(e) => return Err(e.into()),
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+	/// The requested configuration or operation is not supported by the device or driver.
+	Unsupported(std::io::Error),
+
+	/// The device has been removed, or does not exist.
+	DeviceRemoved(std::io::Error),
+
+	/// The operation timed out.
+	TimedOut(std::io::Error),
+
+	/// Access to the device was denied.
+	PermissionDenied(std::io::Error),
+
+	/// Received data was lost because of a receiver overrun.
+	///
+	/// Only reported when enabled with [`SerialPort::set_overrun_detection()`][crate::SerialPort::set_overrun_detection()].
+	Overrun(std::io::Error),
+
+	/// The operation was cancelled with a cancellation token.
+	///
+	/// Only reported by functions that accept a [`CancellationToken`](https://docs.rs/tokio-util/latest/tokio_util/sync/struct.CancellationToken.html),
+	/// which requires the `cancel` feature.
+	Cancelled(std::io::Error),
+
+	/// Any other I/O error.
+	Io(std::io::Error),
+}
+
+impl Error {
+	/// Classify an I/O error.
+	pub fn classify(error: std::io::Error) -> Self {
+		if crate::overrun::is_overrun(&error) {
+			return Self::Overrun(error);
+		}
+		if crate::cancel::is_cancelled(&error) {
+			return Self::Cancelled(error);
+		}
+		if let Some(code) = error.raw_os_error() {
+			if let Some(classify) = sys::classify_os_error(code) {
+				return classify(error);
+			}
+		}
+		match error.kind() {
+			std::io::ErrorKind::Unsupported => Self::Unsupported(error),
+			std::io::ErrorKind::NotFound => Self::DeviceRemoved(error),
+			std::io::ErrorKind::TimedOut => Self::TimedOut(error),
+			std::io::ErrorKind::PermissionDenied => Self::PermissionDenied(error),
+			_ => Self::Io(error),
+		}
+	}
+
+	/// Get a reference to the underlying I/O error.
+	pub fn io_error(&self) -> &std::io::Error {
+		match self {
+			Self::Unsupported(e) => e,
+			Self::DeviceRemoved(e) => e,
+			Self::TimedOut(e) => e,
+			Self::PermissionDenied(e) => e,
+			Self::Overrun(e) => e,
+			Self::Cancelled(e) => e,
+			Self::Io(e) => e,
+		}
+	}
+
+	/// Get the underlying I/O error.
+	pub fn into_io_error(self) -> std::io::Error {
+		match self {
+			Self::Unsupported(e) => e,
+			Self::DeviceRemoved(e) => e,
+			Self::TimedOut(e) => e,
+			Self::PermissionDenied(e) => e,
+			Self::Overrun(e) => e,
+			Self::Cancelled(e) => e,
+			Self::Io(e) => e,
+		}
+	}
+
+	/// Get the OS error code of the underlying I/O error, if it has one.
+	pub fn raw_os_error(&self) -> Option<i32> {
+		self.io_error().raw_os_error()
+	}
+}
+
+impl From<std::io::Error> for Error {
+	fn from(error: std::io::Error) -> Self {
+		Self::classify(error)
+	}
+}
+
+impl From<Error> for std::io::Error {
+	fn from(error: Error) -> Self {
+		error.into_io_error()
+	}
+}
+
+impl std::fmt::Display for Error {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		self.io_error().fmt(f)
+	}
+}
+
+impl std::error::Error for Error {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		Some(self.io_error())
+	}
+}
+
+type Classify = fn(std::io::Error) -> Error;
+
+#[cfg(unix)]
+mod sys {
+	use super::{Classify, Error};
+
+	pub fn classify_os_error(code: i32) -> Option<Classify> {
+		match code {
+			libc::ENOTTY | libc::EOPNOTSUPP => Some(Error::Unsupported),
+			libc::ENXIO | libc::ENODEV | libc::EIO => Some(Error::DeviceRemoved),
+			_ => None,
+		}
+	}
+}
+
+#[cfg(windows)]
+mod sys {
+	use super::{Classify, Error};
+	use winapi::shared::winerror;
+
+	pub fn classify_os_error(code: i32) -> Option<Classify> {
+		match code as u32 {
+			winerror::ERROR_NOT_SUPPORTED | winerror::ERROR_INVALID_FUNCTION => Some(Error::Unsupported),
+			winerror::ERROR_DEVICE_NOT_CONNECTED | winerror::ERROR_BAD_COMMAND | winerror::ERROR_DEV_NOT_EXIST => Some(Error::DeviceRemoved),
+			winerror::ERROR_SEM_TIMEOUT => Some(Error::TimedOut),
+			_ => None,
+		}
+	}
+}
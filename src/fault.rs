@@ -0,0 +1,373 @@
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{ready, Poll};
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::time::Sleep;
+
+use crate::rng::Rng;
+
+/// The faults to inject in one direction of a [`FaultyPort`].
+///
+/// The corruption, drop and duplication rates are probabilities per byte.
+/// The delay and error rates are probabilities per read or write.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FaultConfig {
+	corrupt_rate: f64,
+	drop_rate: f64,
+	duplicate_rate: f64,
+	delay_rate: f64,
+	max_delay: Duration,
+	error_rate: f64,
+}
+
+impl FaultConfig {
+	/// Create a new configuration that does not inject any faults.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Set the probability that a byte is corrupted by flipping one random bit.
+	///
+	/// # Panics
+	/// This function panics if the probability is not in the range `0.0..=1.0`.
+	pub fn set_corrupt_rate(&mut self, probability: f64) {
+		self.corrupt_rate = check_probability(probability);
+	}
+
+	/// Get the probability that a byte is corrupted.
+	pub fn get_corrupt_rate(&self) -> f64 {
+		self.corrupt_rate
+	}
+
+	/// Set the probability that a byte is dropped.
+	///
+	/// # Panics
+	/// This function panics if the probability is not in the range `0.0..=1.0`.
+	pub fn set_drop_rate(&mut self, probability: f64) {
+		self.drop_rate = check_probability(probability);
+	}
+
+	/// Get the probability that a byte is dropped.
+	pub fn get_drop_rate(&self) -> f64 {
+		self.drop_rate
+	}
+
+	/// Set the probability that a byte is duplicated.
+	///
+	/// # Panics
+	/// This function panics if the probability is not in the range `0.0..=1.0`.
+	pub fn set_duplicate_rate(&mut self, probability: f64) {
+		self.duplicate_rate = check_probability(probability);
+	}
+
+	/// Get the probability that a byte is duplicated.
+	pub fn get_duplicate_rate(&self) -> f64 {
+		self.duplicate_rate
+	}
+
+	/// Set the probability that the data of a read or write is delayed, and the maximum delay.
+	///
+	/// The delay of each delayed read or write is chosen randomly between zero and `max_delay`.
+	///
+	/// # Panics
+	/// This function panics if the probability is not in the range `0.0..=1.0`.
+	pub fn set_delay(&mut self, probability: f64, max_delay: Duration) {
+		self.delay_rate = check_probability(probability);
+		self.max_delay = max_delay;
+	}
+
+	/// Get the probability that the data of a read or write is delayed.
+	pub fn get_delay_rate(&self) -> f64 {
+		self.delay_rate
+	}
+
+	/// Get the maximum delay of a delayed read or write.
+	pub fn get_max_delay(&self) -> Duration {
+		self.max_delay
+	}
+
+	/// Set the probability that a read or write fails with a spurious error.
+	///
+	/// A spurious error does not lose data:
+	/// the data of a failed read is returned by the next read, and the data of a failed write is not written.
+	///
+	/// # Panics
+	/// This function panics if the probability is not in the range `0.0..=1.0`.
+	pub fn set_error_rate(&mut self, probability: f64) {
+		self.error_rate = check_probability(probability);
+	}
+
+	/// Get the probability that a read or write fails with a spurious error.
+	pub fn get_error_rate(&self) -> f64 {
+		self.error_rate
+	}
+}
+
+/// The number of faults that have been injected by a [`FaultyPort`].
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct FaultStats {
+	/// The number of corrupted bytes.
+	pub corrupted: u64,
+
+	/// The number of dropped bytes.
+	pub dropped: u64,
+
+	/// The number of duplicated bytes.
+	pub duplicated: u64,
+
+	/// The number of delayed reads and writes.
+	pub delayed: u64,
+
+	/// The number of reads and writes that failed with a spurious error.
+	pub errors: u64,
+}
+
+/// A wrapper that injects faults into the data of a serial port, to test the robustness of protocol code.
+///
+/// The wrapper can corrupt, drop and duplicate bytes, delay reads and writes, and make them fail with spurious errors.
+/// The faults for received data and transmitted data are configured separately with a [`FaultConfig`].
+/// The random decisions come from a seedable generator, so a test with the same seed and the same data injects the same faults.
+///
+/// The wrapper works with anything that implements [`AsyncRead`] and [`AsyncWrite`],
+/// such as a [`SerialPort`][crate::SerialPort], a [`SimulatedPort`][crate::SimulatedPort] or a [`SocketPort`][crate::SocketPort].
+/// Use the utilities of [`tokio::io::AsyncReadExt`] and [`tokio::io::AsyncWriteExt`] to read and write.
+///
+/// Spurious errors have kind [`std::io::ErrorKind::Other`].
+/// Delays use the clock of Tokio, so they also work with a paused clock.
+///
+/// # Example
+/// ```no_run
+/// # async fn example() -> std::io::Result<()> {
+/// use serial2_tokio::{FaultConfig, FaultyPort, SerialPort};
+///
+/// let port = SerialPort::open("/dev/ttyUSB0", 115200)?;
+/// let mut faults = FaultConfig::new();
+/// faults.set_corrupt_rate(0.001);
+/// faults.set_drop_rate(0.001);
+/// let mut port = FaultyPort::new(port);
+/// port.set_seed(1234);
+/// port.set_read_faults(faults);
+/// // Run the protocol code on `port`, then check that it detected the injected faults.
+/// println!("{:?}", port.stats());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+#[cfg_attr(not(feature = "doc"), allow(rustdoc::broken_intra_doc_links))]
+pub struct FaultyPort<T> {
+	inner: T,
+	rng: Rng,
+	read_faults: FaultConfig,
+	write_faults: FaultConfig,
+	stats: FaultStats,
+	/// Received data after injecting faults, not yet returned to the reader.
+	read_pending: VecDeque<u8>,
+	/// The buffer for reading from the inner port.
+	read_buffer: Vec<u8>,
+	/// The timer for a delayed read.
+	read_delay: Option<Pin<Box<Sleep>>>,
+	/// Data to transmit after injecting faults, not yet written to the inner port.
+	write_pending: Vec<u8>,
+	/// The number of bytes from the caller that `write_pending` was made from.
+	write_consumed: usize,
+	/// The timer for a delayed write.
+	write_delay: Option<Pin<Box<Sleep>>>,
+}
+
+impl<T> FaultyPort<T> {
+	/// Wrap a port without injecting any faults yet.
+	///
+	/// The random number generator is seeded with 0.
+	pub fn new(inner: T) -> Self {
+		Self {
+			inner,
+			rng: Rng::new(0),
+			read_faults: FaultConfig::new(),
+			write_faults: FaultConfig::new(),
+			stats: FaultStats::default(),
+			read_pending: VecDeque::new(),
+			read_buffer: Vec::new(),
+			read_delay: None,
+			write_pending: Vec::new(),
+			write_consumed: 0,
+			write_delay: None,
+		}
+	}
+
+	/// Reset the random number generator with a new seed.
+	pub fn set_seed(&mut self, seed: u64) {
+		self.rng = Rng::new(seed);
+	}
+
+	/// Set the faults to inject into received data.
+	pub fn set_read_faults(&mut self, faults: FaultConfig) {
+		self.read_faults = faults;
+	}
+
+	/// Get the faults that are injected into received data.
+	pub fn get_read_faults(&self) -> &FaultConfig {
+		&self.read_faults
+	}
+
+	/// Set the faults to inject into transmitted data.
+	pub fn set_write_faults(&mut self, faults: FaultConfig) {
+		self.write_faults = faults;
+	}
+
+	/// Get the faults that are injected into transmitted data.
+	pub fn get_write_faults(&self) -> &FaultConfig {
+		&self.write_faults
+	}
+
+	/// Get the number of faults that have been injected so far.
+	pub fn stats(&self) -> &FaultStats {
+		&self.stats
+	}
+
+	/// Get a reference to the wrapped port.
+	pub fn get_ref(&self) -> &T {
+		&self.inner
+	}
+
+	/// Get a mutable reference to the wrapped port.
+	pub fn get_mut(&mut self) -> &mut T {
+		&mut self.inner
+	}
+
+	/// Consume the wrapper and get the wrapped port back.
+	///
+	/// Data that was delayed or held back by the wrapper is lost.
+	pub fn into_inner(self) -> T {
+		self.inner
+	}
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for FaultyPort<T> {
+	fn poll_read(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<std::io::Result<()>> {
+		let this = self.get_mut();
+		loop {
+			if let Some(delay) = &mut this.read_delay {
+				ready!(delay.as_mut().poll(cx));
+				this.read_delay = None;
+			}
+
+			if !this.read_pending.is_empty() {
+				let count = this.read_pending.len().min(buf.remaining());
+				for byte in this.read_pending.drain(..count) {
+					buf.put_slice(&[byte]);
+				}
+				return Poll::Ready(Ok(()));
+			}
+
+			this.read_buffer.resize(buf.remaining(), 0);
+			let mut read_buf = ReadBuf::new(&mut this.read_buffer);
+			ready!(Pin::new(&mut this.inner).poll_read(cx, &mut read_buf))?;
+			let data = read_buf.filled();
+			if data.is_empty() {
+				return Poll::Ready(Ok(()));
+			}
+
+			mangle(&mut this.rng, &mut this.stats, &this.read_faults, data, &mut this.read_pending);
+			maybe_error(&mut this.rng, &mut this.stats, &this.read_faults)?;
+			this.read_delay = maybe_delay(&mut this.rng, &mut this.stats, &this.read_faults);
+			// If all bytes were dropped, read again instead of reporting the end of the stream.
+		}
+	}
+}
+
+impl<T: AsyncWrite + Unpin> FaultyPort<T> {
+	/// Write the pending data to the inner port, after the delay.
+	fn poll_write_pending(&mut self, cx: &mut std::task::Context<'_>) -> Poll<std::io::Result<()>> {
+		if let Some(delay) = &mut self.write_delay {
+			ready!(delay.as_mut().poll(cx));
+			self.write_delay = None;
+		}
+		while !self.write_pending.is_empty() {
+			let written = ready!(Pin::new(&mut self.inner).poll_write(cx, &self.write_pending))?;
+			if written == 0 {
+				return Poll::Ready(Err(std::io::ErrorKind::WriteZero.into()));
+			}
+			self.write_pending.drain(..written);
+		}
+		Poll::Ready(Ok(()))
+	}
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for FaultyPort<T> {
+	fn poll_write(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>> {
+		let this = self.get_mut();
+		// Data from an earlier call that returned `Poll::Pending` must be finished first.
+		// The caller retries with the same data, so report the bytes it was made from as written.
+		if this.write_consumed == 0 {
+			if buf.is_empty() {
+				return Poll::Ready(Ok(0));
+			}
+			maybe_error(&mut this.rng, &mut this.stats, &this.write_faults)?;
+			mangle(&mut this.rng, &mut this.stats, &this.write_faults, buf, &mut this.write_pending);
+			this.write_delay = maybe_delay(&mut this.rng, &mut this.stats, &this.write_faults);
+			this.write_consumed = buf.len();
+		}
+		ready!(this.poll_write_pending(cx))?;
+		Poll::Ready(Ok(std::mem::take(&mut this.write_consumed)))
+	}
+
+	fn poll_flush(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<std::io::Result<()>> {
+		let this = self.get_mut();
+		ready!(this.poll_write_pending(cx))?;
+		Pin::new(&mut this.inner).poll_flush(cx)
+	}
+
+	fn poll_shutdown(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<std::io::Result<()>> {
+		let this = self.get_mut();
+		ready!(this.poll_write_pending(cx))?;
+		Pin::new(&mut this.inner).poll_shutdown(cx)
+	}
+}
+
+/// Check that a probability is in the range `0.0..=1.0`.
+fn check_probability(probability: f64) -> f64 {
+	assert!((0.0..=1.0).contains(&probability), "the probability must be between 0.0 and 1.0");
+	probability
+}
+
+/// Inject the byte faults of `faults` into `data`, and append the result to `output`.
+fn mangle(rng: &mut Rng, stats: &mut FaultStats, faults: &FaultConfig, data: &[u8], output: &mut impl Extend<u8>) {
+	for &byte in data {
+		if rng.chance(faults.drop_rate) {
+			stats.dropped += 1;
+			continue;
+		}
+		let mut byte = byte;
+		if rng.chance(faults.corrupt_rate) {
+			byte ^= 1 << (rng.next_u64() % 8);
+			stats.corrupted += 1;
+		}
+		output.extend([byte]);
+		if rng.chance(faults.duplicate_rate) {
+			output.extend([byte]);
+			stats.duplicated += 1;
+		}
+	}
+}
+
+/// Start a delay with the configured probability.
+fn maybe_delay(rng: &mut Rng, stats: &mut FaultStats, faults: &FaultConfig) -> Option<Pin<Box<Sleep>>> {
+	if !rng.chance(faults.delay_rate) {
+		return None;
+	}
+	stats.delayed += 1;
+	Some(Box::pin(tokio::time::sleep(rng.duration_up_to(faults.max_delay))))
+}
+
+/// Fail with a spurious error with the configured probability.
+fn maybe_error(rng: &mut Rng, stats: &mut FaultStats, faults: &FaultConfig) -> std::io::Result<()> {
+	if !rng.chance(faults.error_rate) {
+		return Ok(());
+	}
+	stats.errors += 1;
+	Err(std::io::Error::other("injected fault"))
+}
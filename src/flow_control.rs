@@ -0,0 +1,224 @@
+use crate::SerialPort;
+
+/// The flow control and buffer status of a serial port.
+///
+/// Not all information is available on all platforms.
+/// Fields that are not supported on the current platform are set to `None`.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct FlowControlStatus {
+	/// The number of bytes in the input buffer of the OS.
+	pub input_queue: Option<usize>,
+
+	/// The number of bytes in the output buffer of the OS that have not been transmitted yet.
+	///
+	/// Not supported on all Unix platforms.
+	pub output_queue: Option<usize>,
+
+	/// Transmission is paused because RTS/CTS flow control is enabled and the CTS line is low.
+	///
+	/// On Unix platforms this is derived from the configured flow control and the current state of the CTS line.
+	pub cts_hold: Option<bool>,
+
+	/// Transmission is paused waiting for the DSR line.
+	///
+	/// Only supported on Windows.
+	pub dsr_hold: Option<bool>,
+
+	/// Transmission is paused because an XOFF character was received.
+	///
+	/// Only supported on Windows.
+	pub xoff_hold: Option<bool>,
+
+	/// Transmission is paused because an XOFF character was sent.
+	///
+	/// Only supported on Windows.
+	pub xoff_sent: Option<bool>,
+}
+
+impl SerialPort {
+	/// Ask the remote device to pause or resume transmission by sending an XOFF or XON character.
+	///
+	/// This immediately sends the configured STOP (XOFF) character if `paused` is true,
+	/// or the configured START (XON) character if `paused` is false.
+	/// The character is sent ahead of any data that is waiting in the output buffer.
+	///
+	/// This can be used to implement your own software flow control,
+	/// or to cooperate with a device that uses software flow control when it is not enabled in the OS.
+	pub fn set_tx_paused(&self, paused: bool) -> std::io::Result<()> {
+		self.inner.set_tx_paused(paused)
+	}
+
+	/// Suspend or resume the transmission of data from the output buffer.
+	///
+	/// This behaves as if the remote device sent an XOFF or XON character.
+	/// Data written to the serial port while transmission is suspended is kept in the output buffer of the OS.
+	pub fn set_output_suspended(&self, suspended: bool) -> std::io::Result<()> {
+		self.inner.set_output_suspended(suspended)
+	}
+
+	/// Get the flow control and buffer status of the serial port.
+	///
+	/// This can be used to check if the remote device has paused transmission,
+	/// and how much data is still waiting in the buffers of the OS.
+	///
+	/// On Windows, this also clears the error flags of the serial port in the driver.
+	/// The flags are remembered, so this does not hide overruns from [overrun detection][Self::set_overrun_detection()].
+	pub fn flow_control_status(&self) -> std::io::Result<FlowControlStatus> {
+		self.inner.flow_control_status()
+	}
+
+	/// Request the size of the input and output buffers of the OS driver.
+	///
+	/// A larger input buffer can prevent data loss at high baud rates when the application can not read fast enough.
+	/// A smaller output buffer reduces the latency of writes, because less data can be queued in front of new data.
+	/// The driver may round the sizes or ignore the request altogether.
+	///
+	/// On Windows, this calls `SetupComm()`.
+	/// Unix platforms provide no way to change the buffer sizes of a serial port,
+	/// so this always returns an error of kind [`std::io::ErrorKind::Unsupported`] there.
+	/// On Linux, the TTY layer and most serial drivers use fixed buffers of a few kilobytes.
+	///
+	/// Returns an error of kind [`std::io::ErrorKind::InvalidInput`] if a size is larger than the OS supports.
+	pub fn set_os_buffer_sizes(&self, rx: usize, tx: usize) -> std::io::Result<()> {
+		self.inner.set_os_buffer_sizes(rx, tx)
+	}
+}
+
+/// The configuration of software (XON/XOFF) flow control.
+///
+/// Use [`SerialPort::get_xon_xoff_config()`] and [`SerialPort::set_xon_xoff_config()`] to read and apply the configuration.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct XonXoffConfig {
+	pub(crate) xon_char: u8,
+	pub(crate) xoff_char: u8,
+	pub(crate) input: bool,
+	pub(crate) output: bool,
+}
+
+impl XonXoffConfig {
+	/// The standard XON character (DC1).
+	pub const DEFAULT_XON_CHAR: u8 = 0x11;
+
+	/// The standard XOFF character (DC3).
+	pub const DEFAULT_XOFF_CHAR: u8 = 0x13;
+
+	/// Create a new configuration with the standard control characters and flow control disabled in both directions.
+	pub fn new() -> Self {
+		Self {
+			xon_char: Self::DEFAULT_XON_CHAR,
+			xoff_char: Self::DEFAULT_XOFF_CHAR,
+			input: false,
+			output: false,
+		}
+	}
+
+	/// Set the character used to resume transmission (XON).
+	pub fn set_xon_char(&mut self, xon_char: u8) {
+		self.xon_char = xon_char;
+	}
+
+	/// Get the character used to resume transmission (XON).
+	pub fn get_xon_char(&self) -> u8 {
+		self.xon_char
+	}
+
+	/// Set the character used to pause transmission (XOFF).
+	pub fn set_xoff_char(&mut self, xoff_char: u8) {
+		self.xoff_char = xoff_char;
+	}
+
+	/// Get the character used to pause transmission (XOFF).
+	pub fn get_xoff_char(&self) -> u8 {
+		self.xoff_char
+	}
+
+	/// Enable or disable flow control for incoming data.
+	///
+	/// When enabled, the OS sends the XOFF character when the input buffer is almost full,
+	/// and the XON character when there is room again.
+	pub fn set_input(&mut self, enable: bool) {
+		self.input = enable;
+	}
+
+	/// Check if flow control for incoming data is enabled.
+	pub fn get_input(&self) -> bool {
+		self.input
+	}
+
+	/// Enable or disable flow control for outgoing data.
+	///
+	/// When enabled, the OS pauses transmission when the XOFF character is received,
+	/// and resumes it when the XON character is received.
+	///
+	/// The received XON and XOFF characters are removed from the input stream if and only if this is enabled.
+	/// When it is disabled, the characters are passed through to the application like any other data.
+	pub fn set_output(&mut self, enable: bool) {
+		self.output = enable;
+	}
+
+	/// Check if flow control for outgoing data is enabled.
+	pub fn get_output(&self) -> bool {
+		self.output
+	}
+
+	/// Check if received XON and XOFF characters are removed from the input stream.
+	///
+	/// This is the case when flow control for outgoing data is enabled.
+	pub fn strips_input(&self) -> bool {
+		self.output
+	}
+}
+
+impl Default for XonXoffConfig {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl SerialPort {
+	/// Get the software flow control configuration of the serial port.
+	pub fn get_xon_xoff_config(&self) -> std::io::Result<XonXoffConfig> {
+		self.inner.get_xon_xoff_config()
+	}
+
+	/// Apply a software flow control configuration to the serial port.
+	///
+	/// This can be used to communicate with devices that use non-standard XON and XOFF characters.
+	/// Other settings of the serial port are not changed.
+	///
+	/// On Unix platforms, the configuration is applied after all data in the output buffer has been transmitted,
+	/// just like [`Self::set_configuration()`].
+	///
+	/// # Example
+	/// ```no_run
+	/// # fn example() -> std::io::Result<()> {
+	/// use serial2_tokio::{SerialPort, XonXoffConfig};
+	///
+	/// let port = SerialPort::open("/dev/ttyUSB0", 9600)?;
+	/// let mut config = XonXoffConfig::new();
+	/// config.set_xon_char(b'Q');
+	/// config.set_xoff_char(b'S');
+	/// config.set_input(true);
+	/// config.set_output(true);
+	/// port.set_xon_xoff_config(&config)?;
+	/// # Ok(())
+	/// # }
+	/// ```
+	pub fn set_xon_xoff_config(&self, config: &XonXoffConfig) -> std::io::Result<()> {
+		self.inner.set_xon_xoff_config(config)
+	}
+
+	/// Discard the input and output buffers without leaving the remote device paused.
+	///
+	/// If the OS sent an XOFF character because the input buffer was full,
+	/// discarding the buffers with [`Self::discard_buffers()`] may leave the remote device waiting for an XON character forever.
+	/// This function discards both buffers and then sends an XON character if flow control for incoming data is enabled.
+	pub fn discard_buffers_keeping_flow_state(&self) -> std::io::Result<()> {
+		self.discard_buffers()?;
+		if self.get_xon_xoff_config()?.input {
+			self.set_tx_paused(false)?;
+		}
+		Ok(())
+	}
+}
@@ -0,0 +1,382 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use crate::SerialPort;
+
+/// How a [`FrameAssembler`] finds the boundaries of frames in a stream of bytes.
+#[derive(Debug, Copy, Clone)]
+#[non_exhaustive]
+pub enum Delimiter {
+	/// Each frame ends with the given byte.
+	///
+	/// The delimiter is not included in the frames, and empty frames are skipped.
+	Byte(u8),
+
+	/// Each frame starts with the `start` byte and ends with the `end` byte.
+	///
+	/// The start and end bytes are not included in the frames.
+	/// Bytes outside of a frame are discarded.
+	/// A start byte inside a frame discards the incomplete frame and starts a new one.
+	StartEnd {
+		/// The byte that starts a frame.
+		start: u8,
+
+		/// The byte that ends a frame.
+		end: u8,
+	},
+
+	/// Each frame has the given length.
+	FixedLength(usize),
+
+	/// Each frame starts with a header of a fixed length, which determines the total length of the frame.
+	///
+	/// Once `header_len` bytes are available, `frame_len` is called with the header.
+	/// It must return the total length of the frame including the header,
+	/// or `None` if the header is not valid.
+	/// If the header is not valid or the length is out of range, the first byte is discarded and the next byte is tried as start of a header.
+	/// The frames include the header.
+	LengthFromHeader {
+		/// The length of the header, which can not be zero.
+		header_len: usize,
+
+		/// The function that parses the header.
+		frame_len: fn(&[u8]) -> Option<usize>,
+	},
+
+	/// Frames are separated by a period of silence of at least the given duration.
+	///
+	/// This is used by Modbus RTU and many simple binary protocols.
+	/// A frame is completed when new data arrives after the silence,
+	/// or when [`FrameAssembler::check_gap()`] is called after the silence.
+	SilenceGap(Duration),
+}
+
+/// An incremental frame extractor for a stream of bytes.
+///
+/// Push received data into the assembler with [`Self::push()`], and take complete frames out with [`Self::next_frame()`].
+/// The boundaries of the frames are determined by a [`Delimiter`].
+///
+/// Data that does not fit the framing is discarded, after which the assembler synchronizes again on the next frame boundary.
+/// The number of discarded bytes is available from [`Self::discarded()`].
+/// Frames that are longer than the [maximum frame length][Self::set_max_frame_len()] are discarded too.
+///
+/// The assembler does not depend on a serial port, so it can be used with any source of data.
+/// Use [`SerialPort::read_frame()`] to read frames from a serial port directly.
+/// If the `codec` feature is enabled, the assembler also implements [`Decoder`][tokio_util::codec::Decoder].
+///
+/// # Example
+/// ```no_run
+/// # async fn example() -> std::io::Result<()> {
+/// use serial2_tokio::{Delimiter, FrameAssembler, SerialPort};
+///
+/// let port = SerialPort::open("/dev/ttyUSB0", 115200)?;
+/// let mut assembler = FrameAssembler::new(Delimiter::StartEnd { start: 0x02, end: 0x03 });
+/// loop {
+///     let frame = port.read_frame(&mut assembler).await?;
+///     println!("received frame: {frame:02X?}");
+/// }
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+#[cfg_attr(not(feature = "doc"), allow(rustdoc::broken_intra_doc_links))]
+pub struct FrameAssembler {
+	delimiter: Delimiter,
+	max_frame_len: usize,
+	/// The incomplete frame, or the data that has not been parsed yet.
+	buffer: Vec<u8>,
+	/// Completed frames that have not been taken yet.
+	frames: VecDeque<Vec<u8>>,
+	/// False while discarding data until the next frame boundary.
+	///
+	/// For [`Delimiter::StartEnd`], this is true while inside a frame.
+	synced: bool,
+	/// The time of the last pushed data.
+	last_data: Option<Instant>,
+	discarded: u64,
+}
+
+/// The default maximum frame length.
+const DEFAULT_MAX_FRAME_LEN: usize = 4096;
+
+impl FrameAssembler {
+	/// Create a new frame assembler.
+	///
+	/// # Panics
+	/// This function panics if the delimiter is [`Delimiter::StartEnd`] with the same start and end byte,
+	/// if it is [`Delimiter::FixedLength`] with a length of zero,
+	/// if it is [`Delimiter::LengthFromHeader`] with a header length of zero,
+	/// or if it is [`Delimiter::SilenceGap`] with a duration of zero.
+	pub fn new(delimiter: Delimiter) -> Self {
+		match delimiter {
+			Delimiter::StartEnd { start, end } => assert!(start != end, "the start and end byte must be different, use `Delimiter::Byte` instead"),
+			Delimiter::FixedLength(len) => assert!(len > 0, "the frame length can not be zero"),
+			Delimiter::LengthFromHeader { header_len, .. } => assert!(header_len > 0, "the header length can not be zero"),
+			Delimiter::SilenceGap(gap) => assert!(!gap.is_zero(), "the silence gap can not be zero"),
+			Delimiter::Byte(_) => (),
+		}
+		Self {
+			delimiter,
+			max_frame_len: DEFAULT_MAX_FRAME_LEN,
+			buffer: Vec::new(),
+			frames: VecDeque::new(),
+			synced: starts_synced(delimiter),
+			last_data: None,
+			discarded: 0,
+		}
+	}
+
+	/// Get the delimiter of the assembler.
+	pub fn delimiter(&self) -> Delimiter {
+		self.delimiter
+	}
+
+	/// Set the maximum length of a frame.
+	///
+	/// Longer frames are discarded.
+	/// This does not apply to [`Delimiter::FixedLength`].
+	/// The default is 4096 bytes.
+	///
+	/// # Panics
+	/// This function panics if the length is zero.
+	pub fn set_max_frame_len(&mut self, len: usize) {
+		assert!(len > 0, "the maximum frame length can not be zero");
+		self.max_frame_len = len;
+	}
+
+	/// Get the maximum length of a frame.
+	pub fn get_max_frame_len(&self) -> usize {
+		self.max_frame_len
+	}
+
+	/// Get the total number of bytes that have been discarded to synchronize on a frame boundary.
+	pub fn discarded(&self) -> u64 {
+		self.discarded
+	}
+
+	/// Add received data to the assembler.
+	///
+	/// The data is timestamped with the current time of the Tokio clock.
+	pub fn push(&mut self, data: &[u8]) {
+		self.push_at(data, Instant::now());
+	}
+
+	/// Add received data to the assembler with an explicit receive time.
+	///
+	/// The receive time is only used for [`Delimiter::SilenceGap`].
+	pub fn push_at(&mut self, data: &[u8], time: Instant) {
+		if data.is_empty() {
+			return;
+		}
+		match self.delimiter {
+			Delimiter::Byte(delimiter) => self.push_delimited(data, delimiter),
+			Delimiter::StartEnd { start, end } => self.push_start_end(data, start, end),
+			Delimiter::FixedLength(len) => {
+				self.buffer.extend_from_slice(data);
+				while self.buffer.len() >= len {
+					self.frames.push_back(self.buffer.drain(..len).collect());
+				}
+			},
+			Delimiter::LengthFromHeader { header_len, frame_len } => {
+				self.buffer.extend_from_slice(data);
+				self.parse_headers(header_len, frame_len);
+			},
+			Delimiter::SilenceGap(_) => {
+				self.check_gap(time);
+				self.last_data = Some(time);
+				if !self.synced {
+					self.discarded += data.len() as u64;
+				} else if self.buffer.len() + data.len() > self.max_frame_len {
+					self.discarded += (self.buffer.len() + data.len()) as u64;
+					self.buffer.clear();
+					self.synced = false;
+				} else {
+					self.buffer.extend_from_slice(data);
+				}
+			},
+		}
+	}
+
+	/// Complete the pending frame if the silence gap has passed at the given time.
+	///
+	/// This only has an effect for [`Delimiter::SilenceGap`].
+	/// Use [`Self::gap_deadline()`] to know when to call this function.
+	pub fn check_gap(&mut self, now: Instant) {
+		let Delimiter::SilenceGap(gap) = self.delimiter else {
+			return;
+		};
+		let Some(last_data) = self.last_data else {
+			return;
+		};
+		if now.saturating_duration_since(last_data) >= gap {
+			if self.synced && !self.buffer.is_empty() {
+				self.frames.push_back(std::mem::take(&mut self.buffer));
+			}
+			self.synced = true;
+			self.last_data = None;
+		}
+	}
+
+	/// Get the time when the pending data becomes a complete frame if no more data arrives.
+	///
+	/// Returns `None` if the delimiter is not [`Delimiter::SilenceGap`] or if no data is pending.
+	pub fn gap_deadline(&self) -> Option<Instant> {
+		let Delimiter::SilenceGap(gap) = self.delimiter else {
+			return None;
+		};
+		Some(self.last_data? + gap)
+	}
+
+	/// Take the next complete frame, if any.
+	pub fn next_frame(&mut self) -> Option<Vec<u8>> {
+		self.frames.pop_front()
+	}
+
+	/// Discard all pending data and complete frames.
+	///
+	/// This does not reset the number of discarded bytes.
+	pub fn clear(&mut self) {
+		self.buffer.clear();
+		self.frames.clear();
+		self.synced = starts_synced(self.delimiter);
+		self.last_data = None;
+	}
+
+	/// Process data for [`Delimiter::Byte`].
+	fn push_delimited(&mut self, data: &[u8], delimiter: u8) {
+		for &byte in data {
+			if byte == delimiter {
+				if self.synced && !self.buffer.is_empty() {
+					self.frames.push_back(std::mem::take(&mut self.buffer));
+				}
+				self.synced = true;
+			} else if !self.synced {
+				self.discarded += 1;
+			} else if self.buffer.len() == self.max_frame_len {
+				self.discarded += self.buffer.len() as u64 + 1;
+				self.buffer.clear();
+				self.synced = false;
+			} else {
+				self.buffer.push(byte);
+			}
+		}
+	}
+
+	/// Process data for [`Delimiter::StartEnd`].
+	///
+	/// Here, `synced` is true while inside a frame.
+	fn push_start_end(&mut self, data: &[u8], start: u8, end: u8) {
+		for &byte in data {
+			if byte == start {
+				if self.synced {
+					self.discarded += self.buffer.len() as u64 + 1;
+				}
+				self.buffer.clear();
+				self.synced = true;
+			} else if !self.synced {
+				self.discarded += 1;
+			} else if byte == end {
+				self.frames.push_back(std::mem::take(&mut self.buffer));
+				self.synced = false;
+			} else if self.buffer.len() == self.max_frame_len {
+				self.discarded += self.buffer.len() as u64 + 2;
+				self.buffer.clear();
+				self.synced = false;
+			} else {
+				self.buffer.push(byte);
+			}
+		}
+	}
+
+	/// Extract frames for [`Delimiter::LengthFromHeader`].
+	fn parse_headers(&mut self, header_len: usize, frame_len: fn(&[u8]) -> Option<usize>) {
+		// The start of the data that has not been consumed yet.
+		// Consumed data is removed from the buffer once at the end, so resynchronizing is linear in the amount of data.
+		let mut start = 0;
+		while self.buffer.len() - start >= header_len {
+			let remaining = &self.buffer[start..];
+			match frame_len(&remaining[..header_len]) {
+				Some(len) if len >= header_len && len <= self.max_frame_len => {
+					if remaining.len() < len {
+						break;
+					}
+					self.frames.push_back(remaining[..len].to_vec());
+					start += len;
+				},
+				_ => {
+					start += 1;
+					self.discarded += 1;
+				},
+			}
+		}
+		self.buffer.drain(..start);
+	}
+}
+
+impl SerialPort {
+	/// Read from the serial port until the frame assembler has a complete frame.
+	///
+	/// If the assembler already has a complete frame, it is returned without reading.
+	/// For [`Delimiter::SilenceGap`], this function also returns when the silence gap passes after the last received data.
+	///
+	/// Returns an error of kind [`std::io::ErrorKind::UnexpectedEof`] if the serial port reported end-of-file.
+	///
+	/// This function is cancel safe: if the future is dropped before it completes,
+	/// the data that has been read is kept in the assembler.
+	pub async fn read_frame(&self, assembler: &mut FrameAssembler) -> std::io::Result<Vec<u8>> {
+		let mut buffer = [0; 256];
+		loop {
+			if let Some(frame) = assembler.next_frame() {
+				return Ok(frame);
+			}
+			let read = match assembler.gap_deadline() {
+				Some(deadline) => match tokio::time::timeout_at(deadline, self.read(&mut buffer)).await {
+					Ok(read) => read?,
+					Err(_) => {
+						assembler.check_gap(Instant::now());
+						continue;
+					},
+				},
+				None => self.read(&mut buffer).await?,
+			};
+			if read == 0 {
+				return Err(std::io::ErrorKind::UnexpectedEof.into());
+			}
+			assembler.push(&buffer[..read]);
+		}
+	}
+}
+
+#[cfg(any(feature = "doc", feature = "codec"))]
+#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "codec")))]
+impl tokio_util::codec::Decoder for FrameAssembler {
+	type Item = Vec<u8>;
+	type Error = std::io::Error;
+
+	fn decode(&mut self, src: &mut bytes::BytesMut) -> std::io::Result<Option<Vec<u8>>> {
+		self.push(src);
+		src.clear();
+		Ok(self.next_frame())
+	}
+
+	fn decode_eof(&mut self, src: &mut bytes::BytesMut) -> std::io::Result<Option<Vec<u8>>> {
+		if let Some(frame) = self.decode(src)? {
+			return Ok(Some(frame));
+		}
+		// At the end of the stream, the silence gap has certainly passed.
+		if let Delimiter::SilenceGap(_) = self.delimiter {
+			if self.synced && !self.buffer.is_empty() {
+				return Ok(Some(std::mem::take(&mut self.buffer)));
+			}
+		}
+		Ok(None)
+	}
+}
+
+/// Check if the assembler starts synchronized for a delimiter.
+///
+/// Only [`Delimiter::StartEnd`] needs to find a frame boundary first.
+fn starts_synced(delimiter: Delimiter) -> bool {
+	!matches!(delimiter, Delimiter::StartEnd { .. })
+}
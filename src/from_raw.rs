@@ -0,0 +1,135 @@
+use crate::SerialPort;
+
+impl SerialPort {
+	/// Wrap an already opened serial port from a raw file descriptor.
+	///
+	/// This can be used when the serial port is opened by another process and passed down,
+	/// such as a privileged helper, a container supervisor or systemd (with `ListenSpecial=` in a socket unit).
+	///
+	/// The file descriptor is put in non-blocking mode.
+	/// The configuration of the serial port is not changed.
+	///
+	/// Returns an error of kind [`std::io::ErrorKind::InvalidInput`] if the file descriptor does not refer to a terminal device.
+	///
+	/// # Safety
+	/// The file descriptor must be open and it must not be owned by anything else.
+	/// Ownership of the file descriptor is transferred to this function:
+	/// it is closed when the returned serial port is dropped, or immediately if this function returns an error.
+	#[cfg(unix)]
+	#[cfg_attr(feature = "doc-cfg", doc(cfg(unix)))]
+	pub unsafe fn from_raw_fd(fd: std::os::fd::RawFd) -> std::io::Result<Self> {
+		use std::os::fd::FromRawFd;
+		Self::from_owned_fd(std::os::fd::OwnedFd::from_raw_fd(fd))
+	}
+
+	/// Wrap an already opened serial port from an owned file descriptor.
+	///
+	/// See [`Self::from_raw_fd()`] for details.
+	///
+	/// # Example
+	/// ```no_run
+	/// # async fn example() -> std::io::Result<()> {
+	/// use serial2_tokio::SerialPort;
+	/// use std::os::fd::{FromRawFd, OwnedFd};
+	///
+	/// // The serial port was opened by a privileged helper and passed down as file descriptor 3.
+	/// let fd = unsafe { OwnedFd::from_raw_fd(3) };
+	/// let port = SerialPort::from_owned_fd(fd)?;
+	/// # Ok(())
+	/// # }
+	/// ```
+	#[cfg(unix)]
+	#[cfg_attr(feature = "doc-cfg", doc(cfg(unix)))]
+	pub fn from_owned_fd(fd: std::os::fd::OwnedFd) -> std::io::Result<Self> {
+		sys::wrap(fd)
+	}
+
+	/// Wrap an already opened serial port from a raw handle.
+	///
+	/// This can be used when the serial port is opened by another process and passed down,
+	/// such as a privileged helper or a service supervisor.
+	///
+	/// The handle must have been opened with the `FILE_FLAG_OVERLAPPED` flag.
+	/// The configuration of the serial port is not changed,
+	/// but the read and write timeouts are replaced, just like when the serial port is opened by this library.
+	///
+	/// Returns an error of kind [`std::io::ErrorKind::InvalidInput`] if the handle does not refer to a serial port.
+	///
+	/// # Safety
+	/// The handle must be open and it must not be owned by anything else.
+	/// Ownership of the handle is transferred to this function:
+	/// it is closed when the returned serial port is dropped, or immediately if this function returns an error.
+	#[cfg(windows)]
+	#[cfg_attr(feature = "doc-cfg", doc(cfg(windows)))]
+	pub unsafe fn from_raw_handle(handle: std::os::windows::io::RawHandle) -> std::io::Result<Self> {
+		use std::os::windows::io::FromRawHandle;
+		Self::from_owned_handle(std::os::windows::io::OwnedHandle::from_raw_handle(handle))
+	}
+
+	/// Wrap an already opened serial port from an owned handle.
+	///
+	/// See [`Self::from_raw_handle()`] for details.
+	#[cfg(windows)]
+	#[cfg_attr(feature = "doc-cfg", doc(cfg(windows)))]
+	pub fn from_owned_handle(handle: std::os::windows::io::OwnedHandle) -> std::io::Result<Self> {
+		sys::wrap(handle)
+	}
+}
+
+#[cfg(unix)]
+mod sys {
+	use std::os::fd::{AsRawFd, FromRawFd, IntoRawFd, OwnedFd};
+
+	use crate::SerialPort;
+
+	/// Wrap a file descriptor of a terminal device in a [`SerialPort`], after making it non-blocking.
+	pub fn wrap(fd: OwnedFd) -> std::io::Result<SerialPort> {
+		let raw = fd.as_raw_fd();
+		let path = std::fs::read_link(format!("/proc/self/fd/{raw}")).ok();
+		if unsafe { libc::isatty(raw) } == 0 {
+			let message = if path.as_ref().is_some_and(|path| path.starts_with("/dev/bus/usb")) {
+				"the file descriptor refers to a raw USB device instead of a serial port: it needs a user space driver for the USB serial adapter"
+			} else {
+				"the file descriptor does not refer to a serial port"
+			};
+			return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, message));
+		}
+		unsafe {
+			let flags = check(libc::fcntl(raw, libc::F_GETFL))?;
+			check(libc::fcntl(raw, libc::F_SETFL, flags | libc::O_NONBLOCK))?;
+		}
+		let name = match path {
+			Some(path) => path.to_string_lossy().into_owned(),
+			None => format!("fd {raw}"),
+		};
+		let port = unsafe { serial2::SerialPort::from_raw_fd(fd.into_raw_fd()) };
+		let inner = crate::inner::SerialPort::wrap(port)?;
+		Ok(SerialPort::from_inner(inner, crate::stats::StatsCollector::new(&name)))
+	}
+
+	fn check(ret: i32) -> std::io::Result<i32> {
+		if ret == -1 {
+			Err(std::io::Error::last_os_error())
+		} else {
+			Ok(ret)
+		}
+	}
+}
+
+#[cfg(windows)]
+mod sys {
+	use std::os::windows::io::{AsRawHandle, OwnedHandle};
+
+	use crate::SerialPort;
+
+	/// Wrap a handle of a communications device in a [`SerialPort`].
+	pub fn wrap(handle: OwnedHandle) -> std::io::Result<SerialPort> {
+		let raw = handle.as_raw_handle();
+		let port = serial2::SerialPort::from(handle);
+		if port.get_configuration().is_err() {
+			return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "the handle does not refer to a serial port"));
+		}
+		let inner = crate::inner::SerialPort::wrap(port)?;
+		Ok(SerialPort::from_inner(inner, crate::stats::StatsCollector::new(&format!("handle {raw:?}"))))
+	}
+}
@@ -0,0 +1,252 @@
+//! Use the modem control lines of a serial port as general purpose I/O.
+//!
+//! The RTS and DTR lines can be used as outputs with an [`OutputPin`].
+//! The CTS, DSR, RI and CD lines can be used as inputs with an [`InputPin`].
+//!
+//! The pins share the serial port through an [`Arc`], so they can be cloned and moved to different tasks freely.
+//! The serial port can still be used for normal communication at the same time.
+//!
+//! If the `embedded-hal` feature is enabled, the pins implement the digital I/O traits of the `embedded-hal` crate.
+//!
+//! # Example
+//! ```no_run
+//! # fn example() -> std::io::Result<()> {
+//! use std::sync::Arc;
+//! use serial2_tokio::SerialPort;
+//! use serial2_tokio::gpio::{InputPin, OutputPin};
+//!
+//! let port = Arc::new(SerialPort::open("/dev/ttyUSB0", 115200)?);
+//! let reset = OutputPin::dtr(port.clone());
+//! let button = InputPin::cts(port.clone());
+//! reset.set_low()?;
+//! if button.is_high()? {
+//!     println!("button pressed");
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::sync::Arc;
+
+use crate::SerialPort;
+
+/// A modem control line that can be used as output.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum OutputLine {
+	/// The Ready To Send line.
+	Rts,
+
+	/// The Data Terminal Ready line.
+	Dtr,
+}
+
+/// A modem status line that can be used as input.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum InputLine {
+	/// The Clear To Send line.
+	Cts,
+
+	/// The Data Set Ready line.
+	Dsr,
+
+	/// The Ring Indicator line.
+	Ri,
+
+	/// The Carrier Detect line.
+	Cd,
+}
+
+/// An output pin backed by a modem control line of a serial port.
+#[derive(Debug, Clone)]
+pub struct OutputPin {
+	port: Arc<SerialPort>,
+	line: OutputLine,
+}
+
+impl OutputPin {
+	/// Create an output pin for the given line of a serial port.
+	pub fn new(port: Arc<SerialPort>, line: OutputLine) -> Self {
+		Self { port, line }
+	}
+
+	/// Create an output pin for the RTS line of a serial port.
+	pub fn rts(port: Arc<SerialPort>) -> Self {
+		Self::new(port, OutputLine::Rts)
+	}
+
+	/// Create an output pin for the DTR line of a serial port.
+	pub fn dtr(port: Arc<SerialPort>) -> Self {
+		Self::new(port, OutputLine::Dtr)
+	}
+
+	/// Get the line used by this pin.
+	pub fn line(&self) -> OutputLine {
+		self.line
+	}
+
+	/// Get the serial port used by this pin.
+	pub fn port(&self) -> &Arc<SerialPort> {
+		&self.port
+	}
+
+	/// Set the state of the pin.
+	///
+	/// See [`SerialPort::set_rts()`] and [`SerialPort::set_dtr()`] for the interaction with hardware flow control.
+	pub fn set(&self, state: bool) -> std::io::Result<()> {
+		match self.line {
+			OutputLine::Rts => self.port.set_rts(state),
+			OutputLine::Dtr => self.port.set_dtr(state),
+		}
+	}
+
+	/// Set the pin high.
+	pub fn set_high(&self) -> std::io::Result<()> {
+		self.set(true)
+	}
+
+	/// Set the pin low.
+	pub fn set_low(&self) -> std::io::Result<()> {
+		self.set(false)
+	}
+}
+
+/// An input pin backed by a modem status line of a serial port.
+#[derive(Debug, Clone)]
+pub struct InputPin {
+	port: Arc<SerialPort>,
+	line: InputLine,
+}
+
+impl InputPin {
+	/// Create an input pin for the given line of a serial port.
+	pub fn new(port: Arc<SerialPort>, line: InputLine) -> Self {
+		Self { port, line }
+	}
+
+	/// Create an input pin for the CTS line of a serial port.
+	pub fn cts(port: Arc<SerialPort>) -> Self {
+		Self::new(port, InputLine::Cts)
+	}
+
+	/// Create an input pin for the DSR line of a serial port.
+	pub fn dsr(port: Arc<SerialPort>) -> Self {
+		Self::new(port, InputLine::Dsr)
+	}
+
+	/// Create an input pin for the RI line of a serial port.
+	pub fn ri(port: Arc<SerialPort>) -> Self {
+		Self::new(port, InputLine::Ri)
+	}
+
+	/// Create an input pin for the CD line of a serial port.
+	pub fn cd(port: Arc<SerialPort>) -> Self {
+		Self::new(port, InputLine::Cd)
+	}
+
+	/// Get the line used by this pin.
+	pub fn line(&self) -> InputLine {
+		self.line
+	}
+
+	/// Get the serial port used by this pin.
+	pub fn port(&self) -> &Arc<SerialPort> {
+		&self.port
+	}
+
+	/// Read the state of the pin.
+	pub fn read(&self) -> std::io::Result<bool> {
+		match self.line {
+			InputLine::Cts => self.port.read_cts(),
+			InputLine::Dsr => self.port.read_dsr(),
+			InputLine::Ri => self.port.read_ri(),
+			InputLine::Cd => self.port.read_cd(),
+		}
+	}
+
+	/// Check if the pin is high.
+	pub fn is_high(&self) -> std::io::Result<bool> {
+		self.read()
+	}
+
+	/// Check if the pin is low.
+	pub fn is_low(&self) -> std::io::Result<bool> {
+		Ok(!self.read()?)
+	}
+}
+
+/// Error type for the `embedded-hal` trait implementations.
+#[cfg(any(feature = "doc", feature = "embedded-hal"))]
+#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "embedded-hal")))]
+#[derive(Debug)]
+pub struct PinError {
+	inner: std::io::Error,
+}
+
+#[cfg(any(feature = "doc", feature = "embedded-hal"))]
+impl PinError {
+	/// Get the underlying I/O error.
+	pub fn into_inner(self) -> std::io::Error {
+		self.inner
+	}
+}
+
+#[cfg(any(feature = "doc", feature = "embedded-hal"))]
+impl std::fmt::Display for PinError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		self.inner.fmt(f)
+	}
+}
+
+#[cfg(any(feature = "doc", feature = "embedded-hal"))]
+impl std::error::Error for PinError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		Some(&self.inner)
+	}
+}
+
+#[cfg(any(feature = "doc", feature = "embedded-hal"))]
+impl From<std::io::Error> for PinError {
+	fn from(inner: std::io::Error) -> Self {
+		Self { inner }
+	}
+}
+
+#[cfg(feature = "embedded-hal")]
+mod embedded_hal_impl {
+	use super::{InputPin, OutputPin, PinError};
+	use embedded_hal::digital;
+
+	impl digital::Error for PinError {
+		fn kind(&self) -> digital::ErrorKind {
+			digital::ErrorKind::Other
+		}
+	}
+
+	impl digital::ErrorType for OutputPin {
+		type Error = PinError;
+	}
+
+	impl digital::ErrorType for InputPin {
+		type Error = PinError;
+	}
+
+	impl digital::OutputPin for OutputPin {
+		fn set_low(&mut self) -> Result<(), Self::Error> {
+			Ok(self.set(false)?)
+		}
+
+		fn set_high(&mut self) -> Result<(), Self::Error> {
+			Ok(self.set(true)?)
+		}
+	}
+
+	impl digital::InputPin for InputPin {
+		fn is_high(&mut self) -> Result<bool, Self::Error> {
+			Ok(self.read()?)
+		}
+
+		fn is_low(&mut self) -> Result<bool, Self::Error> {
+			Ok(!self.read()?)
+		}
+	}
+}
@@ -0,0 +1,228 @@
+//! Half-duplex RS-485 direction control using the RTS line.
+//!
+//! Many RS-485 adapters connect the driver enable pin of the transceiver to the RTS line of the UART.
+//! If the driver supports it, the kernel can toggle the RTS line automatically (see the `rs4xx` module).
+//! On Windows, some drivers can toggle the RTS line automatically during transmissions.
+//! For other adapters, the [`HalfDuplexPort`] wrapper in this module toggles the RTS line from software.
+//!
+//! Use [`SerialPort::direction_control_support()`] to find out which method is supported by a serial port.
+
+use std::time::Duration;
+
+use crate::SerialPort;
+
+/// The kind of RS-485 direction control supported by a serial port.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum DirectionControl {
+	/// The kernel driver supports RS-485 mode.
+	///
+	/// Use `SerialPort::set_rs4xx_mode()` to enable it.
+	/// This is only reported on Linux when the `rs4xx` feature is enabled, when RS-485 mode is already enabled.
+	Kernel,
+
+	/// The driver can toggle the RTS line automatically during transmissions.
+	///
+	/// Use `SerialPort::set_rts_toggle()` to enable it.
+	/// This is only reported on Windows, when RTS toggle mode is already enabled.
+	RtsToggle,
+
+	/// Direction control must be done in software, for example with a [`HalfDuplexPort`].
+	Software,
+}
+
+/// Configuration for software controlled half-duplex direction control.
+#[derive(Debug, Clone, Default)]
+pub struct HalfDuplexConfig {
+	delay_before_send: Duration,
+	delay_after_send: Duration,
+	invert_rts: bool,
+}
+
+impl HalfDuplexConfig {
+	/// Create a new configuration with all delays set to zero and a non-inverted RTS signal.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Set the time to delay after setting the RTS signal, before starting a transmission.
+	///
+	/// This may be needed to give the transceiver and other devices on the bus time to switch direction.
+	pub fn set_delay_before_send(&mut self, delay: Duration) {
+		self.delay_before_send = delay;
+	}
+
+	/// Get the delay time after setting the RTS signal, before starting a transmission.
+	pub fn get_delay_before_send(&self) -> Duration {
+		self.delay_before_send
+	}
+
+	/// Set the time to delay after finishing a transmission, before clearing the RTS signal.
+	///
+	/// The transmission is only considered finished when the OS reports that the output buffer has been drained.
+	/// Some drivers report this before the last byte has fully left the UART,
+	/// in which case you should add a delay of at least one character time.
+	pub fn set_delay_after_send(&mut self, delay: Duration) {
+		self.delay_after_send = delay;
+	}
+
+	/// Get the delay time after finishing a transmission, before clearing the RTS signal.
+	pub fn get_delay_after_send(&self) -> Duration {
+		self.delay_after_send
+	}
+
+	/// Invert the RTS signal: set it low during transmissions and high after.
+	pub fn set_invert_rts(&mut self, invert: bool) {
+		self.invert_rts = invert;
+	}
+
+	/// Check if the RTS signal is inverted.
+	pub fn get_invert_rts(&self) -> bool {
+		self.invert_rts
+	}
+}
+
+/// A serial port with software controlled half-duplex direction control.
+///
+/// The RTS line is asserted before each transmission, and deasserted after the transmission has been drained from the output buffer.
+/// Transmissions from multiple tasks are serialized, so they will not be interleaved.
+///
+/// Note that most transceivers in half-duplex mode still receive their own transmission.
+/// You may need to discard the echo when reading, for example with [`SerialPort::set_echo_suppression()`].
+///
+/// # Example
+/// ```no_run
+/// # async fn example() -> std::io::Result<()> {
+/// use std::time::Duration;
+/// use serial2_tokio::SerialPort;
+/// use serial2_tokio::half_duplex::{HalfDuplexConfig, HalfDuplexPort};
+///
+/// let port = SerialPort::open("/dev/ttyUSB0", 115200)?;
+/// let mut config = HalfDuplexConfig::new();
+/// config.set_delay_after_send(Duration::from_micros(100));
+/// let port = HalfDuplexPort::new(port, config)?;
+/// port.write_all(b"hello").await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct HalfDuplexPort {
+	port: SerialPort,
+	config: HalfDuplexConfig,
+	tx_lock: tokio::sync::Mutex<()>,
+}
+
+impl HalfDuplexPort {
+	/// Wrap a serial port for half-duplex communication.
+	///
+	/// This immediately puts the transceiver in receive mode by clearing the RTS signal.
+	pub fn new(port: SerialPort, config: HalfDuplexConfig) -> std::io::Result<Self> {
+		port.set_rts(config.invert_rts)?;
+		Ok(Self {
+			port,
+			config,
+			tx_lock: tokio::sync::Mutex::new(()),
+		})
+	}
+
+	/// Get a reference to the wrapped serial port.
+	///
+	/// Note that writing directly to the serial port bypasses the direction control.
+	pub fn get_ref(&self) -> &SerialPort {
+		&self.port
+	}
+
+	/// Get the half-duplex configuration.
+	pub fn config(&self) -> &HalfDuplexConfig {
+		&self.config
+	}
+
+	/// Consume the wrapper and return the wrapped serial port.
+	pub fn into_inner(self) -> SerialPort {
+		self.port
+	}
+
+	/// Read bytes from the serial port.
+	///
+	/// See [`SerialPort::read()`] for more information.
+	pub async fn read(&self, buf: &mut [u8]) -> std::io::Result<usize> {
+		self.port.read(buf).await
+	}
+
+	/// Transmit all bytes as a single transmission.
+	///
+	/// This asserts the RTS line, waits for the configured delay, writes the data,
+	/// waits for the output buffer to drain, waits for the configured delay and finally deasserts the RTS line.
+	///
+	/// If the returned future is dropped before it completes, the RTS line is deasserted immediately.
+	pub async fn write_all(&self, buf: &[u8]) -> std::io::Result<()> {
+		let _lock = self.tx_lock.lock().await;
+		let guard = TransmitGuard::new(self)?;
+		if !self.config.delay_before_send.is_zero() {
+			tokio::time::sleep(self.config.delay_before_send).await;
+		}
+		self.port.write_all(buf).await?;
+		self.port.drain().await?;
+		if !self.config.delay_after_send.is_zero() {
+			tokio::time::sleep(self.config.delay_after_send).await;
+		}
+		guard.finish()
+	}
+}
+
+/// Guard that puts the transceiver back in receive mode when dropped.
+struct TransmitGuard<'a> {
+	port: &'a HalfDuplexPort,
+	armed: bool,
+}
+
+impl<'a> TransmitGuard<'a> {
+	/// Put the transceiver in transmit mode.
+	fn new(port: &'a HalfDuplexPort) -> std::io::Result<Self> {
+		port.port.set_rts(!port.config.invert_rts)?;
+		Ok(Self { port, armed: true })
+	}
+
+	/// Put the transceiver back in receive mode, reporting any error.
+	fn finish(mut self) -> std::io::Result<()> {
+		self.armed = false;
+		self.port.port.set_rts(self.port.config.invert_rts)
+	}
+}
+
+impl Drop for TransmitGuard<'_> {
+	fn drop(&mut self) {
+		if self.armed {
+			self.port.port.set_rts(self.port.config.invert_rts).ok();
+		}
+	}
+}
+
+impl SerialPort {
+	/// Check what kind of RS-485 direction control is supported by the serial port.
+	///
+	/// This allows cross-platform applications to use the driver supported direction control when available,
+	/// and fall back to software direction control with a [`HalfDuplexPort`] otherwise.
+	///
+	/// This only queries the serial port and never changes its configuration.
+	/// Drivers do not reliably report if they support RS-485 mode or RTS toggle mode:
+	/// on Linux, many drivers accept the request to read the RS-485 configuration even if they do not support RS-485 at all.
+	/// So [`DirectionControl::Kernel`] and [`DirectionControl::RtsToggle`] are only reported when they are already enabled.
+	/// You can try to enable them with `SerialPort::set_rs4xx_mode()` or `SerialPort::set_rts_toggle()`,
+	/// which fail if the driver rejects the mode.
+	#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "half-duplex")))]
+	pub fn direction_control_support(&self) -> DirectionControl {
+		#[cfg(all(feature = "rs4xx", target_os = "linux"))]
+		if let Ok(crate::rs4xx::TransceiverMode::Rs422 | crate::rs4xx::TransceiverMode::Rs485(_)) = self.get_rs4xx_mode() {
+			// The kernel reports RS-422 as RS-485 mode with the RS-422 flag set.
+			return DirectionControl::Kernel;
+		}
+
+		#[cfg(windows)]
+		if self.inner.get_rts_toggle().unwrap_or(false) {
+			return DirectionControl::RtsToggle;
+		}
+
+		DirectionControl::Software
+	}
+}
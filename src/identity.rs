@@ -0,0 +1,180 @@
+use std::path::{Path, PathBuf};
+
+use crate::SerialPort;
+
+/// Identifiers of a serial port device that do not depend on the order in which devices were detected.
+///
+/// Device names like `/dev/ttyUSB0` or `COM3` are assigned in the order the devices are detected,
+/// so they can refer to a different adapter after a reboot or after re-plugging the devices.
+/// Applications that need to remember which device to use should store a stable identifier instead.
+///
+/// Use [`SerialPort::identity()`] for an open serial port, or [`PortIdentity::resolve()`] for a path.
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct PortIdentity {
+	/// The path of the device itself, with all symbolic links resolved, like `/dev/ttyUSB0`.
+	///
+	/// On Windows, this is the name of the device, like `COM3`.
+	pub device_path: PathBuf,
+
+	/// A path that refers to the same device as long as it is connected, regardless of the detection order.
+	///
+	/// On Linux, this is the link in `/dev/serial/by-id` that points to the device.
+	/// These links are created by `udev` for USB devices, based on the vendor, product and serial number of the device.
+	/// Devices without a serial number may not have such a link, and two identical adapters without a serial number may share one.
+	///
+	/// On Windows, this is the device instance ID of the device (see [`PortInfo::instance_id`][crate::PortInfo::instance_id]).
+	/// It can be passed to [`SerialPort::open()`] like a path.
+	///
+	/// This is `None` if the platform does not provide stable paths or if no link exists for the device.
+	pub stable_path: Option<PathBuf>,
+
+	/// A path that refers to the device connected to a specific physical port, like a USB port or a PCI slot.
+	///
+	/// On Linux, this is the link in `/dev/serial/by-path` that points to the device.
+	/// Use this instead of [`Self::stable_path`] to tell identical adapters apart by where they are plugged in.
+	///
+	/// This is `None` if the platform does not provide location based paths or if no link exists for the device.
+	pub location_path: Option<PathBuf>,
+}
+
+impl PortIdentity {
+	/// Get the identity of the serial port device at the given path.
+	///
+	/// The path may be a symbolic link, such as one of the links in `/dev/serial/by-id` on Linux.
+	///
+	/// # Example
+	/// ```no_run
+	/// # fn example() -> std::io::Result<()> {
+	/// use serial2_tokio::PortIdentity;
+	///
+	/// let identity = PortIdentity::resolve("/dev/ttyUSB0")?;
+	/// if let Some(stable_path) = &identity.stable_path {
+	///     println!("next time, open {}", stable_path.display());
+	/// }
+	/// # Ok(())
+	/// # }
+	/// ```
+	pub fn resolve(path: impl AsRef<Path>) -> std::io::Result<Self> {
+		sys::resolve(path.as_ref())
+	}
+}
+
+impl SerialPort {
+	/// Get the identity of the serial port device.
+	///
+	/// This finds the stable identifiers of the device that was opened,
+	/// even if it was opened through a path that has since been assigned to another device.
+	///
+	/// Only supported on Unix platforms.
+	/// On other platforms, this function returns an error of kind [`std::io::ErrorKind::Unsupported`].
+	/// Use [`PortIdentity::resolve()`] with the name of the device instead.
+	pub fn identity(&self) -> std::io::Result<PortIdentity> {
+		sys::identity(self)
+	}
+
+	/// Get a path for a serial port device that does not depend on the order in which devices were detected.
+	///
+	/// This returns [`PortIdentity::stable_path`] if the device has one,
+	/// and the path of the device itself otherwise.
+	/// See [`PortIdentity::resolve()`] for more information.
+	pub fn resolve_stable_path(path: impl AsRef<Path>) -> std::io::Result<PathBuf> {
+		let identity = PortIdentity::resolve(path)?;
+		Ok(identity.stable_path.unwrap_or(identity.device_path))
+	}
+}
+
+#[cfg(unix)]
+mod sys {
+	use std::os::fd::AsRawFd;
+	use std::os::unix::fs::{FileTypeExt, MetadataExt};
+	use std::path::{Path, PathBuf};
+
+	use super::PortIdentity;
+	use crate::SerialPort;
+
+	pub fn resolve(path: &Path) -> std::io::Result<PortIdentity> {
+		let metadata = std::fs::metadata(path)?;
+		if !metadata.file_type().is_char_device() {
+			return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("not a character device: {}", path.display())));
+		}
+		let device_path = std::fs::canonicalize(path)?;
+		Ok(identity_of(device_path, metadata.rdev()))
+	}
+
+	pub fn identity(port: &SerialPort) -> std::io::Result<PortIdentity> {
+		let fd = port.inner.with_raw(|raw| raw.as_raw_fd());
+		let device = unsafe {
+			let mut stat: libc::stat = std::mem::zeroed();
+			if libc::fstat(fd, &mut stat) != 0 {
+				return Err(std::io::Error::last_os_error());
+			}
+			// The type of `dev_t` differs between platforms.
+			#[allow(clippy::unnecessary_cast)]
+			let device = stat.st_rdev as u64;
+			device
+		};
+		let device_path = std::fs::read_link(format!("/proc/self/fd/{fd}"))
+			.or_else(|_| find_device_path(device))?;
+		Ok(identity_of(device_path, device))
+	}
+
+	/// Build the identity of a device from its path and device number.
+	fn identity_of(device_path: PathBuf, device: u64) -> PortIdentity {
+		PortIdentity {
+			device_path,
+			stable_path: find_link("/dev/serial/by-id", device),
+			location_path: find_link("/dev/serial/by-path", device),
+		}
+	}
+
+	/// Find a link in a directory that points to the device with the given device number.
+	fn find_link(dir: &str, device: u64) -> Option<PathBuf> {
+		let mut links: Vec<PathBuf> = std::fs::read_dir(dir)
+			.ok()?
+			.filter_map(|entry| Some(entry.ok()?.path()))
+			.filter(|path| std::fs::metadata(path).is_ok_and(|metadata| metadata.rdev() == device))
+			.collect();
+		// Multiple links can point to the same device: pick one consistently.
+		links.sort();
+		links.into_iter().next()
+	}
+
+	/// Find the path of a device in `/dev` by its device number.
+	///
+	/// Used on platforms without `/proc/self/fd`.
+	fn find_device_path(device: u64) -> std::io::Result<PathBuf> {
+		std::fs::read_dir("/dev")?
+			.filter_map(|entry| Some(entry.ok()?.path()))
+			.find(|path| {
+				std::fs::symlink_metadata(path)
+					.is_ok_and(|metadata| metadata.file_type().is_char_device() && metadata.rdev() == device)
+			})
+			.ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "could not find the device path of the serial port"))
+	}
+}
+
+#[cfg(windows)]
+mod sys {
+	use std::path::{Path, PathBuf};
+
+	use super::PortIdentity;
+	use crate::SerialPort;
+
+	pub fn resolve(path: &Path) -> std::io::Result<PortIdentity> {
+		let device_path = crate::port_info::resolve_instance_id(path)?.into_owned();
+		let instance_id = SerialPort::available_ports_info()?
+			.into_iter()
+			.find(|port| port.path.as_os_str().eq_ignore_ascii_case(device_path.as_os_str()))
+			.and_then(|port| port.instance_id);
+		Ok(PortIdentity {
+			device_path,
+			stable_path: instance_id.map(PathBuf::from),
+			location_path: None,
+		})
+	}
+
+	pub fn identity(_port: &SerialPort) -> std::io::Result<PortIdentity> {
+		Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "getting the identity of an open serial port is not supported on Windows"))
+	}
+}
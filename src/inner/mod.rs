@@ -4,8 +4,14 @@ mod unix;
 #[cfg(unix)]
 pub use unix::*;
 
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+mod uring;
+
 #[cfg(windows)]
 mod windows;
 
 #[cfg(windows)]
 pub use windows::*;
+
+#[cfg(windows)]
+mod windows_rx;
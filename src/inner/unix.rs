@@ -1,17 +1,53 @@
 use std::io::{IoSliceMut, IoSlice};
-use std::os::fd::AsRawFd;
+use std::mem::ManuallyDrop;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::sync::{Mutex, OnceLock};
 use std::task::{ready, Poll};
 use tokio::io::Interest;
 use tokio::io::unix::AsyncFd;
 
 pub struct SerialPort {
 	io: AsyncFd<serial2::SerialPort>,
+	config_lock: Mutex<()>,
+	#[cfg(all(target_os = "linux", feature = "io-uring"))]
+	leftover: super::uring::Leftover,
+	/// The pending read through io_uring of [`Self::poll_read()`].
+	#[cfg(all(target_os = "linux", feature = "io-uring"))]
+	pending_read: Mutex<Option<PendingRead>>,
+	/// A duplicate of the file descriptor to wait for new data, created on first use.
+	input_watch: OnceLock<AsyncFd<OwnedFd>>,
+}
+
+/// A read through io_uring that is polled by [`SerialPort::poll_read()`].
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+type PendingRead = std::pin::Pin<Box<dyn std::future::Future<Output = Option<std::io::Result<(Vec<u8>, super::uring::LeftoverGuard)>>> + Send>>;
+
+/// The original terminal settings of a serial port, saved to restore them when it is closed.
+pub struct SavedSettings {
+	#[cfg(any(target_os = "linux", target_os = "android"))]
+	termios: libc::termios2,
+	#[cfg(not(any(target_os = "linux", target_os = "android")))]
+	termios: libc::termios,
+}
+
+impl SavedSettings {
+	pub fn save(port: &serial2::SerialPort) -> std::io::Result<Self> {
+		Ok(Self {
+			termios: get_termios(port.as_raw_fd())?,
+		})
+	}
 }
 
 impl SerialPort {
 	pub fn wrap(inner: serial2::SerialPort) -> std::io::Result<Self> {
 		Ok(Self {
 			io: AsyncFd::new(inner)?,
+			config_lock: Mutex::new(()),
+			#[cfg(all(target_os = "linux", feature = "io-uring"))]
+			leftover: Default::default(),
+			#[cfg(all(target_os = "linux", feature = "io-uring"))]
+			pending_read: Mutex::new(None),
+			input_watch: OnceLock::new(),
 		})
 	}
 
@@ -26,14 +62,23 @@ impl SerialPort {
 		function(self.io.get_ref())
 	}
 
-	pub fn with_raw_mut<F, R>(&mut self, function: F) -> R
+	pub fn with_raw_mut<F, R>(&self, function: F) -> R
 	where
 		F: FnOnce(&mut serial2::SerialPort) -> R
 	{
-		function(self.io.get_mut())
+		// Hold the lock for the whole closure, so that a read-modify-write of the configuration is atomic.
+		let _guard = self.config_lock.lock().unwrap_or_else(|e| e.into_inner());
+		let mut serial_port = ManuallyDrop::new(unsafe {
+			serial2::SerialPort::from_raw_fd(self.io.as_raw_fd())
+		});
+		function(&mut serial_port)
 	}
 
 	pub async fn read(&self, buf: &mut [u8]) -> std::io::Result<usize> {
+		#[cfg(all(target_os = "linux", feature = "io-uring"))]
+		if let Some(result) = super::uring::read(self.io.as_raw_fd(), buf, &self.leftover).await {
+			return result;
+		}
 		self.io.async_io(Interest::READABLE, |inner| {
 			unsafe {
 				check_ret(libc::read(inner.as_raw_fd(), buf.as_mut_ptr().cast(), buf.len()))
@@ -41,7 +86,54 @@ impl SerialPort {
 		}).await
 	}
 
+	/// Wait until the serial port is readable, without reading from it.
+	pub async fn readable(&self) -> std::io::Result<()> {
+		// Keep the readiness flag: the next read clears it if there turns out to be no data.
+		let _guard = self.io.readable().await?;
+		Ok(())
+	}
+
+	/// Poll until the serial port is readable, without reading from it.
+	pub fn poll_read_ready(&self, cx: &mut std::task::Context<'_>) -> Poll<std::io::Result<()>> {
+		let _guard = ready!(self.io.poll_read_ready(cx))?;
+		Poll::Ready(Ok(()))
+	}
+
+	/// Poll until at least `min_bytes` are available to read, without reading from the serial port.
+	///
+	/// Unlike [`Self::poll_read_ready()`], this wakes up again every time new data arrives.
+	pub fn poll_input_queue_len(&self, cx: &mut std::task::Context<'_>, min_bytes: usize) -> Poll<std::io::Result<()>> {
+		let watch = self.input_watch()?;
+		loop {
+			if self.input_queue_len()? >= min_bytes {
+				return Poll::Ready(Ok(()));
+			}
+			ready!(watch.poll_read_ready(cx))?.clear_ready();
+		}
+	}
+
+	/// Get the duplicate file descriptor used to wait for new data.
+	///
+	/// It has its own readiness flag, so clearing it does not affect the readiness of the file descriptor used for reading.
+	fn input_watch(&self) -> std::io::Result<&AsyncFd<OwnedFd>> {
+		if let Some(watch) = self.input_watch.get() {
+			return Ok(watch);
+		}
+		let fd = unsafe { check(libc::fcntl(self.io.as_raw_fd(), libc::F_DUPFD_CLOEXEC, 0))? };
+		let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+		let watch = AsyncFd::with_interest(fd, Interest::READABLE)?;
+		// If another task was faster, use its watch and drop ours.
+		Ok(self.input_watch.get_or_init(|| watch))
+	}
+
 	pub async fn read_vectored(&self, bufs: &mut [IoSliceMut<'_>]) -> std::io::Result<usize> {
+		// Read into the first non-empty buffer only, which is allowed for vectored reads.
+		#[cfg(all(target_os = "linux", feature = "io-uring"))]
+		if let Some(buf) = bufs.iter_mut().find(|buf| !buf.is_empty()) {
+			if let Some(result) = super::uring::read(self.io.as_raw_fd(), buf, &self.leftover).await {
+				return result;
+			}
+		}
 		self.io.async_io(Interest::READABLE, |inner| {
 			unsafe {
 				let buf_count = i32::try_from(bufs.len()).unwrap_or(i32::MAX);
@@ -50,11 +142,30 @@ impl SerialPort {
 		}).await
 	}
 
+	pub async fn closed(&self) -> std::io::Result<()> {
+		// Register a duplicate of the file descriptor, so we can clear the read readiness
+		// without affecting the readiness of the file descriptor used for reading.
+		let fd = unsafe { check(libc::fcntl(self.io.as_raw_fd(), libc::F_DUPFD_CLOEXEC, 0))? };
+		let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+		let watch = AsyncFd::with_interest(fd, Interest::READABLE)?;
+		loop {
+			let mut guard = watch.readable().await?;
+			if guard.ready().is_read_closed() {
+				return Ok(());
+			}
+			guard.clear_ready();
+		}
+	}
+
 	pub fn is_read_vectored(&self) -> bool {
 		true
 	}
 
 	pub async fn write(&self, buf: &[u8]) -> std::io::Result<usize> {
+		#[cfg(all(target_os = "linux", feature = "io-uring"))]
+		if let Some(result) = super::uring::write(self.io.as_raw_fd(), buf).await {
+			return result;
+		}
 		self.io.async_io(Interest::WRITABLE, |inner| {
 			unsafe {
 				check_ret(libc::write(inner.as_raw_fd(), buf.as_ptr().cast(), buf.len()))
@@ -80,6 +191,12 @@ impl SerialPort {
 		cx: &mut std::task::Context<'_>,
 		buf: &mut tokio::io::ReadBuf<'_>,
 	) -> Poll<std::io::Result<()>> {
+		#[cfg(all(target_os = "linux", feature = "io-uring"))]
+		if super::uring::is_available() {
+			if let Some(result) = ready!(self.poll_read_uring(cx, buf)) {
+				return Poll::Ready(result);
+			}
+		}
 		loop {
 			let mut guard = ready!(self.io.poll_read_ready(cx)?);
 			let result = guard.try_io(|inner|{
@@ -100,6 +217,36 @@ impl SerialPort {
 		}
 	}
 
+	/// Poll a read through io_uring.
+	///
+	/// The read is kept in `self.pending_read` until it completes, so it must be polled again with a buffer of the same size.
+	/// Data that does not fit in the buffer anyway is kept for the next read.
+	#[cfg(all(target_os = "linux", feature = "io-uring"))]
+	fn poll_read_uring(
+		&mut self,
+		cx: &mut std::task::Context<'_>,
+		buf: &mut tokio::io::ReadBuf<'_>,
+	) -> Poll<Option<std::io::Result<()>>> {
+		let fd = self.io.as_raw_fd();
+		let pending_read = self.pending_read.get_mut().unwrap_or_else(|e| e.into_inner());
+		let future = pending_read.get_or_insert_with(|| {
+			let leftover = self.leftover.clone();
+			let len = buf.remaining();
+			Box::pin(async move { super::uring::read_owned(fd, len, &leftover).await })
+		});
+		let result = ready!(future.as_mut().poll(cx));
+		*pending_read = None;
+		let (data, mut guard) = match result {
+			None => return Poll::Ready(None),
+			Some(Err(e)) => return Poll::Ready(Some(Err(e))),
+			Some(Ok(x)) => x,
+		};
+		let len = data.len().min(buf.remaining());
+		buf.put_slice(&data[..len]);
+		guard.splice(0..0, data[len..].iter().copied());
+		Poll::Ready(Some(Ok(())))
+	}
+
 	pub fn poll_write(
 		&mut self,
 		cx: &mut std::task::Context<'_>,
@@ -139,9 +286,334 @@ impl SerialPort {
 		}
 	}
 
-	pub fn poll_shutdown(&mut self, _cx: &mut std::task::Context<'_>) -> Poll<Result<(), std::io::Error>> {
-		// Serial ports can not be shut down.
-		Poll::Ready(Err(std::io::Error::from_raw_os_error(libc::ENOTSOCK)))
+	pub fn set_output_suspended(&self, suspended: bool) -> std::io::Result<()> {
+		let action = if suspended { libc::TCOOFF } else { libc::TCOON };
+		unsafe {
+			check(libc::tcflow(self.io.as_raw_fd(), action))?;
+		}
+		Ok(())
+	}
+
+	pub fn set_tx_paused(&self, paused: bool) -> std::io::Result<()> {
+		let action = if paused { libc::TCIOFF } else { libc::TCION };
+		unsafe {
+			check(libc::tcflow(self.io.as_raw_fd(), action))?;
+		}
+		Ok(())
+	}
+
+	/// Discard data that was read by an abandoned io_uring read, but not returned to the user yet.
+	///
+	/// If a read is still in progress, its data is kept, since it may be received after the buffers were discarded.
+	pub fn discard_leftover(&self) {
+		#[cfg(all(target_os = "linux", feature = "io-uring"))]
+		if let Ok(mut leftover) = self.leftover.try_lock() {
+			leftover.clear();
+		}
+	}
+
+	pub fn input_queue_len(&self) -> std::io::Result<usize> {
+		let mut len: libc::c_int = 0;
+		unsafe {
+			check(libc::ioctl(self.io.as_raw_fd(), libc::FIONREAD as _, &mut len))?;
+		}
+		Ok(len as usize)
+	}
+
+	#[cfg(any(
+		target_os = "android",
+		target_os = "dragonfly",
+		target_os = "freebsd",
+		target_os = "illumos",
+		target_os = "ios",
+		target_os = "linux",
+		target_os = "macos",
+		target_os = "solaris",
+	))]
+	pub fn output_queue_len(&self) -> std::io::Result<Option<usize>> {
+		let mut len: libc::c_int = 0;
+		unsafe {
+			check(libc::ioctl(self.io.as_raw_fd(), libc::TIOCOUTQ as _, &mut len))?;
+		}
+		Ok(Some(len as usize))
+	}
+
+	#[cfg(not(any(
+		target_os = "android",
+		target_os = "dragonfly",
+		target_os = "freebsd",
+		target_os = "illumos",
+		target_os = "ios",
+		target_os = "linux",
+		target_os = "macos",
+		target_os = "solaris",
+	)))]
+	pub fn output_queue_len(&self) -> std::io::Result<Option<usize>> {
+		Ok(None)
+	}
+
+	pub fn get_xon_xoff_config(&self) -> std::io::Result<crate::XonXoffConfig> {
+		let termios = get_termios(self.io.as_raw_fd())?;
+		Ok(crate::XonXoffConfig {
+			xon_char: termios.c_cc[libc::VSTART],
+			xoff_char: termios.c_cc[libc::VSTOP],
+			input: termios.c_iflag & libc::IXOFF != 0,
+			output: termios.c_iflag & libc::IXON != 0,
+		})
+	}
+
+	pub fn set_xon_xoff_config(&self, config: &crate::XonXoffConfig) -> std::io::Result<()> {
+		let _guard = self.config_lock.lock().unwrap_or_else(|e| e.into_inner());
+		let mut termios = get_termios(self.io.as_raw_fd())?;
+		termios.c_cc[libc::VSTART] = config.xon_char;
+		termios.c_cc[libc::VSTOP] = config.xoff_char;
+		termios.c_iflag &= !(libc::IXON | libc::IXOFF | libc::IXANY);
+		if config.input {
+			termios.c_iflag |= libc::IXOFF;
+		}
+		if config.output {
+			termios.c_iflag |= libc::IXON;
+		}
+		set_termios(self.io.as_raw_fd(), &termios)
+	}
+
+	pub fn flow_control_status(&self) -> std::io::Result<crate::FlowControlStatus> {
+		let flow_control = self.with_raw(|raw| raw.get_configuration()?.get_flow_control())?;
+		let cts_hold = flow_control == serial2::FlowControl::RtsCts && !self.with_raw(|raw| raw.read_cts())?;
+		Ok(crate::FlowControlStatus {
+			input_queue: Some(self.input_queue_len()?),
+			output_queue: self.output_queue_len()?,
+			cts_hold: Some(cts_hold),
+			dsr_hold: None,
+			xoff_hold: None,
+			xoff_sent: None,
+		})
+	}
+
+	#[cfg(target_os = "linux")]
+	pub fn line_counters(&self) -> std::io::Result<crate::LineCounters> {
+		let mut counters = SerialIcounter::default();
+		unsafe {
+			if libc::ioctl(self.io.as_raw_fd(), libc::TIOCGICOUNT as _, &mut counters) != 0 {
+				let error = std::io::Error::last_os_error();
+				return match error.raw_os_error() {
+					Some(libc::ENOTTY | libc::EINVAL) => Ok(Default::default()),
+					_ => Err(error),
+				};
+			}
+		}
+		let counter = |value: libc::c_int| Some(value as u32);
+		Ok(crate::LineCounters {
+			rx: counter(counters.rx),
+			tx: counter(counters.tx),
+			frame_errors: counter(counters.frame),
+			parity_errors: counter(counters.parity),
+			overruns: counter(counters.overrun),
+			buffer_overruns: counter(counters.buf_overrun),
+			breaks: counter(counters.brk),
+			cts_changes: counter(counters.cts),
+			dsr_changes: counter(counters.dsr),
+			ri_changes: counter(counters.rng),
+			cd_changes: counter(counters.dcd),
+		})
+	}
+
+	#[cfg(not(target_os = "linux"))]
+	pub fn line_counters(&self) -> std::io::Result<crate::LineCounters> {
+		Ok(Default::default())
+	}
+
+	/// Get the total number of hardware and buffer overruns, or `None` if the driver does not report them.
+	pub fn overrun_count(&self) -> std::io::Result<Option<u32>> {
+		let counters = self.line_counters()?;
+		match (counters.overruns, counters.buffer_overruns) {
+			(Some(overruns), Some(buffer_overruns)) => Ok(Some(overruns.wrapping_add(buffer_overruns))),
+			_ => Ok(None),
+		}
+	}
+
+	/// Restore saved settings immediately.
+	///
+	/// Pending output is discarded instead of transmitted first,
+	/// because waiting for it could block forever if the output is stopped by flow control.
+	pub fn restore_settings(&self, saved: &SavedSettings) -> std::io::Result<()> {
+		let _guard = self.config_lock.lock().unwrap_or_else(|e| e.into_inner());
+		let fd = self.io.as_raw_fd();
+		unsafe {
+			check(libc::tcflush(fd, libc::TCOFLUSH))?;
+		}
+		set_termios_now(fd, &saved.termios)
+	}
+
+	pub fn set_ignore_carrier(&self, ignore: bool) -> std::io::Result<()> {
+		let _guard = self.config_lock.lock().unwrap_or_else(|e| e.into_inner());
+		let mut termios = get_termios(self.io.as_raw_fd())?;
+		if ignore {
+			termios.c_cflag |= libc::CLOCAL;
+		} else {
+			termios.c_cflag &= !libc::CLOCAL;
+		}
+		set_termios(self.io.as_raw_fd(), &termios)
+	}
+
+	pub fn set_os_buffer_sizes(&self, _rx: usize, _tx: usize) -> std::io::Result<()> {
+		Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "changing the buffer sizes of a serial port is not supported on Unix"))
+	}
+
+	/// Read the state of the RTS and DTR output lines.
+	pub fn read_output_lines(&self) -> std::io::Result<(bool, bool)> {
+		let mut bits: libc::c_int = 0;
+		unsafe {
+			check(libc::ioctl(self.io.as_raw_fd(), libc::TIOCMGET as _, &mut bits))?;
+		}
+		Ok((bits & libc::TIOCM_RTS != 0, bits & libc::TIOCM_DTR != 0))
+	}
+
+	pub fn read_lines(&self) -> std::io::Result<crate::LineState> {
+		let mut bits: libc::c_int = 0;
+		unsafe {
+			check(libc::ioctl(self.io.as_raw_fd(), libc::TIOCMGET as _, &mut bits))?;
+		}
+		Ok(crate::LineState {
+			rts: bits & libc::TIOCM_RTS != 0,
+			dtr: bits & libc::TIOCM_DTR != 0,
+			cts: bits & libc::TIOCM_CTS != 0,
+			dsr: bits & libc::TIOCM_DSR != 0,
+			ri: bits & libc::TIOCM_RI != 0,
+			cd: bits & libc::TIOCM_CD != 0,
+		})
+	}
+
+	pub fn set_lines(&self, rts: bool, dtr: bool) -> std::io::Result<()> {
+		let fd = self.io.as_raw_fd();
+		let mut bits: libc::c_int = 0;
+		unsafe {
+			check(libc::ioctl(fd, libc::TIOCMGET as _, &mut bits))?;
+			bits &= !(libc::TIOCM_RTS | libc::TIOCM_DTR);
+			if rts {
+				bits |= libc::TIOCM_RTS;
+			}
+			if dtr {
+				bits |= libc::TIOCM_DTR;
+			}
+			// TIOCMSET changes all lines at once.
+			check(libc::ioctl(fd, libc::TIOCMSET as _, &bits))?;
+		}
+		Ok(())
+	}
+
+	#[cfg(any(target_os = "linux", target_os = "android"))]
+	pub fn set_loopback(&self, enable: bool) -> std::io::Result<()> {
+		// Not exported by the libc crate for all architectures, but the same everywhere on Linux.
+		const TIOCM_LOOP: libc::c_int = 0x8000;
+		let fd = self.io.as_raw_fd();
+		let request = if enable { libc::TIOCMBIS } else { libc::TIOCMBIC };
+		unsafe {
+			check(libc::ioctl(fd, request as _, &TIOCM_LOOP)).map_err(|e| match e.raw_os_error() {
+				Some(libc::ENOTTY | libc::EINVAL) => loopback_unsupported(),
+				_ => e,
+			})?;
+		}
+
+		// Drivers without loopback support silently ignore the flag, so check that it stuck.
+		let mut bits: libc::c_int = 0;
+		unsafe {
+			check(libc::ioctl(fd, libc::TIOCMGET as _, &mut bits))?;
+		}
+		if (bits & TIOCM_LOOP != 0) != enable {
+			return Err(loopback_unsupported());
+		}
+		Ok(())
+	}
+
+	#[cfg(not(any(target_os = "linux", target_os = "android")))]
+	pub fn set_loopback(&self, _enable: bool) -> std::io::Result<()> {
+		Err(loopback_unsupported())
+	}
+}
+
+/// The `serial_icounter_struct` of Linux, filled by the `TIOCGICOUNT` ioctl.
+#[cfg(target_os = "linux")]
+#[repr(C)]
+#[derive(Default)]
+struct SerialIcounter {
+	cts: libc::c_int,
+	dsr: libc::c_int,
+	rng: libc::c_int,
+	dcd: libc::c_int,
+	rx: libc::c_int,
+	tx: libc::c_int,
+	frame: libc::c_int,
+	overrun: libc::c_int,
+	parity: libc::c_int,
+	brk: libc::c_int,
+	buf_overrun: libc::c_int,
+	reserved: [libc::c_int; 9],
+}
+
+fn loopback_unsupported() -> std::io::Error {
+	std::io::Error::new(std::io::ErrorKind::Unsupported, "loopback mode is not supported by the serial port driver")
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn get_termios(fd: std::os::fd::RawFd) -> std::io::Result<libc::termios2> {
+	unsafe {
+		let mut termios: libc::termios2 = std::mem::zeroed();
+		check(libc::ioctl(fd, libc::TCGETS2 as _, &mut termios))?;
+		Ok(termios)
+	}
+}
+
+/// Apply terminal settings after all pending output has been transmitted, like `serial2` does.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn set_termios(fd: std::os::fd::RawFd, termios: &libc::termios2) -> std::io::Result<()> {
+	unsafe {
+		check(libc::ioctl(fd, libc::TCSETSW2 as _, termios))?;
+		Ok(())
+	}
+}
+
+/// Apply terminal settings immediately, without waiting for pending output to be transmitted.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn set_termios_now(fd: std::os::fd::RawFd, termios: &libc::termios2) -> std::io::Result<()> {
+	unsafe {
+		check(libc::ioctl(fd, libc::TCSETS2 as _, termios))?;
+		Ok(())
+	}
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn get_termios(fd: std::os::fd::RawFd) -> std::io::Result<libc::termios> {
+	unsafe {
+		let mut termios: libc::termios = std::mem::zeroed();
+		check(libc::tcgetattr(fd, &mut termios))?;
+		Ok(termios)
+	}
+}
+
+/// Apply terminal settings after all pending output has been transmitted, like `serial2` does.
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn set_termios(fd: std::os::fd::RawFd, termios: &libc::termios) -> std::io::Result<()> {
+	unsafe {
+		check(libc::tcsetattr(fd, libc::TCSADRAIN, termios))?;
+		Ok(())
+	}
+}
+
+/// Apply terminal settings immediately, without waiting for pending output to be transmitted.
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn set_termios_now(fd: std::os::fd::RawFd, termios: &libc::termios) -> std::io::Result<()> {
+	unsafe {
+		check(libc::tcsetattr(fd, libc::TCSANOW, termios))?;
+		Ok(())
+	}
+}
+
+fn check(ret: i32) -> std::io::Result<i32> {
+	if ret == -1 {
+		Err(std::io::Error::last_os_error())
+	} else {
+		Ok(ret)
 	}
 }
 
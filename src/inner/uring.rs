@@ -0,0 +1,333 @@
+//! Reads and writes through io_uring.
+//!
+//! A single ring is shared by all serial ports in the process.
+//! A dedicated thread waits for completions and hands the results back to the waiting futures.
+//!
+//! The file descriptors are in non-blocking mode, so each read or write is submitted as a poll
+//! linked to the actual read or write. The kernel only starts the read or write once the poll completes.
+//!
+//! The buffers are owned by the driver while an operation is in flight,
+//! so dropping a future before the operation completes is safe.
+//! Data read by an abandoned operation is stored in the `leftover` buffer of the serial port,
+//! and returned by the next read.
+//!
+//! The lock of the `leftover` buffer is held for the whole duration of a read,
+//! and by the driver while an abandoned read is still in flight.
+//! So there is at most one read in flight per serial port, and a new read always sees the data of an abandoned one.
+
+use std::collections::HashMap;
+use std::os::fd::RawFd;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use io_uring::{opcode, squeue, types, IoUring, Probe};
+use tokio::sync::oneshot;
+
+/// The number of entries in the submission queue.
+const RING_ENTRIES: u32 = 256;
+
+/// The largest buffer used for a single operation.
+const MAX_BUFFER_SIZE: usize = 64 * 1024;
+
+/// The user data of entries that do not need to be processed on completion.
+const IGNORED: u64 = u64::MAX;
+
+/// Data read by an abandoned read operation.
+pub type Leftover = Arc<tokio::sync::Mutex<Vec<u8>>>;
+
+/// The lock of the `leftover` buffer, held while a read is in progress.
+pub type LeftoverGuard = tokio::sync::OwnedMutexGuard<Vec<u8>>;
+
+/// The result of an operation: the result code, the buffer and the `leftover` lock of a read.
+type Completion = (i32, Vec<u8>, Option<LeftoverGuard>);
+
+/// The shared io_uring driver.
+struct Driver {
+	ring: IoUring,
+	submission: Mutex<()>,
+	operations: Mutex<Operations>,
+}
+
+#[derive(Default)]
+struct Operations {
+	next_id: u64,
+	pending: HashMap<u64, Operation>,
+}
+
+/// An operation that has been submitted to the ring.
+struct Operation {
+	buffer: Vec<u8>,
+	done: oneshot::Sender<Completion>,
+	leftover: Option<LeftoverGuard>,
+
+	/// The error reported by the linked poll, which cancels the read or write.
+	poll_error: Option<i32>,
+}
+
+/// Get the shared driver, or `None` if io_uring is not available.
+fn driver() -> Option<&'static Driver> {
+	static DRIVER: OnceLock<Option<&'static Driver>> = OnceLock::new();
+	*DRIVER.get_or_init(|| {
+		let driver: &'static Driver = Box::leak(Box::new(Driver::new().ok()?));
+		std::thread::Builder::new()
+			.name("serial2-io-uring".into())
+			.spawn(|| driver.run())
+			.ok()?;
+		Some(driver)
+	})
+}
+
+/// Check if io_uring is available.
+pub fn is_available() -> bool {
+	driver().is_some()
+}
+
+/// Read from a file descriptor through io_uring.
+///
+/// Returns `None` if io_uring is not available.
+pub async fn read(fd: RawFd, buf: &mut [u8], leftover: &Leftover) -> Option<std::io::Result<usize>> {
+	let (data, _guard) = match read_owned(fd, buf.len(), leftover).await? {
+		Ok(x) => x,
+		Err(e) => return Some(Err(e)),
+	};
+	buf[..data.len()].copy_from_slice(&data);
+	Some(Ok(data.len()))
+}
+
+/// Read up to `len` bytes from a file descriptor through io_uring into a new buffer.
+///
+/// The returned guard keeps other reads waiting, so data that is not consumed can be put back in the `leftover` buffer.
+///
+/// Returns `None` if io_uring is not available.
+pub async fn read_owned(fd: RawFd, len: usize, leftover: &Leftover) -> Option<std::io::Result<(Vec<u8>, LeftoverGuard)>> {
+	let driver = driver()?;
+	let mut guard = leftover.clone().lock_owned().await;
+	if !guard.is_empty() || len == 0 {
+		let len = len.min(guard.len());
+		let data = guard.drain(..len).collect();
+		return Some(Ok((data, guard)));
+	}
+	let len = len.min(MAX_BUFFER_SIZE);
+	loop {
+		let (result, mut data, returned) = match driver.submit(fd, vec![0; len], Some(guard)).await {
+			Ok(x) => x,
+			Err(e) => return Some(Err(e)),
+		};
+		let Some(returned) = returned else {
+			unreachable!("the driver returns the leftover lock with the result of a read");
+		};
+		guard = returned;
+		match check_result(result) {
+			Ok(read) => {
+				data.truncate(read);
+				return Some(Ok((data, guard)));
+			},
+			Err(e) if is_retryable(&e) => continue,
+			Err(e) => return Some(Err(e)),
+		}
+	}
+}
+
+/// Write to a file descriptor through io_uring.
+///
+/// Returns `None` if io_uring is not available.
+///
+/// If the returned future is dropped before it completes, the data may still be written.
+pub async fn write(fd: RawFd, buf: &[u8]) -> Option<std::io::Result<usize>> {
+	let driver = driver()?;
+	if buf.is_empty() {
+		return Some(Ok(0));
+	}
+	let data = buf[..buf.len().min(MAX_BUFFER_SIZE)].to_vec();
+	loop {
+		let (result, _, _) = match driver.submit(fd, data.clone(), None).await {
+			Ok(x) => x,
+			Err(e) => return Some(Err(e)),
+		};
+		match check_result(result) {
+			Err(e) if is_retryable(&e) => continue,
+			result => return Some(result),
+		}
+	}
+}
+
+impl Driver {
+	fn new() -> std::io::Result<Self> {
+		let ring = IoUring::new(RING_ENTRIES)?;
+		let mut probe = Probe::new();
+		ring.submitter().register_probe(&mut probe)?;
+		let required = [opcode::PollAdd::CODE, opcode::Read::CODE, opcode::Write::CODE, opcode::AsyncCancel::CODE];
+		if !required.iter().all(|&code| probe.is_supported(code)) {
+			return Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "io_uring does not support the required operations"));
+		}
+		Ok(Self {
+			ring,
+			submission: Mutex::new(()),
+			operations: Mutex::new(Operations::default()),
+		})
+	}
+
+	/// Submit a read (if `leftover` is set) or write operation and wait for the result.
+	async fn submit(&self, fd: RawFd, mut buffer: Vec<u8>, leftover: Option<LeftoverGuard>) -> std::io::Result<Completion> {
+		let is_read = leftover.is_some();
+		let len = u32::try_from(buffer.len()).unwrap_or(u32::MAX);
+		let (poll, operation) = if is_read {
+			let poll = opcode::PollAdd::new(types::Fd(fd), libc::POLLIN as u32).build();
+			let read = opcode::Read::new(types::Fd(fd), buffer.as_mut_ptr(), len).build();
+			(poll, read)
+		} else {
+			let poll = opcode::PollAdd::new(types::Fd(fd), libc::POLLOUT as u32).build();
+			let write = opcode::Write::new(types::Fd(fd), buffer.as_ptr(), len).build();
+			(poll, write)
+		};
+
+		let (done, result) = oneshot::channel();
+		let id = {
+			let mut operations = self.operations.lock().unwrap_or_else(|e| e.into_inner());
+			let id = operations.next_id;
+			operations.next_id += 1;
+			operations.pending.insert(id, Operation { buffer, done, leftover, poll_error: None });
+			id
+		};
+
+		let entries = [
+			poll.user_data(poll_user_data(id)).flags(squeue::Flags::IO_LINK),
+			operation.user_data(operation_user_data(id)),
+		];
+		if let Err(e) = self.push(&entries) {
+			self.operations.lock().unwrap_or_else(|e| e.into_inner()).pending.remove(&id);
+			return Err(e);
+		}
+
+		let mut pending = PendingOperation { driver: self, id, result, finished: false };
+		let completion = (&mut pending.result).await;
+		pending.finished = true;
+		completion.map_err(|_| std::io::Error::new(std::io::ErrorKind::BrokenPipe, "io_uring driver stopped"))
+	}
+
+	/// Push entries to the submission queue and submit them to the kernel.
+	fn push(&self, entries: &[squeue::Entry]) -> std::io::Result<()> {
+		let _guard = self.submission.lock().unwrap_or_else(|e| e.into_inner());
+		loop {
+			// SAFETY: Access to the submission queue is serialized by `self.submission`.
+			// The buffers of the entries are kept alive in `self.operations` until they complete.
+			let pushed = unsafe {
+				let mut queue = self.ring.submission_shared();
+				let pushed = queue.push_multiple(entries).is_ok();
+				queue.sync();
+				pushed
+			};
+			match self.ring.submitter().submit() {
+				Ok(_) => (),
+				Err(e) if e.raw_os_error() == Some(libc::EBUSY) || e.raw_os_error() == Some(libc::EAGAIN) => (),
+				Err(e) => return Err(e),
+			}
+			if pushed {
+				return Ok(());
+			}
+			// The submission queue was full, so give the kernel some time to consume it.
+			std::thread::yield_now();
+		}
+	}
+
+	/// Cancel an operation whose future was dropped.
+	fn cancel(&self, id: u64) {
+		// Cancelling the poll also cancels the linked read or write, if it has not started yet.
+		let cancel = opcode::AsyncCancel::new(poll_user_data(id)).build().user_data(IGNORED);
+		self.push(&[cancel]).ok();
+	}
+
+	/// Wait for completions and deliver the results.
+	fn run(&self) {
+		loop {
+			match self.ring.submitter().submit_and_wait(1) {
+				Ok(_) => (),
+				Err(e) if e.raw_os_error() == Some(libc::EINTR) || e.raw_os_error() == Some(libc::EBUSY) => (),
+				Err(_) => return,
+			}
+
+			// SAFETY: Only this thread accesses the completion queue.
+			let completions: Vec<_> = unsafe { self.ring.completion_shared() }
+				.map(|entry| (entry.user_data(), entry.result()))
+				.collect();
+
+			let mut operations = self.operations.lock().unwrap_or_else(|e| e.into_inner());
+			for (user_data, result) in completions {
+				if user_data == IGNORED {
+					continue;
+				}
+				if user_data % 2 == 0 {
+					if let Some(operation) = operations.pending.get_mut(&(user_data / 2)) {
+						if result < 0 && result != -libc::ECANCELED {
+							operation.poll_error = Some(result);
+						}
+					}
+					continue;
+				}
+				let Some(operation) = operations.pending.remove(&(user_data / 2)) else {
+					continue;
+				};
+				let result = match operation.poll_error {
+					Some(error) if result == -libc::ECANCELED => error,
+					_ => result,
+				};
+				if let Err(completion) = operation.done.send((result, operation.buffer, operation.leftover)) {
+					// The future was dropped, so keep the data for the next read.
+					store_leftover(completion);
+				}
+			}
+		}
+	}
+}
+
+/// An operation that is waited for, which is cancelled when the future is dropped before completion.
+struct PendingOperation<'a> {
+	driver: &'a Driver,
+	id: u64,
+	result: oneshot::Receiver<Completion>,
+	finished: bool,
+}
+
+impl Drop for PendingOperation<'_> {
+	fn drop(&mut self) {
+		if self.finished {
+			return;
+		}
+		// Closing the channel makes the driver store the data of a read that completes from now on.
+		// A read that already completed is stored here, so that no data is lost.
+		self.result.close();
+		match self.result.try_recv() {
+			Ok(completion) => store_leftover(completion),
+			Err(_) => self.driver.cancel(self.id),
+		}
+	}
+}
+
+/// Store the data of an abandoned read in the `leftover` buffer, and release its lock.
+fn store_leftover((result, buffer, leftover): Completion) {
+	if let (Some(mut leftover), Ok(read)) = (leftover, usize::try_from(result)) {
+		leftover.extend_from_slice(&buffer[..read]);
+	}
+}
+
+fn poll_user_data(id: u64) -> u64 {
+	id * 2
+}
+
+fn operation_user_data(id: u64) -> u64 {
+	id * 2 + 1
+}
+
+/// Check if an operation should be submitted again after it failed with the given error.
+///
+/// An operation that is interrupted by a signal fails with `EINTR`, just like a regular read or write.
+fn is_retryable(error: &std::io::Error) -> bool {
+	matches!(error.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::Interrupted)
+}
+
+fn check_result(result: i32) -> std::io::Result<usize> {
+	if result < 0 {
+		Err(std::io::Error::from_raw_os_error(-result))
+	} else {
+		Ok(result as usize)
+	}
+}
@@ -1,12 +1,67 @@
+use std::future::Future;
 use std::io::{IoSliceMut, IoSlice};
 use std::mem::ManuallyDrop;
 use std::os::windows::io::{AsRawHandle, FromRawHandle};
 use std::pin::Pin;
-use std::task::Poll;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::task::{ready, Poll};
 use tokio::net::windows::named_pipe::NamedPipeClient;
+use winapi::shared::minwindef::BOOL;
+use winapi::um::{commapi, winbase};
+
+use super::windows_rx::RxWait;
+
+/// The input buffer of the driver overflowed (not defined by `winapi`).
+const CE_RXOVER: u32 = 0x0001;
+
+/// The UART overwrote a character before it was read (not defined by `winapi`).
+const CE_OVERRUN: u32 = 0x0002;
+
+/// The interval for polling the status of the device while waiting for it to be removed.
+///
+/// `WaitCommEvent()` can not be used for this, because the event mask is shared with the wait for received characters.
+const CLOSED_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(200);
 
 pub struct SerialPort {
 	io: NamedPipeClient,
+	config_lock: Mutex<()>,
+	rx_wait: Option<RxWait>,
+	comm_errors: Arc<CommErrors>,
+}
+
+/// The error flags reported by `ClearCommError()`.
+///
+/// Every call to `ClearCommError()` clears the flags in the driver,
+/// so all calls must record the flags here to avoid losing them.
+/// The flags belong to the serial port, so they are shared with cloned handles.
+#[derive(Default)]
+pub struct CommErrors {
+	/// The error flags that have not been consumed yet.
+	flags: AtomicU32,
+
+	/// The number of times the overrun flags were consumed.
+	overruns: AtomicU32,
+}
+
+impl CommErrors {
+	/// Record the error flags returned by `ClearCommError()`.
+	pub fn record(&self, flags: u32) {
+		self.flags.fetch_or(flags, Ordering::Relaxed);
+	}
+}
+
+/// The original settings of a serial port, saved to restore them when it is closed.
+pub struct SavedSettings {
+	settings: serial2::Settings,
+}
+
+impl SavedSettings {
+	pub fn save(port: &serial2::SerialPort) -> std::io::Result<Self> {
+		Ok(Self {
+			settings: port.get_configuration()?,
+		})
+	}
 }
 
 impl SerialPort {
@@ -23,11 +78,29 @@ impl SerialPort {
 
 		Ok(Self {
 			io,
+			config_lock: Mutex::new(()),
+			rx_wait: None,
+			comm_errors: Arc::default(),
 		})
 	}
 
 	pub fn try_clone(&self) -> std::io::Result<Self> {
-		Self::wrap(self.with_raw(|raw| raw.try_clone())?)
+		// The timeouts belong to the serial port, not the handle, so keep the timeouts set by the user.
+		let mut timeouts = self.get_comm_timeouts()?;
+		let mut clone = Self::wrap(self.with_raw(|raw| raw.try_clone())?)?;
+		clone.comm_errors = self.comm_errors.clone();
+		unsafe {
+			check_bool(commapi::SetCommTimeouts(self.io.as_raw_handle(), &mut timeouts))?;
+		}
+		Ok(clone)
+	}
+
+	fn get_comm_timeouts(&self) -> std::io::Result<winbase::COMMTIMEOUTS> {
+		unsafe {
+			let mut timeouts: winbase::COMMTIMEOUTS = std::mem::zeroed();
+			check_bool(commapi::GetCommTimeouts(self.io.as_raw_handle(), &mut timeouts))?;
+			Ok(timeouts)
+		}
 	}
 
 	pub fn with_raw<F, R>(&self, function: F) -> R
@@ -40,10 +113,12 @@ impl SerialPort {
 		function(&serial_port)
 	}
 
-	pub fn with_raw_mut<F, R>(&mut self, function: F) -> R
+	pub fn with_raw_mut<F, R>(&self, function: F) -> R
 	where
 		F: FnOnce(&mut serial2::SerialPort) -> R
 	{
+		// Hold the lock for the whole closure, so that a read-modify-write of the configuration is atomic.
+		let _guard = self.config_lock.lock().unwrap_or_else(|e| e.into_inner());
 		let mut serial_port = ManuallyDrop::new(unsafe {
 			serial2::SerialPort::from_raw_handle(self.io.as_raw_handle())
 		});
@@ -54,6 +129,15 @@ impl SerialPort {
 		loop {
 			self.io.readable().await?;
 			match self.io.try_read(buf) {
+				Ok(0) if !buf.is_empty() => {
+					// Some drivers complete reads without data instead of waiting for it.
+					// Wait until a character is received rather than retrying the read right away.
+					match RxWait::start(self.io.as_raw_handle(), &self.comm_errors) {
+						Ok(Some(wait)) => wait.await,
+						Ok(None) => (),
+						Err(_) => return Ok(0),
+					}
+				},
 				Ok(n) => return Ok(n),
 				Err(e) => {
 					if e.kind() == std::io::ErrorKind::WouldBlock {
@@ -111,7 +195,23 @@ impl SerialPort {
 		cx: &mut std::task::Context<'_>,
 		buf: &mut tokio::io::ReadBuf<'_>,
 	) -> Poll<std::io::Result<()>> {
-		tokio::io::AsyncRead::poll_read(Pin::new(&mut self.io), cx, buf)
+		loop {
+			if let Some(wait) = &mut self.rx_wait {
+				ready!(Pin::new(wait).poll(cx));
+				self.rx_wait = None;
+			}
+			let filled = buf.filled().len();
+			ready!(tokio::io::AsyncRead::poll_read(Pin::new(&mut self.io), cx, buf))?;
+			if buf.filled().len() > filled || buf.remaining() == 0 {
+				return Poll::Ready(Ok(()));
+			}
+			// See `read()`: wait for a character instead of retrying the read right away.
+			match RxWait::start(self.io.as_raw_handle(), &self.comm_errors) {
+				Ok(Some(wait)) => self.rx_wait = Some(wait),
+				Ok(None) => (),
+				Err(_) => return Poll::Ready(Ok(())),
+			}
+		}
 	}
 
 	pub fn poll_write(
@@ -134,10 +234,226 @@ impl SerialPort {
 		}
 	}
 
-	pub fn poll_shutdown(&mut self, _cx: &mut std::task::Context<'_>) -> Poll<Result<(), std::io::Error>> {
-		// Serial ports can not be shut down.
-		let error = winapi::shared::winerror::WSAENOTSOCK;
-		Poll::Ready(Err(std::io::Error::from_raw_os_error(error as i32)))
+	pub fn set_output_suspended(&self, suspended: bool) -> std::io::Result<()> {
+		let function = if suspended { winbase::SETXOFF } else { winbase::SETXON };
+		unsafe {
+			check_bool(commapi::EscapeCommFunction(self.io.as_raw_handle(), function))
+		}
+	}
+
+	pub fn set_tx_paused(&self, paused: bool) -> std::io::Result<()> {
+		let dcb = self.get_dcb()?;
+		let control_char = if paused { dcb.XoffChar } else { dcb.XonChar };
+		unsafe {
+			check_bool(commapi::TransmitCommChar(self.io.as_raw_handle(), control_char))
+		}
+	}
+
+	pub fn get_xon_xoff_config(&self) -> std::io::Result<crate::XonXoffConfig> {
+		let dcb = self.get_dcb()?;
+		Ok(crate::XonXoffConfig {
+			xon_char: dcb.XonChar as u8,
+			xoff_char: dcb.XoffChar as u8,
+			input: dcb.fInX() != 0,
+			output: dcb.fOutX() != 0,
+		})
+	}
+
+	pub fn set_xon_xoff_config(&self, config: &crate::XonXoffConfig) -> std::io::Result<()> {
+		self.modify_dcb(|dcb| {
+			dcb.XonChar = config.xon_char as i8;
+			dcb.XoffChar = config.xoff_char as i8;
+			dcb.set_fInX(config.input.into());
+			dcb.set_fOutX(config.output.into());
+		})
+	}
+
+	/// Restore saved settings immediately.
+	///
+	/// `SetCommState()` does not wait for pending output to be transmitted, so this never blocks.
+	pub fn restore_settings(&self, saved: &SavedSettings) -> std::io::Result<()> {
+		self.with_raw_mut(|raw| raw.set_configuration(&saved.settings))
+	}
+
+	pub async fn closed(&self) -> std::io::Result<()> {
+		// The driver fails all requests once the device has been removed.
+		while self.comm_status().is_ok() {
+			tokio::time::sleep(CLOSED_POLL_INTERVAL).await;
+		}
+		Ok(())
+	}
+
+	pub fn flow_control_status(&self) -> std::io::Result<crate::FlowControlStatus> {
+		let (_errors, status) = self.comm_status()?;
+		Ok(crate::FlowControlStatus {
+			input_queue: Some(status.cbInQue as usize),
+			output_queue: Some(status.cbOutQue as usize),
+			cts_hold: Some(status.fCtsHold() != 0),
+			dsr_hold: Some(status.fDsrHold() != 0),
+			xoff_hold: Some(status.fXoffHold() != 0),
+			xoff_sent: Some(status.fXoffSent() != 0),
+		})
+	}
+
+	pub fn line_counters(&self) -> std::io::Result<crate::LineCounters> {
+		// The serial driver interface on Windows only reports error flags, not counters.
+		Ok(Default::default())
+	}
+
+	/// Get the number of times the overrun error flags were seen, or `None` if the driver does not report them.
+	///
+	/// Windows only reports that an overrun happened since the error flags were last cleared, so multiple overruns count as one.
+	pub fn overrun_count(&self) -> std::io::Result<Option<u32>> {
+		// Record the current flags, then consume the overrun flags recorded by all calls to `ClearCommError()`.
+		self.comm_status()?;
+		let flags = self.comm_errors.flags.fetch_and(!(CE_OVERRUN | CE_RXOVER), Ordering::Relaxed);
+		if flags & (CE_OVERRUN | CE_RXOVER) != 0 {
+			self.comm_errors.overruns.fetch_add(1, Ordering::Relaxed);
+		}
+		Ok(Some(self.comm_errors.overruns.load(Ordering::Relaxed)))
+	}
+
+	pub fn set_ignore_carrier(&self, _ignore: bool) -> std::io::Result<()> {
+		// Windows never hangs up the serial port when the carrier is lost.
+		Ok(())
+	}
+
+	pub fn set_os_buffer_sizes(&self, rx: usize, tx: usize) -> std::io::Result<()> {
+		let to_u32 = |size: usize| u32::try_from(size)
+			.map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidInput, "buffer size is too large"));
+		unsafe {
+			check_bool(commapi::SetupComm(self.io.as_raw_handle(), to_u32(rx)?, to_u32(tx)?))
+		}
+	}
+
+	/// Read the state of the RTS and DTR output lines.
+	pub fn read_output_lines(&self) -> std::io::Result<(bool, bool)> {
+		use winapi::shared::minwindef::{DWORD, FALSE, TRUE};
+		use winapi::shared::winerror::ERROR_IO_PENDING;
+		use winapi::um::minwinbase::OVERLAPPED;
+		use winapi::um::winnt::HANDLE;
+		use winapi::um::{handleapi, ioapiset, synchapi};
+
+		// From `ntddser.h`, which is not covered by the winapi crate.
+		const IOCTL_SERIAL_GET_DTRRTS: DWORD = 0x001B_0078;
+		const SERIAL_DTR_STATE: DWORD = 0x01;
+		const SERIAL_RTS_STATE: DWORD = 0x02;
+
+		let handle = self.io.as_raw_handle();
+		unsafe {
+			let event = synchapi::CreateEventW(std::ptr::null_mut(), TRUE, FALSE, std::ptr::null());
+			if event.is_null() {
+				return Err(std::io::Error::last_os_error());
+			}
+			let mut overlapped: OVERLAPPED = std::mem::zeroed();
+			// Setting the low bit prevents the completion from being queued to the I/O completion port of Tokio.
+			overlapped.hEvent = (event as usize | 1) as HANDLE;
+			let mut state: DWORD = 0;
+			let mut returned: DWORD = 0;
+			let mut result = check_bool(ioapiset::DeviceIoControl(
+				handle,
+				IOCTL_SERIAL_GET_DTRRTS,
+				std::ptr::null_mut(),
+				0,
+				(&mut state as *mut DWORD).cast(),
+				std::mem::size_of::<DWORD>() as DWORD,
+				&mut returned,
+				&mut overlapped,
+			));
+			if let Err(e) = &result {
+				if e.raw_os_error() == Some(ERROR_IO_PENDING as i32) {
+					result = check_bool(ioapiset::GetOverlappedResult(handle, &mut overlapped, &mut returned, TRUE));
+				}
+			}
+			handleapi::CloseHandle(event);
+			result?;
+			Ok((state & SERIAL_RTS_STATE != 0, state & SERIAL_DTR_STATE != 0))
+		}
+	}
+
+	pub fn read_lines(&self) -> std::io::Result<crate::LineState> {
+		// Windows has no single request for all lines, so read the status lines right after the output lines.
+		let (rts, dtr) = self.read_output_lines()?;
+		let mut bits = 0;
+		unsafe {
+			check_bool(commapi::GetCommModemStatus(self.io.as_raw_handle(), &mut bits))?;
+		}
+		Ok(crate::LineState {
+			rts,
+			dtr,
+			cts: bits & winbase::MS_CTS_ON != 0,
+			dsr: bits & winbase::MS_DSR_ON != 0,
+			ri: bits & winbase::MS_RING_ON != 0,
+			cd: bits & winbase::MS_RLSD_ON != 0,
+		})
+	}
+
+	pub fn set_lines(&self, rts: bool, dtr: bool) -> std::io::Result<()> {
+		// Windows has no single request for both lines, so change them with two consecutive calls.
+		let rts = if rts { winbase::SETRTS } else { winbase::CLRRTS };
+		let dtr = if dtr { winbase::SETDTR } else { winbase::CLRDTR };
+		unsafe {
+			check_bool(commapi::EscapeCommFunction(self.io.as_raw_handle(), rts))?;
+			check_bool(commapi::EscapeCommFunction(self.io.as_raw_handle(), dtr))?;
+		}
+		Ok(())
+	}
+
+	pub fn set_loopback(&self, _enable: bool) -> std::io::Result<()> {
+		// The serial driver interface on Windows has no standard request for loopback mode.
+		Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "loopback mode is not supported on Windows"))
+	}
+
+	/// Get the communication status and clear the error flags of the serial port.
+	///
+	/// The error flags are recorded in `self.comm_errors`, so they are not lost.
+	fn comm_status(&self) -> std::io::Result<(u32, winbase::COMSTAT)> {
+		comm_status(self.io.as_raw_handle(), &self.comm_errors)
+	}
+
+	pub fn get_rts_toggle(&self) -> std::io::Result<bool> {
+		let dcb = self.get_dcb()?;
+		Ok(dcb.fRtsControl() == winbase::RTS_CONTROL_TOGGLE)
+	}
+
+	#[cfg(feature = "rs4xx")]
+	pub fn set_rts_toggle(&self, enable: bool) -> std::io::Result<()> {
+		self.modify_dcb(|dcb| {
+			if enable {
+				dcb.set_fRtsControl(winbase::RTS_CONTROL_TOGGLE);
+			} else {
+				dcb.set_fRtsControl(winbase::RTS_CONTROL_DISABLE);
+			}
+		})
+	}
+
+	fn get_dcb(&self) -> std::io::Result<winbase::DCB> {
+		unsafe {
+			let mut dcb: winbase::DCB = std::mem::zeroed();
+			dcb.DCBlength = std::mem::size_of::<winbase::DCB>() as u32;
+			check_bool(commapi::GetCommState(self.io.as_raw_handle(), &mut dcb))?;
+			Ok(dcb)
+		}
+	}
+
+	/// Modify the device control block of the serial port.
+	///
+	/// The configuration lock is held for the whole read-modify-write, so we don't race with other configuration changes.
+	fn modify_dcb(&self, modify: impl FnOnce(&mut winbase::DCB)) -> std::io::Result<()> {
+		let _guard = self.config_lock.lock().unwrap_or_else(|e| e.into_inner());
+		let mut dcb = self.get_dcb()?;
+		modify(&mut dcb);
+		unsafe {
+			check_bool(commapi::SetCommState(self.io.as_raw_handle(), &mut dcb))
+		}
+	}
+}
+
+pub(super) fn check_bool(ret: BOOL) -> std::io::Result<()> {
+	if ret == 0 {
+		Err(std::io::Error::last_os_error())
+	} else {
+		Ok(())
 	}
 }
 
@@ -149,3 +465,16 @@ impl std::fmt::Debug for SerialPort {
 		})
 	}
 }
+
+/// Get the communication status and clear the error flags of a serial port handle.
+///
+/// The error flags are recorded in `errors` before they are returned.
+pub fn comm_status(handle: std::os::windows::io::RawHandle, errors: &CommErrors) -> std::io::Result<(u32, winbase::COMSTAT)> {
+	unsafe {
+		let mut flags = 0;
+		let mut status: winbase::COMSTAT = std::mem::zeroed();
+		check_bool(commapi::ClearCommError(handle, &mut flags, &mut status))?;
+		errors.record(flags);
+		Ok((flags, status))
+	}
+}
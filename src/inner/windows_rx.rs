@@ -0,0 +1,136 @@
+//! Waiting for received characters with `WaitCommEvent()`.
+//!
+//! The handle of the serial port is associated with the I/O completion port of the Tokio runtime.
+//! To keep the completion of `WaitCommEvent()` away from the completion port,
+//! the low bit of the event handle in the `OVERLAPPED` struct is set.
+//! The event is waited for on the system thread pool with `RegisterWaitForSingleObject()`.
+
+use std::future::Future;
+use std::os::windows::io::RawHandle;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::task::Poll;
+
+use tokio::sync::oneshot;
+use winapi::shared::minwindef::{DWORD, FALSE, TRUE};
+use winapi::shared::winerror::ERROR_IO_PENDING;
+use winapi::um::minwinbase::OVERLAPPED;
+use winapi::shared::ntdef::{BOOLEAN, HANDLE, PVOID};
+use winapi::um::{commapi, handleapi, ioapiset, synchapi, threadpoollegacyapiset, winbase, winnt};
+
+use super::windows::{check_bool, comm_status, CommErrors};
+
+type Context = Mutex<Option<oneshot::Sender<()>>>;
+
+/// A character was received and placed in the input buffer (not defined by `winapi`).
+const EV_RXCHAR: DWORD = 0x0001;
+
+/// A pending wait for a received character.
+pub struct RxWait {
+	handle: HANDLE,
+	event: HANDLE,
+	overlapped: Box<OVERLAPPED>,
+	events: Box<DWORD>,
+	armed: bool,
+	registration: HANDLE,
+	context: *const Context,
+	done: oneshot::Receiver<()>,
+}
+
+// The raw handles and pointers are only used by the wait itself and when it is dropped.
+unsafe impl Send for RxWait {}
+unsafe impl Sync for RxWait {}
+
+impl RxWait {
+	/// Start waiting until a character is received.
+	///
+	/// Returns `None` if data is already waiting in the input buffer of the driver.
+	/// Returns an error if the driver does not support waiting for received characters.
+	pub fn start(handle: RawHandle, errors: &CommErrors) -> std::io::Result<Option<Self>> {
+		let (sender, done) = oneshot::channel();
+		unsafe {
+			let event = synchapi::CreateEventW(std::ptr::null_mut(), TRUE, FALSE, std::ptr::null());
+			if event.is_null() {
+				return Err(std::io::Error::last_os_error());
+			}
+
+			let mut wait = Self {
+				handle,
+				event,
+				overlapped: Box::new(std::mem::zeroed()),
+				events: Box::new(0),
+				armed: false,
+				registration: std::ptr::null_mut(),
+				context: std::ptr::null(),
+				done,
+			};
+
+			// Setting the low bit prevents the completion from being queued to the I/O completion port.
+			wait.overlapped.hEvent = (event as usize | 1) as HANDLE;
+			check_bool(commapi::SetCommMask(handle, EV_RXCHAR))?;
+			if commapi::WaitCommEvent(handle, &mut *wait.events, &mut *wait.overlapped) == 0 {
+				let error = std::io::Error::last_os_error();
+				if error.raw_os_error() != Some(ERROR_IO_PENDING as i32) {
+					return Err(error);
+				}
+			}
+			wait.armed = true;
+
+			// A character may have been received before the event mask was set.
+			if comm_status(handle, errors)?.1.cbInQue > 0 {
+				return Ok(None);
+			}
+
+			let context = Box::into_raw(Box::new(Mutex::new(Some(sender))));
+			wait.context = context;
+			check_bool(winbase::RegisterWaitForSingleObject(
+				&mut wait.registration,
+				event,
+				Some(wake),
+				context as PVOID,
+				winbase::INFINITE,
+				winnt::WT_EXECUTEONLYONCE,
+			))?;
+			Ok(Some(wait))
+		}
+	}
+}
+
+impl Future for RxWait {
+	type Output = ();
+
+	fn poll(mut self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<()> {
+		// The sender is only dropped without sending if the wait is dropped, so either way we're done.
+		Pin::new(&mut self.done).poll(cx).map(|_| ())
+	}
+}
+
+impl Drop for RxWait {
+	fn drop(&mut self) {
+		unsafe {
+			if !self.registration.is_null() {
+				// Wait for a running callback to finish before freeing the context.
+				threadpoollegacyapiset::UnregisterWaitEx(self.registration, handleapi::INVALID_HANDLE_VALUE);
+			}
+			if !self.context.is_null() {
+				drop(Box::from_raw(self.context as *mut Context));
+			}
+			if self.armed {
+				// Changing the event mask completes a pending `WaitCommEvent()`.
+				// We must wait for that before the `OVERLAPPED` struct can be freed.
+				commapi::SetCommMask(self.handle, 0);
+				let mut transferred = 0;
+				ioapiset::GetOverlappedResult(self.handle, &mut *self.overlapped, &mut transferred, TRUE);
+			}
+			handleapi::CloseHandle(self.event);
+		}
+	}
+}
+
+/// Called on the system thread pool when the event is signaled.
+unsafe extern "system" fn wake(context: PVOID, _timed_out: BOOLEAN) {
+	let context = &*(context as *const Context);
+	if let Some(sender) = context.lock().unwrap_or_else(|e| e.into_inner()).take() {
+		sender.send(()).ok();
+	}
+}
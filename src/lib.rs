@@ -54,12 +54,185 @@
 #![warn(private_interfaces)]
 #![warn(private_bounds)]
 
+use std::future::Future;
 use std::io::{IoSliceMut, IoSlice};
 use std::path::{Path, PathBuf};
 use std::pin::Pin;
-use std::task::Poll;
-
+use std::task::{ready, Poll};
+
+mod android;
+mod autobaud;
+mod broadcast;
+mod cancel;
+mod carrier;
+#[cfg(any(feature = "doc", unix))]
+mod coalesce;
+mod comm_timeouts;
+mod copy_compat;
+mod deadline;
+mod describe;
+mod diagnose;
+mod echo;
+mod error;
+mod fault;
+mod flow_control;
+mod frame_assembler;
+mod from_raw;
+mod identity;
 mod inner;
+mod line_control;
+mod line_counters;
+mod line_state;
+mod loopback;
+mod open_options;
+mod overrun;
+mod pacing;
+mod poll_scheduler;
+mod port_info;
+mod port_set;
+mod port_state;
+mod preset;
+mod pty;
+mod reader_writer;
+mod request;
+mod rng;
+mod shutdown;
+mod simulated_port;
+mod socket_port;
+mod stats;
+mod subscribe;
+mod task;
+#[cfg(any(feature = "doc", feature = "rfc2217"))]
+mod tcp;
+#[cfg(all(test, unix, feature = "unix"))]
+pub(crate) mod test_util;
+mod timestamps;
+mod transfer;
+mod tx_queue;
+mod uart_fifo;
+#[cfg(unix)]
+mod uucp_lock;
+#[cfg(any(feature = "doc", windows))]
+mod virtual_pair;
+mod write_frame;
+mod write_ticket;
+mod zero_read;
+
+pub mod checksum;
+
+#[cfg(any(feature = "doc", feature = "at"))]
+#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "at")))]
+pub mod at;
+
+#[cfg(any(feature = "doc", feature = "bench"))]
+#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "bench")))]
+pub mod bench;
+
+#[cfg(any(feature = "doc", feature = "bridge"))]
+#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "bridge")))]
+pub mod bridge;
+
+#[cfg(any(feature = "doc", feature = "cmux"))]
+#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "cmux")))]
+#[cfg_attr(not(feature = "doc"), allow(rustdoc::broken_intra_doc_links))]
+pub mod cmux;
+
+#[cfg(any(feature = "doc", feature = "codec"))]
+#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "codec")))]
+pub mod codec;
+
+#[cfg(any(feature = "doc", feature = "console"))]
+#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "console")))]
+pub mod console;
+
+#[cfg(any(feature = "doc", feature = "dynamixel"))]
+#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "dynamixel")))]
+pub mod dynamixel;
+
+#[cfg(any(feature = "doc", feature = "gpio"))]
+#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "gpio")))]
+pub mod gpio;
+
+#[cfg(any(feature = "doc", feature = "half-duplex"))]
+#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "half-duplex")))]
+pub mod half_duplex;
+
+#[cfg(any(feature = "doc", feature = "lin"))]
+#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "lin")))]
+pub mod lin;
+
+#[cfg(any(feature = "doc", feature = "modbus-rtu"))]
+#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "modbus-rtu")))]
+pub mod modbus;
+
+#[cfg(any(feature = "doc", feature = "pps"))]
+#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "pps")))]
+pub mod pps;
+
+#[cfg(any(feature = "doc", feature = "rfc2217"))]
+#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "rfc2217")))]
+pub mod rfc2217;
+
+#[cfg(any(feature = "doc", feature = "scpi"))]
+#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "scpi")))]
+pub mod scpi;
+
+#[cfg(any(feature = "doc", feature = "slcan"))]
+#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "slcan")))]
+pub mod slcan;
+
+#[cfg(any(feature = "doc", feature = "sniffer"))]
+#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "sniffer")))]
+pub mod sniffer;
+
+#[cfg(any(feature = "doc", feature = "stk500"))]
+#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "stk500")))]
+pub mod stk500;
+
+#[cfg(any(feature = "doc", feature = "supervisor"))]
+#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "supervisor")))]
+pub mod supervisor;
+
+#[cfg(any(feature = "doc", feature = "text"))]
+#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "text")))]
+pub mod text;
+
+pub use autobaud::BaudRateProbe;
+pub use broadcast::Broadcast;
+#[cfg(any(feature = "doc", unix))]
+#[cfg_attr(feature = "doc-cfg", doc(cfg(unix)))]
+pub use coalesce::{AdaptiveReadBuffer, ReadCoalescing};
+pub use copy_compat::CopyCompat;
+pub use describe::{PortDescription, SettingsSummary};
+pub use diagnose::{OpenDiagnosis, ProcessInfo};
+pub use error::Error;
+pub use fault::{FaultConfig, FaultStats, FaultyPort};
+pub use flow_control::{FlowControlStatus, XonXoffConfig};
+pub use frame_assembler::{Delimiter, FrameAssembler};
+pub use identity::PortIdentity;
+pub use line_control::LineAction;
+pub use line_counters::LineCounters;
+pub use line_state::{LinePoller, LineState};
+pub use open_options::OpenOptions;
+pub use pacing::WritePacing;
+pub use poll_scheduler::{PollResult, PollScheduler, SlaveStats, SlaveStatus};
+pub use port_info::{PortInfo, UdevInfo, UsbInfo};
+pub use port_set::{PortEvent, PortId, PortSet};
+pub use port_state::{PortState, PortStateGuard};
+pub use preset::Preset;
+pub use reader_writer::ReaderWriterError;
+pub use simulated_port::{SimulatedPort, SimulationConfig};
+pub use socket_port::SocketPort;
+pub use stats::Stats;
+pub use subscribe::{LagPolicy, Subscription, SubscriptionError};
+pub use timestamps::Timestamps;
+pub use transfer::SendOptions;
+pub use tx_queue::TxQueue;
+#[cfg(any(feature = "doc", windows))]
+#[cfg_attr(feature = "doc-cfg", doc(cfg(windows)))]
+pub use virtual_pair::VirtualPortPair;
+pub use write_ticket::WriteTicket;
+pub use zero_read::ZeroReadPolicy;
 
 pub use serial2::{
 	COMMON_BAUD_RATES,
@@ -71,6 +244,7 @@ pub use serial2::{
 	Settings,
 	StopBits,
 	TryFromError,
+	os,
 };
 
 #[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "rs4xx")))]
@@ -80,8 +254,33 @@ pub use serial2::rs4xx;
 use tokio::io::{AsyncRead, AsyncWrite};
 
 /// An asynchronous serial port for Tokio.
+///
+/// The serial port is closed when the last handle to it is dropped.
+/// Dropping does not wait for the output buffer to be transmitted:
+/// use [`SerialPort::close()`] if the last bytes written to the serial port matter.
 pub struct SerialPort {
 	inner: inner::SerialPort,
+	stats: stats::StatsCollector,
+	pacer: pacing::Pacer,
+	echo: echo::EchoFilter,
+	timestamps: timestamps::TimestampRecorder,
+	overrun: overrun::OverrunDetector,
+	zero_read_policy: zero_read::ZeroReadState,
+	zero_read_sleep: Option<Pin<Box<tokio::time::Sleep>>>,
+	write_sleep: Option<Pin<Box<tokio::time::Sleep>>>,
+	/// The time when the pending write through [`AsyncWrite`] started, to record the write duration.
+	write_start: Option<std::time::Instant>,
+	broadcast: subscribe::BroadcastSlot,
+	request_lock: tokio::sync::Mutex<()>,
+	frame_lock: tokio::sync::Mutex<()>,
+	shutdown: shutdown::Shutdown,
+	restore_settings: Option<inner::SavedSettings>,
+	#[cfg(unix)]
+	uucp_lock: Option<uucp_lock::UucpLock>,
+	#[cfg(unix)]
+	coalescer: coalesce::Coalescer,
+	#[cfg(unix)]
+	read_sleep: coalesce::ReadSleep,
 }
 
 impl SerialPort {
@@ -96,6 +295,8 @@ impl SerialPort {
 	///
 	/// The library automatically uses the win32 device namespace on Windows, so COM ports above COM9 are supported out of the box.
 	///
+	/// Use [`OpenOptions`] for more control over how the serial port is opened.
+	///
 	/// # Example
 	/// ```no_run
 	/// # use serial2::SerialPort;
@@ -105,26 +306,126 @@ impl SerialPort {
 	/// # }
 	/// ```
 	pub fn open(path: impl AsRef<Path>, settings: impl IntoSettings) -> std::io::Result<Self> {
-		let inner = serial2::SerialPort::open(path, settings)?;
-		let inner = inner::SerialPort::wrap(inner)?;
-		Ok(Self {
+		OpenOptions::new().open(path, settings)
+	}
+
+	/// Open a serial port to passively observe the received data.
+	///
+	/// The serial port is opened for reading only and the configuration is left as it is.
+	/// This library does not change the DTR and RTS lines either,
+	/// so the serial port can be used to listen in on a link that is driven by another program or device.
+	/// Writing to the returned serial port fails.
+	///
+	/// Note that some operating systems assert the DTR and RTS lines when a serial port is opened while no other process has it open,
+	/// and deassert them when the last handle is closed.
+	/// This can not be prevented by this library.
+	///
+	/// This is equivalent to opening the serial port with [`OpenOptions::read_only()`] and [`KeepSettings`].
+	///
+	/// # Example
+	/// ```no_run
+	/// # async fn example() -> std::io::Result<()> {
+	/// use serial2_tokio::SerialPort;
+	///
+	/// let port = SerialPort::open_observer("/dev/ttyUSB0")?;
+	/// let mut buffer = [0; 256];
+	/// let read = port.read(&mut buffer).await?;
+	/// println!("observed: {:?}", &buffer[..read]);
+	/// # Ok(())
+	/// # }
+	/// ```
+	pub fn open_observer(path: impl AsRef<Path>) -> std::io::Result<Self> {
+		OpenOptions::new().read_only(true).open(path, KeepSettings)
+	}
+
+	/// Wrap an opened serial port.
+	fn from_inner(inner: inner::SerialPort, stats: stats::StatsCollector) -> Self {
+		Self {
 			inner,
-		})
+			stats,
+			pacer: pacing::Pacer::new(),
+			echo: echo::EchoFilter::new(),
+			timestamps: Default::default(),
+			overrun: Default::default(),
+			zero_read_policy: Default::default(),
+			zero_read_sleep: None,
+			write_sleep: None,
+			write_start: None,
+			broadcast: Default::default(),
+			request_lock: Default::default(),
+			frame_lock: Default::default(),
+			shutdown: Default::default(),
+			restore_settings: None,
+			#[cfg(unix)]
+			uucp_lock: None,
+			#[cfg(unix)]
+			coalescer: Default::default(),
+			#[cfg(unix)]
+			read_sleep: None,
+		}
 	}
 
 	/// Get a list of available serial ports.
 	///
 	/// Not currently supported on all platforms.
 	/// On unsupported platforms, this function always returns an error.
+	///
+	/// On Android, apps are often not allowed to read the information in `/sys/class/tty` that is used to find serial ports.
+	/// In that case, the device nodes in `/dev` with common names for serial ports are returned instead,
+	/// which may include devices without hardware behind them.
 	pub fn available_ports() -> std::io::Result<Vec<PathBuf>> {
-		serial2::SerialPort::available_ports()
+		#[cfg(target_os = "android")] {
+			serial2::SerialPort::available_ports().or_else(android::scan_dev)
+		}
+		#[cfg(not(target_os = "android"))] {
+			serial2::SerialPort::available_ports()
+		}
 	}
 
 	/// Configure (or reconfigure) the serial port.
-	pub fn set_configuration(&mut self, settings: &Settings) -> std::io::Result<()> {
+	///
+	/// This function takes a const reference `&self`,
+	/// so you can reconfigure the serial port while it is shared between multiple tasks.
+	///
+	/// Reads and writes that are in progress are not interrupted by a reconfiguration.
+	/// However, data that was already received or queued for transmission by the OS may have been processed with the old settings.
+	/// On Unix platforms, the OS waits until all pending output has been transmitted before applying the new settings.
+	/// This blocks the calling thread until the output buffer is empty.
+	///
+	/// If you want to change only some settings based on the current configuration, use [`Self::modify_configuration()`] instead.
+	pub fn set_configuration(&self, settings: &Settings) -> std::io::Result<()> {
 		self.inner.with_raw_mut(|raw| raw.set_configuration(settings))
 	}
 
+	/// Modify the current configuration of the serial port.
+	///
+	/// The function reads the current configuration, passes it to the given closure, and applies the modified configuration.
+	/// Concurrent calls to this function or to [`Self::set_configuration()`] on the same `SerialPort` are serialized,
+	/// so no changes are lost when multiple tasks reconfigure the serial port at the same time.
+	///
+	/// See [`Self::set_configuration()`] for the interaction with reads and writes that are in progress.
+	///
+	/// # Example
+	/// ```no_run
+	/// # async fn example() -> std::io::Result<()> {
+	/// use serial2_tokio::SerialPort;
+	///
+	/// let port = SerialPort::open("/dev/ttyUSB0", 9600)?;
+	/// port.modify_configuration(|settings| settings.set_baud_rate(115200))?;
+	/// # Ok(())
+	/// # }
+	/// ```
+	pub fn modify_configuration<F>(&self, function: F) -> std::io::Result<()>
+	where
+		F: FnOnce(&mut Settings) -> std::io::Result<()>,
+	{
+		self.inner.with_raw_mut(|raw| {
+			let mut settings = raw.get_configuration()?;
+			function(&mut settings)?;
+			raw.set_configuration(&settings)
+		})
+	}
+
 	/// Get the current configuration of the serial port.
 	///
 	/// This function can fail if the underlying syscall fails,
@@ -141,7 +442,7 @@ impl SerialPort {
 	/// The data may end up interleaved in unpredictable ways.
 	pub fn try_clone(&self) -> std::io::Result<Self> {
 		let inner = self.inner.try_clone()?;
-		Ok(Self { inner })
+		Ok(Self::from_inner(inner, self.stats.new_like()))
 	}
 
 	/// Read bytes from the serial port.
@@ -151,9 +452,25 @@ impl SerialPort {
 	///
 	/// Note that there are no guarantees about which task receives what data when multiple tasks are reading from the serial port.
 	/// You should normally limit yourself to a single reading task and a single writing task.
+	///
+	/// On Linux with the `io-uring` feature enabled, the read is performed through io_uring if the kernel supports it.
+	/// If the returned future is dropped after data was read, the data is returned by the next read instead of being lost.
 	#[cfg_attr(not(feature = "doc"), allow(rustdoc::broken_intra_doc_links))]
 	pub async fn read(&self, buf: &mut [u8]) -> std::io::Result<usize> {
-		self.inner.read(buf).await
+		let (read, _time) = self.read_timestamped(buf).await?;
+		Ok(read)
+	}
+
+	/// Read bytes from the serial port without removing the echo of transmitted data.
+	async fn read_unfiltered(&self, buf: &mut [u8]) -> std::io::Result<usize> {
+		#[cfg(unix)]
+		if let Some(result) = self.read_coalesced(buf).await {
+			self.stats.record_read(&result);
+			return result;
+		}
+		let result = self.inner.read(buf).await;
+		self.stats.record_read(&result);
+		result
 	}
 
 	/// Read bytes from the serial port into a slice of buffers.
@@ -161,7 +478,29 @@ impl SerialPort {
 	/// Note that there are no guarantees about which task receives what data when multiple tasks are reading from the serial port.
 	/// You should normally limit yourself to a single reading task and a single writing task.
 	pub async fn read_vectored(&self, buf: &mut [IoSliceMut<'_>]) -> std::io::Result<usize> {
-		self.inner.read_vectored(buf).await
+		if self.echo.is_enabled() || self.get_zero_read_policy() != ZeroReadPolicy::Eof {
+			return match buf.iter_mut().find(|buf| !buf.is_empty()) {
+				Some(first) => self.read(first).await,
+				None => Ok(0),
+			};
+		}
+		self.check_overrun()?;
+		#[cfg(unix)]
+		if let Some(first) = buf.iter_mut().find(|buf| !buf.is_empty()) {
+			if let Some(result) = self.read_coalesced(first).await {
+				self.stats.record_read(&result);
+				if let Ok(1..) = result {
+					self.timestamps.record_read(std::time::Instant::now());
+				}
+				return result;
+			}
+		}
+		let result = self.inner.read_vectored(buf).await;
+		self.stats.record_read(&result);
+		if let Ok(1..) = result {
+			self.timestamps.record_read(std::time::Instant::now());
+		}
+		result
 	}
 
 	/// Check if the implementation supports vectored reads.
@@ -179,9 +518,22 @@ impl SerialPort {
 	///
 	/// Note that data written to the same serial port from multiple tasks may end up interleaved at the receiving side.
 	/// You should normally limit yourself to a single reading task and a single writing task.
+	///
+	/// On Linux with the `io-uring` feature enabled, the write is performed through io_uring if the kernel supports it.
+	/// In that case, the data may still be written if the returned future is dropped before it completes.
 	#[cfg_attr(not(feature = "doc"), allow(rustdoc::broken_intra_doc_links))]
 	pub async fn write(&self, buf: &[u8]) -> std::io::Result<usize> {
-		self.inner.write(buf).await
+		let buf = &buf[..self.pacer.ready(buf.len()).await];
+		let start = std::time::Instant::now();
+		let result = self.inner.write(buf).await;
+		self.stats.record_write(&result);
+		self.stats.record_write_duration(start.elapsed());
+		if let Ok(written) = result {
+			self.pacer.consume(written);
+			self.echo.record(&buf[..written]);
+			self.timestamps.record_write(std::time::Instant::now());
+		}
+		result
 	}
 
 	/// Write all bytes to the serial port.
@@ -212,7 +564,17 @@ impl SerialPort {
 	/// You should normally limit yourself to a single reading task and a single writing task.
 	#[cfg_attr(not(feature = "doc"), allow(rustdoc::broken_intra_doc_links))]
 	pub async fn write_vectored(&self, buf: &[IoSlice<'_>]) -> std::io::Result<usize> {
-		self.inner.write_vectored(buf).await
+		if self.pacer.is_enabled() || self.echo.is_enabled() {
+			return self.write(first_non_empty(buf)).await;
+		}
+		let start = std::time::Instant::now();
+		let result = self.inner.write_vectored(buf).await;
+		self.stats.record_write(&result);
+		self.stats.record_write_duration(start.elapsed());
+		if result.is_ok() {
+			self.timestamps.record_write(std::time::Instant::now());
+		}
+		result
 	}
 
 	/// Check if the implementation supports vectored writes.
@@ -223,24 +585,75 @@ impl SerialPort {
 		self.inner.is_write_vectored()
 	}
 
+	/// Wait until all data in the output buffer has been transmitted.
+	///
+	/// This waits for the OS to report that all data written to the serial port has been transmitted.
+	/// The blocking system call is performed on a thread from the blocking thread pool of the Tokio runtime.
+	///
+	/// Note that some drivers report that the output buffer is empty before the last byte has fully left the UART.
+	pub async fn drain(&self) -> std::io::Result<()> {
+		let port = self.inner.with_raw(|raw| raw.try_clone())?;
+		tokio::task::spawn_blocking(move || port.flush())
+			.await
+			.map_err(std::io::Error::other)?
+	}
+
+	/// Wait until the underlying device is closed or removed.
+	///
+	/// This resolves when the device disappears, for example because a USB serial adapter was unplugged.
+	/// It can be used by a supervising task to react to device removal immediately,
+	/// without waiting for the next read or write to fail.
+	///
+	/// On Unix platforms, this waits for the OS to report a hang-up on the device.
+	/// This does not interfere with concurrent reads and writes.
+	///
+	/// On Windows, there is no notification for the removal of a serial device that can be used alongside reads and writes.
+	/// Instead, the status of the device is polled every 200 milliseconds by each waiting task,
+	/// so the removal is detected with a delay of up to 200 milliseconds.
+	///
+	/// # Example
+	/// ```no_run
+	/// # async fn example() -> std::io::Result<()> {
+	/// use serial2_tokio::SerialPort;
+	///
+	/// let port = SerialPort::open("/dev/ttyUSB0", 115200)?;
+	/// port.closed().await?;
+	/// println!("serial port disconnected");
+	/// # Ok(())
+	/// # }
+	/// ```
+	pub async fn closed(&self) -> std::io::Result<()> {
+		self.inner.closed().await
+	}
+
 	/// Discard the kernel input and output buffers for the serial port.
 	///
 	/// When you write to a serial port, the data may be put in a buffer by the OS to be transmitted by the actual device later.
 	/// Similarly, data received on the device can be put in a buffer by the OS untill you read it.
 	/// This function clears both buffers: any untransmitted data and received but unread data is discarded by the OS.
+	///
+	/// Received data that was already read into an internal buffer of this [`SerialPort`] is discarded too.
 	pub fn discard_buffers(&self) -> std::io::Result<()> {
-		self.inner.with_raw(|raw| raw.discard_buffers())
+		self.inner.with_raw(|raw| raw.discard_buffers())?;
+		#[cfg(unix)]
+		self.discard_read_buffers();
+		Ok(())
 	}
 
 	/// Discard the kernel input buffers for the serial port.
 	///
 	/// Data received on the device can be put in a buffer by the OS untill you read it.
 	/// This function clears that buffer: received but unread data is discarded by the OS.
+	/// Received data that was already read into an internal buffer of this [`SerialPort`] is discarded too,
+	/// like the buffer used for read coalescing on Unix.
 	///
 	/// This is particularly useful when communicating with a device that only responds to commands that you send to it.
 	/// If you discard the input buffer before sending the command, you discard any noise that may have been received after the last command.
 	pub fn discard_input_buffer(&self) -> std::io::Result<()> {
-		self.inner.with_raw(|raw| raw.discard_input_buffer())
+		self.inner.with_raw(|raw| raw.discard_input_buffer())?;
+		#[cfg(unix)]
+		self.discard_read_buffers();
+		Ok(())
 	}
 
 	/// Discard the kernel output buffers for the serial port.
@@ -248,7 +661,7 @@ impl SerialPort {
 	/// When you write to a serial port, the data is generally put in a buffer by the OS to be transmitted by the actual device later.
 	/// This function clears that buffer: any untransmitted data is discarded by the OS.
 	pub fn discard_output_buffer(&self) -> std::io::Result<()> {
-		self.inner.with_raw(|raw| raw.discard_input_buffer())
+		self.inner.with_raw(|raw| raw.discard_output_buffer())
 	}
 
 	/// Set the state of the Ready To Send line.
@@ -299,6 +712,28 @@ impl SerialPort {
 		self.inner.with_raw(|raw| raw.read_cd())
 	}
 
+	/// Read the state of the Ready To Send line, as set by this or another process.
+	///
+	/// This reports the state of the output line itself, which is useful to check the state after opening a serial port.
+	pub fn read_rts(&self) -> std::io::Result<bool> {
+		Ok(self.inner.read_output_lines()?.0)
+	}
+
+	/// Read the state of the Data Terminal Ready line, as set by this or another process.
+	///
+	/// This reports the state of the output line itself, which is useful to check the state after opening a serial port.
+	pub fn read_dtr(&self) -> std::io::Result<bool> {
+		Ok(self.inner.read_output_lines()?.1)
+	}
+
+	/// Set or clear the break state of the serial port.
+	///
+	/// The serial port will hold the data line in a logical low state while the break state is enabled.
+	/// This can be detected as a break condition on the other side of the line.
+	pub fn set_break(&self, enable: bool) -> std::io::Result<()> {
+		self.inner.with_raw(|raw| raw.set_break(enable))
+	}
+
 	/// Get the RS-4xx mode of the serial port transceiver.
 	///
 	/// This is currently only supported on Linux.
@@ -334,6 +769,47 @@ impl SerialPort {
 	pub fn set_rs4xx_mode(&self, mode: impl Into<rs4xx::TransceiverMode>) -> std::io::Result<()> {
 		self.inner.with_raw(|raw| raw.set_rs4xx_mode(mode))
 	}
+
+	/// Enable or disable RTS toggle mode.
+	///
+	/// This is currently only supported on Windows.
+	///
+	/// In RTS toggle mode, the driver sets the RTS line high while there is data in the output buffer,
+	/// and low when the output buffer is empty.
+	/// This can be used to control the direction of an RS-485 transceiver.
+	///
+	/// Not all drivers support RTS toggle mode.
+	/// Windows provides no way to check this without changing the configuration,
+	/// so this returns an error if the driver rejects RTS toggle mode.
+	///
+	/// Note that [`Settings::get_flow_control()`] will report an error while RTS toggle mode is enabled without RTS/CTS flow control.
+	#[cfg(any(feature = "doc", all(feature = "rs4xx", windows)))]
+	#[cfg_attr(feature = "doc-cfg", doc(cfg(all(feature = "rs4xx", windows))))]
+	pub fn set_rts_toggle(&self, enable: bool) -> std::io::Result<()> {
+		#[cfg(all(feature = "rs4xx", windows))] {
+			self.inner.set_rts_toggle(enable)
+		}
+		#[cfg(not(all(feature = "rs4xx", windows)))] {
+			let _ = enable;
+			unreachable!("this code is only enabled on Windows or during documentation generation")
+		}
+	}
+
+	/// Check if RTS toggle mode is enabled.
+	///
+	/// This is currently only supported on Windows.
+	///
+	/// See [`Self::set_rts_toggle()`] for more information.
+	#[cfg(any(feature = "doc", all(feature = "rs4xx", windows)))]
+	#[cfg_attr(feature = "doc-cfg", doc(cfg(all(feature = "rs4xx", windows))))]
+	pub fn get_rts_toggle(&self) -> std::io::Result<bool> {
+		#[cfg(all(feature = "rs4xx", windows))] {
+			self.inner.get_rts_toggle()
+		}
+		#[cfg(not(all(feature = "rs4xx", windows)))] {
+			unreachable!("this code is only enabled on Windows or during documentation generation")
+		}
+	}
 }
 
 impl AsyncRead for SerialPort {
@@ -342,7 +818,47 @@ impl AsyncRead for SerialPort {
 		cx: &mut std::task::Context<'_>,
 		buf: &mut tokio::io::ReadBuf<'_>,
 	) -> Poll<std::io::Result<()>> {
-		self.get_mut().inner.poll_read(cx, buf)
+		let this = self.get_mut();
+		let filled = buf.filled().len();
+		if let Err(e) = this.check_overrun() {
+			return Poll::Ready(Err(e));
+		}
+		loop {
+			if let Some(sleep) = &mut this.zero_read_sleep {
+				ready!(sleep.as_mut().poll(cx));
+				this.zero_read_sleep = None;
+			}
+			#[cfg(unix)]
+			let result = match this.poll_read_coalesced(cx, buf) {
+				Some(result) => ready!(result),
+				None => ready!(this.inner.poll_read(cx, buf)),
+			};
+			#[cfg(not(unix))]
+			let result = ready!(this.inner.poll_read(cx, buf));
+			let result = result.map(|()| buf.filled().len() - filled);
+			this.stats.record_read(&result);
+			let read = result?;
+			let time = std::time::Instant::now();
+			if read == 0 {
+				if buf.remaining() == 0 {
+					return Poll::Ready(Ok(()));
+				}
+				match this.get_zero_read_policy() {
+					ZeroReadPolicy::Eof => return Poll::Ready(Ok(())),
+					ZeroReadPolicy::Error => return Poll::Ready(Err(zero_read::zero_read_error())),
+					ZeroReadPolicy::Retry => {
+						this.zero_read_sleep = Some(Box::pin(tokio::time::sleep(zero_read::RETRY_DELAY)));
+						continue;
+					},
+				}
+			}
+			let read = this.echo.strip(&mut buf.filled_mut()[filled..]);
+			buf.set_filled(filled + read);
+			if read > 0 {
+				this.timestamps.record_read(time);
+				return Poll::Ready(Ok(()));
+			}
+		}
 	}
 }
 
@@ -352,7 +868,19 @@ impl AsyncWrite for SerialPort {
 		cx: &mut std::task::Context<'_>,
 		buf: &[u8],
 	) -> Poll<std::io::Result<usize>> {
-		self.get_mut().inner.poll_write(cx, buf)
+		let this = self.get_mut();
+		let len = ready!(this.pacer.poll_ready(cx, &mut this.write_sleep, buf.len()));
+		let start = *this.write_start.get_or_insert_with(std::time::Instant::now);
+		let result = ready!(this.inner.poll_write(cx, &buf[..len]));
+		this.write_start = None;
+		this.stats.record_write(&result);
+		this.stats.record_write_duration(start.elapsed());
+		if let Ok(written) = result {
+			this.pacer.consume(written);
+			this.echo.record(&buf[..written]);
+			this.timestamps.record_write(std::time::Instant::now());
+		}
+		Poll::Ready(result)
 	}
 
 	fn poll_write_vectored(
@@ -360,7 +888,19 @@ impl AsyncWrite for SerialPort {
 		cx: &mut std::task::Context<'_>,
 		bufs: &[IoSlice<'_>],
 	) -> Poll<Result<usize, std::io::Error>> {
-		self.get_mut().inner.poll_write_vectored(cx, bufs)
+		if self.pacer.is_enabled() || self.echo.is_enabled() {
+			return self.poll_write(cx, first_non_empty(bufs));
+		}
+		let this = self.get_mut();
+		let start = *this.write_start.get_or_insert_with(std::time::Instant::now);
+		let result = ready!(this.inner.poll_write_vectored(cx, bufs));
+		this.write_start = None;
+		this.stats.record_write(&result);
+		this.stats.record_write_duration(start.elapsed());
+		if result.is_ok() {
+			this.timestamps.record_write(std::time::Instant::now());
+		}
+		Poll::Ready(result)
 	}
 
 	fn poll_flush(self: Pin<&mut Self>, _cx: &mut std::task::Context<'_>) -> Poll<Result<(), std::io::Error>> {
@@ -369,7 +909,20 @@ impl AsyncWrite for SerialPort {
 	}
 
 	fn poll_shutdown(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<Result<(), std::io::Error>> {
-		self.get_mut().inner.poll_shutdown(cx)
+		self.get_mut().poll_shutdown_impl(cx)
+	}
+}
+
+/// Get the first non-empty buffer from a slice of buffers, or an empty buffer if there is none.
+fn first_non_empty<'a>(bufs: &'a [IoSlice<'_>]) -> &'a [u8] {
+	bufs.iter().find(|buf| !buf.is_empty()).map_or(&[], |buf| &buf[..])
+}
+
+impl Drop for SerialPort {
+	fn drop(&mut self) {
+		if let Some(settings) = self.restore_settings.take() {
+			self.inner.restore_settings(&settings).ok();
+		}
 	}
 }
 
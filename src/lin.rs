@@ -0,0 +1,521 @@
+//! LIN bus master operation over a UART with a LIN transceiver.
+//!
+//! The [`LinMaster`] sends the frame headers on the bus: a break field, the sync byte `0x55` and the protected identifier.
+//! It can then publish the response itself with [`LinMaster::write_frame()`],
+//! or wait for a slave to publish the response with [`LinMaster::read_frame()`].
+//!
+//! The break field is generated with [`SerialPort::set_break()`], timed with the Tokio timer.
+//! The timer has a resolution of one millisecond, so the break field is usually a little longer than requested.
+//! This is allowed by the LIN specification, which only sets a minimum length for the break field.
+//!
+//! A [`ScheduleTable`] describes the frames that are sent periodically by the master,
+//! and [`LinMaster::run_schedule()`] processes the table in a loop.
+//!
+//! # Example
+//! ```no_run
+//! # async fn example() -> std::io::Result<()> {
+//! use serial2_tokio::SerialPort;
+//! use serial2_tokio::lin::{ChecksumModel, LinConfig, LinMaster};
+//!
+//! let port = SerialPort::open("/dev/ttyUSB0", 19200)?;
+//! let master = LinMaster::new(port, LinConfig::new())?;
+//! master.write_frame(0x10, &[0x01, 0x02], ChecksumModel::Enhanced).await?;
+//! let response = master.read_frame(0x21, 4, ChecksumModel::Enhanced).await?;
+//! println!("response: {response:02X?}");
+//! # Ok(())
+//! # }
+//! ```
+
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use crate::SerialPort;
+
+/// The sync byte that follows the break field.
+const SYNC: u8 = 0x55;
+
+/// The maximum number of data bytes in a frame.
+const MAX_DATA_LEN: usize = 8;
+
+/// The checksum model of a LIN frame.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ChecksumModel {
+	/// The classic checksum from LIN 1.x, computed over the data bytes only.
+	///
+	/// This is also used for the diagnostic frames with identifiers `0x3C` and `0x3D` in LIN 2.x.
+	Classic,
+
+	/// The enhanced checksum from LIN 2.x, computed over the protected identifier and the data bytes.
+	Enhanced,
+}
+
+/// Compute the protected identifier for a frame identifier.
+///
+/// The protected identifier contains the 6 bit frame identifier and two parity bits.
+/// Only the lower 6 bits of `id` are used.
+pub fn protected_id(id: u8) -> u8 {
+	let id = id & 0x3F;
+	let bit = |n: u8| (id >> n) & 1;
+	let p0 = bit(0) ^ bit(1) ^ bit(2) ^ bit(4);
+	let p1 = !(bit(1) ^ bit(3) ^ bit(4) ^ bit(5)) & 1;
+	id | (p0 << 6) | (p1 << 7)
+}
+
+/// Compute the checksum of a frame.
+///
+/// The `pid` is the protected identifier of the frame, as returned by [`protected_id()`].
+/// It is ignored for the [`ChecksumModel::Classic`] checksum.
+pub fn checksum(model: ChecksumModel, pid: u8, data: &[u8]) -> u8 {
+	let initial = match model {
+		ChecksumModel::Classic => 0,
+		ChecksumModel::Enhanced => u16::from(pid),
+	};
+	let sum = data.iter().fold(initial, |sum, &byte| {
+		// Add with end-around carry.
+		let sum = sum + u16::from(byte);
+		if sum > 0xFF {
+			sum - 0xFF
+		} else {
+			sum
+		}
+	});
+	!(sum as u8)
+}
+
+/// Configuration for a [`LinMaster`].
+#[derive(Debug, Clone)]
+pub struct LinConfig {
+	break_bits: u32,
+	response_timeout: Duration,
+	echo: bool,
+}
+
+impl Default for LinConfig {
+	fn default() -> Self {
+		Self {
+			break_bits: 13,
+			response_timeout: Duration::from_millis(50),
+			echo: true,
+		}
+	}
+}
+
+impl LinConfig {
+	/// Create a new configuration with the default values.
+	///
+	/// The default configuration uses a break field of 13 bit times, a response timeout of 50 milliseconds,
+	/// and expects the transceiver to echo all transmitted data.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Set the minimum length of the break field in bit times.
+	///
+	/// The LIN specification requires at least 13 bit times.
+	pub fn set_break_bits(&mut self, bits: u32) {
+		self.break_bits = bits;
+	}
+
+	/// Get the minimum length of the break field in bit times.
+	pub fn get_break_bits(&self) -> u32 {
+		self.break_bits
+	}
+
+	/// Set the maximum time to wait for the response of a slave.
+	///
+	/// The timeout starts after the header has been sent.
+	/// It should include some margin for the latency of the OS and the serial port driver.
+	pub fn set_response_timeout(&mut self, timeout: Duration) {
+		self.response_timeout = timeout;
+	}
+
+	/// Get the maximum time to wait for the response of a slave.
+	pub fn get_response_timeout(&self) -> Duration {
+		self.response_timeout
+	}
+
+	/// Set whether the transceiver echoes transmitted data.
+	///
+	/// LIN is a single wire bus, so most transceivers receive everything they transmit.
+	/// If enabled, the echo is read back and compared with the transmitted data to detect collisions on the bus.
+	pub fn set_echo(&mut self, echo: bool) {
+		self.echo = echo;
+	}
+
+	/// Check if the transceiver is expected to echo transmitted data.
+	pub fn get_echo(&self) -> bool {
+		self.echo
+	}
+}
+
+/// A LIN bus master.
+///
+/// See the [module documentation](self) for more information.
+#[derive(Debug)]
+pub struct LinMaster {
+	port: SerialPort,
+	config: LinConfig,
+	bit_time: Duration,
+	bus_lock: tokio::sync::Mutex<()>,
+}
+
+impl LinMaster {
+	/// Create a LIN master on a serial port.
+	///
+	/// The serial port must already be configured with the baud rate of the bus, 8 data bits, no parity and 1 stop bit.
+	/// The baud rate is read from the serial port to compute the length of the break field.
+	pub fn new(port: SerialPort, config: LinConfig) -> std::io::Result<Self> {
+		let baud_rate = port.get_configuration()?.get_baud_rate()?;
+		if baud_rate == 0 {
+			return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "the baud rate of the serial port is zero"));
+		}
+		Ok(Self {
+			port,
+			config,
+			bit_time: Duration::from_secs(1) / baud_rate,
+			bus_lock: tokio::sync::Mutex::new(()),
+		})
+	}
+
+	/// Get a reference to the serial port.
+	pub fn get_ref(&self) -> &SerialPort {
+		&self.port
+	}
+
+	/// Get the configuration of the LIN master.
+	pub fn config(&self) -> &LinConfig {
+		&self.config
+	}
+
+	/// Consume the LIN master and return the serial port.
+	pub fn into_inner(self) -> SerialPort {
+		self.port
+	}
+
+	/// Send a frame header and publish the response.
+	///
+	/// The data must contain between 1 and 8 bytes.
+	///
+	/// If the transceiver echoes transmitted data, an error of kind [`std::io::ErrorKind::InvalidData`] is returned when the echo does not match.
+	pub async fn write_frame(&self, id: u8, data: &[u8], model: ChecksumModel) -> std::io::Result<()> {
+		check_data_len(data.len())?;
+		let _lock = self.bus_lock.lock().await;
+		let pid = self.send_header(id).await?;
+
+		let mut response = Vec::with_capacity(data.len() + 1);
+		response.extend_from_slice(data);
+		response.push(checksum(model, pid, data));
+		self.write_checked(&response).await
+	}
+
+	/// Send a frame header and read the response of a slave.
+	///
+	/// The response must contain `len` data bytes, which must be between 1 and 8.
+	///
+	/// Returns an error of kind [`std::io::ErrorKind::TimedOut`] if the slave did not respond in time,
+	/// or an error of kind [`std::io::ErrorKind::InvalidData`] if the checksum is invalid.
+	pub async fn read_frame(&self, id: u8, len: usize, model: ChecksumModel) -> std::io::Result<Vec<u8>> {
+		check_data_len(len)?;
+		let _lock = self.bus_lock.lock().await;
+		let pid = self.send_header(id).await?;
+
+		let mut response = vec![0; len + 1];
+		tokio::time::timeout(self.config.response_timeout, self.read_exact(&mut response)).await
+			.map_err(|_| std::io::Error::new(std::io::ErrorKind::TimedOut, format!("no response for frame 0x{:02X}", id & 0x3F)))??;
+		let received = response.pop().unwrap_or_default();
+		let expected = checksum(model, pid, &response);
+		if received != expected {
+			return Err(std::io::Error::new(
+				std::io::ErrorKind::InvalidData,
+				format!("invalid checksum for frame 0x{:02X}: expected 0x{expected:02X}, got 0x{received:02X}", id & 0x3F),
+			));
+		}
+		Ok(response)
+	}
+
+	/// Process a schedule table in a loop.
+	///
+	/// Each entry of the table is processed in its own time slot.
+	/// The `on_frame` callback is called after each frame with the frame identifier,
+	/// and either the data of the frame or the error that occurred.
+	///
+	/// Timeouts and invalid responses are reported to the callback and do not stop the schedule.
+	/// This function only returns when another error occurs, such as a failure of the serial port.
+	/// An empty table returns immediately.
+	pub async fn run_schedule<F>(&self, table: &ScheduleTable, mut on_frame: F) -> std::io::Result<()>
+	where
+		F: FnMut(u8, std::io::Result<Vec<u8>>),
+	{
+		if table.entries.is_empty() {
+			return Ok(());
+		}
+		let mut slot_start = Instant::now();
+		loop {
+			for entry in &table.entries {
+				let result = match &entry.frame {
+					ScheduledFrame::Publish(data) => self.write_frame(entry.id, data, entry.checksum).await.map(|()| data.clone()),
+					ScheduledFrame::Subscribe(len) => self.read_frame(entry.id, *len, entry.checksum).await,
+				};
+				match result {
+					Err(e) if !matches!(e.kind(), std::io::ErrorKind::TimedOut | std::io::ErrorKind::InvalidData) => return Err(e),
+					result => on_frame(entry.id, result),
+				}
+				slot_start += entry.slot;
+				tokio::time::sleep_until(slot_start).await;
+			}
+		}
+	}
+
+	/// Send a break field, the sync byte and the protected identifier.
+	async fn send_header(&self, id: u8) -> std::io::Result<u8> {
+		let pid = protected_id(id);
+		// Make sure the previous frame has been transmitted before starting the break.
+		self.port.drain().await?;
+		self.port.discard_input_buffer()?;
+		self.port.set_break(true)?;
+		tokio::time::sleep(self.bit_time * self.config.break_bits).await;
+		self.port.set_break(false)?;
+		// The break delimiter must be at least one bit time long.
+		tokio::time::sleep(self.bit_time).await;
+		self.write_checked(&[SYNC, pid]).await?;
+		Ok(pid)
+	}
+
+	/// Write data and check the echo from the transceiver, if enabled.
+	async fn write_checked(&self, data: &[u8]) -> std::io::Result<()> {
+		self.port.write_all(data).await?;
+		if !self.config.echo {
+			return Ok(());
+		}
+
+		let mut echo = vec![0; data.len()];
+		tokio::time::timeout(self.config.response_timeout, self.read_exact(&mut echo)).await
+			.map_err(|_| std::io::Error::new(std::io::ErrorKind::TimedOut, "no echo received from the transceiver"))??;
+		if echo != data {
+			return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "echo does not match transmitted data, possible bus collision"));
+		}
+		Ok(())
+	}
+
+	async fn read_exact(&self, buffer: &mut [u8]) -> std::io::Result<()> {
+		let mut filled = 0;
+		while filled < buffer.len() {
+			match self.port.read(&mut buffer[filled..]).await? {
+				0 => return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof)),
+				read => filled += read,
+			}
+		}
+		Ok(())
+	}
+}
+
+/// A schedule table for a [`LinMaster`].
+///
+/// The table contains the frames that are sent by the master, in order.
+/// Each frame has a time slot: the next frame is sent when the time slot of the previous frame has passed.
+///
+/// # Example
+/// ```no_run
+/// # async fn example() -> std::io::Result<()> {
+/// use std::time::Duration;
+/// use serial2_tokio::SerialPort;
+/// use serial2_tokio::lin::{ChecksumModel, LinConfig, LinMaster, ScheduleTable};
+///
+/// let port = SerialPort::open("/dev/ttyUSB0", 19200)?;
+/// let master = LinMaster::new(port, LinConfig::new())?;
+/// let table = ScheduleTable::new()
+///     .publish(0x10, [0x01, 0x00], ChecksumModel::Enhanced, Duration::from_millis(10))
+///     .subscribe(0x21, 4, ChecksumModel::Enhanced, Duration::from_millis(10));
+/// master.run_schedule(&table, |id, result| match result {
+///     Ok(data) => println!("frame 0x{id:02X}: {data:02X?}"),
+///     Err(e) => eprintln!("frame 0x{id:02X}: {e}"),
+/// }).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ScheduleTable {
+	entries: Vec<ScheduleEntry>,
+}
+
+/// An entry in a [`ScheduleTable`].
+#[derive(Debug, Clone)]
+struct ScheduleEntry {
+	id: u8,
+	frame: ScheduledFrame,
+	checksum: ChecksumModel,
+	slot: Duration,
+}
+
+/// The response of a scheduled frame.
+#[derive(Debug, Clone)]
+enum ScheduledFrame {
+	/// The master publishes the response.
+	Publish(Vec<u8>),
+
+	/// A slave publishes a response with the given number of bytes.
+	Subscribe(usize),
+}
+
+impl ScheduleTable {
+	/// Create an empty schedule table.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Add a frame for which the master publishes the response.
+	///
+	/// The data must contain between 1 and 8 bytes, or sending the frame will fail.
+	pub fn publish(mut self, id: u8, data: impl Into<Vec<u8>>, checksum: ChecksumModel, slot: Duration) -> Self {
+		self.entries.push(ScheduleEntry {
+			id,
+			frame: ScheduledFrame::Publish(data.into()),
+			checksum,
+			slot,
+		});
+		self
+	}
+
+	/// Add a frame for which a slave publishes a response of `len` bytes.
+	///
+	/// The length must be between 1 and 8 bytes, or sending the frame will fail.
+	pub fn subscribe(mut self, id: u8, len: usize, checksum: ChecksumModel, slot: Duration) -> Self {
+		self.entries.push(ScheduleEntry {
+			id,
+			frame: ScheduledFrame::Subscribe(len),
+			checksum,
+			slot,
+		});
+		self
+	}
+
+	/// Get the number of frames in the schedule table.
+	pub fn len(&self) -> usize {
+		self.entries.len()
+	}
+
+	/// Check if the schedule table is empty.
+	pub fn is_empty(&self) -> bool {
+		self.entries.is_empty()
+	}
+
+	/// Get the total duration of one cycle of the schedule table.
+	pub fn cycle_time(&self) -> Duration {
+		self.entries.iter().map(|entry| entry.slot).sum()
+	}
+}
+
+/// Check that the length of the data of a frame is valid.
+fn check_data_len(len: usize) -> std::io::Result<()> {
+	if len == 0 || len > MAX_DATA_LEN {
+		Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("a LIN frame must contain between 1 and {MAX_DATA_LEN} data bytes, got {len}")))
+	} else {
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn protected_id_matches_specification() {
+		// Values from the table of valid frame identifiers in the LIN 2.1 specification.
+		assert_eq!(protected_id(0x00), 0x80);
+		assert_eq!(protected_id(0x01), 0xC1);
+		assert_eq!(protected_id(0x10), 0x50);
+		assert_eq!(protected_id(0x21), 0x61);
+		assert_eq!(protected_id(0x3C), 0x3C);
+		assert_eq!(protected_id(0x3D), 0x7D);
+		assert_eq!(protected_id(0x3F), 0xBF);
+		// The parity bits of the input are ignored.
+		assert_eq!(protected_id(0xFC), 0x3C);
+	}
+
+	#[test]
+	fn checksum_matches_specification_example() {
+		// The example of the LIN 2.1 specification: PID 0x4A with data 0x55, 0x93, 0xE5.
+		assert_eq!(checksum(ChecksumModel::Enhanced, 0x4A, &[0x55, 0x93, 0xE5]), 0xE6);
+		assert_eq!(checksum(ChecksumModel::Classic, 0x4A, &[0x55, 0x93, 0xE5]), 0x31);
+	}
+
+	#[test]
+	fn checksum_uses_end_around_carry() {
+		assert_eq!(checksum(ChecksumModel::Classic, 0, &[0xFF; 8]), 0x00);
+		assert_eq!(checksum(ChecksumModel::Classic, 0, &[0x80, 0x80]), 0xFE);
+		assert_eq!(checksum(ChecksumModel::Classic, 0, &[]), 0xFF);
+	}
+
+	#[test]
+	fn schedule_table() {
+		let table = ScheduleTable::new()
+			.publish(0x10, [0x01, 0x02], ChecksumModel::Enhanced, Duration::from_millis(10))
+			.subscribe(0x21, 4, ChecksumModel::Enhanced, Duration::from_millis(15));
+		assert_eq!(table.len(), 2);
+		assert!(!table.is_empty());
+		assert_eq!(table.cycle_time(), Duration::from_millis(25));
+		assert!(ScheduleTable::new().is_empty());
+	}
+
+	#[test]
+	fn data_length_is_checked() {
+		assert!(check_data_len(1).is_ok());
+		assert!(check_data_len(8).is_ok());
+		assert_eq!(check_data_len(0).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+		assert_eq!(check_data_len(9).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+	}
+
+	#[cfg(all(unix, feature = "unix"))]
+	mod pty {
+		use super::*;
+		use crate::test_util::{expect, expect_then_reply, pair_with_timeout};
+
+		/// Create a master on one side of a pseudo-terminal pair, with the other side acting as slave.
+		///
+		/// A pseudo-terminal does not echo transmitted data like a LIN transceiver, so echo checking is disabled.
+		fn master() -> (LinMaster, SerialPort) {
+			let (a, b, timeout) = pair_with_timeout();
+			a.modify_configuration(|settings| settings.set_baud_rate(19200)).unwrap();
+			let mut config = LinConfig::new();
+			config.set_echo(false);
+			config.set_response_timeout(timeout);
+			(LinMaster::new(a, config).unwrap(), b)
+		}
+
+		#[tokio::test]
+		async fn write_frame_sends_header_and_response() {
+			let (master, slave) = master();
+			let (result, ()) = tokio::join!(
+				master.write_frame(0x10, &[0x01, 0x02], ChecksumModel::Enhanced),
+				expect(&slave, &[SYNC, 0x50, 0x01, 0x02, 0xAC]),
+			);
+			result.unwrap();
+		}
+
+		#[tokio::test]
+		async fn read_frame_checks_response() {
+			let (master, slave) = master();
+			let slave_side = expect_then_reply(&slave, &[SYNC, 0x61], &[0x10, 0x20, 0x30, 0x40, 0xFD]);
+			let (result, ()) = tokio::join!(master.read_frame(0x21, 4, ChecksumModel::Enhanced), slave_side);
+			assert_eq!(result.unwrap(), [0x10, 0x20, 0x30, 0x40]);
+
+			let slave_side = expect_then_reply(&slave, &[SYNC, 0x61], &[0x10, 0x20, 0x30, 0x40, 0xFE]);
+			let (result, ()) = tokio::join!(master.read_frame(0x21, 4, ChecksumModel::Enhanced), slave_side);
+			assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+		}
+
+		#[tokio::test]
+		async fn read_frame_times_out_without_slave() {
+			let (master, _slave) = master();
+			let error = master.read_frame(0x21, 4, ChecksumModel::Enhanced).await.unwrap_err();
+			assert_eq!(error.kind(), std::io::ErrorKind::TimedOut);
+		}
+
+		#[tokio::test]
+		async fn empty_schedule_returns_immediately() {
+			let (master, _slave) = master();
+			master.run_schedule(&ScheduleTable::new(), |_, _| panic!("no frames expected")).await.unwrap();
+		}
+	}
+}
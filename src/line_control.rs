@@ -0,0 +1,106 @@
+use std::time::Duration;
+
+use crate::SerialPort;
+
+/// A single step of a timed modem control line sequence.
+///
+/// Used with [`SerialPort::reset_sequence()`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum LineAction {
+	/// Set the state of the DTR line.
+	SetDtr(bool),
+
+	/// Set the state of the RTS line.
+	SetRts(bool),
+
+	/// Wait for the given duration.
+	Delay(Duration),
+}
+
+impl LineAction {
+	/// Reset an Arduino board with an auto-reset circuit on the DTR line.
+	///
+	/// The board resets on the falling edge of DTR and then runs the bootloader for a short time.
+	pub const ARDUINO_RESET: &'static [LineAction] = &[
+		LineAction::SetDtr(false),
+		LineAction::Delay(Duration::from_millis(250)),
+		LineAction::SetDtr(true),
+		LineAction::Delay(Duration::from_millis(50)),
+	];
+
+	/// Reset an ESP8266 or ESP32 into the serial bootloader.
+	///
+	/// This uses the auto-reset circuit found on most development boards,
+	/// where DTR drives the GPIO0 pin and RTS drives the EN pin through a pair of transistors.
+	pub const ESP32_BOOTLOADER: &'static [LineAction] = &[
+		LineAction::SetDtr(false),
+		LineAction::SetRts(true),
+		LineAction::Delay(Duration::from_millis(100)),
+		LineAction::SetDtr(true),
+		LineAction::SetRts(false),
+		LineAction::Delay(Duration::from_millis(50)),
+		LineAction::SetDtr(false),
+	];
+
+	/// Reset an ESP8266 or ESP32 and run the normal application.
+	///
+	/// This uses the same auto-reset circuit as [`Self::ESP32_BOOTLOADER`].
+	pub const ESP32_HARD_RESET: &'static [LineAction] = &[
+		LineAction::SetRts(true),
+		LineAction::Delay(Duration::from_millis(100)),
+		LineAction::SetRts(false),
+	];
+}
+
+impl SerialPort {
+	/// Set the DTR line for the given duration, and then clear it again.
+	///
+	/// If the returned future is dropped before it completes, the DTR line will remain set.
+	pub async fn pulse_dtr(&self, duration: Duration) -> std::io::Result<()> {
+		self.reset_sequence(&[
+			LineAction::SetDtr(true),
+			LineAction::Delay(duration),
+			LineAction::SetDtr(false),
+		]).await
+	}
+
+	/// Set the RTS line for the given duration, and then clear it again.
+	///
+	/// If the returned future is dropped before it completes, the RTS line will remain set.
+	pub async fn pulse_rts(&self, duration: Duration) -> std::io::Result<()> {
+		self.reset_sequence(&[
+			LineAction::SetRts(true),
+			LineAction::Delay(duration),
+			LineAction::SetRts(false),
+		]).await
+	}
+
+	/// Perform a timed sequence of modem control line changes.
+	///
+	/// This is commonly used to reset a microcontroller or to put it in bootloader mode.
+	/// Some common sequences are available as constants on [`LineAction`].
+	///
+	/// The actions are performed in order, and the function returns on the first error.
+	/// If the returned future is dropped before it completes, the lines will remain in their intermediate state.
+	///
+	/// # Example
+	/// ```no_run
+	/// # async fn example() -> std::io::Result<()> {
+	/// use serial2_tokio::{LineAction, SerialPort};
+	///
+	/// let port = SerialPort::open("/dev/ttyUSB0", 115200)?;
+	/// port.reset_sequence(LineAction::ESP32_BOOTLOADER).await?;
+	/// # Ok(())
+	/// # }
+	/// ```
+	pub async fn reset_sequence(&self, actions: &[LineAction]) -> std::io::Result<()> {
+		for action in actions {
+			match *action {
+				LineAction::SetDtr(state) => self.set_dtr(state)?,
+				LineAction::SetRts(state) => self.set_rts(state)?,
+				LineAction::Delay(duration) => tokio::time::sleep(duration).await,
+			}
+		}
+		Ok(())
+	}
+}
@@ -0,0 +1,78 @@
+use crate::SerialPort;
+
+/// Counters of the serial port driver for received data, line errors and modem status line changes.
+///
+/// Use [`SerialPort::line_counters()`] to read the counters.
+/// The counters are kept by the driver since it was loaded or since the device was connected,
+/// so compare two readings to see what happened in between.
+/// The counters wrap around when they overflow.
+///
+/// Fields that are not supported on the current platform are set to `None`.
+/// Currently, the counters are only supported on Linux, and not by all drivers.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct LineCounters {
+	/// The number of bytes received by the driver.
+	pub rx: Option<u32>,
+
+	/// The number of bytes transmitted by the driver.
+	pub tx: Option<u32>,
+
+	/// The number of framing errors, usually caused by a wrong baud rate or a noisy line.
+	pub frame_errors: Option<u32>,
+
+	/// The number of parity errors.
+	pub parity_errors: Option<u32>,
+
+	/// The number of hardware overruns, where the receive FIFO of the UART was full before the driver could empty it.
+	pub overruns: Option<u32>,
+
+	/// The number of buffer overruns, where the input buffer of the driver was full.
+	pub buffer_overruns: Option<u32>,
+
+	/// The number of received break conditions.
+	pub breaks: Option<u32>,
+
+	/// The number of changes of the CTS line.
+	pub cts_changes: Option<u32>,
+
+	/// The number of changes of the DSR line.
+	pub dsr_changes: Option<u32>,
+
+	/// The number of changes of the RI line.
+	pub ri_changes: Option<u32>,
+
+	/// The number of changes of the CD line.
+	pub cd_changes: Option<u32>,
+}
+
+impl SerialPort {
+	/// Read the counters of the serial port driver.
+	///
+	/// The error counters and modem status line counters are useful to diagnose flaky cabling, wrong settings and lost data.
+	/// For example, increasing framing errors indicate a wrong baud rate or electrical noise,
+	/// and changes of the CTS and DSR lines without a reason may indicate a loose connector.
+	///
+	/// On Linux, this uses the `TIOCGICOUNT` ioctl.
+	/// If the driver does not support it, all fields are `None`.
+	///
+	/// # Example
+	/// ```no_run
+	/// # async fn example() -> std::io::Result<()> {
+	/// use serial2_tokio::SerialPort;
+	/// use std::time::Duration;
+	///
+	/// let port = SerialPort::open("/dev/ttyS0", 115200)?;
+	/// let before = port.line_counters()?;
+	/// tokio::time::sleep(Duration::from_secs(60)).await;
+	/// let after = port.line_counters()?;
+	/// if let (Some(before), Some(after)) = (before.frame_errors, after.frame_errors) {
+	///     println!("{} framing errors in the last minute", after.wrapping_sub(before));
+	/// }
+	/// # Ok(())
+	/// # }
+	/// ```
+	pub fn line_counters(&self) -> std::io::Result<LineCounters> {
+		self.inner.line_counters()
+	}
+}
@@ -0,0 +1,145 @@
+use std::task::{ready, Poll};
+use std::time::Duration;
+
+use tokio::time::{Interval, MissedTickBehavior};
+
+use crate::SerialPort;
+
+/// The state of all modem control and status lines of a serial port.
+///
+/// Read with [`SerialPort::read_lines()`], and change the RTS and DTR lines with [`SerialPort::set_lines()`].
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct LineState {
+	/// The state of the Ready To Send line.
+	pub rts: bool,
+
+	/// The state of the Data Terminal Ready line.
+	pub dtr: bool,
+
+	/// The state of the Clear To Send line.
+	pub cts: bool,
+
+	/// The state of the Data Set Ready line.
+	pub dsr: bool,
+
+	/// The state of the Ring Indicator line.
+	pub ri: bool,
+
+	/// The state of the Carrier Detect line.
+	pub cd: bool,
+}
+
+/// Polls the modem control and status lines of a serial port and reports changes.
+///
+/// Created by [`SerialPort::poll_lines()`].
+#[derive(Debug)]
+pub struct LinePoller<'a> {
+	port: &'a SerialPort,
+	interval: Interval,
+	last: Option<LineState>,
+}
+
+impl SerialPort {
+	/// Read the state of all modem control and status lines at once.
+	///
+	/// On Unix platforms, this uses a single `TIOCMGET` ioctl, so the returned state is consistent.
+	/// On Windows, the output lines and the status lines are read with two consecutive calls.
+	pub fn read_lines(&self) -> std::io::Result<LineState> {
+		self.inner.read_lines()
+	}
+
+	/// Set the RTS and DTR lines at the same time.
+	///
+	/// The state of the other lines in `state` is ignored,
+	/// so you can modify the result of [`Self::read_lines()`] and pass it to this function.
+	///
+	/// On Unix platforms, this uses a single `TIOCMSET` ioctl, so both lines change at the same moment.
+	/// This is needed for reset circuits that must never see an intermediate state of the two lines.
+	/// On Windows, the lines are changed with two consecutive calls, RTS first.
+	///
+	/// See [`Self::set_rts()`] for the interaction with hardware flow control.
+	///
+	/// # Example
+	/// ```no_run
+	/// # fn example() -> std::io::Result<()> {
+	/// use serial2_tokio::SerialPort;
+	///
+	/// let port = SerialPort::open("/dev/ttyUSB0", 115200)?;
+	/// let mut lines = port.read_lines()?;
+	/// lines.rts = false;
+	/// lines.dtr = false;
+	/// port.set_lines(&lines)?;
+	/// # Ok(())
+	/// # }
+	/// ```
+	pub fn set_lines(&self, state: &LineState) -> std::io::Result<()> {
+		self.inner.set_lines(state.rts, state.dtr)
+	}
+
+	/// Poll the modem control and status lines at a fixed interval, and report each change.
+	///
+	/// This works on all platforms, including those that can not wait for line changes in the kernel, like macOS and Windows.
+	/// Changes that are shorter than the interval can be missed.
+	///
+	/// The first call to [`LinePoller::next()`] returns the current state immediately.
+	/// Later calls wait until the state is different from the last returned state.
+	/// This includes changes of the RTS and DTR lines, also when they are made through this serial port.
+	///
+	/// # Panics
+	/// This function panics if `interval` is zero.
+	///
+	/// # Example
+	/// ```no_run
+	/// # async fn example() -> std::io::Result<()> {
+	/// use serial2_tokio::SerialPort;
+	/// use std::time::Duration;
+	///
+	/// let port = SerialPort::open("/dev/ttyUSB0", 115200)?;
+	/// let mut lines = port.poll_lines(Duration::from_millis(20));
+	/// loop {
+	///     let state = lines.next().await?;
+	///     println!("CTS={} DSR={}", state.cts, state.dsr);
+	/// }
+	/// # }
+	/// ```
+	pub fn poll_lines(&self, interval: Duration) -> LinePoller<'_> {
+		assert!(!interval.is_zero(), "the poll interval can not be zero");
+		let mut interval = tokio::time::interval(interval);
+		interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+		LinePoller {
+			port: self,
+			interval,
+			last: None,
+		}
+	}
+}
+
+impl LinePoller<'_> {
+	/// Wait for the next change of the modem control and status lines.
+	///
+	/// This function is cancel safe: if the future is dropped before it completes, no change is lost,
+	/// as long as the lines do not change back before the next poll.
+	pub async fn next(&mut self) -> std::io::Result<LineState> {
+		std::future::poll_fn(|cx| self.poll_next(cx)).await
+	}
+
+	/// Poll for the next change of the modem control and status lines.
+	///
+	/// This can be used to implement a `Stream` on top of the poller, for example with `futures::stream::poll_fn()`.
+	pub fn poll_next(&mut self, cx: &mut std::task::Context<'_>) -> Poll<std::io::Result<LineState>> {
+		loop {
+			ready!(self.interval.poll_tick(cx));
+			let state = self.port.read_lines()?;
+			if self.last != Some(state) {
+				self.last = Some(state);
+				return Poll::Ready(Ok(state));
+			}
+		}
+	}
+
+	/// Get the last returned state of the lines, if any.
+	pub fn last(&self) -> Option<LineState> {
+		self.last
+	}
+}
@@ -0,0 +1,80 @@
+use std::time::Duration;
+
+use crate::{SerialPort, XonXoffConfig};
+
+/// The data sent by [`SerialPort::self_test()`].
+///
+/// Contains alternating bit patterns and every byte value except the default XON and XOFF characters,
+/// which could be consumed by software flow control.
+fn test_pattern() -> Vec<u8> {
+	let mut pattern = vec![0x55, 0xAA, 0x00, 0xFF];
+	pattern.extend((0..=255).filter(|&byte| byte != XonXoffConfig::DEFAULT_XON_CHAR && byte != XonXoffConfig::DEFAULT_XOFF_CHAR));
+	pattern
+}
+
+impl SerialPort {
+	/// Enable or disable the internal loopback mode of the serial port.
+	///
+	/// In loopback mode, transmitted data is routed back to the receiver inside the UART,
+	/// without going to the external pins.
+	/// This allows testing the serial port without any external wiring.
+	///
+	/// Loopback mode is only supported on Linux and Android, and only by drivers that implement it (such as the 8250/16550 UART driver).
+	/// Returns an error of kind [`std::io::ErrorKind::Unsupported`] if it is not supported.
+	pub fn set_loopback(&self, enable: bool) -> std::io::Result<()> {
+		self.inner.set_loopback(enable)
+	}
+
+	/// Test the serial port by sending a data pattern in loopback mode and checking that it is received.
+	///
+	/// This enables loopback mode with [`Self::set_loopback()`], discards the input and output buffers,
+	/// sends the test pattern and waits for it to be received back.
+	/// Loopback mode is disabled again before this function returns.
+	///
+	/// Returns an error of kind [`std::io::ErrorKind::TimedOut`] if the data is not received within `timeout`,
+	/// or an error of kind [`std::io::ErrorKind::InvalidData`] if different data is received.
+	///
+	/// # Example
+	/// ```no_run
+	/// # async fn example() -> std::io::Result<()> {
+	/// use serial2_tokio::SerialPort;
+	/// use std::time::Duration;
+	///
+	/// let port = SerialPort::open("/dev/ttyS0", 115200)?;
+	/// port.self_test(Duration::from_secs(1)).await?;
+	/// # Ok(())
+	/// # }
+	/// ```
+	pub async fn self_test(&self, timeout: Duration) -> std::io::Result<()> {
+		self.set_loopback(true)?;
+		let result = self.loopback_test(timeout).await;
+		let disabled = self.set_loopback(false);
+		result?;
+		disabled
+	}
+
+	async fn loopback_test(&self, timeout: Duration) -> std::io::Result<()> {
+		let pattern = test_pattern();
+		self.discard_buffers()?;
+
+		let test = async {
+			self.write_all(&pattern).await?;
+			let mut received = vec![0; pattern.len()];
+			let mut position = 0;
+			while position < received.len() {
+				match self.read(&mut received[position..]).await? {
+					0 => return Err(std::io::ErrorKind::UnexpectedEof.into()),
+					read => position += read,
+				}
+			}
+			Ok::<_, std::io::Error>(received)
+		};
+
+		let received = tokio::time::timeout(timeout, test).await
+			.map_err(|_| std::io::Error::new(std::io::ErrorKind::TimedOut, "timeout while waiting for loopback data"))??;
+		if received != pattern {
+			return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "loopback data does not match the transmitted data"));
+		}
+		Ok(())
+	}
+}
@@ -0,0 +1,440 @@
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use crate::SerialPort;
+
+use super::*;
+
+/// The default timeout for a response.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The default number of retries for a request.
+const DEFAULT_RETRIES: u32 = 2;
+
+/// A Modbus RTU master.
+///
+/// See the [module documentation](super) for more information.
+#[derive(Debug)]
+pub struct RtuMaster {
+	port: SerialPort,
+	timeout: Duration,
+	retries: u32,
+	frame_gap: Duration,
+	/// The time at which the last frame was sent or received.
+	last_frame: Option<Instant>,
+}
+
+impl RtuMaster {
+	/// Create a Modbus RTU master for a serial port.
+	///
+	/// The silent interval between frames is computed from the current settings of the serial port.
+	/// If you change the settings of the serial port later, create a new master with [`Self::into_inner()`] and [`Self::new()`].
+	pub fn new(port: SerialPort) -> std::io::Result<Self> {
+		let frame_gap = frame_gap(&port.get_configuration()?)?;
+		Ok(Self {
+			port,
+			timeout: DEFAULT_TIMEOUT,
+			retries: DEFAULT_RETRIES,
+			frame_gap,
+			last_frame: None,
+		})
+	}
+
+	/// Set the timeout for the response to a single request.
+	///
+	/// The default timeout is 1 second.
+	pub fn set_timeout(&mut self, timeout: Duration) {
+		self.timeout = timeout;
+	}
+
+	/// Get the timeout for the response to a single request.
+	pub fn get_timeout(&self) -> Duration {
+		self.timeout
+	}
+
+	/// Set the number of times a request is retried if there is no valid response.
+	///
+	/// A request is retried if the response times out or if the response is corrupted.
+	/// Exception responses are not retried.
+	///
+	/// The default is 2 retries.
+	pub fn set_retries(&mut self, retries: u32) {
+		self.retries = retries;
+	}
+
+	/// Get the number of times a request is retried if there is no valid response.
+	pub fn get_retries(&self) -> u32 {
+		self.retries
+	}
+
+	/// Get the silent interval between frames.
+	pub fn frame_gap(&self) -> Duration {
+		self.frame_gap
+	}
+
+	/// Get a reference to the serial port.
+	pub fn get_ref(&self) -> &SerialPort {
+		&self.port
+	}
+
+	/// Consume the master and return the serial port.
+	pub fn into_inner(self) -> SerialPort {
+		self.port
+	}
+
+	/// Read coils (function code 0x01).
+	pub async fn read_coils(&mut self, slave: u8, address: u16, count: u16) -> std::io::Result<Vec<bool>> {
+		self.read_bits(READ_COILS, slave, address, count).await
+	}
+
+	/// Read discrete inputs (function code 0x02).
+	pub async fn read_discrete_inputs(&mut self, slave: u8, address: u16, count: u16) -> std::io::Result<Vec<bool>> {
+		self.read_bits(READ_DISCRETE_INPUTS, slave, address, count).await
+	}
+
+	/// Read holding registers (function code 0x03).
+	pub async fn read_holding_registers(&mut self, slave: u8, address: u16, count: u16) -> std::io::Result<Vec<u16>> {
+		self.read_registers(READ_HOLDING_REGISTERS, slave, address, count).await
+	}
+
+	/// Read input registers (function code 0x04).
+	pub async fn read_input_registers(&mut self, slave: u8, address: u16, count: u16) -> std::io::Result<Vec<u16>> {
+		self.read_registers(READ_INPUT_REGISTERS, slave, address, count).await
+	}
+
+	/// Write a single coil (function code 0x05).
+	///
+	/// Use [`BROADCAST`] as slave address to write to all slaves without waiting for a response.
+	pub async fn write_single_coil(&mut self, slave: u8, address: u16, value: bool) -> std::io::Result<()> {
+		let value: u16 = if value { 0xFF00 } else { 0x0000 };
+		self.write_single(WRITE_SINGLE_COIL, slave, address, value).await
+	}
+
+	/// Write a single holding register (function code 0x06).
+	///
+	/// Use [`BROADCAST`] as slave address to write to all slaves without waiting for a response.
+	pub async fn write_single_register(&mut self, slave: u8, address: u16, value: u16) -> std::io::Result<()> {
+		self.write_single(WRITE_SINGLE_REGISTER, slave, address, value).await
+	}
+
+	/// Write multiple coils (function code 0x0F).
+	///
+	/// Use [`BROADCAST`] as slave address to write to all slaves without waiting for a response.
+	pub async fn write_multiple_coils(&mut self, slave: u8, address: u16, values: &[bool]) -> std::io::Result<()> {
+		let count = check_count(values.len(), MAX_WRITE_BITS)?;
+		let data = pack_bits(values);
+		let mut request = vec![WRITE_MULTIPLE_COILS];
+		request.extend_from_slice(&address.to_be_bytes());
+		request.extend_from_slice(&count.to_be_bytes());
+		request.push(data.len() as u8);
+		request.extend_from_slice(&data);
+		self.write_multiple(slave, &request).await
+	}
+
+	/// Write multiple holding registers (function code 0x10).
+	///
+	/// Use [`BROADCAST`] as slave address to write to all slaves without waiting for a response.
+	pub async fn write_multiple_registers(&mut self, slave: u8, address: u16, values: &[u16]) -> std::io::Result<()> {
+		let count = check_count(values.len(), MAX_WRITE_REGISTERS)?;
+		let mut request = vec![WRITE_MULTIPLE_REGISTERS];
+		request.extend_from_slice(&address.to_be_bytes());
+		request.extend_from_slice(&count.to_be_bytes());
+		request.push((values.len() * 2) as u8);
+		for value in values {
+			request.extend_from_slice(&value.to_be_bytes());
+		}
+		self.write_multiple(slave, &request).await
+	}
+
+	async fn read_bits(&mut self, function: u8, slave: u8, address: u16, count: u16) -> std::io::Result<Vec<bool>> {
+		check_count(count.into(), MAX_READ_BITS)?;
+		let response = self.read(function, slave, address, count).await?;
+		let data = &response[2..];
+		if data.len() != usize::from(count).div_ceil(8) {
+			return Err(invalid_response("wrong number of bytes"));
+		}
+		Ok(unpack_bits(data, count.into()))
+	}
+
+	async fn read_registers(&mut self, function: u8, slave: u8, address: u16, count: u16) -> std::io::Result<Vec<u16>> {
+		check_count(count.into(), MAX_READ_REGISTERS)?;
+		let response = self.read(function, slave, address, count).await?;
+		let data = &response[2..];
+		if data.len() != usize::from(count) * 2 {
+			return Err(invalid_response("wrong number of bytes"));
+		}
+		Ok(data.chunks(2).map(|chunk| u16::from_be_bytes([chunk[0], chunk[1]])).collect())
+	}
+
+	/// Send a read request and return the response, starting with the function code.
+	async fn read(&mut self, function: u8, slave: u8, address: u16, count: u16) -> std::io::Result<Vec<u8>> {
+		if slave == BROADCAST {
+			return Err(invalid_request("read requests can not be broadcast"));
+		}
+		let mut request = vec![function];
+		request.extend_from_slice(&address.to_be_bytes());
+		request.extend_from_slice(&count.to_be_bytes());
+		self.request(slave, &request).await
+	}
+
+	async fn write_single(&mut self, function: u8, slave: u8, address: u16, value: u16) -> std::io::Result<()> {
+		let mut request = vec![function];
+		request.extend_from_slice(&address.to_be_bytes());
+		request.extend_from_slice(&value.to_be_bytes());
+		let response = self.request(slave, &request).await?;
+		if slave != BROADCAST && response != request {
+			return Err(invalid_response("response does not match request"));
+		}
+		Ok(())
+	}
+
+	async fn write_multiple(&mut self, slave: u8, request: &[u8]) -> std::io::Result<()> {
+		let response = self.request(slave, request).await?;
+		// The response repeats the function code, the address and the number of values.
+		if slave != BROADCAST && response != request[..5] {
+			return Err(invalid_response("response does not match request"));
+		}
+		Ok(())
+	}
+
+	/// Send a request and return the response, starting with the function code.
+	///
+	/// Broadcast requests return an empty response.
+	async fn request(&mut self, slave: u8, request: &[u8]) -> std::io::Result<Vec<u8>> {
+		let mut attempt = 0;
+		loop {
+			match self.exchange(slave, request).await {
+				Err(e) if attempt < self.retries && is_retryable(&e) => attempt += 1,
+				result => return result,
+			}
+		}
+	}
+
+	/// Send a request once and read the response.
+	async fn exchange(&mut self, slave: u8, request: &[u8]) -> std::io::Result<Vec<u8>> {
+		let mut frame = Vec::with_capacity(request.len() + 3);
+		frame.push(slave);
+		frame.extend_from_slice(request);
+		append_crc(&mut frame);
+
+		if let Some(last_frame) = self.last_frame {
+			tokio::time::sleep_until(last_frame + self.frame_gap).await;
+		}
+		// Discard late responses to earlier requests and noise on the bus.
+		self.port.discard_input_buffer()?;
+		self.port.write_all(&frame).await?;
+		self.port.drain().await?;
+		self.last_frame = Some(Instant::now());
+		if slave == BROADCAST {
+			return Ok(Vec::new());
+		}
+
+		let response = tokio::time::timeout(self.timeout, self.read_response(slave, request[0])).await
+			.map_err(|_| std::io::Error::new(std::io::ErrorKind::TimedOut, "timeout while waiting for modbus response"));
+		self.last_frame = Some(Instant::now());
+		response?
+	}
+
+	/// Read a response frame and return it without slave address and CRC.
+	async fn read_response(&self, slave: u8, function: u8) -> std::io::Result<Vec<u8>> {
+		let mut frame = Vec::new();
+		self.read_more(&mut frame, 2).await?;
+		let remaining = if frame[1] == function | EXCEPTION_BIT {
+			3
+		} else if frame[1] == function {
+			match function {
+				READ_COILS | READ_DISCRETE_INPUTS | READ_HOLDING_REGISTERS | READ_INPUT_REGISTERS => {
+					self.read_more(&mut frame, 1).await?;
+					usize::from(frame[2]) + 2
+				},
+				_ => 6,
+			}
+		} else {
+			return Err(invalid_response(&format!("expected function code 0x{function:02X}, got 0x{:02X}", frame[1])));
+		};
+		self.read_more(&mut frame, remaining).await?;
+
+		if !check_crc(&frame) {
+			return Err(invalid_response("CRC mismatch"));
+		}
+		if frame[0] != slave {
+			return Err(invalid_response(&format!("expected response from slave {slave}, got {}", frame[0])));
+		}
+		if frame[1] & EXCEPTION_BIT != 0 {
+			let exception = Exception {
+				function,
+				code: frame[2].into(),
+			};
+			return Err(std::io::Error::other(exception));
+		}
+		frame.truncate(frame.len() - 2);
+		frame.remove(0);
+		Ok(frame)
+	}
+
+	/// Read exactly `count` more bytes into the frame.
+	async fn read_more(&self, frame: &mut Vec<u8>, count: usize) -> std::io::Result<()> {
+		let start = frame.len();
+		frame.resize(start + count, 0);
+		let mut read = start;
+		while read < frame.len() {
+			match self.port.read(&mut frame[read..]).await? {
+				0 => return Err(std::io::ErrorKind::UnexpectedEof.into()),
+				n => read += n,
+			}
+		}
+		Ok(())
+	}
+}
+
+/// Check the number of values in a request.
+fn check_count(count: usize, max: u16) -> std::io::Result<u16> {
+	match u16::try_from(count) {
+		Ok(count) if (1..=max).contains(&count) => Ok(count),
+		_ => Err(invalid_request(&format!("the number of values must be between 1 and {max}"))),
+	}
+}
+
+/// Check if a request should be retried after an error.
+fn is_retryable(error: &std::io::Error) -> bool {
+	matches!(error.kind(), std::io::ErrorKind::TimedOut | std::io::ErrorKind::InvalidData)
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn check_count_enforces_limits() {
+		assert_eq!(check_count(1, MAX_READ_REGISTERS).unwrap(), 1);
+		assert_eq!(check_count(125, MAX_READ_REGISTERS).unwrap(), 125);
+		assert_eq!(check_count(0, MAX_READ_REGISTERS).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+		assert_eq!(check_count(126, MAX_READ_REGISTERS).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+		assert_eq!(check_count(0x1_0001, MAX_READ_BITS).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+	}
+
+	#[cfg(all(unix, feature = "unix"))]
+	mod pty {
+		use super::*;
+		use crate::test_util::{expect_then_reply, pair_with_timeout};
+
+		/// Create a master on one side of a pseudo-terminal pair, with the other side acting as slave.
+		fn master() -> (RtuMaster, SerialPort) {
+			let (a, b, timeout) = pair_with_timeout();
+			let mut master = RtuMaster::new(a).unwrap();
+			master.set_timeout(timeout);
+			master.set_retries(0);
+			(master, b)
+		}
+
+		#[tokio::test]
+		async fn read_holding_registers_specification_example() {
+			let (mut master, slave) = master();
+			let (registers, ()) = tokio::join!(
+				master.read_holding_registers(0x11, 0x006B, 3),
+				expect_then_reply(
+					&slave,
+					&[0x11, 0x03, 0x00, 0x6B, 0x00, 0x03, 0x76, 0x87],
+					&[0x11, 0x03, 0x06, 0x02, 0x2B, 0x00, 0x00, 0x00, 0x64, 0xC8, 0xBA],
+				),
+			);
+			assert_eq!(registers.unwrap(), [0x022B, 0x0000, 0x0064]);
+		}
+
+		#[tokio::test]
+		async fn read_coils_specification_example() {
+			let (mut master, slave) = master();
+			let (coils, ()) = tokio::join!(
+				master.read_coils(0x11, 0x0013, 37),
+				expect_then_reply(
+					&slave,
+					&[0x11, 0x01, 0x00, 0x13, 0x00, 0x25, 0x0E, 0x84],
+					&[0x11, 0x01, 0x05, 0xCD, 0x6B, 0xB2, 0x0E, 0x1B, 0x45, 0xE6],
+				),
+			);
+			let coils = coils.unwrap();
+			assert_eq!(coils.len(), 37);
+			assert_eq!(pack_bits(&coils), [0xCD, 0x6B, 0xB2, 0x0E, 0x1B]);
+		}
+
+		#[tokio::test]
+		async fn write_single_register_checks_echo() {
+			let (mut master, slave) = master();
+			let (result, ()) = tokio::join!(
+				master.write_single_register(0x11, 0x0001, 0x0003),
+				expect_then_reply(
+					&slave,
+					&[0x11, 0x06, 0x00, 0x01, 0x00, 0x03, 0x9A, 0x9B],
+					&[0x11, 0x06, 0x00, 0x01, 0x00, 0x03, 0x9A, 0x9B],
+				),
+			);
+			result.unwrap();
+		}
+
+		#[tokio::test]
+		async fn write_multiple_coils_specification_example() {
+			let (mut master, slave) = master();
+			let coils = unpack_bits(&[0xCD, 0x01], 10);
+			let (result, ()) = tokio::join!(
+				master.write_multiple_coils(0x11, 0x0013, &coils),
+				expect_then_reply(
+					&slave,
+					&[0x11, 0x0F, 0x00, 0x13, 0x00, 0x0A, 0x02, 0xCD, 0x01, 0xBF, 0x0B],
+					&[0x11, 0x0F, 0x00, 0x13, 0x00, 0x0A, 0x26, 0x99],
+				),
+			);
+			result.unwrap();
+		}
+
+		#[tokio::test]
+		async fn exception_response_is_reported() {
+			let (mut master, slave) = master();
+			let (result, ()) = tokio::join!(
+				master.read_holding_registers(0x11, 0x006B, 3),
+				expect_then_reply(
+					&slave,
+					&[0x11, 0x03, 0x00, 0x6B, 0x00, 0x03, 0x76, 0x87],
+					&[0x11, 0x83, 0x02, 0xC1, 0x34],
+				),
+			);
+			let error = result.unwrap_err();
+			let exception = error.get_ref().and_then(|e| e.downcast_ref::<Exception>()).unwrap();
+			assert_eq!(exception.function, READ_HOLDING_REGISTERS);
+			assert_eq!(exception.code, ExceptionCode::IllegalDataAddress);
+		}
+
+		#[tokio::test]
+		async fn corrupted_response_is_rejected() {
+			let (mut master, slave) = master();
+			let (result, ()) = tokio::join!(
+				master.read_holding_registers(0x11, 0x006B, 3),
+				expect_then_reply(
+					&slave,
+					&[0x11, 0x03, 0x00, 0x6B, 0x00, 0x03, 0x76, 0x87],
+					&[0x11, 0x03, 0x06, 0x02, 0x2B, 0x00, 0x00, 0x00, 0x65, 0xC8, 0xBA],
+				),
+			);
+			assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+		}
+
+		#[tokio::test]
+		async fn missing_response_times_out() {
+			let (mut master, _slave) = master();
+			let result = master.read_holding_registers(0x11, 0x006B, 3).await;
+			assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::TimedOut);
+		}
+
+		#[tokio::test]
+		async fn broadcast_does_not_wait_for_response() {
+			let (mut master, slave) = master();
+			let (result, ()) = tokio::join!(
+				master.write_single_register(BROADCAST, 0x0001, 0x0003),
+				expect_then_reply(&slave, &[0x00, 0x06, 0x00, 0x01, 0x00, 0x03, 0x99, 0xDA], &[]),
+			);
+			result.unwrap();
+			let error = master.read_holding_registers(BROADCAST, 0x0001, 1).await.unwrap_err();
+			assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+		}
+	}
+}
@@ -0,0 +1,283 @@
+//! Modbus RTU over serial ports.
+//!
+//! Modbus RTU is a simple request/response protocol used by many industrial devices, often over an RS-485 bus.
+//! The [`RtuMaster`] sends requests to the devices on the bus (the slaves) and waits for their responses.
+//! It computes the CRC of each frame, turns exception responses into errors,
+//! and retries requests that time out or that receive a corrupted response.
+//!
+//! The [`RtuSlave`] implements the other side: it answers the requests from a master using a [`SlaveHandler`],
+//! such as the in-memory [`RegisterMap`].
+//! This is useful to build device simulators and gateways.
+//!
+//! Modbus RTU separates frames with a silent interval of 3.5 character times.
+//! The [`RtuMaster`] and [`RtuSlave`] derive this interval from the settings of the serial port,
+//! so it is important to configure the serial port before creating them.
+//! Following the specification, a fixed interval of 1.75 milliseconds is used for baud rates above 19200.
+//!
+//! This module is only available when the `modbus-rtu` feature is enabled.
+//!
+//! # Example
+//! ```no_run
+//! # async fn example() -> std::io::Result<()> {
+//! use serial2_tokio::{Preset, SerialPort};
+//! use serial2_tokio::modbus::RtuMaster;
+//!
+//! let port = SerialPort::open("/dev/ttyUSB0", Preset::ModbusRtu(19200))?;
+//! let mut modbus = RtuMaster::new(port)?;
+//! let registers = modbus.read_holding_registers(1, 0x0000, 4).await?;
+//! println!("registers: {registers:?}");
+//! modbus.write_single_register(1, 0x0010, 1234).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::time::Duration;
+
+use crate::checksum::crc16_modbus;
+
+mod master;
+mod slave;
+
+pub use master::RtuMaster;
+pub use slave::{RegisterMap, RtuSlave, SlaveHandler};
+
+/// The slave address used to send a request to all slaves.
+pub const BROADCAST: u8 = 0;
+
+const READ_COILS: u8 = 0x01;
+const READ_DISCRETE_INPUTS: u8 = 0x02;
+const READ_HOLDING_REGISTERS: u8 = 0x03;
+const READ_INPUT_REGISTERS: u8 = 0x04;
+const WRITE_SINGLE_COIL: u8 = 0x05;
+const WRITE_SINGLE_REGISTER: u8 = 0x06;
+const WRITE_MULTIPLE_COILS: u8 = 0x0F;
+const WRITE_MULTIPLE_REGISTERS: u8 = 0x10;
+
+/// The bit that is set in the function code of an exception response.
+const EXCEPTION_BIT: u8 = 0x80;
+
+/// The maximum number of coils or discrete inputs that can be read with a single request.
+const MAX_READ_BITS: u16 = 2000;
+
+/// The maximum number of registers that can be read with a single request.
+const MAX_READ_REGISTERS: u16 = 125;
+
+/// The maximum number of coils that can be written with a single request.
+const MAX_WRITE_BITS: u16 = 1968;
+
+/// The maximum number of registers that can be written with a single request.
+const MAX_WRITE_REGISTERS: u16 = 123;
+
+/// The silent interval between frames for baud rates above 19200.
+const FIXED_FRAME_GAP: Duration = Duration::from_micros(1750);
+
+/// The exception code of a Modbus exception response.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum ExceptionCode {
+	/// The function code is not supported by the slave.
+	IllegalFunction,
+
+	/// The address range is not valid for the slave.
+	IllegalDataAddress,
+
+	/// A value in the request is not valid for the slave.
+	IllegalDataValue,
+
+	/// The slave failed to perform the requested action.
+	ServerDeviceFailure,
+
+	/// The slave accepted the request, but needs a long time to process it.
+	Acknowledge,
+
+	/// The slave is busy processing a long-duration command.
+	ServerDeviceBusy,
+
+	/// The slave detected a parity error in its memory.
+	MemoryParityError,
+
+	/// A gateway could not allocate a path to the target device.
+	GatewayPathUnavailable,
+
+	/// A gateway did not receive a response from the target device.
+	GatewayTargetFailedToRespond,
+
+	/// An exception code that is not defined by the specification.
+	Other(u8),
+}
+
+impl From<u8> for ExceptionCode {
+	fn from(code: u8) -> Self {
+		match code {
+			0x01 => Self::IllegalFunction,
+			0x02 => Self::IllegalDataAddress,
+			0x03 => Self::IllegalDataValue,
+			0x04 => Self::ServerDeviceFailure,
+			0x05 => Self::Acknowledge,
+			0x06 => Self::ServerDeviceBusy,
+			0x08 => Self::MemoryParityError,
+			0x0A => Self::GatewayPathUnavailable,
+			0x0B => Self::GatewayTargetFailedToRespond,
+			code => Self::Other(code),
+		}
+	}
+}
+
+impl From<ExceptionCode> for u8 {
+	fn from(code: ExceptionCode) -> Self {
+		match code {
+			ExceptionCode::IllegalFunction => 0x01,
+			ExceptionCode::IllegalDataAddress => 0x02,
+			ExceptionCode::IllegalDataValue => 0x03,
+			ExceptionCode::ServerDeviceFailure => 0x04,
+			ExceptionCode::Acknowledge => 0x05,
+			ExceptionCode::ServerDeviceBusy => 0x06,
+			ExceptionCode::MemoryParityError => 0x08,
+			ExceptionCode::GatewayPathUnavailable => 0x0A,
+			ExceptionCode::GatewayTargetFailedToRespond => 0x0B,
+			ExceptionCode::Other(code) => code,
+		}
+	}
+}
+
+impl std::fmt::Display for ExceptionCode {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::IllegalFunction => write!(f, "illegal function"),
+			Self::IllegalDataAddress => write!(f, "illegal data address"),
+			Self::IllegalDataValue => write!(f, "illegal data value"),
+			Self::ServerDeviceFailure => write!(f, "server device failure"),
+			Self::Acknowledge => write!(f, "acknowledge"),
+			Self::ServerDeviceBusy => write!(f, "server device busy"),
+			Self::MemoryParityError => write!(f, "memory parity error"),
+			Self::GatewayPathUnavailable => write!(f, "gateway path unavailable"),
+			Self::GatewayTargetFailedToRespond => write!(f, "gateway target device failed to respond"),
+			Self::Other(code) => write!(f, "exception code 0x{code:02X}"),
+		}
+	}
+}
+
+/// An exception response from a Modbus slave.
+///
+/// The functions of the [`RtuMaster`] report exceptions as an [`std::io::Error`] of kind [`std::io::ErrorKind::Other`] that wraps this type.
+///
+/// # Example
+/// ```no_run
+/// # async fn example(modbus: &mut serial2_tokio::modbus::RtuMaster) -> std::io::Result<()> {
+/// use serial2_tokio::modbus::{Exception, ExceptionCode};
+///
+/// match modbus.read_holding_registers(1, 0x1000, 1).await {
+///     Ok(registers) => println!("{registers:?}"),
+///     Err(e) => match e.get_ref().and_then(|e| e.downcast_ref::<Exception>()) {
+///         Some(exception) if exception.code == ExceptionCode::IllegalDataAddress => println!("register not supported"),
+///         _ => return Err(e),
+///     },
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Exception {
+	/// The function code of the request that caused the exception.
+	pub function: u8,
+
+	/// The exception code.
+	pub code: ExceptionCode,
+}
+
+impl std::fmt::Display for Exception {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "modbus exception for function 0x{:02X}: {}", self.function, self.code)
+	}
+}
+
+impl std::error::Error for Exception {}
+
+/// Get the silent interval between frames for the settings of a serial port.
+fn frame_gap(settings: &crate::Settings) -> std::io::Result<Duration> {
+	if settings.get_baud_rate()? > 19200 {
+		Ok(FIXED_FRAME_GAP)
+	} else {
+		Ok(crate::pacing::char_time(settings)? * 7 / 2)
+	}
+}
+
+/// Append the CRC to a frame.
+fn append_crc(frame: &mut Vec<u8>) {
+	let crc = crc16_modbus(frame);
+	frame.extend_from_slice(&crc.to_le_bytes());
+}
+
+/// Check the CRC at the end of a frame.
+fn check_crc(frame: &[u8]) -> bool {
+	match frame.len().checked_sub(2) {
+		Some(len) => crc16_modbus(&frame[..len]).to_le_bytes() == frame[len..],
+		None => false,
+	}
+}
+
+/// Pack bits into bytes, with the first bit in the least significant bit of the first byte.
+fn pack_bits(bits: &[bool]) -> Vec<u8> {
+	let mut bytes = vec![0; bits.len().div_ceil(8)];
+	for (i, &bit) in bits.iter().enumerate() {
+		if bit {
+			bytes[i / 8] |= 1 << (i % 8);
+		}
+	}
+	bytes
+}
+
+/// Unpack bits from bytes, with the first bit in the least significant bit of the first byte.
+fn unpack_bits(bytes: &[u8], count: usize) -> Vec<bool> {
+	(0..count).map(|i| bytes[i / 8] & (1 << (i % 8)) != 0).collect()
+}
+
+/// Create an error for an invalid request.
+fn invalid_request(message: &str) -> std::io::Error {
+	std::io::Error::new(std::io::ErrorKind::InvalidInput, message)
+}
+
+/// Create an error for an invalid response.
+fn invalid_response(message: &str) -> std::io::Error {
+	std::io::Error::new(std::io::ErrorKind::InvalidData, format!("invalid modbus response: {message}"))
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn crc_matches_specification_example() {
+		// Read holding registers 0x006B..0x006D from slave 0x11, from the Modbus over serial line specification.
+		let mut frame = vec![0x11, 0x03, 0x00, 0x6B, 0x00, 0x03];
+		append_crc(&mut frame);
+		assert_eq!(frame, [0x11, 0x03, 0x00, 0x6B, 0x00, 0x03, 0x76, 0x87]);
+		assert!(check_crc(&frame));
+	}
+
+	#[test]
+	fn check_crc_rejects_corrupted_and_short_frames() {
+		assert!(!check_crc(&[0x11, 0x03, 0x00, 0x6B, 0x00, 0x03, 0x87, 0x76]));
+		assert!(!check_crc(&[0x11, 0x03, 0x00, 0x6C, 0x00, 0x03, 0x76, 0x87]));
+		assert!(!check_crc(&[0x11]));
+		assert!(!check_crc(&[]));
+	}
+
+	#[test]
+	fn pack_bits_starts_at_least_significant_bit() {
+		// Coils 20..=29 from the read coils example of the specification.
+		let bits = [true, false, true, true, false, false, true, true, true, false];
+		assert_eq!(pack_bits(&bits), [0xCD, 0x01]);
+		assert_eq!(unpack_bits(&[0xCD, 0x01], bits.len()), bits);
+		assert_eq!(pack_bits(&[]), [0u8; 0]);
+	}
+
+	#[test]
+	fn exception_code_round_trips() {
+		for code in 0..=255u8 {
+			assert_eq!(u8::from(ExceptionCode::from(code)), code);
+		}
+		assert_eq!(ExceptionCode::from(0x02), ExceptionCode::IllegalDataAddress);
+		assert_eq!(ExceptionCode::from(0x07), ExceptionCode::Other(0x07));
+	}
+}
@@ -0,0 +1,476 @@
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use crate::SerialPort;
+
+use super::*;
+
+/// Handle the requests received by an [`RtuSlave`].
+///
+/// Each function corresponds to one or more Modbus function codes.
+/// The default implementations return [`ExceptionCode::IllegalFunction`],
+/// so you only need to implement the functions that your device supports.
+///
+/// The slave checks the request before calling the handler:
+/// the number of values is always within the limits of the specification.
+/// The handler should return [`ExceptionCode::IllegalDataAddress`] for addresses it does not support.
+pub trait SlaveHandler {
+	/// Read `count` coils starting at `address` (function code 0x01).
+	fn read_coils(&mut self, address: u16, count: u16) -> Result<Vec<bool>, ExceptionCode> {
+		let _ = (address, count);
+		Err(ExceptionCode::IllegalFunction)
+	}
+
+	/// Read `count` discrete inputs starting at `address` (function code 0x02).
+	fn read_discrete_inputs(&mut self, address: u16, count: u16) -> Result<Vec<bool>, ExceptionCode> {
+		let _ = (address, count);
+		Err(ExceptionCode::IllegalFunction)
+	}
+
+	/// Read `count` holding registers starting at `address` (function code 0x03).
+	fn read_holding_registers(&mut self, address: u16, count: u16) -> Result<Vec<u16>, ExceptionCode> {
+		let _ = (address, count);
+		Err(ExceptionCode::IllegalFunction)
+	}
+
+	/// Read `count` input registers starting at `address` (function code 0x04).
+	fn read_input_registers(&mut self, address: u16, count: u16) -> Result<Vec<u16>, ExceptionCode> {
+		let _ = (address, count);
+		Err(ExceptionCode::IllegalFunction)
+	}
+
+	/// Write coils starting at `address` (function codes 0x05 and 0x0F).
+	fn write_coils(&mut self, address: u16, values: &[bool]) -> Result<(), ExceptionCode> {
+		let _ = (address, values);
+		Err(ExceptionCode::IllegalFunction)
+	}
+
+	/// Write holding registers starting at `address` (function codes 0x06 and 0x10).
+	fn write_registers(&mut self, address: u16, values: &[u16]) -> Result<(), ExceptionCode> {
+		let _ = (address, values);
+		Err(ExceptionCode::IllegalFunction)
+	}
+}
+
+/// A simple in-memory register map that implements [`SlaveHandler`].
+///
+/// Each table starts at address 0.
+/// Requests outside of the tables are answered with [`ExceptionCode::IllegalDataAddress`].
+/// The discrete inputs and input registers are read-only for the master, but you can change them from your application.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct RegisterMap {
+	/// The coils.
+	pub coils: Vec<bool>,
+
+	/// The discrete inputs.
+	pub discrete_inputs: Vec<bool>,
+
+	/// The holding registers.
+	pub holding_registers: Vec<u16>,
+
+	/// The input registers.
+	pub input_registers: Vec<u16>,
+}
+
+impl RegisterMap {
+	/// Create an empty register map.
+	pub fn new() -> Self {
+		Self::default()
+	}
+}
+
+impl SlaveHandler for RegisterMap {
+	fn read_coils(&mut self, address: u16, count: u16) -> Result<Vec<bool>, ExceptionCode> {
+		Ok(table_range(&mut self.coils, address, count.into())?.to_vec())
+	}
+
+	fn read_discrete_inputs(&mut self, address: u16, count: u16) -> Result<Vec<bool>, ExceptionCode> {
+		Ok(table_range(&mut self.discrete_inputs, address, count.into())?.to_vec())
+	}
+
+	fn read_holding_registers(&mut self, address: u16, count: u16) -> Result<Vec<u16>, ExceptionCode> {
+		Ok(table_range(&mut self.holding_registers, address, count.into())?.to_vec())
+	}
+
+	fn read_input_registers(&mut self, address: u16, count: u16) -> Result<Vec<u16>, ExceptionCode> {
+		Ok(table_range(&mut self.input_registers, address, count.into())?.to_vec())
+	}
+
+	fn write_coils(&mut self, address: u16, values: &[bool]) -> Result<(), ExceptionCode> {
+		table_range(&mut self.coils, address, values.len())?.copy_from_slice(values);
+		Ok(())
+	}
+
+	fn write_registers(&mut self, address: u16, values: &[u16]) -> Result<(), ExceptionCode> {
+		table_range(&mut self.holding_registers, address, values.len())?.copy_from_slice(values);
+		Ok(())
+	}
+}
+
+/// Get a range of a register table, or an exception if the range is out of bounds.
+fn table_range<T>(table: &mut [T], address: u16, count: usize) -> Result<&mut [T], ExceptionCode> {
+	let start = usize::from(address);
+	table.get_mut(start..start + count).ok_or(ExceptionCode::IllegalDataAddress)
+}
+
+/// A Modbus RTU slave.
+///
+/// The slave reads requests from the serial port, passes them to a [`SlaveHandler`] and sends the response.
+/// Requests for other slaves and requests with an invalid CRC are ignored.
+/// Broadcast write requests are passed to the handler, but no response is sent.
+///
+/// See the [module documentation](super) for more information.
+///
+/// # Example
+/// ```no_run
+/// # async fn example() -> std::io::Result<()> {
+/// use serial2_tokio::SerialPort;
+/// use serial2_tokio::modbus::{RegisterMap, RtuSlave};
+///
+/// let port = SerialPort::open("/dev/ttyUSB0", 19200)?;
+/// let mut registers = RegisterMap::new();
+/// registers.holding_registers = vec![0; 100];
+/// registers.input_registers = vec![42; 10];
+/// RtuSlave::new(port, 1)?.run(&mut registers).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct RtuSlave {
+	port: SerialPort,
+	address: u8,
+	frame_gap: Duration,
+}
+
+impl RtuSlave {
+	/// Create a Modbus RTU slave with the given address.
+	///
+	/// The silent interval between frames is computed from the current settings of the serial port.
+	///
+	/// Returns an error of kind [`std::io::ErrorKind::InvalidInput`] if the address is not in the range 1 to 247.
+	pub fn new(port: SerialPort, address: u8) -> std::io::Result<Self> {
+		if !(1..=247).contains(&address) {
+			return Err(invalid_request("slave address must be between 1 and 247"));
+		}
+		let frame_gap = frame_gap(&port.get_configuration()?)?;
+		Ok(Self {
+			port,
+			address,
+			frame_gap,
+		})
+	}
+
+	/// Get the address of the slave.
+	pub fn address(&self) -> u8 {
+		self.address
+	}
+
+	/// Get the silent interval between frames.
+	pub fn frame_gap(&self) -> Duration {
+		self.frame_gap
+	}
+
+	/// Get a reference to the serial port.
+	pub fn get_ref(&self) -> &SerialPort {
+		&self.port
+	}
+
+	/// Consume the slave and return the serial port.
+	pub fn into_inner(self) -> SerialPort {
+		self.port
+	}
+
+	/// Answer requests from the master until an error occurs.
+	///
+	/// This returns `Ok(())` when the serial port reports end-of-file (which normally means the device was removed).
+	pub async fn run<H: SlaveHandler>(&self, handler: &mut H) -> std::io::Result<()> {
+		loop {
+			let Some((frame, frame_end)) = self.read_frame().await? else {
+				return Ok(());
+			};
+			if frame.len() < 4 || !check_crc(&frame) {
+				continue;
+			}
+			let slave = frame[0];
+			if slave != self.address && slave != BROADCAST {
+				continue;
+			}
+
+			let request = &frame[1..frame.len() - 2];
+			let response = match handle_request(handler, request, slave == BROADCAST) {
+				Ok(response) => response,
+				Err(code) => vec![request[0] | EXCEPTION_BIT, code.into()],
+			};
+			if slave == BROADCAST {
+				continue;
+			}
+
+			let mut reply = Vec::with_capacity(response.len() + 3);
+			reply.push(self.address);
+			reply.extend_from_slice(&response);
+			append_crc(&mut reply);
+			tokio::time::sleep_until(frame_end + self.frame_gap).await;
+			self.port.write_all(&reply).await?;
+		}
+	}
+
+	/// Read a request frame.
+	///
+	/// The end of the frame is determined from the function code if possible, or else by the silent interval after the frame.
+	/// Returns the frame and the time at which its last byte was received, or `None` at end-of-file.
+	async fn read_frame(&self) -> std::io::Result<Option<(Vec<u8>, Instant)>> {
+		let mut frame = Vec::new();
+		let mut buffer = [0; 256];
+		loop {
+			let read = if frame.is_empty() {
+				self.port.read(&mut buffer).await?
+			} else {
+				match tokio::time::timeout(self.frame_gap, self.port.read(&mut buffer)).await {
+					Ok(read) => read?,
+					Err(_) => break,
+				}
+			};
+			if read == 0 {
+				return Ok(None);
+			}
+			frame.extend_from_slice(&buffer[..read]);
+			if request_len(&frame).is_some_and(|len| frame.len() >= len) {
+				break;
+			}
+		}
+		let frame_end = Instant::now();
+		// Drop data after the frame: the master waits for a response before it sends the next request.
+		if let Some(len) = request_len(&frame) {
+			frame.truncate(len);
+		}
+		Ok(Some((frame, frame_end)))
+	}
+}
+
+/// Get the length of a request frame from the function code, if it is known.
+fn request_len(frame: &[u8]) -> Option<usize> {
+	match *frame.get(1)? {
+		READ_COILS | READ_DISCRETE_INPUTS | READ_HOLDING_REGISTERS | READ_INPUT_REGISTERS | WRITE_SINGLE_COIL | WRITE_SINGLE_REGISTER => Some(8),
+		WRITE_MULTIPLE_COILS | WRITE_MULTIPLE_REGISTERS => Some(9 + usize::from(*frame.get(6)?)),
+		_ => None,
+	}
+}
+
+/// Handle a request, starting with the function code, and return the response.
+fn handle_request<H: SlaveHandler>(handler: &mut H, request: &[u8], broadcast: bool) -> Result<Vec<u8>, ExceptionCode> {
+	let function = request[0];
+	let data = &request[1..];
+	if broadcast && !matches!(function, WRITE_SINGLE_COIL | WRITE_SINGLE_REGISTER | WRITE_MULTIPLE_COILS | WRITE_MULTIPLE_REGISTERS) {
+		return Err(ExceptionCode::IllegalFunction);
+	}
+	if !matches!(function, READ_COILS..=WRITE_SINGLE_REGISTER | WRITE_MULTIPLE_COILS | WRITE_MULTIPLE_REGISTERS) {
+		return Err(ExceptionCode::IllegalFunction);
+	}
+	if data.len() < 4 {
+		return Err(ExceptionCode::IllegalDataValue);
+	}
+	let address = u16::from_be_bytes([data[0], data[1]]);
+	let value = u16::from_be_bytes([data[2], data[3]]);
+
+	match function {
+		READ_COILS | READ_DISCRETE_INPUTS => {
+			check_request_count(value, MAX_READ_BITS)?;
+			let bits = if function == READ_COILS {
+				handler.read_coils(address, value)?
+			} else {
+				handler.read_discrete_inputs(address, value)?
+			};
+			if bits.len() != usize::from(value) {
+				return Err(ExceptionCode::ServerDeviceFailure);
+			}
+			let bytes = pack_bits(&bits);
+			let mut response = vec![function, bytes.len() as u8];
+			response.extend_from_slice(&bytes);
+			Ok(response)
+		},
+		READ_HOLDING_REGISTERS | READ_INPUT_REGISTERS => {
+			check_request_count(value, MAX_READ_REGISTERS)?;
+			let registers = if function == READ_HOLDING_REGISTERS {
+				handler.read_holding_registers(address, value)?
+			} else {
+				handler.read_input_registers(address, value)?
+			};
+			if registers.len() != usize::from(value) {
+				return Err(ExceptionCode::ServerDeviceFailure);
+			}
+			let mut response = vec![function, (registers.len() * 2) as u8];
+			for register in registers {
+				response.extend_from_slice(&register.to_be_bytes());
+			}
+			Ok(response)
+		},
+		WRITE_SINGLE_COIL => {
+			let state = match value {
+				0xFF00 => true,
+				0x0000 => false,
+				_ => return Err(ExceptionCode::IllegalDataValue),
+			};
+			handler.write_coils(address, &[state])?;
+			Ok(request.to_vec())
+		},
+		WRITE_SINGLE_REGISTER => {
+			handler.write_registers(address, &[value])?;
+			Ok(request.to_vec())
+		},
+		WRITE_MULTIPLE_COILS => {
+			check_request_count(value, MAX_WRITE_BITS)?;
+			let (byte_count, bytes) = multiple_write_data(data)?;
+			if byte_count != bytes.len() || bytes.len() != usize::from(value).div_ceil(8) {
+				return Err(ExceptionCode::IllegalDataValue);
+			}
+			handler.write_coils(address, &unpack_bits(bytes, value.into()))?;
+			Ok(request[..5].to_vec())
+		},
+		WRITE_MULTIPLE_REGISTERS => {
+			check_request_count(value, MAX_WRITE_REGISTERS)?;
+			let (byte_count, bytes) = multiple_write_data(data)?;
+			if byte_count != bytes.len() || bytes.len() != usize::from(value) * 2 {
+				return Err(ExceptionCode::IllegalDataValue);
+			}
+			let registers: Vec<u16> = bytes.chunks(2).map(|chunk| u16::from_be_bytes([chunk[0], chunk[1]])).collect();
+			handler.write_registers(address, &registers)?;
+			Ok(request[..5].to_vec())
+		},
+		_ => Err(ExceptionCode::IllegalFunction),
+	}
+}
+
+/// Get the byte count and the values of a request to write multiple coils or registers.
+fn multiple_write_data(data: &[u8]) -> Result<(usize, &[u8]), ExceptionCode> {
+	match data.get(4..) {
+		Some([byte_count, bytes @ ..]) => Ok((usize::from(*byte_count), bytes)),
+		_ => Err(ExceptionCode::IllegalDataValue),
+	}
+}
+
+/// Check the number of values in a request.
+fn check_request_count(count: u16, max: u16) -> Result<(), ExceptionCode> {
+	if (1..=max).contains(&count) {
+		Ok(())
+	} else {
+		Err(ExceptionCode::IllegalDataValue)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	/// The register map used by the examples of the Modbus application protocol specification.
+	fn specification_map() -> RegisterMap {
+		let mut map = RegisterMap::new();
+		map.coils = vec![false; 64];
+		map.coils[0x13..0x13 + 10].copy_from_slice(&unpack_bits(&[0xCD, 0x01], 10));
+		map.holding_registers = vec![0; 0x80];
+		map.holding_registers[0x6B..0x6E].copy_from_slice(&[0x022B, 0x0000, 0x0064]);
+		map
+	}
+
+	#[test]
+	fn read_holding_registers_specification_example() {
+		let mut map = specification_map();
+		let response = handle_request(&mut map, &[0x03, 0x00, 0x6B, 0x00, 0x03], false);
+		assert_eq!(response.unwrap(), [0x03, 0x06, 0x02, 0x2B, 0x00, 0x00, 0x00, 0x64]);
+	}
+
+	#[test]
+	fn read_coils_packs_bits() {
+		let mut map = specification_map();
+		let response = handle_request(&mut map, &[0x01, 0x00, 0x13, 0x00, 0x0A], false);
+		assert_eq!(response.unwrap(), [0x01, 0x02, 0xCD, 0x01]);
+	}
+
+	#[test]
+	fn write_single_register_echoes_request() {
+		let mut map = specification_map();
+		let request = [0x06, 0x00, 0x01, 0x00, 0x03];
+		assert_eq!(handle_request(&mut map, &request, false).unwrap(), request);
+		assert_eq!(map.holding_registers[1], 0x0003);
+	}
+
+	#[test]
+	fn write_multiple_registers_specification_example() {
+		let mut map = specification_map();
+		let request = [0x10, 0x00, 0x01, 0x00, 0x02, 0x04, 0x00, 0x0A, 0x01, 0x02];
+		assert_eq!(handle_request(&mut map, &request, false).unwrap(), [0x10, 0x00, 0x01, 0x00, 0x02]);
+		assert_eq!(map.holding_registers[1..3], [0x000A, 0x0102]);
+	}
+
+	#[test]
+	fn write_single_coil_accepts_only_on_and_off() {
+		let mut map = specification_map();
+		assert_eq!(handle_request(&mut map, &[0x05, 0x00, 0x01, 0xFF, 0x00], false).unwrap(), [0x05, 0x00, 0x01, 0xFF, 0x00]);
+		assert!(map.coils[1]);
+		assert_eq!(handle_request(&mut map, &[0x05, 0x00, 0x01, 0x12, 0x34], false), Err(ExceptionCode::IllegalDataValue));
+	}
+
+	#[test]
+	fn exceptions_for_invalid_requests() {
+		let mut map = specification_map();
+		assert_eq!(handle_request(&mut map, &[0x03, 0x00, 0x7F, 0x00, 0x02], false), Err(ExceptionCode::IllegalDataAddress));
+		assert_eq!(handle_request(&mut map, &[0x03, 0x00, 0x00, 0x00, 0x00], false), Err(ExceptionCode::IllegalDataValue));
+		assert_eq!(handle_request(&mut map, &[0x03, 0x00, 0x00, 0x00, 0x7E], false), Err(ExceptionCode::IllegalDataValue));
+		assert_eq!(handle_request(&mut map, &[0x2B, 0x0E, 0x01, 0x00], false), Err(ExceptionCode::IllegalFunction));
+		assert_eq!(handle_request(&mut map, &[0x03, 0x00, 0x6B, 0x00, 0x03], true), Err(ExceptionCode::IllegalFunction));
+		assert_eq!(handle_request(&mut map, &[0x10, 0x00, 0x01, 0x00, 0x02, 0x04, 0x00], false), Err(ExceptionCode::IllegalDataValue));
+	}
+
+	#[test]
+	fn default_handler_rejects_all_functions() {
+		struct Empty;
+		impl SlaveHandler for Empty {}
+		assert_eq!(handle_request(&mut Empty, &[0x03, 0x00, 0x6B, 0x00, 0x03], false), Err(ExceptionCode::IllegalFunction));
+		assert_eq!(handle_request(&mut Empty, &[0x06, 0x00, 0x01, 0x00, 0x03], false), Err(ExceptionCode::IllegalFunction));
+	}
+
+	#[test]
+	fn request_len_from_function_code() {
+		assert_eq!(request_len(&[0x11]), None);
+		assert_eq!(request_len(&[0x11, 0x03]), Some(8));
+		assert_eq!(request_len(&[0x11, 0x06]), Some(8));
+		assert_eq!(request_len(&[0x11, 0x0F, 0x00, 0x13, 0x00]), None);
+		assert_eq!(request_len(&[0x11, 0x0F, 0x00, 0x13, 0x00, 0x0A, 0x02]), Some(11));
+		assert_eq!(request_len(&[0x11, 0x2B]), None);
+	}
+
+	#[cfg(all(unix, feature = "unix"))]
+	#[tokio::test]
+	async fn slave_answers_master() {
+		let (a, b, timeout) = crate::test_util::pair_with_timeout();
+		let mut master = crate::modbus::RtuMaster::new(a).unwrap();
+		master.set_timeout(timeout);
+		master.set_retries(0);
+		let slave = RtuSlave::new(b, 0x11).unwrap();
+		let mut map = specification_map();
+
+		let exchange = async {
+			assert_eq!(master.read_holding_registers(0x11, 0x006B, 3).await.unwrap(), [0x022B, 0x0000, 0x0064]);
+			master.write_multiple_registers(0x11, 0x0001, &[0x000A, 0x0102]).await.unwrap();
+			let error = master.read_input_registers(0x11, 0x0000, 1).await.unwrap_err();
+			let exception = error.get_ref().and_then(|e| e.downcast_ref::<Exception>()).unwrap();
+			assert_eq!(exception.code, ExceptionCode::IllegalDataAddress);
+			let error = master.read_holding_registers(0x12, 0x006B, 3).await.unwrap_err();
+			assert_eq!(error.kind(), std::io::ErrorKind::TimedOut);
+		};
+		tokio::select! {
+			() = exchange => (),
+			result = slave.run(&mut map) => panic!("slave stopped: {result:?}"),
+		}
+		assert_eq!(map.holding_registers[1..3], [0x000A, 0x0102]);
+	}
+
+	#[cfg(all(unix, feature = "unix"))]
+	#[tokio::test]
+	async fn slave_address_must_be_valid() {
+		for address in [BROADCAST, 248] {
+			let (port, _peer) = SerialPort::pair().unwrap();
+			assert_eq!(RtuSlave::new(port, address).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+		}
+	}
+}
@@ -0,0 +1,238 @@
+use std::path::Path;
+
+use crate::{inner, stats, IntoSettings, KeepSettings, SerialPort, Settings};
+
+/// Options for opening a serial port.
+///
+/// This can be used to open a serial port with more control than [`SerialPort::open()`].
+///
+/// # Example
+/// ```no_run
+/// # async fn example() -> std::io::Result<()> {
+/// use serial2_tokio::OpenOptions;
+///
+/// let port = OpenOptions::new()
+///     .restore_settings_on_close(true)
+///     .open("/dev/ttyS0", 115200)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct OpenOptions {
+	restore_settings_on_close: bool,
+	read_only: bool,
+	wait_for_carrier: bool,
+	preserve_line_state: bool,
+	#[cfg(unix)]
+	uucp_lock: bool,
+}
+
+impl OpenOptions {
+	/// Create new options with the default values.
+	///
+	/// With the default values, the serial port is opened exactly like [`SerialPort::open()`] does.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Restore the original settings of the serial port when it is closed.
+	///
+	/// If enabled, the configuration of the serial port is saved before the new settings are applied.
+	/// It is restored when the returned [`SerialPort`] is dropped or closed with [`SerialPort::close()`].
+	/// Handles created with [`SerialPort::try_clone()`] do not restore the settings.
+	///
+	/// The settings are restored immediately, without waiting for pending output to be transmitted,
+	/// so that dropping the serial port can not block when the output is stopped by flow control.
+	/// On Unix, data that is still in the output buffer is discarded when the settings are restored.
+	/// Use [`SerialPort::close()`] to wait for the output buffer to drain first.
+	///
+	/// This is useful when the serial port is shared with other programs,
+	/// such as a login prompt on a serial console that stops working when the port is left in raw mode.
+	///
+	/// Note that many platforms reset the configuration of a serial port when it is no longer in use anyway.
+	pub fn restore_settings_on_close(&mut self, enable: bool) -> &mut Self {
+		self.restore_settings_on_close = enable;
+		self
+	}
+
+	/// Open the serial port for reading only.
+	///
+	/// Writing to a serial port that was opened for reading only fails.
+	/// Combined with [`KeepSettings`], this can be used to passively listen to an existing link.
+	/// See [`SerialPort::open_observer()`] for more information.
+	pub fn read_only(&mut self, read_only: bool) -> &mut Self {
+		self.read_only = read_only;
+		self
+	}
+
+	/// Use the CD (carrier detect) line like a classic modem connection.
+	///
+	/// If enabled, [`Self::open_async()`] waits until the CD line is asserted before it returns the serial port.
+	/// On Unix platforms, the `CLOCAL` flag is also cleared after the settings are applied,
+	/// so that the OS reports a hang-up when the carrier is lost (see [`SerialPort::closed()`]).
+	///
+	/// The serial port is still opened without blocking, and [`Self::open()`] does not wait for carrier.
+	/// You can use [`SerialPort::wait_for_carrier()`] to wait later.
+	pub fn wait_for_carrier(&mut self, enable: bool) -> &mut Self {
+		self.wait_for_carrier = enable;
+		self
+	}
+
+	/// Guarantee that opening the serial port does not change the state of the modem control lines.
+	///
+	/// Some devices reset or enter a bootloader when the DTR or RTS line changes, like many microcontroller boards with an auto-reset circuit.
+	/// With this option enabled, the library never changes the DTR and RTS lines while opening the serial port.
+	/// Settings that could change the lines are refused with an error of kind [`std::io::ErrorKind::InvalidInput`] instead of being applied:
+	/// a baud rate of 0 (which hangs up the line), and a different flow control mode than the current one (which can change the RTS and DTR lines).
+	/// If the current flow control configuration is not one of the [`FlowControl`][crate::FlowControl] modes,
+	/// it is only accepted if the new settings do not change it either.
+	/// Use [`KeepSettings`] or a closure that only changes the baud rate and character format to be safe.
+	///
+	/// The OS or the driver may still change the lines when the serial port is opened, which can not be prevented by this library:
+	/// * On Linux, Android, macOS and the BSDs, the kernel asserts DTR and RTS when the device is opened while no other process has it open,
+	///   unless the current baud rate is 0.
+	///   It deasserts them again when the last handle is closed if the `HUPCL` flag is set (which is the default).
+	///   Leave the device open in another process, or clear `HUPCL` once with `stty -F /dev/ttyUSB0 -hupcl`, to keep the lines stable across programs.
+	/// * On Windows, opening the device does not change the lines by itself, but some USB serial drivers do.
+	///   The lines keep the state of the DTR and RTS control settings of the port, which are not changed by this option.
+	///
+	/// This option is disabled by default.
+	pub fn preserve_line_state(&mut self, enable: bool) -> &mut Self {
+		self.preserve_line_state = enable;
+		self
+	}
+
+	/// Create a UUCP style lock file while the serial port is open.
+	///
+	/// If enabled, a lock file like `/var/lock/LCK..ttyUSB0` is created before the serial port is opened,
+	/// and removed again when the returned [`SerialPort`] is dropped.
+	/// This is the convention used by minicom, pppd and other classic tools to avoid using the same serial port at the same time.
+	/// Handles created with [`SerialPort::try_clone()`] do not keep the lock.
+	///
+	/// If the lock file already exists and belongs to a running process,
+	/// opening the serial port fails with an error of kind [`std::io::ErrorKind::ResourceBusy`].
+	/// A stale lock file of a process that no longer exists is removed.
+	/// The lock file is named after the device that the path points to, so symbolic links like `/dev/serial/by-id/...` use the same lock file.
+	///
+	/// Creating the lock file requires write access to `/var/lock`.
+	/// On many distributions, that directory is only writable by root and a group like `lock` or `uucp`.
+	///
+	/// This option is disabled by default.
+	#[cfg(any(feature = "doc", unix))]
+	#[cfg_attr(feature = "doc-cfg", doc(cfg(unix)))]
+	pub fn uucp_lock(&mut self, enable: bool) -> &mut Self {
+		#[cfg(unix)] {
+			self.uucp_lock = enable;
+		}
+		#[cfg(not(unix))] {
+			let _ = enable;
+		}
+		self
+	}
+
+	/// Open and configure a serial port with these options, and wait for carrier if requested.
+	///
+	/// This is the same as [`Self::open()`], except that it waits until the CD line is asserted
+	/// if [`Self::wait_for_carrier()`] is enabled.
+	///
+	/// # Example
+	/// ```no_run
+	/// # async fn example() -> std::io::Result<()> {
+	/// use serial2_tokio::OpenOptions;
+	///
+	/// let port = OpenOptions::new()
+	///     .wait_for_carrier(true)
+	///     .open_async("/dev/ttyS0", 9600)
+	///     .await?;
+	/// port.closed().await?;
+	/// println!("carrier lost");
+	/// # Ok(())
+	/// # }
+	/// ```
+	pub async fn open_async(&self, path: impl AsRef<Path>, settings: impl IntoSettings) -> std::io::Result<SerialPort> {
+		let port = self.open(path, settings)?;
+		if self.wait_for_carrier {
+			port.wait_for_carrier().await?;
+		}
+		Ok(port)
+	}
+
+	/// Open and configure a serial port with these options.
+	///
+	/// See [`SerialPort::open()`] for more information.
+	pub fn open(&self, path: impl AsRef<Path>, settings: impl IntoSettings) -> std::io::Result<SerialPort> {
+		let path = path.as_ref();
+		#[cfg(windows)]
+		let path = &*crate::port_info::resolve_instance_id(path)?;
+		#[cfg(unix)]
+		let lock = self.uucp_lock.then(|| crate::uucp_lock::UucpLock::acquire(path)).transpose()?;
+		let mut inner = if self.read_only {
+			open_read_only(path)?
+		} else {
+			serial2::SerialPort::open(path, KeepSettings)?
+		};
+
+		let mut current = inner.get_configuration()?;
+		let original = self.restore_settings_on_close.then(|| crate::inner::SavedSettings::save(&inner)).transpose()?;
+		let before = self.preserve_line_state.then(|| current.clone());
+		settings.apply_to_settings(&mut current)?;
+		if let Some(before) = &before {
+			check_line_state_preserved(before, &current)?;
+		}
+		inner.set_configuration(&current)?;
+
+		let inner = inner::SerialPort::wrap(inner)?;
+		if self.wait_for_carrier {
+			inner.set_ignore_carrier(false)?;
+		}
+		let mut port = SerialPort::from_inner(inner, stats::StatsCollector::new(&path.to_string_lossy()));
+		port.restore_settings = original;
+		#[cfg(unix)] {
+			port.uucp_lock = lock;
+		}
+		Ok(port)
+	}
+}
+
+/// Check that applying new settings does not change the state of the modem control lines.
+fn check_line_state_preserved(current: &Settings, new: &Settings) -> std::io::Result<()> {
+	if new.get_baud_rate()? == 0 {
+		return Err(std::io::Error::new(
+			std::io::ErrorKind::InvalidInput,
+			"a baud rate of 0 would change the modem control lines",
+		));
+	}
+	if new.get_flow_control().ok() != current.get_flow_control().ok() {
+		return Err(std::io::Error::new(
+			std::io::ErrorKind::InvalidInput,
+			"changing the flow control mode could change the modem control lines",
+		));
+	}
+	Ok(())
+}
+
+/// Open a serial port for reading only.
+#[cfg(unix)]
+fn open_read_only(path: &Path) -> std::io::Result<serial2::SerialPort> {
+	use std::os::unix::fs::OpenOptionsExt;
+	let file = std::fs::OpenOptions::new()
+		.read(true)
+		.custom_flags(libc::O_NONBLOCK | libc::O_NOCTTY)
+		.open(path)?;
+	Ok(serial2::SerialPort::from(std::os::fd::OwnedFd::from(file)))
+}
+
+/// Open a serial port for reading only.
+#[cfg(windows)]
+fn open_read_only(path: &Path) -> std::io::Result<serial2::SerialPort> {
+	use std::os::windows::fs::OpenOptionsExt;
+
+	// Use the win32 device namespace, like `serial2::SerialPort::open()`.
+	let mut device = std::ffi::OsString::from("\\\\.\\");
+	device.push(path.as_os_str());
+	let file = std::fs::OpenOptions::new()
+		.read(true)
+		.custom_flags(winapi::um::winbase::FILE_FLAG_OVERLAPPED)
+		.open(device)?;
+	Ok(serial2::SerialPort::from(std::os::windows::io::OwnedHandle::from(file)))
+}
@@ -0,0 +1,119 @@
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+use crate::SerialPort;
+
+impl SerialPort {
+	/// Report receiver overruns as read errors.
+	///
+	/// When the application or the OS can not keep up with the incoming data, the UART or the input buffer of the driver overflows
+	/// and the received data has a gap.
+	/// Normally, the data after the gap is returned as if nothing happened.
+	/// With overrun detection enabled, the read functions (including [`AsyncRead`][tokio::io::AsyncRead]) check for new overruns before reading,
+	/// and return an error if data was lost since the previous read.
+	/// The error can be recognized by converting it to an [`Error`][crate::Error], which gives [`Error::Overrun`][crate::Error::Overrun].
+	/// The next read continues with the data after the gap.
+	///
+	/// The number of detected overruns is also counted in [`Stats::overruns`][crate::Stats::overruns].
+	///
+	/// Note that a [`Framed`](https://docs.rs/tokio-util/latest/tokio_util/codec/struct.Framed.html) stream stops after the first error.
+	/// If you use a codec, you may prefer to check the statistics periodically instead.
+	///
+	/// On Linux, overruns are detected with the `TIOCGICOUNT` counters of the driver (see [`Self::line_counters()`]),
+	/// which includes both hardware overruns and overruns of the input buffer.
+	/// On Windows, the `CE_OVERRUN` and `CE_RXOVER` error flags are used.
+	/// The flags are collected from every internal call to `ClearCommError()`, so other operations of this crate do not hide an overrun.
+	/// Each check costs a system call, so overrun detection is disabled by default.
+	///
+	/// Returns an error of kind [`std::io::ErrorKind::Unsupported`] if the platform or driver can not report overruns.
+	///
+	/// # Example
+	/// ```no_run
+	/// # async fn example() -> std::io::Result<()> {
+	/// use serial2_tokio::{Error, SerialPort};
+	///
+	/// let port = SerialPort::open("/dev/ttyS0", 921600)?;
+	/// port.set_overrun_detection(true)?;
+	/// let mut buffer = [0; 4096];
+	/// loop {
+	///     match port.read(&mut buffer).await.map_err(Error::from) {
+	///         Ok(read) => println!("read {read} bytes"),
+	///         Err(Error::Overrun(_)) => eprintln!("receiver overrun: data was lost"),
+	///         Err(e) => return Err(e.into()),
+	///     }
+	/// }
+	/// # }
+	/// ```
+	pub fn set_overrun_detection(&self, enabled: bool) -> std::io::Result<()> {
+		if !enabled {
+			self.overrun.enabled.store(false, Ordering::Relaxed);
+			return Ok(());
+		}
+		match self.inner.overrun_count()? {
+			Some(count) => {
+				self.overrun.last.store(count, Ordering::Relaxed);
+				self.overrun.enabled.store(true, Ordering::Release);
+				Ok(())
+			},
+			None => Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "the serial port driver does not report receiver overruns")),
+		}
+	}
+
+	/// Check if receiver overruns are reported as read errors.
+	pub fn get_overrun_detection(&self) -> bool {
+		self.overrun.enabled.load(Ordering::Relaxed)
+	}
+
+	/// Check for new receiver overruns, if overrun detection is enabled.
+	///
+	/// Returns an error if there were new overruns since the last check.
+	/// The error is recorded in the statistics as a failed read.
+	pub(crate) fn check_overrun(&self) -> std::io::Result<()> {
+		if !self.overrun.enabled.load(Ordering::Acquire) {
+			return Ok(());
+		}
+		let Some(count) = self.inner.overrun_count()? else {
+			return Ok(());
+		};
+		let new = count.wrapping_sub(self.overrun.last.swap(count, Ordering::Relaxed));
+		if new == 0 {
+			return Ok(());
+		}
+		self.stats.record_overruns(new);
+		let result = Err(std::io::Error::other(OverrunError { count: new }));
+		self.stats.record_read(&result);
+		result.map(drop)
+	}
+}
+
+/// Keeps track of the overrun count of the driver.
+#[derive(Debug, Default)]
+pub(crate) struct OverrunDetector {
+	/// Set when overrun detection is enabled, so reads can skip the check without a lock.
+	enabled: AtomicBool,
+
+	/// The overrun count at the last check.
+	last: AtomicU32,
+}
+
+/// The payload of the I/O error returned for receiver overruns.
+#[derive(Debug)]
+struct OverrunError {
+	count: u32,
+}
+
+impl std::fmt::Display for OverrunError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		if self.count == 1 {
+			write!(f, "receiver overrun: received data was lost")
+		} else {
+			write!(f, "{} receiver overruns: received data was lost", self.count)
+		}
+	}
+}
+
+impl std::error::Error for OverrunError {}
+
+/// Check if an I/O error reports a receiver overrun.
+pub(crate) fn is_overrun(error: &std::io::Error) -> bool {
+	error.get_ref().is_some_and(|error| error.is::<OverrunError>())
+}
@@ -0,0 +1,256 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::task::Poll;
+use std::time::Duration;
+
+use tokio::time::{Instant, Sleep};
+
+use crate::SerialPort;
+
+/// How long a burst of data may be when pacing by bytes per second.
+const BURST_DURATION: Duration = Duration::from_millis(10);
+
+/// Pacing of data written to a serial port.
+///
+/// Used with [`SerialPort::set_write_pacing()`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum WritePacing {
+	/// Limit the average number of bytes per second handed to the OS.
+	///
+	/// Data is written in small bursts of at most 10 milliseconds worth of data.
+	BytesPerSecond(u32),
+
+	/// Hand data to the OS one byte at a time, waiting the given duration after each byte.
+	///
+	/// The delay starts when the byte is handed to the OS, so it includes the time needed to transmit the byte.
+	InterByteDelay(Duration),
+
+	/// Transmit data in chunks of at most `size` bytes, with a pause of `gap` between the chunks.
+	///
+	/// This is useful for devices with a small receive FIFO and no flow control.
+	/// The time needed to transmit a chunk is estimated from the baud rate, character size, parity and stop bits
+	/// when [`SerialPort::set_write_pacing()`] is called, so the gap applies to the line itself.
+	/// If you change these settings afterwards, you should set the write pacing again.
+	Chunks {
+		/// The maximum number of bytes in a chunk.
+		size: usize,
+
+		/// The pause between chunks.
+		gap: Duration,
+	},
+}
+
+impl WritePacing {
+	/// Get the maximum number of bytes to write at once.
+	fn burst_size(&self) -> usize {
+		match *self {
+			Self::BytesPerSecond(rate) => (u128::from(rate) * BURST_DURATION.as_millis() / 1000).max(1) as usize,
+			Self::InterByteDelay(_) => 1,
+			Self::Chunks { size, .. } => size,
+		}
+	}
+}
+
+/// Estimate the time needed to transmit a single character with the given settings.
+pub(crate) fn char_time(settings: &crate::Settings) -> std::io::Result<Duration> {
+	let data_bits = match settings.get_char_size()? {
+		crate::CharSize::Bits5 => 5,
+		crate::CharSize::Bits6 => 6,
+		crate::CharSize::Bits7 => 7,
+		crate::CharSize::Bits8 => 8,
+	};
+	let parity_bits = match settings.get_parity()? {
+		crate::Parity::None => 0,
+		crate::Parity::Odd | crate::Parity::Even => 1,
+	};
+	let stop_bits = match settings.get_stop_bits()? {
+		crate::StopBits::One => 1,
+		crate::StopBits::Two => 2,
+	};
+	let baud_rate = settings.get_baud_rate()?.max(1);
+	Ok(Duration::from_secs(1 + data_bits + parity_bits + stop_bits) / baud_rate)
+}
+
+impl SerialPort {
+	/// Throttle the data written to the serial port.
+	///
+	/// Some devices drop bytes when they receive data faster than they can process it, even at the configured baud rate.
+	/// With write pacing enabled, the write functions (including [`AsyncWrite`][tokio::io::AsyncWrite])
+	/// wait asynchronously before handing more data to the OS.
+	/// A single call may write less than the given buffer, and [`Self::write_all()`] takes correspondingly longer.
+	///
+	/// Pass `None` to disable pacing.
+	///
+	/// Returns an error of kind [`std::io::ErrorKind::InvalidInput`] for a rate of zero bytes per second or a chunk size of zero.
+	///
+	/// # Example
+	/// ```no_run
+	/// # async fn example() -> std::io::Result<()> {
+	/// use serial2_tokio::{SerialPort, WritePacing};
+	/// use std::time::Duration;
+	///
+	/// let port = SerialPort::open("/dev/ttyUSB0", 9600)?;
+	/// port.set_write_pacing(Some(WritePacing::InterByteDelay(Duration::from_millis(5))))?;
+	/// port.write_all(b"PRINT 1\r\n").await?;
+	/// # Ok(())
+	/// # }
+	/// ```
+	pub fn set_write_pacing(&self, pacing: Option<WritePacing>) -> std::io::Result<()> {
+		let char_time = self.pacing_char_time(pacing)?;
+		self.pacer.set(pacing, char_time);
+		Ok(())
+	}
+
+	/// Get the current write pacing of the serial port.
+	pub fn get_write_pacing(&self) -> Option<WritePacing> {
+		self.pacer.state.lock().unwrap_or_else(|e| e.into_inner()).pacing
+	}
+
+	/// Validate write pacing and estimate the time to transmit a single character, if the pacing needs it.
+	pub(crate) fn pacing_char_time(&self, pacing: Option<WritePacing>) -> std::io::Result<Duration> {
+		match pacing {
+			Some(WritePacing::BytesPerSecond(0)) => {
+				Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "write pacing rate must be greater than zero"))
+			},
+			Some(WritePacing::Chunks { size: 0, .. }) => {
+				Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "write pacing chunk size must be greater than zero"))
+			},
+			Some(WritePacing::Chunks { .. }) => char_time(&self.get_configuration()?),
+			_ => Ok(Duration::ZERO),
+		}
+	}
+}
+
+/// Keeps track of when more data may be written.
+#[derive(Debug)]
+pub(crate) struct Pacer {
+	/// Set when write pacing is enabled, so writes can skip the lock when it is not.
+	enabled: AtomicBool,
+
+	state: Mutex<PacerState>,
+}
+
+#[derive(Debug)]
+struct PacerState {
+	pacing: Option<WritePacing>,
+
+	/// The time when more data may be written.
+	next: Instant,
+
+	/// The number of bytes left in the current chunk.
+	remaining: usize,
+
+	/// The estimated time to transmit a single character.
+	char_time: Duration,
+}
+
+impl Pacer {
+	pub fn new() -> Self {
+		Self {
+			enabled: AtomicBool::new(false),
+			state: Mutex::new(PacerState {
+				pacing: None,
+				next: Instant::now(),
+				remaining: 0,
+				char_time: Duration::ZERO,
+			}),
+		}
+	}
+
+	/// Set the pacing and the estimated time to transmit a single character.
+	pub fn set(&self, pacing: Option<WritePacing>, char_time: Duration) {
+		let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+		state.pacing = pacing;
+		state.next = Instant::now();
+		state.remaining = pacing.map_or(0, |pacing| pacing.burst_size());
+		state.char_time = char_time;
+		self.enabled.store(pacing.is_some(), Ordering::Relaxed);
+	}
+
+	/// Check if write pacing is enabled.
+	pub fn is_enabled(&self) -> bool {
+		self.enabled.load(Ordering::Relaxed)
+	}
+
+	/// Get the number of bytes that may be written now, or the time to wait before writing.
+	fn check(&self, len: usize) -> Result<usize, Instant> {
+		if !self.is_enabled() {
+			return Ok(len);
+		}
+		let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+		let Some(pacing) = state.pacing else {
+			return Ok(len);
+		};
+		if len == 0 {
+			return Ok(0);
+		}
+		if Instant::now() < state.next {
+			return Err(state.next);
+		}
+		match pacing {
+			WritePacing::Chunks { .. } => Ok(len.min(state.remaining)),
+			_ => Ok(len.min(pacing.burst_size())),
+		}
+	}
+
+	/// Wait until data may be written and get the number of bytes that may be written.
+	pub async fn ready(&self, len: usize) -> usize {
+		loop {
+			match self.check(len) {
+				Ok(len) => return len,
+				Err(deadline) => tokio::time::sleep_until(deadline).await,
+			}
+		}
+	}
+
+	/// Poll until data may be written and get the number of bytes that may be written.
+	///
+	/// The `sleep` future is kept by the caller between polls.
+	pub fn poll_ready(&self, cx: &mut std::task::Context<'_>, sleep: &mut Option<Pin<Box<Sleep>>>, len: usize) -> Poll<usize> {
+		loop {
+			match self.check(len) {
+				Ok(len) => {
+					*sleep = None;
+					return Poll::Ready(len);
+				},
+				Err(deadline) => {
+					let sleep = sleep.get_or_insert_with(|| Box::pin(tokio::time::sleep_until(deadline)));
+					if sleep.deadline() != deadline {
+						sleep.as_mut().reset(deadline);
+					}
+					std::task::ready!(sleep.as_mut().poll(cx));
+				},
+			}
+		}
+	}
+
+	/// Record that data was written.
+	pub fn consume(&self, written: usize) {
+		if !self.is_enabled() {
+			return;
+		}
+		let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+		let now = Instant::now();
+		match state.pacing {
+			None => (),
+			Some(WritePacing::BytesPerSecond(rate)) => {
+				let written = u32::try_from(written).unwrap_or(u32::MAX);
+				state.next = state.next.max(now) + Duration::from_secs(1) / rate * written;
+			},
+			Some(WritePacing::InterByteDelay(delay)) => {
+				let written = u32::try_from(written).unwrap_or(u32::MAX);
+				state.next = state.next.max(now) + delay * written;
+			},
+			Some(WritePacing::Chunks { size, gap }) => {
+				state.remaining = state.remaining.saturating_sub(written);
+				if state.remaining == 0 {
+					let transmit_time = state.char_time * u32::try_from(size).unwrap_or(u32::MAX);
+					state.remaining = size;
+					state.next = now + transmit_time + gap;
+				}
+			},
+		}
+	}
+}
@@ -0,0 +1,405 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+#[cfg(any(feature = "doc", feature = "cancel"))]
+use tokio_util::sync::CancellationToken;
+
+use crate::SerialPort;
+use crate::cancel::Cancel;
+
+/// A function that builds the request for a slave.
+type RequestFn = Box<dyn FnMut() -> Vec<u8> + Send>;
+
+/// A function that checks if a response is complete.
+type CompleteFn = Box<dyn FnMut(&[u8]) -> bool + Send>;
+
+/// Polls the slaves on a multi-drop bus, like an RS-485 network, in round-robin order.
+///
+/// Each slave has an address, a function that builds the request, and a function that checks if the response is complete.
+/// [`Self::next()`] performs the request/response exchange with the next slave using [`SerialPort::request()`],
+/// and returns the result.
+/// Call it in a loop to poll all slaves continuously.
+///
+/// When the exchange with a slave fails, the slave is skipped for a while, so that a missing device does not slow down the bus.
+/// The time a slave is skipped doubles for each consecutive failure, up to a maximum (see [`Self::set_backoff()`]).
+/// Slaves that keep timing out can also be marked offline, so that they are only probed occasionally (see [`Self::set_offline_policy()`]).
+///
+/// The scheduler keeps [statistics][Self::stats()] for each slave, like the number of errors and the response latency.
+///
+/// # Example
+/// ```no_run
+/// # async fn example() -> std::io::Result<()> {
+/// use serial2_tokio::{PollScheduler, SerialPort};
+/// use std::time::Duration;
+///
+/// let port = SerialPort::open("/dev/ttyUSB0", 9600)?;
+/// let mut scheduler = PollScheduler::new(port);
+/// for address in 1..=4 {
+///     let request = format!("#{address:02}STATUS\r");
+///     scheduler.add_slave(address, Duration::from_millis(100), move || request.clone().into_bytes(), |data| data.ends_with(b"\r"));
+/// }
+/// while let Some(result) = scheduler.next().await {
+///     match result.response {
+///         Ok(response) => println!("slave {}: {response:?} after {:?}", result.address, result.latency),
+///         Err(e) => eprintln!("slave {}: {e}", result.address),
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct PollScheduler {
+	port: Arc<SerialPort>,
+	slaves: Vec<Slave>,
+	next: usize,
+	request_gap: Duration,
+	initial_backoff: Duration,
+	max_backoff: Duration,
+	offline_threshold: Option<u32>,
+	offline_probe_interval: Duration,
+	last_request: Option<Instant>,
+	cancel: Cancel,
+}
+
+/// The result of polling a slave.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct PollResult {
+	/// The address of the slave.
+	pub address: u8,
+
+	/// The response of the slave, or the error that occurred.
+	///
+	/// If the slave did not respond in time, the error has kind [`std::io::ErrorKind::TimedOut`].
+	pub response: std::io::Result<Vec<u8>>,
+
+	/// The time from the start of the request until the response was complete or the exchange failed.
+	pub latency: Duration,
+
+	/// The new status of the slave, if this poll changed it.
+	///
+	/// This is [`SlaveStatus::Offline`] when the slave went offline because of this poll,
+	/// and [`SlaveStatus::Online`] when an offline slave responded again.
+	pub status_change: Option<SlaveStatus>,
+}
+
+/// The status of a slave of a [`PollScheduler`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum SlaveStatus {
+	/// The slave is polled normally.
+	Online,
+
+	/// The slave timed out too often and is only probed occasionally.
+	Offline,
+}
+
+/// Statistics for a slave of a [`PollScheduler`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct SlaveStats {
+	/// The current status of the slave.
+	pub status: SlaveStatus,
+
+	/// The number of successful polls.
+	pub successes: u64,
+
+	/// The number of polls that timed out.
+	pub timeouts: u64,
+
+	/// The number of polls that failed with another error.
+	pub errors: u64,
+
+	/// The number of consecutive failed polls, including timeouts.
+	pub consecutive_failures: u32,
+
+	/// The latency of the last successful poll.
+	pub last_latency: Option<Duration>,
+
+	/// The average latency of all successful polls.
+	pub average_latency: Option<Duration>,
+
+	/// The highest latency of all successful polls.
+	pub max_latency: Option<Duration>,
+}
+
+/// A slave on the bus.
+struct Slave {
+	address: u8,
+	timeout: Duration,
+	request: RequestFn,
+	is_complete: CompleteFn,
+	/// The slave is skipped until this time.
+	skip_until: Option<Instant>,
+	/// The number of consecutive timeouts.
+	consecutive_timeouts: u32,
+	/// The sum of the latency of all successful polls.
+	total_latency: Duration,
+	stats: SlaveStats,
+}
+
+impl std::fmt::Debug for Slave {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("Slave")
+			.field("address", &self.address)
+			.field("timeout", &self.timeout)
+			.field("skip_until", &self.skip_until)
+			.field("stats", &self.stats)
+			.finish_non_exhaustive()
+	}
+}
+
+impl PollScheduler {
+	/// Create a poll scheduler for a serial port, without any slaves.
+	pub fn new(port: impl Into<Arc<SerialPort>>) -> Self {
+		Self {
+			port: port.into(),
+			slaves: Vec::new(),
+			next: 0,
+			request_gap: Duration::ZERO,
+			initial_backoff: Duration::from_secs(1),
+			max_backoff: Duration::from_secs(30),
+			offline_threshold: None,
+			offline_probe_interval: Duration::from_secs(60),
+			last_request: None,
+			cancel: Cancel::never(),
+		}
+	}
+
+	/// Get the serial port used by the scheduler.
+	pub fn port(&self) -> &Arc<SerialPort> {
+		&self.port
+	}
+
+	/// Add a slave to poll.
+	///
+	/// The `request` function is called before each poll to build the request,
+	/// so the request can change over time.
+	/// The `is_complete` function is called with the data received so far, and must return true once the response is complete.
+	/// If the response is not complete within `timeout`, the poll fails with an error of kind [`std::io::ErrorKind::TimedOut`].
+	///
+	/// # Panics
+	/// This function panics if a slave with the same address was already added.
+	pub fn add_slave<R, C>(&mut self, address: u8, timeout: Duration, request: R, is_complete: C)
+	where
+		R: FnMut() -> Vec<u8> + Send + 'static,
+		C: FnMut(&[u8]) -> bool + Send + 'static,
+	{
+		assert!(!self.contains(address), "a slave with address {address} was already added");
+		self.slaves.push(Slave {
+			address,
+			timeout,
+			request: Box::new(request),
+			is_complete: Box::new(is_complete),
+			skip_until: None,
+			consecutive_timeouts: 0,
+			total_latency: Duration::ZERO,
+			stats: SlaveStats {
+				status: SlaveStatus::Online,
+				successes: 0,
+				timeouts: 0,
+				errors: 0,
+				consecutive_failures: 0,
+				last_latency: None,
+				average_latency: None,
+				max_latency: None,
+			},
+		});
+	}
+
+	/// Remove a slave.
+	///
+	/// Returns false if there was no slave with the given address.
+	pub fn remove_slave(&mut self, address: u8) -> bool {
+		let Some(index) = self.slaves.iter().position(|slave| slave.address == address) else {
+			return false;
+		};
+		self.slaves.remove(index);
+		if self.next > index {
+			self.next -= 1;
+		}
+		true
+	}
+
+	/// Check if a slave with the given address was added.
+	pub fn contains(&self, address: u8) -> bool {
+		self.slaves.iter().any(|slave| slave.address == address)
+	}
+
+	/// Get the addresses of the slaves, in polling order.
+	pub fn addresses(&self) -> impl Iterator<Item = u8> + '_ {
+		self.slaves.iter().map(|slave| slave.address)
+	}
+
+	/// Set the minimum time between the end of one exchange and the start of the next request.
+	///
+	/// Some devices need time to switch their RS-485 transceiver back to receive mode before the bus is used again.
+	/// The default is zero.
+	pub fn set_request_gap(&mut self, gap: Duration) {
+		self.request_gap = gap;
+	}
+
+	/// Get the minimum time between the end of one exchange and the start of the next request.
+	pub fn get_request_gap(&self) -> Duration {
+		self.request_gap
+	}
+
+	/// Set how long a slave is skipped after a failed poll.
+	///
+	/// After the first failure, the slave is skipped for `initial`.
+	/// The time doubles for each consecutive failure, up to `max`.
+	/// A successful poll resets the backoff.
+	/// The default is one second initially, up to 30 seconds.
+	///
+	/// # Panics
+	/// This function panics if `initial` is larger than `max`.
+	pub fn set_backoff(&mut self, initial: Duration, max: Duration) {
+		assert!(initial <= max, "the initial backoff can not be larger than the maximum backoff");
+		self.initial_backoff = initial;
+		self.max_backoff = max;
+	}
+
+	/// Get the initial and maximum time that a slave is skipped after a failed poll.
+	pub fn get_backoff(&self) -> (Duration, Duration) {
+		(self.initial_backoff, self.max_backoff)
+	}
+
+	/// Set when slaves are marked offline.
+	///
+	/// A slave is marked offline after `threshold` consecutive polls timed out.
+	/// An offline slave is only polled once every `probe_interval`, instead of using the normal backoff,
+	/// so that devices that are switched off or disconnected do not take up bus time.
+	/// When an offline slave responds again, it is marked online.
+	/// Both changes are reported in [`PollResult::status_change`].
+	///
+	/// Pass `None` as threshold to never mark slaves offline, which is the default.
+	///
+	/// # Panics
+	/// This function panics if the threshold is zero.
+	pub fn set_offline_policy(&mut self, threshold: Option<u32>, probe_interval: Duration) {
+		assert!(threshold != Some(0), "the offline threshold can not be zero");
+		self.offline_threshold = threshold;
+		self.offline_probe_interval = probe_interval;
+	}
+
+	/// Get the number of consecutive timeouts after which a slave is marked offline, and the probe interval for offline slaves.
+	pub fn get_offline_policy(&self) -> (Option<u32>, Duration) {
+		(self.offline_threshold, self.offline_probe_interval)
+	}
+
+	/// Set a cancellation token to stop polling.
+	///
+	/// When the token is cancelled, [`Self::next()`] returns `None` instead of starting a new exchange.
+	/// An exchange that is already in progress is completed first and its result is returned,
+	/// so the bus is never left with a request that was cut off halfway.
+	#[cfg(any(feature = "doc", feature = "cancel"))]
+	#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "cancel")))]
+	pub fn set_cancel_token(&mut self, token: Option<CancellationToken>) {
+		self.cancel = Cancel::new(token);
+	}
+
+	/// Get the cancellation token of the scheduler.
+	#[cfg(any(feature = "doc", feature = "cancel"))]
+	#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "cancel")))]
+	pub fn get_cancel_token(&self) -> Option<&CancellationToken> {
+		self.cancel.token()
+	}
+
+	/// Get the statistics of a slave.
+	///
+	/// Returns `None` if there is no slave with the given address.
+	pub fn stats(&self, address: u8) -> Option<&SlaveStats> {
+		self.slaves.iter().find(|slave| slave.address == address).map(|slave| &slave.stats)
+	}
+
+	/// Poll the next slave and return the result.
+	///
+	/// Slaves that are skipped because of earlier failures are passed over.
+	/// If all slaves are skipped, this waits until the first of them may be polled again.
+	///
+	/// Returns `None` if there are no slaves, or if the cancellation token of the scheduler has been cancelled.
+	///
+	/// If the returned future is dropped before it completes, the exchange is aborted and the same slave is polled again next time.
+	pub async fn next(&mut self) -> Option<PollResult> {
+		if self.cancel.is_cancelled() {
+			return None;
+		}
+		let index = self.pick_slave()?;
+		let skip_until = self.slaves[index].skip_until;
+		let gap_end = self.last_request.map(|last_request| last_request + self.request_gap);
+		let wait_until = skip_until.max(gap_end);
+		if let Some(wait_until) = wait_until {
+			self.cancel.run(tokio::time::sleep_until(wait_until)).await?;
+		}
+
+		let count = self.slaves.len();
+		let slave = &mut self.slaves[index];
+		let request = (slave.request)();
+		let start = Instant::now();
+		let response = self.port.request(&request, slave.timeout, &mut slave.is_complete).await;
+		let latency = start.elapsed();
+		self.last_request = Some(Instant::now());
+		self.next = (index + 1) % count;
+
+		let previous_status = slave.stats.status;
+		let stats = &mut slave.stats;
+		match &response {
+			Ok(_) => {
+				stats.successes += 1;
+				stats.consecutive_failures = 0;
+				stats.status = SlaveStatus::Online;
+				stats.last_latency = Some(latency);
+				stats.max_latency = stats.max_latency.max(Some(latency));
+				slave.total_latency += latency;
+				stats.average_latency = Some(slave.total_latency / u32::try_from(stats.successes).unwrap_or(u32::MAX));
+				slave.consecutive_timeouts = 0;
+				slave.skip_until = None;
+			},
+			Err(e) => {
+				if e.kind() == std::io::ErrorKind::TimedOut {
+					stats.timeouts += 1;
+					slave.consecutive_timeouts = slave.consecutive_timeouts.saturating_add(1);
+				} else {
+					stats.errors += 1;
+					slave.consecutive_timeouts = 0;
+				}
+				stats.consecutive_failures = stats.consecutive_failures.saturating_add(1);
+				if self.offline_threshold.is_some_and(|threshold| slave.consecutive_timeouts >= threshold) {
+					stats.status = SlaveStatus::Offline;
+				}
+				let skip = match stats.status {
+					SlaveStatus::Offline => self.offline_probe_interval,
+					SlaveStatus::Online => {
+						let exponent = (stats.consecutive_failures - 1).min(16);
+						self.initial_backoff.saturating_mul(1 << exponent).min(self.max_backoff)
+					},
+				};
+				slave.skip_until = Some(Instant::now() + skip);
+			},
+		}
+
+		Some(PollResult {
+			address: slave.address,
+			response,
+			latency,
+			status_change: (slave.stats.status != previous_status).then_some(slave.stats.status),
+		})
+	}
+
+	/// Pick the next slave to poll.
+	///
+	/// This is the first slave in round-robin order that is not skipped,
+	/// or the slave that may be polled again first if all slaves are skipped.
+	fn pick_slave(&self) -> Option<usize> {
+		let count = self.slaves.len();
+		if count == 0 {
+			return None;
+		}
+		let now = Instant::now();
+		let order = (0..count).map(|offset| (self.next + offset) % count);
+		order.clone()
+			.find(|&index| self.slaves[index].skip_until.is_none_or(|skip_until| skip_until <= now))
+			.or_else(|| order.min_by_key(|&index| self.slaves[index].skip_until))
+	}
+}
@@ -0,0 +1,664 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use crate::SerialPort;
+
+/// Information about an available serial port.
+///
+/// Returned by [`SerialPort::available_ports_info()`].
+///
+/// If the `serde` feature is enabled, this implements `Serialize` and `Deserialize`,
+/// so that a list of ports can be sent to other processes directly.
+/// If the `schemars` feature is enabled, this also implements `JsonSchema`, to generate a JSON schema for the serialized form.
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[non_exhaustive]
+pub struct PortInfo {
+	/// The path or name to open the serial port with, like `/dev/ttyUSB0` or `COM3`.
+	pub path: PathBuf,
+
+	/// A human readable name of the device, like `USB Serial Port (COM7)`.
+	///
+	/// This is the friendly name that the Windows device manager shows for the device.
+	/// It is always `None` on other platforms.
+	pub friendly_name: Option<String>,
+
+	/// The device instance ID of the device, like `FTDIBUS\VID_0403+PID_6001+A50285BIA\0000`.
+	///
+	/// Unlike the COM port number, the instance ID of a USB device with a serial number does not change when it is plugged into another USB port.
+	/// The instance ID can be passed to [`SerialPort::open()`] instead of the COM port name.
+	///
+	/// It is always `None` on other platforms.
+	pub instance_id: Option<String>,
+
+	/// The device is a dial-in device, like `/dev/tty.usbserial-A50285BI` on macOS.
+	///
+	/// On macOS, each serial port has two device nodes: a dial-in device (`/dev/tty.*`) and a call-out device (`/dev/cu.*`).
+	/// The dial-in device is meant for answering incoming modem calls:
+	/// the OS expects the carrier detect line to be asserted and may hang up when it is not.
+	/// For everything else, use the call-out device.
+	/// See [`Self::prefer_callout()`] to remove the dial-in devices from a list of ports.
+	///
+	/// This is always `false` on other platforms.
+	pub dial_in: bool,
+
+	/// Information about the USB device that provides the serial port, if it is a USB device.
+	///
+	/// This is available on Linux, macOS and Windows.
+	/// On Windows, only the vendor and product ID are known.
+	pub usb: Option<UsbInfo>,
+
+	/// The properties of the device in the udev database.
+	///
+	/// This is only available on Linux, when the device is managed by udev.
+	pub udev: Option<UdevInfo>,
+}
+
+/// Information about the USB device that provides a serial port.
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[non_exhaustive]
+pub struct UsbInfo {
+	/// The USB vendor ID.
+	pub vendor_id: u16,
+
+	/// The USB product ID.
+	pub product_id: u16,
+
+	/// The manufacturer name reported by the device.
+	pub manufacturer: Option<String>,
+
+	/// The product name reported by the device.
+	pub product: Option<String>,
+
+	/// The serial number reported by the device.
+	pub serial_number: Option<String>,
+}
+
+/// The properties of a serial port in the udev database on Linux.
+///
+/// The common properties are parsed into separate fields.
+/// All properties are also available as strings in [`Self::properties`].
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[non_exhaustive]
+pub struct UdevInfo {
+	/// The model of the device, from the `ID_MODEL` property.
+	pub model: Option<String>,
+
+	/// The number of the USB interface that provides the serial port, from the `ID_USB_INTERFACE_NUM` property.
+	///
+	/// This distinguishes the serial ports of a USB device with multiple ports, like many cellular modems.
+	pub usb_interface_num: Option<u8>,
+
+	/// ModemManager considers the device as a possible modem, from the `ID_MM_CANDIDATE` property.
+	pub mm_candidate: bool,
+
+	/// ModemManager has been told to ignore the device, from the `ID_MM_DEVICE_IGNORE` or `ID_MM_PORT_IGNORE` property.
+	pub mm_ignore: bool,
+
+	/// All properties of the device.
+	pub properties: BTreeMap<String, String>,
+}
+
+impl PortInfo {
+	/// Remove the dial-in devices that have a corresponding call-out device from a list of ports.
+	///
+	/// On macOS, every serial port shows up twice: once as `/dev/tty.*` and once as `/dev/cu.*`.
+	/// Unless you are answering modem calls, you want the call-out device (see [`Self::dial_in`]).
+	/// This function keeps the order of the remaining ports.
+	/// On other platforms, the list is returned unchanged.
+	///
+	/// # Example
+	/// ```no_run
+	/// # fn example() -> std::io::Result<()> {
+	/// use serial2_tokio::{PortInfo, SerialPort};
+	///
+	/// for port in PortInfo::prefer_callout(SerialPort::available_ports_info()?) {
+	///     println!("{}", port.path.display());
+	/// }
+	/// # Ok(())
+	/// # }
+	/// ```
+	pub fn prefer_callout(ports: Vec<PortInfo>) -> Vec<PortInfo> {
+		let callout_names: Vec<_> = ports
+			.iter()
+			.filter(|port| !port.dial_in)
+			.filter_map(|port| port.path.file_name()?.to_str()?.strip_prefix("cu.").map(str::to_owned))
+			.collect();
+		ports
+			.into_iter()
+			.filter(|port| {
+				let name = port.path.file_name().and_then(|name| name.to_str()).and_then(|name| name.strip_prefix("tty."));
+				!(port.dial_in && name.is_some_and(|name| callout_names.iter().any(|callout| callout == name)))
+			})
+			.collect()
+	}
+
+	/// Remove the ports that ModemManager may open from a list of ports.
+	///
+	/// On Linux, ModemManager probes serial ports that udev marks as possible modem with the `ID_MM_CANDIDATE` property.
+	/// Opening such a port at the same time as ModemManager leads to garbled communication for both,
+	/// so gateway software should normally leave these ports alone.
+	///
+	/// This only removes ports if a ModemManager process is running,
+	/// and keeps ports that ModemManager has been told to ignore (see [`UdevInfo::mm_ignore`]).
+	/// This function keeps the order of the remaining ports.
+	/// On other platforms, the list is returned unchanged.
+	///
+	/// # Example
+	/// ```no_run
+	/// # fn example() -> std::io::Result<()> {
+	/// use serial2_tokio::{PortInfo, SerialPort};
+	///
+	/// for port in PortInfo::exclude_modem_manager(SerialPort::available_ports_info()?) {
+	///     println!("{}", port.path.display());
+	/// }
+	/// # Ok(())
+	/// # }
+	/// ```
+	pub fn exclude_modem_manager(ports: Vec<PortInfo>) -> Vec<PortInfo> {
+		let claimed = |port: &PortInfo| port.udev.as_ref().is_some_and(|udev| udev.mm_candidate && !udev.mm_ignore);
+		if !ports.iter().any(claimed) || !udev::modem_manager_running() {
+			return ports;
+		}
+		ports.into_iter().filter(|port| !claimed(port)).collect()
+	}
+}
+
+impl SerialPort {
+	/// Get a list of available serial ports with extra information about each port.
+	///
+	/// This returns the same serial ports as [`Self::available_ports()`],
+	/// but with extra information that helps users to choose a port, like the friendly name on Windows.
+	///
+	/// Not currently supported on all platforms.
+	/// On unsupported platforms, this function always returns an error.
+	///
+	/// # Example
+	/// ```no_run
+	/// # fn example() -> std::io::Result<()> {
+	/// use serial2_tokio::SerialPort;
+	///
+	/// for port in SerialPort::available_ports_info()? {
+	///     match &port.friendly_name {
+	///         Some(name) => println!("{}: {name}", port.path.display()),
+	///         None => println!("{}", port.path.display()),
+	///     }
+	/// }
+	/// # Ok(())
+	/// # }
+	/// ```
+	pub fn available_ports_info() -> std::io::Result<Vec<PortInfo>> {
+		sys::available_ports()
+	}
+}
+
+#[cfg(not(windows))]
+mod sys {
+	use super::PortInfo;
+
+	pub fn available_ports() -> std::io::Result<Vec<PortInfo>> {
+		let paths = crate::SerialPort::available_ports()?;
+		let usb = super::usb::usb_info(&paths);
+		let udev = super::udev::udev_info(&paths);
+		Ok(paths
+			.into_iter()
+			.zip(usb)
+			.zip(udev)
+			.map(|((path, usb), udev)| PortInfo {
+				dial_in: is_dial_in(&path),
+				path,
+				friendly_name: None,
+				instance_id: None,
+				usb,
+				udev,
+			})
+			.collect())
+	}
+
+	/// Check if a path is a dial-in device.
+	#[cfg(target_os = "macos")]
+	fn is_dial_in(path: &std::path::Path) -> bool {
+		path.file_name().and_then(|name| name.to_str()).is_some_and(|name| name.starts_with("tty."))
+	}
+
+	/// Check if a path is a dial-in device.
+	#[cfg(not(target_os = "macos"))]
+	fn is_dial_in(_path: &std::path::Path) -> bool {
+		false
+	}
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod usb {
+	use std::path::{Path, PathBuf};
+
+	use super::UsbInfo;
+
+	/// Get the USB information for a list of serial ports from sysfs.
+	pub fn usb_info(paths: &[PathBuf]) -> Vec<Option<UsbInfo>> {
+		paths.iter().map(|path| get(path)).collect()
+	}
+
+	fn get(path: &Path) -> Option<UsbInfo> {
+		let name = std::fs::canonicalize(path).ok()?.file_name()?.to_owned();
+		let device = std::fs::canonicalize(Path::new("/sys/class/tty").join(name).join("device")).ok()?;
+		// The USB device is a parent of the USB interface that provides the serial port.
+		let usb_device = device.ancestors().find(|dir| dir.join("idVendor").exists())?;
+		Some(UsbInfo {
+			vendor_id: u16::from_str_radix(&read_attribute(usb_device, "idVendor")?, 16).ok()?,
+			product_id: u16::from_str_radix(&read_attribute(usb_device, "idProduct")?, 16).ok()?,
+			manufacturer: read_attribute(usb_device, "manufacturer"),
+			product: read_attribute(usb_device, "product"),
+			serial_number: read_attribute(usb_device, "serial"),
+		})
+	}
+
+	/// Read a sysfs attribute, without the trailing newline.
+	fn read_attribute(dir: &Path, name: &str) -> Option<String> {
+		let value = std::fs::read_to_string(dir.join(name)).ok()?;
+		Some(value.trim_end().to_owned())
+	}
+}
+
+#[cfg(target_os = "linux")]
+mod udev {
+	use std::collections::BTreeMap;
+	use std::path::{Path, PathBuf};
+
+	use super::UdevInfo;
+
+	/// Get the udev properties for a list of serial ports from the udev database.
+	pub fn udev_info(paths: &[PathBuf]) -> Vec<Option<UdevInfo>> {
+		paths.iter().map(|path| get(path)).collect()
+	}
+
+	fn get(path: &Path) -> Option<UdevInfo> {
+		let name = std::fs::canonicalize(path).ok()?.file_name()?.to_owned();
+		// The database is indexed by the major and minor device number, like `c188:0`.
+		let dev = std::fs::read_to_string(Path::new("/sys/class/tty").join(name).join("dev")).ok()?;
+		let data = std::fs::read_to_string(format!("/run/udev/data/c{}", dev.trim_end())).ok()?;
+		let properties: BTreeMap<String, String> = data
+			.lines()
+			.filter_map(|line| line.strip_prefix("E:")?.split_once('='))
+			.map(|(key, value)| (key.to_owned(), value.to_owned()))
+			.collect();
+		let flag = |key: &str| properties.get(key).is_some_and(|value| value == "1");
+		Some(UdevInfo {
+			model: properties.get("ID_MODEL").cloned(),
+			usb_interface_num: properties.get("ID_USB_INTERFACE_NUM").and_then(|value| u8::from_str_radix(value, 16).ok()),
+			mm_candidate: flag("ID_MM_CANDIDATE"),
+			mm_ignore: flag("ID_MM_DEVICE_IGNORE") || flag("ID_MM_PORT_IGNORE"),
+			properties,
+		})
+	}
+
+	/// Check if a ModemManager process is running.
+	pub fn modem_manager_running() -> bool {
+		let Ok(entries) = std::fs::read_dir("/proc") else {
+			return false;
+		};
+		entries
+			.filter_map(|entry| entry.ok())
+			.filter(|entry| entry.file_name().to_str().is_some_and(|name| name.bytes().all(|byte| byte.is_ascii_digit())))
+			.any(|entry| std::fs::read_to_string(entry.path().join("comm")).is_ok_and(|comm| comm.trim_end() == "ModemManager"))
+	}
+}
+
+#[cfg(not(target_os = "linux"))]
+mod udev {
+	#[cfg(not(windows))]
+	use std::path::PathBuf;
+
+	#[cfg(not(windows))]
+	use super::UdevInfo;
+
+	/// The udev database is only available on Linux.
+	#[cfg(not(windows))]
+	pub fn udev_info(paths: &[PathBuf]) -> Vec<Option<UdevInfo>> {
+		vec![None; paths.len()]
+	}
+
+	/// ModemManager only runs on Linux.
+	pub fn modem_manager_running() -> bool {
+		false
+	}
+}
+
+#[cfg(target_os = "macos")]
+mod usb {
+	use std::ffi::{c_char, c_void, CStr};
+	use std::path::PathBuf;
+
+	use super::UsbInfo;
+
+	type CFTypeRef = *const c_void;
+	type CFStringRef = *const c_void;
+	type CFAllocatorRef = *const c_void;
+	type CFMutableDictionaryRef = *mut c_void;
+	type IoObject = u32;
+
+	const K_CF_STRING_ENCODING_UTF8: u32 = 0x0800_0100;
+	const K_CF_NUMBER_SINT32_TYPE: isize = 3;
+	const K_IO_REGISTRY_ITERATE_RECURSIVELY: u32 = 1;
+	const K_IO_REGISTRY_ITERATE_PARENTS: u32 = 2;
+	const K_IO_SERVICE_PLANE: &CStr = c"IOService";
+
+	#[link(name = "IOKit", kind = "framework")]
+	extern "C" {
+		fn IOServiceMatching(name: *const c_char) -> CFMutableDictionaryRef;
+		fn IOServiceGetMatchingServices(main_port: u32, matching: CFMutableDictionaryRef, existing: *mut IoObject) -> i32;
+		fn IOIteratorNext(iterator: IoObject) -> IoObject;
+		fn IOObjectRelease(object: IoObject) -> i32;
+		fn IORegistryEntryCreateCFProperty(entry: IoObject, key: CFStringRef, allocator: CFAllocatorRef, options: u32) -> CFTypeRef;
+		fn IORegistryEntrySearchCFProperty(
+			entry: IoObject,
+			plane: *const c_char,
+			key: CFStringRef,
+			allocator: CFAllocatorRef,
+			options: u32,
+		) -> CFTypeRef;
+	}
+
+	#[link(name = "CoreFoundation", kind = "framework")]
+	extern "C" {
+		fn CFStringCreateWithCString(allocator: CFAllocatorRef, string: *const c_char, encoding: u32) -> CFStringRef;
+		fn CFStringGetCString(string: CFStringRef, buffer: *mut c_char, size: isize, encoding: u32) -> u8;
+		fn CFStringGetTypeID() -> usize;
+		fn CFNumberGetValue(number: CFTypeRef, kind: isize, value: *mut c_void) -> u8;
+		fn CFNumberGetTypeID() -> usize;
+		fn CFGetTypeID(object: CFTypeRef) -> usize;
+		fn CFRelease(object: CFTypeRef);
+	}
+
+	/// Get the USB information for a list of serial ports from the IOKit registry.
+	pub fn usb_info(paths: &[PathBuf]) -> Vec<Option<UsbInfo>> {
+		let devices = unsafe { serial_devices() };
+		paths
+			.iter()
+			.map(|path| {
+				devices
+					.iter()
+					.find(|(callout, dialin, _)| callout.as_ref() == Some(path) || dialin.as_ref() == Some(path))
+					.and_then(|(_, _, usb)| usb.clone())
+			})
+			.collect()
+	}
+
+	/// Get the call-out device, dial-in device and USB information of all serial devices.
+	unsafe fn serial_devices() -> Vec<(Option<PathBuf>, Option<PathBuf>, Option<UsbInfo>)> {
+		let mut devices = Vec::new();
+		let matching = IOServiceMatching(c"IOSerialBSDClient".as_ptr());
+		if matching.is_null() {
+			return devices;
+		}
+		let mut iterator = 0;
+		// This consumes the reference to the matching dictionary.
+		if IOServiceGetMatchingServices(0, matching, &mut iterator) != 0 {
+			return devices;
+		}
+		loop {
+			let service = IOIteratorNext(iterator);
+			if service == 0 {
+				break;
+			}
+			let callout = string_property(service, c"IOCalloutDevice", false).map(PathBuf::from);
+			let dialin = string_property(service, c"IODialinDevice", false).map(PathBuf::from);
+			let usb = usb_properties(service);
+			devices.push((callout, dialin, usb));
+			IOObjectRelease(service);
+		}
+		IOObjectRelease(iterator);
+		devices
+	}
+
+	/// Get the USB properties of the USB device that is a parent of a serial device.
+	unsafe fn usb_properties(service: IoObject) -> Option<UsbInfo> {
+		Some(UsbInfo {
+			vendor_id: number_property(service, c"idVendor")?,
+			product_id: number_property(service, c"idProduct")?,
+			manufacturer: string_property(service, c"USB Vendor Name", true),
+			product: string_property(service, c"USB Product Name", true),
+			serial_number: string_property(service, c"USB Serial Number", true),
+		})
+	}
+
+	/// Get a property of a registry entry, or of its parents if `search_parents` is true.
+	unsafe fn property(service: IoObject, key: &CStr, search_parents: bool) -> Option<CFTypeRef> {
+		let key = CFStringCreateWithCString(std::ptr::null(), key.as_ptr(), K_CF_STRING_ENCODING_UTF8);
+		if key.is_null() {
+			return None;
+		}
+		let value = if search_parents {
+			IORegistryEntrySearchCFProperty(
+				service,
+				K_IO_SERVICE_PLANE.as_ptr(),
+				key,
+				std::ptr::null(),
+				K_IO_REGISTRY_ITERATE_RECURSIVELY | K_IO_REGISTRY_ITERATE_PARENTS,
+			)
+		} else {
+			IORegistryEntryCreateCFProperty(service, key, std::ptr::null(), 0)
+		};
+		CFRelease(key);
+		(!value.is_null()).then_some(value)
+	}
+
+	/// Get a string property of a registry entry.
+	unsafe fn string_property(service: IoObject, key: &CStr, search_parents: bool) -> Option<String> {
+		let value = property(service, key, search_parents)?;
+		let mut buffer = [0 as c_char; 256];
+		let ok = CFGetTypeID(value) == CFStringGetTypeID()
+			&& CFStringGetCString(value, buffer.as_mut_ptr(), buffer.len() as isize, K_CF_STRING_ENCODING_UTF8) != 0;
+		CFRelease(value);
+		if !ok {
+			return None;
+		}
+		Some(CStr::from_ptr(buffer.as_ptr()).to_string_lossy().into_owned())
+	}
+
+	/// Get a 16 bit number property of a registry entry or one of its parents.
+	unsafe fn number_property(service: IoObject, key: &CStr) -> Option<u16> {
+		let value = property(service, key, true)?;
+		let mut number: i32 = 0;
+		let ok = CFGetTypeID(value) == CFNumberGetTypeID()
+			&& CFNumberGetValue(value, K_CF_NUMBER_SINT32_TYPE, (&mut number as *mut i32).cast()) != 0;
+		CFRelease(value);
+		if !ok {
+			return None;
+		}
+		u16::try_from(number).ok()
+	}
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "macos", windows)))]
+mod usb {
+	use std::path::PathBuf;
+
+	use super::UsbInfo;
+
+	/// USB information is not supported on this platform.
+	pub fn usb_info(paths: &[PathBuf]) -> Vec<Option<UsbInfo>> {
+		vec![None; paths.len()]
+	}
+}
+
+#[cfg(windows)]
+mod sys {
+	use std::borrow::Cow;
+	use std::ffi::OsString;
+	use std::os::windows::ffi::OsStringExt;
+	use std::path::Path;
+
+	use winapi::shared::devguid::GUID_DEVCLASS_PORTS;
+	use winapi::shared::minwindef::DWORD;
+	use winapi::um::handleapi::INVALID_HANDLE_VALUE;
+	use winapi::um::setupapi::{
+		SetupDiDestroyDeviceInfoList,
+		SetupDiEnumDeviceInfo,
+		SetupDiGetClassDevsW,
+		SetupDiGetDeviceInstanceIdW,
+		SetupDiGetDeviceRegistryPropertyW,
+		SetupDiOpenDevRegKey,
+		DICS_FLAG_GLOBAL,
+		DIGCF_PRESENT,
+		DIREG_DEV,
+		HDEVINFO,
+		SPDRP_FRIENDLYNAME,
+		SP_DEVINFO_DATA,
+	};
+	use winapi::um::winnt::KEY_READ;
+	use winapi::um::winreg::{RegCloseKey, RegQueryValueExW};
+
+	use super::{PortInfo, UsbInfo};
+
+	/// A device information set that is destroyed when dropped.
+	struct DeviceInfoSet(HDEVINFO);
+
+	impl Drop for DeviceInfoSet {
+		fn drop(&mut self) {
+			unsafe {
+				SetupDiDestroyDeviceInfoList(self.0);
+			}
+		}
+	}
+
+	pub fn available_ports() -> std::io::Result<Vec<PortInfo>> {
+		unsafe {
+			let devices = SetupDiGetClassDevsW(&GUID_DEVCLASS_PORTS, std::ptr::null(), std::ptr::null_mut(), DIGCF_PRESENT);
+			if devices == INVALID_HANDLE_VALUE {
+				return Err(std::io::Error::last_os_error());
+			}
+			let devices = DeviceInfoSet(devices);
+
+			let mut ports = Vec::new();
+			for index in 0.. {
+				let mut device: SP_DEVINFO_DATA = std::mem::zeroed();
+				device.cbSize = std::mem::size_of::<SP_DEVINFO_DATA>() as DWORD;
+				if SetupDiEnumDeviceInfo(devices.0, index, &mut device) == 0 {
+					break;
+				}
+				// The ports class also contains parallel ports.
+				let Some(name) = port_name(&devices, &mut device).filter(|name| name.starts_with("COM")) else {
+					continue;
+				};
+				let instance_id = instance_id(&devices, &mut device);
+				ports.push(PortInfo {
+					path: name.into(),
+					friendly_name: friendly_name(&devices, &mut device),
+					usb: instance_id.as_deref().and_then(usb_info),
+					instance_id,
+					dial_in: false,
+					udev: None,
+				});
+			}
+			Ok(ports)
+		}
+	}
+
+	/// Get the COM port name of a device from the device registry key.
+	unsafe fn port_name(devices: &DeviceInfoSet, device: &mut SP_DEVINFO_DATA) -> Option<String> {
+		let key = SetupDiOpenDevRegKey(devices.0, device, DICS_FLAG_GLOBAL, 0, DIREG_DEV, KEY_READ);
+		if key.is_null() || std::ptr::eq(key.cast(), INVALID_HANDLE_VALUE) {
+			return None;
+		}
+		let value_name = to_wide("PortName");
+		let mut buffer = [0u16; 64];
+		let mut size = std::mem::size_of_val(&buffer) as DWORD;
+		let status = RegQueryValueExW(
+			key,
+			value_name.as_ptr(),
+			std::ptr::null_mut(),
+			std::ptr::null_mut(),
+			buffer.as_mut_ptr().cast(),
+			&mut size,
+		);
+		RegCloseKey(key);
+		if status != 0 {
+			return None;
+		}
+		from_wide(&buffer[..size as usize / 2])
+	}
+
+	/// Get the friendly name of a device.
+	unsafe fn friendly_name(devices: &DeviceInfoSet, device: &mut SP_DEVINFO_DATA) -> Option<String> {
+		let mut buffer = [0u16; 256];
+		let mut size = 0;
+		let ok = SetupDiGetDeviceRegistryPropertyW(
+			devices.0,
+			device,
+			SPDRP_FRIENDLYNAME,
+			std::ptr::null_mut(),
+			buffer.as_mut_ptr().cast(),
+			std::mem::size_of_val(&buffer) as DWORD,
+			&mut size,
+		);
+		if ok == 0 {
+			return None;
+		}
+		from_wide(&buffer[..(size as usize / 2).min(buffer.len())])
+	}
+
+	/// Get the device instance ID of a device.
+	unsafe fn instance_id(devices: &DeviceInfoSet, device: &mut SP_DEVINFO_DATA) -> Option<String> {
+		let mut buffer = [0u16; 256];
+		let mut size = 0;
+		let ok = SetupDiGetDeviceInstanceIdW(devices.0, device, buffer.as_mut_ptr(), buffer.len() as DWORD, &mut size);
+		if ok == 0 {
+			return None;
+		}
+		from_wide(&buffer[..(size as usize).min(buffer.len())])
+	}
+
+	/// Get the USB vendor and product ID from a device instance ID.
+	///
+	/// Instance IDs of USB devices contain the IDs in the form `VID_0403&PID_6001` or `VID_0403+PID_6001`.
+	fn usb_info(instance_id: &str) -> Option<UsbInfo> {
+		let id = |prefix: &str| {
+			let start = instance_id.find(prefix)? + prefix.len();
+			u16::from_str_radix(instance_id.get(start..start + 4)?, 16).ok()
+		};
+		Some(UsbInfo {
+			vendor_id: id("VID_")?,
+			product_id: id("PID_")?,
+			manufacturer: None,
+			product: None,
+			serial_number: None,
+		})
+	}
+
+	/// Resolve a device instance ID to the name of the COM port.
+	///
+	/// Other paths are returned unchanged.
+	pub fn resolve_instance_id(path: &Path) -> std::io::Result<Cow<'_, Path>> {
+		// COM port names and paths in the win32 device namespace never look like a device instance ID.
+		let name = path.to_string_lossy();
+		if !name.contains('\\') || name.starts_with('\\') {
+			return Ok(Cow::Borrowed(path));
+		}
+		available_ports()?
+			.into_iter()
+			.find(|port| port.instance_id.as_deref().is_some_and(|id| id.eq_ignore_ascii_case(&name)))
+			.map(|port| Cow::Owned(port.path))
+			.ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, format!("no serial port with device instance ID {name}")))
+	}
+
+	/// Convert a string to a nul terminated wide string.
+	fn to_wide(value: &str) -> Vec<u16> {
+		value.encode_utf16().chain(Some(0)).collect()
+	}
+
+	/// Convert a wide string to a string, stopping at the first nul character.
+	fn from_wide(value: &[u16]) -> Option<String> {
+		let end = value.iter().position(|&c| c == 0).unwrap_or(value.len());
+		let value = OsString::from_wide(&value[..end]).into_string().ok()?;
+		(!value.is_empty()).then_some(value)
+	}
+}
+
+#[cfg(windows)]
+pub(crate) use sys::resolve_instance_id;
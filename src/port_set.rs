@@ -0,0 +1,203 @@
+use std::path::Path;
+use std::pin::Pin;
+use std::task::Poll;
+
+use tokio::io::{AsyncRead, ReadBuf};
+
+use crate::{IntoSettings, SerialPort};
+
+/// The size of the buffer used to read from the ports.
+const READ_BUFFER_SIZE: usize = 4096;
+
+/// A set of serial ports that are read from a single task.
+///
+/// Gateways that handle many serial ports would normally need a separate task for each port.
+/// Instead, a [`PortSet`] reads from all its ports at once,
+/// and [`Self::next_event()`] returns the data received on any of them.
+///
+/// The ports are checked in round-robin order, starting after the port that produced the previous event.
+/// This prevents a single busy port from starving the others.
+///
+/// Each port is identified by the [`PortId`] returned when it was added.
+/// You can use [`Self::get()`] to write to a port or to change its settings.
+///
+/// # Example
+/// ```no_run
+/// # async fn example() -> std::io::Result<()> {
+/// use serial2_tokio::{PortEvent, PortSet};
+///
+/// let mut ports = PortSet::open_all(["/dev/ttyUSB0", "/dev/ttyUSB1", "/dev/ttyUSB2"], 115200)?;
+/// while let Some((id, event)) = ports.next_event().await {
+///     match event {
+///         PortEvent::Data(data) => println!("port {}: {data:?}", id.index()),
+///         PortEvent::Error(e) => eprintln!("port {}: {e}", id.index()),
+///         PortEvent::Closed => eprintln!("port {}: closed", id.index()),
+///         _ => (),
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default)]
+pub struct PortSet {
+	ports: Vec<Option<Entry>>,
+	next: usize,
+	buffer: Vec<u8>,
+}
+
+/// The identifier of a serial port in a [`PortSet`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct PortId(usize);
+
+/// An event from a serial port in a [`PortSet`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum PortEvent {
+	/// Data was received.
+	Data(Vec<u8>),
+
+	/// Reading from the serial port failed.
+	///
+	/// The port is no longer read from, but it stays in the set until it is removed.
+	Error(std::io::Error),
+
+	/// The serial port reported end-of-file.
+	///
+	/// The port is no longer read from, but it stays in the set until it is removed.
+	Closed,
+}
+
+#[derive(Debug)]
+struct Entry {
+	port: SerialPort,
+	active: bool,
+}
+
+impl PortSet {
+	/// Create an empty set.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Open all the given serial ports with the same settings.
+	///
+	/// The ports get identifiers in the same order as the paths, so the first port has index 0.
+	///
+	/// If any port fails to open, the error is returned and the ports that were already opened are closed again.
+	/// Use [`Self::insert()`] if you need to handle failures for each port separately.
+	pub fn open_all<I, P, S>(paths: I, settings: S) -> std::io::Result<Self>
+	where
+		I: IntoIterator<Item = P>,
+		P: AsRef<Path>,
+		S: IntoSettings + Clone,
+	{
+		let mut set = Self::new();
+		for path in paths {
+			set.insert(SerialPort::open(path, settings.clone())?);
+		}
+		Ok(set)
+	}
+
+	/// Add a serial port to the set.
+	pub fn insert(&mut self, port: SerialPort) -> PortId {
+		let entry = Entry { port, active: true };
+		match self.ports.iter().position(Option::is_none) {
+			Some(index) => {
+				self.ports[index] = Some(entry);
+				PortId(index)
+			},
+			None => {
+				self.ports.push(Some(entry));
+				PortId(self.ports.len() - 1)
+			},
+		}
+	}
+
+	/// Remove a serial port from the set.
+	///
+	/// The identifier may be reused for a port that is inserted later.
+	pub fn remove(&mut self, id: PortId) -> Option<SerialPort> {
+		let entry = self.ports.get_mut(id.0)?.take()?;
+		Some(entry.port)
+	}
+
+	/// Get a serial port from the set.
+	pub fn get(&self, id: PortId) -> Option<&SerialPort> {
+		self.ports.get(id.0)?.as_ref().map(|entry| &entry.port)
+	}
+
+	/// Iterate over all serial ports in the set.
+	pub fn iter(&self) -> impl Iterator<Item = (PortId, &SerialPort)> {
+		self.ports.iter()
+			.enumerate()
+			.filter_map(|(index, entry)| Some((PortId(index), &entry.as_ref()?.port)))
+	}
+
+	/// Get the number of serial ports in the set.
+	pub fn len(&self) -> usize {
+		self.ports.iter().filter(|entry| entry.is_some()).count()
+	}
+
+	/// Check if the set contains no serial ports.
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+
+	/// Wait for the next event from any serial port in the set.
+	///
+	/// Returns `None` if no ports are being read from,
+	/// either because the set is empty or because all ports reported an error or end-of-file.
+	///
+	/// This function is cancel safe: if the future is dropped before it completes, no data is lost.
+	pub async fn next_event(&mut self) -> Option<(PortId, PortEvent)> {
+		std::future::poll_fn(|cx| self.poll_next_event(cx)).await
+	}
+
+	/// Poll all active ports once, starting after the port that produced the previous event.
+	fn poll_next_event(&mut self, cx: &mut std::task::Context<'_>) -> Poll<Option<(PortId, PortEvent)>> {
+		let count = self.ports.len();
+		if self.buffer.is_empty() {
+			self.buffer.resize(READ_BUFFER_SIZE, 0);
+		}
+
+		let mut any_active = false;
+		for offset in 0..count {
+			let index = (self.next + offset) % count;
+			let Some(entry) = &mut self.ports[index] else {
+				continue;
+			};
+			if !entry.active {
+				continue;
+			}
+			any_active = true;
+
+			let mut buffer = ReadBuf::new(&mut self.buffer);
+			let event = match Pin::new(&mut entry.port).poll_read(cx, &mut buffer) {
+				Poll::Pending => continue,
+				Poll::Ready(Ok(())) if buffer.filled().is_empty() => PortEvent::Closed,
+				Poll::Ready(Ok(())) => PortEvent::Data(buffer.filled().to_vec()),
+				Poll::Ready(Err(e)) => PortEvent::Error(e),
+			};
+			if !matches!(event, PortEvent::Data(_)) {
+				entry.active = false;
+			}
+			self.next = index + 1;
+			return Poll::Ready(Some((PortId(index), event)));
+		}
+
+		if any_active {
+			Poll::Pending
+		} else {
+			Poll::Ready(None)
+		}
+	}
+}
+
+impl PortId {
+	/// Get the index of the port.
+	///
+	/// For sets created with [`PortSet::open_all()`], this is the position of the port in the list of paths.
+	pub fn index(self) -> usize {
+		self.0
+	}
+}
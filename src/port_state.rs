@@ -0,0 +1,168 @@
+use std::future::Future;
+
+use crate::{IntoSettings, SerialPort, Settings};
+
+/// A snapshot of the configuration and the output lines of a serial port.
+///
+/// Created by [`SerialPort::snapshot()`] and applied again with [`SerialPort::restore()`].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct PortState {
+	/// The settings of the serial port.
+	pub settings: Settings,
+
+	/// The state of the RTS line, or `None` if it could not be read.
+	pub rts: Option<bool>,
+
+	/// The state of the DTR line, or `None` if it could not be read.
+	pub dtr: Option<bool>,
+}
+
+/// A guard that restores the state of a serial port when it is dropped.
+///
+/// Created by [`SerialPort::snapshot_guard()`].
+/// The guard dereferences to the serial port, so it can be used to change the configuration temporarily.
+///
+/// Dropping the guard ignores errors from restoring the state.
+/// Use [`Self::restore()`] to restore the state and check for errors,
+/// or [`Self::keep()`] to keep the current state instead.
+#[derive(Debug)]
+pub struct PortStateGuard<'a> {
+	port: &'a SerialPort,
+	state: Option<PortState>,
+}
+
+impl SerialPort {
+	/// Take a snapshot of the configuration and the RTS and DTR lines of the serial port.
+	///
+	/// If the state of the RTS or DTR line can not be read (for example on a pseudo-terminal),
+	/// it is set to `None` in the snapshot, and [`Self::restore()`] leaves that line alone.
+	///
+	/// Returns an error if the configuration can not be read.
+	pub fn snapshot(&self) -> std::io::Result<PortState> {
+		let output_lines = self.inner.read_output_lines().ok();
+		Ok(PortState {
+			settings: self.get_configuration()?,
+			rts: output_lines.map(|(rts, _dtr)| rts),
+			dtr: output_lines.map(|(_rts, dtr)| dtr),
+		})
+	}
+
+	/// Restore the configuration and the RTS and DTR lines from a snapshot.
+	///
+	/// The configuration is applied before the output lines,
+	/// so that the lines are left in the saved state even if the configuration affects them.
+	/// If one of the steps fails, the other steps are still performed before the first error is returned.
+	pub fn restore(&self, state: &PortState) -> std::io::Result<()> {
+		let settings = self.set_configuration(&state.settings);
+		let rts = state.rts.map_or(Ok(()), |rts| self.set_rts(rts));
+		let dtr = state.dtr.map_or(Ok(()), |dtr| self.set_dtr(dtr));
+		settings.and(rts).and(dtr)
+	}
+
+	/// Take a snapshot of the state of the serial port, and restore it when the returned guard is dropped.
+	///
+	/// This makes it easy to change the configuration temporarily and reliably return to the previous state,
+	/// also when an error is returned with the `?` operator.
+	///
+	/// # Example
+	/// ```no_run
+	/// # async fn example() -> std::io::Result<()> {
+	/// use serial2_tokio::SerialPort;
+	///
+	/// let port = SerialPort::open("/dev/ttyUSB0", 115200)?;
+	/// {
+	///     // Talk to the bootloader at 9600 baud.
+	///     let port = port.snapshot_guard()?;
+	///     port.modify_configuration(|settings| settings.set_baud_rate(9600))?;
+	///     port.write_all(b"BOOT\r").await?;
+	/// }
+	/// // Back at 115200 baud, even if one of the calls above failed.
+	/// # Ok(())
+	/// # }
+	/// ```
+	pub fn snapshot_guard(&self) -> std::io::Result<PortStateGuard<'_>> {
+		Ok(PortStateGuard {
+			port: self,
+			state: Some(self.snapshot()?),
+		})
+	}
+
+	/// Apply settings temporarily while running an async function, and restore the previous state afterwards.
+	///
+	/// The `settings` are applied on top of the current configuration, like [`Self::open()`] does.
+	/// The previous configuration and RTS and DTR lines are restored when the future returned by `function` completes,
+	/// also when it returns an error, and when the future returned by this function is dropped before it completes.
+	///
+	/// If `function` returns an error, that error is returned and an error from restoring the state is ignored.
+	/// Otherwise, an error from restoring the state is returned instead of the result of `function`.
+	///
+	/// The function does not receive the serial port, but it can simply borrow it:
+	///
+	/// # Example
+	/// ```no_run
+	/// # async fn example() -> std::io::Result<()> {
+	/// use serial2_tokio::SerialPort;
+	///
+	/// let port = SerialPort::open("/dev/ttyUSB0", 115200)?;
+	/// let mut response = [0; 64];
+	/// // Probe the bootloader at 9600 baud, and return to 115200 baud afterwards.
+	/// let read = port.with_temporary_config(9600, || async {
+	///     port.write_all(b"BOOT\r").await?;
+	///     port.read(&mut response).await
+	/// }).await?;
+	/// # Ok(())
+	/// # }
+	/// ```
+	pub async fn with_temporary_config<F, Fut, T, E>(&self, settings: impl IntoSettings, function: F) -> Result<T, E>
+	where
+		F: FnOnce() -> Fut,
+		Fut: Future<Output = Result<T, E>>,
+		E: From<std::io::Error>,
+	{
+		let guard = self.snapshot_guard()?;
+		let mut temporary = guard.state().settings.clone();
+		settings.apply_to_settings(&mut temporary)?;
+		self.set_configuration(&temporary)?;
+		let value = function().await?;
+		guard.restore()?;
+		Ok(value)
+	}
+}
+
+impl PortStateGuard<'_> {
+	/// Get the saved state.
+	pub fn state(&self) -> &PortState {
+		// The state is only taken by functions that consume the guard.
+		self.state.as_ref().unwrap()
+	}
+
+	/// Restore the saved state now and report errors.
+	pub fn restore(mut self) -> std::io::Result<()> {
+		match self.state.take() {
+			Some(state) => self.port.restore(&state),
+			None => Ok(()),
+		}
+	}
+
+	/// Keep the current state of the serial port instead of restoring the saved state.
+	pub fn keep(mut self) {
+		self.state = None;
+	}
+}
+
+impl std::ops::Deref for PortStateGuard<'_> {
+	type Target = SerialPort;
+
+	fn deref(&self) -> &SerialPort {
+		self.port
+	}
+}
+
+impl Drop for PortStateGuard<'_> {
+	fn drop(&mut self) {
+		if let Some(state) = self.state.take() {
+			self.port.restore(&state).ok();
+		}
+	}
+}
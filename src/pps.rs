@@ -0,0 +1,360 @@
+//! Timestamp pulse-per-second (PPS) signals on a modem status line.
+//!
+//! GNSS receivers can output a pulse at the start of each second, which is often connected to the DCD or CTS line of a serial port.
+//! A [`PpsReceiver`] timestamps the edges of that pulse, so that the system clock can be synchronized to the GNSS time,
+//! for example together with the NMEA sentences read from the same serial port.
+//!
+//! On Linux, the receiver tries to use the PPS line discipline of the kernel for the DCD line.
+//! The kernel then timestamps the edges in the interrupt handler of the serial port, which gives the lowest possible latency.
+//! This requires a kernel with `CONFIG_PPS_CLIENT_LDISC`, and permission to change the line discipline and to open the `/dev/pps*` device.
+//! Otherwise, the receiver falls back to waiting for line changes with the `TIOCMIWAIT` ioctl on a background thread,
+//! and timestamps the edges when the thread wakes up.
+//! Use [`PpsReceiver::is_kernel_timestamped()`] to check which method is used.
+//!
+//! Other platforms are not supported yet.
+//!
+//! # Example
+//! ```no_run
+//! # async fn example() -> std::io::Result<()> {
+//! use serial2_tokio::SerialPort;
+//! use serial2_tokio::pps::{PpsLine, PpsReceiver};
+//!
+//! let port = SerialPort::open("/dev/ttyUSB0", 9600)?;
+//! let mut pps = PpsReceiver::new(&port, PpsLine::Cd)?;
+//! loop {
+//!     let event = pps.recv().await?;
+//!     if event.assert {
+//!         println!("pulse at {:?}", event.time);
+//!     }
+//! }
+//! # }
+//! ```
+
+use std::time::SystemTime;
+
+use crate::SerialPort;
+
+/// The modem status line carrying the PPS signal.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum PpsLine {
+	/// The Carrier Detect line.
+	///
+	/// This is the line used by most GNSS receivers and the only line supported by the PPS line discipline of Linux.
+	Cd,
+
+	/// The Clear To Send line.
+	Cts,
+}
+
+/// An edge of the PPS signal.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct PpsEvent {
+	/// The time of the edge, according to the system clock.
+	pub time: SystemTime,
+
+	/// True if the line became active (the assert edge), false if it became inactive (the clear edge).
+	///
+	/// Most GNSS receivers mark the start of the second with the assert edge.
+	pub assert: bool,
+}
+
+/// Receives timestamped edges of a PPS signal.
+///
+/// See the [module documentation](self) for more information.
+#[derive(Debug)]
+pub struct PpsReceiver {
+	events: tokio::sync::mpsc::Receiver<std::io::Result<PpsEvent>>,
+	kernel: bool,
+	#[cfg(target_os = "linux")]
+	restore_ldisc: Option<serial2::SerialPort>,
+}
+
+impl PpsReceiver {
+	/// Start timestamping the PPS signal on a line of the serial port.
+	///
+	/// The serial port can still be used for normal communication.
+	///
+	/// Returns an error of kind [`std::io::ErrorKind::Unsupported`] on platforms other than Linux.
+	pub fn new(port: &SerialPort, line: PpsLine) -> std::io::Result<Self> {
+		#[cfg(target_os = "linux")] {
+			let port = port.inner.with_raw(|raw| raw.try_clone())?;
+			let (sender, events) = tokio::sync::mpsc::channel(16);
+			if line == PpsLine::Cd {
+				if let Some(source) = sys::KernelPps::open(&port) {
+					std::thread::Builder::new()
+						.name("serial2-tokio-pps".into())
+						.spawn(move || sys::run_kernel(source, sender))?;
+					return Ok(Self { events, kernel: true, restore_ldisc: Some(port) });
+				}
+			}
+			let mask = match line {
+				PpsLine::Cd => libc::TIOCM_CD,
+				PpsLine::Cts => libc::TIOCM_CTS,
+			};
+			std::thread::Builder::new()
+				.name("serial2-tokio-pps".into())
+				.spawn(move || sys::run_modem_wait(port, mask, sender))?;
+			Ok(Self { events, kernel: false, restore_ldisc: None })
+		}
+		#[cfg(not(target_os = "linux"))] {
+			let _ = (port, line);
+			Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "PPS signals are only supported on Linux"))
+		}
+	}
+
+	/// Check if the edges are timestamped by the kernel.
+	///
+	/// If this returns false, the edges are timestamped by a background thread after the kernel reported a line change.
+	pub fn is_kernel_timestamped(&self) -> bool {
+		self.kernel
+	}
+
+	/// Wait for the next edge of the PPS signal.
+	///
+	/// Edges that are not received in time are buffered, up to a limit.
+	/// If an error occurs, the receiver stops and all following calls return an error too.
+	pub async fn recv(&mut self) -> std::io::Result<PpsEvent> {
+		match self.events.recv().await {
+			Some(event) => event,
+			None => Err(std::io::Error::new(std::io::ErrorKind::NotConnected, "the PPS receiver stopped after an error")),
+		}
+	}
+}
+
+impl Drop for PpsReceiver {
+	fn drop(&mut self) {
+		// The background thread stops at the next edge or timeout, after it notices that the channel is closed.
+		#[cfg(target_os = "linux")]
+		if let Some(port) = &self.restore_ldisc {
+			sys::set_ldisc(port, sys::N_TTY).ok();
+		}
+	}
+}
+
+#[cfg(target_os = "linux")]
+mod sys {
+	use std::io::{BufRead, Read};
+	use std::os::fd::AsRawFd;
+	use std::time::{Duration, SystemTime};
+
+	use tokio::sync::mpsc::Sender;
+
+	use super::PpsEvent;
+
+	/// The default line discipline.
+	pub const N_TTY: libc::c_int = 0;
+
+	/// The PPS line discipline.
+	const N_PPS: libc::c_int = 18;
+
+	/// `_IOWR('p', 0xa4, struct pps_fdata *)`.
+	///
+	/// The read/write direction bits have the same value on all Linux architectures for this request.
+	const PPS_FETCH: libc::c_ulong = 0xC000_70A4 | ((std::mem::size_of::<usize>() as libc::c_ulong) << 16);
+
+	/// Flag to indicate that a `pps_ktime` is not set.
+	const PPS_TIME_INVALID: u32 = 1;
+
+	/// How long to wait for an edge before checking if the receiver was dropped.
+	const FETCH_TIMEOUT: Duration = Duration::from_secs(1);
+
+	#[repr(C)]
+	#[derive(Default)]
+	struct PpsKtime {
+		sec: i64,
+		nsec: i32,
+		flags: u32,
+	}
+
+	#[repr(C)]
+	#[derive(Default)]
+	struct PpsKinfo {
+		assert_sequence: u32,
+		clear_sequence: u32,
+		assert_tu: PpsKtime,
+		clear_tu: PpsKtime,
+		#[allow(dead_code)]
+		current_mode: libc::c_int,
+	}
+
+	#[repr(C)]
+	#[derive(Default)]
+	struct PpsFdata {
+		info: PpsKinfo,
+		timeout: PpsKtime,
+	}
+
+	/// A PPS device created by the PPS line discipline.
+	pub struct KernelPps {
+		device: std::fs::File,
+		assert_sequence: u32,
+		clear_sequence: u32,
+	}
+
+	impl KernelPps {
+		/// Switch the serial port to the PPS line discipline and open the PPS device.
+		///
+		/// Returns `None` and restores the default line discipline if this fails for any reason.
+		pub fn open(port: &serial2::SerialPort) -> Option<Self> {
+			set_ldisc(port, N_PPS).ok()?;
+			let device = find_pps_device(port).and_then(|path| std::fs::File::open(path).ok());
+			let Some(device) = device else {
+				set_ldisc(port, N_TTY).ok();
+				return None;
+			};
+			let mut pps = Self {
+				device,
+				assert_sequence: 0,
+				clear_sequence: 0,
+			};
+			// A zero timeout returns the current state without waiting.
+			match pps.fetch(Duration::ZERO) {
+				Ok(info) => {
+					let info = info.unwrap_or_default();
+					pps.assert_sequence = info.assert_sequence;
+					pps.clear_sequence = info.clear_sequence;
+					Some(pps)
+				},
+				Err(_) => {
+					set_ldisc(port, N_TTY).ok();
+					None
+				},
+			}
+		}
+
+		/// Wait for a new edge, and return the latest timestamps, or `None` on timeout.
+		fn fetch(&self, timeout: Duration) -> std::io::Result<Option<PpsKinfo>> {
+			let mut data = PpsFdata {
+				timeout: PpsKtime {
+					sec: timeout.as_secs() as i64,
+					nsec: timeout.subsec_nanos() as i32,
+					flags: 0,
+				},
+				..Default::default()
+			};
+			unsafe {
+				if libc::ioctl(self.device.as_raw_fd(), PPS_FETCH as _, &mut data) != 0 {
+					let error = std::io::Error::last_os_error();
+					return match error.raw_os_error() {
+						Some(libc::ETIMEDOUT | libc::EINTR) => Ok(None),
+						_ => Err(error),
+					};
+				}
+			}
+			Ok(Some(data.info))
+		}
+
+		/// Wait for new edges and return them in chronological order.
+		fn wait(&mut self) -> std::io::Result<Vec<PpsEvent>> {
+			let Some(info) = self.fetch(FETCH_TIMEOUT)? else {
+				return Ok(Vec::new());
+			};
+			let mut events = Vec::with_capacity(2);
+			if info.assert_sequence != self.assert_sequence {
+				self.assert_sequence = info.assert_sequence;
+				events.extend(to_system_time(&info.assert_tu).map(|time| PpsEvent { time, assert: true }));
+			}
+			if info.clear_sequence != self.clear_sequence {
+				self.clear_sequence = info.clear_sequence;
+				events.extend(to_system_time(&info.clear_tu).map(|time| PpsEvent { time, assert: false }));
+			}
+			events.sort_by_key(|event| event.time);
+			Ok(events)
+		}
+	}
+
+	/// Forward the edges reported by the PPS device until the receiver is dropped or an error occurs.
+	pub fn run_kernel(mut source: KernelPps, sender: Sender<std::io::Result<PpsEvent>>) {
+		while !sender.is_closed() {
+			match source.wait() {
+				Ok(events) => {
+					for event in events {
+						if sender.blocking_send(Ok(event)).is_err() {
+							return;
+						}
+					}
+				},
+				Err(e) => {
+					sender.blocking_send(Err(e)).ok();
+					return;
+				},
+			}
+		}
+	}
+
+	/// Forward the changes of a modem status line until the receiver is dropped or an error occurs.
+	pub fn run_modem_wait(port: serial2::SerialPort, mask: libc::c_int, sender: Sender<std::io::Result<PpsEvent>>) {
+		let fd = port.as_raw_fd();
+		while !sender.is_closed() {
+			let result = unsafe {
+				if libc::ioctl(fd, libc::TIOCMIWAIT as _, mask) != 0 {
+					Err(std::io::Error::last_os_error())
+				} else {
+					let time = SystemTime::now();
+					let mut bits: libc::c_int = 0;
+					if libc::ioctl(fd, libc::TIOCMGET as _, &mut bits) != 0 {
+						Err(std::io::Error::last_os_error())
+					} else {
+						Ok(PpsEvent { time, assert: bits & mask != 0 })
+					}
+				}
+			};
+			match result {
+				Err(e) if e.raw_os_error() == Some(libc::EINTR) => continue,
+				Err(e) if matches!(e.raw_os_error(), Some(libc::ENOTTY | libc::EINVAL)) => {
+					let e = std::io::Error::new(std::io::ErrorKind::Unsupported, "the serial port driver does not support waiting for modem status line changes");
+					sender.blocking_send(Err(e)).ok();
+					return;
+				},
+				Err(e) => {
+					sender.blocking_send(Err(e)).ok();
+					return;
+				},
+				Ok(event) => {
+					if sender.blocking_send(Ok(event)).is_err() {
+						return;
+					}
+				},
+			}
+		}
+	}
+
+	/// Set the line discipline of a serial port.
+	pub fn set_ldisc(port: &serial2::SerialPort, ldisc: libc::c_int) -> std::io::Result<()> {
+		unsafe {
+			if libc::ioctl(port.as_raw_fd(), libc::TIOCSETD as _, &ldisc) != 0 {
+				return Err(std::io::Error::last_os_error());
+			}
+		}
+		Ok(())
+	}
+
+	/// Find the PPS device that the PPS line discipline created for a serial port.
+	fn find_pps_device(port: &serial2::SerialPort) -> Option<std::path::PathBuf> {
+		let tty_path = std::fs::read_link(format!("/proc/self/fd/{}", port.as_raw_fd())).ok()?;
+		for entry in std::fs::read_dir("/sys/class/pps").ok()? {
+			let Ok(entry) = entry else {
+				continue;
+			};
+			let mut path = String::new();
+			let Ok(file) = std::fs::File::open(entry.path().join("path")) else {
+				continue;
+			};
+			if std::io::BufReader::new(file).take(256).read_line(&mut path).is_err() {
+				continue;
+			}
+			if std::path::Path::new(path.trim_end()) == tty_path {
+				return Some(std::path::Path::new("/dev").join(entry.file_name()));
+			}
+		}
+		None
+	}
+
+	/// Convert a kernel timestamp to a [`SystemTime`].
+	fn to_system_time(time: &PpsKtime) -> Option<SystemTime> {
+		if time.flags & PPS_TIME_INVALID != 0 || time.sec < 0 || time.nsec < 0 {
+			return None;
+		}
+		Some(SystemTime::UNIX_EPOCH + Duration::new(time.sec as u64, time.nsec as u32))
+	}
+}
@@ -0,0 +1,86 @@
+use crate::{CharSize, FlowControl, IntoSettings, Parity, Settings, StopBits};
+
+/// Serial port settings for common classes of devices.
+///
+/// A preset sets the baud rate, character size, parity, stop bits and flow control required by a standard,
+/// and puts the serial port in raw mode (see [`Settings::set_raw()`]).
+/// Pass a preset to [`SerialPort::open()`][crate::SerialPort::open()] like any other [`IntoSettings`],
+/// or apply it to existing settings with [`IntoSettings::apply_to_settings()`].
+///
+/// # Example
+/// ```no_run
+/// # async fn example() -> std::io::Result<()> {
+/// use serial2_tokio::{Preset, SerialPort};
+///
+/// let port = SerialPort::open("/dev/ttyUSB0", Preset::Nmea0183)?;
+/// let mut buffer = [0; 256];
+/// let read = port.read(&mut buffer).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum Preset {
+	/// Modbus RTU with the given baud rate: 8 data bits, even parity, 1 stop bit, no flow control.
+	///
+	/// Even parity is the default of the Modbus specification.
+	/// Devices configured without parity use 2 stop bits instead, which you can set with [`Settings::set_stop_bits()`] afterwards.
+	ModbusRtu(u32),
+
+	/// NMEA 0183 for GPS receivers and marine electronics: 4800 baud, 8 data bits, no parity, 1 stop bit, no flow control.
+	Nmea0183,
+
+	/// High speed NMEA 0183, used by AIS transponders: 38400 baud, 8 data bits, no parity, 1 stop bit, no flow control.
+	Nmea0183HighSpeed,
+
+	/// DMX512 for stage lighting: 250000 baud, 8 data bits, no parity, 2 stop bits, no flow control.
+	///
+	/// Each DMX512 packet must start with a break, which you send with [`SerialPort::set_break()`][crate::SerialPort::set_break()].
+	Dmx512,
+
+	/// MIDI: 31250 baud, 8 data bits, no parity, 1 stop bit, no flow control.
+	///
+	/// Most serial ports can only use this baud rate if the platform supports arbitrary baud rates.
+	Midi,
+}
+
+impl Preset {
+	/// Get the baud rate of the preset.
+	pub fn baud_rate(&self) -> u32 {
+		match *self {
+			Self::ModbusRtu(baud_rate) => baud_rate,
+			Self::Nmea0183 => 4800,
+			Self::Nmea0183HighSpeed => 38400,
+			Self::Dmx512 => 250000,
+			Self::Midi => 31250,
+		}
+	}
+
+	/// Get the parity of the preset.
+	pub fn parity(&self) -> Parity {
+		match self {
+			Self::ModbusRtu(_) => Parity::Even,
+			_ => Parity::None,
+		}
+	}
+
+	/// Get the number of stop bits of the preset.
+	pub fn stop_bits(&self) -> StopBits {
+		match self {
+			Self::Dmx512 => StopBits::Two,
+			_ => StopBits::One,
+		}
+	}
+}
+
+impl IntoSettings for Preset {
+	fn apply_to_settings(self, settings: &mut Settings) -> std::io::Result<()> {
+		settings.set_raw();
+		settings.set_baud_rate(self.baud_rate())?;
+		settings.set_char_size(CharSize::Bits8);
+		settings.set_parity(self.parity());
+		settings.set_stop_bits(self.stop_bits());
+		settings.set_flow_control(FlowControl::None);
+		Ok(())
+	}
+}
@@ -0,0 +1,180 @@
+use crate::SerialPort;
+
+impl SerialPort {
+	/// Open a connected pair of pseudo-terminals.
+	///
+	/// Both pseudo-terminals are configured in raw mode.
+	/// This can be used to test code that uses serial ports without actual hardware.
+	#[cfg(any(feature = "doc", all(unix, feature = "unix")))]
+	#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "unix")))]
+	pub fn pair() -> std::io::Result<(Self, Self)> {
+		#[cfg(all(unix, feature = "unix"))] {
+			let (pty_a, pty_b) = serial2::SerialPort::pair()?;
+			Ok((sys::wrap(pty_a, "pty")?, sys::wrap(pty_b, "pty")?))
+		}
+		#[cfg(not(all(unix, feature = "unix")))] {
+			unreachable!("this code is only enabled on Unix platforms or during documentation generation")
+		}
+	}
+
+	/// Open a connected pair of pseudo-terminals that transfer data at the speed of a real serial link.
+	///
+	/// This works like [`Self::pair()`], but both pseudo-terminals are configured with the given baud rate (8N1),
+	/// and [write pacing][Self::set_write_pacing()] limits the data written to each side to what the baud rate allows.
+	/// This exposes timing problems that an instant transfer hides, such as timeouts that are too short for a large message.
+	///
+	/// The data is transferred in bursts of at most 10 milliseconds worth of data,
+	/// and the timers of Tokio have a resolution of one millisecond,
+	/// so the timing approximates a real serial link rather than matching it for each byte.
+	/// If you change the write pacing or the configuration of a side, the throttling of that side changes accordingly.
+	/// For byte-accurate timing with latency and bit errors, use a [`SimulatedPort`][crate::SimulatedPort] pair instead.
+	///
+	/// Returns an error of kind [`std::io::ErrorKind::InvalidInput`] if the baud rate is 0.
+	///
+	/// # Example
+	/// ```no_run
+	/// # async fn example() -> std::io::Result<()> {
+	/// use serial2_tokio::SerialPort;
+	///
+	/// let (a, b) = SerialPort::pair_with_baud(9600)?;
+	/// // Takes about one second: 960 characters of 10 bits at 9600 baud.
+	/// a.write_all(&[b'x'; 960]).await?;
+	/// # Ok(())
+	/// # }
+	/// ```
+	#[cfg(any(feature = "doc", all(unix, feature = "unix")))]
+	#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "unix")))]
+	pub fn pair_with_baud(baud_rate: u32) -> std::io::Result<(Self, Self)> {
+		if baud_rate == 0 {
+			return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "the baud rate can not be 0"));
+		}
+		let (a, b) = Self::pair()?;
+		for port in [&a, &b] {
+			let mut settings = port.get_configuration()?;
+			settings.set_baud_rate(baud_rate)?;
+			settings.set_char_size(crate::CharSize::Bits8);
+			settings.set_parity(crate::Parity::None);
+			settings.set_stop_bits(crate::StopBits::One);
+			port.set_configuration(&settings)?;
+			let char_time = crate::pacing::char_time(&settings)?;
+			let rate = (std::time::Duration::from_secs(1).as_nanos() / char_time.as_nanos().max(1)).max(1);
+			port.set_write_pacing(Some(crate::WritePacing::BytesPerSecond(u32::try_from(rate).unwrap_or(u32::MAX))))?;
+		}
+		Ok((a, b))
+	}
+
+	/// Create a pseudo-terminal and return the controlling side together with the path of the terminal device.
+	///
+	/// The path (for example `/dev/pts/3`) can be given to an external program that expects a serial port,
+	/// while the returned [`SerialPort`] is used to simulate the device on the other end.
+	/// The `settings` are applied to the terminal device before this function returns,
+	/// and the returned side is configured in raw mode.
+	///
+	/// Note that on some platforms (including Linux), reading from the returned side fails with an I/O error
+	/// while no process has the terminal device opened.
+	/// If you need to wait for the other program, you can keep the terminal device open yourself.
+	///
+	/// # Example
+	/// ```no_run
+	/// # async fn example() -> std::io::Result<()> {
+	/// use serial2_tokio::SerialPort;
+	///
+	/// let (device, path) = SerialPort::open_pty(115200)?;
+	/// println!("simulated device available at {}", path.display());
+	/// let mut buffer = [0; 256];
+	/// let read = device.read(&mut buffer).await?;
+	/// # Ok(())
+	/// # }
+	/// ```
+	#[cfg(any(feature = "doc", all(unix, feature = "unix")))]
+	#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "unix")))]
+	pub fn open_pty(settings: impl crate::IntoSettings) -> std::io::Result<(Self, std::path::PathBuf)> {
+		#[cfg(all(unix, feature = "unix"))] {
+			let (master, path) = sys::open_pty()?;
+			serial2::SerialPort::open(&path, settings)?;
+			let name = path.to_string_lossy().into_owned();
+			Ok((sys::wrap(master, &name)?, path))
+		}
+		#[cfg(not(all(unix, feature = "unix")))] {
+			let _ = settings;
+			unreachable!("this code is only enabled on Unix platforms or during documentation generation")
+		}
+	}
+}
+
+#[cfg(all(unix, feature = "unix"))]
+mod sys {
+	use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+	use std::path::PathBuf;
+
+	use crate::SerialPort;
+
+	/// Wrap a pseudo-terminal in a [`SerialPort`], after making it non-blocking.
+	pub fn wrap(pty: serial2::SerialPort, name: &str) -> std::io::Result<SerialPort> {
+		unsafe {
+			let fd = pty.as_raw_fd();
+			let flags = check(libc::fcntl(fd, libc::F_GETFL))?;
+			check(libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK))?;
+		}
+		let inner = crate::inner::SerialPort::wrap(pty)?;
+		Ok(SerialPort::from_inner(inner, crate::stats::StatsCollector::new(name)))
+	}
+
+	/// Open a pseudo-terminal master in raw mode and return it with the path of the slave.
+	pub fn open_pty() -> std::io::Result<(serial2::SerialPort, PathBuf)> {
+		unsafe {
+			let fd = check(libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY))?;
+			let fd = OwnedFd::from_raw_fd(fd);
+			check(libc::fcntl(fd.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC))?;
+			check(libc::grantpt(fd.as_raw_fd()))?;
+			check(libc::unlockpt(fd.as_raw_fd()))?;
+			let path = pts_name(&fd)?;
+			let mut master = serial2::SerialPort::from(fd);
+			let mut settings = master.get_configuration()?;
+			settings.set_raw();
+			master.set_configuration(&settings)?;
+			Ok((master, path))
+		}
+	}
+
+	#[cfg(any(target_os = "ios", target_os = "macos", target_os = "netbsd", target_os = "illumos", target_os = "solaris"))]
+	fn pts_name(master: &OwnedFd) -> std::io::Result<PathBuf> {
+		use std::os::unix::ffi::OsStrExt;
+		static PTSNAME: std::sync::Mutex<()> = std::sync::Mutex::new(());
+		let _lock = PTSNAME.lock();
+		unsafe {
+			let name = libc::ptsname(master.as_raw_fd());
+			if name.is_null() {
+				return Err(std::io::Error::last_os_error());
+			}
+			let name = std::ffi::CStr::from_ptr(name);
+			Ok(std::ffi::OsStr::from_bytes(name.to_bytes()).into())
+		}
+	}
+
+	#[cfg(not(any(target_os = "ios", target_os = "macos", target_os = "netbsd", target_os = "illumos", target_os = "solaris")))]
+	fn pts_name(master: &OwnedFd) -> std::io::Result<PathBuf> {
+		use std::os::unix::ffi::OsStrExt;
+		let mut buffer = vec![0u8; 256];
+		loop {
+			unsafe {
+				match libc::ptsname_r(master.as_raw_fd(), buffer.as_mut_ptr().cast(), buffer.len()) {
+					0 => {
+						let name = std::ffi::CStr::from_ptr(buffer.as_ptr().cast());
+						return Ok(std::ffi::OsStr::from_bytes(name.to_bytes()).into());
+					},
+					libc::ERANGE if buffer.len() < 4096 => buffer.resize(buffer.len() * 2, 0),
+					error => return Err(std::io::Error::from_raw_os_error(error)),
+				}
+			}
+		}
+	}
+
+	fn check(ret: i32) -> std::io::Result<i32> {
+		if ret == -1 {
+			Err(std::io::Error::last_os_error())
+		} else {
+			Ok(ret)
+		}
+	}
+}
@@ -0,0 +1,127 @@
+use std::future::Future;
+use std::task::Poll;
+
+use crate::SerialPort;
+
+/// An error returned by [`SerialPort::run()`].
+///
+/// The error tells you which of the two tasks failed.
+/// It can be converted into a [`std::io::Error`] with the same kind as the original error.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ReaderWriterError {
+	/// The reader task failed.
+	Reader(std::io::Error),
+
+	/// The writer task failed, or draining the output buffer after the writer task completed failed.
+	Writer(std::io::Error),
+}
+
+impl ReaderWriterError {
+	/// Get a reference to the underlying I/O error.
+	pub fn io_error(&self) -> &std::io::Error {
+		match self {
+			Self::Reader(e) => e,
+			Self::Writer(e) => e,
+		}
+	}
+}
+
+impl std::fmt::Display for ReaderWriterError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Reader(e) => write!(f, "reader task failed: {e}"),
+			Self::Writer(e) => write!(f, "writer task failed: {e}"),
+		}
+	}
+}
+
+impl std::error::Error for ReaderWriterError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		Some(self.io_error())
+	}
+}
+
+impl From<ReaderWriterError> for std::io::Error {
+	fn from(error: ReaderWriterError) -> Self {
+		Self::new(error.io_error().kind(), error)
+	}
+}
+
+impl SerialPort {
+	/// Run a reader task and a writer task on the serial port concurrently.
+	///
+	/// The `reader` and `writer` functions are called with a reference to the serial port,
+	/// and the futures they return are polled concurrently until both have completed.
+	/// When the writer task completes successfully, the output buffer is drained (see [`Self::drain()`]),
+	/// so that all data it wrote has been transmitted when this function returns.
+	///
+	/// As soon as one of the tasks fails, the other task is dropped and the error is returned,
+	/// together with the direction of the task that failed.
+	/// Reads and writes that are in progress when a task is dropped are aborted without losing received data,
+	/// but a write may have been partially completed.
+	///
+	/// Returns the results of both tasks if both complete successfully.
+	///
+	/// # Example
+	/// ```no_run
+	/// # async fn example() -> std::io::Result<()> {
+	/// use serial2_tokio::SerialPort;
+	///
+	/// let port = SerialPort::open("/dev/ttyUSB0", 115200)?;
+	/// let (received, ()) = port.run(
+	///     |port| async move {
+	///         let mut received = Vec::new();
+	///         let mut buffer = [0; 256];
+	///         while !received.ends_with(b"OK\r\n") {
+	///             let read = port.read(&mut buffer).await?;
+	///             received.extend_from_slice(&buffer[..read]);
+	///         }
+	///         Ok(received)
+	///     },
+	///     |port| async move {
+	///         port.write_all(b"ATI\r\n").await
+	///     },
+	/// ).await?;
+	/// println!("{}", String::from_utf8_lossy(&received));
+	/// # Ok(())
+	/// # }
+	/// ```
+	pub async fn run<'a, R, RF, A, W, WF, B>(&'a self, reader: R, writer: W) -> Result<(A, B), ReaderWriterError>
+	where
+		R: FnOnce(&'a SerialPort) -> RF,
+		RF: Future<Output = std::io::Result<A>>,
+		W: FnOnce(&'a SerialPort) -> WF,
+		WF: Future<Output = std::io::Result<B>>,
+	{
+		let mut reader = std::pin::pin!(reader(self));
+		let mut writer = std::pin::pin!(async {
+			let value = writer(self).await?;
+			self.drain().await?;
+			Ok(value)
+		});
+		let mut read_result = None;
+		let mut write_result = None;
+
+		std::future::poll_fn(|cx| {
+			if read_result.is_none() {
+				if let Poll::Ready(result) = reader.as_mut().poll(cx) {
+					read_result = Some(result.map_err(ReaderWriterError::Reader)?);
+				}
+			}
+			if write_result.is_none() {
+				if let Poll::Ready(result) = writer.as_mut().poll(cx) {
+					write_result = Some(result.map_err(ReaderWriterError::Writer)?);
+				}
+			}
+			match (read_result.take(), write_result.take()) {
+				(Some(a), Some(b)) => Poll::Ready(Ok((a, b))),
+				(a, b) => {
+					read_result = a;
+					write_result = b;
+					Poll::Pending
+				},
+			}
+		}).await
+	}
+}
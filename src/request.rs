@@ -0,0 +1,105 @@
+use std::time::Duration;
+
+use crate::SerialPort;
+
+impl SerialPort {
+	/// Send a request and wait for the matching response.
+	///
+	/// This performs a complete request/response exchange:
+	/// it discards stale data from the input buffer, writes the request,
+	/// and then reads until `is_complete` returns true for the data received so far.
+	/// The received data is returned, including the bytes that completed the response.
+	///
+	/// Concurrent calls to this function on the same [`SerialPort`] are serialized,
+	/// so the response of one request can not be mixed up with another request from a different task.
+	/// Reads and writes that do not use this function are not blocked,
+	/// so you should not read from the serial port in another task while using this function.
+	///
+	/// Returns an error of kind [`std::io::ErrorKind::TimedOut`] if no complete response was received within `timeout`,
+	/// or an error of kind [`std::io::ErrorKind::UnexpectedEof`] if the serial port reported end-of-file.
+	///
+	/// # Example
+	/// ```no_run
+	/// # async fn example() -> std::io::Result<()> {
+	/// use serial2_tokio::SerialPort;
+	/// use std::time::Duration;
+	///
+	/// let port = SerialPort::open("/dev/ttyUSB0", 9600)?;
+	/// let response = port.request(b"*IDN?\n", Duration::from_secs(1), |data| data.ends_with(b"\n")).await?;
+	/// println!("{}", String::from_utf8_lossy(&response));
+	/// # Ok(())
+	/// # }
+	/// ```
+	pub async fn request<F>(&self, request: &[u8], timeout: Duration, mut is_complete: F) -> std::io::Result<Vec<u8>>
+	where
+		F: FnMut(&[u8]) -> bool,
+	{
+		let _lock = self.request_lock.lock().await;
+		self.discard_input_buffer()?;
+
+		let exchange = async {
+			self.write_all(request).await?;
+			let mut response = Vec::new();
+			let mut buffer = [0; 256];
+			loop {
+				match self.read(&mut buffer).await? {
+					0 => return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof)),
+					read => response.extend_from_slice(&buffer[..read]),
+				}
+				if is_complete(&response) {
+					return Ok(response);
+				}
+			}
+		};
+
+		tokio::time::timeout(timeout, exchange).await
+			.map_err(|_| std::io::Error::new(std::io::ErrorKind::TimedOut, "timeout while waiting for response"))?
+	}
+}
+
+#[cfg(test)]
+#[cfg(all(unix, feature = "unix"))]
+mod test {
+	use super::*;
+	use crate::test_util::{expect_then_reply, pair_with_timeout};
+	use crate::ReadCoalescing;
+
+	/// Answer one request with `response` on the other side of a pseudo-terminal pair.
+	fn respond(port: SerialPort, request: &'static [u8], response: &'static [u8]) -> tokio::task::JoinHandle<()> {
+		tokio::spawn(async move {
+			expect_then_reply(&port, request, response).await;
+		})
+	}
+
+	#[tokio::test]
+	async fn request_returns_response() {
+		let (port, device, timeout) = pair_with_timeout();
+		let device = respond(device, b"ping\n", b"pong\n");
+		let response = port.request(b"ping\n", timeout, |data| data.ends_with(b"\n")).await.unwrap();
+		assert_eq!(response, b"pong\n");
+		device.await.unwrap();
+	}
+
+	#[tokio::test]
+	async fn request_discards_coalesced_data() {
+		let (port, device, timeout) = pair_with_timeout();
+		port.set_read_coalescing(Some(ReadCoalescing::new(1, Duration::from_millis(10)))).unwrap();
+
+		// Read one byte of stale data, so the rest ends up in the internal buffer.
+		device.write_all(b"stale\n").await.unwrap();
+		let mut buffer = [0; 1];
+		assert_eq!(port.read(&mut buffer).await.unwrap(), 1);
+
+		let device = respond(device, b"ping\n", b"pong\n");
+		let response = port.request(b"ping\n", timeout, |data| data.ends_with(b"\n")).await.unwrap();
+		assert_eq!(response, b"pong\n");
+		device.await.unwrap();
+	}
+
+	#[tokio::test]
+	async fn request_times_out() {
+		let (port, _device) = SerialPort::pair().unwrap();
+		let error = port.request(b"ping\n", Duration::from_millis(50), |data| data.ends_with(b"\n")).await.unwrap_err();
+		assert_eq!(error.kind(), std::io::ErrorKind::TimedOut);
+	}
+}
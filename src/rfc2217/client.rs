@@ -0,0 +1,574 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, ReadBuf};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpStream, ToSocketAddrs};
+use tokio::sync::{mpsc, oneshot, watch, Mutex};
+
+use super::telnet::{self, command, control, modem_state, option, purge, Event};
+use crate::task::AbortOnDrop;
+use crate::{tcp, CharSize, FlowControl, Parity, StopBits};
+
+/// A serial port on a remote RFC 2217 server.
+///
+/// The remote serial port can be read from and written to concurrently from multiple tasks,
+/// just like a local [`SerialPort`][crate::SerialPort].
+/// Configuration changes and modem control line operations are sent to the server as RFC 2217 commands.
+/// These functions are asynchronous, because they wait for the response of the server.
+///
+/// Received data is buffered until it is read, so that a slow reader does not delay the responses to commands.
+/// When too much data is buffered, the server is asked to suspend sending data with the FLOWCONTROL-SUSPEND command,
+/// and to resume once the data has been read.
+/// If the server ignores that request, data is no longer read from the connection until the buffer has room again.
+/// The modem status lines are reported by the server when they change, and the last reported state is cached.
+///
+/// The remote serial port also implements [`AsyncRead`] and [`AsyncWrite`],
+/// so it can be used with codecs and other code that is generic over the transport.
+///
+/// See the [module documentation](super) for more information.
+///
+/// # Example
+/// ```no_run
+/// # async fn example() -> std::io::Result<()> {
+/// use serial2_tokio::rfc2217::RemoteSerialPort;
+///
+/// let port = RemoteSerialPort::open("rfc2217://192.168.1.20:2217").await?;
+/// port.set_baud_rate(115200).await?;
+/// port.write_all(b"hello").await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct RemoteSerialPort {
+	writer: Arc<Mutex<OwnedWriteHalf>>,
+	reader: Mutex<DataReader>,
+	shared: Arc<Shared>,
+	command_lock: Mutex<()>,
+	/// The command timeout in nanoseconds.
+	command_timeout: AtomicU64,
+	/// Set when the modem state mask has been sent to the server.
+	modem_state_mask_sent: AtomicBool,
+	/// The pending write through [`AsyncWrite`].
+	write: std::sync::Mutex<Option<WriteFuture>>,
+	_task: AbortOnDrop,
+}
+
+type WriteFuture = Pin<Box<dyn Future<Output = std::io::Result<usize>> + Send>>;
+
+/// The maximum number of received chunks of data that are buffered.
+///
+/// Each chunk holds the data of one read from the connection, so at most [`RECEIVE_BUFFER_SIZE`] bytes.
+/// This leaves plenty of room above [`SUSPEND_THRESHOLD`] for the data that the server sent before it received the FLOWCONTROL-SUSPEND command.
+const MAX_BUFFERED_CHUNKS: usize = 1024;
+
+/// The size of the buffer used to read from the connection.
+const RECEIVE_BUFFER_SIZE: usize = 4096;
+
+/// Ask the server to suspend sending data when this many bytes are buffered.
+const SUSPEND_THRESHOLD: usize = 64 * 1024;
+
+/// Ask the server to resume sending data when the buffered data drops to this many bytes.
+const RESUME_THRESHOLD: usize = 16 * 1024;
+
+/// Received data that has not been read yet.
+struct DataReader {
+	channel: mpsc::Receiver<Vec<u8>>,
+	pending: Vec<u8>,
+	position: usize,
+}
+
+/// State shared with the background task.
+struct Shared {
+	response: std::sync::Mutex<Option<(u8, oneshot::Sender<Vec<u8>>)>>,
+	closed: AtomicBool,
+	/// The last modem state reported by the server.
+	modem_state: watch::Sender<Option<u8>>,
+	/// The number of received bytes that have not been read yet.
+	buffered: AtomicUsize,
+	/// Set when the server has been asked to suspend sending data.
+	suspended: AtomicBool,
+}
+
+impl RemoteSerialPort {
+	/// Open a remote serial port by URL.
+	///
+	/// The URL must have the form `rfc2217://host:port`.
+	pub async fn open(url: &str) -> std::io::Result<Self> {
+		let address = url.strip_prefix("rfc2217://")
+			.ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "URL must start with rfc2217://"))?;
+		let address = address.trim_end_matches('/');
+		Self::connect(address).await
+	}
+
+	/// Connect to a remote serial port on an RFC 2217 server.
+	pub async fn connect(address: impl ToSocketAddrs) -> std::io::Result<Self> {
+		let stream = TcpStream::connect(address).await?;
+		stream.set_nodelay(true).ok();
+		let (reader, writer) = stream.into_split();
+		let writer = Arc::new(Mutex::new(writer));
+
+		let mut options = telnet::Options::new(
+			&[option::BINARY, option::SUPPRESS_GO_AHEAD, option::COM_PORT],
+			&[option::BINARY, option::SUPPRESS_GO_AHEAD],
+		);
+		let mut greeting = Vec::new();
+		options.request_all(&mut greeting);
+		tcp::write_all_locked(&writer, &greeting).await?;
+
+		let shared = Arc::new(Shared {
+			response: std::sync::Mutex::new(None),
+			closed: AtomicBool::new(false),
+			modem_state: watch::Sender::new(None),
+			buffered: AtomicUsize::new(0),
+			suspended: AtomicBool::new(false),
+		});
+		let (data_tx, data_rx) = mpsc::channel(MAX_BUFFERED_CHUNKS);
+		let task = tokio::spawn(receive(reader, writer.clone(), shared.clone(), options, data_tx));
+
+		Ok(Self {
+			writer,
+			reader: Mutex::new(DataReader {
+				channel: data_rx,
+				pending: Vec::new(),
+				position: 0,
+			}),
+			shared,
+			command_lock: Mutex::new(()),
+			command_timeout: AtomicU64::new(duration_to_nanos(Duration::from_secs(3))),
+			modem_state_mask_sent: AtomicBool::new(false),
+			write: std::sync::Mutex::new(None),
+			_task: AbortOnDrop(task.abort_handle()),
+		})
+	}
+
+	/// Set the maximum time to wait for the server to respond to a command.
+	///
+	/// The default is 3 seconds.
+	pub fn set_command_timeout(&self, timeout: Duration) {
+		self.command_timeout.store(duration_to_nanos(timeout), Ordering::Relaxed);
+	}
+
+	/// Get the maximum time to wait for the server to respond to a command.
+	pub fn get_command_timeout(&self) -> Duration {
+		Duration::from_nanos(self.command_timeout.load(Ordering::Relaxed))
+	}
+
+	/// Read bytes from the remote serial port.
+	///
+	/// Returns `Ok(0)` when the connection to the server has been closed.
+	pub async fn read(&self, buf: &mut [u8]) -> std::io::Result<usize> {
+		let read = {
+			let mut reader = self.reader.lock().await;
+			std::future::poll_fn(|cx| reader.poll_read(cx, buf)).await
+		};
+		if self.shared.data_read(read) {
+			resume(&self.writer).await;
+		}
+		Ok(read)
+	}
+
+	/// Write bytes to the remote serial port.
+	///
+	/// This always writes the whole buffer, so the returned length is equal to the length of `buf`.
+	pub async fn write(&self, buf: &[u8]) -> std::io::Result<usize> {
+		self.write_all(buf).await?;
+		Ok(buf.len())
+	}
+
+	/// Write all bytes to the remote serial port.
+	pub async fn write_all(&self, buf: &[u8]) -> std::io::Result<()> {
+		let mut escaped = Vec::with_capacity(buf.len());
+		telnet::escape(buf, &mut escaped);
+		tcp::write_all_locked(&self.writer, &escaped).await
+	}
+
+	/// Get the signature of the server.
+	pub async fn get_signature(&self) -> std::io::Result<String> {
+		let response = self.command(command::SIGNATURE, &[]).await?;
+		Ok(String::from_utf8_lossy(&response).into_owned())
+	}
+
+	/// Set the baud rate of the remote serial port.
+	///
+	/// Returns an error if the server did not apply the requested baud rate.
+	pub async fn set_baud_rate(&self, baud_rate: u32) -> std::io::Result<()> {
+		let applied = self.baud_rate_command(baud_rate).await?;
+		check_applied(applied == baud_rate, "baud rate")
+	}
+
+	/// Get the baud rate of the remote serial port.
+	pub async fn get_baud_rate(&self) -> std::io::Result<u32> {
+		self.baud_rate_command(0).await
+	}
+
+	async fn baud_rate_command(&self, baud_rate: u32) -> std::io::Result<u32> {
+		let response = self.command(command::SET_BAUDRATE, &baud_rate.to_be_bytes()).await?;
+		let response = response.try_into().map_err(|_| invalid_response())?;
+		Ok(u32::from_be_bytes(response))
+	}
+
+	/// Set the character size of the remote serial port.
+	pub async fn set_char_size(&self, char_size: CharSize) -> std::io::Result<()> {
+		let applied = self.char_size_command(telnet::encode_char_size(char_size)).await?;
+		check_applied(applied == char_size, "character size")
+	}
+
+	/// Get the character size of the remote serial port.
+	pub async fn get_char_size(&self) -> std::io::Result<CharSize> {
+		self.char_size_command(0).await
+	}
+
+	async fn char_size_command(&self, value: u8) -> std::io::Result<CharSize> {
+		let response = self.command_u8(command::SET_DATASIZE, value).await?;
+		telnet::decode_char_size(response).ok_or_else(invalid_response)
+	}
+
+	/// Set the parity mode of the remote serial port.
+	pub async fn set_parity(&self, parity: Parity) -> std::io::Result<()> {
+		let applied = self.parity_command(telnet::encode_parity(parity)).await?;
+		check_applied(applied == parity, "parity")
+	}
+
+	/// Get the parity mode of the remote serial port.
+	pub async fn get_parity(&self) -> std::io::Result<Parity> {
+		self.parity_command(0).await
+	}
+
+	async fn parity_command(&self, value: u8) -> std::io::Result<Parity> {
+		let response = self.command_u8(command::SET_PARITY, value).await?;
+		telnet::decode_parity(response).ok_or_else(invalid_response)
+	}
+
+	/// Set the number of stop bits of the remote serial port.
+	pub async fn set_stop_bits(&self, stop_bits: StopBits) -> std::io::Result<()> {
+		let applied = self.stop_bits_command(telnet::encode_stop_bits(stop_bits)).await?;
+		check_applied(applied == stop_bits, "stop bits")
+	}
+
+	/// Get the number of stop bits of the remote serial port.
+	pub async fn get_stop_bits(&self) -> std::io::Result<StopBits> {
+		self.stop_bits_command(0).await
+	}
+
+	async fn stop_bits_command(&self, value: u8) -> std::io::Result<StopBits> {
+		let response = self.command_u8(command::SET_STOPSIZE, value).await?;
+		telnet::decode_stop_bits(response).ok_or_else(invalid_response)
+	}
+
+	/// Set the flow control mode of the remote serial port.
+	pub async fn set_flow_control(&self, flow_control: FlowControl) -> std::io::Result<()> {
+		let response = self.command_u8(command::SET_CONTROL, telnet::encode_flow_control(flow_control)).await?;
+		let applied = telnet::decode_flow_control(response).ok_or_else(invalid_response)?;
+		check_applied(applied == flow_control, "flow control")
+	}
+
+	/// Get the flow control mode of the remote serial port.
+	pub async fn get_flow_control(&self) -> std::io::Result<FlowControl> {
+		let response = self.command_u8(command::SET_CONTROL, control::REQUEST_FLOW).await?;
+		telnet::decode_flow_control(response).ok_or_else(invalid_response)
+	}
+
+	/// Set the state of the Ready To Send line of the remote serial port.
+	pub async fn set_rts(&self, state: bool) -> std::io::Result<()> {
+		let value = if state { control::RTS_ON } else { control::RTS_OFF };
+		let applied = self.command_u8(command::SET_CONTROL, value).await?;
+		check_applied(applied == value, "RTS state")
+	}
+
+	/// Set the state of the Data Terminal Ready line of the remote serial port.
+	pub async fn set_dtr(&self, state: bool) -> std::io::Result<()> {
+		let value = if state { control::DTR_ON } else { control::DTR_OFF };
+		let applied = self.command_u8(command::SET_CONTROL, value).await?;
+		check_applied(applied == value, "DTR state")
+	}
+
+	/// Set or clear the break state of the remote serial port.
+	pub async fn set_break(&self, enable: bool) -> std::io::Result<()> {
+		let value = if enable { control::BREAK_ON } else { control::BREAK_OFF };
+		let applied = self.command_u8(command::SET_CONTROL, value).await?;
+		check_applied(applied == value, "break state")
+	}
+
+	/// Read the state of the Clear To Send line of the remote serial port.
+	pub async fn read_cts(&self) -> std::io::Result<bool> {
+		Ok(self.modem_state().await? & modem_state::CTS != 0)
+	}
+
+	/// Read the state of the Data Set Ready line of the remote serial port.
+	pub async fn read_dsr(&self) -> std::io::Result<bool> {
+		Ok(self.modem_state().await? & modem_state::DSR != 0)
+	}
+
+	/// Read the state of the Ring Indicator line of the remote serial port.
+	pub async fn read_ri(&self) -> std::io::Result<bool> {
+		Ok(self.modem_state().await? & modem_state::RI != 0)
+	}
+
+	/// Read the state of the Carrier Detect line of the remote serial port.
+	pub async fn read_cd(&self) -> std::io::Result<bool> {
+		Ok(self.modem_state().await? & modem_state::CD != 0)
+	}
+
+	/// Get the modem state last reported by the server.
+	///
+	/// The first call sets the modem state mask on the server, so that it reports the modem state.
+	/// Until the first report is received, this waits for at most the command timeout.
+	async fn modem_state(&self) -> std::io::Result<u8> {
+		if !self.modem_state_mask_sent.load(Ordering::Acquire) {
+			let mask = modem_state::CD | modem_state::RI | modem_state::DSR | modem_state::CTS;
+			self.command_u8(command::SET_MODEMSTATE_MASK, mask).await?;
+			self.modem_state_mask_sent.store(true, Ordering::Release);
+		}
+
+		let mut modem_state = self.shared.modem_state.subscribe();
+		let state = tokio::time::timeout(self.get_command_timeout(), modem_state.wait_for(Option::is_some)).await;
+		match state {
+			Ok(Ok(state)) => Ok(state.unwrap_or_default()),
+			Ok(Err(_)) => Err(std::io::ErrorKind::NotConnected.into()),
+			Err(_) => Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "RFC 2217 server did not report the modem state")),
+		}
+	}
+
+	/// Discard the input and output buffers of the remote serial port.
+	pub async fn discard_buffers(&self) -> std::io::Result<()> {
+		self.command_u8(command::PURGE_DATA, purge::BOTH).await?;
+		Ok(())
+	}
+
+	/// Discard the input buffer of the remote serial port.
+	pub async fn discard_input_buffer(&self) -> std::io::Result<()> {
+		self.command_u8(command::PURGE_DATA, purge::RECEIVE).await?;
+		Ok(())
+	}
+
+	/// Discard the output buffer of the remote serial port.
+	pub async fn discard_output_buffer(&self) -> std::io::Result<()> {
+		self.command_u8(command::PURGE_DATA, purge::TRANSMIT).await?;
+		Ok(())
+	}
+
+	async fn command_u8(&self, code: u8, value: u8) -> std::io::Result<u8> {
+		match self.command(code, &[value]).await?.as_slice() {
+			&[response] => Ok(response),
+			_ => Err(invalid_response()),
+		}
+	}
+
+	/// Send a command and wait for the response of the server.
+	async fn command(&self, code: u8, value: &[u8]) -> std::io::Result<Vec<u8>> {
+		let _lock = self.command_lock.lock().await;
+		let (response_tx, response_rx) = oneshot::channel();
+		*self.shared.response.lock().unwrap_or_else(|e| e.into_inner()) = Some((code + command::SERVER_OFFSET, response_tx));
+		if self.shared.closed.load(Ordering::Acquire) {
+			return Err(std::io::ErrorKind::NotConnected.into());
+		}
+
+		let mut message = Vec::new();
+		telnet::com_port_command(code, value, &mut message);
+		tcp::write_all_locked(&self.writer, &message).await?;
+
+		match tokio::time::timeout(self.get_command_timeout(), response_rx).await {
+			Ok(Ok(response)) => Ok(response),
+			Ok(Err(_)) => Err(std::io::ErrorKind::NotConnected.into()),
+			Err(_) => Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "no response from RFC 2217 server")),
+		}
+	}
+}
+
+impl DataReader {
+	/// Copy buffered data into `buf`, waiting for more data if there is none.
+	///
+	/// Returns zero when the connection has been closed and all data has been read.
+	fn poll_read(&mut self, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<usize> {
+		if self.position == self.pending.len() {
+			match self.channel.poll_recv(cx) {
+				Poll::Pending => return Poll::Pending,
+				Poll::Ready(None) => return Poll::Ready(0),
+				Poll::Ready(Some(data)) => {
+					self.pending = data;
+					self.position = 0;
+				},
+			}
+		}
+		let available = &self.pending[self.position..];
+		let read = available.len().min(buf.len());
+		buf[..read].copy_from_slice(&available[..read]);
+		self.position += read;
+		Poll::Ready(read)
+	}
+}
+
+impl Shared {
+	/// Account for received data, and check if the server should be asked to suspend sending data.
+	fn data_received(&self, len: usize) -> bool {
+		let buffered = self.buffered.fetch_add(len, Ordering::Relaxed) + len;
+		buffered >= SUSPEND_THRESHOLD && !self.suspended.swap(true, Ordering::Relaxed)
+	}
+
+	/// Account for read data, and check if the server should be asked to resume sending data.
+	fn data_read(&self, len: usize) -> bool {
+		let buffered = self.buffered.fetch_sub(len, Ordering::Relaxed) - len;
+		buffered <= RESUME_THRESHOLD && self.suspended.swap(false, Ordering::Relaxed)
+	}
+}
+
+impl AsyncRead for RemoteSerialPort {
+	fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<std::io::Result<()>> {
+		let this = self.get_mut();
+		let reader = this.reader.get_mut();
+		let read = match reader.poll_read(cx, buf.initialize_unfilled()) {
+			Poll::Pending => return Poll::Pending,
+			Poll::Ready(read) => read,
+		};
+		buf.advance(read);
+		if this.shared.data_read(read) {
+			// Send the request in the background, because a read can not wait for a write to complete.
+			let writer = this.writer.clone();
+			tokio::spawn(async move { resume(&writer).await });
+		}
+		Poll::Ready(Ok(()))
+	}
+}
+
+impl AsyncWrite for RemoteSerialPort {
+	/// Write data to the remote serial port.
+	///
+	/// The data is sent to the server as a whole, so the returned length is equal to the length of `buf`.
+	/// If this returns [`Poll::Pending`], it must be called again with the same data.
+	fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>> {
+		let this = self.get_mut();
+		let write = this.write.get_mut().unwrap_or_else(|e| e.into_inner());
+		if buf.is_empty() && write.is_none() {
+			return Poll::Ready(Ok(0));
+		}
+		let future = write.get_or_insert_with(|| {
+			let writer = this.writer.clone();
+			let mut escaped = Vec::with_capacity(buf.len());
+			telnet::escape(buf, &mut escaped);
+			let len = buf.len();
+			Box::pin(async move {
+				tcp::write_all_locked(&writer, &escaped).await?;
+				Ok(len)
+			})
+		});
+		let result = match future.as_mut().poll(cx) {
+			Poll::Pending => return Poll::Pending,
+			Poll::Ready(result) => result,
+		};
+		*write = None;
+		Poll::Ready(result)
+	}
+
+	fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+		let write = self.get_mut().write.get_mut().unwrap_or_else(|e| e.into_inner());
+		if let Some(future) = write {
+			let result = match future.as_mut().poll(cx) {
+				Poll::Pending => return Poll::Pending,
+				Poll::Ready(result) => result,
+			};
+			*write = None;
+			result?;
+		}
+		Poll::Ready(Ok(()))
+	}
+
+	/// Flush pending data.
+	///
+	/// The connection to the server stays open, so that commands can still be sent.
+	fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+		self.poll_flush(cx)
+	}
+}
+
+impl std::fmt::Debug for RemoteSerialPort {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("RemoteSerialPort")
+			.field("command_timeout", &self.get_command_timeout())
+			.finish_non_exhaustive()
+	}
+}
+
+/// Receive data and responses from the server.
+///
+/// When too much received data is buffered, the server is asked to suspend sending data.
+/// If the buffer is full anyway, this stops reading from the connection until there is room again.
+async fn receive(mut reader: OwnedReadHalf, writer: Arc<Mutex<OwnedWriteHalf>>, shared: Arc<Shared>, mut options: telnet::Options, data_tx: mpsc::Sender<Vec<u8>>) {
+	let mut parser = telnet::Parser::new();
+	let mut buffer = vec![0; RECEIVE_BUFFER_SIZE];
+	let mut events = Vec::new();
+	let mut reply = Vec::new();
+	loop {
+		let read = match reader.read(&mut buffer).await {
+			Ok(0) | Err(_) => break,
+			Ok(read) => read,
+		};
+		let mut data = Vec::new();
+		events.clear();
+		parser.parse(&buffer[..read], &mut events);
+
+		reply.clear();
+		for event in events.drain(..) {
+			match event {
+				Event::Data(received) => data.extend_from_slice(&received),
+				Event::Negotiate(verb, opt) => options.handle(verb, opt, &mut reply),
+				Event::Subnegotiation(option::COM_PORT, payload) => {
+					if let Some((&code, value)) = payload.split_first() {
+						if code == command::NOTIFY_MODEMSTATE + command::SERVER_OFFSET {
+							if let Some(&state) = value.first() {
+								shared.modem_state.send_replace(Some(state));
+							}
+							continue;
+						}
+						let mut response = shared.response.lock().unwrap_or_else(|e| e.into_inner());
+						if response.as_ref().is_some_and(|(expected, _)| *expected == code) {
+							if let Some((_, sender)) = response.take() {
+								sender.send(value.to_vec()).ok();
+							}
+						}
+					}
+				},
+				Event::Subnegotiation(_, _) => (),
+			}
+		}
+		if !data.is_empty() && shared.data_received(data.len()) {
+			telnet::com_port_command(command::FLOWCONTROL_SUSPEND, &[], &mut reply);
+		}
+		if !reply.is_empty() && tcp::write_all_locked(&writer, &reply).await.is_err() {
+			break;
+		}
+		if !data.is_empty() && data_tx.send(data).await.is_err() {
+			break;
+		}
+	}
+	shared.closed.store(true, Ordering::Release);
+	shared.response.lock().unwrap_or_else(|e| e.into_inner()).take();
+}
+
+/// Ask the server to resume sending data.
+async fn resume(writer: &Mutex<OwnedWriteHalf>) {
+	let mut message = Vec::new();
+	telnet::com_port_command(command::FLOWCONTROL_RESUME, &[], &mut message);
+	// If this fails, the connection is broken and reading reports the end of the data.
+	tcp::write_all_locked(writer, &message).await.ok();
+}
+
+/// Convert a duration to nanoseconds, saturating at the maximum value of a `u64`.
+fn duration_to_nanos(duration: Duration) -> u64 {
+	duration.as_nanos().try_into().unwrap_or(u64::MAX)
+}
+
+fn check_applied(applied: bool, what: &str) -> std::io::Result<()> {
+	if applied {
+		Ok(())
+	} else {
+		Err(std::io::Error::new(std::io::ErrorKind::Unsupported, format!("the RFC 2217 server did not apply the requested {what}")))
+	}
+}
+
+fn invalid_response() -> std::io::Error {
+	std::io::Error::new(std::io::ErrorKind::InvalidData, "invalid response from RFC 2217 server")
+}
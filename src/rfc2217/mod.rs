@@ -0,0 +1,50 @@
+//! Serial ports over the network with RFC 2217 (Telnet Com Port Control).
+//!
+//! RFC 2217 extends the Telnet protocol with commands to configure a remote serial port,
+//! including the baud rate, character size, parity, stop bits, flow control and the modem control lines.
+//! It is supported by many device servers and tools, such as `ser2net`, PuTTY, pySerial and ESPHome.
+//!
+//! The [`Server`] exposes a local [`SerialPort`][crate::SerialPort] to RFC 2217 clients.
+//! Configuration commands from the client are applied to the local serial port,
+//! and changes of the modem status lines are reported to the client.
+//!
+//! The [`RemoteSerialPort`] connects to an RFC 2217 server, such as a network device server from Moxa or Digi.
+//! It offers the same operations as a local serial port, but configuration changes are asynchronous.
+//!
+//! MARK and SPACE parity and 1.5 stop bits are not supported.
+//! When a client requests an unsupported setting, the server responds with the current setting instead.
+//!
+//! # Example
+//! Expose a local serial port to the network:
+//! ```no_run
+//! # async fn example() -> std::io::Result<()> {
+//! use serial2_tokio::SerialPort;
+//! use serial2_tokio::rfc2217::Server;
+//! use tokio::net::TcpListener;
+//!
+//! let port = SerialPort::open("/dev/ttyUSB0", 115200)?;
+//! let listener = TcpListener::bind("0.0.0.0:2217").await?;
+//! Server::new(port).serve(listener).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Use a remote serial port:
+//! ```no_run
+//! # async fn example() -> std::io::Result<()> {
+//! use serial2_tokio::rfc2217::RemoteSerialPort;
+//!
+//! let port = RemoteSerialPort::open("rfc2217://192.168.1.20:2217").await?;
+//! port.set_baud_rate(115200).await?;
+//! let mut buffer = [0; 256];
+//! let read = port.read(&mut buffer).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+mod client;
+mod server;
+mod telnet;
+
+pub use client::RemoteSerialPort;
+pub use server::Server;
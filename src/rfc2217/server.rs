@@ -0,0 +1,492 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::Arc;
+use std::task::Poll;
+use std::time::Duration;
+
+use tokio::io::AsyncReadExt;
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{watch, Mutex};
+
+use super::telnet::{self, command, control, line_state, modem_state, option, purge, Event};
+use crate::task::AbortOnDrop;
+use crate::{tcp, SerialPort, Settings};
+
+/// An RFC 2217 server that exposes a local serial port to network clients.
+///
+/// See the [module documentation](super) for more information.
+#[derive(Debug)]
+pub struct Server {
+	port: Arc<SerialPort>,
+	signature: String,
+	modem_poll_interval: Duration,
+}
+
+impl Server {
+	/// Create a new server for a serial port.
+	pub fn new(port: impl Into<Arc<SerialPort>>) -> Self {
+		Self {
+			port: port.into(),
+			signature: format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")),
+			modem_poll_interval: Duration::from_millis(100),
+		}
+	}
+
+	/// Set the signature that is sent to clients that request it.
+	pub fn signature(mut self, signature: impl Into<String>) -> Self {
+		self.signature = signature.into();
+		self
+	}
+
+	/// Set the interval for polling the modem status lines and the line error counters.
+	///
+	/// Changes of the modem status lines and new line errors (break, framing, parity and overrun errors)
+	/// are reported to the client, if it requested notifications for them.
+	/// Line errors are only reported on platforms that count them (see [`SerialPort::line_counters()`]).
+	/// The default interval is 100 milliseconds.
+	pub fn modem_poll_interval(mut self, interval: Duration) -> Self {
+		self.modem_poll_interval = interval;
+		self
+	}
+
+	/// Get the serial port used by the server.
+	pub fn port(&self) -> &Arc<SerialPort> {
+		&self.port
+	}
+
+	/// Accept clients from a TCP listener and serve them one at a time.
+	///
+	/// Connections that are made while a client is being served wait in the backlog of the listener.
+	///
+	/// This only returns when accepting a client fails, or when an I/O error occurs on the serial port.
+	pub async fn serve(&self, listener: TcpListener) -> std::io::Result<()> {
+		loop {
+			let (stream, _address) = listener.accept().await?;
+			self.serve_connection(stream).await?;
+		}
+	}
+
+	/// Serve a single client connection.
+	///
+	/// This returns `Ok(())` when the client disconnects or when a network error occurs.
+	/// An error is returned only when an I/O error occurs on the serial port.
+	pub async fn serve_connection(&self, stream: TcpStream) -> std::io::Result<()> {
+		stream.set_nodelay(true).ok();
+		let (reader, writer) = stream.into_split();
+		let writer = Arc::new(Mutex::new(writer));
+
+		let mut options = telnet::Options::new(
+			&[option::BINARY, option::SUPPRESS_GO_AHEAD],
+			&[option::BINARY, option::SUPPRESS_GO_AHEAD, option::COM_PORT],
+		);
+		let mut greeting = Vec::new();
+		options.request_all(&mut greeting);
+		if tcp::write_all_locked(&writer, &greeting).await.is_err() {
+			return Ok(());
+		}
+
+		let (suspended, _) = watch::channel(false);
+		let masks = Arc::new(NotifyMasks {
+			modem_state: AtomicU8::new(0xFF),
+			line_state: AtomicU8::new(0),
+			report_modem_state: AtomicBool::new(false),
+		});
+
+		let mut serial_task = tokio::spawn(forward_serial(self.port.clone(), writer.clone(), suspended.subscribe()));
+		let _serial_guard = AbortOnDrop(serial_task.abort_handle());
+		let notify_task = tokio::spawn(notify_state(self.port.clone(), writer.clone(), masks.clone(), self.modem_poll_interval));
+		let _notify_guard = AbortOnDrop(notify_task.abort_handle());
+
+		let mut session = Session {
+			port: &self.port,
+			signature: &self.signature,
+			suspended,
+			masks,
+			dtr: self.port.read_dtr().ok(),
+			rts: self.port.read_rts().ok(),
+			break_state: false,
+			options,
+		};
+		let mut network = std::pin::pin!(session.forward_network(reader, &writer));
+
+		std::future::poll_fn(|cx| {
+			if let Poll::Ready(result) = Pin::new(&mut serial_task).poll(cx) {
+				return Poll::Ready(result.unwrap_or_else(|e| Err(std::io::Error::other(e))));
+			}
+			network.as_mut().poll(cx)
+		}).await
+	}
+}
+
+/// The notification masks set by the client.
+struct NotifyMasks {
+	modem_state: AtomicU8,
+	line_state: AtomicU8,
+	/// Set when the client changed the modem state mask, to report the current modem state even if it did not change.
+	report_modem_state: AtomicBool,
+}
+
+/// The state of a client session.
+struct Session<'a> {
+	port: &'a Arc<SerialPort>,
+	signature: &'a str,
+	suspended: watch::Sender<bool>,
+	masks: Arc<NotifyMasks>,
+	/// The last known state of the DTR line, used if the driver can not report it.
+	dtr: Option<bool>,
+	/// The last known state of the RTS line, used if the driver can not report it.
+	rts: Option<bool>,
+	break_state: bool,
+	options: telnet::Options,
+}
+
+impl Session<'_> {
+	/// Read from the client, write data to the serial port and handle commands.
+	async fn forward_network(&mut self, mut reader: OwnedReadHalf, writer: &Mutex<OwnedWriteHalf>) -> std::io::Result<()> {
+		let mut parser = telnet::Parser::new();
+		let mut buffer = vec![0; 4096];
+		let mut events = Vec::new();
+		let mut reply = Vec::new();
+		loop {
+			let read = match reader.read(&mut buffer).await {
+				Ok(0) | Err(_) => return Ok(()),
+				Ok(read) => read,
+			};
+			events.clear();
+			parser.parse(&buffer[..read], &mut events);
+
+			// Handle data and commands in order, so that data is sent with the settings that were in effect when the client sent it.
+			reply.clear();
+			for event in events.drain(..) {
+				match event {
+					Event::Data(data) => self.port.write_all(&data).await?,
+					event => self.handle_event(event, &mut reply).await,
+				}
+			}
+			if !reply.is_empty() && tcp::write_all_locked(writer, &reply).await.is_err() {
+				return Ok(());
+			}
+		}
+	}
+
+	async fn handle_event(&mut self, event: Event, reply: &mut Vec<u8>) {
+		match event {
+			Event::Negotiate(verb, opt) => self.options.handle(verb, opt, reply),
+			Event::Subnegotiation(option::COM_PORT, payload) => {
+				if let Some((&code, value)) = payload.split_first() {
+					if let Some(response) = self.handle_command(code, value).await {
+						telnet::com_port_command(code + command::SERVER_OFFSET, &response, reply);
+					}
+				}
+			},
+			Event::Data(_) | Event::Subnegotiation(_, _) => (),
+		}
+	}
+
+	/// Handle an RFC 2217 command and return the value for the response, if any.
+	///
+	/// Failures to configure the serial port are reported to the client by responding with the value that is in effect.
+	async fn handle_command(&mut self, code: u8, value: &[u8]) -> Option<Vec<u8>> {
+		match code {
+			command::SIGNATURE => {
+				if value.is_empty() {
+					Some(self.signature.as_bytes().to_vec())
+				} else {
+					None
+				}
+			},
+			command::SET_BAUDRATE => {
+				let baud_rate = u32::from_be_bytes(value.try_into().ok()?);
+				let settings = if baud_rate != 0 {
+					self.configure(move |settings| settings.set_baud_rate(baud_rate)).await?
+				} else {
+					self.port.get_configuration().ok()?
+				};
+				let baud_rate = settings.get_baud_rate().ok()?;
+				Some(baud_rate.to_be_bytes().to_vec())
+			},
+			command::SET_DATASIZE => {
+				let settings = match telnet::decode_char_size(*value.first()?) {
+					Some(char_size) => self.configure(move |settings| {
+						settings.set_char_size(char_size);
+						Ok(())
+					}).await?,
+					None => self.port.get_configuration().ok()?,
+				};
+				let char_size = settings.get_char_size().ok()?;
+				Some(vec![telnet::encode_char_size(char_size)])
+			},
+			command::SET_PARITY => {
+				let settings = match telnet::decode_parity(*value.first()?) {
+					Some(parity) => self.configure(move |settings| {
+						settings.set_parity(parity);
+						Ok(())
+					}).await?,
+					None => self.port.get_configuration().ok()?,
+				};
+				let parity = settings.get_parity().ok()?;
+				Some(vec![telnet::encode_parity(parity)])
+			},
+			command::SET_STOPSIZE => {
+				let settings = match telnet::decode_stop_bits(*value.first()?) {
+					Some(stop_bits) => self.configure(move |settings| {
+						settings.set_stop_bits(stop_bits);
+						Ok(())
+					}).await?,
+					None => self.port.get_configuration().ok()?,
+				};
+				let stop_bits = settings.get_stop_bits().ok()?;
+				Some(vec![telnet::encode_stop_bits(stop_bits)])
+			},
+			command::SET_CONTROL => self.handle_control(*value.first()?).await.map(|x| vec![x]),
+			command::NOTIFY_MODEMSTATE => {
+				// Not defined by RFC 2217, but some clients use this to request the current modem state.
+				let state = read_modem_state(self.port).ok()?;
+				Some(vec![state & self.masks.modem_state.load(Ordering::Relaxed)])
+			},
+			command::FLOWCONTROL_SUSPEND => {
+				self.suspended.send_replace(true);
+				None
+			},
+			command::FLOWCONTROL_RESUME => {
+				self.suspended.send_replace(false);
+				None
+			},
+			command::SET_LINESTATE_MASK => {
+				let mask = *value.first()?;
+				self.masks.line_state.store(mask, Ordering::Relaxed);
+				Some(vec![mask])
+			},
+			command::SET_MODEMSTATE_MASK => {
+				let mask = *value.first()?;
+				self.masks.modem_state.store(mask, Ordering::Relaxed);
+				self.masks.report_modem_state.store(true, Ordering::Relaxed);
+				Some(vec![mask])
+			},
+			command::PURGE_DATA => {
+				let value = *value.first()?;
+				match value {
+					purge::RECEIVE => self.port.discard_input_buffer().ok(),
+					purge::TRANSMIT => self.port.discard_output_buffer().ok(),
+					purge::BOTH => self.port.discard_buffers().ok(),
+					_ => None,
+				};
+				Some(vec![value])
+			},
+			_ => None,
+		}
+	}
+
+	/// Handle a SET-CONTROL command and return the value for the response, if any.
+	///
+	/// Values that are not supported, like DCD, DTR and DSR flow control, get the current flow control setting as response,
+	/// so that the client does not wait for a response that never comes.
+	async fn handle_control(&mut self, value: u8) -> Option<u8> {
+		match value {
+			control::REQUEST_FLOW => encode_flow_control(&self.port.get_configuration().ok()?, false),
+			control::REQUEST_INBOUND_FLOW => encode_flow_control(&self.port.get_configuration().ok()?, true),
+			control::FLOW_NONE..=control::FLOW_HARDWARE | control::INBOUND_FLOW_NONE..=control::INBOUND_FLOW_HARDWARE => {
+				let flow_control = telnet::decode_flow_control(value)?;
+				let settings = self.configure(move |settings| {
+					settings.set_flow_control(flow_control);
+					Ok(())
+				}).await?;
+				encode_flow_control(&settings, value >= control::INBOUND_FLOW_NONE)
+			},
+			control::REQUEST_BREAK => Some(encode_state(self.break_state, control::BREAK_ON, control::BREAK_OFF)),
+			control::BREAK_ON | control::BREAK_OFF => {
+				let state = value == control::BREAK_ON;
+				if self.port.set_break(state).is_ok() {
+					self.break_state = state;
+				}
+				Some(encode_state(self.break_state, control::BREAK_ON, control::BREAK_OFF))
+			},
+			control::REQUEST_DTR => Some(encode_state(self.read_dtr()?, control::DTR_ON, control::DTR_OFF)),
+			control::DTR_ON | control::DTR_OFF => {
+				let state = value == control::DTR_ON;
+				if self.port.set_dtr(state).is_ok() {
+					self.dtr = Some(state);
+				}
+				Some(encode_state(self.read_dtr()?, control::DTR_ON, control::DTR_OFF))
+			},
+			control::REQUEST_RTS => Some(encode_state(self.read_rts()?, control::RTS_ON, control::RTS_OFF)),
+			control::RTS_ON | control::RTS_OFF => {
+				let state = value == control::RTS_ON;
+				if self.port.set_rts(state).is_ok() {
+					self.rts = Some(state);
+				}
+				Some(encode_state(self.read_rts()?, control::RTS_ON, control::RTS_OFF))
+			},
+			control::INBOUND_FLOW_DTR => encode_flow_control(&self.port.get_configuration().ok()?, true),
+			_ => encode_flow_control(&self.port.get_configuration().ok()?, false),
+		}
+	}
+
+	/// Apply a configuration change and get the configuration that is in effect afterwards.
+	///
+	/// Applying a configuration waits until the output buffer has been transmitted,
+	/// so it runs on the blocking thread pool to keep the runtime responsive.
+	///
+	/// If the change fails, the current configuration is returned, so the client is told the value that is really used.
+	async fn configure<F>(&self, function: F) -> Option<Settings>
+	where
+		F: FnOnce(&mut Settings) -> std::io::Result<()> + Send + 'static,
+	{
+		let port = self.port.clone();
+		tokio::task::spawn_blocking(move || {
+			// Partially applied settings are reported by reading back the configuration below.
+			let _: std::io::Result<()> = port.modify_configuration(function);
+			port.get_configuration().ok()
+		}).await.ok()?
+	}
+}
+
+impl Session<'_> {
+	/// Read the state of the DTR line, or use the last known state if the driver can not report it.
+	fn read_dtr(&mut self) -> Option<bool> {
+		if let Ok(state) = self.port.read_dtr() {
+			self.dtr = Some(state);
+		}
+		self.dtr
+	}
+
+	/// Read the state of the RTS line, or use the last known state if the driver can not report it.
+	fn read_rts(&mut self) -> Option<bool> {
+		if let Ok(state) = self.port.read_rts() {
+			self.rts = Some(state);
+		}
+		self.rts
+	}
+}
+
+/// Encode the flow control of a configuration as response to a SET-CONTROL command for outbound or inbound flow control.
+fn encode_flow_control(settings: &Settings, inbound: bool) -> Option<u8> {
+	let value = telnet::encode_flow_control(settings.get_flow_control().ok()?);
+	if inbound {
+		Some(value + control::REQUEST_INBOUND_FLOW)
+	} else {
+		Some(value)
+	}
+}
+
+fn encode_state(state: bool, on: u8, off: u8) -> u8 {
+	if state {
+		on
+	} else {
+		off
+	}
+}
+
+/// Read from the serial port and send the data to the client.
+async fn forward_serial(port: Arc<SerialPort>, writer: Arc<Mutex<OwnedWriteHalf>>, mut suspended: watch::Receiver<bool>) -> std::io::Result<()> {
+	let mut buffer = vec![0; 4096];
+	let mut escaped = Vec::new();
+	loop {
+		// The sender is owned by the session, which outlives this task.
+		suspended.wait_for(|&suspended| !suspended).await.ok();
+		let read = port.read(&mut buffer).await?;
+		if read == 0 {
+			return Ok(());
+		}
+		escaped.clear();
+		telnet::escape(&buffer[..read], &mut escaped);
+		if tcp::write_all_locked(&writer, &escaped).await.is_err() {
+			return Ok(());
+		}
+	}
+}
+
+/// Poll the modem status lines and line error counters, and notify the client of changes.
+///
+/// The current modem state is also reported when the client sets the modem state mask,
+/// so the client learns the initial state without waiting for a change.
+///
+/// The modem status lines and the line errors are only reported if the serial port supports reading them.
+async fn notify_state(port: Arc<SerialPort>, writer: Arc<Mutex<OwnedWriteHalf>>, masks: Arc<NotifyMasks>, interval: Duration) {
+	let mut previous_modem_state = None;
+	let mut previous_counters = None;
+	let mut notification = Vec::new();
+	loop {
+		notification.clear();
+
+		let modem_state = read_modem_state(&port).ok();
+		let report = masks.report_modem_state.swap(false, Ordering::Relaxed);
+		if let Some(state) = modem_state {
+			let previous = previous_modem_state.unwrap_or(state);
+			let changed = state ^ previous;
+			let mut deltas = 0;
+			if changed & modem_state::CD != 0 {
+				deltas |= modem_state::DELTA_CD;
+			}
+			if changed & modem_state::DSR != 0 {
+				deltas |= modem_state::DELTA_DSR;
+			}
+			if changed & modem_state::CTS != 0 {
+				deltas |= modem_state::DELTA_CTS;
+			}
+			if previous & modem_state::RI != 0 && state & modem_state::RI == 0 {
+				deltas |= modem_state::TRAILING_EDGE_RI;
+			}
+			let mask = masks.modem_state.load(Ordering::Relaxed);
+			if report || (changed | deltas) & mask != 0 {
+				telnet::com_port_command(command::NOTIFY_MODEMSTATE + command::SERVER_OFFSET, &[(state | deltas) & mask], &mut notification);
+			}
+		}
+		previous_modem_state = modem_state;
+
+		let counters = port.line_counters().ok();
+		if let (Some(counters), Some(previous)) = (&counters, &previous_counters) {
+			let state = line_errors(previous, counters) & masks.line_state.load(Ordering::Relaxed);
+			if state != 0 {
+				telnet::com_port_command(command::NOTIFY_LINESTATE + command::SERVER_OFFSET, &[state], &mut notification);
+			}
+		}
+		previous_counters = counters;
+
+		if !notification.is_empty() && tcp::write_all_locked(&writer, &notification).await.is_err() {
+			return;
+		}
+		tokio::time::sleep(interval).await;
+	}
+}
+
+/// Get the line state bits for the errors that occurred between two readings of the line counters.
+fn line_errors(previous: &crate::LineCounters, current: &crate::LineCounters) -> u8 {
+	let increased = |previous: Option<u32>, current: Option<u32>| match (previous, current) {
+		(Some(previous), Some(current)) => current != previous,
+		_ => false,
+	};
+	let mut state = 0;
+	if increased(previous.breaks, current.breaks) {
+		state |= line_state::BREAK_DETECT;
+	}
+	if increased(previous.frame_errors, current.frame_errors) {
+		state |= line_state::FRAMING_ERROR;
+	}
+	if increased(previous.parity_errors, current.parity_errors) {
+		state |= line_state::PARITY_ERROR;
+	}
+	if increased(previous.overruns, current.overruns) || increased(previous.buffer_overruns, current.buffer_overruns) {
+		state |= line_state::OVERRUN_ERROR;
+	}
+	state
+}
+
+fn read_modem_state(port: &SerialPort) -> std::io::Result<u8> {
+	let mut state = 0;
+	if port.read_cd()? {
+		state |= modem_state::CD;
+	}
+	if port.read_ri()? {
+		state |= modem_state::RI;
+	}
+	if port.read_dsr()? {
+		state |= modem_state::DSR;
+	}
+	if port.read_cts()? {
+		state |= modem_state::CTS;
+	}
+	Ok(state)
+}
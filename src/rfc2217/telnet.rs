@@ -0,0 +1,434 @@
+//! Minimal Telnet protocol support for RFC 2217.
+
+/// Interpret As Command.
+pub const IAC: u8 = 255;
+pub const DONT: u8 = 254;
+pub const DO: u8 = 253;
+pub const WONT: u8 = 252;
+pub const WILL: u8 = 251;
+/// Subnegotiation begin.
+pub const SB: u8 = 250;
+/// Subnegotiation end.
+pub const SE: u8 = 240;
+
+/// Telnet options.
+pub mod option {
+	pub const BINARY: u8 = 0;
+	pub const SUPPRESS_GO_AHEAD: u8 = 3;
+	pub const COM_PORT: u8 = 44;
+}
+
+/// RFC 2217 commands sent by the client.
+///
+/// The server responds with the same command plus [`SERVER_OFFSET`].
+pub mod command {
+	pub const SIGNATURE: u8 = 0;
+	pub const SET_BAUDRATE: u8 = 1;
+	pub const SET_DATASIZE: u8 = 2;
+	pub const SET_PARITY: u8 = 3;
+	pub const SET_STOPSIZE: u8 = 4;
+	pub const SET_CONTROL: u8 = 5;
+	pub const NOTIFY_LINESTATE: u8 = 6;
+	pub const NOTIFY_MODEMSTATE: u8 = 7;
+	pub const FLOWCONTROL_SUSPEND: u8 = 8;
+	pub const FLOWCONTROL_RESUME: u8 = 9;
+	pub const SET_LINESTATE_MASK: u8 = 10;
+	pub const SET_MODEMSTATE_MASK: u8 = 11;
+	pub const PURGE_DATA: u8 = 12;
+
+	/// Offset added to the command by the server.
+	pub const SERVER_OFFSET: u8 = 100;
+}
+
+/// Values for the SET-CONTROL command.
+pub mod control {
+	pub const REQUEST_FLOW: u8 = 0;
+	pub const FLOW_NONE: u8 = 1;
+	pub const FLOW_XON_XOFF: u8 = 2;
+	pub const FLOW_HARDWARE: u8 = 3;
+	pub const REQUEST_BREAK: u8 = 4;
+	pub const BREAK_ON: u8 = 5;
+	pub const BREAK_OFF: u8 = 6;
+	pub const REQUEST_DTR: u8 = 7;
+	pub const DTR_ON: u8 = 8;
+	pub const DTR_OFF: u8 = 9;
+	pub const REQUEST_RTS: u8 = 10;
+	pub const RTS_ON: u8 = 11;
+	pub const RTS_OFF: u8 = 12;
+	pub const REQUEST_INBOUND_FLOW: u8 = 13;
+	pub const INBOUND_FLOW_NONE: u8 = 14;
+	pub const INBOUND_FLOW_XON_XOFF: u8 = 15;
+	pub const INBOUND_FLOW_HARDWARE: u8 = 16;
+	pub const INBOUND_FLOW_DTR: u8 = 18;
+}
+
+/// Bits of the modem state.
+pub mod modem_state {
+	pub const CD: u8 = 0x80;
+	pub const RI: u8 = 0x40;
+	pub const DSR: u8 = 0x20;
+	pub const CTS: u8 = 0x10;
+	pub const DELTA_CD: u8 = 0x08;
+	pub const TRAILING_EDGE_RI: u8 = 0x04;
+	pub const DELTA_DSR: u8 = 0x02;
+	pub const DELTA_CTS: u8 = 0x01;
+}
+
+/// Bits of the line state.
+pub mod line_state {
+	pub const BREAK_DETECT: u8 = 0x10;
+	pub const FRAMING_ERROR: u8 = 0x08;
+	pub const PARITY_ERROR: u8 = 0x04;
+	pub const OVERRUN_ERROR: u8 = 0x02;
+}
+
+/// Values for the PURGE-DATA command.
+pub mod purge {
+	pub const RECEIVE: u8 = 1;
+	pub const TRANSMIT: u8 = 2;
+	pub const BOTH: u8 = 3;
+}
+
+/// Data or a Telnet command extracted from the data stream.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Event {
+	/// Unescaped data.
+	///
+	/// Consecutive data bytes are combined in one event.
+	Data(Vec<u8>),
+
+	/// A WILL, WONT, DO or DONT option negotiation.
+	Negotiate(u8, u8),
+
+	/// A subnegotiation for an option, with the unescaped payload.
+	Subnegotiation(u8, Vec<u8>),
+}
+
+#[derive(Debug, Copy, Clone)]
+enum State {
+	Data,
+	Iac,
+	Negotiate(u8),
+	Subnegotiation,
+	SubnegotiationIac,
+}
+
+/// The maximum size of a subnegotiation, including the option.
+///
+/// RFC 2217 subnegotiations are only a few bytes long, except for the signature.
+/// Larger subnegotiations are dropped, so that a peer can not make us buffer an unlimited amount of data.
+const MAX_SUBNEGOTIATION_LEN: usize = 256;
+
+/// Incremental parser to separate data from Telnet commands.
+///
+/// Data and commands are reported in the order they were received,
+/// so that commands can be applied at the right place in the data stream.
+#[derive(Debug)]
+pub struct Parser {
+	state: State,
+	subnegotiation: Vec<u8>,
+	subnegotiation_too_long: bool,
+}
+
+impl Parser {
+	pub fn new() -> Self {
+		Self {
+			state: State::Data,
+			subnegotiation: Vec::new(),
+			subnegotiation_too_long: false,
+		}
+	}
+
+	/// Parse received bytes, appending data and commands to `events`.
+	pub fn parse(&mut self, input: &[u8], events: &mut Vec<Event>) {
+		for &byte in input {
+			self.state = match (self.state, byte) {
+				(State::Data, IAC) => State::Iac,
+				(State::Data, byte) => {
+					push_data(events, byte);
+					State::Data
+				},
+				(State::Iac, IAC) => {
+					push_data(events, IAC);
+					State::Data
+				},
+				(State::Iac, WILL | WONT | DO | DONT) => State::Negotiate(byte),
+				(State::Iac, SB) => {
+					self.subnegotiation.clear();
+					self.subnegotiation_too_long = false;
+					State::Subnegotiation
+				},
+				// Other commands (NOP, GA, ...) carry no information we need.
+				(State::Iac, _) => State::Data,
+				(State::Negotiate(command), option) => {
+					events.push(Event::Negotiate(command, option));
+					State::Data
+				},
+				(State::Subnegotiation, IAC) => State::SubnegotiationIac,
+				(State::Subnegotiation, byte) => {
+					self.push_subnegotiation(byte);
+					State::Subnegotiation
+				},
+				(State::SubnegotiationIac, SE) => {
+					if let Some((&option, payload)) = self.subnegotiation.split_first().filter(|_| !self.subnegotiation_too_long) {
+						events.push(Event::Subnegotiation(option, payload.to_vec()));
+					}
+					self.subnegotiation.clear();
+					State::Data
+				},
+				(State::SubnegotiationIac, byte) => {
+					self.push_subnegotiation(byte);
+					State::Subnegotiation
+				},
+			}
+		}
+	}
+}
+
+impl Parser {
+	/// Add a byte to the current subnegotiation, unless it is too long already.
+	fn push_subnegotiation(&mut self, byte: u8) {
+		if self.subnegotiation.len() < MAX_SUBNEGOTIATION_LEN {
+			self.subnegotiation.push(byte);
+		} else {
+			self.subnegotiation_too_long = true;
+		}
+	}
+}
+
+/// Add a data byte to the last event if it is data, or start a new data event.
+fn push_data(events: &mut Vec<Event>, byte: u8) {
+	match events.last_mut() {
+		Some(Event::Data(data)) => data.push(byte),
+		_ => events.push(Event::Data(vec![byte])),
+	}
+}
+
+/// The state of Telnet option negotiation.
+#[derive(Debug)]
+pub struct Options {
+	local_supported: &'static [u8],
+	remote_supported: &'static [u8],
+	local: Vec<u8>,
+	remote: Vec<u8>,
+}
+
+impl Options {
+	/// Create the negotiation state with the options we are willing to enable locally and remotely.
+	pub fn new(local_supported: &'static [u8], remote_supported: &'static [u8]) -> Self {
+		Self {
+			local_supported,
+			remote_supported,
+			local: Vec::new(),
+			remote: Vec::new(),
+		}
+	}
+
+	/// Request all supported options.
+	pub fn request_all(&mut self, output: &mut Vec<u8>) {
+		for &option in self.local_supported {
+			self.local.push(option);
+			negotiate(WILL, option, output);
+		}
+		for &option in self.remote_supported {
+			self.remote.push(option);
+			negotiate(DO, option, output);
+		}
+	}
+
+	/// Handle an option negotiation from the peer.
+	///
+	/// Only state changes are acknowledged, to prevent negotiation loops.
+	pub fn handle(&mut self, command: u8, option: u8, reply: &mut Vec<u8>) {
+		match command {
+			DO => {
+				if !self.local_supported.contains(&option) {
+					negotiate(WONT, option, reply);
+				} else if !self.local.contains(&option) {
+					self.local.push(option);
+					negotiate(WILL, option, reply);
+				}
+			},
+			DONT => {
+				if let Some(index) = self.local.iter().position(|&x| x == option) {
+					self.local.remove(index);
+					negotiate(WONT, option, reply);
+				}
+			},
+			WILL => {
+				if !self.remote_supported.contains(&option) {
+					negotiate(DONT, option, reply);
+				} else if !self.remote.contains(&option) {
+					self.remote.push(option);
+					negotiate(DO, option, reply);
+				}
+			},
+			WONT => {
+				if let Some(index) = self.remote.iter().position(|&x| x == option) {
+					self.remote.remove(index);
+					negotiate(DONT, option, reply);
+				}
+			},
+			_ => (),
+		}
+	}
+}
+
+/// Append data to a buffer, escaping IAC bytes.
+pub fn escape(data: &[u8], output: &mut Vec<u8>) {
+	for &byte in data {
+		if byte == IAC {
+			output.push(IAC);
+		}
+		output.push(byte);
+	}
+}
+
+/// Append an option negotiation to a buffer.
+pub fn negotiate(command: u8, option: u8, output: &mut Vec<u8>) {
+	output.extend_from_slice(&[IAC, command, option]);
+}
+
+/// Append a subnegotiation to a buffer.
+pub fn subnegotiation(option: u8, payload: &[u8], output: &mut Vec<u8>) {
+	output.extend_from_slice(&[IAC, SB, option]);
+	escape(payload, output);
+	output.extend_from_slice(&[IAC, SE]);
+}
+
+/// Append an RFC 2217 command to a buffer.
+pub fn com_port_command(command: u8, value: &[u8], output: &mut Vec<u8>) {
+	let mut payload = Vec::with_capacity(value.len() + 1);
+	payload.push(command);
+	payload.extend_from_slice(value);
+	subnegotiation(option::COM_PORT, &payload, output);
+}
+
+/// Encode a character size for the SET-DATASIZE command.
+pub fn encode_char_size(char_size: crate::CharSize) -> u8 {
+	match char_size {
+		crate::CharSize::Bits5 => 5,
+		crate::CharSize::Bits6 => 6,
+		crate::CharSize::Bits7 => 7,
+		crate::CharSize::Bits8 => 8,
+	}
+}
+
+/// Decode a character size from the SET-DATASIZE command.
+pub fn decode_char_size(value: u8) -> Option<crate::CharSize> {
+	match value {
+		5 => Some(crate::CharSize::Bits5),
+		6 => Some(crate::CharSize::Bits6),
+		7 => Some(crate::CharSize::Bits7),
+		8 => Some(crate::CharSize::Bits8),
+		_ => None,
+	}
+}
+
+/// Encode a parity mode for the SET-PARITY command.
+pub fn encode_parity(parity: crate::Parity) -> u8 {
+	match parity {
+		crate::Parity::None => 1,
+		crate::Parity::Odd => 2,
+		crate::Parity::Even => 3,
+	}
+}
+
+/// Decode a parity mode from the SET-PARITY command.
+///
+/// MARK and SPACE parity are not supported.
+pub fn decode_parity(value: u8) -> Option<crate::Parity> {
+	match value {
+		1 => Some(crate::Parity::None),
+		2 => Some(crate::Parity::Odd),
+		3 => Some(crate::Parity::Even),
+		_ => None,
+	}
+}
+
+/// Encode the number of stop bits for the SET-STOPSIZE command.
+pub fn encode_stop_bits(stop_bits: crate::StopBits) -> u8 {
+	match stop_bits {
+		crate::StopBits::One => 1,
+		crate::StopBits::Two => 2,
+	}
+}
+
+/// Decode the number of stop bits from the SET-STOPSIZE command.
+///
+/// 1.5 stop bits is not supported.
+pub fn decode_stop_bits(value: u8) -> Option<crate::StopBits> {
+	match value {
+		1 => Some(crate::StopBits::One),
+		2 => Some(crate::StopBits::Two),
+		_ => None,
+	}
+}
+
+/// Encode a flow control mode for the SET-CONTROL command.
+pub fn encode_flow_control(flow_control: crate::FlowControl) -> u8 {
+	match flow_control {
+		crate::FlowControl::None => control::FLOW_NONE,
+		crate::FlowControl::XonXoff => control::FLOW_XON_XOFF,
+		crate::FlowControl::RtsCts => control::FLOW_HARDWARE,
+	}
+}
+
+/// Decode a flow control mode from the SET-CONTROL command.
+pub fn decode_flow_control(value: u8) -> Option<crate::FlowControl> {
+	match value {
+		control::FLOW_NONE | control::INBOUND_FLOW_NONE => Some(crate::FlowControl::None),
+		control::FLOW_XON_XOFF | control::INBOUND_FLOW_XON_XOFF => Some(crate::FlowControl::XonXoff),
+		control::FLOW_HARDWARE | control::INBOUND_FLOW_HARDWARE => Some(crate::FlowControl::RtsCts),
+		_ => None,
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn parse_keeps_data_and_commands_in_order() {
+		let mut parser = Parser::new();
+		let mut events = Vec::new();
+		parser.parse(&[b'a', IAC, IAC, IAC, SB, option::COM_PORT, command::PURGE_DATA, purge::BOTH, IAC, SE, b'b'], &mut events);
+		assert_eq!(events, [
+			Event::Data(vec![b'a', IAC]),
+			Event::Subnegotiation(option::COM_PORT, vec![command::PURGE_DATA, purge::BOTH]),
+			Event::Data(vec![b'b']),
+		]);
+	}
+
+	#[test]
+	fn parse_keeps_state_between_calls() {
+		let mut parser = Parser::new();
+		let mut events = Vec::new();
+		parser.parse(&[b'a', IAC], &mut events);
+		parser.parse(&[DO, option::BINARY, IAC], &mut events);
+		parser.parse(&[IAC, b'b'], &mut events);
+		assert_eq!(events, [
+			Event::Data(vec![b'a']),
+			Event::Negotiate(DO, option::BINARY),
+			Event::Data(vec![IAC, b'b']),
+		]);
+	}
+
+	#[test]
+	fn parse_drops_long_subnegotiation() {
+		let mut parser = Parser::new();
+		let mut events = Vec::new();
+		let mut input = vec![IAC, SB, option::COM_PORT];
+		input.resize(input.len() + MAX_SUBNEGOTIATION_LEN, b'x');
+		input.extend_from_slice(&[IAC, SE, b'c']);
+		parser.parse(&input, &mut events);
+		assert_eq!(events, [Event::Data(vec![b'c'])]);
+	}
+
+	#[test]
+	fn escape_doubles_iac() {
+		let mut output = Vec::new();
+		escape(&[1, IAC, 2], &mut output);
+		assert_eq!(output, [1, IAC, IAC, 2]);
+	}
+}
@@ -0,0 +1,43 @@
+use std::time::Duration;
+
+/// A small seedable pseudo-random number generator (SplitMix64).
+///
+/// Used by the simulation and test utilities, which need reproducible randomness but no cryptographic quality.
+#[derive(Debug, Clone)]
+pub(crate) struct Rng {
+	state: u64,
+}
+
+impl Rng {
+	/// Create a new generator from a seed.
+	pub fn new(seed: u64) -> Self {
+		Self { state: seed }
+	}
+
+	/// Get the next random number.
+	pub fn next_u64(&mut self) -> u64 {
+		self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+		let mut z = self.state;
+		z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+		z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+		z ^ (z >> 31)
+	}
+
+	/// Get a random number in the range `0.0..1.0`.
+	pub fn next_f64(&mut self) -> f64 {
+		(self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+	}
+
+	/// Return true with the given probability.
+	pub fn chance(&mut self, probability: f64) -> bool {
+		probability > 0.0 && self.next_f64() < probability
+	}
+
+	/// Get a random duration in the range `0..=max`.
+	pub fn duration_up_to(&mut self, max: Duration) -> Duration {
+		if max.is_zero() {
+			return Duration::ZERO;
+		}
+		max.mul_f64(self.next_f64())
+	}
+}
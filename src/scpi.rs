@@ -0,0 +1,247 @@
+//! Sessions with SCPI instruments.
+//!
+//! Many test and measurement instruments, like multimeters, oscilloscopes and power supplies,
+//! are controlled with SCPI commands over a serial port or a USB virtual serial port.
+//! The [`ScpiPort`] sends commands and reads responses using an [`AsciiCodec`],
+//! and adds support for binary block data and operation complete synchronization.
+//!
+//! This module is only available when the `scpi` feature is enabled.
+//!
+//! # Example
+//! ```no_run
+//! # async fn example() -> std::io::Result<()> {
+//! use serial2_tokio::SerialPort;
+//! use serial2_tokio::scpi::ScpiPort;
+//! use std::time::Duration;
+//!
+//! let port = SerialPort::open("/dev/ttyUSB0", 115200)?;
+//! let mut scpi = ScpiPort::new(port);
+//! println!("Connected to {}", scpi.query("*IDN?").await?);
+//!
+//! scpi.command("CONF:VOLT:DC 10").await?;
+//! scpi.wait_operation_complete(Duration::from_secs(5)).await?;
+//! let voltage: f64 = scpi.query("READ?").await?
+//!     .parse()
+//!     .map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidData, "invalid voltage"))?;
+//! println!("{voltage} V");
+//! # Ok(())
+//! # }
+//! ```
+
+use std::time::Duration;
+
+use bytes::{Buf, BytesMut};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::codec::{AsciiCodec, AsciiConfig, AsciiMessage};
+
+/// The default timeout for responses.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// A session with a SCPI instrument.
+///
+/// See the [module documentation](self) for more information.
+#[derive(Debug)]
+pub struct ScpiPort<T> {
+	inner: T,
+	codec: AsciiCodec,
+	buffer: BytesMut,
+	timeout: Duration,
+	/// The terminator after a binary block has not been consumed yet.
+	skip_terminator: bool,
+}
+
+impl<T> ScpiPort<T> {
+	/// Create a session that terminates commands and responses with `\n`.
+	pub fn new(inner: T) -> Self {
+		Self::with_config(inner, AsciiConfig::new())
+	}
+
+	/// Create a session with a custom configuration for the [`AsciiCodec`].
+	///
+	/// Use this for instruments that use a different terminator or that echo commands.
+	pub fn with_config(inner: T, config: AsciiConfig) -> Self {
+		Self {
+			inner,
+			codec: AsciiCodec::new(config),
+			buffer: BytesMut::new(),
+			timeout: DEFAULT_TIMEOUT,
+			skip_terminator: false,
+		}
+	}
+
+	/// Set the timeout for responses.
+	///
+	/// The timeout applies to each query, and can be overridden for a single query with [`Self::query_with_timeout()`].
+	/// The default timeout is 1 second.
+	pub fn set_timeout(&mut self, timeout: Duration) {
+		self.timeout = timeout;
+	}
+
+	/// Get the timeout for responses.
+	pub fn get_timeout(&self) -> Duration {
+		self.timeout
+	}
+
+	/// Get the configuration of the [`AsciiCodec`].
+	pub fn config(&self) -> &AsciiConfig {
+		self.codec.config()
+	}
+
+	/// Discard all received data that has not been read yet.
+	///
+	/// This only discards data that has already been read from the wrapped stream.
+	/// To discard the input buffer of a serial port, also use [`SerialPort::discard_input_buffer()`][crate::SerialPort::discard_input_buffer].
+	pub fn clear(&mut self) {
+		self.buffer.clear();
+		self.skip_terminator = false;
+	}
+
+	/// Get a reference to the wrapped stream.
+	pub fn get_ref(&self) -> &T {
+		&self.inner
+	}
+
+	/// Consume the session and return the wrapped stream.
+	///
+	/// Received data that has not been read yet is lost.
+	pub fn into_inner(self) -> T {
+		self.inner
+	}
+}
+
+impl<T: AsyncRead + AsyncWrite + Unpin> ScpiPort<T> {
+	/// Send a command that does not have a response.
+	pub async fn command(&mut self, command: &str) -> std::io::Result<()> {
+		let mut message = BytesMut::new();
+		self.codec.encode(command, &mut message)?;
+		self.inner.write_all(&message).await?;
+		self.inner.flush().await
+	}
+
+	/// Send a query and read the response.
+	///
+	/// Returns an error of kind [`std::io::ErrorKind::TimedOut`] if no response was received before the timeout,
+	/// or an error of kind [`std::io::ErrorKind::UnexpectedEof`] if the stream reported end-of-file.
+	pub async fn query(&mut self, query: &str) -> std::io::Result<String> {
+		self.query_with_timeout(query, self.timeout).await
+	}
+
+	/// Send a query and read the response with a custom timeout.
+	///
+	/// This is useful for queries that take longer than normal, like a measurement with a long integration time.
+	pub async fn query_with_timeout(&mut self, query: &str, timeout: Duration) -> std::io::Result<String> {
+		self.command(query).await?;
+		with_timeout(timeout, self.read_line()).await
+	}
+
+	/// Send a query and read a response in the IEEE 488.2 definite or indefinite length binary block format.
+	///
+	/// A definite length block starts with `#`, a single digit with the number of length digits, and the length itself.
+	/// An indefinite length block starts with `#0` and ends at the terminator.
+	/// The returned data does not include the block header or the terminator.
+	///
+	/// The response must start with the block header, so this does not work for instruments that echo commands.
+	pub async fn query_binary(&mut self, query: &str) -> std::io::Result<Vec<u8>> {
+		self.command(query).await?;
+		with_timeout(self.timeout, self.read_binary_block()).await
+	}
+
+	/// Wait until the instrument has completed all pending operations, using the `*OPC?` query.
+	pub async fn wait_operation_complete(&mut self, timeout: Duration) -> std::io::Result<()> {
+		let response = self.query_with_timeout("*OPC?", timeout).await?;
+		if response.trim() != "1" {
+			return Err(std::io::Error::new(
+				std::io::ErrorKind::InvalidData,
+				format!("unexpected response to *OPC?: {response:?}"),
+			));
+		}
+		Ok(())
+	}
+
+	/// Read a line, skipping prompts.
+	async fn read_line(&mut self) -> std::io::Result<String> {
+		self.skip_block_terminator().await?;
+		loop {
+			match self.codec.decode(&mut self.buffer)? {
+				Some(AsciiMessage::Line(line)) => return Ok(line.trim_end_matches('\r').to_owned()),
+				Some(AsciiMessage::Prompt) => continue,
+				None => self.fill(self.buffer.len() + 1).await?,
+			}
+		}
+	}
+
+	/// Read a binary block.
+	async fn read_binary_block(&mut self) -> std::io::Result<Vec<u8>> {
+		self.skip_block_terminator().await?;
+		self.fill(2).await?;
+		if self.buffer[0] != b'#' || !self.buffer[1].is_ascii_digit() {
+			return Err(invalid_block("missing block header"));
+		}
+		let digits = usize::from(self.buffer[1] - b'0');
+		let terminator = self.config().get_rx_terminator().as_bytes().to_vec();
+
+		if digits == 0 {
+			let end = loop {
+				if let Some(end) = self.buffer[2..].windows(terminator.len()).position(|window| window == terminator) {
+					break end + 2;
+				}
+				self.fill(self.buffer.len() + 1).await?;
+			};
+			let data = self.buffer[2..end].to_vec();
+			self.buffer.advance(end + terminator.len());
+			return Ok(data);
+		}
+
+		self.fill(2 + digits).await?;
+		let length: usize = std::str::from_utf8(&self.buffer[2..2 + digits])
+			.ok()
+			.and_then(|length| length.parse().ok())
+			.ok_or_else(|| invalid_block("invalid block length"))?;
+		let start = 2 + digits;
+		self.fill(start + length).await?;
+		let data = self.buffer[start..start + length].to_vec();
+		self.buffer.advance(start + length);
+
+		// The terminator after the block is consumed by the next read, so we do not wait for it here.
+		self.skip_terminator = true;
+		Ok(data)
+	}
+
+	/// Consume the terminator after a definite length binary block, if the instrument sent one.
+	async fn skip_block_terminator(&mut self) -> std::io::Result<()> {
+		if !std::mem::take(&mut self.skip_terminator) {
+			return Ok(());
+		}
+		let terminator = self.config().get_rx_terminator().as_bytes().to_vec();
+		while self.buffer.len() < terminator.len() && terminator.starts_with(&self.buffer) {
+			self.fill(self.buffer.len() + 1).await?;
+		}
+		if self.buffer.starts_with(&terminator) {
+			self.buffer.advance(terminator.len());
+		}
+		Ok(())
+	}
+
+	/// Read from the wrapped stream until the buffer holds at least `len` bytes.
+	async fn fill(&mut self, len: usize) -> std::io::Result<()> {
+		while self.buffer.len() < len {
+			if self.inner.read_buf(&mut self.buffer).await? == 0 {
+				return Err(std::io::ErrorKind::UnexpectedEof.into());
+			}
+		}
+		Ok(())
+	}
+}
+
+/// Run a future with a timeout, and turn a timeout into an error.
+async fn with_timeout<T>(timeout: Duration, future: impl std::future::Future<Output = std::io::Result<T>>) -> std::io::Result<T> {
+	tokio::time::timeout(timeout, future).await
+		.map_err(|_| std::io::Error::new(std::io::ErrorKind::TimedOut, "timeout while waiting for response"))?
+}
+
+/// Create an error for an invalid binary block.
+fn invalid_block(message: &str) -> std::io::Error {
+	std::io::Error::new(std::io::ErrorKind::InvalidData, format!("invalid binary block: {message}"))
+}
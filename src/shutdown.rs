@@ -0,0 +1,167 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::task::{ready, Poll};
+use std::time::Duration;
+
+use crate::SerialPort;
+
+/// The state of a shutdown through the `AsyncWrite` implementation.
+#[derive(Debug, Default)]
+pub(crate) struct Shutdown {
+	/// Deassert the DTR and RTS lines after draining the output buffer.
+	hangup: AtomicBool,
+
+	/// The blocking task that is draining the output buffer.
+	task: Option<tokio::task::JoinHandle<std::io::Result<()>>>,
+}
+
+impl SerialPort {
+	/// Deassert the DTR and RTS lines when the serial port is shut down.
+	///
+	/// Calling [`AsyncWriteExt::shutdown()`][tokio::io::AsyncWriteExt::shutdown] on a serial port waits until the output buffer has been drained.
+	/// If this option is enabled, the DTR and RTS lines are deasserted afterwards,
+	/// which signals the end of the session to devices that monitor these lines.
+	///
+	/// This option is disabled by default.
+	///
+	/// # Example
+	/// ```no_run
+	/// # async fn example() -> std::io::Result<()> {
+	/// use serial2_tokio::SerialPort;
+	/// use tokio::io::AsyncWriteExt;
+	///
+	/// let mut port = SerialPort::open("/dev/ttyUSB0", 115200)?;
+	/// port.set_hangup_on_shutdown(true);
+	/// port.write_all(b"BYE\r\n").await?;
+	/// port.shutdown().await?;
+	/// # Ok(())
+	/// # }
+	/// ```
+	#[cfg_attr(not(feature = "doc"), allow(rustdoc::broken_intra_doc_links))]
+	pub fn set_hangup_on_shutdown(&self, enable: bool) {
+		self.shutdown.hangup.store(enable, Ordering::Relaxed);
+	}
+
+	/// Check if the DTR and RTS lines are deasserted when the serial port is shut down.
+	///
+	/// See [`Self::set_hangup_on_shutdown()`] for more information.
+	pub fn get_hangup_on_shutdown(&self) -> bool {
+		self.shutdown.hangup.load(Ordering::Relaxed)
+	}
+
+	/// Close the serial port after the output buffer has been transmitted.
+	///
+	/// This waits until all data in the output buffer has been transmitted, or until `timeout` expires,
+	/// and then closes the serial port handle.
+	/// If the timeout expires, the remaining data in the output buffer is discarded
+	/// and an error of kind [`std::io::ErrorKind::TimedOut`] is returned.
+	/// The serial port is closed in either case.
+	///
+	/// Simply dropping a [`SerialPort`] does not wait for the output buffer to drain.
+	/// Depending on the platform and driver, untransmitted data may be discarded,
+	/// or the OS may block the dropping thread while it waits for the data to be transmitted.
+	/// Use this function if the last bytes written to the serial port matter.
+	///
+	/// Other handles to the same serial port created with [`Self::try_clone()`] are not closed.
+	///
+	/// # Example
+	/// ```no_run
+	/// # async fn example() -> std::io::Result<()> {
+	/// use serial2_tokio::SerialPort;
+	/// use std::time::Duration;
+	///
+	/// let port = SerialPort::open("/dev/ttyUSB0", 115200)?;
+	/// port.write_all(b"RESET\r\n").await?;
+	/// port.close(Duration::from_secs(1)).await?;
+	/// # Ok(())
+	/// # }
+	/// ```
+	pub async fn close(self, timeout: Duration) -> std::io::Result<()> {
+		match tokio::time::timeout(timeout, self.drain()).await {
+			Ok(result) => result,
+			Err(_) => {
+				// Make sure the OS does not keep waiting for the data to be transmitted when the handle is closed.
+				self.discard_output_buffer().ok();
+				Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "timeout while waiting for the output buffer to drain"))
+			},
+		}
+	}
+
+	/// Write all data and wait until it has been transmitted, with an overall deadline.
+	///
+	/// This writes the whole buffer to the serial port and waits until the output buffer of the OS has been drained.
+	/// This is useful for bootloader protocols that must know the data has left the host before toggling the reset lines.
+	///
+	/// Returns the number of bytes that are confirmed to be transmitted.
+	/// This is equal to the length of `buf` if all data was transmitted before the deadline.
+	/// If the deadline expires first, the returned value is the number of written bytes minus the bytes still waiting in the output buffer.
+	/// On platforms that can not report the size of the output buffer, zero bytes are reported as transmitted when the deadline expires.
+	/// The data that was already written is not discarded: use [`Self::discard_output_buffer()`] to stop the transmission.
+	///
+	/// Note that some drivers report that the output buffer is empty before the last byte has fully left the UART.
+	///
+	/// # Example
+	/// ```no_run
+	/// # async fn example() -> std::io::Result<()> {
+	/// use serial2_tokio::SerialPort;
+	/// use std::time::Duration;
+	/// use tokio::time::Instant;
+	///
+	/// let port = SerialPort::open("/dev/ttyUSB0", 115200)?;
+	/// let command = b"\x30\x20";
+	/// let deadline = Instant::now() + Duration::from_millis(100);
+	/// let sent = port.write_all_drained(command, deadline).await?;
+	/// if sent < command.len() {
+	///     port.discard_output_buffer()?;
+	///     eprintln!("only {sent} bytes were transmitted before the deadline");
+	/// }
+	/// # Ok(())
+	/// # }
+	/// ```
+	pub async fn write_all_drained(&self, buf: &[u8], deadline: tokio::time::Instant) -> std::io::Result<usize> {
+		let mut written = 0;
+		while written < buf.len() {
+			match tokio::time::timeout_at(deadline, self.write(&buf[written..])).await {
+				Ok(result) => written += result?,
+				Err(_) => return self.confirmed_transmitted(written),
+			}
+		}
+		match tokio::time::timeout_at(deadline, self.drain()).await {
+			Ok(result) => result.map(|()| written),
+			Err(_) => self.confirmed_transmitted(written),
+		}
+	}
+
+	/// Get the number of bytes that have been transmitted out of the given number of written bytes.
+	fn confirmed_transmitted(&self, written: usize) -> std::io::Result<usize> {
+		match self.flow_control_status()?.output_queue {
+			Some(queued) => Ok(written.saturating_sub(queued)),
+			None => Ok(0),
+		}
+	}
+
+	/// Drain the output buffer and optionally deassert DTR and RTS.
+	///
+	/// The blocking system calls are performed on a thread from the blocking thread pool of the Tokio runtime.
+	pub(crate) fn poll_shutdown_impl(&mut self, cx: &mut std::task::Context<'_>) -> Poll<std::io::Result<()>> {
+		let task = match &mut self.shutdown.task {
+			Some(task) => task,
+			None => {
+				let port = self.inner.with_raw(|raw| raw.try_clone())?;
+				let hangup = self.get_hangup_on_shutdown();
+				self.shutdown.task.insert(tokio::task::spawn_blocking(move || {
+					port.flush()?;
+					if hangup {
+						port.set_dtr(false)?;
+						port.set_rts(false)?;
+					}
+					Ok(())
+				}))
+			},
+		};
+		let result = ready!(Pin::new(task).poll(cx));
+		self.shutdown.task = None;
+		Poll::Ready(result.map_err(std::io::Error::other)?)
+	}
+}
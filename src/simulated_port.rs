@@ -0,0 +1,481 @@
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Poll, Waker};
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::time::{Instant, Sleep};
+
+use crate::rng::Rng;
+
+/// The timing and error behaviour of a simulated serial link.
+///
+/// Used by [`SimulatedPort::pair()`].
+#[derive(Debug, Clone)]
+pub struct SimulationConfig {
+	baud_rate: u32,
+	bits_per_char: u32,
+	latency: Duration,
+	jitter: Duration,
+	bit_error_rate: f64,
+	seed: u64,
+}
+
+impl Default for SimulationConfig {
+	fn default() -> Self {
+		Self {
+			baud_rate: 115200,
+			bits_per_char: 10,
+			latency: Duration::ZERO,
+			jitter: Duration::ZERO,
+			bit_error_rate: 0.0,
+			seed: 0,
+		}
+	}
+}
+
+impl SimulationConfig {
+	/// Create a new configuration with the default values.
+	///
+	/// The default configuration simulates a link at 115200 baud with 10 bits per character (8N1),
+	/// without additional latency, jitter or bit errors.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Set the baud rate of the link.
+	///
+	/// # Panics
+	/// This function panics if the baud rate is 0.
+	pub fn set_baud_rate(&mut self, baud_rate: u32) {
+		assert!(baud_rate > 0, "the baud rate can not be 0");
+		self.baud_rate = baud_rate;
+	}
+
+	/// Get the baud rate of the link.
+	pub fn get_baud_rate(&self) -> u32 {
+		self.baud_rate
+	}
+
+	/// Set the number of bits on the line for each character, including the start, parity and stop bits.
+	///
+	/// For example, use 10 for 8N1 or 12 for 8E2.
+	///
+	/// # Panics
+	/// This function panics if the number of bits is 0.
+	pub fn set_bits_per_char(&mut self, bits: u32) {
+		assert!(bits > 0, "the number of bits per character can not be 0");
+		self.bits_per_char = bits;
+	}
+
+	/// Get the number of bits on the line for each character.
+	pub fn get_bits_per_char(&self) -> u32 {
+		self.bits_per_char
+	}
+
+	/// Set the fixed delay between the transmission of a character and its delivery to the reader.
+	///
+	/// This simulates the latency of USB adapters and drivers.
+	pub fn set_latency(&mut self, latency: Duration) {
+		self.latency = latency;
+	}
+
+	/// Get the fixed delay between the transmission of a character and its delivery to the reader.
+	pub fn get_latency(&self) -> Duration {
+		self.latency
+	}
+
+	/// Set the maximum random delay that is added to the latency of each write.
+	///
+	/// The data is never reordered: a write is never delivered before the data of an earlier write.
+	pub fn set_jitter(&mut self, jitter: Duration) {
+		self.jitter = jitter;
+	}
+
+	/// Get the maximum random delay that is added to the latency of each write.
+	pub fn get_jitter(&self) -> Duration {
+		self.jitter
+	}
+
+	/// Set the probability that a data bit is flipped during transmission.
+	///
+	/// # Panics
+	/// This function panics if the probability is not in the range `0.0..=1.0`.
+	pub fn set_bit_error_rate(&mut self, probability: f64) {
+		assert!((0.0..=1.0).contains(&probability), "the bit error rate must be between 0.0 and 1.0");
+		self.bit_error_rate = probability;
+	}
+
+	/// Get the probability that a data bit is flipped during transmission.
+	pub fn get_bit_error_rate(&self) -> f64 {
+		self.bit_error_rate
+	}
+
+	/// Set the seed of the random number generator for the jitter and bit errors.
+	///
+	/// The same seed and the same sequence of writes give the same results.
+	pub fn set_seed(&mut self, seed: u64) {
+		self.seed = seed;
+	}
+
+	/// Get the seed of the random number generator.
+	pub fn get_seed(&self) -> u64 {
+		self.seed
+	}
+
+	/// Get the time it takes to transmit one character.
+	fn char_time(&self) -> Duration {
+		Duration::from_secs(self.bits_per_char.into()) / self.baud_rate
+	}
+}
+
+/// One end of a simulated serial link with realistic timing.
+///
+/// Unlike a pseudo-terminal pair (`SerialPort::pair()` on Unix), which transfers data instantly,
+/// a simulated link delivers each character after the time it takes to transmit it at the configured baud rate,
+/// optionally with additional latency, jitter and bit errors (see [`SimulationConfig`]).
+/// All timing uses the clock of Tokio, so the simulation runs on virtual time when the clock is paused with `tokio::time::pause()`
+/// (which needs the `test-util` feature of Tokio).
+/// This makes protocol tests with timeouts deterministic and fast.
+/// Note that the timers of Tokio have a resolution of one millisecond, so a read may complete up to a millisecond late.
+///
+/// The simulated port has the same read and write API as [`SerialPort`][crate::SerialPort].
+/// Writes complete immediately, as if the data was copied to the output buffer of the driver.
+/// Use [`Self::drain()`] to wait until the data has been transmitted.
+///
+/// The RTS and DTR lines of one end are connected to the CTS and DSR lines of the other end, like in a null-modem cable.
+/// The CD line follows the DTR line of the other end too.
+/// The RTS and DTR lines are asserted initially.
+///
+/// When one end is dropped, the other end reads the end of the stream after all data has been delivered,
+/// and writes fail with an error of kind [`std::io::ErrorKind::BrokenPipe`].
+///
+/// # Example
+/// ```no_run
+/// # async fn example() -> std::io::Result<()> {
+/// use serial2_tokio::{SimulatedPort, SimulationConfig};
+/// use std::time::Duration;
+///
+/// tokio::time::pause();
+/// let mut config = SimulationConfig::new();
+/// config.set_baud_rate(9600);
+/// config.set_latency(Duration::from_millis(2));
+/// let (device, host) = SimulatedPort::pair(&config);
+///
+/// let start = tokio::time::Instant::now();
+/// host.write_all(b"ping").await?;
+/// let mut buffer = [0; 4];
+/// let mut read = 0;
+/// while read < 4 {
+///     read += device.read(&mut buffer[read..]).await?;
+/// }
+/// // 4 characters of 10 bits at 9600 baud, plus the latency: about 6.2 ms of virtual time.
+/// println!("received after {:?}", start.elapsed());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct SimulatedPort {
+	shared: Arc<Mutex<Shared>>,
+	/// The index of the direction in which this end transmits.
+	side: usize,
+	/// The timer for the next delivery, used by the `AsyncRead` implementation.
+	read_sleep: Option<Pin<Box<Sleep>>>,
+	/// The timer for the end of the transmission, used by the `AsyncWrite` implementation.
+	drain_sleep: Option<Pin<Box<Sleep>>>,
+}
+
+#[derive(Debug)]
+struct Shared {
+	config: SimulationConfig,
+	rng: Rng,
+	directions: [Direction; 2],
+}
+
+/// The state of one direction of the link, named after the transmitting end.
+#[derive(Debug)]
+struct Direction {
+	/// The characters in transit, with the time they are delivered.
+	queue: VecDeque<(Instant, u8)>,
+	/// The time when the transmitter has sent all queued characters.
+	line_free_at: Instant,
+	/// The delivery time of the last queued character.
+	last_delivery: Instant,
+	/// The tasks waiting for data.
+	read_wakers: Vec<Waker>,
+	rts: bool,
+	dtr: bool,
+	/// The transmitting end has been dropped.
+	closed: bool,
+}
+
+impl Direction {
+	fn new(now: Instant) -> Self {
+		Self {
+			queue: VecDeque::new(),
+			line_free_at: now,
+			last_delivery: now,
+			read_wakers: Vec::new(),
+			rts: true,
+			dtr: true,
+			closed: false,
+		}
+	}
+
+	fn wake_readers(&mut self) {
+		for waker in self.read_wakers.drain(..) {
+			waker.wake();
+		}
+	}
+}
+
+impl SimulatedPort {
+	/// Create a simulated link and return both ends.
+	pub fn pair(config: &SimulationConfig) -> (Self, Self) {
+		let now = Instant::now();
+		let shared = Arc::new(Mutex::new(Shared {
+			config: config.clone(),
+			rng: Rng::new(config.seed),
+			directions: [Direction::new(now), Direction::new(now)],
+		}));
+		(Self::new(shared.clone(), 0), Self::new(shared, 1))
+	}
+
+	fn new(shared: Arc<Mutex<Shared>>, side: usize) -> Self {
+		Self {
+			shared,
+			side,
+			read_sleep: None,
+			drain_sleep: None,
+		}
+	}
+
+	/// Read bytes that have been delivered to this end.
+	///
+	/// Waits until at least one byte has been delivered.
+	/// Returns `Ok(0)` when the other end has been dropped and all data has been read.
+	pub async fn read(&self, buf: &mut [u8]) -> std::io::Result<usize> {
+		let mut sleep = None;
+		std::future::poll_fn(|cx| self.poll_read_slice(cx, buf, &mut sleep)).await
+	}
+
+	/// Write bytes to the link.
+	///
+	/// This always writes all bytes immediately, unless the other end has been dropped.
+	pub async fn write(&self, buf: &[u8]) -> std::io::Result<usize> {
+		self.write_slice(buf)
+	}
+
+	/// Write all bytes to the link.
+	pub async fn write_all(&self, buf: &[u8]) -> std::io::Result<()> {
+		self.write_slice(buf)?;
+		Ok(())
+	}
+
+	/// Wait until all written data has been transmitted.
+	///
+	/// The data may be delivered to the other end later, because of the configured latency and jitter.
+	pub async fn drain(&self) -> std::io::Result<()> {
+		let line_free_at = self.lock().directions[self.side].line_free_at;
+		tokio::time::sleep_until(line_free_at).await;
+		Ok(())
+	}
+
+	/// Discard data that has been delivered to this end but not read yet.
+	///
+	/// Data that is still in transit is not discarded.
+	pub fn discard_input_buffer(&self) -> std::io::Result<()> {
+		let now = Instant::now();
+		let mut shared = self.lock();
+		let rx = &mut shared.directions[1 - self.side];
+		while rx.queue.front().is_some_and(|&(time, _)| time <= now) {
+			rx.queue.pop_front();
+		}
+		Ok(())
+	}
+
+	/// Discard data that has been written but not transmitted yet.
+	pub fn discard_output_buffer(&self) -> std::io::Result<()> {
+		let now = Instant::now();
+		let mut shared = self.lock();
+		let char_time = shared.config.char_time();
+		let tx = &mut shared.directions[self.side];
+		if tx.line_free_at <= now {
+			return Ok(());
+		}
+		// Keep the characters that have already left the transmitter.
+		let pending = (tx.line_free_at - now).as_nanos() / char_time.as_nanos().max(1);
+		let keep = tx.queue.len().saturating_sub(pending as usize);
+		tx.queue.truncate(keep);
+		tx.line_free_at = now;
+		tx.last_delivery = tx.queue.back().map_or(now, |&(time, _)| time);
+		Ok(())
+	}
+
+	/// Set the state of the RTS line, which is connected to the CTS line of the other end.
+	pub fn set_rts(&self, state: bool) -> std::io::Result<()> {
+		self.lock().directions[self.side].rts = state;
+		Ok(())
+	}
+
+	/// Read the state of the RTS line of this end.
+	pub fn read_rts(&self) -> std::io::Result<bool> {
+		Ok(self.lock().directions[self.side].rts)
+	}
+
+	/// Set the state of the DTR line, which is connected to the DSR and CD lines of the other end.
+	pub fn set_dtr(&self, state: bool) -> std::io::Result<()> {
+		self.lock().directions[self.side].dtr = state;
+		Ok(())
+	}
+
+	/// Read the state of the DTR line of this end.
+	pub fn read_dtr(&self) -> std::io::Result<bool> {
+		Ok(self.lock().directions[self.side].dtr)
+	}
+
+	/// Read the state of the CTS line, which follows the RTS line of the other end.
+	pub fn read_cts(&self) -> std::io::Result<bool> {
+		Ok(self.lock().directions[1 - self.side].rts)
+	}
+
+	/// Read the state of the DSR line, which follows the DTR line of the other end.
+	pub fn read_dsr(&self) -> std::io::Result<bool> {
+		Ok(self.lock().directions[1 - self.side].dtr)
+	}
+
+	/// Read the state of the CD line, which follows the DTR line of the other end.
+	pub fn read_cd(&self) -> std::io::Result<bool> {
+		self.read_dsr()
+	}
+
+	/// Read the state of the RI line, which is never asserted.
+	pub fn read_ri(&self) -> std::io::Result<bool> {
+		Ok(false)
+	}
+
+	fn lock(&self) -> MutexGuard<'_, Shared> {
+		self.shared.lock().unwrap_or_else(|e| e.into_inner())
+	}
+
+	fn write_slice(&self, buf: &[u8]) -> std::io::Result<usize> {
+		let now = Instant::now();
+		let mut shared = self.lock();
+		let Shared { config, rng, directions } = &mut *shared;
+		if directions[1 - self.side].closed {
+			return Err(std::io::Error::new(std::io::ErrorKind::BrokenPipe, "the other end of the simulated link has been dropped"));
+		}
+		let char_time = config.char_time();
+		let delay = config.latency + rng.duration_up_to(config.jitter);
+		let tx = &mut directions[self.side];
+		let mut sent = tx.line_free_at.max(now);
+		for &byte in buf {
+			sent += char_time;
+			let mut byte = byte;
+			for bit in 0..8 {
+				if rng.chance(config.bit_error_rate) {
+					byte ^= 1 << bit;
+				}
+			}
+			let delivery = (sent + delay).max(tx.last_delivery);
+			tx.queue.push_back((delivery, byte));
+			tx.last_delivery = delivery;
+		}
+		tx.line_free_at = sent;
+		tx.wake_readers();
+		Ok(buf.len())
+	}
+
+	fn poll_read_slice(
+		&self,
+		cx: &mut std::task::Context<'_>,
+		buf: &mut [u8],
+		sleep: &mut Option<Pin<Box<Sleep>>>,
+	) -> Poll<std::io::Result<usize>> {
+		loop {
+			let now = Instant::now();
+			let next = {
+				let mut shared = self.lock();
+				let rx = &mut shared.directions[1 - self.side];
+				let mut read = 0;
+				while read < buf.len() {
+					match rx.queue.front() {
+						Some(&(time, byte)) if time <= now => {
+							buf[read] = byte;
+							read += 1;
+							rx.queue.pop_front();
+						},
+						_ => break,
+					}
+				}
+				if read > 0 || buf.is_empty() {
+					return Poll::Ready(Ok(read));
+				}
+				match rx.queue.front() {
+					Some(&(time, _)) => time,
+					None if rx.closed => return Poll::Ready(Ok(0)),
+					None => {
+						if !rx.read_wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+							rx.read_wakers.push(cx.waker().clone());
+						}
+						return Poll::Pending;
+					},
+				}
+			};
+			// Data written later is never delivered earlier, so only the first character in transit matters.
+			match sleep {
+				Some(sleep) if sleep.deadline() == next => (),
+				_ => *sleep = Some(Box::pin(tokio::time::sleep_until(next))),
+			}
+			if let Some(timer) = sleep {
+				std::task::ready!(timer.as_mut().poll(cx));
+			}
+			*sleep = None;
+		}
+	}
+}
+
+impl Drop for SimulatedPort {
+	fn drop(&mut self) {
+		let mut shared = self.lock();
+		let tx = &mut shared.directions[self.side];
+		tx.closed = true;
+		tx.wake_readers();
+	}
+}
+
+impl AsyncRead for SimulatedPort {
+	fn poll_read(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<std::io::Result<()>> {
+		let this = self.get_mut();
+		let mut sleep = this.read_sleep.take();
+		let result = this.poll_read_slice(cx, buf.initialize_unfilled(), &mut sleep);
+		this.read_sleep = sleep;
+		let read = std::task::ready!(result)?;
+		buf.advance(read);
+		Poll::Ready(Ok(()))
+	}
+}
+
+impl AsyncWrite for SimulatedPort {
+	fn poll_write(self: Pin<&mut Self>, _cx: &mut std::task::Context<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>> {
+		Poll::Ready(self.write_slice(buf))
+	}
+
+	fn poll_flush(self: Pin<&mut Self>, _cx: &mut std::task::Context<'_>) -> Poll<std::io::Result<()>> {
+		Poll::Ready(Ok(()))
+	}
+
+	fn poll_shutdown(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<std::io::Result<()>> {
+		let this = self.get_mut();
+		let line_free_at = this.lock().directions[this.side].line_free_at;
+		let sleep = this.drain_sleep.get_or_insert_with(|| Box::pin(tokio::time::sleep_until(line_free_at)));
+		if sleep.deadline() != line_free_at {
+			sleep.as_mut().reset(line_free_at);
+		}
+		std::task::ready!(sleep.as_mut().poll(cx));
+		this.drain_sleep = None;
+		Poll::Ready(Ok(()))
+	}
+}
@@ -0,0 +1,635 @@
+//! CAN bus adapters that use the SLCAN (LAWICEL) protocol.
+//!
+//! Many cheap USB-CAN adapters show up as a serial port and speak the ASCII based SLCAN protocol,
+//! originally defined by LAWICEL for the CANUSB adapter.
+//!
+//! The [`Slcan`] session sends configuration commands to the adapter, like setting the bitrate and opening the CAN channel.
+//! When the channel is open, [`Slcan::into_framed()`] turns the session into a [`Framed`] stream and sink of [`CanFrame`]s,
+//! using the [`SlcanCodec`].
+//!
+//! This module is only available when the `slcan` feature is enabled.
+//!
+//! # Example
+//! ```no_run
+//! # async fn example() -> std::io::Result<()> {
+//! use futures::{SinkExt, StreamExt};
+//! use serial2_tokio::SerialPort;
+//! use serial2_tokio::slcan::{Bitrate, CanFrame, CanId, Slcan};
+//!
+//! let port = SerialPort::open("/dev/ttyACM0", 115200)?;
+//! let mut slcan = Slcan::new(port);
+//! slcan.set_bitrate(Bitrate::Kbps500).await?;
+//! slcan.open().await?;
+//!
+//! let mut can = slcan.into_framed();
+//! can.send(CanFrame::new(CanId::Standard(0x123), &[1, 2, 3])?).await?;
+//! while let Some(frame) = can.next().await {
+//!     let frame = frame?;
+//!     println!("{:?}: {:02X?}", frame.id(), frame.data());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::time::Duration;
+
+use bytes::{Buf, BufMut, BytesMut};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio_util::codec::{Decoder, Encoder, Framed, FramedParts};
+
+/// The default timeout for the response to a command.
+const DEFAULT_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// The byte that terminates commands and responses.
+const TERMINATOR: u8 = b'\r';
+
+/// The byte that the adapter sends when a command failed.
+const ERROR: u8 = 0x07;
+
+/// The maximum length of a valid line: an extended frame with 8 data bytes and a timestamp.
+const MAX_LINE_LEN: usize = 1 + 8 + 1 + 16 + 4;
+
+/// The maximum standard (11 bit) CAN identifier.
+const MAX_STANDARD_ID: u16 = 0x7FF;
+
+/// The maximum extended (29 bit) CAN identifier.
+const MAX_EXTENDED_ID: u32 = 0x1FFF_FFFF;
+
+/// The identifier of a CAN frame.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum CanId {
+	/// A standard 11 bit identifier.
+	Standard(u16),
+
+	/// An extended 29 bit identifier.
+	Extended(u32),
+}
+
+impl CanId {
+	/// Check if the identifier fits in the number of bits of the identifier format.
+	pub fn is_valid(self) -> bool {
+		match self {
+			Self::Standard(id) => id <= MAX_STANDARD_ID,
+			Self::Extended(id) => id <= MAX_EXTENDED_ID,
+		}
+	}
+}
+
+/// A classic CAN frame with up to 8 data bytes.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct CanFrame {
+	id: CanId,
+	remote: bool,
+	len: u8,
+	data: [u8; 8],
+}
+
+impl CanFrame {
+	/// Create a data frame.
+	///
+	/// Returns an error of kind [`std::io::ErrorKind::InvalidInput`] if the identifier is not valid or if there are more than 8 data bytes.
+	pub fn new(id: CanId, data: &[u8]) -> std::io::Result<Self> {
+		check_id(id)?;
+		let len = check_len(data.len())?;
+		let mut frame = Self {
+			id,
+			remote: false,
+			len,
+			data: [0; 8],
+		};
+		frame.data[..data.len()].copy_from_slice(data);
+		Ok(frame)
+	}
+
+	/// Create a remote transmission request for `len` data bytes.
+	///
+	/// Returns an error of kind [`std::io::ErrorKind::InvalidInput`] if the identifier is not valid or if `len` is more than 8.
+	pub fn new_remote(id: CanId, len: usize) -> std::io::Result<Self> {
+		check_id(id)?;
+		let len = check_len(len)?;
+		Ok(Self {
+			id,
+			remote: true,
+			len,
+			data: [0; 8],
+		})
+	}
+
+	/// Get the identifier of the frame.
+	pub fn id(&self) -> CanId {
+		self.id
+	}
+
+	/// Check if the frame is a remote transmission request.
+	pub fn is_remote(&self) -> bool {
+		self.remote
+	}
+
+	/// Get the data length code of the frame.
+	///
+	/// For remote transmission requests, this is the number of requested data bytes.
+	pub fn len(&self) -> usize {
+		self.len.into()
+	}
+
+	/// Check if the data length code of the frame is zero.
+	pub fn is_empty(&self) -> bool {
+		self.len == 0
+	}
+
+	/// Get the data of the frame.
+	///
+	/// Remote transmission requests have no data, so this returns an empty slice for them.
+	pub fn data(&self) -> &[u8] {
+		if self.remote {
+			&[]
+		} else {
+			&self.data[..self.len.into()]
+		}
+	}
+}
+
+/// The bitrate of the CAN bus.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Bitrate {
+	/// 10 kbit/s.
+	Kbps10,
+
+	/// 20 kbit/s.
+	Kbps20,
+
+	/// 50 kbit/s.
+	Kbps50,
+
+	/// 100 kbit/s.
+	Kbps100,
+
+	/// 125 kbit/s.
+	Kbps125,
+
+	/// 250 kbit/s.
+	Kbps250,
+
+	/// 500 kbit/s.
+	Kbps500,
+
+	/// 800 kbit/s.
+	Kbps800,
+
+	/// 1 Mbit/s.
+	Mbps1,
+}
+
+impl Bitrate {
+	/// Get the command to set the bitrate, without terminator.
+	fn command(self) -> &'static str {
+		match self {
+			Self::Kbps10 => "S0",
+			Self::Kbps20 => "S1",
+			Self::Kbps50 => "S2",
+			Self::Kbps100 => "S3",
+			Self::Kbps125 => "S4",
+			Self::Kbps250 => "S5",
+			Self::Kbps500 => "S6",
+			Self::Kbps800 => "S7",
+			Self::Mbps1 => "S8",
+		}
+	}
+}
+
+/// A configuration session with an SLCAN adapter.
+///
+/// See the [module documentation](self) for more information.
+#[derive(Debug)]
+pub struct Slcan<T> {
+	inner: T,
+	buffer: BytesMut,
+	timeout: Duration,
+}
+
+impl<T> Slcan<T> {
+	/// Create a session with an SLCAN adapter.
+	///
+	/// The baud rate of the serial port is ignored by most USB adapters.
+	/// For adapters with a real UART, the serial port must be configured with the baud rate of the adapter.
+	pub fn new(inner: T) -> Self {
+		Self {
+			inner,
+			buffer: BytesMut::new(),
+			timeout: DEFAULT_TIMEOUT,
+		}
+	}
+
+	/// Set the timeout for the response to a command.
+	///
+	/// The default timeout is 500 milliseconds.
+	pub fn set_timeout(&mut self, timeout: Duration) {
+		self.timeout = timeout;
+	}
+
+	/// Get the timeout for the response to a command.
+	pub fn get_timeout(&self) -> Duration {
+		self.timeout
+	}
+
+	/// Get a reference to the wrapped stream.
+	pub fn get_ref(&self) -> &T {
+		&self.inner
+	}
+
+	/// Consume the session and return the wrapped stream.
+	///
+	/// Received data that has not been processed yet is lost.
+	pub fn into_inner(self) -> T {
+		self.inner
+	}
+
+	/// Turn the session into a stream and sink of CAN frames.
+	///
+	/// Received data that has not been processed yet is passed on to the [`SlcanCodec`].
+	/// To close the CAN channel later, use [`Framed::into_inner()`] and create a new session.
+	pub fn into_framed(self) -> Framed<T, SlcanCodec> {
+		let mut parts = FramedParts::new::<CanFrame>(self.inner, SlcanCodec::new());
+		parts.read_buf = self.buffer;
+		Framed::from_parts(parts)
+	}
+}
+
+impl<T: AsyncRead + AsyncWrite + Unpin> Slcan<T> {
+	/// Set the bitrate of the CAN bus.
+	///
+	/// The bitrate can only be changed while the CAN channel is closed.
+	pub async fn set_bitrate(&mut self, bitrate: Bitrate) -> std::io::Result<()> {
+		self.command(bitrate.command()).await
+	}
+
+	/// Open the CAN channel.
+	pub async fn open(&mut self) -> std::io::Result<()> {
+		self.command("O").await
+	}
+
+	/// Open the CAN channel in listen only mode.
+	///
+	/// In listen only mode, the adapter does not acknowledge frames and can not send frames.
+	/// Not all adapters support this mode.
+	pub async fn open_listen_only(&mut self) -> std::io::Result<()> {
+		self.command("L").await
+	}
+
+	/// Close the CAN channel.
+	pub async fn close(&mut self) -> std::io::Result<()> {
+		self.command("C").await
+	}
+
+	/// Send a command and wait for the response.
+	///
+	/// Received CAN frames are discarded while waiting for the response.
+	async fn command(&mut self, command: &str) -> std::io::Result<()> {
+		let mut message = Vec::with_capacity(command.len() + 1);
+		message.extend_from_slice(command.as_bytes());
+		message.push(TERMINATOR);
+		self.inner.write_all(&message).await?;
+		self.inner.flush().await?;
+
+		tokio::time::timeout(self.timeout, self.read_response(command)).await
+			.map_err(|_| std::io::Error::new(std::io::ErrorKind::TimedOut, "timeout while waiting for SLCAN response"))?
+	}
+
+	/// Read the response to a command, skipping received CAN frames.
+	async fn read_response(&mut self, command: &str) -> std::io::Result<()> {
+		loop {
+			if let Some(end) = self.buffer.iter().position(|&byte| byte == TERMINATOR || byte == ERROR) {
+				let byte = self.buffer[end];
+				self.buffer.advance(end + 1);
+				if byte == ERROR {
+					return Err(std::io::Error::other(format!("SLCAN adapter rejected command {command:?}")));
+				} else if end == 0 {
+					return Ok(());
+				}
+				// A non-empty line is a received CAN frame or a late response to an earlier command.
+				continue;
+			}
+			if self.inner.read_buf(&mut self.buffer).await? == 0 {
+				return Err(std::io::ErrorKind::UnexpectedEof.into());
+			}
+		}
+	}
+}
+
+/// A codec for the CAN frames sent and received by an SLCAN adapter.
+///
+/// The decoder produces the CAN frames received by the adapter.
+/// Frames with a timestamp are accepted, but the timestamp is ignored.
+/// The acknowledgements for transmitted frames are skipped,
+/// and errors reported by the adapter are counted (see [`Self::errors()`]).
+/// Invalid lines are discarded.
+///
+/// The encoder sends CAN frames with the `t`, `T`, `r` and `R` commands.
+///
+/// See the [module documentation](self) for more information.
+#[derive(Debug, Clone, Default)]
+pub struct SlcanCodec {
+	errors: u64,
+	discarded: u64,
+}
+
+impl SlcanCodec {
+	/// Create a new codec.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Get the total number of errors reported by the adapter.
+	///
+	/// The adapter reports an error if it could not send a frame, for example because the CAN channel is not open.
+	pub fn errors(&self) -> u64 {
+		self.errors
+	}
+
+	/// Get the total number of bytes discarded by the decoder because they did not form a valid line.
+	pub fn discarded_bytes(&self) -> u64 {
+		self.discarded
+	}
+}
+
+impl Decoder for SlcanCodec {
+	type Item = CanFrame;
+	type Error = std::io::Error;
+
+	fn decode(&mut self, src: &mut BytesMut) -> Result<Option<CanFrame>, std::io::Error> {
+		loop {
+			let Some(end) = src.iter().position(|&byte| byte == TERMINATOR || byte == ERROR) else {
+				if src.len() > MAX_LINE_LEN {
+					self.discarded += src.len() as u64;
+					src.clear();
+				}
+				return Ok(None);
+			};
+
+			let line = src.split_to(end);
+			let byte = src.get_u8();
+			if byte == ERROR {
+				self.discarded += line.len() as u64;
+				self.errors += 1;
+				continue;
+			}
+			match line.first() {
+				// An empty line is the response to a command, and `z` or `Z` is the acknowledgement of a transmitted frame.
+				None => continue,
+				Some(b'z' | b'Z') if line.len() == 1 => continue,
+				_ => (),
+			}
+			match parse_frame(&line) {
+				Some(frame) => return Ok(Some(frame)),
+				None => self.discarded += line.len() as u64 + 1,
+			}
+		}
+	}
+}
+
+impl Encoder<CanFrame> for SlcanCodec {
+	type Error = std::io::Error;
+
+	fn encode(&mut self, frame: CanFrame, dst: &mut BytesMut) -> Result<(), std::io::Error> {
+		dst.reserve(MAX_LINE_LEN + 1);
+		match (frame.id, frame.remote) {
+			(CanId::Standard(id), false) => {
+				dst.put_u8(b't');
+				put_hex(dst, id.into(), 3);
+			},
+			(CanId::Standard(id), true) => {
+				dst.put_u8(b'r');
+				put_hex(dst, id.into(), 3);
+			},
+			(CanId::Extended(id), false) => {
+				dst.put_u8(b'T');
+				put_hex(dst, id, 8);
+			},
+			(CanId::Extended(id), true) => {
+				dst.put_u8(b'R');
+				put_hex(dst, id, 8);
+			},
+		}
+		put_hex(dst, frame.len.into(), 1);
+		for &byte in frame.data() {
+			put_hex(dst, byte.into(), 2);
+		}
+		dst.put_u8(TERMINATOR);
+		Ok(())
+	}
+}
+
+/// Parse a received CAN frame, without the terminator.
+fn parse_frame(line: &[u8]) -> Option<CanFrame> {
+	let (id_digits, remote) = match line.first()? {
+		b't' => (3, false),
+		b'r' => (3, true),
+		b'T' => (8, false),
+		b'R' => (8, true),
+		_ => return None,
+	};
+	let id = parse_hex(line.get(1..1 + id_digits)?)?;
+	let id = if id_digits == 3 {
+		CanId::Standard(id as u16)
+	} else {
+		CanId::Extended(id)
+	};
+	let len = parse_hex(line.get(1 + id_digits..2 + id_digits)?)? as usize;
+	if len > 8 {
+		return None;
+	}
+	let rest = &line[2 + id_digits..];
+	let data_digits = if remote { 0 } else { len * 2 };
+
+	// The data may be followed by a timestamp of 4 hex digits.
+	if rest.len() != data_digits && rest.len() != data_digits + 4 {
+		return None;
+	}
+	if !rest.iter().all(u8::is_ascii_hexdigit) {
+		return None;
+	}
+	if remote {
+		return CanFrame::new_remote(id, len).ok();
+	}
+	let mut data = [0; 8];
+	for (i, byte) in data.iter_mut().enumerate().take(len) {
+		*byte = parse_hex(&rest[i * 2..i * 2 + 2])? as u8;
+	}
+	CanFrame::new(id, &data[..len]).ok()
+}
+
+/// Parse a hexadecimal number.
+fn parse_hex(digits: &[u8]) -> Option<u32> {
+	if !digits.iter().all(u8::is_ascii_hexdigit) {
+		return None;
+	}
+	u32::from_str_radix(std::str::from_utf8(digits).ok()?, 16).ok()
+}
+
+/// Write a number as a fixed number of uppercase hexadecimal digits.
+fn put_hex(dst: &mut BytesMut, value: u32, digits: u32) {
+	for i in (0..digits).rev() {
+		let nibble = (value >> (i * 4)) & 0xF;
+		dst.put_u8(b"0123456789ABCDEF"[nibble as usize]);
+	}
+}
+
+/// Check that a CAN identifier is valid.
+fn check_id(id: CanId) -> std::io::Result<()> {
+	if !id.is_valid() {
+		return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("invalid CAN identifier: {id:?}")));
+	}
+	Ok(())
+}
+
+/// Check the data length of a CAN frame.
+fn check_len(len: usize) -> std::io::Result<u8> {
+	if len > 8 {
+		return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "a CAN frame can not have more than 8 data bytes"));
+	}
+	Ok(len as u8)
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	fn decode_all(codec: &mut SlcanCodec, data: &[u8]) -> Vec<CanFrame> {
+		let mut buffer = BytesMut::from(data);
+		let mut frames = Vec::new();
+		while let Some(frame) = codec.decode(&mut buffer).unwrap() {
+			frames.push(frame);
+		}
+		frames
+	}
+
+	fn encode(frame: CanFrame) -> BytesMut {
+		let mut buffer = BytesMut::new();
+		SlcanCodec::new().encode(frame, &mut buffer).unwrap();
+		buffer
+	}
+
+	#[test]
+	fn encode_frames() {
+		// The examples of the LAWICEL CANUSB manual.
+		assert_eq!(encode(CanFrame::new(CanId::Standard(0x123), &[0x11, 0x22, 0x33]).unwrap()), "t1233112233\r");
+		assert_eq!(encode(CanFrame::new(CanId::Standard(0x456), &[]).unwrap()), "t4560\r");
+		assert_eq!(encode(CanFrame::new(CanId::Extended(0x12ABCDEF), &[0xAA, 0x55]).unwrap()), "T12ABCDEF2AA55\r");
+		assert_eq!(encode(CanFrame::new_remote(CanId::Standard(0x123), 0).unwrap()), "r1230\r");
+		assert_eq!(encode(CanFrame::new_remote(CanId::Extended(0x12ABCDEF), 2).unwrap()), "R12ABCDEF2\r");
+	}
+
+	#[test]
+	fn decode_frames() {
+		let mut codec = SlcanCodec::new();
+		let frames = decode_all(&mut codec, b"t1233112233\rT12ABCDEF2AA55\rr1234\rR12ABCDEF0\r");
+		assert_eq!(frames, [
+			CanFrame::new(CanId::Standard(0x123), &[0x11, 0x22, 0x33]).unwrap(),
+			CanFrame::new(CanId::Extended(0x12ABCDEF), &[0xAA, 0x55]).unwrap(),
+			CanFrame::new_remote(CanId::Standard(0x123), 4).unwrap(),
+			CanFrame::new_remote(CanId::Extended(0x12ABCDEF), 0).unwrap(),
+		]);
+		assert_eq!(codec.errors(), 0);
+		assert_eq!(codec.discarded_bytes(), 0);
+	}
+
+	#[test]
+	fn decode_frame_with_timestamp() {
+		let mut codec = SlcanCodec::new();
+		let frames = decode_all(&mut codec, b"t1232AABB1F3A\r");
+		assert_eq!(frames, [CanFrame::new(CanId::Standard(0x123), &[0xAA, 0xBB]).unwrap()]);
+	}
+
+	#[test]
+	fn decode_skips_acknowledgements_and_counts_errors() {
+		let mut codec = SlcanCodec::new();
+		let frames = decode_all(&mut codec, b"z\rZ\r\r\x07t1230\r");
+		assert_eq!(frames, [CanFrame::new(CanId::Standard(0x123), &[]).unwrap()]);
+		assert_eq!(codec.errors(), 1);
+	}
+
+	#[test]
+	fn decode_discards_invalid_lines() {
+		let mut codec = SlcanCodec::new();
+		// Identifier too large, too many data bytes, wrong number of data digits, and an unknown command.
+		let frames = decode_all(&mut codec, b"t8000\rt1239\rt12321\rV1013\rt1230\r");
+		assert_eq!(frames, [CanFrame::new(CanId::Standard(0x123), &[]).unwrap()]);
+		assert_eq!(codec.discarded_bytes(), 6 + 6 + 7 + 6);
+	}
+
+	#[test]
+	fn decode_waits_for_terminator() {
+		let mut codec = SlcanCodec::new();
+		let mut buffer = BytesMut::from(&b"t123111"[..]);
+		assert!(codec.decode(&mut buffer).unwrap().is_none());
+		buffer.extend_from_slice(b"\r");
+		assert_eq!(codec.decode(&mut buffer).unwrap(), Some(CanFrame::new(CanId::Standard(0x123), &[0x11]).unwrap()));
+	}
+
+	#[test]
+	fn decode_discards_overlong_garbage() {
+		let mut codec = SlcanCodec::new();
+		let mut buffer = BytesMut::from(&[b'x'; MAX_LINE_LEN + 1][..]);
+		assert!(codec.decode(&mut buffer).unwrap().is_none());
+		assert!(buffer.is_empty());
+		assert_eq!(codec.discarded_bytes(), MAX_LINE_LEN as u64 + 1);
+	}
+
+	#[test]
+	fn frame_validation() {
+		assert!(CanId::Standard(0x7FF).is_valid());
+		assert!(!CanId::Standard(0x800).is_valid());
+		assert!(CanId::Extended(0x1FFF_FFFF).is_valid());
+		assert!(!CanId::Extended(0x2000_0000).is_valid());
+		assert_eq!(CanFrame::new(CanId::Standard(0x800), &[]).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+		assert_eq!(CanFrame::new(CanId::Standard(1), &[0; 9]).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+
+		let remote = CanFrame::new_remote(CanId::Standard(1), 3).unwrap();
+		assert!(remote.is_remote());
+		assert_eq!(remote.len(), 3);
+		assert!(remote.data().is_empty());
+	}
+
+	#[tokio::test]
+	async fn session_commands() {
+		let (client, mut adapter) = tokio::io::duplex(64);
+		let mut slcan = Slcan::new(client);
+		let adapter_side = async {
+			let mut buffer = [0; 3];
+			adapter.read_exact(&mut buffer).await.unwrap();
+			assert_eq!(&buffer, b"S6\r");
+			adapter.write_all(b"\r").await.unwrap();
+			adapter.read_exact(&mut buffer[..2]).await.unwrap();
+			assert_eq!(&buffer[..2], b"O\r");
+			// A received frame before the response is skipped.
+			adapter.write_all(b"t1230\r\r").await.unwrap();
+			adapter.read_exact(&mut buffer[..2]).await.unwrap();
+			assert_eq!(&buffer[..2], b"L\r");
+			adapter.write_all(b"\x07").await.unwrap();
+			adapter
+		};
+		let (result, mut adapter) = tokio::join!(
+			async {
+				slcan.set_bitrate(Bitrate::Kbps500).await?;
+				slcan.open().await?;
+				slcan.open_listen_only().await
+			},
+			adapter_side,
+		);
+		assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::Other);
+
+		// Data that was received after the response is passed to the codec.
+		adapter.write_all(b"t4561AB\r").await.unwrap();
+		let mut framed = slcan.into_framed();
+		let frame = futures::StreamExt::next(&mut framed).await.unwrap().unwrap();
+		assert_eq!(frame, CanFrame::new(CanId::Standard(0x456), &[0xAB]).unwrap());
+	}
+
+	#[tokio::test]
+	async fn session_command_times_out() {
+		let (client, _adapter) = tokio::io::duplex(64);
+		let mut slcan = Slcan::new(client);
+		slcan.set_timeout(Duration::from_millis(10));
+		assert_eq!(slcan.close().await.unwrap_err().kind(), std::io::ErrorKind::TimedOut);
+	}
+}
@@ -0,0 +1,333 @@
+//! Passively capture the traffic on a serial link.
+//!
+//! A [`Sniffer`] reads from two serial ports that each observe one direction of a serial link,
+//! for example through a tap cable that connects the TX line of each side to the RX line of a separate serial port.
+//! The data from both ports is merged into a single stream of [`Capture`]s,
+//! each tagged with the [`Direction`] of the data and the time at which it was received.
+//!
+//! If only one direction is of interest, use [`Sniffer::single()`] with a single serial port.
+//!
+//! Optionally, the sniffer can write all captured data to a log in a human readable text format, in the `pcap` format or in the `pcapng` format.
+//! The [`PcapngWriter`] can also be used on its own to record captures.
+//!
+//! The serial ports are normally opened with [`SerialPort::open_observer()`] with [`KeepSettings`][crate::KeepSettings],
+//! so that the sniffer does not disturb the link.
+//! If the serial ports used for sniffing are not configured yet, use [`OpenOptions`][crate::OpenOptions] to open them for reading only with the baud rate of the link.
+//!
+//! # Example
+//! ```no_run
+//! # async fn example() -> std::io::Result<()> {
+//! use serial2_tokio::OpenOptions;
+//! use serial2_tokio::sniffer::Sniffer;
+//!
+//! let a = OpenOptions::new().read_only(true).open("/dev/ttyUSB0", 115200)?;
+//! let b = OpenOptions::new().read_only(true).open("/dev/ttyUSB1", 115200)?;
+//! let mut sniffer = Sniffer::new(a, b)
+//!     .pcap_log(std::fs::File::create("capture.pcap")?);
+//! while let Some(capture) = sniffer.next_capture().await? {
+//!     println!("{}: {:02X?}", capture.direction, capture.data);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::io::Write;
+use std::time::{Duration, SystemTime};
+
+use crate::{PortEvent, PortSet, SerialPort};
+
+mod pcapng;
+
+pub use pcapng::PcapngWriter;
+
+/// The link type of the `pcap` log: `LINKTYPE_USER0`.
+const PCAP_LINKTYPE: u32 = 147;
+
+/// The maximum length of a packet in the `pcap` log.
+const PCAP_SNAPLEN: u32 = 65535;
+
+/// The direction of captured data on a serial link.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Direction {
+	/// The data was sent from side A to side B.
+	AToB,
+
+	/// The data was sent from side B to side A.
+	BToA,
+}
+
+impl Direction {
+	/// Get the direction in the opposite way.
+	pub fn reversed(self) -> Self {
+		match self {
+			Self::AToB => Self::BToA,
+			Self::BToA => Self::AToB,
+		}
+	}
+}
+
+impl std::fmt::Display for Direction {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::AToB => f.write_str("A->B"),
+			Self::BToA => f.write_str("B->A"),
+		}
+	}
+}
+
+/// Data captured by a [`Sniffer`].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct Capture {
+	/// The direction in which the data was sent.
+	pub direction: Direction,
+
+	/// The time at which the data was received by the sniffer.
+	///
+	/// The data may have been sent a little earlier, depending on the buffering done by the OS and the serial port hardware.
+	pub timestamp: SystemTime,
+
+	/// The captured data.
+	pub data: Vec<u8>,
+}
+
+/// Capture the traffic on a serial link.
+///
+/// See the [module documentation](self) for more information.
+pub struct Sniffer {
+	ports: PortSet,
+	/// The direction of the data from each port, indexed by the index of the port in the set.
+	directions: Vec<Direction>,
+	log: Option<Log>,
+}
+
+impl std::fmt::Debug for Sniffer {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("Sniffer")
+			.field("ports", &self.ports)
+			.field("directions", &self.directions)
+			.finish_non_exhaustive()
+	}
+}
+
+/// The log of a sniffer.
+enum Log {
+	Text(Box<dyn Write + Send>),
+	Pcap {
+		writer: Box<dyn Write + Send>,
+		header_written: bool,
+	},
+	Pcapng {
+		writer: PcapngWriter<Box<dyn Write + Send>>,
+		header_written: bool,
+	},
+}
+
+impl Sniffer {
+	/// Create a sniffer for both directions of a serial link.
+	///
+	/// Data received on serial port `a` was sent by side A of the link, so it is tagged with [`Direction::AToB`].
+	/// Data received on serial port `b` was sent by side B of the link, so it is tagged with [`Direction::BToA`].
+	pub fn new(a: SerialPort, b: SerialPort) -> Self {
+		let mut sniffer = Self::empty();
+		sniffer.add_port(a, Direction::AToB);
+		sniffer.add_port(b, Direction::BToA);
+		sniffer
+	}
+
+	/// Create a sniffer for a single direction of a serial link.
+	///
+	/// All data received on the serial port is tagged with the given direction.
+	pub fn single(port: SerialPort, direction: Direction) -> Self {
+		let mut sniffer = Self::empty();
+		sniffer.add_port(port, direction);
+		sniffer
+	}
+
+	fn empty() -> Self {
+		Self {
+			ports: PortSet::new(),
+			directions: Vec::new(),
+			log: None,
+		}
+	}
+
+	fn add_port(&mut self, port: SerialPort, direction: Direction) {
+		// Ports are never removed from the set, so the index of each new port is the next index.
+		self.ports.insert(port);
+		self.directions.push(direction);
+	}
+
+	/// Write all captured data to a log in a human readable text format.
+	///
+	/// Each capture is written as a single line with the timestamp in seconds since the Unix epoch,
+	/// the direction, the data in hexadecimal and the data as ASCII text.
+	/// Bytes that are not printable ASCII characters are shown as `.` in the text.
+	///
+	/// The log is written directly from [`Self::next_capture()`],
+	/// so the writer should not block for a long time.
+	/// This replaces any log that was set earlier.
+	pub fn text_log(mut self, writer: impl Write + Send + 'static) -> Self {
+		self.log = Some(Log::Text(Box::new(writer)));
+		self
+	}
+
+	/// Write all captured data to a log in the `pcap` format.
+	///
+	/// The log uses the `LINKTYPE_USER0` link type.
+	/// Each packet starts with a single byte for the direction of the data:
+	/// `0` for [`Direction::AToB`] and `1` for [`Direction::BToA`].
+	/// The captured data follows directly after the direction byte.
+	///
+	/// The log is written directly from [`Self::next_capture()`],
+	/// so the writer should not block for a long time.
+	/// This replaces any log that was set earlier.
+	pub fn pcap_log(mut self, writer: impl Write + Send + 'static) -> Self {
+		self.log = Some(Log::Pcap {
+			writer: Box::new(writer),
+			header_written: false,
+		});
+		self
+	}
+
+	/// Write all captured data to a log in the `pcapng` format.
+	///
+	/// Each serial port of the sniffer is recorded as a separate interface, with the baud rate of the serial port.
+	/// See [`PcapngWriter`] for more information about the format.
+	///
+	/// The log is written directly from [`Self::next_capture()`],
+	/// so the writer should not block for a long time.
+	/// This replaces any log that was set earlier.
+	pub fn pcapng_log(mut self, writer: impl Write + Send + 'static) -> Self {
+		self.log = Some(Log::Pcapng {
+			writer: PcapngWriter::new(Box::new(writer)),
+			header_written: false,
+		});
+		self
+	}
+
+	/// Get a serial port of the sniffer by direction.
+	pub fn port(&self, direction: Direction) -> Option<&SerialPort> {
+		self.ports.iter()
+			.find(|(id, _)| self.directions[id.index()] == direction)
+			.map(|(_, port)| port)
+	}
+
+	/// Wait for the next captured data.
+	///
+	/// If a log was configured, the data is written to the log before it is returned.
+	///
+	/// Returns `Ok(None)` when all serial ports have reported end-of-file,
+	/// which normally means the devices were removed.
+	/// If reading from a serial port or writing to the log fails, the error is returned.
+	/// A serial port that failed is not read from again, but the sniffer can still be used to capture data from the other port.
+	pub async fn next_capture(&mut self) -> std::io::Result<Option<Capture>> {
+		if let Some(log) = &mut self.log {
+			log.write_header(&self.ports, &self.directions)?;
+		}
+
+		loop {
+			let Some((id, event)) = self.ports.next_event().await else {
+				return Ok(None);
+			};
+			let data = match event {
+				PortEvent::Data(data) => data,
+				PortEvent::Error(e) => return Err(e),
+				_ => continue,
+			};
+
+			let capture = Capture {
+				direction: self.directions[id.index()],
+				timestamp: SystemTime::now(),
+				data,
+			};
+			if let Some(log) = &mut self.log {
+				log.write_capture(&capture)?;
+			}
+			return Ok(Some(capture));
+		}
+	}
+
+	/// Capture data until all serial ports have reported end-of-file.
+	///
+	/// This is useful when the captured data is only written to a log.
+	/// The function returns an error as soon as reading from a serial port or writing to the log fails.
+	pub async fn run(&mut self) -> std::io::Result<()> {
+		while self.next_capture().await?.is_some() {}
+		Ok(())
+	}
+}
+
+impl Log {
+	fn write_header(&mut self, ports: &PortSet, directions: &[Direction]) -> std::io::Result<()> {
+		match self {
+			Self::Text(_) => Ok(()),
+			Self::Pcap { header_written: true, .. } | Self::Pcapng { header_written: true, .. } => Ok(()),
+			Self::Pcap { writer, header_written } => {
+				let mut header = Vec::with_capacity(24);
+				header.extend_from_slice(&0xA1B2_C3D4u32.to_le_bytes());
+				header.extend_from_slice(&2u16.to_le_bytes());
+				header.extend_from_slice(&4u16.to_le_bytes());
+				header.extend_from_slice(&0i32.to_le_bytes());
+				header.extend_from_slice(&0u32.to_le_bytes());
+				header.extend_from_slice(&PCAP_SNAPLEN.to_le_bytes());
+				header.extend_from_slice(&PCAP_LINKTYPE.to_le_bytes());
+				writer.write_all(&header)?;
+				writer.flush()?;
+				*header_written = true;
+				Ok(())
+			},
+			Self::Pcapng { writer, header_written } => {
+				for (id, port) in ports.iter() {
+					let direction = directions[id.index()];
+					let baud_rate = port.get_configuration().and_then(|settings| settings.get_baud_rate()).ok();
+					writer.add_interface(direction, &direction.to_string(), baud_rate)?;
+				}
+				*header_written = true;
+				Ok(())
+			},
+		}
+	}
+
+	fn write_capture(&mut self, capture: &Capture) -> std::io::Result<()> {
+		let timestamp = capture.timestamp.duration_since(SystemTime::UNIX_EPOCH).unwrap_or(Duration::ZERO);
+		match self {
+			Self::Text(writer) => {
+				let hex: Vec<String> = capture.data.iter().map(|byte| format!("{byte:02X}")).collect();
+				let text: String = capture.data.iter()
+					.map(|&byte| if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' })
+					.collect();
+				writeln!(
+					writer,
+					"{}.{:06} {} {} |{}|",
+					timestamp.as_secs(),
+					timestamp.subsec_micros(),
+					capture.direction,
+					hex.join(" "),
+					text,
+				)?;
+				writer.flush()
+			},
+			Self::Pcap { writer, .. } => {
+				let direction = match capture.direction {
+					Direction::AToB => 0u8,
+					Direction::BToA => 1u8,
+				};
+				// Split large captures over multiple packets to stay within the snapshot length.
+				for chunk in capture.data.chunks(PCAP_SNAPLEN as usize - 1) {
+					let length = chunk.len() as u32 + 1;
+					let mut record = Vec::with_capacity(16 + length as usize);
+					record.extend_from_slice(&(timestamp.as_secs() as u32).to_le_bytes());
+					record.extend_from_slice(&timestamp.subsec_micros().to_le_bytes());
+					record.extend_from_slice(&length.to_le_bytes());
+					record.extend_from_slice(&length.to_le_bytes());
+					record.push(direction);
+					record.extend_from_slice(chunk);
+					writer.write_all(&record)?;
+				}
+				writer.flush()
+			},
+			Self::Pcapng { writer, .. } => writer.write_capture(capture),
+		}
+	}
+}
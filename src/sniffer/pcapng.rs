@@ -0,0 +1,343 @@
+use std::io::Write;
+use std::time::{Duration, SystemTime};
+
+use super::{Capture, Direction};
+
+/// The link type of the interfaces: `LINKTYPE_USER0`.
+const LINKTYPE: u16 = 147;
+
+/// The byte-order magic of a section header block.
+const BYTE_ORDER_MAGIC: u32 = 0x1A2B_3C4D;
+
+/// Block types.
+const BLOCK_SECTION_HEADER: u32 = 0x0A0D_0D0A;
+const BLOCK_INTERFACE_DESCRIPTION: u32 = 0x0000_0001;
+const BLOCK_ENHANCED_PACKET: u32 = 0x0000_0006;
+
+/// Option codes.
+const OPT_END: u16 = 0;
+const OPT_SHB_USERAPPL: u16 = 4;
+const OPT_IF_NAME: u16 = 2;
+const OPT_IF_DESCRIPTION: u16 = 3;
+const OPT_IF_SPEED: u16 = 8;
+const OPT_IF_TSRESOL: u16 = 9;
+const OPT_EPB_FLAGS: u16 = 2;
+
+/// Packet direction flags for the `epb_flags` option.
+const EPB_FLAG_INBOUND: u32 = 0b01;
+const EPB_FLAG_OUTBOUND: u32 = 0b10;
+
+/// Write captured serial traffic in the `pcapng` format.
+///
+/// The file can be opened with Wireshark and other tools that support the `pcapng` format.
+/// Each direction of the serial link is recorded as a separate interface with the `LINKTYPE_USER0` link type,
+/// so that Wireshark can be configured to decode the data with a dissector for the protocol on the serial link.
+/// The interfaces can carry the name and baud rate of the serial port that captured the data.
+///
+/// Each packet records the direction of the data as seen from side A of the link:
+/// data sent from A to B is marked as outbound, and data sent from B to A is marked as inbound.
+/// Timestamps are recorded with nanosecond resolution.
+///
+/// The writer can be used directly with the [`Capture`]s from a [`Sniffer`][super::Sniffer],
+/// or you can use [`Sniffer::pcapng_log()`][super::Sniffer::pcapng_log] to let the sniffer write the log.
+///
+/// # Example
+/// ```no_run
+/// # async fn example() -> std::io::Result<()> {
+/// use serial2_tokio::SerialPort;
+/// use serial2_tokio::sniffer::{Direction, PcapngWriter, Sniffer};
+///
+/// let a = SerialPort::open_observer("/dev/ttyUSB0")?;
+/// let b = SerialPort::open_observer("/dev/ttyUSB1")?;
+/// let mut writer = PcapngWriter::new(std::fs::File::create("capture.pcapng")?);
+/// writer.add_interface(Direction::AToB, "/dev/ttyUSB0", Some(115200))?;
+/// writer.add_interface(Direction::BToA, "/dev/ttyUSB1", Some(115200))?;
+///
+/// let mut sniffer = Sniffer::new(a, b);
+/// while let Some(capture) = sniffer.next_capture().await? {
+///     writer.write_capture(&capture)?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct PcapngWriter<W: Write> {
+	writer: W,
+	section_written: bool,
+	/// The direction of each interface, indexed by interface ID.
+	interfaces: Vec<Direction>,
+}
+
+impl<W: Write> PcapngWriter<W> {
+	/// Create a new `pcapng` writer.
+	///
+	/// Nothing is written until the first interface or capture is added.
+	pub fn new(writer: W) -> Self {
+		Self {
+			writer,
+			section_written: false,
+			interfaces: Vec::new(),
+		}
+	}
+
+	/// Add an interface for one direction of the serial link.
+	///
+	/// The `name` is normally the name or path of the serial port that observes the direction.
+	/// If the baud rate is given, it is recorded as the speed of the interface.
+	///
+	/// If a capture is written for a direction without an interface,
+	/// an interface is added automatically with the direction as name.
+	///
+	/// Returns an error of kind [`std::io::ErrorKind::AlreadyExists`] if the direction already has an interface.
+	pub fn add_interface(&mut self, direction: Direction, name: &str, baud_rate: Option<u32>) -> std::io::Result<()> {
+		if self.interfaces.contains(&direction) {
+			return Err(std::io::Error::new(std::io::ErrorKind::AlreadyExists, format!("direction {direction} already has an interface")));
+		}
+		self.write_section_header()?;
+
+		let mut body = Vec::new();
+		body.extend_from_slice(&LINKTYPE.to_le_bytes());
+		body.extend_from_slice(&0u16.to_le_bytes());
+		// A snapshot length of zero means there is no limit.
+		body.extend_from_slice(&0u32.to_le_bytes());
+		push_option(&mut body, OPT_IF_NAME, name.as_bytes());
+		push_option(&mut body, OPT_IF_DESCRIPTION, description(direction).as_bytes());
+		if let Some(baud_rate) = baud_rate {
+			push_option(&mut body, OPT_IF_SPEED, &u64::from(baud_rate).to_le_bytes());
+		}
+		push_option(&mut body, OPT_IF_TSRESOL, &[9]);
+		push_option(&mut body, OPT_END, &[]);
+		write_block(&mut self.writer, BLOCK_INTERFACE_DESCRIPTION, &body)?;
+
+		self.interfaces.push(direction);
+		Ok(())
+	}
+
+	/// Write captured data.
+	///
+	/// The data is flushed to the underlying writer before this function returns.
+	pub fn write_capture(&mut self, capture: &Capture) -> std::io::Result<()> {
+		let interface = match self.interfaces.iter().position(|&direction| direction == capture.direction) {
+			Some(interface) => interface,
+			None => {
+				self.add_interface(capture.direction, &capture.direction.to_string(), None)?;
+				self.interfaces.len() - 1
+			},
+		};
+
+		let timestamp = capture.timestamp.duration_since(SystemTime::UNIX_EPOCH).unwrap_or(Duration::ZERO);
+		let timestamp = u64::try_from(timestamp.as_nanos()).unwrap_or(u64::MAX);
+		let length = u32::try_from(capture.data.len())
+			.map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidInput, "captured data is too large for a pcapng packet"))?;
+		let flags = match capture.direction {
+			Direction::AToB => EPB_FLAG_OUTBOUND,
+			Direction::BToA => EPB_FLAG_INBOUND,
+		};
+
+		let mut body = Vec::with_capacity(capture.data.len() + 40);
+		body.extend_from_slice(&(interface as u32).to_le_bytes());
+		body.extend_from_slice(&((timestamp >> 32) as u32).to_le_bytes());
+		body.extend_from_slice(&(timestamp as u32).to_le_bytes());
+		body.extend_from_slice(&length.to_le_bytes());
+		body.extend_from_slice(&length.to_le_bytes());
+		body.extend_from_slice(&capture.data);
+		pad(&mut body);
+		push_option(&mut body, OPT_EPB_FLAGS, &flags.to_le_bytes());
+		push_option(&mut body, OPT_END, &[]);
+		write_block(&mut self.writer, BLOCK_ENHANCED_PACKET, &body)?;
+		self.writer.flush()
+	}
+
+	/// Get a reference to the underlying writer.
+	pub fn get_ref(&self) -> &W {
+		&self.writer
+	}
+
+	/// Consume the `pcapng` writer and return the underlying writer.
+	pub fn into_inner(self) -> W {
+		self.writer
+	}
+
+	fn write_section_header(&mut self) -> std::io::Result<()> {
+		if self.section_written {
+			return Ok(());
+		}
+		let mut body = Vec::new();
+		body.extend_from_slice(&BYTE_ORDER_MAGIC.to_le_bytes());
+		body.extend_from_slice(&1u16.to_le_bytes());
+		body.extend_from_slice(&0u16.to_le_bytes());
+		// The section length is not known in advance.
+		body.extend_from_slice(&(-1i64).to_le_bytes());
+		push_option(&mut body, OPT_SHB_USERAPPL, concat!("serial2-tokio ", env!("CARGO_PKG_VERSION")).as_bytes());
+		push_option(&mut body, OPT_END, &[]);
+		write_block(&mut self.writer, BLOCK_SECTION_HEADER, &body)?;
+		self.section_written = true;
+		Ok(())
+	}
+}
+
+/// Get the description of the interface for a direction.
+fn description(direction: Direction) -> &'static str {
+	match direction {
+		Direction::AToB => "serial data sent from side A to side B",
+		Direction::BToA => "serial data sent from side B to side A",
+	}
+}
+
+/// Pad a buffer with zeroes to a multiple of 4 bytes.
+fn pad(buffer: &mut Vec<u8>) {
+	buffer.resize(buffer.len().next_multiple_of(4), 0);
+}
+
+/// Append an option to the body of a block.
+fn push_option(body: &mut Vec<u8>, code: u16, value: &[u8]) {
+	body.extend_from_slice(&code.to_le_bytes());
+	body.extend_from_slice(&(value.len() as u16).to_le_bytes());
+	body.extend_from_slice(value);
+	pad(body);
+}
+
+/// Write a block with the given type and body.
+///
+/// The body must already be padded to a multiple of 4 bytes.
+fn write_block(writer: &mut impl Write, block_type: u32, body: &[u8]) -> std::io::Result<()> {
+	let length = (body.len() + 12) as u32;
+	let mut block = Vec::with_capacity(body.len() + 12);
+	block.extend_from_slice(&block_type.to_le_bytes());
+	block.extend_from_slice(&length.to_le_bytes());
+	block.extend_from_slice(body);
+	block.extend_from_slice(&length.to_le_bytes());
+	writer.write_all(&block)
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	/// Split a `pcapng` file into blocks, checking the framing of each block.
+	///
+	/// Returns the type and body of each block.
+	fn blocks(data: &[u8]) -> Vec<(u32, &[u8])> {
+		let mut blocks = Vec::new();
+		let mut rest = data;
+		while !rest.is_empty() {
+			let block_type = u32::from_le_bytes(rest[0..4].try_into().unwrap());
+			let length = u32::from_le_bytes(rest[4..8].try_into().unwrap()) as usize;
+			assert_eq!(length % 4, 0, "block length must be a multiple of 4");
+			assert_eq!(rest[length - 4..length], rest[4..8], "trailing block length must match");
+			blocks.push((block_type, &rest[8..length - 4]));
+			rest = &rest[length..];
+		}
+		blocks
+	}
+
+	/// Parse the options at the start of `data`, checking the end-of-options marker.
+	fn options(mut data: &[u8]) -> Vec<(u16, &[u8])> {
+		let mut options = Vec::new();
+		loop {
+			let code = u16::from_le_bytes([data[0], data[1]]);
+			let len = usize::from(u16::from_le_bytes([data[2], data[3]]));
+			if code == OPT_END {
+				assert_eq!(len, 0);
+				assert_eq!(data.len(), 4, "no data may follow the end of the options");
+				return options;
+			}
+			options.push((code, &data[4..4 + len]));
+			data = &data[(4 + len).next_multiple_of(4)..];
+		}
+	}
+
+	fn capture(direction: Direction, nanos: u64, data: &[u8]) -> Capture {
+		Capture {
+			direction,
+			timestamp: SystemTime::UNIX_EPOCH + Duration::from_nanos(nanos),
+			data: data.to_vec(),
+		}
+	}
+
+	#[test]
+	fn section_header_and_interfaces() {
+		let mut writer = PcapngWriter::new(Vec::new());
+		writer.add_interface(Direction::AToB, "/dev/ttyUSB0", Some(115200)).unwrap();
+		writer.add_interface(Direction::BToA, "/dev/ttyUSB1", None).unwrap();
+		let error = writer.add_interface(Direction::AToB, "/dev/ttyUSB2", None).unwrap_err();
+		assert_eq!(error.kind(), std::io::ErrorKind::AlreadyExists);
+
+		let data = writer.into_inner();
+		let blocks = blocks(&data);
+		assert_eq!(blocks.len(), 3);
+
+		let (block_type, body) = blocks[0];
+		assert_eq!(block_type, BLOCK_SECTION_HEADER);
+		assert_eq!(body[0..4], [0x4D, 0x3C, 0x2B, 0x1A]);
+		assert_eq!(body[4..8], [1, 0, 0, 0]);
+		assert_eq!(body[8..16], [0xFF; 8]);
+		let shb_options = options(&body[16..]);
+		assert_eq!(shb_options[0].0, OPT_SHB_USERAPPL);
+		assert!(shb_options[0].1.starts_with(b"serial2-tokio "));
+
+		let (block_type, body) = blocks[1];
+		assert_eq!(block_type, BLOCK_INTERFACE_DESCRIPTION);
+		assert_eq!(body[0..8], [147, 0, 0, 0, 0, 0, 0, 0]);
+		assert_eq!(options(&body[8..]), [
+			(OPT_IF_NAME, &b"/dev/ttyUSB0"[..]),
+			(OPT_IF_DESCRIPTION, description(Direction::AToB).as_bytes()),
+			(OPT_IF_SPEED, &115200u64.to_le_bytes()[..]),
+			(OPT_IF_TSRESOL, &[9][..]),
+		]);
+
+		let (block_type, body) = blocks[2];
+		assert_eq!(block_type, BLOCK_INTERFACE_DESCRIPTION);
+		assert_eq!(options(&body[8..]), [
+			(OPT_IF_NAME, &b"/dev/ttyUSB1"[..]),
+			(OPT_IF_DESCRIPTION, description(Direction::BToA).as_bytes()),
+			(OPT_IF_TSRESOL, &[9][..]),
+		]);
+	}
+
+	#[test]
+	fn enhanced_packet_block() {
+		let mut writer = PcapngWriter::new(Vec::new());
+		writer.add_interface(Direction::BToA, "B", None).unwrap();
+		writer.add_interface(Direction::AToB, "A", None).unwrap();
+		let start = writer.get_ref().len();
+		writer.write_capture(&capture(Direction::AToB, 1_700_000_000_123_456_789, b"ABCDE")).unwrap();
+
+		let data = writer.into_inner();
+		let expected: [u8; 52] = [
+			0x06, 0x00, 0x00, 0x00, // block type
+			0x34, 0x00, 0x00, 0x00, // block length
+			0x01, 0x00, 0x00, 0x00, // interface ID
+			0xFE, 0x9C, 0x97, 0x17, // timestamp (high)
+			0x15, 0xCD, 0x85, 0x3D, // timestamp (low)
+			0x05, 0x00, 0x00, 0x00, // captured length
+			0x05, 0x00, 0x00, 0x00, // original length
+			b'A', b'B', b'C', b'D', b'E', 0x00, 0x00, 0x00, // data and padding
+			0x02, 0x00, 0x04, 0x00, 0x02, 0x00, 0x00, 0x00, // epb_flags: outbound
+			0x00, 0x00, 0x00, 0x00, // end of options
+			0x34, 0x00, 0x00, 0x00, // block length
+		];
+		assert_eq!(data[start..], expected);
+	}
+
+	#[test]
+	fn capture_adds_missing_interface() {
+		let mut writer = PcapngWriter::new(Vec::new());
+		writer.write_capture(&capture(Direction::BToA, 0, b"x")).unwrap();
+		writer.write_capture(&capture(Direction::BToA, 1, b"yz")).unwrap();
+
+		let data = writer.into_inner();
+		let blocks = blocks(&data);
+		let types: Vec<u32> = blocks.iter().map(|&(block_type, _)| block_type).collect();
+		assert_eq!(types, [BLOCK_SECTION_HEADER, BLOCK_INTERFACE_DESCRIPTION, BLOCK_ENHANCED_PACKET, BLOCK_ENHANCED_PACKET]);
+		assert_eq!(options(&blocks[1].1[8..])[0], (OPT_IF_NAME, &b"B->A"[..]));
+
+		// Both packets use interface 0 and are marked as inbound.
+		for &(_, body) in &blocks[2..] {
+			assert_eq!(body[0..4], [0, 0, 0, 0]);
+			let length = u32::from_le_bytes(body[12..16].try_into().unwrap()) as usize;
+			let packet_options = options(&body[20 + length.next_multiple_of(4)..]);
+			assert_eq!(packet_options, [(OPT_EPB_FLAGS, &EPB_FLAG_INBOUND.to_le_bytes()[..])]);
+		}
+	}
+}
@@ -0,0 +1,242 @@
+use std::pin::Pin;
+use std::task::{ready, Poll};
+
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpStream, ToSocketAddrs};
+
+/// A socket that can be used in place of a serial port.
+///
+/// Device simulators and emulators often expose a virtual serial port as a TCP or Unix socket.
+/// This type wraps such a socket with the same read and write API as [`SerialPort`][crate::SerialPort],
+/// so protocol code can be tested against a simulator without changes.
+/// Like a serial port, it can be read from and written to concurrently from multiple tasks through a shared reference.
+///
+/// Sockets have no modem control lines.
+/// Setting the RTS, DTR or break state does nothing,
+/// and reading the CTS, DSR, RI or CD lines returns an error of kind [`std::io::ErrorKind::Unsupported`].
+///
+/// # Example
+/// ```no_run
+/// # async fn example() -> std::io::Result<()> {
+/// use serial2_tokio::SocketPort;
+///
+/// let port = SocketPort::connect_tcp("localhost:5555").await?;
+/// port.write_all(b"*IDN?\n").await?;
+/// let mut buffer = [0; 256];
+/// let read = port.read(&mut buffer).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct SocketPort {
+	inner: Inner,
+}
+
+#[derive(Debug)]
+enum Inner {
+	Tcp(TcpStream),
+	#[cfg(unix)]
+	Unix(tokio::net::UnixStream),
+}
+
+impl SocketPort {
+	/// Connect to a TCP socket.
+	pub async fn connect_tcp(address: impl ToSocketAddrs) -> std::io::Result<Self> {
+		let stream = TcpStream::connect(address).await?;
+		stream.set_nodelay(true)?;
+		Ok(Self::from(stream))
+	}
+
+	/// Connect to a Unix socket.
+	#[cfg(any(feature = "doc", unix))]
+	#[cfg_attr(feature = "doc-cfg", doc(cfg(unix)))]
+	pub async fn connect_unix(path: impl AsRef<std::path::Path>) -> std::io::Result<Self> {
+		#[cfg(unix)] {
+			let stream = tokio::net::UnixStream::connect(path).await?;
+			Ok(Self::from(stream))
+		}
+		#[cfg(not(unix))] {
+			let _ = path;
+			unreachable!("this code is only enabled on Unix platforms or during documentation generation")
+		}
+	}
+
+	/// Read bytes from the socket.
+	///
+	/// Returns `Ok(0)` when the peer closed the connection.
+	pub async fn read(&self, buf: &mut [u8]) -> std::io::Result<usize> {
+		std::future::poll_fn(|cx| self.poll_read_slice(cx, buf)).await
+	}
+
+	/// Write bytes to the socket.
+	pub async fn write(&self, buf: &[u8]) -> std::io::Result<usize> {
+		std::future::poll_fn(|cx| self.poll_write_slice(cx, buf)).await
+	}
+
+	/// Write all bytes to the socket.
+	pub async fn write_all(&self, mut buf: &[u8]) -> std::io::Result<()> {
+		while !buf.is_empty() {
+			match self.write(buf).await? {
+				0 => return Err(std::io::ErrorKind::WriteZero.into()),
+				written => buf = &buf[written..],
+			}
+		}
+		Ok(())
+	}
+
+	/// Discard data that has been received but not read yet.
+	///
+	/// Only data that is currently available is discarded.
+	pub fn discard_input_buffer(&self) -> std::io::Result<()> {
+		let mut buffer = [0; 1024];
+		loop {
+			let result = match &self.inner {
+				Inner::Tcp(stream) => stream.try_read(&mut buffer),
+				#[cfg(unix)]
+				Inner::Unix(stream) => stream.try_read(&mut buffer),
+			};
+			match result {
+				Ok(0) => return Ok(()),
+				Ok(_) => continue,
+				Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => return Ok(()),
+				Err(e) => return Err(e),
+			}
+		}
+	}
+
+	/// Does nothing: sockets have no RTS line.
+	pub fn set_rts(&self, _state: bool) -> std::io::Result<()> {
+		Ok(())
+	}
+
+	/// Does nothing: sockets have no DTR line.
+	pub fn set_dtr(&self, _state: bool) -> std::io::Result<()> {
+		Ok(())
+	}
+
+	/// Does nothing: sockets have no break state.
+	pub fn set_break(&self, _enable: bool) -> std::io::Result<()> {
+		Ok(())
+	}
+
+	/// Always returns an error: sockets have no CTS line.
+	pub fn read_cts(&self) -> std::io::Result<bool> {
+		Err(no_modem_lines())
+	}
+
+	/// Always returns an error: sockets have no DSR line.
+	pub fn read_dsr(&self) -> std::io::Result<bool> {
+		Err(no_modem_lines())
+	}
+
+	/// Always returns an error: sockets have no RI line.
+	pub fn read_ri(&self) -> std::io::Result<bool> {
+		Err(no_modem_lines())
+	}
+
+	/// Always returns an error: sockets have no CD line.
+	pub fn read_cd(&self) -> std::io::Result<bool> {
+		Err(no_modem_lines())
+	}
+
+	fn poll_read_slice(&self, cx: &mut std::task::Context<'_>, buf: &mut [u8]) -> Poll<std::io::Result<usize>> {
+		loop {
+			let result = match &self.inner {
+				Inner::Tcp(stream) => {
+					ready!(stream.poll_read_ready(cx))?;
+					stream.try_read(buf)
+				},
+				#[cfg(unix)]
+				Inner::Unix(stream) => {
+					ready!(stream.poll_read_ready(cx))?;
+					stream.try_read(buf)
+				},
+			};
+			match result {
+				Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => continue,
+				result => return Poll::Ready(result),
+			}
+		}
+	}
+
+	fn poll_write_slice(&self, cx: &mut std::task::Context<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>> {
+		loop {
+			let result = match &self.inner {
+				Inner::Tcp(stream) => {
+					ready!(stream.poll_write_ready(cx))?;
+					stream.try_write(buf)
+				},
+				#[cfg(unix)]
+				Inner::Unix(stream) => {
+					ready!(stream.poll_write_ready(cx))?;
+					stream.try_write(buf)
+				},
+			};
+			match result {
+				Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => continue,
+				result => return Poll::Ready(result),
+			}
+		}
+	}
+}
+
+fn no_modem_lines() -> std::io::Error {
+	std::io::Error::new(std::io::ErrorKind::Unsupported, "sockets have no modem status lines")
+}
+
+impl From<TcpStream> for SocketPort {
+	fn from(stream: TcpStream) -> Self {
+		Self { inner: Inner::Tcp(stream) }
+	}
+}
+
+#[cfg(unix)]
+impl From<tokio::net::UnixStream> for SocketPort {
+	fn from(stream: tokio::net::UnixStream) -> Self {
+		Self { inner: Inner::Unix(stream) }
+	}
+}
+
+impl AsyncRead for SocketPort {
+	fn poll_read(
+		self: Pin<&mut Self>,
+		cx: &mut std::task::Context<'_>,
+		buf: &mut tokio::io::ReadBuf<'_>,
+	) -> Poll<std::io::Result<()>> {
+		match &mut self.get_mut().inner {
+			Inner::Tcp(stream) => Pin::new(stream).poll_read(cx, buf),
+			#[cfg(unix)]
+			Inner::Unix(stream) => Pin::new(stream).poll_read(cx, buf),
+		}
+	}
+}
+
+impl AsyncWrite for SocketPort {
+	fn poll_write(
+		self: Pin<&mut Self>,
+		cx: &mut std::task::Context<'_>,
+		buf: &[u8],
+	) -> Poll<std::io::Result<usize>> {
+		match &mut self.get_mut().inner {
+			Inner::Tcp(stream) => Pin::new(stream).poll_write(cx, buf),
+			#[cfg(unix)]
+			Inner::Unix(stream) => Pin::new(stream).poll_write(cx, buf),
+		}
+	}
+
+	fn poll_flush(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<std::io::Result<()>> {
+		match &mut self.get_mut().inner {
+			Inner::Tcp(stream) => Pin::new(stream).poll_flush(cx),
+			#[cfg(unix)]
+			Inner::Unix(stream) => Pin::new(stream).poll_flush(cx),
+		}
+	}
+
+	fn poll_shutdown(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<std::io::Result<()>> {
+		match &mut self.get_mut().inner {
+			Inner::Tcp(stream) => Pin::new(stream).poll_shutdown(cx),
+			#[cfg(unix)]
+			Inner::Unix(stream) => Pin::new(stream).poll_shutdown(cx),
+		}
+	}
+}
@@ -0,0 +1,368 @@
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::SerialPort;
+
+/// The time constant of the throughput estimate in seconds.
+const THROUGHPUT_TIME_CONSTANT: f64 = 5.0;
+
+/// Statistics about the use of a serial port.
+///
+/// Use [`SerialPort::stats()`] to get the current statistics.
+/// The counters are cumulative since the serial port was opened or since the last call to [`SerialPort::reset_stats()`].
+///
+/// # Metrics
+/// With the `metrics` feature enabled, the same information is also reported through the facade of the [`metrics`](https://docs.rs/metrics) crate,
+/// with a `port` label set to the path of the serial port:
+/// * `serial_port_read_bytes` and `serial_port_written_bytes` (counters)
+/// * `serial_port_read_errors` and `serial_port_write_errors` (counters)
+/// * `serial_port_overruns` (counter): receiver overruns detected while [overrun detection][SerialPort::set_overrun_detection()] is enabled
+/// * `serial_port_timeouts` (counter): read and write errors of kind [`std::io::ErrorKind::TimedOut`] and timed out health checks of a [`Supervisor`][crate::supervisor::Supervisor]
+/// * `serial_port_write_duration_seconds` (histogram): the time taken by [`SerialPort::write()`], [`SerialPort::write_vectored()`] and writes through the [`AsyncWrite`][tokio::io::AsyncWrite] trait
+/// * `serial_port_reopens` (counter): the number of times a [`Supervisor`][crate::supervisor::Supervisor] reopened the serial port
+///
+/// The metrics are registered when the serial port is opened, so the metrics recorder must be installed before that.
+/// Resetting the statistics does not affect the reported metrics.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[non_exhaustive]
+#[cfg_attr(not(feature = "doc"), allow(rustdoc::broken_intra_doc_links))]
+pub struct Stats {
+	/// The total number of bytes read.
+	pub bytes_read: u64,
+
+	/// The total number of bytes written.
+	pub bytes_written: u64,
+
+	/// The number of successful read calls.
+	pub reads: u64,
+
+	/// The number of successful write calls.
+	pub writes: u64,
+
+	/// The number of read calls that failed with an error.
+	pub read_errors: u64,
+
+	/// The number of write calls that failed with an error.
+	pub write_errors: u64,
+
+	/// The estimated receive throughput in bytes per second.
+	///
+	/// This is an exponentially weighted moving average with a time constant of 5 seconds.
+	pub read_throughput: f64,
+
+	/// The estimated transmit throughput in bytes per second.
+	///
+	/// This is an exponentially weighted moving average with a time constant of 5 seconds.
+	pub write_throughput: f64,
+
+	/// The number of receiver overruns detected while [overrun detection][SerialPort::set_overrun_detection()] was enabled.
+	pub overruns: u64,
+
+	#[cfg_attr(any(feature = "doc", unix), doc = "The current size of the [adaptive read buffer][SerialPort::set_adaptive_read_buffer()], or 0 if it is disabled.")]
+	#[cfg_attr(not(any(feature = "doc", unix)), doc = "The current size of the adaptive read buffer, which is only supported on Unix, so always 0.")]
+	///
+	/// Unlike the other fields, this is not reset by [`SerialPort::reset_stats()`].
+	pub read_buffer_size: usize,
+
+	/// The time since the statistics were last reset.
+	pub elapsed: Duration,
+}
+
+impl SerialPort {
+	/// Get the read and write statistics of the serial port.
+	///
+	/// The statistics are kept for each [`SerialPort`] object separately:
+	/// a handle created with [`Self::try_clone()`] starts with empty statistics.
+	/// Reads and writes through the [`AsyncRead`][tokio::io::AsyncRead] and [`AsyncWrite`][tokio::io::AsyncWrite] traits are included.
+	///
+	/// # Example
+	/// ```no_run
+	/// # async fn example() -> std::io::Result<()> {
+	/// use serial2_tokio::SerialPort;
+	///
+	/// let port = SerialPort::open("/dev/ttyUSB0", 115200)?;
+	/// port.write_all(b"hello").await?;
+	/// let stats = port.stats();
+	/// println!("written {} bytes in {} calls", stats.bytes_written, stats.writes);
+	/// # Ok(())
+	/// # }
+	/// ```
+	pub fn stats(&self) -> Stats {
+		self.stats.snapshot()
+	}
+
+	/// Reset all statistics of the serial port to zero.
+	pub fn reset_stats(&self) {
+		self.stats.reset()
+	}
+}
+
+/// Collects statistics for a serial port.
+#[derive(Debug)]
+pub(crate) struct StatsCollector {
+	bytes_read: AtomicU64,
+	bytes_written: AtomicU64,
+	reads: AtomicU64,
+	writes: AtomicU64,
+	read_errors: AtomicU64,
+	write_errors: AtomicU64,
+	overruns: AtomicU64,
+	read_buffer_size: AtomicUsize,
+	read_throughput: Throughput,
+	write_throughput: Throughput,
+
+	/// The reference point of the timestamps, which are stored as nanoseconds since this time.
+	created: Instant,
+
+	/// The time of the last reset.
+	reset_time: AtomicU64,
+	#[cfg(feature = "metrics")]
+	metrics: Metrics,
+}
+
+/// Handles to the metrics reported through the `metrics` crate.
+#[cfg(feature = "metrics")]
+#[derive(Debug)]
+struct Metrics {
+	port_name: String,
+	bytes_read: metrics::Counter,
+	bytes_written: metrics::Counter,
+	read_errors: metrics::Counter,
+	write_errors: metrics::Counter,
+	overruns: metrics::Counter,
+	timeouts: metrics::Counter,
+	write_duration: metrics::Histogram,
+}
+
+/// An exponentially decaying estimate of the transfer rate.
+///
+/// The estimate is kept in atomics, so recording a transfer does not need a lock.
+#[derive(Debug)]
+struct Throughput {
+	/// The estimate at the time of the last update, as the bits of an `f64`.
+	value: AtomicU64,
+
+	/// The time of the last update.
+	updated: AtomicU64,
+}
+
+impl StatsCollector {
+	/// Create empty statistics for a serial port.
+	///
+	/// The port name is used as label for the metrics reported through the `metrics` crate.
+	pub fn new(port_name: &str) -> Self {
+		let now = Instant::now();
+		#[cfg(not(feature = "metrics"))]
+		let _ = port_name;
+		Self {
+			bytes_read: AtomicU64::new(0),
+			bytes_written: AtomicU64::new(0),
+			reads: AtomicU64::new(0),
+			writes: AtomicU64::new(0),
+			read_errors: AtomicU64::new(0),
+			write_errors: AtomicU64::new(0),
+			overruns: AtomicU64::new(0),
+			read_buffer_size: AtomicUsize::new(0),
+			read_throughput: Throughput::new(),
+			write_throughput: Throughput::new(),
+			created: now,
+			reset_time: AtomicU64::new(0),
+			#[cfg(feature = "metrics")]
+			metrics: Metrics::new(port_name),
+		}
+	}
+
+	/// Create empty statistics for another handle to the same serial port.
+	pub fn new_like(&self) -> Self {
+		#[cfg(feature = "metrics")]
+		return Self::new(&self.metrics.port_name);
+		#[cfg(not(feature = "metrics"))]
+		return Self::new("");
+	}
+
+	/// Record the result of a read call.
+	pub fn record_read(&self, result: &std::io::Result<usize>) {
+		match result {
+			Ok(read) => {
+				self.reads.fetch_add(1, Ordering::Relaxed);
+				self.bytes_read.fetch_add(*read as u64, Ordering::Relaxed);
+				if *read > 0 {
+					self.read_throughput.add(*read, self.timestamp(Instant::now()));
+				}
+				#[cfg(feature = "metrics")]
+				self.metrics.bytes_read.increment(*read as u64);
+			},
+			Err(e) => {
+				self.read_errors.fetch_add(1, Ordering::Relaxed);
+				#[cfg(feature = "metrics")]
+				self.metrics.record_error(&self.metrics.read_errors, e);
+				#[cfg(not(feature = "metrics"))]
+				let _ = e;
+			},
+		}
+	}
+
+	/// Record the result of a write call.
+	pub fn record_write(&self, result: &std::io::Result<usize>) {
+		match result {
+			Ok(written) => {
+				self.writes.fetch_add(1, Ordering::Relaxed);
+				self.bytes_written.fetch_add(*written as u64, Ordering::Relaxed);
+				if *written > 0 {
+					self.write_throughput.add(*written, self.timestamp(Instant::now()));
+				}
+				#[cfg(feature = "metrics")]
+				self.metrics.bytes_written.increment(*written as u64);
+			},
+			Err(e) => {
+				self.write_errors.fetch_add(1, Ordering::Relaxed);
+				#[cfg(feature = "metrics")]
+				self.metrics.record_error(&self.metrics.write_errors, e);
+				#[cfg(not(feature = "metrics"))]
+				let _ = e;
+			},
+		}
+	}
+
+	/// Record how long a write call took to complete.
+	pub fn record_write_duration(&self, duration: Duration) {
+		#[cfg(feature = "metrics")]
+		self.metrics.write_duration.record(duration);
+		#[cfg(not(feature = "metrics"))]
+		let _ = duration;
+	}
+
+	/// Record detected receiver overruns.
+	pub fn record_overruns(&self, count: u32) {
+		self.overruns.fetch_add(count.into(), Ordering::Relaxed);
+		#[cfg(feature = "metrics")]
+		self.metrics.overruns.increment(count.into());
+	}
+
+	/// Record the current size of the adaptive read buffer.
+	#[cfg(unix)]
+	pub fn set_read_buffer_size(&self, size: usize) {
+		self.read_buffer_size.store(size, Ordering::Relaxed);
+	}
+
+	/// Record a timeout that is not reported as the result of a read or write call.
+	#[cfg(any(feature = "doc", feature = "supervisor"))]
+	pub fn record_timeout(&self) {
+		#[cfg(feature = "metrics")]
+		self.metrics.timeouts.increment(1);
+	}
+
+	/// Get a timestamp as nanoseconds since the statistics were created.
+	fn timestamp(&self, time: Instant) -> u64 {
+		time.saturating_duration_since(self.created).as_nanos().try_into().unwrap_or(u64::MAX)
+	}
+
+	fn snapshot(&self) -> Stats {
+		let now = self.timestamp(Instant::now());
+		let reset_time = self.reset_time.load(Ordering::Relaxed);
+		Stats {
+			bytes_read: self.bytes_read.load(Ordering::Relaxed),
+			bytes_written: self.bytes_written.load(Ordering::Relaxed),
+			reads: self.reads.load(Ordering::Relaxed),
+			writes: self.writes.load(Ordering::Relaxed),
+			read_errors: self.read_errors.load(Ordering::Relaxed),
+			write_errors: self.write_errors.load(Ordering::Relaxed),
+			overruns: self.overruns.load(Ordering::Relaxed),
+			read_buffer_size: self.read_buffer_size.load(Ordering::Relaxed),
+			read_throughput: self.read_throughput.value_at(now, reset_time),
+			write_throughput: self.write_throughput.value_at(now, reset_time),
+			elapsed: Duration::from_nanos(now.saturating_sub(reset_time)),
+		}
+	}
+
+	fn reset(&self) {
+		let now = self.timestamp(Instant::now());
+		self.reset_time.store(now, Ordering::Relaxed);
+		self.bytes_read.store(0, Ordering::Relaxed);
+		self.bytes_written.store(0, Ordering::Relaxed);
+		self.reads.store(0, Ordering::Relaxed);
+		self.writes.store(0, Ordering::Relaxed);
+		self.read_errors.store(0, Ordering::Relaxed);
+		self.write_errors.store(0, Ordering::Relaxed);
+		self.overruns.store(0, Ordering::Relaxed);
+		self.read_throughput.reset(now);
+		self.write_throughput.reset(now);
+	}
+}
+
+#[cfg(feature = "metrics")]
+impl Metrics {
+	fn new(port_name: &str) -> Self {
+		let port = port_name.to_owned();
+		Self {
+			bytes_read: metrics::counter!("serial_port_read_bytes", "port" => port.clone()),
+			bytes_written: metrics::counter!("serial_port_written_bytes", "port" => port.clone()),
+			read_errors: metrics::counter!("serial_port_read_errors", "port" => port.clone()),
+			write_errors: metrics::counter!("serial_port_write_errors", "port" => port.clone()),
+			overruns: metrics::counter!("serial_port_overruns", "port" => port.clone()),
+			timeouts: metrics::counter!("serial_port_timeouts", "port" => port.clone()),
+			write_duration: metrics::histogram!("serial_port_write_duration_seconds", "port" => port),
+			port_name: port_name.to_owned(),
+		}
+	}
+
+	fn record_error(&self, counter: &metrics::Counter, error: &std::io::Error) {
+		counter.increment(1);
+		if error.kind() == std::io::ErrorKind::TimedOut {
+			self.timeouts.increment(1);
+		}
+	}
+}
+
+/// Record that a serial port was reopened after it was closed.
+#[cfg(any(feature = "doc", feature = "supervisor"))]
+pub(crate) fn record_reopen(port_name: &str) {
+	#[cfg(feature = "metrics")]
+	metrics::counter!("serial_port_reopens", "port" => port_name.to_owned()).increment(1);
+	#[cfg(not(feature = "metrics"))]
+	let _ = port_name;
+}
+
+impl Throughput {
+	fn new() -> Self {
+		Self {
+			value: AtomicU64::new(0.0f64.to_bits()),
+			updated: AtomicU64::new(0),
+		}
+	}
+
+	fn reset(&self, now: u64) {
+		self.value.store(0.0f64.to_bits(), Ordering::Relaxed);
+		self.updated.store(now, Ordering::Relaxed);
+	}
+
+	fn value_at(&self, now: u64, started: u64) -> f64 {
+		// Compensate for the estimate starting at zero, so it is not too low shortly after a reset.
+		// The running time is clamped to avoid huge estimates from the very first transfers.
+		let running = nanos_to_secs(now.saturating_sub(started)).max(1.0);
+		let correction = 1.0 - (-running / THROUGHPUT_TIME_CONSTANT).exp();
+		let elapsed = now.saturating_sub(self.updated.load(Ordering::Relaxed));
+		decay(f64::from_bits(self.value.load(Ordering::Relaxed)), elapsed) / correction
+	}
+
+	fn add(&self, bytes: usize, now: u64) {
+		// Concurrent updates each decay the estimate by the time since the previous update,
+		// so together they decay it by the total elapsed time.
+		let elapsed = now.saturating_sub(self.updated.fetch_max(now, Ordering::Relaxed));
+		let added = bytes as f64 / THROUGHPUT_TIME_CONSTANT;
+		self.value
+			.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |value| {
+				Some((decay(f64::from_bits(value), elapsed) + added).to_bits())
+			})
+			.ok();
+	}
+}
+
+/// Decay a throughput estimate by the given number of nanoseconds.
+fn decay(value: f64, elapsed: u64) -> f64 {
+	value * (-nanos_to_secs(elapsed) / THROUGHPUT_TIME_CONSTANT).exp()
+}
+
+fn nanos_to_secs(nanos: u64) -> f64 {
+	Duration::from_nanos(nanos).as_secs_f64()
+}
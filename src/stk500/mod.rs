@@ -0,0 +1,583 @@
+//! Program AVR microcontrollers through an STK500 compatible bootloader.
+//!
+//! Most Arduino boards run a bootloader that implements a subset of the STK500 protocol from Atmel.
+//! Boards with an ATmega328P, like the Arduino Uno and Nano, use version 1 of the protocol.
+//! Boards with an ATmega2560, like the Arduino Mega, use version 2 of the protocol.
+//!
+//! The [`Stk500`] client resets the board into the bootloader with the DTR line,
+//! synchronizes with the bootloader and then programs and verifies the flash memory one page at a time.
+//! Progress is reported through a callback, so that flashing tools can show a progress bar.
+//!
+//! The firmware image must be given as raw binary data.
+//! Intel HEX files must be converted to binary data first.
+//!
+//! This module is only available when the `stk500` feature is enabled.
+//!
+//! # Example
+//! ```no_run
+//! # async fn example() -> std::io::Result<()> {
+//! use serial2_tokio::SerialPort;
+//! use serial2_tokio::stk500::{Protocol, Stk500};
+//!
+//! let firmware = std::fs::read("firmware.bin")?;
+//! let port = SerialPort::open("/dev/ttyACM0", 115200)?;
+//! let mut programmer = Stk500::new(port, Protocol::V1);
+//! programmer.connect().await?;
+//! println!("signature: {:02X?}", programmer.read_signature().await?);
+//! programmer.flash(&firmware, |progress| {
+//!     println!("{:?}: {}/{}", progress.stage, progress.done, progress.total);
+//! }).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::time::Duration;
+
+use crate::{LineAction, SerialPort};
+
+mod v1;
+mod v2;
+
+/// The default timeout for a single command.
+const DEFAULT_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// The default page size of the flash memory, which is the page size of the ATmega328P.
+const DEFAULT_PAGE_SIZE: usize = 128;
+
+/// The number of attempts to synchronize with the bootloader.
+const SYNC_ATTEMPTS: usize = 10;
+
+/// The version of the STK500 protocol used by the bootloader.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum Protocol {
+	/// Version 1 of the protocol, used by the Optiboot bootloader on the Arduino Uno and similar boards.
+	V1,
+
+	/// Version 2 of the protocol, used by the bootloader on the Arduino Mega 2560.
+	V2,
+}
+
+/// The stage of a flashing operation.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum Stage {
+	/// The flash memory is being programmed.
+	Programming,
+
+	/// The flash memory is being read back and compared with the firmware image.
+	Verifying,
+}
+
+/// The progress of a flashing operation.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct Progress {
+	/// The stage of the operation.
+	pub stage: Stage,
+
+	/// The number of bytes that have been processed in this stage.
+	pub done: usize,
+
+	/// The total number of bytes to process in this stage.
+	pub total: usize,
+}
+
+/// A client for an STK500 compatible bootloader.
+///
+/// See the [module documentation](self) for more information.
+#[derive(Debug)]
+pub struct Stk500 {
+	port: SerialPort,
+	protocol: Protocol,
+	timeout: Duration,
+	page_size: usize,
+	/// The sequence number of the next message for version 2 of the protocol.
+	sequence: u8,
+}
+
+impl Stk500 {
+	/// Create a new client for a bootloader on a serial port.
+	///
+	/// The serial port must already be configured with the baud rate of the bootloader.
+	/// This is usually 115200 baud for Optiboot and the Arduino Mega, and 57600 baud for older Arduino boards.
+	pub fn new(port: SerialPort, protocol: Protocol) -> Self {
+		Self {
+			port,
+			protocol,
+			timeout: DEFAULT_TIMEOUT,
+			page_size: DEFAULT_PAGE_SIZE,
+			sequence: 0,
+		}
+	}
+
+	/// Set the timeout for a single command.
+	///
+	/// The default timeout is 500 milliseconds.
+	pub fn set_timeout(&mut self, timeout: Duration) {
+		self.timeout = timeout;
+	}
+
+	/// Get the timeout for a single command.
+	pub fn get_timeout(&self) -> Duration {
+		self.timeout
+	}
+
+	/// Set the page size of the flash memory in bytes.
+	///
+	/// The default page size is 128 bytes, which is correct for the ATmega328P.
+	/// The ATmega2560 has a page size of 256 bytes.
+	pub fn set_page_size(&mut self, page_size: usize) {
+		self.page_size = page_size;
+	}
+
+	/// Get the page size of the flash memory in bytes.
+	pub fn get_page_size(&self) -> usize {
+		self.page_size
+	}
+
+	/// Get a reference to the serial port.
+	pub fn get_ref(&self) -> &SerialPort {
+		&self.port
+	}
+
+	/// Consume the client and return the serial port.
+	pub fn into_inner(self) -> SerialPort {
+		self.port
+	}
+
+	/// Reset the board into the bootloader and synchronize with it.
+	///
+	/// This resets the board with [`LineAction::ARDUINO_RESET`] and then calls [`Self::sync()`].
+	pub async fn connect(&mut self) -> std::io::Result<()> {
+		self.port.reset_sequence(LineAction::ARDUINO_RESET).await?;
+		self.sync().await
+	}
+
+	/// Synchronize with the bootloader.
+	///
+	/// The bootloader may need some time to start after a reset, and the board may send garbage while starting.
+	/// This function retries the synchronization a few times before giving up.
+	pub async fn sync(&mut self) -> std::io::Result<()> {
+		let mut last_error = None;
+		for _ in 0..SYNC_ATTEMPTS {
+			self.port.discard_input_buffer()?;
+			let result = match self.protocol {
+				Protocol::V1 => v1::sync(&self.port, self.timeout).await,
+				Protocol::V2 => self.command_v2(&[v2::CMD_SIGN_ON]).await.map(drop),
+			};
+			match result {
+				Ok(()) => return Ok(()),
+				Err(e) => last_error = Some(e),
+			}
+		}
+		let error = last_error.unwrap_or_else(|| std::io::ErrorKind::TimedOut.into());
+		Err(std::io::Error::new(error.kind(), format!("failed to synchronize with the bootloader: {error}")))
+	}
+
+	/// Read the signature bytes of the microcontroller.
+	///
+	/// The signature identifies the type of microcontroller.
+	/// For example, the signature of the ATmega328P is `[0x1E, 0x95, 0x0F]`.
+	pub async fn read_signature(&mut self) -> std::io::Result<[u8; 3]> {
+		match self.protocol {
+			Protocol::V1 => {
+				let response = v1::command(&self.port, &[v1::STK_READ_SIGN], 3, self.timeout).await?;
+				Ok([response[0], response[1], response[2]])
+			},
+			Protocol::V2 => {
+				let mut signature = [0; 3];
+				for (index, byte) in signature.iter_mut().enumerate() {
+					let response = self.command_v2(&[v2::CMD_READ_SIGNATURE_ISP, 0x04, 0x30, 0x00, index as u8, 0x00]).await?;
+					*byte = *response.get(2).ok_or_else(|| invalid_response("signature response is too short"))?;
+				}
+				Ok(signature)
+			},
+		}
+	}
+
+	/// Enter programming mode.
+	///
+	/// This must be called before reading or writing the flash memory.
+	/// [`Self::flash()`] calls this function for you.
+	pub async fn enter_programming_mode(&mut self) -> std::io::Result<()> {
+		match self.protocol {
+			Protocol::V1 => v1::command(&self.port, &[v1::STK_ENTER_PROGMODE], 0, self.timeout).await.map(drop),
+			Protocol::V2 => self.command_v2(&v2::ENTER_PROGMODE_ISP).await.map(drop),
+		}
+	}
+
+	/// Leave programming mode.
+	///
+	/// Most bootloaders start the application when programming mode is left.
+	/// [`Self::flash()`] calls this function for you.
+	pub async fn leave_programming_mode(&mut self) -> std::io::Result<()> {
+		match self.protocol {
+			Protocol::V1 => v1::command(&self.port, &[v1::STK_LEAVE_PROGMODE], 0, self.timeout).await.map(drop),
+			Protocol::V2 => self.command_v2(&v2::LEAVE_PROGMODE_ISP).await.map(drop),
+		}
+	}
+
+	/// Program the flash memory with a firmware image, verify it and leave programming mode.
+	///
+	/// The image is written starting at address zero.
+	/// The `progress` callback is called after each page has been programmed or verified.
+	///
+	/// Returns an error of kind [`std::io::ErrorKind::InvalidData`] if the verification fails.
+	pub async fn flash<F>(&mut self, image: &[u8], mut progress: F) -> std::io::Result<()>
+	where
+		F: FnMut(Progress),
+	{
+		self.enter_programming_mode().await?;
+		self.write_flash(0, image, &mut progress).await?;
+		self.verify_flash(0, image, &mut progress).await?;
+		self.leave_programming_mode().await
+	}
+
+	/// Program the flash memory, starting at the given byte address.
+	///
+	/// The address should be aligned to the page size.
+	/// The data is written one page at a time, and the last page is padded with `0xFF` bytes.
+	/// The `progress` callback is called after each page.
+	pub async fn write_flash<F>(&mut self, address: u32, data: &[u8], mut progress: F) -> std::io::Result<()>
+	where
+		F: FnMut(Progress),
+	{
+		let mut page = Vec::with_capacity(self.page_size);
+		for (index, chunk) in data.chunks(self.page_size).enumerate() {
+			let offset = index * self.page_size;
+			page.clear();
+			page.extend_from_slice(chunk);
+			page.resize(self.page_size, 0xFF);
+
+			self.load_address(page_address(address, offset)?).await?;
+			match self.protocol {
+				Protocol::V1 => v1::program_page(&self.port, &page, self.timeout).await?,
+				Protocol::V2 => self.command_v2(&v2::program_flash(&page)?).await.map(drop)?,
+			}
+			progress(Progress {
+				stage: Stage::Programming,
+				done: offset + chunk.len(),
+				total: data.len(),
+			});
+		}
+		Ok(())
+	}
+
+	/// Read the flash memory, starting at the given byte address.
+	pub async fn read_flash(&mut self, address: u32, len: usize) -> std::io::Result<Vec<u8>> {
+		let mut data = Vec::with_capacity(len);
+		while data.len() < len {
+			let size = self.page_size.min(len - data.len());
+			self.load_address(page_address(address, data.len())?).await?;
+			data.extend_from_slice(&self.read_page(size).await?);
+		}
+		Ok(data)
+	}
+
+	/// Read back the flash memory and compare it with the given data.
+	///
+	/// The `progress` callback is called after each page.
+	///
+	/// Returns an error of kind [`std::io::ErrorKind::InvalidData`] with the first mismatching address if the data does not match.
+	pub async fn verify_flash<F>(&mut self, address: u32, data: &[u8], mut progress: F) -> std::io::Result<()>
+	where
+		F: FnMut(Progress),
+	{
+		for (index, chunk) in data.chunks(self.page_size).enumerate() {
+			let offset = index * self.page_size;
+			self.load_address(page_address(address, offset)?).await?;
+			let read = self.read_page(chunk.len()).await?;
+			if let Some(position) = read.iter().zip(chunk).position(|(a, b)| a != b) {
+				return Err(std::io::Error::new(
+					std::io::ErrorKind::InvalidData,
+					format!(
+						"verification failed at address 0x{:04X}: expected 0x{:02X}, read 0x{:02X}",
+						u64::from(address) + (offset + position) as u64,
+						chunk[position],
+						read[position],
+					),
+				));
+			}
+			progress(Progress {
+				stage: Stage::Verifying,
+				done: offset + chunk.len(),
+				total: data.len(),
+			});
+		}
+		Ok(())
+	}
+
+	/// Set the address for the next page operation.
+	async fn load_address(&mut self, address: u32) -> std::io::Result<()> {
+		match self.protocol {
+			Protocol::V1 => v1::load_address(&self.port, address, self.timeout).await,
+			Protocol::V2 => self.command_v2(&v2::load_address(address)).await.map(drop),
+		}
+	}
+
+	/// Read a page of flash memory from the current address.
+	async fn read_page(&mut self, size: usize) -> std::io::Result<Vec<u8>> {
+		match self.protocol {
+			Protocol::V1 => v1::read_page(&self.port, size, self.timeout).await,
+			Protocol::V2 => {
+				let response = self.command_v2(&v2::read_flash(size)?).await?;
+				// The answer contains the command, a status byte, the data and a final status byte.
+				response.get(2..2 + size)
+					.map(|data| data.to_vec())
+					.ok_or_else(|| invalid_response("flash read response is too short"))
+			},
+		}
+	}
+
+	/// Send a command with version 2 of the protocol and return the answer.
+	async fn command_v2(&mut self, body: &[u8]) -> std::io::Result<Vec<u8>> {
+		let sequence = self.sequence;
+		self.sequence = self.sequence.wrapping_add(1);
+		v2::command(&self.port, sequence, body, self.timeout).await
+	}
+}
+
+/// Compute the byte address of a page.
+fn page_address(address: u32, offset: usize) -> std::io::Result<u32> {
+	u32::try_from(offset)
+		.ok()
+		.and_then(|offset| address.checked_add(offset))
+		.ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "address is out of range"))
+}
+
+/// Encode the size of a page as big-endian bytes.
+fn encode_size(size: usize) -> std::io::Result<[u8; 2]> {
+	u16::try_from(size)
+		.map(u16::to_be_bytes)
+		.map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidInput, "page size is too large"))
+}
+
+/// Read exactly enough bytes to fill the buffer, with a timeout for the whole read.
+async fn read_exact(port: &SerialPort, buffer: &mut [u8], timeout: Duration) -> std::io::Result<()> {
+	let read = async {
+		let mut filled = 0;
+		while filled < buffer.len() {
+			match port.read(&mut buffer[filled..]).await? {
+				0 => return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof)),
+				read => filled += read,
+			}
+		}
+		Ok(())
+	};
+	tokio::time::timeout(timeout, read).await
+		.map_err(|_| std::io::Error::new(std::io::ErrorKind::TimedOut, "timeout while waiting for response from bootloader"))?
+}
+
+/// Create an error for an invalid response from the bootloader.
+fn invalid_response(message: &str) -> std::io::Error {
+	std::io::Error::new(std::io::ErrorKind::InvalidData, format!("invalid response from bootloader: {message}"))
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn page_address_checks_overflow() {
+		assert_eq!(page_address(0x100, 0x80).unwrap(), 0x180);
+		assert_eq!(page_address(u32::MAX, 1).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+	}
+
+	#[test]
+	fn encode_size_is_big_endian() {
+		assert_eq!(encode_size(0x80).unwrap(), [0x00, 0x80]);
+		assert_eq!(encode_size(0x100).unwrap(), [0x01, 0x00]);
+		assert!(encode_size(0x1_0000).is_err());
+	}
+
+	#[cfg(all(unix, feature = "unix"))]
+	mod pty {
+		use super::*;
+		use crate::test_util::{expect_then_reply, pair_with_timeout};
+
+		/// The signature of the ATmega328P.
+		const ATMEGA328P: [u8; 3] = [0x1E, 0x95, 0x0F];
+
+		/// Create a client on one side of a pseudo-terminal pair, with the other side acting as bootloader.
+		fn client(protocol: Protocol) -> (Stk500, SerialPort) {
+			let (a, b, timeout) = pair_with_timeout();
+			let mut client = Stk500::new(a, protocol);
+			client.set_timeout(timeout);
+			(client, b)
+		}
+
+		/// Read exactly `len` bytes on the bootloader side.
+		async fn receive(port: &SerialPort, len: usize) -> Vec<u8> {
+			let mut buffer = vec![0; len];
+			read_exact(port, &mut buffer, Duration::from_secs(1)).await.unwrap();
+			buffer
+		}
+
+		/// A minimal version 1 bootloader with flash memory, like Optiboot.
+		///
+		/// Runs until the programming mode is left and returns the flash memory.
+		async fn bootloader_v1(port: &SerialPort, flash_size: usize) -> Vec<u8> {
+			let mut flash = vec![0xFF; flash_size];
+			let mut address = 0;
+			loop {
+				let command = receive(port, 1).await[0];
+				let mut response = vec![v1::STK_INSYNC];
+				match command {
+					v1::STK_GET_SYNC | v1::STK_ENTER_PROGMODE | v1::STK_LEAVE_PROGMODE => (),
+					v1::STK_READ_SIGN => response.extend_from_slice(&ATMEGA328P),
+					v1::STK_LOAD_ADDRESS => {
+						let args = receive(port, 2).await;
+						address = usize::from(u16::from_le_bytes([args[0], args[1]])) * 2;
+					},
+					v1::STK_PROG_PAGE => {
+						let args = receive(port, 3).await;
+						assert_eq!(args[2], b'F');
+						let size = usize::from(u16::from_be_bytes([args[0], args[1]]));
+						let page = receive(port, size).await;
+						flash[address..address + size].copy_from_slice(&page);
+					},
+					v1::STK_READ_PAGE => {
+						let args = receive(port, 3).await;
+						let size = usize::from(u16::from_be_bytes([args[0], args[1]]));
+						response.extend_from_slice(&flash[address..address + size]);
+					},
+					other => panic!("unexpected command 0x{other:02X}"),
+				}
+				assert_eq!(receive(port, 1).await, [0x20]);
+				response.push(v1::STK_OK);
+				port.write_all(&response).await.unwrap();
+				if command == v1::STK_LEAVE_PROGMODE {
+					return flash;
+				}
+			}
+		}
+
+		#[tokio::test]
+		async fn sync_and_read_signature_v1() {
+			let (mut client, bootloader) = client(Protocol::V1);
+			let bootloader_side = async {
+				expect_then_reply(&bootloader, &[0x30, 0x20], &[0x14, 0x10]).await;
+				expect_then_reply(&bootloader, &[0x75, 0x20], &[0x14, 0x1E, 0x95, 0x0F, 0x10]).await;
+			};
+			let (result, ()) = tokio::join!(
+				async {
+					client.sync().await?;
+					client.read_signature().await
+				},
+				bootloader_side,
+			);
+			assert_eq!(result.unwrap(), ATMEGA328P);
+		}
+
+		#[tokio::test]
+		async fn sync_retries_after_garbage_v1() {
+			let (mut client, bootloader) = client(Protocol::V1);
+			let bootloader_side = async {
+				expect_then_reply(&bootloader, &[0x30, 0x20], &[0x00]).await;
+				expect_then_reply(&bootloader, &[0x30, 0x20], &[0x14, 0x10]).await;
+			};
+			let (result, ()) = tokio::join!(client.sync(), bootloader_side);
+			result.unwrap();
+		}
+
+		#[tokio::test]
+		async fn failed_command_is_reported_v1() {
+			let (mut client, bootloader) = client(Protocol::V1);
+			let (result, ()) = tokio::join!(
+				client.enter_programming_mode(),
+				expect_then_reply(&bootloader, &[0x50, 0x20], &[0x14, 0x11]),
+			);
+			assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+		}
+
+		#[tokio::test]
+		async fn flash_and_verify_v1() {
+			let (mut client, bootloader) = client(Protocol::V1);
+			let image: Vec<u8> = (0..300).map(|i| i as u8).collect();
+			let mut progress = Vec::new();
+			let (result, flash) = tokio::join!(
+				client.flash(&image, |p| progress.push((p.stage, p.done, p.total))),
+				bootloader_v1(&bootloader, 1024),
+			);
+			result.unwrap();
+			assert_eq!(flash[..300], image);
+			// The last page is padded with 0xFF.
+			assert!(flash[300..384].iter().all(|&byte| byte == 0xFF));
+			assert_eq!(progress, [
+				(Stage::Programming, 128, 300),
+				(Stage::Programming, 256, 300),
+				(Stage::Programming, 300, 300),
+				(Stage::Verifying, 128, 300),
+				(Stage::Verifying, 256, 300),
+				(Stage::Verifying, 300, 300),
+			]);
+		}
+
+		#[tokio::test]
+		async fn verify_reports_first_mismatch_v1() {
+			let (mut client, bootloader) = client(Protocol::V1);
+			let bootloader_side = async {
+				expect_then_reply(&bootloader, &[0x55, 0x00, 0x00, 0x20], &[0x14, 0x10]).await;
+				expect_then_reply(&bootloader, &[0x74, 0x00, 0x04, b'F', 0x20], &[0x14, 0x01, 0x02, 0x03, 0xFF, 0x10]).await;
+			};
+			let (result, ()) = tokio::join!(client.verify_flash(0, &[0x01, 0x02, 0x03, 0x04], |_| ()), bootloader_side);
+			let error = result.unwrap_err();
+			assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+			assert!(error.to_string().contains("0x0003"), "{error}");
+		}
+
+		#[tokio::test]
+		async fn sync_and_read_signature_v2() {
+			let (mut client, bootloader) = client(Protocol::V2);
+			let bootloader_side = async {
+				expect_then_reply(
+					&bootloader,
+					&[0x1B, 0x00, 0x00, 0x01, 0x0E, 0x01, 0x15],
+					&[0x1B, 0x00, 0x00, 0x0B, 0x0E, 0x01, 0x00, 0x08, 0x41, 0x56, 0x52, 0x49, 0x53, 0x50, 0x5F, 0x32, 0x75],
+				).await;
+				// The signature of the ATmega2560, one byte at a time.
+				expect_then_reply(
+					&bootloader,
+					&[0x1B, 0x01, 0x00, 0x06, 0x0E, 0x1B, 0x04, 0x30, 0x00, 0x00, 0x00, 0x3D],
+					&[0x1B, 0x01, 0x00, 0x04, 0x0E, 0x1B, 0x00, 0x1E, 0x00, 0x15],
+				).await;
+				expect_then_reply(
+					&bootloader,
+					&[0x1B, 0x02, 0x00, 0x06, 0x0E, 0x1B, 0x04, 0x30, 0x00, 0x01, 0x00, 0x3F],
+					&[0x1B, 0x02, 0x00, 0x04, 0x0E, 0x1B, 0x00, 0x98, 0x00, 0x90],
+				).await;
+				expect_then_reply(
+					&bootloader,
+					&[0x1B, 0x03, 0x00, 0x06, 0x0E, 0x1B, 0x04, 0x30, 0x00, 0x02, 0x00, 0x3D],
+					&[0x1B, 0x03, 0x00, 0x04, 0x0E, 0x1B, 0x00, 0x01, 0x00, 0x08],
+				).await;
+			};
+			let (result, ()) = tokio::join!(
+				async {
+					client.sync().await?;
+					client.read_signature().await
+				},
+				bootloader_side,
+			);
+			assert_eq!(result.unwrap(), [0x1E, 0x98, 0x01]);
+		}
+
+		#[tokio::test]
+		async fn failed_status_is_reported_v2() {
+			let (client, bootloader) = client(Protocol::V2);
+			let bootloader_side = async {
+				expect_then_reply(&bootloader, &[0x1B, 0x00, 0x00, 0x01, 0x0E, 0x01, 0x15], &[0x1B, 0x00, 0x00, 0x02, 0x0E, 0x01, 0xC0, 0xD6]).await;
+			};
+			let (result, ()) = tokio::join!(v2::command(&client.port, 0, &[v2::CMD_SIGN_ON], client.timeout), bootloader_side);
+			assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+		}
+
+		#[tokio::test]
+		async fn sync_times_out_without_bootloader() {
+			let (mut client, _bootloader) = client(Protocol::V1);
+			client.set_timeout(Duration::from_millis(5));
+			assert_eq!(client.sync().await.unwrap_err().kind(), std::io::ErrorKind::TimedOut);
+		}
+	}
+}
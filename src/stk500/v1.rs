@@ -0,0 +1,85 @@
+//! Version 1 of the STK500 protocol.
+//!
+//! Each command ends with [`CRC_EOP`].
+//! The bootloader answers with [`STK_INSYNC`], the response data and [`STK_OK`].
+
+use std::time::Duration;
+
+use crate::SerialPort;
+
+use super::{encode_size, invalid_response, read_exact};
+
+const CRC_EOP: u8 = 0x20;
+pub const STK_OK: u8 = 0x10;
+const STK_FAILED: u8 = 0x11;
+pub const STK_INSYNC: u8 = 0x14;
+const STK_NOSYNC: u8 = 0x15;
+
+pub const STK_GET_SYNC: u8 = 0x30;
+pub const STK_ENTER_PROGMODE: u8 = 0x50;
+pub const STK_LEAVE_PROGMODE: u8 = 0x51;
+pub const STK_LOAD_ADDRESS: u8 = 0x55;
+pub const STK_PROG_PAGE: u8 = 0x64;
+pub const STK_READ_PAGE: u8 = 0x74;
+pub const STK_READ_SIGN: u8 = 0x75;
+
+/// The memory type for flash memory in page commands.
+const MEMORY_FLASH: u8 = b'F';
+
+/// Check if the bootloader is in sync.
+pub async fn sync(port: &SerialPort, timeout: Duration) -> std::io::Result<()> {
+	command(port, &[STK_GET_SYNC], 0, timeout).await.map(drop)
+}
+
+/// Send a command and read the response data.
+///
+/// The [`CRC_EOP`] byte is added to the command automatically.
+/// The returned data does not include the [`STK_INSYNC`] and [`STK_OK`] bytes.
+pub async fn command(port: &SerialPort, command: &[u8], response_len: usize, timeout: Duration) -> std::io::Result<Vec<u8>> {
+	let mut message = Vec::with_capacity(command.len() + 1);
+	message.extend_from_slice(command);
+	message.push(CRC_EOP);
+	port.write_all(&message).await?;
+
+	let mut response = vec![0; response_len + 2];
+	read_exact(port, &mut response[..1], timeout).await?;
+	match response[0] {
+		STK_INSYNC => (),
+		STK_NOSYNC => return Err(invalid_response("bootloader lost sync")),
+		other => return Err(invalid_response(&format!("expected INSYNC (0x14), got 0x{other:02X}"))),
+	}
+	read_exact(port, &mut response[1..], timeout).await?;
+	match response[response_len + 1] {
+		STK_OK => (),
+		STK_FAILED => return Err(invalid_response("command failed")),
+		other => return Err(invalid_response(&format!("expected OK (0x10), got 0x{other:02X}"))),
+	}
+	response.truncate(response_len + 1);
+	response.remove(0);
+	Ok(response)
+}
+
+/// Set the address for the next page operation.
+///
+/// The protocol uses word addresses for flash memory, so the byte address must be even and below 128 KiB.
+pub async fn load_address(port: &SerialPort, address: u32, timeout: Duration) -> std::io::Result<()> {
+	let word_address = u16::try_from(address / 2)
+		.map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidInput, "address is too large for version 1 of the STK500 protocol"))?;
+	let [high, low] = word_address.to_be_bytes();
+	command(port, &[STK_LOAD_ADDRESS, low, high], 0, timeout).await.map(drop)
+}
+
+/// Program a page of flash memory at the current address.
+pub async fn program_page(port: &SerialPort, page: &[u8], timeout: Duration) -> std::io::Result<()> {
+	let [high, low] = encode_size(page.len())?;
+	let mut message = Vec::with_capacity(page.len() + 4);
+	message.extend_from_slice(&[STK_PROG_PAGE, high, low, MEMORY_FLASH]);
+	message.extend_from_slice(page);
+	command(port, &message, 0, timeout).await.map(drop)
+}
+
+/// Read a page of flash memory from the current address.
+pub async fn read_page(port: &SerialPort, size: usize, timeout: Duration) -> std::io::Result<Vec<u8>> {
+	let [high, low] = encode_size(size)?;
+	command(port, &[STK_READ_PAGE, high, low, MEMORY_FLASH], size, timeout).await
+}
@@ -0,0 +1,130 @@
+//! Version 2 of the STK500 protocol.
+//!
+//! Each message is framed as `[MESSAGE_START, sequence, size (2 bytes), TOKEN, body..., checksum]`,
+//! where the checksum is the XOR of all other bytes.
+//! The answer uses the same framing, and its body starts with the command and a status byte.
+
+use std::time::Duration;
+
+use crate::SerialPort;
+use crate::checksum::xor as checksum;
+
+use super::{encode_size, invalid_response, read_exact};
+
+const MESSAGE_START: u8 = 0x1B;
+const TOKEN: u8 = 0x0E;
+const STATUS_CMD_OK: u8 = 0x00;
+
+pub const CMD_SIGN_ON: u8 = 0x01;
+const CMD_LOAD_ADDRESS: u8 = 0x06;
+const CMD_ENTER_PROGMODE_ISP: u8 = 0x10;
+const CMD_LEAVE_PROGMODE_ISP: u8 = 0x11;
+const CMD_PROGRAM_FLASH_ISP: u8 = 0x13;
+const CMD_READ_FLASH_ISP: u8 = 0x14;
+pub const CMD_READ_SIGNATURE_ISP: u8 = 0x1B;
+
+/// Enter programming mode, with the ISP parameters that `avrdude` uses for the ATmega2560.
+///
+/// Bootloaders ignore the parameters, but they expect them to be present.
+pub const ENTER_PROGMODE_ISP: [u8; 12] = [CMD_ENTER_PROGMODE_ISP, 0xC8, 0x64, 0x19, 0x20, 0x00, 0x53, 0x03, 0xAC, 0x53, 0x00, 0x00];
+
+/// Leave programming mode, with a pre-delay and post-delay of 1 millisecond.
+pub const LEAVE_PROGMODE_ISP: [u8; 3] = [CMD_LEAVE_PROGMODE_ISP, 0x01, 0x01];
+
+/// The maximum size of a message body.
+const MAX_BODY_SIZE: usize = 275;
+
+/// Send a command and read the answer.
+///
+/// The returned answer starts with the command and the status byte.
+pub async fn command(port: &SerialPort, sequence: u8, body: &[u8], timeout: Duration) -> std::io::Result<Vec<u8>> {
+	let [size_high, size_low] = encode_size(body.len())?;
+	let mut message = Vec::with_capacity(body.len() + 6);
+	message.extend_from_slice(&[MESSAGE_START, sequence, size_high, size_low, TOKEN]);
+	message.extend_from_slice(body);
+	message.push(checksum(&message));
+	port.write_all(&message).await?;
+
+	let mut header = [0; 5];
+	read_exact(port, &mut header, timeout).await?;
+	if header[0] != MESSAGE_START || header[4] != TOKEN {
+		return Err(invalid_response("invalid message header"));
+	}
+	if header[1] != sequence {
+		return Err(invalid_response(&format!("expected sequence number {sequence}, got {}", header[1])));
+	}
+	let size = usize::from(u16::from_be_bytes([header[2], header[3]]));
+	if !(2..=MAX_BODY_SIZE).contains(&size) {
+		return Err(invalid_response(&format!("invalid message size: {size}")));
+	}
+
+	let mut answer = vec![0; size + 1];
+	read_exact(port, &mut answer, timeout).await?;
+	if checksum(&header) ^ checksum(&answer) != 0 {
+		return Err(invalid_response("checksum mismatch"));
+	}
+	answer.pop();
+
+	if answer[0] != body[0] {
+		return Err(invalid_response(&format!("expected answer to command 0x{:02X}, got 0x{:02X}", body[0], answer[0])));
+	}
+	if answer[1] != STATUS_CMD_OK {
+		return Err(invalid_response(&format!("command 0x{:02X} failed with status 0x{:02X}", body[0], answer[1])));
+	}
+	Ok(answer)
+}
+
+/// Create the body of a command to set the address for the next page operation.
+///
+/// The protocol uses word addresses for flash memory.
+/// For addresses above 128 KiB, the highest bit is set to tell the bootloader to load the extended address byte.
+pub fn load_address(address: u32) -> [u8; 5] {
+	let mut word_address = address / 2;
+	if word_address > 0xFFFF {
+		word_address |= 0x8000_0000;
+	}
+	let [a, b, c, d] = word_address.to_be_bytes();
+	[CMD_LOAD_ADDRESS, a, b, c, d]
+}
+
+/// Create the body of a command to program a page of flash memory at the current address.
+pub fn program_flash(page: &[u8]) -> std::io::Result<Vec<u8>> {
+	let [size_high, size_low] = encode_size(page.len())?;
+	// Page mode with a write page command, followed by the ISP instructions and poll values used by `avrdude`.
+	let mut body = vec![CMD_PROGRAM_FLASH_ISP, size_high, size_low, 0xC1, 0x0A, 0x40, 0x4C, 0x20, 0x00, 0x00];
+	body.extend_from_slice(page);
+	Ok(body)
+}
+
+/// Create the body of a command to read a page of flash memory from the current address.
+pub fn read_flash(size: usize) -> std::io::Result<[u8; 4]> {
+	let [size_high, size_low] = encode_size(size)?;
+	Ok([CMD_READ_FLASH_ISP, size_high, size_low, 0x20])
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn load_address_uses_word_addresses() {
+		assert_eq!(load_address(0x0000), [CMD_LOAD_ADDRESS, 0x00, 0x00, 0x00, 0x00]);
+		assert_eq!(load_address(0x0100), [CMD_LOAD_ADDRESS, 0x00, 0x00, 0x00, 0x80]);
+		assert_eq!(load_address(0x1_FF00), [CMD_LOAD_ADDRESS, 0x00, 0x00, 0xFF, 0x80]);
+	}
+
+	#[test]
+	fn load_address_sets_extended_bit_above_128k() {
+		assert_eq!(load_address(0x2_0000), [CMD_LOAD_ADDRESS, 0x80, 0x01, 0x00, 0x00]);
+		assert_eq!(load_address(0x3_FF00), [CMD_LOAD_ADDRESS, 0x80, 0x01, 0xFF, 0x80]);
+	}
+
+	#[test]
+	fn page_commands() {
+		let body = program_flash(&[0xAA; 256]).unwrap();
+		assert_eq!(body[..10], [CMD_PROGRAM_FLASH_ISP, 0x01, 0x00, 0xC1, 0x0A, 0x40, 0x4C, 0x20, 0x00, 0x00]);
+		assert_eq!(body.len(), 10 + 256);
+		assert_eq!(read_flash(256).unwrap(), [CMD_READ_FLASH_ISP, 0x01, 0x00, 0x20]);
+		assert_eq!(read_flash(0x1_0000).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+	}
+}
@@ -0,0 +1,221 @@
+use std::sync::{Arc, Mutex, Weak};
+
+use tokio::sync::broadcast;
+
+use crate::SerialPort;
+use crate::task::AbortOnDrop;
+
+/// The default number of chunks buffered for subscribers.
+const DEFAULT_CAPACITY: usize = 64;
+
+/// What happens when a [`Subscription`] falls too far behind.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+#[non_exhaustive]
+pub enum LagPolicy {
+	/// Skip the oldest chunks that are no longer buffered.
+	///
+	/// The next call to [`Subscription::recv()`] returns [`SubscriptionError::Lagged`] with the number of skipped chunks,
+	/// and the following calls continue with the oldest chunk that is still buffered.
+	#[default]
+	Skip,
+
+	/// Close the subscription.
+	///
+	/// The next call to [`Subscription::recv()`] and all calls after that return [`SubscriptionError::Closed`].
+	Disconnect,
+}
+
+/// A subscription to the data received on a serial port.
+///
+/// Created with [`SerialPort::subscribe()`].
+#[derive(Debug)]
+pub struct Subscription {
+	receiver: broadcast::Receiver<Item>,
+	policy: LagPolicy,
+	closed: bool,
+}
+
+/// An error returned by [`Subscription::recv()`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum SubscriptionError {
+	/// The subscriber fell behind and the given number of chunks were skipped.
+	Lagged(u64),
+
+	/// Reading from the serial port failed.
+	///
+	/// The error is delivered to all subscribers, and no more data will be received.
+	Io(std::io::Error),
+
+	/// No more data will be received.
+	///
+	/// This happens when the serial port reported end-of-file, after a read error was reported,
+	/// when the serial port was dropped, or when the subscriber fell behind with [`LagPolicy::Disconnect`].
+	Closed,
+}
+
+/// A chunk of data or an error sent to subscribers.
+#[derive(Debug, Clone)]
+enum Item {
+	Data(Arc<[u8]>),
+	Error(Arc<std::io::Error>),
+}
+
+/// The shared read task for all subscribers.
+#[derive(Debug)]
+pub(crate) struct Broadcaster {
+	sender: broadcast::Sender<Item>,
+	_task: AbortOnDrop,
+}
+
+pub(crate) type BroadcastSlot = Arc<Mutex<Option<Broadcaster>>>;
+
+impl SerialPort {
+	/// Subscribe to the data received on the serial port.
+	///
+	/// All subscribers receive all data that is read from the serial port after they subscribed,
+	/// so multiple tasks can observe the same incoming data without taking bytes from each other.
+	/// The data is read by a single background task that is started for the first subscriber.
+	/// It stops when data is received after all subscriptions have been dropped.
+	///
+	/// While the background task is running, data read directly from the serial port with [`Self::read()`]
+	/// is not seen by the subscribers, and vice versa.
+	///
+	/// This uses a buffer of 64 chunks and [`LagPolicy::Skip`].
+	/// Use [`Self::subscribe_with()`] to change these.
+	///
+	/// # Example
+	/// ```no_run
+	/// # async fn example() -> std::io::Result<()> {
+	/// use serial2_tokio::SerialPort;
+	///
+	/// let port = SerialPort::open("/dev/ttyUSB0", 115200)?;
+	/// let mut logger = port.subscribe()?;
+	/// tokio::spawn(async move {
+	///     while let Ok(data) = logger.recv().await {
+	///         println!("received: {data:?}");
+	///     }
+	/// });
+	/// # Ok(())
+	/// # }
+	/// ```
+	pub fn subscribe(&self) -> std::io::Result<Subscription> {
+		self.subscribe_with(DEFAULT_CAPACITY, LagPolicy::default())
+	}
+
+	/// Subscribe to the data received on the serial port with a custom buffer size and lag policy.
+	///
+	/// The `capacity` is the number of chunks buffered for the subscribers.
+	/// It is only used if this call starts the background read task:
+	/// if other subscriptions already exist, the existing buffer is shared.
+	///
+	/// See [`Self::subscribe()`] for more information.
+	///
+	/// # Panics
+	/// This function panics if `capacity` is zero.
+	pub fn subscribe_with(&self, capacity: usize, policy: LagPolicy) -> std::io::Result<Subscription> {
+		let mut slot = self.broadcast.lock().unwrap_or_else(|e| e.into_inner());
+		let receiver = match &*slot {
+			Some(broadcaster) => broadcaster.sender.subscribe(),
+			None => {
+				let port = self.try_clone()?;
+				let (sender, receiver) = broadcast::channel(capacity);
+				let task = tokio::spawn(forward(port, sender.clone(), Arc::downgrade(&self.broadcast)));
+				*slot = Some(Broadcaster {
+					sender,
+					_task: AbortOnDrop(task.abort_handle()),
+				});
+				receiver
+			},
+		};
+		Ok(Subscription {
+			receiver,
+			policy,
+			closed: false,
+		})
+	}
+}
+
+impl Subscription {
+	/// Receive the next chunk of data.
+	pub async fn recv(&mut self) -> Result<Arc<[u8]>, SubscriptionError> {
+		if self.closed {
+			return Err(SubscriptionError::Closed);
+		}
+		match self.receiver.recv().await {
+			Ok(Item::Data(data)) => Ok(data),
+			Ok(Item::Error(error)) => {
+				self.closed = true;
+				Err(SubscriptionError::Io(copy_error(&error)))
+			},
+			Err(broadcast::error::RecvError::Lagged(skipped)) => match self.policy {
+				LagPolicy::Skip => Err(SubscriptionError::Lagged(skipped)),
+				LagPolicy::Disconnect => {
+					self.closed = true;
+					Err(SubscriptionError::Closed)
+				},
+			},
+			Err(broadcast::error::RecvError::Closed) => {
+				self.closed = true;
+				Err(SubscriptionError::Closed)
+			},
+		}
+	}
+}
+
+impl std::fmt::Display for SubscriptionError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Lagged(skipped) => write!(f, "subscriber fell behind and skipped {skipped} chunks"),
+			Self::Io(e) => write!(f, "failed to read from serial port: {e}"),
+			Self::Closed => write!(f, "subscription closed"),
+		}
+	}
+}
+
+impl std::error::Error for SubscriptionError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			Self::Io(e) => Some(e),
+			_ => None,
+		}
+	}
+}
+
+/// Read from the serial port and send the data to all subscribers.
+async fn forward(port: SerialPort, sender: broadcast::Sender<Item>, slot: Weak<Mutex<Option<Broadcaster>>>) {
+	let mut buffer = vec![0; 4096];
+	loop {
+		let item = match port.read(&mut buffer).await {
+			Ok(0) => break,
+			Ok(read) => Item::Data(buffer[..read].into()),
+			Err(e) => {
+				sender.send(Item::Error(Arc::new(e))).ok();
+				break;
+			},
+		};
+		if sender.send(item).is_err() {
+			// Only stop if nobody subscribed in the meantime.
+			let Some(slot) = slot.upgrade() else {
+				return;
+			};
+			let mut slot = slot.lock().unwrap_or_else(|e| e.into_inner());
+			if sender.receiver_count() == 0 {
+				// Dropping the broadcaster aborts this task, which is fine: we're done anyway.
+				slot.take();
+				return;
+			}
+		}
+	}
+	if let Some(slot) = slot.upgrade() {
+		slot.lock().unwrap_or_else(|e| e.into_inner()).take();
+	}
+}
+
+/// Make a copy of an I/O error to give to each subscriber.
+fn copy_error(error: &std::io::Error) -> std::io::Error {
+	match error.raw_os_error() {
+		Some(code) => std::io::Error::from_raw_os_error(code),
+		None => std::io::Error::new(error.kind(), error.to_string()),
+	}
+}
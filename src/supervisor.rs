@@ -0,0 +1,274 @@
+//! Keep a serial port open and monitor its health.
+//!
+//! A [`Supervisor`] opens a serial port in a background task and reopens it automatically when the device is removed or stops responding.
+//! Optionally, it periodically runs a protocol level health check on the open port.
+//!
+//! The current [`Status`] and the currently open serial port can be retrieved or watched for changes from the [`Supervisor`].
+//!
+//! # Example
+//! ```no_run
+//! # async fn example() -> std::io::Result<()> {
+//! use std::time::Duration;
+//! use serial2_tokio::supervisor::{Status, Supervisor};
+//!
+//! let supervisor = Supervisor::builder("/dev/ttyUSB0", 115200)
+//!     .health_check(Duration::from_secs(5), |port| async move {
+//!         port.write_all(b"PING\r\n").await?;
+//!         let mut buffer = [0; 16];
+//!         port.read(&mut buffer).await?;
+//!         Ok(())
+//!     })
+//!     .spawn();
+//!
+//! let mut status = supervisor.watch_status();
+//! loop {
+//!     status.changed().await.unwrap();
+//!     match *status.borrow_and_update() {
+//!         Status::Up => println!("connected"),
+//!         Status::Degraded => println!("health check failed"),
+//!         Status::Down => println!("disconnected"),
+//!     }
+//! }
+//! # }
+//! ```
+
+use std::future::Future;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::watch;
+
+use crate::{IntoSettings, SerialPort};
+
+/// The status of a supervised serial port.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Status {
+	/// The serial port is open and the last health check (if any) succeeded.
+	Up,
+
+	/// The serial port is open, but the last health check failed.
+	Degraded,
+
+	/// The serial port is not open.
+	Down,
+}
+
+type HealthCheck = Box<dyn Fn(Arc<SerialPort>) -> Pin<Box<dyn Future<Output = std::io::Result<()>> + Send>> + Send + Sync>;
+
+/// Builder for a [`Supervisor`].
+pub struct SupervisorBuilder<S> {
+	path: PathBuf,
+	settings: S,
+	reopen_delay: Duration,
+	monitor: Monitor,
+}
+
+/// The health check configuration of a supervisor.
+struct Monitor {
+	health_check: Option<HealthCheck>,
+	interval: Duration,
+	timeout: Duration,
+	max_failed_checks: u32,
+}
+
+impl<S: IntoSettings + Clone + Send + 'static> SupervisorBuilder<S> {
+	/// Set the delay between attempts to open the serial port.
+	///
+	/// The default is 1 second.
+	pub fn reopen_delay(mut self, delay: Duration) -> Self {
+		self.reopen_delay = delay;
+		self
+	}
+
+	/// Run a health check on the serial port at a fixed interval.
+	///
+	/// The health check receives the open serial port and should return an error if the device is not responding correctly.
+	/// A health check that does not finish within the timeout is considered failed.
+	/// The timeout defaults to the interval, and can be changed with [`Self::health_check_timeout()`].
+	///
+	/// When a health check fails the status changes to [`Status::Degraded`].
+	/// After too many consecutive failures, the serial port is closed and reopened.
+	pub fn health_check<F, Fut>(mut self, interval: Duration, check: F) -> Self
+	where
+		F: Fn(Arc<SerialPort>) -> Fut + Send + Sync + 'static,
+		Fut: Future<Output = std::io::Result<()>> + Send + 'static,
+	{
+		self.monitor.health_check = Some(Box::new(move |port| Box::pin(check(port))));
+		self.monitor.interval = interval;
+		self.monitor.timeout = interval;
+		self
+	}
+
+	/// Set the maximum duration of a single health check.
+	pub fn health_check_timeout(mut self, timeout: Duration) -> Self {
+		self.monitor.timeout = timeout;
+		self
+	}
+
+	/// Set the number of consecutive failed health checks after which the serial port is reopened.
+	///
+	/// The default is 3.
+	pub fn max_failed_checks(mut self, count: u32) -> Self {
+		self.monitor.max_failed_checks = count.max(1);
+		self
+	}
+
+	/// Start supervising the serial port in a background task.
+	///
+	/// The background task is stopped when the returned [`Supervisor`] is dropped.
+	///
+	/// # Panics
+	/// This function panics if it is called outside of the context of a Tokio runtime.
+	pub fn spawn(self) -> Supervisor {
+		let (status_tx, status_rx) = watch::channel(Status::Down);
+		let (port_tx, port_rx) = watch::channel(None);
+		let task = tokio::spawn(self.run(status_tx, port_tx));
+		Supervisor {
+			status: status_rx,
+			port: port_rx,
+			task,
+		}
+	}
+
+	async fn run(self, status: watch::Sender<Status>, port_tx: watch::Sender<Option<Arc<SerialPort>>>) {
+		let Self { path, settings, reopen_delay, monitor } = self;
+		let mut opened_before = false;
+		loop {
+			let open_path = path.clone();
+			let settings = settings.clone();
+			let port = tokio::task::spawn_blocking(move || SerialPort::open(open_path, settings)).await;
+			if let Ok(Ok(port)) = port {
+				if opened_before {
+					crate::stats::record_reopen(&path.to_string_lossy());
+				}
+				opened_before = true;
+				let port = Arc::new(port);
+				port_tx.send_replace(Some(port.clone()));
+				status.send_replace(Status::Up);
+				monitor.run(&port, &status).await;
+				port_tx.send_replace(None);
+				status.send_replace(Status::Down);
+			}
+			tokio::time::sleep(reopen_delay).await;
+		}
+	}
+
+}
+
+impl Monitor {
+	/// Monitor an open serial port until it should be reopened.
+	async fn run(&self, port: &Arc<SerialPort>, status: &watch::Sender<Status>) {
+		let Some(health_check) = &self.health_check else {
+			port.closed().await.ok();
+			return;
+		};
+
+		let mut failed_checks = 0;
+		loop {
+			if tokio::time::timeout(self.interval, port.closed()).await.is_ok() {
+				return;
+			}
+			let result = tokio::time::timeout(self.timeout, health_check(port.clone())).await;
+			if result.is_err() {
+				port.stats.record_timeout();
+			}
+			match result {
+				Ok(Ok(())) => {
+					failed_checks = 0;
+					status.send_replace(Status::Up);
+				},
+				Ok(Err(_)) | Err(_) => {
+					failed_checks += 1;
+					if failed_checks >= self.max_failed_checks {
+						return;
+					}
+					status.send_replace(Status::Degraded);
+				},
+			}
+		}
+	}
+}
+
+/// A serial port that is kept open by a background task.
+///
+/// See the [module documentation](self) for more information.
+pub struct Supervisor {
+	status: watch::Receiver<Status>,
+	port: watch::Receiver<Option<Arc<SerialPort>>>,
+	task: tokio::task::JoinHandle<()>,
+}
+
+impl Supervisor {
+	/// Create a builder to supervise the serial port with the given path and settings.
+	///
+	/// The settings are applied every time the serial port is opened.
+	pub fn builder<S>(path: impl Into<PathBuf>, settings: S) -> SupervisorBuilder<S>
+	where
+		S: IntoSettings + Clone + Send + 'static,
+	{
+		SupervisorBuilder {
+			path: path.into(),
+			settings,
+			reopen_delay: Duration::from_secs(1),
+			monitor: Monitor {
+				health_check: None,
+				interval: Duration::MAX,
+				timeout: Duration::MAX,
+				max_failed_checks: 3,
+			},
+		}
+	}
+
+	/// Get the current status of the serial port.
+	pub fn status(&self) -> Status {
+		*self.status.borrow()
+	}
+
+	/// Get a receiver to watch for status changes.
+	pub fn watch_status(&self) -> watch::Receiver<Status> {
+		self.status.clone()
+	}
+
+	/// Get the currently open serial port.
+	///
+	/// Returns `None` if the serial port is not open.
+	/// When the serial port is reopened, a new [`SerialPort`] object is created.
+	pub fn port(&self) -> Option<Arc<SerialPort>> {
+		self.port.borrow().clone()
+	}
+
+	/// Get a receiver to watch for changes of the open serial port.
+	pub fn watch_port(&self) -> watch::Receiver<Option<Arc<SerialPort>>> {
+		self.port.clone()
+	}
+
+	/// Wait until a serial port is open and return it.
+	pub async fn wait_for_port(&self) -> Arc<SerialPort> {
+		let mut port = self.port.clone();
+		loop {
+			if let Some(port) = port.borrow_and_update().clone() {
+				return port;
+			}
+			if port.changed().await.is_err() {
+				// The background task panicked, so no serial port will ever be opened.
+				std::future::pending::<()>().await;
+			}
+		}
+	}
+}
+
+impl Drop for Supervisor {
+	fn drop(&mut self) {
+		self.task.abort();
+	}
+}
+
+impl std::fmt::Debug for Supervisor {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("Supervisor")
+			.field("status", &self.status())
+			.finish_non_exhaustive()
+	}
+}
@@ -0,0 +1,10 @@
+use tokio::io::AsyncWriteExt;
+use tokio::net::tcp::OwnedWriteHalf;
+use tokio::sync::Mutex;
+
+/// Write all data to a TCP stream shared between multiple tasks.
+///
+/// The lock is held until all data is written, so messages from different tasks are not interleaved.
+pub async fn write_all_locked(stream: &Mutex<OwnedWriteHalf>, buffer: &[u8]) -> std::io::Result<()> {
+	stream.lock().await.write_all(buffer).await
+}
@@ -0,0 +1,32 @@
+//! Helpers for unit tests that simulate a device on the other side of a pseudo-terminal pair.
+
+use std::time::Duration;
+
+use crate::SerialPort;
+
+/// Open a pseudo-terminal pair, and get the response timeout to use for a protocol client on the first port.
+///
+/// The timeout is long enough for a pseudo-terminal, but short enough to keep tests that expect a timeout fast.
+pub(crate) fn pair_with_timeout() -> (SerialPort, SerialPort, Duration) {
+	let (a, b) = SerialPort::pair().unwrap();
+	(a, b, Duration::from_millis(200))
+}
+
+/// Read exactly the expected bytes from `port` and check them.
+pub(crate) async fn expect(port: &SerialPort, expected: &[u8]) {
+	let mut buffer = vec![0; expected.len()];
+	let mut read = 0;
+	while read < buffer.len() {
+		match port.read(&mut buffer[read..]).await.unwrap() {
+			0 => panic!("unexpected end of file after {:02X?}", &buffer[..read]),
+			n => read += n,
+		}
+	}
+	assert_eq!(buffer, expected);
+}
+
+/// Read exactly the expected bytes from `port`, check them and send the reply.
+pub(crate) async fn expect_then_reply(port: &SerialPort, expected: &[u8], reply: &[u8]) {
+	expect(port, expected).await;
+	port.write_all(reply).await.unwrap();
+}
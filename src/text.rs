@@ -0,0 +1,337 @@
+//! Text mode helpers for serial consoles.
+//!
+//! Devices on a serial port use different conventions for text:
+//! some end lines with CR LF, some with only CR, and some expect DEL instead of BS for backspace.
+//! The [`TextPort`] wrapper converts between these conventions and the normal Rust conventions while data streams through,
+//! and can echo written data back to the reader for devices that do not echo their input.
+//!
+//! The application always uses `\n` as the newline character.
+//! Written `\n` characters are translated to the newline sequence of the device,
+//! and the newline sequence of the device is translated to `\n` in read data.
+//! The translation is correct even when a newline sequence is split over multiple reads or writes.
+//!
+//! The wrapper works with any type that implements [`AsyncRead`] and [`AsyncWrite`],
+//! such as [`SerialPort`][crate::SerialPort] and [`SocketPort`][crate::SocketPort].
+//!
+//! # Example
+//! ```no_run
+//! # async fn example() -> std::io::Result<()> {
+//! use serial2_tokio::SerialPort;
+//! use serial2_tokio::text::{Newline, TextConfig, TextPort};
+//! use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+//!
+//! let port = SerialPort::open("/dev/ttyUSB0", 115200)?;
+//! let mut config = TextConfig::new();
+//! config.set_tx_newline(Newline::CrLf);
+//! config.set_rx_newline(Newline::CrLf);
+//! let mut port = BufReader::new(TextPort::new(port, config));
+//! port.write_all(b"AT\n").await?;
+//! let mut line = String::new();
+//! port.read_line(&mut line).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::task::{ready, Poll};
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+/// The BS control character.
+const BS: u8 = 0x08;
+
+/// The DEL control character.
+const DEL: u8 = 0x7F;
+
+/// A newline sequence.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum Newline {
+	/// A single line feed (`\n`), as used by Unix systems.
+	#[default]
+	Lf,
+
+	/// A single carriage return (`\r`).
+	Cr,
+
+	/// A carriage return followed by a line feed (`\r\n`), as used by Windows and many network protocols.
+	CrLf,
+}
+
+impl Newline {
+	/// Get the bytes of the newline sequence.
+	pub fn as_bytes(self) -> &'static [u8] {
+		match self {
+			Self::Lf => b"\n",
+			Self::Cr => b"\r",
+			Self::CrLf => b"\r\n",
+		}
+	}
+}
+
+/// How to send backspace characters to the device.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum Backspace {
+	/// Send BS and DEL characters unchanged.
+	#[default]
+	Unchanged,
+
+	/// Send both BS and DEL characters as BS (`0x08`).
+	Bs,
+
+	/// Send both BS and DEL characters as DEL (`0x7F`).
+	Del,
+}
+
+/// Configuration for a [`TextPort`].
+#[derive(Debug, Clone, Default)]
+pub struct TextConfig {
+	tx_newline: Newline,
+	rx_newline: Newline,
+	backspace: Backspace,
+	local_echo: bool,
+}
+
+impl TextConfig {
+	/// Create a new configuration that does not change the data and does not echo.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Set the newline sequence to send to the device for each written `\n`.
+	pub fn set_tx_newline(&mut self, newline: Newline) {
+		self.tx_newline = newline;
+	}
+
+	/// Get the newline sequence that is sent to the device for each written `\n`.
+	pub fn get_tx_newline(&self) -> Newline {
+		self.tx_newline
+	}
+
+	/// Set the newline sequence used by the device, which is translated to `\n` in read data.
+	///
+	/// With [`Newline::CrLf`], a lone CR or LF is passed through unchanged.
+	/// A CR at the end of the available data is held back until the next byte is received,
+	/// to check if it is followed by a LF.
+	pub fn set_rx_newline(&mut self, newline: Newline) {
+		self.rx_newline = newline;
+	}
+
+	/// Get the newline sequence used by the device.
+	pub fn get_rx_newline(&self) -> Newline {
+		self.rx_newline
+	}
+
+	/// Set how backspace characters are sent to the device.
+	pub fn set_backspace(&mut self, backspace: Backspace) {
+		self.backspace = backspace;
+	}
+
+	/// Get how backspace characters are sent to the device.
+	pub fn get_backspace(&self) -> Backspace {
+		self.backspace
+	}
+
+	/// Enable or disable local echo.
+	///
+	/// With local echo enabled, all data written to the wrapper is also returned by the next reads,
+	/// before any data from the device.
+	/// The echo contains the written data as given by the application, without translation.
+	pub fn set_local_echo(&mut self, enable: bool) {
+		self.local_echo = enable;
+	}
+
+	/// Check if local echo is enabled.
+	pub fn get_local_echo(&self) -> bool {
+		self.local_echo
+	}
+}
+
+/// A wrapper that translates newlines and backspace characters, and optionally echoes written data.
+///
+/// See the [module documentation](self) for more information.
+#[derive(Debug)]
+pub struct TextPort<T> {
+	inner: T,
+	config: TextConfig,
+	/// Translated data waiting to be written to the inner stream.
+	tx_pending: Vec<u8>,
+	/// Written data waiting to be echoed to the reader.
+	echo: VecDeque<u8>,
+	/// Translated data waiting to be returned to the reader.
+	rx_ready: VecDeque<u8>,
+	/// A CR was received at the end of the previous read, and may be the start of a CR LF sequence.
+	rx_cr: bool,
+	/// Buffer for data read from the inner stream.
+	rx_buffer: Vec<u8>,
+}
+
+impl<T> TextPort<T> {
+	/// Wrap a stream with the given text configuration.
+	pub fn new(inner: T, config: TextConfig) -> Self {
+		Self {
+			inner,
+			config,
+			tx_pending: Vec::new(),
+			echo: VecDeque::new(),
+			rx_ready: VecDeque::new(),
+			rx_cr: false,
+			rx_buffer: Vec::new(),
+		}
+	}
+
+	/// Get the text configuration.
+	pub fn config(&self) -> &TextConfig {
+		&self.config
+	}
+
+	/// Change the text configuration.
+	///
+	/// The new configuration applies to data read or written after the change.
+	pub fn set_config(&mut self, config: TextConfig) {
+		self.config = config;
+	}
+
+	/// Get a reference to the wrapped stream.
+	pub fn get_ref(&self) -> &T {
+		&self.inner
+	}
+
+	/// Get a mutable reference to the wrapped stream.
+	///
+	/// Reading from or writing to the wrapped stream directly bypasses the translation.
+	pub fn get_mut(&mut self) -> &mut T {
+		&mut self.inner
+	}
+
+	/// Consume the wrapper and return the wrapped stream.
+	///
+	/// Translated data that has not been written to the wrapped stream yet is lost.
+	/// Use [`AsyncWriteExt::flush()`][tokio::io::AsyncWriteExt::flush] first to make sure all data has been written.
+	#[cfg_attr(not(feature = "doc"), allow(rustdoc::broken_intra_doc_links))]
+	pub fn into_inner(self) -> T {
+		self.inner
+	}
+
+	/// Translate received data to the application conventions.
+	fn translate_rx(&mut self, data: &[u8]) {
+		for &byte in data {
+			match self.config.rx_newline {
+				Newline::Lf => self.rx_ready.push_back(byte),
+				Newline::Cr => self.rx_ready.push_back(if byte == b'\r' { b'\n' } else { byte }),
+				Newline::CrLf => {
+					if std::mem::take(&mut self.rx_cr) {
+						if byte == b'\n' {
+							self.rx_ready.push_back(b'\n');
+							continue;
+						}
+						self.rx_ready.push_back(b'\r');
+					}
+					if byte == b'\r' {
+						self.rx_cr = true;
+					} else {
+						self.rx_ready.push_back(byte);
+					}
+				},
+			}
+		}
+	}
+
+	/// Translate written data to the device conventions.
+	fn translate_tx(&mut self, data: &[u8]) {
+		for &byte in data {
+			match byte {
+				b'\n' => self.tx_pending.extend_from_slice(self.config.tx_newline.as_bytes()),
+				BS | DEL => self.tx_pending.push(match self.config.backspace {
+					Backspace::Unchanged => byte,
+					Backspace::Bs => BS,
+					Backspace::Del => DEL,
+				}),
+				_ => self.tx_pending.push(byte),
+			}
+		}
+	}
+}
+
+impl<T: AsyncWrite + Unpin> TextPort<T> {
+	/// Write all pending translated data to the wrapped stream.
+	fn poll_write_pending(&mut self, cx: &mut std::task::Context<'_>) -> Poll<std::io::Result<()>> {
+		while !self.tx_pending.is_empty() {
+			let written = ready!(Pin::new(&mut self.inner).poll_write(cx, &self.tx_pending))?;
+			if written == 0 {
+				return Poll::Ready(Err(std::io::ErrorKind::WriteZero.into()));
+			}
+			self.tx_pending.drain(..written);
+		}
+		Poll::Ready(Ok(()))
+	}
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for TextPort<T> {
+	fn poll_read(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<std::io::Result<()>> {
+		let this = self.get_mut();
+		if buf.remaining() == 0 {
+			return Poll::Ready(Ok(()));
+		}
+		if !this.echo.is_empty() {
+			take_into(&mut this.echo, buf);
+			return Poll::Ready(Ok(()));
+		}
+
+		while this.rx_ready.is_empty() {
+			this.rx_buffer.resize(buf.remaining(), 0);
+			let mut read_buf = ReadBuf::new(&mut this.rx_buffer);
+			ready!(Pin::new(&mut this.inner).poll_read(cx, &mut read_buf))?;
+			let read = read_buf.filled().len();
+			if read == 0 {
+				// Emit a held back CR before reporting end-of-file.
+				if std::mem::take(&mut this.rx_cr) {
+					buf.put_slice(b"\r");
+				}
+				return Poll::Ready(Ok(()));
+			}
+			// If all data was held back, keep reading, since returning nothing would signal end-of-file.
+			let data = std::mem::take(&mut this.rx_buffer);
+			this.translate_rx(&data[..read]);
+			this.rx_buffer = data;
+		}
+		take_into(&mut this.rx_ready, buf);
+		Poll::Ready(Ok(()))
+	}
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for TextPort<T> {
+	fn poll_write(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>> {
+		let this = self.get_mut();
+		ready!(this.poll_write_pending(cx))?;
+		this.translate_tx(buf);
+		if this.config.local_echo {
+			this.echo.extend(buf);
+		}
+		// The data has been accepted, so errors are reported by the next write or flush.
+		let _ = this.poll_write_pending(cx);
+		Poll::Ready(Ok(buf.len()))
+	}
+
+	fn poll_flush(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<std::io::Result<()>> {
+		let this = self.get_mut();
+		ready!(this.poll_write_pending(cx))?;
+		Pin::new(&mut this.inner).poll_flush(cx)
+	}
+
+	fn poll_shutdown(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<std::io::Result<()>> {
+		let this = self.get_mut();
+		ready!(this.poll_write_pending(cx))?;
+		Pin::new(&mut this.inner).poll_shutdown(cx)
+	}
+}
+
+/// Move as much data as fits from a queue into a read buffer.
+fn take_into(queue: &mut VecDeque<u8>, buf: &mut ReadBuf<'_>) {
+	let len = queue.len().min(buf.remaining());
+	let (front, back) = queue.as_slices();
+	let from_front = len.min(front.len());
+	buf.put_slice(&front[..from_front]);
+	buf.put_slice(&back[..len - from_front]);
+	queue.drain(..len);
+}
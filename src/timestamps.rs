@@ -0,0 +1,199 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use crate::SerialPort;
+
+/// The time of the last read and write on a serial port.
+///
+/// Use [`SerialPort::timestamps()`] to get the timestamps,
+/// after enabling them with [`SerialPort::set_timestamping()`].
+///
+/// The timestamps are taken from the monotonic clock of [`std::time::Instant`], right after the OS call returned.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct Timestamps {
+	/// The time when the data of the last successful read became available, or `None` if nothing was read yet.
+	pub last_read: Option<Instant>,
+
+	/// The time when the last successful write was handed to the OS, or `None` if nothing was written yet.
+	///
+	/// The data may still be in the output buffer of the OS or the serial port at this time.
+	/// Use [`SerialPort::drain()`] if you need to know when the data has been transmitted.
+	pub last_write: Option<Instant>,
+}
+
+impl SerialPort {
+	/// Read bytes from the serial port and get the time when they became available.
+	///
+	/// This is identical to [`Self::read()`], except that it also returns a timestamp from the monotonic clock,
+	/// taken right after the OS returned the data.
+	/// The timestamp is taken for each read, regardless of [`Self::set_timestamping()`].
+	///
+	/// Because the OS may buffer received data, the timestamp is an upper bound for the time when the data arrived.
+	/// Read often with a buffer that is large enough for all available data to get the most accurate timestamps.
+	///
+	/// Use [`Self::enable_rx_hardware_timestamps()`] to get timestamps from the driver instead, where it supports them.
+	///
+	/// # Example
+	/// ```no_run
+	/// # async fn example() -> std::io::Result<()> {
+	/// use serial2_tokio::SerialPort;
+	///
+	/// let port = SerialPort::open("/dev/ttyUSB0", 115200)?;
+	/// let mut buffer = [0; 256];
+	/// loop {
+	///     let (read, time) = port.read_timestamped(&mut buffer).await?;
+	///     println!("{:?}: {:02X?}", time, &buffer[..read]);
+	/// }
+	/// # }
+	/// ```
+	pub async fn read_timestamped(&self, buf: &mut [u8]) -> std::io::Result<(usize, Instant)> {
+		self.check_overrun()?;
+		loop {
+			let read = self.read_unfiltered(buf).await?;
+			let time = Instant::now();
+			if read == 0 {
+				if buf.is_empty() {
+					return Ok((0, time));
+				}
+				match self.get_zero_read_policy() {
+					crate::ZeroReadPolicy::Eof => return Ok((0, time)),
+					crate::ZeroReadPolicy::Error => return Err(crate::zero_read::zero_read_error()),
+					crate::ZeroReadPolicy::Retry => tokio::time::sleep(crate::zero_read::RETRY_DELAY).await,
+				}
+				continue;
+			}
+			let read = self.echo.strip(&mut buf[..read]);
+			if read > 0 {
+				self.timestamps.record_read(time);
+				return Ok((read, time));
+			}
+		}
+	}
+
+	/// Enable or disable recording the time of the last read and write.
+	///
+	/// When enabled, all successful reads and writes on this handle update the [`Timestamps`] returned by [`Self::timestamps()`],
+	/// including reads and writes through the [`AsyncRead`][tokio::io::AsyncRead] and [`AsyncWrite`][tokio::io::AsyncWrite] traits.
+	/// This is useful to timestamp the data of a protocol analyzer or codec without changing how the data is read,
+	/// or to schedule the next transmission relative to the last one.
+	///
+	/// Timestamping is disabled by default.
+	/// Disabling it clears the recorded timestamps.
+	///
+	/// # Example
+	/// ```no_run
+	/// # async fn example() -> std::io::Result<()> {
+	/// use serial2_tokio::SerialPort;
+	///
+	/// let port = SerialPort::open("/dev/ttyUSB0", 19200)?;
+	/// port.set_timestamping(true);
+	/// port.write_all(&[0x55, 0x3C]).await?;
+	/// let mut buffer = [0; 8];
+	/// port.read(&mut buffer).await?;
+	/// let timestamps = port.timestamps();
+	/// if let (Some(write), Some(read)) = (timestamps.last_write, timestamps.last_read) {
+	///     println!("response after {:?}", read.saturating_duration_since(write));
+	/// }
+	/// # Ok(())
+	/// # }
+	/// ```
+	pub fn set_timestamping(&self, enabled: bool) {
+		self.timestamps.enabled.store(enabled, Ordering::Relaxed);
+		if !enabled {
+			*self.timestamps.timestamps.lock().unwrap_or_else(|e| e.into_inner()) = Timestamps::default();
+		}
+	}
+
+	/// Enable hardware receive timestamps, if the driver of the serial port supports them.
+	///
+	/// This is a best-effort function.
+	/// It returns `Ok(true)` if the timestamps returned by [`Self::read_timestamped()`] and [`Self::timestamps()`]
+	/// are now taken by the driver when the data is received,
+	/// and `Ok(false)` if they remain userspace timestamps taken right after the OS returned the data.
+	/// In both cases, timestamping is enabled as with [`Self::set_timestamping()`].
+	///
+	/// Unlike network interfaces, the serial drivers of Linux, macOS and Windows do not currently report when the data was received,
+	/// so this function always falls back to userspace timestamps.
+	/// It allows applications to use hardware timestamps as soon as a platform provides them, without changing their code.
+	///
+	/// For accurate clock synchronization, GNSS receivers provide a separate pulse-per-second signal, often connected to the DCD line.
+	/// See the [`pps`][crate::pps] module.
+	///
+	/// # Example
+	/// ```no_run
+	/// # async fn example() -> std::io::Result<()> {
+	/// use serial2_tokio::SerialPort;
+	///
+	/// let port = SerialPort::open("/dev/ttyUSB0", 9600)?;
+	/// if !port.enable_rx_hardware_timestamps()? {
+	///     eprintln!("hardware timestamps not supported, using userspace timestamps");
+	/// }
+	/// let mut buffer = [0; 256];
+	/// let (read, time) = port.read_timestamped(&mut buffer).await?;
+	/// println!("{:?}: {:02X?}", time, &buffer[..read]);
+	/// # Ok(())
+	/// # }
+	/// ```
+	#[cfg_attr(not(feature = "doc"), allow(rustdoc::broken_intra_doc_links))]
+	pub fn enable_rx_hardware_timestamps(&self) -> std::io::Result<bool> {
+		self.set_timestamping(true);
+		Ok(false)
+	}
+
+	/// Check if recording the time of the last read and write is enabled.
+	pub fn get_timestamping(&self) -> bool {
+		self.timestamps.enabled.load(Ordering::Relaxed)
+	}
+
+	/// Get the time of the last read and write on this handle.
+	///
+	/// The timestamps are only recorded when enabled with [`Self::set_timestamping()`].
+	pub fn timestamps(&self) -> Timestamps {
+		*self.timestamps.timestamps.lock().unwrap_or_else(|e| e.into_inner())
+	}
+}
+
+/// Records the time of the last read and write, if enabled.
+#[derive(Debug, Default)]
+pub(crate) struct TimestampRecorder {
+	enabled: AtomicBool,
+	timestamps: Mutex<Timestamps>,
+}
+
+impl TimestampRecorder {
+	/// Record the time of a successful read.
+	pub fn record_read(&self, time: Instant) {
+		if self.enabled.load(Ordering::Relaxed) {
+			self.timestamps.lock().unwrap_or_else(|e| e.into_inner()).last_read = Some(time);
+		}
+	}
+
+	/// Record the time of a successful write.
+	pub fn record_write(&self, time: Instant) {
+		if self.enabled.load(Ordering::Relaxed) {
+			self.timestamps.lock().unwrap_or_else(|e| e.into_inner()).last_write = Some(time);
+		}
+	}
+}
+
+#[cfg(test)]
+#[cfg(all(unix, feature = "unix"))]
+mod test {
+	use super::*;
+
+	#[tokio::test]
+	async fn rx_hardware_timestamps_fall_back_to_userspace_timestamps() {
+		let (port, other) = SerialPort::pair().unwrap();
+		assert!(!port.enable_rx_hardware_timestamps().unwrap());
+		assert!(port.get_timestamping());
+
+		let before = Instant::now();
+		other.write_all(b"$GPGGA").await.unwrap();
+		let mut buffer = [0; 16];
+		let (_read, time) = port.read_timestamped(&mut buffer).await.unwrap();
+		assert!(time >= before);
+		assert_eq!(port.timestamps().last_read, Some(time));
+	}
+}
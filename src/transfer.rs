@@ -0,0 +1,355 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::Poll;
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+#[cfg(any(feature = "doc", feature = "cancel"))]
+use tokio_util::sync::CancellationToken;
+
+use crate::cancel::{cancelled_error, Cancel};
+use crate::pacing::Pacer;
+use crate::{SerialPort, WritePacing};
+
+/// The size of each of the two buffers used by [`SerialPort::read_n()`].
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Options for [`SerialPort::send_from()`].
+#[derive(Debug, Clone)]
+pub struct SendOptions {
+	chunk_size: usize,
+	pacing: Option<WritePacing>,
+	drain: bool,
+	cancel: Cancel,
+}
+
+impl Default for SendOptions {
+	fn default() -> Self {
+		Self {
+			chunk_size: 4096,
+			pacing: None,
+			drain: true,
+			cancel: Cancel::never(),
+		}
+	}
+}
+
+impl SendOptions {
+	/// Create new options with the default values.
+	///
+	/// By default, data is read and written in chunks of 4096 bytes without pacing,
+	/// and the transfer waits until the output buffer has been transmitted.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Set the maximum number of bytes to read from the reader and write to the serial port at once.
+	///
+	/// # Panics
+	/// This function panics if the size is 0.
+	pub fn set_chunk_size(&mut self, size: usize) {
+		assert!(size > 0, "the chunk size can not be 0");
+		self.chunk_size = size;
+	}
+
+	/// Get the maximum number of bytes to read from the reader and write to the serial port at once.
+	pub fn get_chunk_size(&self) -> usize {
+		self.chunk_size
+	}
+
+	/// Set the pacing of the transfer.
+	///
+	/// This works the same as [`SerialPort::set_write_pacing()`], but it only applies to this transfer.
+	/// If the serial port itself also has write pacing enabled, both limits apply.
+	pub fn set_pacing(&mut self, pacing: Option<WritePacing>) {
+		self.pacing = pacing;
+	}
+
+	/// Get the pacing of the transfer.
+	pub fn get_pacing(&self) -> Option<WritePacing> {
+		self.pacing
+	}
+
+	/// Set whether to wait until all data has been transmitted before the transfer completes.
+	///
+	/// See [`SerialPort::drain()`] for details.
+	pub fn set_drain(&mut self, drain: bool) {
+		self.drain = drain;
+	}
+
+	/// Check whether the transfer waits until all data has been transmitted.
+	pub fn get_drain(&self) -> bool {
+		self.drain
+	}
+
+	/// Set a cancellation token to stop the transfer.
+	///
+	/// When the token is cancelled, the transfer stops at the next chunk boundary:
+	/// a chunk that is being written to the serial port is always written completely,
+	/// so the number of bytes passed to the last call of `progress` is exactly the number of bytes written to the serial port.
+	/// If draining is enabled, the transfer still waits until the written data has been transmitted.
+	/// The transfer then returns an error that converts to [`Error::Cancelled`][crate::Error::Cancelled].
+	#[cfg(any(feature = "doc", feature = "cancel"))]
+	#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "cancel")))]
+	pub fn set_cancel_token(&mut self, token: Option<CancellationToken>) {
+		self.cancel = Cancel::new(token);
+	}
+
+	/// Get the cancellation token of the transfer.
+	#[cfg(any(feature = "doc", feature = "cancel"))]
+	#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "cancel")))]
+	pub fn get_cancel_token(&self) -> Option<&CancellationToken> {
+		self.cancel.token()
+	}
+}
+
+impl SerialPort {
+	/// Read exactly `n` bytes from the serial port and write them to `sink`.
+	///
+	/// This is meant for large transfers, like a firmware dump or the download of a data logger.
+	/// The data is read in large chunks, and the serial port is read while the previous chunk is written to the sink,
+	/// so that a slow sink does not cause the input buffer of the serial port to overflow.
+	/// The sink is flushed when all data has been written.
+	///
+	/// The `progress` callback is called with the total number of bytes written to the sink after each write.
+	///
+	/// Returns an error of kind [`std::io::ErrorKind::TimedOut`] if no data is received for `stall_timeout`,
+	/// or an error of kind [`std::io::ErrorKind::UnexpectedEof`] if the serial port reports the end of the stream.
+	/// The data received before the error has been written to the sink.
+	///
+	/// # Example
+	/// ```no_run
+	/// # async fn example() -> std::io::Result<()> {
+	/// use serial2_tokio::SerialPort;
+	/// use std::time::Duration;
+	///
+	/// let port = SerialPort::open("/dev/ttyUSB0", 921600)?;
+	/// port.write_all(b"DUMP\r").await?;
+	/// let mut dump = Vec::new();
+	/// let size = 4 * 1024 * 1024;
+	/// port.read_n(size, &mut dump, Duration::from_secs(2), |done| {
+	///     println!("{}%", done * 100 / size);
+	/// }).await?;
+	/// # Ok(())
+	/// # }
+	/// ```
+	pub async fn read_n(
+		&self,
+		n: u64,
+		sink: impl AsyncWrite + Unpin,
+		stall_timeout: Duration,
+		progress: impl FnMut(u64),
+	) -> std::io::Result<()> {
+		self.read_n_impl(n, sink, stall_timeout, &Cancel::never(), progress).await
+	}
+
+	/// Read exactly `n` bytes from the serial port and write them to `sink`, until the transfer is cancelled.
+	///
+	/// This works like [`Self::read_n()`], but it stops when the cancellation token is cancelled,
+	/// and returns an error that converts to [`Error::Cancelled`][crate::Error::Cancelled].
+	///
+	/// Cancellation only aborts a read from the serial port that is waiting for data.
+	/// All data that was already received is written to the sink and the sink is flushed before the function returns,
+	/// so the number of bytes passed to the last call of `progress` is exactly the number of bytes taken from the serial port.
+	/// Data that has not been read yet stays in the input buffer of the serial port.
+	///
+	/// # Example
+	/// ```no_run
+	/// # async fn example() -> std::io::Result<()> {
+	/// use serial2_tokio::SerialPort;
+	/// use std::time::Duration;
+	/// use tokio_util::sync::CancellationToken;
+	///
+	/// let port = SerialPort::open("/dev/ttyUSB0", 921600)?;
+	/// let cancel = CancellationToken::new();
+	/// tokio::spawn({
+	///     let cancel = cancel.clone();
+	///     async move {
+	///         tokio::time::sleep(Duration::from_secs(60)).await;
+	///         cancel.cancel();
+	///     }
+	/// });
+	/// let mut dump = Vec::new();
+	/// port.write_all(b"DUMP\r").await?;
+	/// port.read_n_with_cancel(4 * 1024 * 1024, &mut dump, Duration::from_secs(2), &cancel, |_| ()).await?;
+	/// # Ok(())
+	/// # }
+	/// ```
+	#[cfg(any(feature = "doc", feature = "cancel"))]
+	#[cfg_attr(feature = "doc-cfg", doc(cfg(feature = "cancel")))]
+	pub async fn read_n_with_cancel(
+		&self,
+		n: u64,
+		sink: impl AsyncWrite + Unpin,
+		stall_timeout: Duration,
+		cancel: &CancellationToken,
+		progress: impl FnMut(u64),
+	) -> std::io::Result<()> {
+		self.read_n_impl(n, sink, stall_timeout, &Cancel::new(Some(cancel.clone())), progress).await
+	}
+
+	async fn read_n_impl(
+		&self,
+		n: u64,
+		mut sink: impl AsyncWrite + Unpin,
+		stall_timeout: Duration,
+		cancel: &Cancel,
+		mut progress: impl FnMut(u64),
+	) -> std::io::Result<()> {
+		// Data is read into `fill` while the data in `drain` is written to the sink.
+		let mut fill = vec![0; CHUNK_SIZE];
+		let mut filled = 0;
+		let mut drain = vec![0; CHUNK_SIZE];
+		let mut drain_range = 0..0;
+		let mut received = 0;
+		let mut written = 0;
+
+		while written < n {
+			if drain_range.is_empty() && filled > 0 {
+				std::mem::swap(&mut fill, &mut drain);
+				drain_range = 0..filled;
+				filled = 0;
+			}
+
+			let wanted = (n - received).min((CHUNK_SIZE - filled) as u64) as usize;
+			let result = {
+				let mut read = std::pin::pin!(tokio::time::timeout(stall_timeout, self.read(&mut fill[filled..filled + wanted])));
+				let mut cancelled = std::pin::pin!(cancel.cancelled());
+				std::future::poll_fn(|cx| {
+					while !drain_range.is_empty() {
+						match Pin::new(&mut sink).poll_write(cx, &drain[drain_range.clone()]) {
+							Poll::Ready(Ok(0)) => return Poll::Ready(Err(std::io::ErrorKind::WriteZero.into())),
+							Poll::Ready(Ok(count)) => {
+								drain_range.start += count;
+								written += count as u64;
+								progress(written);
+							},
+							Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+							Poll::Pending => break,
+						}
+					}
+					if wanted == 0 {
+						// Nothing to read: wait until the sink has taken all data.
+						return if drain_range.is_empty() { Poll::Ready(Ok(0)) } else { Poll::Pending };
+					}
+					if cancelled.as_mut().poll(cx).is_ready() {
+						return Poll::Ready(Err(cancelled_error()));
+					}
+					match read.as_mut().poll(cx) {
+						Poll::Ready(Ok(Ok(0))) => Poll::Ready(Err(std::io::ErrorKind::UnexpectedEof.into())),
+						Poll::Ready(Ok(result)) => Poll::Ready(result),
+						Poll::Ready(Err(_)) => Poll::Ready(Err(std::io::Error::new(
+							std::io::ErrorKind::TimedOut,
+							format!("no data received for {stall_timeout:?} after {received} of {n} bytes"),
+						))),
+						Poll::Pending => Poll::Pending,
+					}
+				})
+				.await
+			};
+			match result {
+				Ok(read) => {
+					filled += read;
+					received += read as u64;
+				},
+				Err(e) => {
+					// Pass on the data that was already received before reporting the error.
+					for data in [&drain[drain_range.clone()], &fill[..filled]] {
+						write_all(&mut sink, data).await?;
+						written += data.len() as u64;
+						progress(written);
+					}
+					std::future::poll_fn(|cx| Pin::new(&mut sink).poll_flush(cx)).await?;
+					return Err(e);
+				},
+			}
+		}
+
+		std::future::poll_fn(|cx| Pin::new(&mut sink).poll_flush(cx)).await
+	}
+
+	/// Write all data from `reader` to the serial port.
+	///
+	/// This is meant for large transfers, like uploading a firmware image or a file.
+	/// The data is read from `reader` and written to the serial port in chunks until the reader reports the end of the stream,
+	/// with optional pacing and by default waiting for the data to be transmitted at the end (see [`SendOptions`]).
+	///
+	/// The `progress` callback is called with the total number of bytes written to the serial port after each write.
+	///
+	/// Returns the total number of bytes written.
+	/// Returns an error of kind [`std::io::ErrorKind::InvalidInput`] if the pacing in the options is invalid.
+	///
+	/// With the `cancel` feature, the options can also hold a cancellation token to stop the transfer cleanly.
+	///
+	/// # Example
+	/// ```no_run
+	/// # async fn example() -> std::io::Result<()> {
+	/// use serial2_tokio::{SendOptions, SerialPort, WritePacing};
+	///
+	/// let port = SerialPort::open("/dev/ttyUSB0", 115200)?;
+	/// let image = std::fs::read("firmware.bin")?;
+	/// let mut options = SendOptions::new();
+	/// options.set_pacing(Some(WritePacing::BytesPerSecond(8000)));
+	/// port.send_from(&image[..], &options, |done| {
+	///     println!("{}%", done * 100 / image.len() as u64);
+	/// }).await?;
+	/// # Ok(())
+	/// # }
+	/// ```
+	pub async fn send_from(
+		&self,
+		mut reader: impl AsyncRead + Unpin,
+		options: &SendOptions,
+		mut progress: impl FnMut(u64),
+	) -> std::io::Result<u64> {
+		let cancel = &options.cancel;
+		let pacer = Pacer::new();
+		pacer.set(options.pacing, self.pacing_char_time(options.pacing)?);
+		let mut buffer = vec![0; options.chunk_size];
+		let mut sent = 0;
+
+		loop {
+			let mut read_buf = ReadBuf::new(&mut buffer);
+			let read = cancel.run(std::future::poll_fn(|cx| Pin::new(&mut reader).poll_read(cx, &mut read_buf))).await;
+			let Some(read) = read else {
+				if options.drain {
+					self.drain().await?;
+				}
+				return Err(cancelled_error());
+			};
+			read?;
+			let len = read_buf.filled().len();
+			if len == 0 {
+				break;
+			}
+
+			let mut written = 0;
+			while written < len {
+				let allowed = pacer.ready(len - written).await;
+				let count = self.write(&buffer[written..written + allowed]).await?;
+				pacer.consume(count);
+				written += count;
+				sent += count as u64;
+				progress(sent);
+			}
+		}
+
+		if options.drain {
+			self.drain().await?;
+		}
+		Ok(sent)
+	}
+}
+
+/// Write all data to a sink without requiring the `io-util` feature of `tokio`.
+async fn write_all(sink: &mut (impl AsyncWrite + Unpin), mut data: &[u8]) -> std::io::Result<()> {
+	while !data.is_empty() {
+		let written = std::future::poll_fn(|cx| Pin::new(&mut *sink).poll_write(cx, data)).await?;
+		if written == 0 {
+			return Err(std::io::ErrorKind::WriteZero.into());
+		}
+		data = &data[written..];
+	}
+	Ok(())
+}
@@ -0,0 +1,167 @@
+use std::collections::BinaryHeap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::{oneshot, Notify};
+
+use crate::SerialPort;
+use crate::task::AbortOnDrop;
+
+/// A queue that serializes frames from multiple tasks onto a serial port.
+///
+/// Writing to the same serial port from multiple tasks can cause the data to be interleaved.
+/// Instead, tasks can submit complete frames to a transmit queue.
+/// A background task writes the frames to the serial port one at a time, so frames are never interleaved.
+///
+/// Each frame has a priority: waiting frames with a higher priority are written first.
+/// Frames with the same priority are written in the order they were submitted.
+/// A frame that is already being written is never interrupted.
+///
+/// When the queue is dropped, frames that have not been written yet are discarded.
+///
+/// # Example
+/// ```no_run
+/// # async fn example() -> std::io::Result<()> {
+/// use serial2_tokio::{SerialPort, TxQueue};
+///
+/// let port = SerialPort::open("/dev/ttyUSB0", 115200)?;
+/// let queue = TxQueue::new(port);
+/// let status = queue.submit(b"STATUS\r\n".to_vec(), 0);
+/// let stop = queue.submit(b"STOP\r\n".to_vec(), 10);
+/// stop.await?;
+/// status.await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct TxQueue {
+	port: Arc<SerialPort>,
+	queue: Arc<Queue>,
+	_task: AbortOnDrop,
+}
+
+#[derive(Debug, Default)]
+struct Queue {
+	state: Mutex<QueueState>,
+	notify: Notify,
+}
+
+#[derive(Debug, Default)]
+struct QueueState {
+	frames: BinaryHeap<Frame>,
+	next_sequence: u64,
+}
+
+#[derive(Debug)]
+struct Frame {
+	priority: u8,
+	sequence: u64,
+	data: Vec<u8>,
+	done: oneshot::Sender<std::io::Result<()>>,
+}
+
+impl TxQueue {
+	/// Create a transmit queue for a serial port.
+	///
+	/// This spawns a background task on the current Tokio runtime, so it must be called from within a runtime.
+	pub fn new(port: impl Into<Arc<SerialPort>>) -> Self {
+		let port = port.into();
+		let queue = Arc::new(Queue::default());
+		let task = tokio::spawn(transmit(port.clone(), queue.clone()));
+		Self {
+			port,
+			queue,
+			_task: AbortOnDrop(task.abort_handle()),
+		}
+	}
+
+	/// Get the serial port used by the queue.
+	pub fn port(&self) -> &Arc<SerialPort> {
+		&self.port
+	}
+
+	/// Submit a frame to the queue.
+	///
+	/// The frame is added to the queue immediately.
+	/// The returned future resolves when the whole frame has been written to the serial port,
+	/// or when writing it failed.
+	/// Dropping the returned future does not remove the frame from the queue.
+	///
+	/// Higher values for `priority` are written first.
+	pub fn submit(&self, frame: impl Into<Vec<u8>>, priority: u8) -> impl Future<Output = std::io::Result<()>> + Send + 'static {
+		let (done, result) = oneshot::channel();
+		{
+			let mut state = self.queue.state.lock().unwrap_or_else(|e| e.into_inner());
+			let sequence = state.next_sequence;
+			state.next_sequence += 1;
+			state.frames.push(Frame {
+				priority,
+				sequence,
+				data: frame.into(),
+				done,
+			});
+		}
+		self.queue.notify.notify_one();
+
+		async move {
+			match result.await {
+				Ok(result) => result,
+				Err(_) => Err(std::io::Error::new(std::io::ErrorKind::BrokenPipe, "transmit queue was dropped")),
+			}
+		}
+	}
+
+	/// Submit a frame to the queue and wait until it has been written.
+	///
+	/// This is equivalent to awaiting the future returned by [`Self::submit()`].
+	pub async fn send(&self, frame: impl Into<Vec<u8>>, priority: u8) -> std::io::Result<()> {
+		self.submit(frame, priority).await
+	}
+
+	/// Get the number of frames that are waiting to be written.
+	///
+	/// This does not include a frame that is currently being written.
+	pub fn len(&self) -> usize {
+		self.queue.state.lock().unwrap_or_else(|e| e.into_inner()).frames.len()
+	}
+
+	/// Check if no frames are waiting to be written.
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+}
+
+/// Write frames from the queue to the serial port.
+async fn transmit(port: Arc<SerialPort>, queue: Arc<Queue>) {
+	loop {
+		let frame = queue.state.lock().unwrap_or_else(|e| e.into_inner()).frames.pop();
+		let Some(frame) = frame else {
+			queue.notify.notified().await;
+			continue;
+		};
+		let result = port.write_all(&frame.data).await;
+		frame.done.send(result).ok();
+	}
+}
+
+impl PartialEq for Frame {
+	fn eq(&self, other: &Self) -> bool {
+		self.cmp(other) == std::cmp::Ordering::Equal
+	}
+}
+
+impl Eq for Frame {}
+
+impl PartialOrd for Frame {
+	fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+impl Ord for Frame {
+	fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+		// The heap pops the greatest frame first: highest priority, then lowest sequence number.
+		self.priority.cmp(&other.priority)
+			.then_with(|| other.sequence.cmp(&self.sequence))
+	}
+}
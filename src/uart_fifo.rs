@@ -0,0 +1,121 @@
+use crate::SerialPort;
+
+impl SerialPort {
+	/// Get the receive FIFO trigger level of the UART.
+	///
+	/// The UART raises an interrupt when this many bytes are waiting in its receive FIFO.
+	/// A low trigger level reduces the latency for received data, at the cost of more interrupts.
+	///
+	/// This is only supported by drivers that expose the `rx_trig_bytes` attribute in sysfs,
+	/// such as the driver for 16550 compatible UARTs.
+	/// For other serial ports, this returns an error of kind [`std::io::ErrorKind::Unsupported`].
+	#[cfg(any(feature = "doc", target_os = "linux"))]
+	#[cfg_attr(feature = "doc-cfg", doc(cfg(target_os = "linux")))]
+	pub fn get_rx_fifo_trigger(&self) -> std::io::Result<usize> {
+		#[cfg(target_os = "linux")] {
+			sys::read_attribute(self, "rx_trig_bytes")
+		}
+		#[cfg(not(target_os = "linux"))] {
+			unreachable!("this code is only enabled on Linux or during documentation generation")
+		}
+	}
+
+	/// Set the receive FIFO trigger level of the UART.
+	///
+	/// The driver rounds the value down to a trigger level supported by the hardware,
+	/// so use [`Self::get_rx_fifo_trigger()`] to check the applied trigger level.
+	/// The trigger level is usually reset when the serial port is closed by all processes.
+	///
+	/// Changing the trigger level normally requires write access to the sysfs attributes of the serial port,
+	/// which is usually reserved for the root user.
+	///
+	/// See [`Self::get_rx_fifo_trigger()`] for more information.
+	///
+	/// # Example
+	/// ```no_run
+	/// # fn example() -> std::io::Result<()> {
+	/// use serial2_tokio::SerialPort;
+	///
+	/// let port = SerialPort::open("/dev/ttyS0", 9600)?;
+	/// // Wake up on every received byte, for the lowest latency.
+	/// port.set_rx_fifo_trigger(1)?;
+	/// println!("trigger level: {}", port.get_rx_fifo_trigger()?);
+	/// # Ok(())
+	/// # }
+	/// ```
+	#[cfg(any(feature = "doc", target_os = "linux"))]
+	#[cfg_attr(feature = "doc-cfg", doc(cfg(target_os = "linux")))]
+	pub fn set_rx_fifo_trigger(&self, bytes: usize) -> std::io::Result<()> {
+		#[cfg(target_os = "linux")] {
+			sys::write_attribute(self, "rx_trig_bytes", bytes)
+		}
+		#[cfg(not(target_os = "linux"))] {
+			let _ = bytes;
+			unreachable!("this code is only enabled on Linux or during documentation generation")
+		}
+	}
+
+	/// Get the size of the transmit FIFO of the UART, as reported by the driver.
+	///
+	/// Returns an error of kind [`std::io::ErrorKind::Unsupported`] if the driver does not report the FIFO size.
+	#[cfg(any(feature = "doc", target_os = "linux"))]
+	#[cfg_attr(feature = "doc-cfg", doc(cfg(target_os = "linux")))]
+	pub fn get_tx_fifo_size(&self) -> std::io::Result<usize> {
+		#[cfg(target_os = "linux")] {
+			sys::read_attribute(self, "xmit_fifo_size")
+		}
+		#[cfg(not(target_os = "linux"))] {
+			unreachable!("this code is only enabled on Linux or during documentation generation")
+		}
+	}
+}
+
+#[cfg(target_os = "linux")]
+mod sys {
+	use std::os::fd::AsRawFd;
+	use std::path::PathBuf;
+
+	use crate::SerialPort;
+
+	/// Get the sysfs directory of the TTY device.
+	fn sysfs_dir(port: &SerialPort) -> std::io::Result<PathBuf> {
+		let fd = port.inner.with_raw(|raw| raw.as_raw_fd());
+		let (major, minor) = unsafe {
+			let mut stat: libc::stat = std::mem::zeroed();
+			if libc::fstat(fd, &mut stat) != 0 {
+				return Err(std::io::Error::last_os_error());
+			}
+			(libc::major(stat.st_rdev), libc::minor(stat.st_rdev))
+		};
+		Ok(PathBuf::from(format!("/sys/dev/char/{major}:{minor}")))
+	}
+
+	pub fn read_attribute(port: &SerialPort, name: &str) -> std::io::Result<usize> {
+		let path = sysfs_dir(port)?.join(name);
+		let value = std::fs::read_to_string(path).map_err(|e| match e.raw_os_error() {
+			// Drivers without FIFO support reject reading the attribute.
+			Some(libc::EINVAL | libc::EOPNOTSUPP) => unsupported(name),
+			_ => map_not_found(e, name),
+		})?;
+		value.trim().parse().map_err(|_| {
+			std::io::Error::new(std::io::ErrorKind::InvalidData, format!("invalid value for {name} in sysfs: {:?}", value.trim()))
+		})
+	}
+
+	pub fn write_attribute(port: &SerialPort, name: &str, value: usize) -> std::io::Result<()> {
+		let path = sysfs_dir(port)?.join(name);
+		std::fs::write(path, value.to_string()).map_err(|e| map_not_found(e, name))
+	}
+
+	fn map_not_found(error: std::io::Error, name: &str) -> std::io::Error {
+		if error.kind() == std::io::ErrorKind::NotFound {
+			unsupported(name)
+		} else {
+			error
+		}
+	}
+
+	fn unsupported(name: &str) -> std::io::Error {
+		std::io::Error::new(std::io::ErrorKind::Unsupported, format!("the serial port driver does not support {name}"))
+	}
+}
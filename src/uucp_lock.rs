@@ -0,0 +1,157 @@
+//! UUCP style lock files, as used by minicom, pppd and other classic serial port tools.
+//!
+//! A lock file is named `LCK..<device name>` and contains the process ID of the owner as ten right aligned ASCII digits and a newline.
+
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+
+/// The directory where the lock files are created.
+const LOCK_DIR: &str = "/var/lock";
+
+/// A lock file that is removed when dropped.
+#[derive(Debug)]
+pub struct UucpLock {
+	path: PathBuf,
+}
+
+impl UucpLock {
+	/// Create the lock file for a device.
+	///
+	/// The lock file is written under a temporary name first, and then linked to the real name.
+	/// Creating the link fails if the lock file already exists, so the lock file is never observed without a process ID.
+	///
+	/// A stale lock file of a process that no longer exists is removed first.
+	/// Returns an error of kind [`std::io::ErrorKind::ResourceBusy`] if the device is locked by a running process.
+	pub fn acquire(device: &Path) -> std::io::Result<Self> {
+		let device = std::fs::canonicalize(device)?;
+		let path = Path::new(LOCK_DIR).join(lock_file_name(&device)?);
+		let temp = TempFile::new(format!("{:>10}\n", std::process::id()).as_bytes())?;
+
+		// Try twice: the second attempt follows the removal of a stale lock file.
+		for _ in 0..2 {
+			match std::fs::hard_link(&temp.0, &path) {
+				Ok(()) => return Ok(Self { path }),
+				Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+					let Some((owner, inode)) = read_owner(&path)? else {
+						continue;
+					};
+					if let Some(pid) = owner {
+						return Err(std::io::Error::new(
+							std::io::ErrorKind::ResourceBusy,
+							format!("{} is locked by process {pid} (lock file {})", device.display(), path.display()),
+						));
+					}
+					remove_stale(&path, inode)?;
+				},
+				Err(e) => return Err(e),
+			}
+		}
+		Err(std::io::Error::new(
+			std::io::ErrorKind::ResourceBusy,
+			format!("failed to create lock file {}", path.display()),
+		))
+	}
+}
+
+impl Drop for UucpLock {
+	fn drop(&mut self) {
+		std::fs::remove_file(&self.path).ok();
+	}
+}
+
+/// Get the name of the lock file for a device.
+///
+/// Devices in a subdirectory of `/dev` get the path relative to `/dev` with slashes replaced by underscores,
+/// like `LCK..pts_3` for `/dev/pts/3`.
+fn lock_file_name(device: &Path) -> std::io::Result<std::ffi::OsString> {
+	use std::os::unix::ffi::{OsStrExt, OsStringExt};
+	let name = match device.strip_prefix("/dev") {
+		Ok(relative) => relative.as_os_str(),
+		Err(_) => device.file_name().unwrap_or_default(),
+	};
+	if name.is_empty() {
+		return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "the device path has no file name"));
+	}
+	let mut file_name = b"LCK..".to_vec();
+	file_name.extend(name.as_bytes().iter().map(|&byte| if byte == b'/' { b'_' } else { byte }));
+	Ok(std::ffi::OsString::from_vec(file_name))
+}
+
+/// Read the process ID from a lock file, and check if the process is still running.
+///
+/// Returns `None` if the lock file does not exist.
+/// Otherwise, returns the process ID of the owner, or `None` if the lock file is stale, together with the inode number of the lock file.
+/// Some old programs write the process ID as a binary integer, which is supported too.
+fn read_owner(path: &Path) -> std::io::Result<Option<(Option<u32>, u64)>> {
+	let (data, inode) = match std::fs::File::open(path) {
+		Ok(mut file) => {
+			let inode = file.metadata()?.ino();
+			let mut data = Vec::new();
+			std::io::Read::read_to_end(&mut file, &mut data)?;
+			(data, inode)
+		},
+		Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+		Err(e) => return Err(e),
+	};
+	let pid = match std::str::from_utf8(&data).ok().and_then(|text| text.trim().parse::<u32>().ok()) {
+		Some(pid) => pid,
+		None if data.len() == 4 => u32::from_ne_bytes([data[0], data[1], data[2], data[3]]),
+		None => return Ok(Some((None, inode))),
+	};
+	let Ok(pid_t) = libc::pid_t::try_from(pid) else {
+		return Ok(Some((None, inode)));
+	};
+	if pid_t <= 0 {
+		return Ok(Some((None, inode)));
+	}
+	// Sending signal 0 checks if the process exists without affecting it.
+	// EPERM means that the process exists, but belongs to another user.
+	let alive = unsafe { libc::kill(pid_t, 0) == 0 } || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM);
+	Ok(Some((alive.then_some(pid), inode)))
+}
+
+/// Remove a stale lock file, unless another process replaced it since it was checked.
+///
+/// The lock file is first renamed to a temporary name, which succeeds for only one of the processes that found the same stale lock file.
+/// If the renamed file is not the stale lock file that was checked, it is a new lock file of another process, and it is linked back.
+fn remove_stale(path: &Path, stale_inode: u64) -> std::io::Result<()> {
+	let aside = temp_path();
+	match std::fs::rename(path, &aside) {
+		Ok(()) => (),
+		Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+		Err(e) => return Err(e),
+	}
+	let aside = TempFile(aside);
+	if std::fs::symlink_metadata(&aside.0)?.ino() != stale_inode {
+		std::fs::hard_link(&aside.0, path).ok();
+	}
+	Ok(())
+}
+
+/// A temporary file in the lock directory that is removed when dropped.
+struct TempFile(PathBuf);
+
+impl TempFile {
+	/// Create a temporary file with the given contents.
+	fn new(contents: &[u8]) -> std::io::Result<Self> {
+		let file = Self(temp_path());
+		std::fs::write(&file.0, contents)?;
+		Ok(file)
+	}
+}
+
+impl Drop for TempFile {
+	fn drop(&mut self) {
+		std::fs::remove_file(&self.0).ok();
+	}
+}
+
+/// Get a unique name for a temporary file in the lock directory.
+///
+/// The name contains the process ID and a counter, so it is unique across processes and across threads of this process.
+fn temp_path() -> PathBuf {
+	static COUNTER: AtomicU32 = AtomicU32::new(0);
+	let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+	Path::new(LOCK_DIR).join(format!("LTMP.{}.{count}", std::process::id()))
+}
@@ -0,0 +1,287 @@
+use std::path::{Path, PathBuf};
+
+use crate::{IntoSettings, KeepSettings, SerialPort};
+
+/// A pair of connected virtual serial ports, created by the com0com driver.
+///
+/// Data written to one port of the pair is received on the other port, like with a null-modem cable.
+/// Returned by [`SerialPort::find_virtual_pairs()`].
+#[cfg(any(feature = "doc", windows))]
+#[cfg_attr(feature = "doc-cfg", doc(cfg(windows)))]
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct VirtualPortPair {
+	/// The number of the pair in the driver.
+	///
+	/// This is the `n` in the names `CNCAn` and `CNCBn` that com0com uses for the ports of the pair.
+	pub index: u32,
+
+	/// The name of the first port of the pair, like `COM10` or `CNCA0`.
+	pub a: PathBuf,
+
+	/// The name of the second port of the pair, like `COM11` or `CNCB0`.
+	pub b: PathBuf,
+}
+
+#[cfg(any(feature = "doc", windows))]
+impl VirtualPortPair {
+	/// Get the name of the other port of the pair.
+	///
+	/// The name is compared case insensitively, and may include the `\\.\` prefix of the win32 device namespace.
+	/// Returns `None` if `name` is not one of the ports of this pair.
+	pub fn partner(&self, name: &str) -> Option<&Path> {
+		let name = name.strip_prefix(r"\\.\").unwrap_or(name);
+		let matches = |path: &Path| path.to_str().is_some_and(|path| path.eq_ignore_ascii_case(name));
+		if matches(&self.a) {
+			Some(&self.b)
+		} else if matches(&self.b) {
+			Some(&self.a)
+		} else {
+			None
+		}
+	}
+}
+
+impl SerialPort {
+	/// Find the pairs of virtual serial ports created by the com0com driver.
+	///
+	/// The pairs are read from the registry, so they are found regardless of the COM port numbers they were given.
+	/// This allows tests on Windows to use a pair of connected ports without hard-coding their names.
+	///
+	/// The pairs are sorted by their index in the driver.
+	/// Returns an empty list if com0com is not installed.
+	///
+	/// # Example
+	/// ```no_run
+	/// # fn example() -> std::io::Result<()> {
+	/// use serial2_tokio::SerialPort;
+	///
+	/// for pair in SerialPort::find_virtual_pairs()? {
+	///     println!("{} <-> {}", pair.a.display(), pair.b.display());
+	/// }
+	/// # Ok(())
+	/// # }
+	/// ```
+	#[cfg(any(feature = "doc", windows))]
+	#[cfg_attr(feature = "doc-cfg", doc(cfg(windows)))]
+	pub fn find_virtual_pairs() -> std::io::Result<Vec<VirtualPortPair>> {
+		#[cfg(windows)] {
+			sys::find_virtual_pairs()
+		}
+		#[cfg(not(windows))] {
+			unreachable!("this code is only enabled on Windows or during documentation generation")
+		}
+	}
+
+	/// Open both ports of a virtual serial port pair by the name of one of them.
+	///
+	/// The port with the given name is opened with `settings`,
+	/// and the other port of the pair is opened with the same configuration.
+	/// The ports are returned in that order.
+	///
+	/// Returns an error of kind [`std::io::ErrorKind::NotFound`] if the port is not part of a pair found by [`Self::find_virtual_pairs()`].
+	///
+	/// # Example
+	/// ```no_run
+	/// # async fn example() -> std::io::Result<()> {
+	/// use serial2_tokio::SerialPort;
+	///
+	/// let (a, b) = SerialPort::open_pair_by_name("CNCA0", 115200)?;
+	/// a.write_all(b"ping").await?;
+	/// let mut buffer = [0; 4];
+	/// let read = b.read(&mut buffer).await?;
+	/// assert_eq!(&buffer[..read], b"ping");
+	/// # Ok(())
+	/// # }
+	/// ```
+	#[cfg(any(feature = "doc", windows))]
+	#[cfg_attr(feature = "doc-cfg", doc(cfg(windows)))]
+	pub fn open_pair_by_name(name: &str, settings: impl IntoSettings) -> std::io::Result<(Self, Self)> {
+		let pairs = Self::find_virtual_pairs()?;
+		let Some(partner) = pairs.iter().find_map(|pair| pair.partner(name)) else {
+			return Err(std::io::Error::new(
+				std::io::ErrorKind::NotFound,
+				format!("{name} is not part of a virtual serial port pair"),
+			));
+		};
+		let port = Self::open(name, settings)?;
+		let partner = Self::open(partner, KeepSettings)?;
+		partner.set_configuration(&port.get_configuration()?)?;
+		Ok((port, partner))
+	}
+}
+
+#[cfg(windows)]
+mod sys {
+	use std::collections::BTreeMap;
+	use std::ffi::OsString;
+	use std::os::windows::ffi::OsStringExt;
+
+	use winapi::shared::minwindef::{DWORD, HKEY};
+	use winapi::shared::winerror::{ERROR_FILE_NOT_FOUND, ERROR_MORE_DATA, ERROR_NO_MORE_ITEMS};
+	use winapi::um::winnt::{KEY_READ, REG_SZ};
+	use winapi::um::winreg::{RegCloseKey, RegEnumKeyExW, RegEnumValueW, RegOpenKeyExW, HKEY_LOCAL_MACHINE};
+
+	use super::VirtualPortPair;
+
+	/// The registry key with the kernel device and COM port name of all serial ports.
+	const SERIALCOMM_KEY: &str = r"HARDWARE\DEVICEMAP\SERIALCOMM";
+
+	/// The registry key with a subkey for each port created by com0com.
+	const COM0COM_PARAMETERS_KEY: &str = r"SYSTEM\CurrentControlSet\Services\com0com\Parameters";
+
+	/// The prefix of the kernel device names of com0com ports.
+	///
+	/// Port `CNCAn` is named `\Device\com0com1n` and port `CNCBn` is named `\Device\com0com2n`.
+	const COM0COM_DEVICE_PREFIX: &str = r"\Device\com0com";
+
+	/// An open registry key that is closed when dropped.
+	struct Key(HKEY);
+
+	impl Drop for Key {
+		fn drop(&mut self) {
+			unsafe {
+				RegCloseKey(self.0);
+			}
+		}
+	}
+
+	impl Key {
+		/// Open a key in `HKEY_LOCAL_MACHINE` for reading.
+		///
+		/// Returns `None` if the key does not exist.
+		fn open(path: &str) -> std::io::Result<Option<Self>> {
+			let path = to_wide(path);
+			let mut key = std::ptr::null_mut();
+			let status = unsafe { RegOpenKeyExW(HKEY_LOCAL_MACHINE, path.as_ptr(), 0, KEY_READ, &mut key) };
+			match status as DWORD {
+				0 => Ok(Some(Self(key))),
+				ERROR_FILE_NOT_FOUND => Ok(None),
+				_ => Err(std::io::Error::from_raw_os_error(status)),
+			}
+		}
+
+		/// Get the names of all subkeys.
+		fn subkeys(&self) -> std::io::Result<Vec<String>> {
+			let mut names = Vec::new();
+			for index in 0.. {
+				let mut name = [0u16; 256];
+				let mut name_len = name.len() as DWORD;
+				let status = unsafe {
+					RegEnumKeyExW(
+						self.0,
+						index,
+						name.as_mut_ptr(),
+						&mut name_len,
+						std::ptr::null_mut(),
+						std::ptr::null_mut(),
+						std::ptr::null_mut(),
+						std::ptr::null_mut(),
+					)
+				};
+				match status as DWORD {
+					0 => names.extend(from_wide(&name[..name_len as usize])),
+					ERROR_NO_MORE_ITEMS => break,
+					ERROR_MORE_DATA => continue,
+					_ => return Err(std::io::Error::from_raw_os_error(status)),
+				}
+			}
+			Ok(names)
+		}
+
+		/// Get the names and data of all string values.
+		fn string_values(&self) -> std::io::Result<Vec<(String, String)>> {
+			let mut values = Vec::new();
+			for index in 0.. {
+				let mut name = [0u16; 256];
+				let mut name_len = name.len() as DWORD;
+				let mut data = [0u16; 256];
+				let mut data_size = std::mem::size_of_val(&data) as DWORD;
+				let mut kind = 0;
+				let status = unsafe {
+					RegEnumValueW(
+						self.0,
+						index,
+						name.as_mut_ptr(),
+						&mut name_len,
+						std::ptr::null_mut(),
+						&mut kind,
+						data.as_mut_ptr().cast(),
+						&mut data_size,
+					)
+				};
+				match status as DWORD {
+					0 if kind == REG_SZ => {
+						let name = from_wide(&name[..name_len as usize]);
+						let data = from_wide(&data[..(data_size as usize / 2).min(data.len())]);
+						values.extend(name.zip(data));
+					},
+					0 | ERROR_MORE_DATA => continue,
+					ERROR_NO_MORE_ITEMS => break,
+					_ => return Err(std::io::Error::from_raw_os_error(status)),
+				}
+			}
+			Ok(values)
+		}
+	}
+
+	pub fn find_virtual_pairs() -> std::io::Result<Vec<VirtualPortPair>> {
+		// The names of side A and side B of each pair, by pair index.
+		let mut pairs: BTreeMap<u32, [Option<String>; 2]> = BTreeMap::new();
+
+		// Every port has a subkey in the driver parameters, named after the port (`CNCAn` or `CNCBn`).
+		// Ports that are not renamed can be opened with that name.
+		if let Some(key) = Key::open(COM0COM_PARAMETERS_KEY)? {
+			for name in key.subkeys()? {
+				let Some((side, index)) = parse_side(&name, "CNCA", "CNCB") else {
+					continue;
+				};
+				pairs.entry(index).or_default()[side].get_or_insert(name);
+			}
+		}
+
+		// Ports that were given a different name, like `COM10`, are listed with that name in the serial device map.
+		if let Some(key) = Key::open(SERIALCOMM_KEY)? {
+			for (device, port) in key.string_values()? {
+				let Some((side, index)) = device.strip_prefix(COM0COM_DEVICE_PREFIX).and_then(|rest| parse_side(rest, "1", "2")) else {
+					continue;
+				};
+				pairs.entry(index).or_default()[side] = Some(port);
+			}
+		}
+
+		Ok(pairs
+			.into_iter()
+			.filter_map(|(index, [a, b])| Some(VirtualPortPair {
+				index,
+				a: a?.into(),
+				b: b?.into(),
+			}))
+			.collect())
+	}
+
+	/// Parse the side and the index of a port from a name that consists of a prefix for the side and the index.
+	fn parse_side(name: &str, prefix_a: &str, prefix_b: &str) -> Option<(usize, u32)> {
+		let (side, index) = if let Some(index) = name.strip_prefix(prefix_a) {
+			(0, index)
+		} else {
+			(1, name.strip_prefix(prefix_b)?)
+		};
+		if index.is_empty() || !index.bytes().all(|byte| byte.is_ascii_digit()) {
+			return None;
+		}
+		Some((side, index.parse().ok()?))
+	}
+
+	/// Convert a string to a nul terminated wide string.
+	fn to_wide(value: &str) -> Vec<u16> {
+		value.encode_utf16().chain(Some(0)).collect()
+	}
+
+	/// Convert a wide string to a string, stopping at the first nul character.
+	fn from_wide(value: &[u16]) -> Option<String> {
+		let end = value.iter().position(|&c| c == 0).unwrap_or(value.len());
+		let value = OsString::from_wide(&value[..end]).into_string().ok()?;
+		(!value.is_empty()).then_some(value)
+	}
+}
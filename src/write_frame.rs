@@ -0,0 +1,58 @@
+use std::io::IoSlice;
+
+use crate::SerialPort;
+
+impl SerialPort {
+	/// Write a frame made of multiple buffers, without interleaving it with frames from other tasks.
+	///
+	/// This writes all buffers in order, using vectored writes where possible,
+	/// and only returns when the whole frame has been handed to the OS.
+	///
+	/// Concurrent calls to this function on the same [`SerialPort`] are serialized,
+	/// so each frame is written contiguously even if multiple tasks write frames at the same time.
+	/// Writes that do not use this function are not blocked,
+	/// so all tasks that write to the serial port should use this function to get the guarantee.
+	///
+	/// If the returned future is dropped before it completes, only part of the frame may have been written.
+	/// Use [`Self::write_tracked()`] if you need to know how much.
+	///
+	/// # Example
+	/// ```no_run
+	/// # async fn example() -> std::io::Result<()> {
+	/// use serial2_tokio::SerialPort;
+	/// use std::io::IoSlice;
+	///
+	/// let port = SerialPort::open("/dev/ttyUSB0", 115200)?;
+	/// let payload = b"hello";
+	/// let header = [0x02, payload.len() as u8];
+	/// let trailer = [0x03];
+	/// port.write_frame_vectored(&[IoSlice::new(&header), IoSlice::new(payload), IoSlice::new(&trailer)]).await?;
+	/// # Ok(())
+	/// # }
+	/// ```
+	pub async fn write_frame_vectored(&self, bufs: &[IoSlice<'_>]) -> std::io::Result<()> {
+		let _lock = self.frame_lock.lock().await;
+		let mut index = 0;
+		let mut offset = 0;
+		loop {
+			// Skip the buffers that have been written completely, including empty buffers.
+			while index < bufs.len() && offset >= bufs[index].len() {
+				offset -= bufs[index].len();
+				index += 1;
+			}
+			if index == bufs.len() {
+				return Ok(());
+			}
+
+			let written = if offset == 0 {
+				self.write_vectored(&bufs[index..]).await?
+			} else {
+				self.write(&bufs[index][offset..]).await?
+			};
+			if written == 0 {
+				return Err(std::io::ErrorKind::WriteZero.into());
+			}
+			offset += written;
+		}
+	}
+}
@@ -0,0 +1,70 @@
+use crate::SerialPort;
+
+/// Tracks how much of a buffer has been written by [`SerialPort::write_tracked()`].
+///
+/// The ticket is updated after every partial write,
+/// so it stays accurate when the write is cancelled or times out.
+/// Pass the same ticket and buffer again to resume the write where it stopped.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct WriteTicket {
+	written: usize,
+}
+
+impl WriteTicket {
+	/// Create a new ticket for a write that has not started yet.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Get the number of bytes that have been handed to the OS so far.
+	///
+	/// Like for [`SerialPort::write()`], this does not mean that the bytes have been transmitted.
+	/// Use [`SerialPort::drain()`] to wait until the OS has transmitted them.
+	pub fn written(&self) -> usize {
+		self.written
+	}
+
+	/// Reset the ticket to use it for a new buffer.
+	pub fn reset(&mut self) {
+		self.written = 0;
+	}
+}
+
+impl SerialPort {
+	/// Write all bytes to the serial port, and keep track of the progress in a [`WriteTicket`].
+	///
+	/// This works like [`Self::write_all()`], but it starts at [`WriteTicket::written()`] instead of at the start of `buf`,
+	/// and it updates the ticket after each partial write.
+	/// If the returned future is dropped because it was cancelled or timed out,
+	/// the ticket tells you exactly how many bytes of `buf` were accepted by the OS.
+	/// This allows you to resume the transfer later, or to account for partially sent messages in a protocol.
+	///
+	/// On Linux with the `io-uring` feature enabled, a write that is in progress when the future is dropped may still complete.
+	/// In that case, the ticket does not include the bytes of that last write.
+	///
+	/// # Panics
+	/// This function panics if the ticket is beyond the end of `buf`.
+	///
+	/// # Example
+	/// ```no_run
+	/// # async fn example() -> std::io::Result<()> {
+	/// use serial2_tokio::{SerialPort, WriteTicket};
+	/// use std::time::Duration;
+	///
+	/// let port = SerialPort::open("/dev/ttyUSB0", 115200)?;
+	/// let data = vec![0x55; 4096];
+	/// let mut ticket = WriteTicket::new();
+	/// while tokio::time::timeout(Duration::from_millis(100), port.write_tracked(&data, &mut ticket)).await.is_err() {
+	///     println!("timeout after writing {} bytes, retrying", ticket.written());
+	/// }
+	/// # Ok(())
+	/// # }
+	/// ```
+	pub async fn write_tracked(&self, buf: &[u8], ticket: &mut WriteTicket) -> std::io::Result<()> {
+		assert!(ticket.written <= buf.len(), "the write ticket is beyond the end of the buffer");
+		while ticket.written < buf.len() {
+			ticket.written += self.write(&buf[ticket.written..]).await?;
+		}
+		Ok(())
+	}
+}
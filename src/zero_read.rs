@@ -0,0 +1,82 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::time::Duration;
+
+use crate::SerialPort;
+
+/// How long to wait before reading again after a read returned zero bytes, with [`ZeroReadPolicy::Retry`].
+pub(crate) const RETRY_DELAY: Duration = Duration::from_millis(20);
+
+/// What to do when the OS returns zero bytes from a read.
+///
+/// Used with [`SerialPort::set_zero_read_policy()`].
+#[cfg_attr(not(feature = "doc"), allow(rustdoc::broken_intra_doc_links))]
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub enum ZeroReadPolicy {
+	/// Return zero bytes, which signals the end of the stream.
+	///
+	/// This is the default, and passes on the behavior of the OS unchanged.
+	#[default]
+	Eof,
+
+	/// Return an error of kind [`std::io::ErrorKind::UnexpectedEof`].
+	///
+	/// This turns a disconnected device into an error on all platforms,
+	/// so that [`AsyncReadExt::read_to_end()`][tokio::io::AsyncReadExt::read_to_end] and codecs do not mistake it for a normal end of the stream.
+	Error,
+
+	/// Wait a short time and read again.
+	///
+	/// The read only completes when data is received or an error occurs.
+	/// This is useful for devices that return zero bytes when there is no data, but not for detecting a disconnected device.
+	Retry,
+}
+
+impl SerialPort {
+	/// Set what the read functions do when the OS returns zero bytes.
+	///
+	/// Depending on the platform and the driver, a disconnected USB serial adapter can result in an error or in reads that return zero bytes forever.
+	/// For [`AsyncRead`][tokio::io::AsyncRead] consumers, zero bytes means the end of the stream,
+	/// which may not be what you want for a serial port.
+	/// The policy applies to all read functions, including [`AsyncRead`][tokio::io::AsyncRead].
+	///
+	/// Reads into an empty buffer always return zero bytes, regardless of the policy.
+	///
+	/// # Example
+	/// ```no_run
+	/// # async fn example() -> std::io::Result<()> {
+	/// use serial2_tokio::{SerialPort, ZeroReadPolicy};
+	/// use tokio::io::AsyncReadExt;
+	///
+	/// let mut port = SerialPort::open("/dev/ttyUSB0", 115200)?;
+	/// port.set_zero_read_policy(ZeroReadPolicy::Error);
+	/// let mut data = Vec::new();
+	/// if let Err(e) = port.read_to_end(&mut data).await {
+	///     eprintln!("read {} bytes before the error: {e}", data.len());
+	/// }
+	/// # Ok(())
+	/// # }
+	/// ```
+	pub fn set_zero_read_policy(&self, policy: ZeroReadPolicy) {
+		self.zero_read_policy.0.store(policy as u8, Ordering::Relaxed);
+	}
+
+	/// Get what the read functions do when the OS returns zero bytes.
+	pub fn get_zero_read_policy(&self) -> ZeroReadPolicy {
+		match self.zero_read_policy.0.load(Ordering::Relaxed) {
+			x if x == ZeroReadPolicy::Error as u8 => ZeroReadPolicy::Error,
+			x if x == ZeroReadPolicy::Retry as u8 => ZeroReadPolicy::Retry,
+			_ => ZeroReadPolicy::Eof,
+		}
+	}
+}
+
+/// The policy for reads that return zero bytes.
+///
+/// Stored as an atomic, so the read functions can check it without taking a lock.
+#[derive(Debug, Default)]
+pub(crate) struct ZeroReadState(AtomicU8);
+
+/// Create the error returned for reads that return zero bytes with [`ZeroReadPolicy::Error`].
+pub(crate) fn zero_read_error() -> std::io::Error {
+	std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "read returned zero bytes, the device may have been disconnected")
+}
@@ -0,0 +1,174 @@
+//! Tests for the TCP bridge, using a pseudo terminal pair as serial port.
+
+#![cfg(all(unix, feature = "unix", feature = "bridge"))]
+
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use serial2_tokio::bridge::{Bridge, BridgeCounters, ClientPolicy};
+use serial2_tokio::SerialPort;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Start a bridge for one side of a pseudo terminal pair.
+///
+/// Returns the address of the bridge, its counters and the other side of the pair.
+async fn start_bridge(policy: ClientPolicy) -> (SocketAddr, Arc<BridgeCounters>, SerialPort) {
+	let (port, peer) = SerialPort::pair().unwrap();
+	let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+	let address = listener.local_addr().unwrap();
+	let bridge = Bridge::new(port).client_policy(policy);
+	let counters = bridge.counters();
+	tokio::spawn(async move { bridge.serve(listener).await });
+	(address, counters, peer)
+}
+
+/// Connect a client and wait until the bridge forwards its data to the serial port.
+async fn connect(address: SocketAddr, peer: &SerialPort) -> TcpStream {
+	let mut client = TcpStream::connect(address).await.unwrap();
+	client.write_all(b"ping").await.unwrap();
+	let mut buffer = [0; 4];
+	tokio::time::timeout(Duration::from_secs(5), read_exact(peer, &mut buffer)).await.unwrap().unwrap();
+	assert_eq!(&buffer, b"ping");
+	client
+}
+
+/// Read exactly enough bytes from a serial port to fill the buffer.
+async fn read_exact(port: &SerialPort, mut buffer: &mut [u8]) -> std::io::Result<()> {
+	while !buffer.is_empty() {
+		let read = port.read(buffer).await?;
+		buffer = &mut buffer[read..];
+	}
+	Ok(())
+}
+
+#[tokio::test]
+async fn exclusive_rejects_second_client() {
+	let (address, _counters, peer) = start_bridge(ClientPolicy::Exclusive).await;
+	let mut first = connect(address, &peer).await;
+
+	// The second connection is closed without receiving any data.
+	let mut second = TcpStream::connect(address).await.unwrap();
+	let mut buffer = [0; 16];
+	let read = tokio::time::timeout(Duration::from_secs(5), second.read(&mut buffer)).await.unwrap();
+	assert!(matches!(read, Ok(0) | Err(_)));
+
+	// The first client keeps working.
+	peer.write_all(b"hello").await.unwrap();
+	let mut buffer = [0; 5];
+	tokio::time::timeout(Duration::from_secs(5), first.read_exact(&mut buffer)).await.unwrap().unwrap();
+	assert_eq!(&buffer, b"hello");
+}
+
+#[tokio::test]
+async fn exclusive_accepts_client_after_disconnect() {
+	let (address, _counters, peer) = start_bridge(ClientPolicy::Exclusive).await;
+	drop(connect(address, &peer).await);
+
+	// The bridge notices the disconnect asynchronously, so retry until a new client is accepted.
+	let mut client = tokio::time::timeout(Duration::from_secs(5), async {
+		loop {
+			let mut client = TcpStream::connect(address).await.unwrap();
+			client.write_all(b"ping").await.unwrap();
+			let mut buffer = [0; 4];
+			match tokio::time::timeout(Duration::from_millis(200), read_exact(&peer, &mut buffer)).await {
+				Ok(read) => {
+					read.unwrap();
+					break client;
+				},
+				Err(_) => continue,
+			}
+		}
+	}).await.unwrap();
+
+	peer.write_all(b"hello").await.unwrap();
+	let mut buffer = [0; 5];
+	tokio::time::timeout(Duration::from_secs(5), client.read_exact(&mut buffer)).await.unwrap().unwrap();
+	assert_eq!(&buffer, b"hello");
+}
+
+#[tokio::test]
+async fn broadcast_sends_data_to_all_clients() {
+	let (address, counters, peer) = start_bridge(ClientPolicy::Broadcast).await;
+	let mut first = connect(address, &peer).await;
+	let mut second = connect(address, &peer).await;
+
+	peer.write_all(b"hello").await.unwrap();
+	for client in [&mut first, &mut second] {
+		let mut buffer = [0; 5];
+		tokio::time::timeout(Duration::from_secs(5), client.read_exact(&mut buffer)).await.unwrap().unwrap();
+		assert_eq!(&buffer, b"hello");
+	}
+	assert_eq!(counters.serial_to_network(), 5);
+	assert_eq!(counters.network_to_serial(), 8);
+}
+
+#[tokio::test]
+async fn slow_client_applies_backpressure() {
+	const TOTAL: usize = 16 << 20;
+
+	let (address, counters, peer) = start_bridge(ClientPolicy::Exclusive).await;
+	let mut client = connect(address, &peer).await;
+	let peer = Arc::new(peer);
+
+	let writer = tokio::spawn({
+		let peer = peer.clone();
+		async move { peer.write_all(&vec![0x55; TOTAL]).await }
+	});
+
+	// While the client is not reading, the bridge must stop reading from the serial port.
+	tokio::time::sleep(Duration::from_millis(500)).await;
+	assert!(!writer.is_finished());
+	let forwarded = counters.serial_to_network();
+	assert!(forwarded < TOTAL as u64);
+	tokio::time::sleep(Duration::from_millis(200)).await;
+	assert_eq!(counters.serial_to_network(), forwarded);
+
+	// When the client reads again, all data is forwarded without loss.
+	let mut buffer = vec![0; 1 << 16];
+	let mut received = 0;
+	while received < TOTAL {
+		let read = tokio::time::timeout(Duration::from_secs(5), client.read(&mut buffer)).await.unwrap().unwrap();
+		assert!(read > 0);
+		assert!(buffer[..read].iter().all(|&byte| byte == 0x55));
+		received += read;
+	}
+	assert_eq!(received, TOTAL);
+	writer.await.unwrap().unwrap();
+	assert_eq!(counters.serial_to_network(), TOTAL as u64);
+}
+
+#[tokio::test]
+async fn broadcast_slow_client_does_not_delay_other_clients() {
+	let (address, counters, peer) = start_bridge(ClientPolicy::Broadcast).await;
+	let _slow = connect(address, &peer).await;
+	let mut fast = connect(address, &peer).await;
+	let peer = Arc::new(peer);
+
+	let received = Arc::new(AtomicUsize::new(0));
+	let _reader = tokio::spawn({
+		let received = received.clone();
+		async move {
+			let mut buffer = vec![0; 1 << 16];
+			loop {
+				match fast.read(&mut buffer).await {
+					Ok(0) | Err(_) => break,
+					Ok(read) => received.fetch_add(read, Ordering::Relaxed),
+				};
+			}
+		}
+	});
+	let _writer = tokio::spawn({
+		let peer = peer.clone();
+		async move { peer.write_all(&vec![0x55; 16 << 20]).await }
+	});
+
+	// The slow client stops the bridge from reading more data,
+	// but the data that was already read must still reach the fast client.
+	tokio::time::sleep(Duration::from_millis(1000)).await;
+	let forwarded = counters.serial_to_network();
+	assert!(forwarded < 16 << 20);
+	assert_eq!(received.load(Ordering::Relaxed) as u64, forwarded);
+}
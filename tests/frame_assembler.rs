@@ -0,0 +1,130 @@
+//! Tests for resynchronizing a frame assembler with frames that have a length header.
+
+use serial2_tokio::{Delimiter, FrameAssembler};
+
+/// The first byte of a valid header.
+const SYNC: u8 = 0xAA;
+
+/// Parse a header of the form `[SYNC, total_len]`.
+fn frame_len(header: &[u8]) -> Option<usize> {
+	match header {
+		&[SYNC, len] => Some(len.into()),
+		_ => None,
+	}
+}
+
+fn header_assembler() -> FrameAssembler {
+	FrameAssembler::new(Delimiter::LengthFromHeader { header_len: 2, frame_len })
+}
+
+/// A small deterministic pseudo random number generator (xorshift64).
+struct Rng(u64);
+
+impl Rng {
+	fn next(&mut self) -> u64 {
+		self.0 ^= self.0 << 13;
+		self.0 ^= self.0 >> 7;
+		self.0 ^= self.0 << 17;
+		self.0
+	}
+
+	fn below(&mut self, max: u64) -> usize {
+		(self.next() % max) as usize
+	}
+
+	fn byte(&mut self) -> u8 {
+		self.next() as u8
+	}
+}
+
+#[test]
+fn header_resync_random_stream() {
+	for seed in 1..=200 {
+		let mut rng = Rng(seed);
+		let mut stream = Vec::new();
+		let mut expected = Vec::new();
+		let mut garbage = 0;
+
+		for _ in 0..50 {
+			// Garbage never contains the sync byte, so it can never be mistaken for a header.
+			for _ in 0..rng.below(20) {
+				stream.push(loop {
+					let byte = rng.byte();
+					if byte != SYNC {
+						break byte;
+					}
+				});
+				garbage += 1;
+			}
+
+			// A header with a length shorter than the header itself is invalid too.
+			if rng.below(4) == 0 {
+				stream.extend_from_slice(&[SYNC, rng.below(2) as u8]);
+				garbage += 2;
+			}
+
+			let len = 2 + rng.below(30);
+			let mut frame = vec![SYNC, len as u8];
+			frame.extend((2..len).map(|_| rng.byte()));
+			stream.extend_from_slice(&frame);
+			expected.push(frame);
+		}
+
+		// Push the stream in chunks of random sizes, to split headers and frames at every possible position.
+		let mut assembler = header_assembler();
+		let mut frames = Vec::new();
+		let mut rest = stream.as_slice();
+		while !rest.is_empty() {
+			let (chunk, tail) = rest.split_at((1 + rng.below(40)).min(rest.len()));
+			assembler.push(chunk);
+			frames.extend(std::iter::from_fn(|| assembler.next_frame()));
+			rest = tail;
+		}
+
+		assert_eq!(frames, expected, "seed {seed}");
+		assert_eq!(assembler.discarded(), garbage, "seed {seed}");
+	}
+}
+
+#[test]
+fn header_resync_large_garbage() {
+	// A large block of garbage in a single push must be skipped in one pass.
+	let mut assembler = header_assembler();
+	let mut data = vec![0x55; 1 << 20];
+	data.extend_from_slice(&[SYNC, 4, 1, 2]);
+	assembler.push(&data);
+	assert_eq!(assembler.next_frame(), Some(vec![SYNC, 4, 1, 2]));
+	assert_eq!(assembler.next_frame(), None);
+	assert_eq!(assembler.discarded(), 1 << 20);
+}
+
+#[test]
+fn header_resync_on_too_long_frame() {
+	let mut assembler = header_assembler();
+	assembler.set_max_frame_len(8);
+
+	// The header of the long frame is skipped one byte at a time, and the bytes of its body are discarded as garbage.
+	assembler.push(&[SYNC, 9, 0, 0, 0, 0, 0, 0, 0]);
+	assembler.push(&[SYNC, 3, 7]);
+	assert_eq!(assembler.next_frame(), Some(vec![SYNC, 3, 7]));
+	assert_eq!(assembler.next_frame(), None);
+	assert_eq!(assembler.discarded(), 9);
+}
+
+#[test]
+fn header_keeps_incomplete_frame() {
+	let mut assembler = header_assembler();
+	assembler.push(&[0x00, SYNC]);
+	assert_eq!(assembler.next_frame(), None);
+	assembler.push(&[5, 1, 2]);
+	assert_eq!(assembler.next_frame(), None);
+	assembler.push(&[3, SYNC]);
+	assert_eq!(assembler.next_frame(), Some(vec![SYNC, 5, 1, 2, 3]));
+	assert_eq!(assembler.discarded(), 1);
+}
+
+#[test]
+#[should_panic(expected = "the header length can not be zero")]
+fn header_len_zero_panics() {
+	FrameAssembler::new(Delimiter::LengthFromHeader { header_len: 0, frame_len });
+}
@@ -0,0 +1,55 @@
+//! Tests for reads and writes through io_uring, using a pseudo terminal pair.
+
+#![cfg(all(target_os = "linux", feature = "unix", feature = "io-uring"))]
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use serial2_tokio::SerialPort;
+use tokio::io::AsyncReadExt;
+
+#[tokio::test]
+async fn abandoned_reads_keep_data_in_order() {
+	const TOTAL: usize = 20_000;
+
+	let (port, peer) = SerialPort::pair().unwrap();
+	let writer = tokio::spawn(async move {
+		for chunk in (0..TOTAL).collect::<Vec<_>>().chunks(100) {
+			let data: Vec<u8> = chunk.iter().map(|&i| i as u8).collect();
+			peer.write_all(&data).await.unwrap();
+			tokio::time::sleep(Duration::from_micros(200)).await;
+		}
+		peer
+	});
+
+	// Abandon many reads with a short timeout, some of them after the kernel completed them.
+	let mut received = Vec::new();
+	while received.len() < TOTAL {
+		let mut buffer = [0; 64];
+		if let Ok(read) = tokio::time::timeout(Duration::from_micros(100), port.read(&mut buffer)).await {
+			received.extend_from_slice(&buffer[..read.unwrap()]);
+		}
+	}
+	let expected: Vec<u8> = (0..TOTAL).map(|i| i as u8).collect();
+	assert!(received == expected, "received data out of order");
+	writer.await.unwrap();
+}
+
+#[tokio::test]
+async fn async_read_and_read_share_the_data_stream() {
+	let (port, peer) = SerialPort::pair().unwrap();
+	let mut port = Arc::new(port);
+	peer.write_all(b"hello world").await.unwrap();
+
+	let mut buffer = [0; 5];
+	tokio::time::timeout(Duration::from_secs(5), Arc::get_mut(&mut port).unwrap().read_exact(&mut buffer)).await.unwrap().unwrap();
+	assert_eq!(&buffer, b"hello");
+
+	let mut rest = Vec::new();
+	while rest.len() < 6 {
+		let mut buffer = [0; 16];
+		let read = tokio::time::timeout(Duration::from_secs(5), port.read(&mut buffer)).await.unwrap().unwrap();
+		rest.extend_from_slice(&buffer[..read]);
+	}
+	assert_eq!(rest, b" world");
+}
@@ -0,0 +1,354 @@
+//! Tests for the RFC 2217 server and client, using a pseudo terminal pair as serial port.
+
+#![cfg(all(unix, feature = "unix", feature = "rfc2217"))]
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serial2_tokio::rfc2217::{RemoteSerialPort, Server};
+use serial2_tokio::SerialPort;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+const IAC: u8 = 255;
+const SB: u8 = 250;
+const SE: u8 = 240;
+const COM_PORT: u8 = 44;
+
+/// Something received from the server.
+#[derive(Debug, Eq, PartialEq)]
+enum Received {
+	Data(Vec<u8>),
+	Command(u8, Vec<u8>),
+}
+
+/// A raw Telnet connection to the server, with an independent parser for what the server sends.
+struct Client {
+	stream: TcpStream,
+	input: Vec<u8>,
+}
+
+impl Client {
+	/// Start a server and connect to it.
+	///
+	/// Returns the client and the other side of the pseudo terminal pair.
+	async fn start() -> (Self, SerialPort) {
+		let (address, peer) = start_server().await;
+		let stream = TcpStream::connect(address).await.unwrap();
+		let client = Self { stream, input: Vec::new() };
+		(client, peer)
+	}
+
+	/// Send a COM-PORT command with the value escaped.
+	async fn send_command(&mut self, code: u8, value: &[u8]) {
+		let mut message = vec![IAC, SB, COM_PORT, code];
+		for &byte in value {
+			if byte == IAC {
+				message.push(IAC);
+			}
+			message.push(byte);
+		}
+		message.extend_from_slice(&[IAC, SE]);
+		self.stream.write_all(&message).await.unwrap();
+	}
+
+	/// Receive the next data or COM-PORT command, skipping option negotiation.
+	async fn receive(&mut self) -> Received {
+		tokio::time::timeout(Duration::from_secs(5), async {
+			loop {
+				if let Some(received) = self.parse() {
+					return received;
+				}
+				let mut buffer = [0; 1024];
+				let read = self.stream.read(&mut buffer).await.unwrap();
+				assert!(read > 0, "connection closed by the server");
+				self.input.extend_from_slice(&buffer[..read]);
+			}
+		}).await.unwrap()
+	}
+
+	/// Receive COM-PORT commands until one with the given response code arrives, and return its value.
+	async fn response(&mut self, code: u8) -> Vec<u8> {
+		loop {
+			if let Received::Command(received, value) = self.receive().await {
+				if received == code {
+					return value;
+				}
+			}
+		}
+	}
+
+	/// Receive data until `len` bytes have been received, ignoring commands.
+	async fn data(&mut self, len: usize) -> Vec<u8> {
+		let mut data = Vec::new();
+		while data.len() < len {
+			if let Received::Data(received) = self.receive().await {
+				data.extend_from_slice(&received);
+			}
+		}
+		data
+	}
+
+	/// Parse the next complete item from the input.
+	fn parse(&mut self) -> Option<Received> {
+		let mut data = Vec::new();
+		let mut i = 0;
+		while i < self.input.len() {
+			if self.input[i] != IAC {
+				data.push(self.input[i]);
+				i += 1;
+				continue;
+			}
+			match self.input.get(i + 1) {
+				None => break,
+				Some(&IAC) => {
+					data.push(IAC);
+					i += 2;
+				},
+				Some(&SB) if data.is_empty() => {
+					let (consumed, payload) = parse_subnegotiation(&self.input[i + 2..])?;
+					self.input.drain(..i + 2 + consumed);
+					let (&option, payload) = payload.split_first().unwrap();
+					assert_eq!(option, COM_PORT);
+					let (&code, value) = payload.split_first().unwrap();
+					return Some(Received::Command(code, value.to_vec()));
+				},
+				Some(&SB) => break,
+				Some(_) if data.is_empty() => {
+					// Option negotiation: IAC, verb and option.
+					if self.input.len() < i + 3 {
+						break;
+					}
+					self.input.drain(..i + 3);
+					return self.parse();
+				},
+				Some(_) => break,
+			}
+		}
+		if data.is_empty() {
+			return None;
+		}
+		self.input.drain(..i);
+		Some(Received::Data(data))
+	}
+}
+
+/// Start a server for one side of a pseudo terminal pair.
+///
+/// Returns the address of the server and the other side of the pair.
+async fn start_server() -> (SocketAddr, SerialPort) {
+	let (port, peer) = SerialPort::pair().unwrap();
+	let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+	let address = listener.local_addr().unwrap();
+	let server = Server::new(port).signature("test server");
+	tokio::spawn(async move { server.serve(listener).await });
+	(address, peer)
+}
+
+/// Parse the rest of a subnegotiation after `IAC SB`.
+///
+/// Returns the number of bytes consumed and the unescaped payload, or `None` if the subnegotiation is incomplete.
+fn parse_subnegotiation(input: &[u8]) -> Option<(usize, Vec<u8>)> {
+	let mut payload = Vec::new();
+	let mut i = 0;
+	loop {
+		match (*input.get(i)?, input.get(i + 1)) {
+			(IAC, Some(&SE)) => return Some((i + 2, payload)),
+			(IAC, Some(&IAC)) => {
+				payload.push(IAC);
+				i += 2;
+			},
+			(IAC, Some(_)) => panic!("unexpected command in subnegotiation"),
+			(IAC, None) => return None,
+			(byte, _) => {
+				payload.push(byte);
+				i += 1;
+			},
+		}
+	}
+}
+
+/// Read exactly enough bytes from a serial port to fill the buffer.
+async fn read_exact(port: &SerialPort, mut buffer: &mut [u8]) {
+	tokio::time::timeout(Duration::from_secs(5), async {
+		while !buffer.is_empty() {
+			let read = port.read(buffer).await.unwrap();
+			buffer = &mut buffer[read..];
+		}
+	}).await.unwrap()
+}
+
+#[tokio::test]
+async fn server_unescapes_client_data() {
+	let (mut client, peer) = Client::start().await;
+
+	// Split the escaped IAC over two writes, to test that the parser keeps its state.
+	client.stream.write_all(&[b'a', IAC]).await.unwrap();
+	client.stream.flush().await.unwrap();
+	tokio::time::sleep(Duration::from_millis(50)).await;
+	client.stream.write_all(&[IAC, b'b']).await.unwrap();
+
+	let mut buffer = [0; 3];
+	read_exact(&peer, &mut buffer).await;
+	assert_eq!(buffer, [b'a', IAC, b'b']);
+}
+
+#[tokio::test]
+async fn server_escapes_serial_data() {
+	let (mut client, peer) = Client::start().await;
+	peer.write_all(&[1, IAC, 2, IAC, IAC]).await.unwrap();
+	assert_eq!(client.data(5).await, [1, IAC, 2, IAC, IAC]);
+}
+
+#[tokio::test]
+async fn server_replies_to_commands() {
+	let (mut client, _peer) = Client::start().await;
+
+	// SIGNATURE
+	client.send_command(0, &[]).await;
+	assert_eq!(client.response(100).await, b"test server");
+
+	// SET-BAUDRATE
+	client.send_command(1, &9600u32.to_be_bytes()).await;
+	assert_eq!(client.response(101).await, 9600u32.to_be_bytes());
+
+	// SET-DATASIZE with an unsupported value gets the current value.
+	client.send_command(2, &[9]).await;
+	assert_eq!(client.response(102).await, [8]);
+
+	// SET-LINESTATE-MASK with an escaped IAC in the value.
+	client.send_command(10, &[IAC]).await;
+	assert_eq!(client.response(110).await, [IAC]);
+
+	// PURGE-DATA
+	client.send_command(12, &[3]).await;
+	assert_eq!(client.response(112).await, [3]);
+}
+
+#[tokio::test]
+async fn server_replies_to_unsupported_control_values() {
+	let (mut client, _peer) = Client::start().await;
+
+	// SET-CONTROL: request outbound flow control.
+	client.send_command(5, &[0]).await;
+	assert_eq!(client.response(105).await, [1]);
+
+	// SET-CONTROL: DCD flow control is not supported, so the current outbound flow control is the response.
+	client.send_command(5, &[17]).await;
+	assert_eq!(client.response(105).await, [1]);
+
+	// SET-CONTROL: DTR flow control is not supported, so the current inbound flow control is the response.
+	client.send_command(5, &[18]).await;
+	assert_eq!(client.response(105).await, [14]);
+
+	// SET-CONTROL: unknown values get a response too.
+	client.send_command(5, &[200]).await;
+	assert_eq!(client.response(105).await, [1]);
+}
+
+#[tokio::test]
+async fn server_handles_split_subnegotiation() {
+	let (mut client, _peer) = Client::start().await;
+
+	// SET-MODEMSTATE-MASK, sent one byte at a time.
+	for byte in [IAC, SB, COM_PORT, 11, 0x30, IAC, SE] {
+		client.stream.write_all(&[byte]).await.unwrap();
+		client.stream.flush().await.unwrap();
+		tokio::time::sleep(Duration::from_millis(10)).await;
+	}
+	assert_eq!(client.response(111).await, [0x30]);
+}
+
+#[tokio::test]
+async fn remote_port_loopback() {
+	let (address, peer) = start_server().await;
+	let remote = RemoteSerialPort::connect(address).await.unwrap();
+
+	remote.write_all(&[1, IAC, 2]).await.unwrap();
+	let mut buffer = [0; 3];
+	read_exact(&peer, &mut buffer).await;
+	assert_eq!(buffer, [1, IAC, 2]);
+
+	peer.write_all(&[3, IAC, 4]).await.unwrap();
+	let mut received = Vec::new();
+	while received.len() < 3 {
+		let mut buffer = [0; 16];
+		let read = tokio::time::timeout(Duration::from_secs(5), remote.read(&mut buffer)).await.unwrap().unwrap();
+		received.extend_from_slice(&buffer[..read]);
+	}
+	assert_eq!(received, [3, IAC, 4]);
+
+	assert_eq!(remote.get_signature().await.unwrap(), "test server");
+	remote.set_baud_rate(9600).await.unwrap();
+	assert_eq!(remote.get_baud_rate().await.unwrap(), 9600);
+	assert_eq!(peer.get_configuration().unwrap().get_baud_rate().unwrap(), 9600);
+	assert_eq!(remote.get_char_size().await.unwrap(), serial2_tokio::CharSize::Bits8);
+	remote.discard_buffers().await.unwrap();
+}
+
+#[tokio::test]
+async fn remote_port_async_read_write() {
+	let (address, peer) = start_server().await;
+	let mut remote = RemoteSerialPort::connect(address).await.unwrap();
+
+	AsyncWriteExt::write_all(&mut remote, b"hello").await.unwrap();
+	remote.flush().await.unwrap();
+	let mut buffer = [0; 5];
+	read_exact(&peer, &mut buffer).await;
+	assert_eq!(&buffer, b"hello");
+
+	peer.write_all(b"world").await.unwrap();
+	let mut buffer = [0; 5];
+	tokio::time::timeout(Duration::from_secs(5), AsyncReadExt::read_exact(&mut remote, &mut buffer)).await.unwrap().unwrap();
+	assert_eq!(&buffer, b"world");
+}
+
+#[tokio::test]
+async fn remote_port_slow_reader_applies_backpressure() {
+	const TOTAL: usize = 16 << 20;
+
+	let (address, peer) = start_server().await;
+	let remote = RemoteSerialPort::connect(address).await.unwrap();
+	let peer = Arc::new(peer);
+	let writer = tokio::spawn({
+		let peer = peer.clone();
+		async move { peer.write_all(&vec![0x55; TOTAL]).await }
+	});
+
+	// While nothing is read, the server must stop sending data, but commands must still work.
+	tokio::time::sleep(Duration::from_millis(500)).await;
+	assert!(!writer.is_finished());
+	assert_eq!(remote.get_signature().await.unwrap(), "test server");
+
+	// When the data is read, everything arrives without loss.
+	let mut buffer = vec![0; 1 << 16];
+	let mut received = 0;
+	while received < TOTAL {
+		let read = tokio::time::timeout(Duration::from_secs(5), remote.read(&mut buffer)).await.unwrap().unwrap();
+		assert!(read > 0);
+		assert!(buffer[..read].iter().all(|&byte| byte == 0x55));
+		received += read;
+	}
+	assert_eq!(received, TOTAL);
+	writer.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn server_handles_data_and_commands_in_one_segment() {
+	let (mut client, peer) = Client::start().await;
+
+	// Data, SET-BAUDRATE and more data in a single write.
+	let mut message = b"hello".to_vec();
+	message.extend_from_slice(&[IAC, SB, COM_PORT, 1]);
+	message.extend_from_slice(&9600u32.to_be_bytes());
+	message.extend_from_slice(&[IAC, SE]);
+	message.extend_from_slice(b"world");
+	client.stream.write_all(&message).await.unwrap();
+
+	assert_eq!(client.response(101).await, 9600u32.to_be_bytes());
+	let mut buffer = [0; 10];
+	read_exact(&peer, &mut buffer).await;
+	assert_eq!(&buffer, b"helloworld");
+	assert_eq!(peer.get_configuration().unwrap().get_baud_rate().unwrap(), 9600);
+}
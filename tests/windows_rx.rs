@@ -0,0 +1,57 @@
+//! Regression test for waiting on received characters on Windows.
+//!
+//! This needs a com0com virtual port pair, and is skipped if none is installed.
+
+#![cfg(windows)]
+
+use std::time::Duration;
+
+use serial2_tokio::SerialPort;
+
+/// Open the first com0com pair, or return `None` if there is none.
+fn open_virtual_pair() -> Option<(SerialPort, SerialPort)> {
+	let pair = SerialPort::find_virtual_pairs().unwrap().into_iter().next();
+	let Some(pair) = pair else {
+		eprintln!("no com0com virtual port pair installed, skipping test");
+		return None;
+	};
+	let name = pair.a.to_str().unwrap();
+	Some(SerialPort::open_pair_by_name(name, 115200).unwrap())
+}
+
+#[tokio::test]
+async fn read_waits_for_data() {
+	let Some((a, b)) = open_virtual_pair() else {
+		return;
+	};
+
+	// Without data, a read must stay pending instead of completing empty.
+	let mut buffer = [0; 16];
+	assert!(tokio::time::timeout(Duration::from_millis(200), a.read(&mut buffer)).await.is_err());
+
+	// A read that is waiting must wake up when data arrives.
+	let (read, write) = tokio::join!(a.read(&mut buffer), async {
+		tokio::time::sleep(Duration::from_millis(50)).await;
+		b.write_all(b"hello").await
+	});
+	write.unwrap();
+	let read = read.unwrap();
+	assert!(read > 0);
+	assert_eq!(&buffer[..read], &b"hello"[..read]);
+}
+
+#[tokio::test]
+async fn async_read_waits_for_data() {
+	use tokio::io::AsyncReadExt;
+
+	let Some((mut a, b)) = open_virtual_pair() else {
+		return;
+	};
+	// The `AsyncRead` implementation has its own wait path, so test it separately from `SerialPort::read()`.
+	let mut buffer = [0; 16];
+	assert!(tokio::time::timeout(Duration::from_millis(200), AsyncReadExt::read(&mut a, &mut buffer)).await.is_err());
+
+	b.write_all(b"x").await.unwrap();
+	let read = tokio::time::timeout(Duration::from_secs(2), AsyncReadExt::read(&mut a, &mut buffer)).await.unwrap().unwrap();
+	assert_eq!(&buffer[..read], b"x");
+}